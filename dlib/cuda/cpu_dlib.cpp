@@ -15,6 +15,85 @@ namespace dlib
     namespace cpu 
     {
 
+    // -----------------------------------------------------------------------------------
+
+        namespace
+        {
+            // Elementwise kernels only bother the thread pool when each thread gets at
+            // least this many elements.  Below that the dispatch costs more than it saves.
+            const long min_elements_per_thread = 1<<15;
+
+            long num_parallel_blocks (
+                long num,
+                long min_block_size
+            )
+            {
+                const long max_blocks = num/std::max(1L, min_block_size);
+                return std::max(1L, std::min<long>(dnn_cpu_num_threads(), max_blocks));
+            }
+
+            template <typename T>
+            void cpu_parallel_for_blocked (
+                long begin,
+                long end,
+                const T& funct,
+                long min_block_size = 1
+            )
+            /*!
+                ensures
+                    - Splits [begin, end) into at most dnn_cpu_num_threads() disjoint
+                      ranges, each at least min_block_size long, and calls
+                      funct(range_begin, range_end) on each of them using the
+                      default_thread_pool().
+                    - The split only depends on end-begin, min_block_size and
+                      dnn_cpu_num_threads().
+            !*/
+            {
+                const long num = end-begin;
+                if (num <= 0)
+                    return;
+                const long num_blocks = num_parallel_blocks(num, min_block_size);
+                if (num_blocks == 1)
+                {
+                    funct(begin, end);
+                    return;
+                }
+                parallel_for(default_thread_pool(), 0, num_blocks, [&](long b)
+                {
+                    funct(begin + num*b/num_blocks, begin + num*(b+1)/num_blocks);
+                }, 1);
+            }
+
+            template <typename T>
+            void cpu_parallel_for (
+                long begin,
+                long end,
+                const T& funct,
+                long min_block_size = 1
+            )
+            /*!
+                ensures
+                    - calls funct(i) for all i in [begin, end), split over threads the
+                      same way as cpu_parallel_for_blocked().
+            !*/
+            {
+                cpu_parallel_for_blocked(begin, end, [&](long block_begin, long block_end)
+                {
+                    for (long i = block_begin; i < block_end; ++i)
+                        funct(i);
+                }, min_block_size);
+            }
+
+            template <typename T>
+            void parallel_for_each_element (
+                size_t size,
+                const T& funct
+            )
+            {
+                cpu_parallel_for(0, size, funct, min_elements_per_thread);
+            }
+        }
+
     // -----------------------------------------------------------------------------------
 
         void multiply (
@@ -712,6 +791,337 @@ namespace dlib
             }
         }
 
+    // -----------------------------------------------------------------------------------
+
+        void compute_adamw_update (
+            size_t begin,
+            size_t end,
+            tensor& s,
+            tensor& m,
+            tensor& v,
+            const float t,
+            const float learning_rate,
+            const float weight_decay,
+            const float momentum1,
+            const float momentum2,
+            const tensor& params,
+            const tensor& params_grad
+        )
+        {
+            DLIB_CASSERT(s.size() == m.size() &&
+                         s.size() == v.size() &&
+                         s.size() == params.size() &&
+                         s.size() == params_grad.size());
+            DLIB_CASSERT(begin <= end && end <= params.size());
+            const float eps = 1e-8;
+            const float alpha = learning_rate*std::sqrt(1-std::pow(momentum2,t))/(1-std::pow(momentum1, t));
+
+            // The loop is equivalent to doing this:
+            //   m = momentum1*m + (1-momentum1)    *   params_grad;
+            //   v = momentum2*v + (1-momentum2)*squared(params_grad);
+            //   s = -alpha*m/(sqrt(v) + eps) - learning_rate*weight_decay*params;
+            auto pm = m.host();
+            auto pv = v.host();
+            auto ps = s.host_write_only();
+            auto pparams = params.host();
+            auto ppgrad = params_grad.host();
+            for (size_t i = begin; i < end; ++i)
+            {
+                float g = ppgrad[i];
+                pm[i] = momentum1*pm[i] + (1-momentum1)*g;
+                pv[i] = momentum2*pv[i] + (1-momentum2)*g*g;
+                ps[i] = -alpha*pm[i]/(std::sqrt(pv[i]) + eps) - learning_rate*weight_decay*pparams[i];
+            }
+        }
+
+    // -----------------------------------------------------------------------------------
+
+        void compute_rmsprop_update (
+            size_t begin,
+            size_t end,
+            tensor& s,
+            tensor& v,
+            const float learning_rate,
+            const float weight_decay,
+            const float decay,
+            const tensor& params,
+            const tensor& params_grad
+        )
+        {
+            DLIB_CASSERT(s.size() == v.size() &&
+                         s.size() == params.size() &&
+                         s.size() == params_grad.size());
+            DLIB_CASSERT(begin <= end && end <= params.size());
+            const float eps = 1e-8;
+
+            // The loop is equivalent to doing this:
+            //   v = decay*v + (1-decay)*squared(weight_decay*params + params_grad);
+            //   s = -learning_rate*(weight_decay*params + params_grad)/(sqrt(v) + eps);
+            auto pv = v.host();
+            auto ps = s.host_write_only();
+            auto pparams = params.host();
+            auto ppgrad = params_grad.host();
+            for (size_t i = begin; i < end; ++i)
+            {
+                float g = weight_decay*pparams[i] + ppgrad[i];
+                pv[i] = decay*pv[i] + (1-decay)*g*g;
+                ps[i] = -learning_rate*g/(std::sqrt(pv[i]) + eps);
+            }
+        }
+
+    // -----------------------------------------------------------------------------------
+
+        void compute_adagrad_update (
+            size_t begin,
+            size_t end,
+            tensor& s,
+            tensor& v,
+            const float learning_rate,
+            const float weight_decay,
+            const tensor& params,
+            const tensor& params_grad
+        )
+        {
+            DLIB_CASSERT(s.size() == v.size() &&
+                         s.size() == params.size() &&
+                         s.size() == params_grad.size());
+            DLIB_CASSERT(begin <= end && end <= params.size());
+            const float eps = 1e-8;
+
+            // The loop is equivalent to doing this:
+            //   v = v + squared(weight_decay*params + params_grad);
+            //   s = -learning_rate*(weight_decay*params + params_grad)/(sqrt(v) + eps);
+            auto pv = v.host();
+            auto ps = s.host_write_only();
+            auto pparams = params.host();
+            auto ppgrad = params_grad.host();
+            for (size_t i = begin; i < end; ++i)
+            {
+                float g = weight_decay*pparams[i] + ppgrad[i];
+                pv[i] += g*g;
+                ps[i] = -learning_rate*g/(std::sqrt(pv[i]) + eps);
+            }
+        }
+
+    // -----------------------------------------------------------------------------------
+
+        void compute_lion_update (
+            size_t begin,
+            size_t end,
+            tensor& s,
+            tensor& m,
+            const float learning_rate,
+            const float weight_decay,
+            const float momentum1,
+            const float momentum2,
+            const tensor& params,
+            const tensor& params_grad
+        )
+        {
+            DLIB_CASSERT(s.size() == m.size() &&
+                         s.size() == params.size() &&
+                         s.size() == params_grad.size());
+            DLIB_CASSERT(begin <= end && end <= params.size());
+
+            // The loop is equivalent to doing this:
+            //   s = -learning_rate*(sign(momentum1*m + (1-momentum1)*params_grad) + weight_decay*params);
+            //   m = momentum2*m + (1-momentum2)*params_grad;
+            auto pm = m.host();
+            auto ps = s.host_write_only();
+            auto pparams = params.host();
+            auto ppgrad = params_grad.host();
+            for (size_t i = begin; i < end; ++i)
+            {
+                float c = momentum1*pm[i] + (1-momentum1)*ppgrad[i];
+                float sign_c = (c > 0) ? 1.0f : ((c < 0) ? -1.0f : 0.0f);
+                ps[i] = -learning_rate*(sign_c + weight_decay*pparams[i]);
+                pm[i] = momentum2*pm[i] + (1-momentum2)*ppgrad[i];
+            }
+        }
+
+    // -----------------------------------------------------------------------------------
+
+        void round_to_half_precision (
+            tensor& data,
+            half_format fmt
+        )
+        {
+            auto d = data.host();
+            if (fmt == half_format::fp16)
+            {
+                for (size_t i = 0; i < data.size(); ++i)
+                    d[i] = fp16_to_float(float_to_fp16(d[i]));
+            }
+            else
+            {
+                for (size_t i = 0; i < data.size(); ++i)
+                    d[i] = bf16_to_float(float_to_bf16(d[i]));
+            }
+        }
+
+    // -----------------------------------------------------------------------------------
+
+        void clamp (
+            tensor& data,
+            float lower,
+            float upper
+        )
+        {
+            DLIB_CASSERT(lower <= upper);
+            auto d = data.host();
+            for (size_t i = 0; i < data.size(); ++i)
+                d[i] = std::min(upper, std::max(lower, d[i]));
+        }
+
+    // ------------------------------------------------------------------------------------
+
+        namespace
+        {
+            inline int32_t int8_dot (
+                const int8_t* a,
+                const int8_t* b,
+                long n
+            )
+            {
+                int32_t sum = 0;
+                for (long i = 0; i < n; ++i)
+                    sum += static_cast<int32_t>(a[i])*static_cast<int32_t>(b[i]);
+                return sum;
+            }
+
+            void quantize_input_to_int8 (
+                std::vector<int8_t>& dest,
+                const tensor& src,
+                float scale
+            )
+            {
+                dest.resize(src.size());
+                const float* s = src.host();
+                for (size_t i = 0; i < src.size(); ++i)
+                    dest[i] = float_to_int8(s[i], scale);
+            }
+        }
+
+        void int8_fc (
+            resizable_tensor& output,
+            const tensor& input,
+            float input_scale,
+            const int8_tensor& weights,
+            const tensor& biases
+        )
+        {
+            const long num_inputs = input.size()/input.num_samples();
+            const long num_outputs = weights.num_samples();
+            DLIB_CASSERT(input_scale > 0);
+            DLIB_CASSERT(weights.size() == (size_t)(num_inputs*num_outputs));
+            DLIB_CASSERT(biases.size() == 0 || biases.size() == (size_t)num_outputs);
+
+            std::vector<int8_t> x;
+            quantize_input_to_int8(x, input, input_scale);
+
+            output.set_size(input.num_samples(), num_outputs);
+            float* out = output.host_write_only();
+            const float* b = biases.size() != 0 ? biases.host() : nullptr;
+            const int8_t* w = weights.host();
+            for (long n = 0; n < input.num_samples(); ++n)
+            {
+                for (long o = 0; o < num_outputs; ++o)
+                {
+                    const int32_t sum = int8_dot(&x[n*num_inputs], w + o*num_inputs, num_inputs);
+                    out[n*num_outputs + o] = sum*input_scale*weights.get_scale(o) + (b ? b[o] : 0);
+                }
+            }
+        }
+
+        void int8_conv (
+            resizable_tensor& output,
+            const tensor& input,
+            float input_scale,
+            const int8_tensor& filters,
+            const tensor& biases,
+            int stride_y,
+            int stride_x,
+            int padding_y,
+            int padding_x,
+            int dilation_y,
+            int dilation_x,
+            long groups,
+            bool use_relu
+        )
+        {
+            DLIB_CASSERT(input_scale > 0);
+            DLIB_CASSERT(stride_y > 0 && stride_x > 0);
+            DLIB_CASSERT(dilation_y > 0 && dilation_x > 0);
+            DLIB_CASSERT(groups > 0 && filters.num_samples()%groups == 0);
+            DLIB_CASSERT(filters.k()*groups == input.k());
+            DLIB_CASSERT(biases.size() == 0 || biases.size() == (size_t)filters.num_samples());
+
+            const long window_nr = dilation_y*(filters.nr()-1)+1;
+            const long window_nc = dilation_x*(filters.nc()-1)+1;
+            DLIB_CASSERT(window_nr <= input.nr() + 2*padding_y,
+                "Filter windows must be small enough to fit into the padded image.");
+            DLIB_CASSERT(window_nc <= input.nc() + 2*padding_x,
+                "Filter windows must be small enough to fit into the padded image.");
+            const long out_nr = 1+(input.nr()+2*padding_y-window_nr)/stride_y;
+            const long out_nc = 1+(input.nc()+2*padding_x-window_nc)/stride_x;
+            output.set_size(input.num_samples(), filters.num_samples(), out_nr, out_nc);
+
+            std::vector<int8_t> x;
+            quantize_input_to_int8(x, input, input_scale);
+
+            const long filters_per_group = filters.num_samples()/groups;
+            const long filter_size = filters.k()*filters.nr()*filters.nc();
+            const long out_size = out_nr*out_nc;
+            const float* b = biases.size() != 0 ? biases.host() : nullptr;
+            float* out = output.host_write_only();
+
+            // Like the float convolution, we unroll the input windows into the rows of a
+            // matrix so that each output value is a dot product of two contiguous arrays.
+            std::vector<int8_t> cols(out_size*filter_size);
+            for (long n = 0; n < input.num_samples(); ++n)
+            {
+                for (long g = 0; g < groups; ++g)
+                {
+                    int8_t* t = cols.data();
+                    for (long r = 0; r < out_nr; ++r)
+                    {
+                        for (long c = 0; c < out_nc; ++c)
+                        {
+                            for (long k = g*filters.k(); k < (g+1)*filters.k(); ++k)
+                            {
+                                const int8_t* channel = &x[((n*input.k() + k)*input.nr())*input.nc()];
+                                for (long y = 0; y < filters.nr(); ++y)
+                                {
+                                    const long yy = r*stride_y - padding_y + y*dilation_y;
+                                    for (long xx = 0; xx < filters.nc(); ++xx)
+                                    {
+                                        const long cc = c*stride_x - padding_x + xx*dilation_x;
+                                        if (0 <= yy && yy < input.nr() && 0 <= cc && cc < input.nc())
+                                            *t++ = channel[yy*input.nc() + cc];
+                                        else
+                                            *t++ = 0;
+                                    }
+                                }
+                            }
+                        }
+                    }
+
+                    for (long f = g*filters_per_group; f < (g+1)*filters_per_group; ++f)
+                    {
+                        const int8_t* filt = filters.host() + f*filter_size;
+                        const float scale = input_scale*filters.get_scale(f);
+                        const float bias = b ? b[f] : 0;
+                        float* o = out + (n*filters.num_samples() + f)*out_size;
+                        for (long i = 0; i < out_size; ++i)
+                        {
+                            const float val = int8_dot(&cols[i*filter_size], filt, filter_size)*scale + bias;
+                            o[i] = (use_relu && val < 0) ? 0 : val;
+                        }
+                    }
+                }
+            }
+        }
+
     // -----------------------------------------------------------------------------------
 
         void batch_normalize_inference (
@@ -764,15 +1174,11 @@ namespace dlib
             auto v = running_variances.host();
 
             const long num = src.k()*src.nr()*src.nc();
-            for (long n = 0; n < src.num_samples(); ++n)
+            cpu_parallel_for(0, src.num_samples(), [&](long n)
             {
                 for (long k = 0; k < num; ++k)
-                {
-                    *d = g[k]*(*s - m[k])/std::sqrt(v[k]+eps) + b[k];
-                    ++d;
-                    ++s;
-                }
-            }
+                    d[n*num+k] = g[k]*(s[n*num+k] - m[k])/std::sqrt(v[k]+eps) + b[k];
+            }, min_elements_per_thread/std::max(1L, num));
         }
 
         void batch_normalize (
@@ -825,7 +1231,7 @@ namespace dlib
             auto p_src = src.host();
             const long num = src.k()*src.nr()*src.nc();
             // compute means, and sum of squares
-            for (long i = 0; i < num; ++i)
+            cpu_parallel_for(0, num, [&](long i)
             {
                 for (long n = 0; n < src.num_samples(); ++n)
                 {
@@ -833,7 +1239,7 @@ namespace dlib
                     p_means[i] += val;
                     p_invstds[i] += val*val;
                 }
-            }
+            }, min_elements_per_thread/src.num_samples());
             means /= src.num_samples();
             invstds /= src.num_samples();
             // copy data back to host
@@ -859,16 +1265,14 @@ namespace dlib
             auto p_dest = dest.host();
             const auto p_gamma = gamma.host();   
             const auto p_beta = beta.host();   
-            for (long n = 0; n < src.num_samples(); ++n)
+            cpu_parallel_for(0, src.num_samples(), [&](long n)
             {
                 for (long i = 0; i < num; ++i)
                 {
-                    *p_dest = (*p_src - p_means[i])*p_invstds[i];
-                    *p_dest = (*p_dest)*p_gamma[i] + p_beta[i];
-                    ++p_src;
-                    ++p_dest;
+                    const float x_hat = (p_src[n*num+i] - p_means[i])*p_invstds[i];
+                    p_dest[n*num+i] = x_hat*p_gamma[i] + p_beta[i];
                 }
-            }
+            }, min_elements_per_thread/std::max(1L, num));
 
             // now keep track of the running means 
             running_means.copy_size(means);
@@ -920,57 +1324,45 @@ namespace dlib
             const auto p_dvars = dvars.host();
             const auto p_dmeans = dmeans.host();
 
-            for (long n = 0; n < src.num_samples(); ++n)
+            // Each feature only accumulates over the samples, so the features can be
+            // processed in parallel.
+            const float invnum = 1.0f/src.num_samples();
+            cpu_parallel_for(0, num, [&](long i)
             {
-                for (long i = 0; i < num; ++i)
+                for (long n = 0; n < src.num_samples(); ++n)
                 {
-                    const float x_hat = (*p_src - p_means[i])*p_invstds[i];
-                    p_beta_grad[i] += *p_grad;
-                    p_gamma_grad[i] += (*p_grad)*x_hat;
+                    const float x = p_src[n*num+i];
+                    const float gi = p_grad[n*num+i];
+                    const float x_hat = (x - p_means[i])*p_invstds[i];
+                    p_beta_grad[i] += gi;
+                    p_gamma_grad[i] += gi*x_hat;
 
-                    const float dx = *p_grad * p_gamma[i];
+                    const float dx = gi * p_gamma[i];
 
-                    p_dvars[i] += dx*(*p_src - p_means[i])*-0.5*std::pow(p_invstds[i], 3.0f);
-
-                    ++p_grad;
-                    ++p_src;
+                    p_dvars[i] += dx*(x - p_means[i])*-0.5*std::pow(p_invstds[i], 3.0f);
                 }
-            }
 
-            const float invnum = 1.0f/src.num_samples();
-            p_grad = gradient_input.host();
-            p_src = src.host();
-            for (long n = 0; n < src.num_samples(); ++n)
-            {
-                for (long i = 0; i < num; ++i)
+                for (long n = 0; n < src.num_samples(); ++n)
                 {
-                    const float dx = *p_grad * p_gamma[i];
-
-                    p_dmeans[i] += dx*-p_invstds[i] + p_dvars[i] * -2*(*p_src - p_means[i])*invnum;
+                    const float x = p_src[n*num+i];
+                    const float dx = p_grad[n*num+i] * p_gamma[i];
 
-                    ++p_grad;
-                    ++p_src;
+                    p_dmeans[i] += dx*-p_invstds[i] + p_dvars[i] * -2*(x - p_means[i])*invnum;
                 }
-            }
-            p_grad = gradient_input.host();
-            p_src = src.host();
+            }, min_elements_per_thread/src.num_samples());
+
             auto p_src_grad = src_grad.host();
-            for (long n = 0; n < src.num_samples(); ++n)
+            cpu_parallel_for(0, src.num_samples(), [&](long n)
             {
                 for (long i = 0; i < num; ++i)
                 {
-                    const float dx = *p_grad * p_gamma[i];
+                    const float dx = p_grad[n*num+i] * p_gamma[i];
 
-                    *p_src_grad += dx*p_invstds[i] + 
-                        p_dvars[i] *2*(*p_src - p_means[i])*invnum + 
+                    p_src_grad[n*num+i] += dx*p_invstds[i] + 
+                        p_dvars[i] *2*(p_src[n*num+i] - p_means[i])*invnum + 
                         p_dmeans[i]*invnum;
-
-
-                    ++p_grad;
-                    ++p_src;
-                    ++p_src_grad;
                 }
-            }
+            }, min_elements_per_thread/std::max(1L, num));
         }
 
     // ----------------------------------------------------------------------------------------
@@ -1025,19 +1417,13 @@ namespace dlib
             auto v = running_variances.host();
 
             const long num = src.nr()*src.nc();
-            for (long n = 0; n < src.num_samples(); ++n)
+            cpu_parallel_for(0, src.num_samples()*src.k(), [&](long plane)
             {
-                for (long k = 0; k < src.k(); ++k)
-                {
-                    const float invstd = 1.0f/std::sqrt(v[k] + eps);
-                    for (long j = 0; j < num; ++j)
-                    {
-                        *d = g[k]*(*s - m[k])*invstd + b[k];
-                        ++d;
-                        ++s;
-                    }
-                }
-            }
+                const long k = plane%src.k();
+                const float invstd = 1.0f/std::sqrt(v[k] + eps);
+                for (long j = plane*num; j < (plane+1)*num; ++j)
+                    d[j] = g[k]*(s[j] - m[k])*invstd + b[k];
+            }, min_elements_per_thread/std::max(1L, num));
         }
 
         void batch_normalize_conv (
@@ -1093,19 +1479,20 @@ namespace dlib
             const auto p_beta = beta.host();   
             auto p_src = src.host();
             const long num = src.nr()*src.nc();
-            // compute means, and sum of squares
-            for (long n = 0; n < src.num_samples(); ++n)
+            // compute means, and sum of squares.  Each channel is summed on its own
+            // thread.
+            cpu_parallel_for(0, src.k(), [&](long k)
             {
-                for (long k = 0; k < src.k(); ++k)
+                for (long n = 0; n < src.num_samples(); ++n)
                 {
+                    const float* x = p_src + (n*src.k() + k)*num;
                     for (long i = 0; i < num; ++i)
                     {
-                        p_means[k] += *p_src;
-                        p_invstds[k] += (*p_src)*(*p_src);
-                        ++p_src;
+                        p_means[k] += x[i];
+                        p_invstds[k] += x[i]*x[i];
                     }
                 }
-            }
+            }, min_elements_per_thread/std::max<long long>(1, src.num_samples()*num));
             means /= src.num_samples()*num;
             invstds /= src.num_samples()*num;
             // copy data back to host
@@ -1130,19 +1517,15 @@ namespace dlib
 
             p_src = src.host();
             auto p_dest = dest.host();
-            for (long n = 0; n < src.num_samples(); ++n)
+            cpu_parallel_for(0, src.num_samples()*src.k(), [&](long plane)
             {
-                for (long k = 0; k < src.k(); ++k)
+                const long k = plane%src.k();
+                for (long i = plane*num; i < (plane+1)*num; ++i)
                 {
-                    for (long i = 0; i < num; ++i)
-                    {
-                        *p_dest = (*p_src - p_means[k])*p_invstds[k];
-                        *p_dest = (*p_dest)*p_gamma[k] + p_beta[k];
-                        ++p_src;
-                        ++p_dest;
-                    }
+                    const float x_hat = (p_src[i] - p_means[k])*p_invstds[k];
+                    p_dest[i] = x_hat*p_gamma[k] + p_beta[k];
                 }
-            }
+            }, min_elements_per_thread/std::max(1L, num));
 
             // now keep track of the running means 
             running_means.copy_size(means);
@@ -1195,67 +1578,54 @@ namespace dlib
             const auto p_dvars = dvars.host();
             const auto p_dmeans = dmeans.host();
 
-            for (long n = 0; n < src.num_samples(); ++n)
+            // Each channel only accumulates over its own planes, so the channels can be
+            // processed in parallel.
+            const float invnum = 1.0f/(src.num_samples()*num);
+            cpu_parallel_for(0, src.k(), [&](long k)
             {
-                for (long k = 0; k < src.k(); ++k)
+                const float invstd_pow = -0.5*std::pow(p_invstds[k], 3.0f);
+                for (long n = 0; n < src.num_samples(); ++n)
                 {
-                    const float invstd_pow = -0.5*std::pow(p_invstds[k], 3.0f);
+                    const float* x = p_src + (n*src.k() + k)*num;
+                    const float* gi = p_grad + (n*src.k() + k)*num;
                     for (long i = 0; i < num; ++i)
                     {
-                        const float x_hat = (*p_src - p_means[k])*p_invstds[k];
-                        p_beta_grad[k] += *p_grad;
-                        p_gamma_grad[k] += (*p_grad)*x_hat;
-
-                        const float dx = *p_grad * p_gamma[k];
+                        const float x_hat = (x[i] - p_means[k])*p_invstds[k];
+                        p_beta_grad[k] += gi[i];
+                        p_gamma_grad[k] += gi[i]*x_hat;
 
-                        p_dvars[k] += dx*(*p_src - p_means[k])*invstd_pow;
+                        const float dx = gi[i] * p_gamma[k];
 
-                        ++p_grad;
-                        ++p_src;
+                        p_dvars[k] += dx*(x[i] - p_means[k])*invstd_pow;
                     }
                 }
-            }
 
-            p_grad = gradient_input.host();
-            p_src = src.host();
-            const float invnum = 1.0f/(src.num_samples()*num);
-            for (long n = 0; n < src.num_samples(); ++n)
-            {
-                for (long k = 0; k < src.k(); ++k)
+                for (long n = 0; n < src.num_samples(); ++n)
                 {
+                    const float* x = p_src + (n*src.k() + k)*num;
+                    const float* gi = p_grad + (n*src.k() + k)*num;
                     for (long i = 0; i < num; ++i)
                     {
-                        const float dx = *p_grad * p_gamma[k];
-
-                        p_dmeans[k] += -dx*p_invstds[k] + p_dvars[k] * -2*(*p_src - p_means[k])*invnum;
+                        const float dx = gi[i] * p_gamma[k];
 
-                        ++p_grad;
-                        ++p_src;
+                        p_dmeans[k] += -dx*p_invstds[k] + p_dvars[k] * -2*(x[i] - p_means[k])*invnum;
                     }
                 }
-            }
-            p_grad = gradient_input.host();
-            p_src = src.host();
+            }, min_elements_per_thread/std::max<long long>(1, src.num_samples()*num));
+
             auto p_src_grad = src_grad.host();
-            for (long n = 0; n < src.num_samples(); ++n)
+            cpu_parallel_for(0, src.num_samples()*src.k(), [&](long plane)
             {
-                for (long k = 0; k < src.k(); ++k)
+                const long k = plane%src.k();
+                for (long i = plane*num; i < (plane+1)*num; ++i)
                 {
-                    for (long i = 0; i < num; ++i)
-                    {
-                        const float dx = *p_grad * p_gamma[k];
-
-                        *p_src_grad += dx*p_invstds[k] + 
-                            p_dvars[k]*2*(*p_src - p_means[k])*invnum + 
-                            p_dmeans[k]*invnum;
+                    const float dx = p_grad[i] * p_gamma[k];
 
-
-                        ++p_grad;
-                        ++p_src;
-                        ++p_src_grad;
-                    }
+                    p_src_grad[i] += dx*p_invstds[k] + 
+                        p_dvars[k]*2*(p_src[i] - p_means[k])*invnum + 
+                        p_dmeans[k]*invnum;
                 }
-            }
+            }, min_elements_per_thread/std::max(1L, num));
         }
 
     // -----------------------------------------------------------------------------------
@@ -1636,39 +2006,27 @@ namespace dlib
             // exp() to avoid numeric overflow in the subsequent computations.  Doing this
             // doesn't change the resulting output, it just makes it more numerically
             // stable.
-            for (long n = 0; n < src.num_samples(); ++n)
+            cpu_parallel_for(0, src.num_samples()*num_locations, [&](long j)
             {
-                auto ss = s + num_locations*num_channels*n;
-                auto dd = d + num_locations*num_channels*n;
-                for (long i = 0; i < num_locations; ++i)
-                {
-                    float max_val = -std::numeric_limits<float>::infinity();
-                    for (long k = 0; k < num_channels; ++k)
-                        max_val = std::max(max_val, ss[k*num_locations]);
+                const long n = j/num_locations;
+                const long i = j%num_locations;
+                const auto ss = s + num_locations*num_channels*n + i;
+                const auto dd = d + num_locations*num_channels*n + i;
 
-                    for (long k = 0; k < num_channels; ++k)
-                        dd[k*num_locations] = std::exp(ss[k*num_locations]-max_val);
+                float max_val = -std::numeric_limits<float>::infinity();
+                for (long k = 0; k < num_channels; ++k)
+                    max_val = std::max(max_val, ss[k*num_locations]);
 
-                    ++ss;
-                    ++dd;
-                }
-            }
-
-            // Now normalize each channel so they sum to 1.
-            for (long n = 0; n < src.num_samples(); ++n)
-            {
-                const auto dd = d + num_locations*num_channels*n;
-                for (long i = 0; i < num_locations; ++i)
-                {
-                    const auto ddd = dd+i;
+                for (long k = 0; k < num_channels; ++k)
+                    dd[k*num_locations] = std::exp(ss[k*num_locations]-max_val);
 
-                    float temp = 0;
-                    for (long k = 0; k < num_channels; ++k)
-                        temp += ddd[k*num_locations];
-                    for (long k = 0; k < num_channels; ++k)
-                        ddd[k*num_locations] /= temp;
-                }
-            }
+                // Now normalize each channel so they sum to 1.
+                float temp = 0;
+                for (long k = 0; k < num_channels; ++k)
+                    temp += dd[k*num_locations];
+                for (long k = 0; k < num_channels; ++k)
+                    dd[k*num_locations] /= temp;
+            }, min_elements_per_thread/std::max(1L, num_channels));
         }
 
         void softmax_gradient (
@@ -1687,32 +2045,28 @@ namespace dlib
             const auto in = gradient_input.host();
 
 
-            for (long n = 0; n < grad.num_samples(); ++n)
+            cpu_parallel_for(0, grad.num_samples()*num_locations, [&](long j)
             {
-                const auto d2 = d + num_locations*num_channels*n;
-                const auto g2 = g + num_locations*num_channels*n;
-                const auto in2 = in + num_locations*num_channels*n;
-                for (long i = 0; i < num_locations; ++i)
-                {
-                    const auto d3 = d2+i;
-                    const auto g3 = g2+i;
-                    const auto in3 = in2+i;
+                const long n = j/num_locations;
+                const long i = j%num_locations;
+                const auto d3 = d + num_locations*num_channels*n + i;
+                const auto g3 = g + num_locations*num_channels*n + i;
+                const auto in3 = in + num_locations*num_channels*n + i;
 
-                    float temp = 0;
+                float temp = 0;
+                for (long k = 0; k < num_channels; ++k)
+                    temp += -d3[k*num_locations]*in3[k*num_locations];
+                if (is_same_object(gradient_input, grad))
+                {
                     for (long k = 0; k < num_channels; ++k)
-                        temp += -d3[k*num_locations]*in3[k*num_locations];
-                    if (is_same_object(gradient_input, grad))
-                    {
-                        for (long k = 0; k < num_channels; ++k)
-                            g3[k*num_locations] = d3[k*num_locations]*(temp+in3[k*num_locations]);
-                    }
-                    else
-                    {
-                        for (long k = 0; k < num_channels; ++k)
-                            g3[k*num_locations] += d3[k*num_locations]*(temp+in3[k*num_locations]);
-                    }
+                        g3[k*num_locations] = d3[k*num_locations]*(temp+in3[k*num_locations]);
                 }
-            }
+                else
+                {
+                    for (long k = 0; k < num_channels; ++k)
+                        g3[k*num_locations] += d3[k*num_locations]*(temp+in3[k*num_locations]);
+                }
+            }, min_elements_per_thread/std::max(1L, num_channels));
         }
         }
 
@@ -1769,8 +2123,10 @@ namespace dlib
         {
             const auto d = dest.host();
             const auto s = src.host();
-            for (size_t i = 0; i < src.size(); ++i)
+            parallel_for_each_element(src.size(), [&](long i)
+            {
                 d[i] = 1/(1+std::exp(-s[i]));
+            });
         }
 
         void sigmoid_gradient (
@@ -1784,13 +2140,17 @@ namespace dlib
             const auto in = gradient_input.host();
             if (is_same_object(gradient_input, grad))
             {
-                for (size_t i = 0; i < dest.size(); ++i)
+                parallel_for_each_element(dest.size(), [&](long i)
+                {
                     g[i] = in[i]*d[i]*(1-d[i]);
+                });
             }
             else
             {
-                for (size_t i = 0; i < dest.size(); ++i)
+                parallel_for_each_element(dest.size(), [&](long i)
+                {
                     g[i] += in[i]*d[i]*(1-d[i]);
+                });
             }
         }
 
@@ -1803,12 +2163,12 @@ namespace dlib
         {
             const auto d = dest.host_write_only();
             const auto s = src.host();
-            for (size_t i = 0; i < src.size(); ++i)
+            parallel_for_each_element(src.size(), [&](long i)
             {
                 const auto e = std::exp(s[i]);
                 const auto delta = 2*e + e*e + 2;
                 d[i] = s[i] - 2*s[i]/delta;
-            }
+            });
         }
 
         void mish_gradient(
@@ -1836,13 +2196,17 @@ namespace dlib
 
             if (is_same_object(gradient_input, grad))
             {
-                for (size_t i = 0; i < src.size(); ++i)
+                parallel_for_each_element(src.size(), [&](long i)
+                {
                     g[i] = in[i]*calculate_gradient(s[i]);
+                });
             }
             else
             {
-                for (size_t i = 0; i < src.size(); ++i)
+                parallel_for_each_element(src.size(), [&](long i)
+                {
                     g[i] += in[i]*calculate_gradient(s[i]);
+                });
             }
         }
 
@@ -1853,7 +2217,12 @@ namespace dlib
             const tensor& src
         )
         {
-            dest = lowerbound(mat(src), 0);
+            const float* s = src.host();
+            float* d = dest.host();
+            parallel_for_each_element(dest.size(), [&](long i)
+            {
+                d[i] = std::max(s[i], 0.0f);
+            });
         }
 
         void relu_gradient (
@@ -1867,21 +2236,21 @@ namespace dlib
             float* out = grad.host();
             if (is_same_object(grad, gradient_input))
             {
-                for (size_t i = 0; i < dest.size(); ++i)
+                parallel_for_each_element(dest.size(), [&](long i)
                 {
                     if (in[i] > 0)
                         out[i] = gi[i];
                     else
                         out[i] = 0;
-                }
+                });
             }
             else
             {
-                for (size_t i = 0; i < dest.size(); ++i)
+                parallel_for_each_element(dest.size(), [&](long i)
                 {
                     if (in[i] > 0)
                         out[i] += gi[i];
-                }
+                });
             }
         }
 
@@ -1896,13 +2265,13 @@ namespace dlib
             const float p = param.host()[0];
             const float* s = src.host();
             float* d = dest.host();
-            for (size_t i = 0; i < dest.size(); ++i)
+            parallel_for_each_element(dest.size(), [&](long i)
             {
                 if (s[i] > 0)
                     d[i] = s[i];
                 else
                     d[i] = p*s[i];
-            }
+            });
         }
 
         void prelu_gradient (
@@ -1944,13 +2313,13 @@ namespace dlib
         {
             const float* s = src.host();
             float* d = dest.host();
-            for (size_t i = 0; i < dest.size(); ++i)
+            parallel_for_each_element(dest.size(), [&](long i)
             {
                 if (s[i] > 0)
                     d[i] = s[i];
                 else
                     d[i] = alpha * s[i];
-            }
+            });
         }
 
         void leaky_relu_gradient (
@@ -1965,23 +2334,23 @@ namespace dlib
             float* out = grad.host();
             if (is_same_object(grad, gradient_input))
             {
-                for (size_t i = 0; i < dest.size(); ++i)
+                parallel_for_each_element(dest.size(), [&](long i)
                 {
                     if (in[i] > 0)
                         out[i] = gi[i];
                     else
                         out[i] = alpha * gi[i];
-                }
+                });
             }
             else
             {
-                for (size_t i = 0; i < dest.size(); ++i)
+                parallel_for_each_element(dest.size(), [&](long i)
                 {
                     if (in[i] > 0)
                         out[i] += gi[i];
                     else
                         out[i] += alpha * gi[i];
-                }
+                });
             }
         }
 
@@ -1994,8 +2363,10 @@ namespace dlib
         {
             const auto d = dest.host();
             const auto s = src.host();
-            for (size_t i = 0; i < src.size(); ++i)
+            parallel_for_each_element(src.size(), [&](long i)
+            {
                 d[i] = std::tanh(s[i]);
+            });
         }
 
         void tanh_gradient (
@@ -2009,13 +2380,17 @@ namespace dlib
             const auto in = gradient_input.host();
             if (is_same_object(grad, gradient_input))
             {
-                for (size_t i = 0; i < dest.size(); ++i)
+                parallel_for_each_element(dest.size(), [&](long i)
+                {
                     g[i] = in[i]*(1-d[i]*d[i]);
+                });
             }
             else
             {
-                for (size_t i = 0; i < dest.size(); ++i)
+                parallel_for_each_element(dest.size(), [&](long i)
+                {
                     g[i] += in[i]*(1-d[i]*d[i]);
+                });
             }
         }
 
@@ -2027,7 +2402,12 @@ namespace dlib
             const float ceiling
         )
         {
-            dest = upperbound(lowerbound(mat(src), 0), ceiling);
+            const float* s = src.host();
+            float* d = dest.host();
+            parallel_for_each_element(dest.size(), [&](long i)
+            {
+                d[i] = std::min(std::max(s[i], 0.0f), ceiling);
+            });
         }
 
         void clipped_relu_gradient (
@@ -2042,21 +2422,21 @@ namespace dlib
             const auto gi = gradient_input.host();
             if (is_same_object(grad, gradient_input))
             {
-                for (size_t i = 0; i < dest.size(); ++i)
+                parallel_for_each_element(dest.size(), [&](long i)
                 {
                     if (in[i] > 0 && in[i] < ceiling)
                         out[i] = gi[i];
                     else
                         out[i] = 0;
-                }
+                });
             }
             else
             {
-                for (size_t i = 0; i < dest.size(); ++i)
+                parallel_for_each_element(dest.size(), [&](long i)
                 {
                     if (in[i] > 0 && in[i] < ceiling)
                         out[i] += gi[i];
-                }
+                });
             }
         }
 
@@ -2070,13 +2450,13 @@ namespace dlib
         {
             const auto d = dest.host();
             const auto s = src.host();
-            for (size_t i = 0; i < src.size(); ++i)
+            parallel_for_each_element(src.size(), [&](long i)
             {
                 if (s[i] > 0)
                     d[i] = s[i];
                 else
                     d[i] = alpha * (std::exp(s[i]) - 1.0f);
-            }
+            });
         }
 
         void elu_gradient (
@@ -2091,23 +2471,23 @@ namespace dlib
             const auto gi = gradient_input.host();
             if (is_same_object(grad, gradient_input))
             {
-                for (size_t i = 0; i < dest.size(); ++i)
+                parallel_for_each_element(dest.size(), [&](long i)
                 {
                     if (in[i] > 0)
                         out[i] = gi[i];
                     else
                         out[i] = (alpha + in[i]) * gi[i];
-                }
+                });
             }
             else
             {
-                for (size_t i = 0; i < dest.size(); ++i)
+                parallel_for_each_element(dest.size(), [&](long i)
                 {
                     if (in[i] > 0)
                         out[i] += gi[i];
                     else
                         out[i] += (alpha + in[i]) * gi[i];
-                }
+                });
             }
         }
 
@@ -2120,8 +2500,10 @@ namespace dlib
         {
             const auto d = dest.host();
             const auto s = src.host();
-            for (size_t i = 0; i < src.size(); ++i)
+            parallel_for_each_element(src.size(), [&](long i)
+            {
                 d[i] = 0.5f*s[i]*(1.0f + std::erf(s[i]/sqrt_2));
+            });
         }
 
         void gelu_gradient (
@@ -2142,13 +2524,17 @@ namespace dlib
             const auto in = gradient_input.host();
             if (is_same_object(grad, gradient_input))
             {
-                for (size_t i = 0; i < src.size(); ++i)
+                parallel_for_each_element(src.size(), [&](long i)
+                {
                     g[i] = in[i]*compute_gradient(s[i]);
+                });
             }
             else
             {
-                for (size_t i = 0; i < src.size(); ++i)
+                parallel_for_each_element(src.size(), [&](long i)
+                {
                     g[i] += in[i]*compute_gradient(s[i]);
+                });
             }
         }
 
@@ -2162,7 +2548,7 @@ namespace dlib
         {
             const float* s = src.host();
             float* d = dest.host();
-            for (size_t i = 0; i < dest.size(); ++i)
+            parallel_for_each_element(dest.size(), [&](long i)
             {
                 if (s[i] >= beta)
                     d[i] = s[i];
@@ -2170,7 +2556,7 @@ namespace dlib
                     d[i] = 0;
                 else
                     d[i] = (s[i] + beta) * (s[i] + beta) / (4 * beta);
-            }
+            });
         }
 
         void smelu_gradient (
@@ -2185,7 +2571,7 @@ namespace dlib
             float* out = grad.host();
             if (is_same_object(grad, gradient_input))
             {
-                for (size_t i = 0; i < dest.size(); ++i)
+                parallel_for_each_element(dest.size(), [&](long i)
                 {
                     if (in[i] >= beta)
                         out[i] = gi[i];
@@ -2193,19 +2579,19 @@ namespace dlib
                         out[i] = 0;
                     else
                         out[i] = std::sqrt(beta * in[i]) / beta * gi[i];
-                }
+                });
             }
             else
             {
-                for (size_t i = 0; i < dest.size(); ++i)
+                parallel_for_each_element(dest.size(), [&](long i)
                 {
                     if (in[i] >= beta)
                         out[i] += gi[i];
                     else if (in[i] == 0)
-                        continue;
+                        return;
                     else
                         out[i] += std::sqrt(beta * in[i]) / beta * gi[i];
-                }
+                });
             }
         }
 
@@ -2218,8 +2604,10 @@ namespace dlib
         {
             const auto d = dest.host();
             const auto s = src.host();
-            for (size_t i = 0; i < src.size(); ++i)
+            parallel_for_each_element(src.size(), [&](long i)
+            {
                 d[i] = s[i] * impl::sigmoid(s[i]);
+            });
         }
 
         void silu_gradient (
@@ -2233,19 +2621,19 @@ namespace dlib
             const auto in = gradient_input.host();
             if (is_same_object(grad, gradient_input))
             {
-                for (size_t i = 0; i < src.size(); ++i)
+                parallel_for_each_element(src.size(), [&](long i)
                 {
                     const auto sig_s = impl::sigmoid(s[i]);
                     g[i] = in[i] * (sig_s * (1.0f + s[i] * (1.0f - sig_s)));
-                }
+                });
             }
             else
             {
-                for (size_t i = 0; i < src.size(); ++i)
+                parallel_for_each_element(src.size(), [&](long i)
                 {
                     const auto sig_s = impl::sigmoid(s[i]);
                     g[i] += in[i] * (sig_s * (1.0f + s[i] * (1.0f - sig_s)));
-                }
+                });
             }
         }
 
@@ -2270,7 +2658,7 @@ namespace dlib
             const float* s = src.host();
             float* d = dest.host();
 
-            parallel_for(0, dest.k()*dest.num_samples(), [&](long i)
+            cpu_parallel_for(0, dest.k()*dest.num_samples(), [&](long i)
             {
                 auto simg = sub_image(s+i*src_channel_stride, src.nr(), src.nc(), src_row_stride);
                 auto dimg = sub_image(d+i*dest_channel_stride, dest.nr(), dest.nc(), dest_row_stride);
@@ -2355,7 +2743,7 @@ namespace dlib
             const size_t sk = src.k(), snr = src.nr(), snc = src.nc();
             const size_t dk = dest.k(), dnr = dest.nr(), dnc = dest.nc(), dsize = dest.size();
 
-            dlib::parallel_for(0, dsize, [&](long i)
+            parallel_for_each_element(dsize, [&](long i)
             {
                 const size_t out_plane_size = dnr * dnc;
                 const size_t out_sample_size = dk * out_plane_size;
@@ -2397,7 +2785,7 @@ namespace dlib
             const float* gi = gradient_input.host();
             float* g = grad.host();
 
-            parallel_for(0, gradient_input.num_samples(), [&](long n)
+            cpu_parallel_for(0, gradient_input.num_samples(), [&](long n)
             {
                 for (long k = 0; k < gradient_input.k(); ++k)
                 {
@@ -2511,7 +2899,7 @@ namespace dlib
             long nr = gradient_input.nr(), nc = gradient_input.nc();
 
             std::vector<dlib::mutex> embedding_mutexes(grads.num_samples());
-            parallel_for(0, ns * nk, [&](long i)
+            cpu_parallel_for(0, ns * nk, [&](long i)
                 {
                     long s = i / nk;
                     long k = i % nk;
@@ -2640,53 +3028,50 @@ namespace dlib
 
 
             auto d = dest.host();
+            // image_plane() reads src.host(), so make sure src is on the host before
+            // the threads start.
+            src.host();
             const long x_offset = window_width/2 - padding_x;
             const long y_offset = window_height/2 - padding_y;
             if (does_max_pooling())
             {
-                for (long n = 0; n < dest.num_samples(); ++n)
+                cpu_parallel_for(0, dest.num_samples()*dest.k(), [&](long plane)
                 {
-                    for (long k = 0; k < dest.k(); ++k)
-                    {
-                        auto simg = image_plane(src,n,k);
-                        auto dimg = d + (n*dest.k() + k)*dest.nr()*dest.nc();
+                    auto simg = image_plane(src,plane/dest.k(),plane%dest.k());
+                    auto dimg = d + plane*dest.nr()*dest.nc();
 
-                        for (long r = 0; r < dest.nr(); ++r)
+                    for (long r = 0; r < dest.nr(); ++r)
+                    {
+                        for (long c = 0; c < dest.nc(); ++c)
                         {
-                            for (long c = 0; c < dest.nc(); ++c)
-                            {
-                                auto win = centered_rect(c*stride_x+x_offset,
-                                    r*stride_y+y_offset,
-                                    window_width,
-                                    window_height);
-                                dimg[r*dest.nc() + c] = max(subm_clipped(simg,win));
-                            }
+                            auto win = centered_rect(c*stride_x+x_offset,
+                                r*stride_y+y_offset,
+                                window_width,
+                                window_height);
+                            dimg[r*dest.nc() + c] = max(subm_clipped(simg,win));
                         }
                     }
-                }
+                });
             }
             else
             {
-                for (long n = 0; n < dest.num_samples(); ++n)
+                cpu_parallel_for(0, dest.num_samples()*dest.k(), [&](long plane)
                 {
-                    for (long k = 0; k < dest.k(); ++k)
-                    {
-                        auto simg = image_plane(src,n,k);
-                        auto dimg = d + (n*dest.k() + k)*dest.nr()*dest.nc();
+                    auto simg = image_plane(src,plane/dest.k(),plane%dest.k());
+                    auto dimg = d + plane*dest.nr()*dest.nc();
 
-                        for (long r = 0; r < dest.nr(); ++r)
+                    for (long r = 0; r < dest.nr(); ++r)
+                    {
+                        for (long c = 0; c < dest.nc(); ++c)
                         {
-                            for (long c = 0; c < dest.nc(); ++c)
-                            {
-                                auto win = centered_rect(c*stride_x+x_offset,
-                                    r*stride_y+y_offset,
-                                    window_width,
-                                    window_height);
-                                dimg[r*dest.nc() + c] = mean(subm_clipped(simg,win));
-                            }
+                            auto win = centered_rect(c*stride_x+x_offset,
+                                r*stride_y+y_offset,
+                                window_width,
+                                window_height);
+                            dimg[r*dest.nc() + c] = mean(subm_clipped(simg,win));
                         }
                     }
-                }
+                });
             }
 
         }
@@ -2710,65 +3095,62 @@ namespace dlib
 
             auto gi = gradient_input.host();
             auto g = grad.host();
+            src.host();
             const long x_offset = window_width/2 - padding_x;
             const long y_offset = window_height/2 - padding_y;
             if (does_max_pooling())
             {
-                for (long n = 0; n < dest.num_samples(); ++n)
+                // Each plane of grad only receives gradient from the same plane of
+                // gradient_input, so the planes can be done in parallel.
+                cpu_parallel_for(0, dest.num_samples()*dest.k(), [&](long plane)
                 {
-                    for (long k = 0; k < dest.k(); ++k)
-                    {
-                        auto simg = image_plane(src,n,k);
-                        auto gimg = g + (n*grad.k() + k)*grad.nr()*grad.nc();
-                        auto giimg = gi + (n*dest.k() + k)*dest.nr()*dest.nc();
-                        auto imgbox = get_rect(simg);
+                    auto simg = image_plane(src,plane/dest.k(),plane%dest.k());
+                    auto gimg = g + plane*grad.nr()*grad.nc();
+                    auto giimg = gi + plane*dest.nr()*dest.nc();
+                    auto imgbox = get_rect(simg);
 
-                        for (long r = 0; r < dest.nr(); ++r)
+                    for (long r = 0; r < dest.nr(); ++r)
+                    {
+                        for (long c = 0; c < dest.nc(); ++c)
                         {
-                            for (long c = 0; c < dest.nc(); ++c)
-                            {
-                                auto win = centered_rect(c*stride_x+x_offset,
-                                    r*stride_y+y_offset,
-                                    window_width,
-                                    window_height).intersect(imgbox);
-                                auto p = max_point(subm(simg,win))+win.tl_corner();
-                                gimg[p.y()*grad.nc()+p.x()] += giimg[r*dest.nc()+c];
-                            }
+                            auto win = centered_rect(c*stride_x+x_offset,
+                                r*stride_y+y_offset,
+                                window_width,
+                                window_height).intersect(imgbox);
+                            auto p = max_point(subm(simg,win))+win.tl_corner();
+                            gimg[p.y()*grad.nc()+p.x()] += giimg[r*dest.nc()+c];
                         }
                     }
-                }
+                });
             }
             else
             {
-                for (long n = 0; n < dest.num_samples(); ++n)
+                cpu_parallel_for(0, dest.num_samples()*dest.k(), [&](long plane)
                 {
-                    for (long k = 0; k < dest.k(); ++k)
-                    {
-                        auto simg = image_plane(src,n,k);
-                        auto gimg = g + (n*grad.k() + k)*grad.nr()*grad.nc();
-                        auto giimg = gi + (n*dest.k() + k)*dest.nr()*dest.nc();
-                        auto imgbox = get_rect(simg);
+                    auto simg = image_plane(src,plane/dest.k(),plane%dest.k());
+                    auto gimg = g + plane*grad.nr()*grad.nc();
+                    auto giimg = gi + plane*dest.nr()*dest.nc();
+                    auto imgbox = get_rect(simg);
 
-                        for (long r = 0; r < dest.nr(); ++r)
+                    for (long r = 0; r < dest.nr(); ++r)
+                    {
+                        for (long c = 0; c < dest.nc(); ++c)
                         {
-                            for (long c = 0; c < dest.nc(); ++c)
+                            auto win = centered_rect(c*stride_x+x_offset,
+                                r*stride_y+y_offset,
+                                window_width,
+                                window_height).intersect(imgbox);
+                            const float delta = giimg[r*dest.nc()+c]/win.area();
+                            for (long y = win.top(); y <= win.bottom(); ++y)
                             {
-                                auto win = centered_rect(c*stride_x+x_offset,
-                                    r*stride_y+y_offset,
-                                    window_width,
-                                    window_height).intersect(imgbox);
-                                const float delta = giimg[r*dest.nc()+c]/win.area();
-                                for (long y = win.top(); y <= win.bottom(); ++y)
+                                for (long x = win.left(); x <= win.right(); ++x)
                                 {
-                                    for (long x = win.left(); x <= win.right(); ++x)
-                                    {
-                                        gimg[y*grad.nc()+x] += delta;
-                                    }
+                                    gimg[y*grad.nc()+x] += delta;
                                 }
                             }
                         }
                     }
-                }
+                });
             }
 
         }
@@ -2777,46 +3159,64 @@ namespace dlib
     // ------------------------------------------------------------------------------------
     // ------------------------------------------------------------------------------------
 
+        long conv_rows_per_band (
+            long out_nc,
+            long filter_size
+        )
+        {
+            // Aim for an im2col() matrix of about 256KB.
+            const long max_band_size = 1<<16;
+            return std::max(1L, max_band_size/std::max(1L, out_nc*filter_size));
+        }
+
         void img2col(
             matrix<float>& output,
             const tensor& data,
             long n,
+            long k_offset,
+            long num_channels,
             long filter_nr,
             long filter_nc,
             long stride_y,
             long stride_x,
             long padding_y,
-            long padding_x
+            long padding_x,
+            long dilation_y,
+            long dilation_x,
+            long out_row_begin,
+            long out_row_end
         )
         {
             const auto d = data.host() + data.k()*data.nr()*data.nc()*n;
             const rectangle boundary = get_rect(data);
 
-            const long out_nr = 1+(data.nr()+2*padding_y-filter_nr)/stride_y;
-            const long out_nc = 1+(data.nc()+2*padding_x-filter_nc)/stride_x;
+            // The size of the input window covered by a dilated filter.
+            const long window_nc = dilation_x*(filter_nc-1)+1;
+            const long out_nc = 1+(data.nc()+2*padding_x-window_nc)/stride_x;
 
-            output.set_size(out_nr*out_nc, 
-                            data.k()*filter_nr*filter_nc);
+            output.set_size((out_row_end-out_row_begin)*out_nc, 
+                            num_channels*filter_nr*filter_nc);
             DLIB_CASSERT(output.size() != 0);
             float* t = &output(0,0);
 
-            // now fill in the Toeplitz output matrix for the n-th sample in data.  
+            // now fill in the Toeplitz output matrix for output rows [out_row_begin,
+            // out_row_end) of the n-th sample in data.  
             long cnt = 0;
-            const long max_r = data.nr() + padding_y-(filter_nr-1);
-            const long max_c = data.nc() + padding_x-(filter_nc-1);
-            for (long r = -padding_y; r < max_r; r+=stride_y)
+            const long max_c = data.nc() + padding_x-(window_nc-1);
+            for (long out_r = out_row_begin; out_r < out_row_end; ++out_r)
             {
+                const long r = out_r*stride_y - padding_y;
                 for (long c = -padding_x; c < max_c; c+=stride_x)
                 {
-                    for (long k = 0; k < data.k(); ++k)
+                    for (long k = k_offset; k < k_offset+num_channels; ++k)
                     {
                         for (long y = 0; y < filter_nr; ++y)
                         {
                             for (long x = 0; x < filter_nc; ++x)
                             {
                                 DLIB_ASSERT(cnt < output.size());
-                                long xx = c+x;
-                                long yy = r+y;
+                                long xx = c+x*dilation_x;
+                                long yy = r+y*dilation_y;
                                 if (boundary.contains(xx,yy))
                                     *t = d[(k*data.nr() + yy)*data.nc() + xx];
                                 else
@@ -2832,37 +3232,44 @@ namespace dlib
 
         void col2img(
             const matrix<float>& output,
-            tensor& data,
+            const tensor& data,
+            float* data_host,
             long n,
+            long k_offset,
+            long num_channels,
             long filter_nr,
             long filter_nc,
             long stride_y,
             long stride_x,
             long padding_y,
-            long padding_x
+            long padding_x,
+            long dilation_y,
+            long dilation_x
         )
         {
-            const auto d = data.host() + data.k()*data.nr()*data.nc()*n;
+            // data_host is data.host().  It's passed in separately so that several threads
+            // can write to different channels of data at the same time.
+            const auto d = data_host + data.k()*data.nr()*data.nc()*n;
             const rectangle boundary = get_rect(data);
 
             DLIB_CASSERT(output.size() != 0);
             const float* t = &output(0,0);
 
             // now fill in the Toeplitz output matrix for the n-th sample in data.  
-            const long max_r = data.nr() + padding_y-(filter_nr-1);
-            const long max_c = data.nc() + padding_x-(filter_nc-1);
+            const long max_r = data.nr() + padding_y-dilation_y*(filter_nr-1);
+            const long max_c = data.nc() + padding_x-dilation_x*(filter_nc-1);
             for (long r = -padding_y; r < max_r; r+=stride_y)
             {
                 for (long c = -padding_x; c < max_c; c+=stride_x)
                 {
-                    for (long k = 0; k < data.k(); ++k)
+                    for (long k = k_offset; k < k_offset+num_channels; ++k)
                     {
                         for (long y = 0; y < filter_nr; ++y)
                         {
                             for (long x = 0; x < filter_nc; ++x)
                             {
-                                long xx = c+x;
-                                long yy = r+y;
+                                long xx = c+x*dilation_x;
+                                long yy = r+y*dilation_y;
                                 if (boundary.contains(xx,yy))
                                     d[(k*data.nr() + yy)*data.nc() + xx] += *t;
                                 ++t;
@@ -2883,8 +3290,8 @@ namespace dlib
             DLIB_CASSERT(last_stride_y > 0 && last_stride_x > 0, "You must call setup() before calling this function.");
             output.set_size(data.num_samples(),
                             filters.num_samples(),
-                            1+(data.nr()+2*last_padding_y-filters.nr())/last_stride_y,
-                            1+(data.nc()+2*last_padding_x-filters.nc())/last_stride_x);
+                            1+(data.nr()+2*last_padding_y-(last_dilation_y*(filters.nr()-1)+1))/last_stride_y,
+                            1+(data.nc()+2*last_padding_x-(last_dilation_x*(filters.nc()-1)+1))/last_stride_x);
             (*this)(add_to_output, static_cast<tensor&>(output),data,filters);
         }
 
@@ -2895,31 +3302,70 @@ namespace dlib
             const tensor& filters
         )
         {
+            const long window_nr = last_dilation_y*(filters.nr()-1)+1;
+            const long window_nc = last_dilation_x*(filters.nc()-1)+1;
             DLIB_CASSERT(is_same_object(output,data) == false);
             DLIB_CASSERT(is_same_object(output,filters) == false);
-            DLIB_CASSERT(filters.k() == data.k());
+            DLIB_CASSERT(filters.k()*last_groups == data.k());
             DLIB_CASSERT(last_stride_y > 0 && last_stride_x > 0, "You must call setup() before calling this function.");
-            DLIB_CASSERT(filters.nr() <= data.nr() + 2*last_padding_y,
+            DLIB_CASSERT(window_nr <= data.nr() + 2*last_padding_y,
                 "Filter windows must be small enough to fit into the padded image.");
-            DLIB_CASSERT(filters.nc() <= data.nc() + 2*last_padding_x,
+            DLIB_CASSERT(window_nc <= data.nc() + 2*last_padding_x,
                 "Filter windows must be small enough to fit into the padded image.");
 
             DLIB_CASSERT(output.num_samples() == data.num_samples());
             DLIB_CASSERT(output.k() == filters.num_samples());
-            DLIB_CASSERT(output.nr() == 1+(data.nr()+2*last_padding_y-filters.nr())/last_stride_y);
-            DLIB_CASSERT(output.nc() == 1+(data.nc()+2*last_padding_x-filters.nc())/last_stride_x);
-
-
-            matrix<float> temp;
-            for (long n = 0; n < data.num_samples(); ++n)
-            {
-                img2col(temp, data, n, filters.nr(), filters.nc(), last_stride_y, last_stride_x, last_padding_y, last_padding_x);
+            DLIB_CASSERT(output.nr() == 1+(data.nr()+2*last_padding_y-window_nr)/last_stride_y);
+            DLIB_CASSERT(output.nc() == 1+(data.nc()+2*last_padding_x-window_nc)/last_stride_x);
+
+
+            // The output is computed in bands of rows.  Each band is a single matrix
+            // multiply between the filters and the im2col() matrix of the input windows
+            // under the band.  The bands are sized so that the im2col() matrix stays in
+            // cache, and since they are independent of each other they also give the
+            // threads enough to work on when there are only a few samples.
+            const long filters_per_group = filters.num_samples()/last_groups;
+            const long out_size = output.nr()*output.nc();
+            const long rows_per_band = conv_rows_per_band(output.nc(), filters.k()*filters.nr()*filters.nc());
+            const long bands_per_sample = (output.nr()+rows_per_band-1)/rows_per_band;
+            // Make sure everything is on the host before the threads start reading it.
+            data.host();
+            filters.host();
+            float* out = output.host();
+            cpu_parallel_for_blocked(0, data.num_samples()*bands_per_sample, [&](long begin, long end)
+            {
+                matrix<float> temp, result;
+                for (long band = begin; band < end; ++band)
+                {
+                    const long n = band/bands_per_sample;
+                    const long row_begin = (band%bands_per_sample)*rows_per_band;
+                    const long row_end = std::min<long>(row_begin+rows_per_band, output.nr());
+                    for (long g = 0; g < last_groups; ++g)
+                    {
+                        img2col(temp, data, n, g*filters.k(), filters.k(), filters.nr(), filters.nc(), last_stride_y, last_stride_x,
+                            last_padding_y, last_padding_x, last_dilation_y, last_dilation_x, row_begin, row_end);
+                        if (last_groups == 1)
+                            result = mat(filters)*trans(temp);
+                        else
+                            result = rowm(mat(filters), range(g*filters_per_group, (g+1)*filters_per_group-1))*trans(temp);
 
-                if (add_to_output)
-                    output.add_to_sample(n, mat(filters)*trans(temp));
-                else 
-                    output.set_sample(n, mat(filters)*trans(temp));
-            }
+                        for (long f = 0; f < filters_per_group; ++f)
+                        {
+                            float* o = out + (n*output.k() + g*filters_per_group + f)*out_size + row_begin*output.nc();
+                            const float* r = &result(f,0);
+                            if (add_to_output)
+                            {
+                                for (long i = 0; i < result.nc(); ++i)
+                                    o[i] += r[i];
+                            }
+                            else
+                            {
+                                std::copy(r, r+result.nc(), o);
+                            }
+                        }
+                    }
+                }
+            });
         }
 
         void tensor_conv::operator() (
@@ -2963,19 +3409,31 @@ namespace dlib
             tensor& data_gradient
         )
         {
-            matrix<float> temp;
             if (!add_to_output)
                 data_gradient = 0;
-            for (long n = 0; n < gradient_input.num_samples(); ++n)
-            {
-                auto gi = mat(gradient_input.host()+gradient_input.k()*gradient_input.nr()*gradient_input.nc()*n,
-                              gradient_input.k(),
-                              gradient_input.nr()*gradient_input.nc());
-                                    
-
-                temp = trans(gi)*mat(filters);
-                col2img(temp, data_gradient, n, filters.nr(), filters.nc(), last_stride_y, last_stride_x, last_padding_y, last_padding_x);
-            }
+            const long filters_per_group = filters.num_samples()/last_groups;
+            const long out_size = gradient_input.nr()*gradient_input.nc();
+            const float* gi_data = gradient_input.host();
+            filters.host();
+            float* dg_data = data_gradient.host();
+            // Each (sample, group) pair writes to its own channels of data_gradient, so
+            // they can all be done in parallel.
+            cpu_parallel_for_blocked(0, gradient_input.num_samples()*last_groups, [&](long begin, long end)
+            {
+                matrix<float> temp;
+                for (long job = begin; job < end; ++job)
+                {
+                    const long n = job/last_groups;
+                    const long g = job%last_groups;
+                    auto gi = mat(gi_data+(gradient_input.k()*n + g*filters_per_group)*out_size,
+                                  filters_per_group,
+                                  out_size);
+
+                    temp = trans(gi)*rowm(mat(filters), range(g*filters_per_group, (g+1)*filters_per_group-1));
+                    col2img(temp, data_gradient, dg_data, n, g*filters.k(), filters.k(), filters.nr(), filters.nc(), last_stride_y, last_stride_x,
+                        last_padding_y, last_padding_x, last_dilation_y, last_dilation_x);
+                }
+            });
         }
 
     // ------------------------------------------------------------------------------------
@@ -2988,27 +3446,56 @@ namespace dlib
             tensor& filters_gradient
         )
         {
-            matrix<float> temp;
-            for (long n = 0; n < gradient_input.num_samples(); ++n)
-            {
-                auto gi = mat(gradient_input.host()+gradient_input.k()*gradient_input.nr()*gradient_input.nc()*n,
-                              gradient_input.k(),
-                              gradient_input.nr()*gradient_input.nc());
-
-
-                img2col(temp, data, n, filters_gradient.nr(), filters_gradient.nc(), last_stride_y, last_stride_x, last_padding_y, last_padding_x);
-                if (n == 0)
-                {
-                    if (add_to_output)
-                        filters_gradient += gi*temp;
-                    else
-                        filters_gradient = gi*temp;
-                }
-                else
-                {
-                    filters_gradient += gi*temp;
+            // This uses the same bands of output rows as the forward pass.  Each thread
+            // sums the gradient from its bands into its own matrix and the matrices are
+            // added up at the end, always in the same order so the result doesn't depend
+            // on how the threads were scheduled.
+            const long filters_per_group = filters_gradient.num_samples()/last_groups;
+            const long filter_size = filters_gradient.k()*filters_gradient.nr()*filters_gradient.nc();
+            const long out_size = gradient_input.nr()*gradient_input.nc();
+            const long rows_per_band = conv_rows_per_band(gradient_input.nc(), filter_size);
+            const long bands_per_sample = (gradient_input.nr()+rows_per_band-1)/rows_per_band;
+            const long num_bands = gradient_input.num_samples()*bands_per_sample;
+            const long num_blocks = num_parallel_blocks(num_bands, 1);
+            const float* gi_data = gradient_input.host();
+            data.host();
+            std::vector<matrix<float>> partial_grads(num_blocks);
+            cpu_parallel_for(0, num_blocks, [&](long block)
+            {
+                matrix<float> temp;
+                matrix<float>& grad = partial_grads[block];
+                grad = zeros_matrix<float>(filters_gradient.num_samples(), filter_size);
+                for (long band = num_bands*block/num_blocks; band < num_bands*(block+1)/num_blocks; ++band)
+                {
+                    const long n = band/bands_per_sample;
+                    const long row_begin = (band%bands_per_sample)*rows_per_band;
+                    const long row_end = std::min<long>(row_begin+rows_per_band, gradient_input.nr());
+                    for (long g = 0; g < last_groups; ++g)
+                    {
+                        auto gi_all = mat(gi_data+(gradient_input.k()*n + g*filters_per_group)*out_size,
+                                          filters_per_group,
+                                          out_size);
+                        auto gi = subm(gi_all, 0, row_begin*gradient_input.nc(),
+                                       filters_per_group, (row_end-row_begin)*gradient_input.nc());
+
+                        img2col(temp, data, n, g*filters_gradient.k(), filters_gradient.k(), filters_gradient.nr(), filters_gradient.nc(),
+                            last_stride_y, last_stride_x, last_padding_y, last_padding_x, last_dilation_y, last_dilation_x,
+                            row_begin, row_end);
+                        if (last_groups == 1)
+                            grad += gi*temp;
+                        else
+                            set_rowm(grad, range(g*filters_per_group, (g+1)*filters_per_group-1)) += gi*temp;
+                    }
                 }
-            }
+            });
+
+            matrix<float>& grad = partial_grads[0];
+            for (long block = 1; block < num_blocks; ++block)
+                grad += partial_grads[block];
+            if (add_to_output)
+                filters_gradient += grad;
+            else
+                filters_gradient = grad;
         }
 
      // ------------------------------------------------------------------------------------
@@ -3076,7 +3563,7 @@ namespace dlib
             const long dest_nr = dest.nr();
             const long dest_nc = dest.nc();
 
-            parallel_for(0, num_samples * k_dim, [&](long i) {
+            cpu_parallel_for(0, num_samples * k_dim, [&](long i) {
                 const long n = i / k_dim;
                 const long k = i % k_dim;
                 const long src_nk_offset = (n * src.k() + k) * src_nr;
@@ -7,6 +7,8 @@
 // and cudnn_dlibapi.h
 
 #include "tensor.h"
+#include "half_tensor.h"
+#include "int8_tensor.h"
 #include "../geometry/rectangle.h"
 #include "../dnn/utilities.h"
 
@@ -157,6 +159,106 @@ namespace dlib
             const tensor& params_grad
         );
 
+    // -----------------------------------------------------------------------------------
+
+        void compute_adamw_update (
+            size_t begin,
+            size_t end,
+            tensor& s,
+            tensor& m,
+            tensor& v,
+            const float t,
+            const float learning_rate,
+            const float weight_decay,
+            const float momentum1,
+            const float momentum2,
+            const tensor& params,
+            const tensor& params_grad
+        );
+
+    // -----------------------------------------------------------------------------------
+
+        void compute_rmsprop_update (
+            size_t begin,
+            size_t end,
+            tensor& s,
+            tensor& v,
+            const float learning_rate,
+            const float weight_decay,
+            const float decay,
+            const tensor& params,
+            const tensor& params_grad
+        );
+
+    // -----------------------------------------------------------------------------------
+
+        void compute_adagrad_update (
+            size_t begin,
+            size_t end,
+            tensor& s,
+            tensor& v,
+            const float learning_rate,
+            const float weight_decay,
+            const tensor& params,
+            const tensor& params_grad
+        );
+
+    // -----------------------------------------------------------------------------------
+
+        void compute_lion_update (
+            size_t begin,
+            size_t end,
+            tensor& s,
+            tensor& m,
+            const float learning_rate,
+            const float weight_decay,
+            const float momentum1,
+            const float momentum2,
+            const tensor& params,
+            const tensor& params_grad
+        );
+
+    // -----------------------------------------------------------------------------------
+
+        void round_to_half_precision (
+            tensor& data,
+            half_format fmt
+        );
+
+    // -----------------------------------------------------------------------------------
+
+        void clamp (
+            tensor& data,
+            float lower,
+            float upper
+        );
+
+    // -----------------------------------------------------------------------------------
+
+        void int8_fc (
+            resizable_tensor& output,
+            const tensor& input,
+            float input_scale,
+            const int8_tensor& weights,
+            const tensor& biases
+        );
+
+        void int8_conv (
+            resizable_tensor& output,
+            const tensor& input,
+            float input_scale,
+            const int8_tensor& filters,
+            const tensor& biases,
+            int stride_y,
+            int stride_x,
+            int padding_y,
+            int padding_x,
+            int dilation_y,
+            int dilation_x,
+            long groups,
+            bool use_relu
+        );
+
     // -----------------------------------------------------------------------------------
 
         void batch_normalize_inference (
@@ -612,17 +714,25 @@ namespace dlib
                 int stride_y,
                 int stride_x,
                 int padding_y,
-                int padding_x
+                int padding_x,
+                int dilation_y = 1,
+                int dilation_x = 1,
+                int groups = 1
             ) 
             {
                 (void)data;    /* silence compiler */
                 DLIB_CASSERT(stride_y > 0 && stride_x > 0);
-                DLIB_CASSERT(0 <= padding_y && padding_y < filters.nr());
-                DLIB_CASSERT(0 <= padding_x && padding_x < filters.nc());
+                DLIB_CASSERT(dilation_y > 0 && dilation_x > 0);
+                DLIB_CASSERT(groups > 0 && filters.num_samples()%groups == 0);
+                DLIB_CASSERT(0 <= padding_y && padding_y < dilation_y*(filters.nr()-1)+1);
+                DLIB_CASSERT(0 <= padding_x && padding_x < dilation_x*(filters.nc()-1)+1);
                 last_stride_y = stride_y;
                 last_stride_x = stride_x;
                 last_padding_y = padding_y;
                 last_padding_x = padding_x;            
+                last_dilation_y = dilation_y;
+                last_dilation_x = dilation_x;
+                last_groups = groups;
             }
 
              void operator() (
@@ -677,6 +787,9 @@ namespace dlib
             long last_stride_x = 0;
             long last_padding_y = 0;
             long last_padding_x = 0;
+            long last_dilation_y = 1;
+            long last_dilation_x = 1;
+            long last_groups = 1;
         };
 
     // -----------------------------------------------------------------------------------
@@ -5,6 +5,8 @@
 #include "cuda_dlib.h"
 #include "cudnn_dlibapi.h"
 #include <math_constants.h>
+#include <cuda_fp16.h>
+#include <cuda_bf16.h>
 
 
 namespace dlib 
@@ -1160,6 +1162,255 @@ namespace dlib
                     momentum1, momentum2, params.device(), params_grad.device());
         }
 
+    // ----------------------------------------------------------------------------------------
+
+        __global__ void _cuda_compute_adamw_update(
+            size_t begin,
+            size_t end,
+            float* s,
+            float* m,
+            float* v,
+            const float alpha,
+            const float learning_rate,
+            const float weight_decay,
+            const float momentum1,
+            const float momentum2,
+            const float* params,
+            const float* params_grad
+        )
+        {
+            const float eps = 1e-8;
+            // The loop is equivalent to doing this:
+            //   m = momentum1*m + (1-momentum1)    *   params_grad;
+            //   v = momentum2*v + (1-momentum2)*squared(params_grad);
+            //   s = -alpha*m/(sqrt(v) + eps) - learning_rate*weight_decay*params;
+            for (auto i : grid_stride_range(begin, end))
+            {
+                float g = params_grad[i];
+                m[i] = momentum1*m[i] + (1-momentum1)*g;
+                v[i] = momentum2*v[i] + (1-momentum2)*g*g;
+                s[i] = -alpha*m[i]/(std::sqrt(v[i]) + eps) - learning_rate*weight_decay*params[i];
+            }
+        }
+
+        void compute_adamw_update (
+            size_t begin,
+            size_t end,
+            tensor& s,
+            tensor& m,
+            tensor& v,
+            const float t,
+            const float learning_rate,
+            const float weight_decay,
+            const float momentum1,
+            const float momentum2,
+            const tensor& params,
+            const tensor& params_grad
+        )
+        {
+            DLIB_CASSERT(s.size() == m.size() &&
+                         s.size() == v.size() &&
+                         s.size() == params.size() &&
+                         s.size() == params_grad.size());
+            DLIB_CASSERT(begin <= end && end <= params.size());
+            const float alpha = learning_rate*std::sqrt(1-std::pow(momentum2,t))/(1-std::pow(momentum1, t));
+
+            launch_kernel(_cuda_compute_adamw_update,max_jobs(end-begin),
+                    begin, end, s.device(), m.device(), v.device(), alpha, learning_rate, weight_decay,
+                    momentum1, momentum2, params.device(), params_grad.device());
+        }
+
+    // ----------------------------------------------------------------------------------------
+
+        __global__ void _cuda_compute_rmsprop_update(
+            size_t begin,
+            size_t end,
+            float* s,
+            float* v,
+            const float learning_rate,
+            const float weight_decay,
+            const float decay,
+            const float* params,
+            const float* params_grad
+        )
+        {
+            const float eps = 1e-8;
+            // The loop is equivalent to doing this:
+            //   v = decay*v + (1-decay)*squared(weight_decay*params + params_grad);
+            //   s = -learning_rate*(weight_decay*params + params_grad)/(sqrt(v) + eps);
+            for (auto i : grid_stride_range(begin, end))
+            {
+                float g = weight_decay*params[i] + params_grad[i];
+                v[i] = decay*v[i] + (1-decay)*g*g;
+                s[i] = -learning_rate*g/(std::sqrt(v[i]) + eps);
+            }
+        }
+
+        void compute_rmsprop_update (
+            size_t begin,
+            size_t end,
+            tensor& s,
+            tensor& v,
+            const float learning_rate,
+            const float weight_decay,
+            const float decay,
+            const tensor& params,
+            const tensor& params_grad
+        )
+        {
+            DLIB_CASSERT(s.size() == v.size() &&
+                         s.size() == params.size() &&
+                         s.size() == params_grad.size());
+            DLIB_CASSERT(begin <= end && end <= params.size());
+
+            launch_kernel(_cuda_compute_rmsprop_update,max_jobs(end-begin),
+                    begin, end, s.device(), v.device(), learning_rate, weight_decay, decay,
+                    params.device(), params_grad.device());
+        }
+
+    // ----------------------------------------------------------------------------------------
+
+        __global__ void _cuda_compute_adagrad_update(
+            size_t begin,
+            size_t end,
+            float* s,
+            float* v,
+            const float learning_rate,
+            const float weight_decay,
+            const float* params,
+            const float* params_grad
+        )
+        {
+            const float eps = 1e-8;
+            // The loop is equivalent to doing this:
+            //   v = v + squared(weight_decay*params + params_grad);
+            //   s = -learning_rate*(weight_decay*params + params_grad)/(sqrt(v) + eps);
+            for (auto i : grid_stride_range(begin, end))
+            {
+                float g = weight_decay*params[i] + params_grad[i];
+                v[i] += g*g;
+                s[i] = -learning_rate*g/(std::sqrt(v[i]) + eps);
+            }
+        }
+
+        void compute_adagrad_update (
+            size_t begin,
+            size_t end,
+            tensor& s,
+            tensor& v,
+            const float learning_rate,
+            const float weight_decay,
+            const tensor& params,
+            const tensor& params_grad
+        )
+        {
+            DLIB_CASSERT(s.size() == v.size() &&
+                         s.size() == params.size() &&
+                         s.size() == params_grad.size());
+            DLIB_CASSERT(begin <= end && end <= params.size());
+
+            launch_kernel(_cuda_compute_adagrad_update,max_jobs(end-begin),
+                    begin, end, s.device(), v.device(), learning_rate, weight_decay,
+                    params.device(), params_grad.device());
+        }
+
+    // ----------------------------------------------------------------------------------------
+
+        __global__ void _cuda_compute_lion_update(
+            size_t begin,
+            size_t end,
+            float* s,
+            float* m,
+            const float learning_rate,
+            const float weight_decay,
+            const float momentum1,
+            const float momentum2,
+            const float* params,
+            const float* params_grad
+        )
+        {
+            // The loop is equivalent to doing this:
+            //   s = -learning_rate*(sign(momentum1*m + (1-momentum1)*params_grad) + weight_decay*params);
+            //   m = momentum2*m + (1-momentum2)*params_grad;
+            for (auto i : grid_stride_range(begin, end))
+            {
+                float c = momentum1*m[i] + (1-momentum1)*params_grad[i];
+                float sign_c = (c > 0) ? 1.0f : ((c < 0) ? -1.0f : 0.0f);
+                s[i] = -learning_rate*(sign_c + weight_decay*params[i]);
+                m[i] = momentum2*m[i] + (1-momentum2)*params_grad[i];
+            }
+        }
+
+        void compute_lion_update (
+            size_t begin,
+            size_t end,
+            tensor& s,
+            tensor& m,
+            const float learning_rate,
+            const float weight_decay,
+            const float momentum1,
+            const float momentum2,
+            const tensor& params,
+            const tensor& params_grad
+        )
+        {
+            DLIB_CASSERT(s.size() == m.size() &&
+                         s.size() == params.size() &&
+                         s.size() == params_grad.size());
+            DLIB_CASSERT(begin <= end && end <= params.size());
+
+            launch_kernel(_cuda_compute_lion_update,max_jobs(end-begin),
+                    begin, end, s.device(), m.device(), learning_rate, weight_decay,
+                    momentum1, momentum2, params.device(), params_grad.device());
+        }
+
+    // ----------------------------------------------------------------------------------------
+
+        __global__ void _cuda_round_to_fp16(float* d, size_t n)
+        {
+            for (auto i : grid_stride_range(0, n))
+                d[i] = __half2float(__float2half_rn(d[i]));
+        }
+
+        __global__ void _cuda_round_to_bf16(float* d, size_t n)
+        {
+            for (auto i : grid_stride_range(0, n))
+                d[i] = __bfloat162float(__float2bfloat16_rn(d[i]));
+        }
+
+        void round_to_half_precision (
+            tensor& data,
+            half_format fmt
+        )
+        {
+            if (data.size() == 0)
+                return;
+            if (fmt == half_format::fp16)
+                launch_kernel(_cuda_round_to_fp16, max_jobs(data.size()), data.device(), data.size());
+            else
+                launch_kernel(_cuda_round_to_bf16, max_jobs(data.size()), data.device(), data.size());
+        }
+
+    // ----------------------------------------------------------------------------------------
+
+        __global__ void _cuda_clamp(float* d, size_t n, float lower, float upper)
+        {
+            for (auto i : grid_stride_range(0, n))
+                d[i] = ::min(upper, ::max(lower, d[i]));
+        }
+
+        void clamp (
+            tensor& data,
+            float lower,
+            float upper
+        )
+        {
+            DLIB_CASSERT(lower <= upper);
+            if (data.size() == 0)
+                return;
+            launch_kernel(_cuda_clamp, max_jobs(data.size()), data.device(), data.size(), lower, upper);
+        }
+
     // -----------------------------------------------------------------------------------
 
         __global__ void _cuda_affine_transform_conv(float* d, const float* s, size_t n, const float* A, const float* B, size_t bs, size_t ks)
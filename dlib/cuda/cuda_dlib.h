@@ -5,6 +5,7 @@
 
 
 #include "tensor.h"
+#include "half_tensor.h"
 #include "../geometry/rectangle.h"
 #include "../dnn/utilities.h"
 
@@ -329,6 +330,80 @@ namespace dlib
             const tensor& params_grad
         );
 
+    // ----------------------------------------------------------------------------------------
+
+        void compute_adamw_update (
+            size_t begin,
+            size_t end,
+            tensor& s,
+            tensor& m,
+            tensor& v,
+            const float t,
+            const float learning_rate,
+            const float weight_decay,
+            const float momentum1,
+            const float momentum2,
+            const tensor& params,
+            const tensor& params_grad
+        );
+
+    // ----------------------------------------------------------------------------------------
+
+        void compute_rmsprop_update (
+            size_t begin,
+            size_t end,
+            tensor& s,
+            tensor& v,
+            const float learning_rate,
+            const float weight_decay,
+            const float decay,
+            const tensor& params,
+            const tensor& params_grad
+        );
+
+    // ----------------------------------------------------------------------------------------
+
+        void compute_adagrad_update (
+            size_t begin,
+            size_t end,
+            tensor& s,
+            tensor& v,
+            const float learning_rate,
+            const float weight_decay,
+            const tensor& params,
+            const tensor& params_grad
+        );
+
+    // ----------------------------------------------------------------------------------------
+
+        void compute_lion_update (
+            size_t begin,
+            size_t end,
+            tensor& s,
+            tensor& m,
+            const float learning_rate,
+            const float weight_decay,
+            const float momentum1,
+            const float momentum2,
+            const tensor& params,
+            const tensor& params_grad
+        );
+
+    // ----------------------------------------------------------------------------------------
+
+        void round_to_half_precision (
+            tensor& data,
+            half_format fmt
+        );
+
+    // ----------------------------------------------------------------------------------------
+
+        void clamp (
+            tensor& data,
+            float lower,
+            float upper
+        );
+
     // -----------------------------------------------------------------------------------
 
         void assign_bias_gradient (
@@ -737,6 +737,9 @@ namespace dlib
             stride_x = 0;
             padding_y = 0;
             padding_x = 0;
+            dilation_y = 1;
+            dilation_x = 1;
+            groups = 1;
             data_num_samples = 0;
             data_k = 0;
             data_nr = 0;
@@ -793,12 +796,12 @@ namespace dlib
         {
             // Calling the cuDNN "find the best algorithm" functions is really slow.  So we keep a
             // cache that tells us what method was best for a particular configuration.
-            thread_local std::map<std::tuple<int,int,int,int,long,long>,
+            thread_local std::map<std::tuple<int,int,int,int,int,int,int,long,long>,
                                   std::tuple<int,int,int>> config_to_algo_cache;
 
             // If we have already found good algorithms for this setting then just pull them from
             // the cache.
-            const auto cache_key = std::make_tuple(stride_y, stride_x, padding_y, padding_x, filters_nr, filters_nc);
+            const auto cache_key = std::make_tuple(stride_y, stride_x, padding_y, padding_x, dilation_y, dilation_x, groups, filters_nr, filters_nc);
             const auto iter = config_to_algo_cache.find(cache_key);
             if (iter != config_to_algo_cache.end() && allow_cache_use_ == allow_cache_use::yes)
             {
@@ -975,10 +978,15 @@ namespace dlib
             int stride_y_,
             int stride_x_,
             int padding_y_,
-            int padding_x_
+            int padding_x_,
+            int dilation_y_,
+            int dilation_x_,
+            int groups_
         ) 
         {
-            DLIB_CASSERT(data.k() == filters.k());
+            DLIB_CASSERT(data.k() == filters.k()*groups_);
+            DLIB_CASSERT(dilation_y_ > 0 && dilation_x_ > 0);
+            DLIB_CASSERT(groups_ > 0 && filters.num_samples()%groups_ == 0);
 
             // if the last call to setup gave the same exact settings then don't do
             // anything.
@@ -990,6 +998,9 @@ namespace dlib
                 stride_x_ == stride_x &&
                 padding_y_ == padding_y && 
                 padding_x_ == padding_x &&
+                dilation_y_ == dilation_y &&
+                dilation_x_ == dilation_x &&
+                groups_ == groups &&
                 filters_num_samples == filters.num_samples() &&
                 filters_k == filters.k() &&
                 filters_nr == filters.nr() &&
@@ -1006,6 +1017,9 @@ namespace dlib
                 stride_x = stride_x_;
                 padding_y = padding_y_;
                 padding_x = padding_x_;
+                dilation_y = dilation_y_;
+                dilation_x = dilation_x_;
+                groups = groups_;
                 data_num_samples = data.num_samples();
                 data_k = data.k();
                 data_nr = data.nr();
@@ -1031,10 +1045,12 @@ namespace dlib
                         padding_x, // horizontal padding
                         stride_y,
                         stride_x,
-                        1, 1, // must be 1,1
+                        dilation_y,
+                        dilation_x,
                         CUDNN_CROSS_CORRELATION,
                         CUDNN_DATA_FLOAT)); // could also be CUDNN_CONVOLUTION
 #else
+                DLIB_CASSERT(dilation_y == 1 && dilation_x == 1, "Dilated convolutions require cuDNN 6 or newer.");
                 CHECK_CUDNN(cudnnSetConvolution2dDescriptor((cudnnConvolutionDescriptor_t)conv_handle,
                         padding_y, // vertical padding
                         padding_x, // horizontal padding
@@ -1043,6 +1059,11 @@ namespace dlib
                         1, 1, // must be 1,1
                         CUDNN_CROSS_CORRELATION)); // could also be CUDNN_CONVOLUTION
 #endif
+#if CUDNN_MAJOR >= 7
+                CHECK_CUDNN(cudnnSetConvolutionGroupCount((cudnnConvolutionDescriptor_t)conv_handle, groups));
+#else
+                DLIB_CASSERT(groups == 1, "Grouped convolutions require cuDNN 7 or newer.");
+#endif
 
                 CHECK_CUDNN(cudnnGetConvolution2dForwardOutputDim(
                         (const cudnnConvolutionDescriptor_t)conv_handle,
@@ -1105,15 +1126,15 @@ namespace dlib
         {
             DLIB_CASSERT(is_same_object(output,data) == false);
             DLIB_CASSERT(is_same_object(output,filters) == false);
-            DLIB_CASSERT(filters.k() == data.k());
+            DLIB_CASSERT(filters.k()*groups == data.k());
             DLIB_CASSERT(stride_y > 0 && stride_x > 0, "You must call setup() before calling this function");
-            DLIB_CASSERT(filters.nc() <= data.nc() + 2*padding_x,
+            DLIB_CASSERT(dilation_x*(filters.nc()-1)+1 <= data.nc() + 2*padding_x,
                 "Filter windows must be small enough to fit into the padded image."
                 << "\n\t filters.nc(): " << filters.nc() 
                 << "\n\t data.nc():  " << data.nc() 
                 << "\n\t padding_x: " << padding_x 
                 );
-            DLIB_CASSERT(filters.nr() <= data.nr() + 2*padding_y,
+            DLIB_CASSERT(dilation_y*(filters.nr()-1)+1 <= data.nr() + 2*padding_y,
                 "Filter windows must be small enough to fit into the padded image."
                 << "\n\t filters.nr(): " << filters.nr() 
                 << "\n\t data.nr():  " << data.nr() 
@@ -1123,8 +1144,8 @@ namespace dlib
 
             DLIB_CASSERT(output.num_samples() == data.num_samples(),out_num_samples << "  " << data.num_samples());
             DLIB_CASSERT(output.k() == filters.num_samples());
-            DLIB_CASSERT(output.nr() == 1+(data.nr()+2*padding_y-filters.nr())/stride_y);
-            DLIB_CASSERT(output.nc() == 1+(data.nc()+2*padding_x-filters.nc())/stride_x);
+            DLIB_CASSERT(output.nr() == 1+(data.nr()+2*padding_y-(dilation_y*(filters.nr()-1)+1))/stride_y);
+            DLIB_CASSERT(output.nc() == 1+(data.nc()+2*padding_x-(dilation_x*(filters.nc()-1)+1))/stride_x);
 
 
 
@@ -1195,15 +1216,15 @@ namespace dlib
 
             DLIB_CASSERT(is_same_object(output,data) == false);
             DLIB_CASSERT(is_same_object(output,filters) == false);
-            DLIB_CASSERT(filters.k() == data.k());
+            DLIB_CASSERT(filters.k()*groups == data.k());
             DLIB_CASSERT(stride_y > 0 && stride_x > 0, "You must call setup() before calling this function");
-            DLIB_CASSERT(filters.nc() <= data.nc() + 2*padding_x,
+            DLIB_CASSERT(dilation_x*(filters.nc()-1)+1 <= data.nc() + 2*padding_x,
                 "Filter windows must be small enough to fit into the padded image."
                 << "\n\t filters.nc(): " << filters.nc()
                 << "\n\t data.nc():  " << data.nc()
                 << "\n\t padding_x: " << padding_x
                 );
-            DLIB_CASSERT(filters.nr() <= data.nr() + 2*padding_y,
+            DLIB_CASSERT(dilation_y*(filters.nr()-1)+1 <= data.nr() + 2*padding_y,
                 "Filter windows must be small enough to fit into the padded image."
                 << "\n\t filters.nr(): " << filters.nr()
                 << "\n\t data.nr():  " << data.nr()
@@ -1213,8 +1234,8 @@ namespace dlib
 
             DLIB_CASSERT(output.num_samples() == data.num_samples(),out_num_samples << "  " << data.num_samples());
             DLIB_CASSERT(output.k() == filters.num_samples());
-            DLIB_CASSERT(output.nr() == 1+(data.nr()+2*padding_y-filters.nr())/stride_y);
-            DLIB_CASSERT(output.nc() == 1+(data.nc()+2*padding_x-filters.nc())/stride_x);
+            DLIB_CASSERT(output.nr() == 1+(data.nr()+2*padding_y-(dilation_y*(filters.nr()-1)+1))/stride_y);
+            DLIB_CASSERT(output.nc() == 1+(data.nc()+2*padding_x-(dilation_x*(filters.nc()-1)+1))/stride_x);
             DLIB_CASSERT(filters.num_samples() == biases.k());
 
 
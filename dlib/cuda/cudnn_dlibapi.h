@@ -223,7 +223,10 @@ namespace dlib
                 int stride_y,
                 int stride_x,
                 int padding_y,
-                int padding_x
+                int padding_x,
+                int dilation_y = 1,
+                int dilation_x = 1,
+                int groups = 1
             );
 
            void setup(
@@ -243,6 +246,9 @@ namespace dlib
             int stride_x;
             int padding_y;
             int padding_x;
+            int dilation_y;
+            int dilation_x;
+            int groups;
             long data_num_samples, data_k, data_nr, data_nc;
             long filters_num_samples, filters_k, filters_nr, filters_nc;
 
@@ -0,0 +1,279 @@
+// Copyright (C) 2026  Davis E. King (davis@dlib.net)
+// License: Boost Software License   See LICENSE.txt for the full license.
+#ifndef DLIB_DNn_HALF_TENSOR_H_
+#define DLIB_DNn_HALF_TENSOR_H_
+
+#include "half_tensor_abstract.h"
+#include "tensor.h"
+#include "../serialize.h"
+#include <cmath>
+#include <cstdint>
+#include <cstring>
+#include <vector>
+
+namespace dlib
+{
+
+// ----------------------------------------------------------------------------------------
+
+    enum class half_format
+    {
+        fp16,
+        bf16
+    };
+
+    inline std::ostream& operator<< (std::ostream& out, half_format fmt)
+    {
+        switch (fmt)
+        {
+            case half_format::fp16: out << "fp16"; break;
+            case half_format::bf16: out << "bf16"; break;
+        }
+        return out;
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    inline uint16_t float_to_fp16 (
+        float value
+    )
+    {
+        uint32_t x;
+        std::memcpy(&x, &value, sizeof(x));
+        const uint16_t sign = (x >> 16) & 0x8000;
+        const uint32_t mag = x & 0x7fffffff;
+
+        // inf and nan
+        if (mag >= 0x7f800000)
+            return sign | 0x7c00 | ((mag > 0x7f800000) ? 0x200 : 0);
+        // too big for a half, so it becomes inf
+        if (mag >= 0x47800000)
+            return sign | 0x7c00;
+        // Below the smallest normal half.  In this range the half is just an integer
+        // count of 2^-24 units, so we can let the FPU do the rounding for us.
+        if (mag < 0x38800000)
+        {
+            float f;
+            std::memcpy(&f, &mag, sizeof(f));
+            return sign | static_cast<uint16_t>(std::nearbyint(f*16777216.0f));
+        }
+
+        // Rebias the exponent and round the mantissa to nearest even.  A carry out of
+        // the mantissa correctly bumps the exponent, all the way up to inf.
+        uint32_t h = ((((mag >> 23) - 127 + 15)) << 10) | ((mag & 0x7fffff) >> 13);
+        const uint32_t rem = mag & 0x1fff;
+        if (rem > 0x1000 || (rem == 0x1000 && (h & 1)))
+            ++h;
+        return sign | static_cast<uint16_t>(h);
+    }
+
+    inline float fp16_to_float (
+        uint16_t value
+    )
+    {
+        const uint32_t sign = static_cast<uint32_t>(value & 0x8000) << 16;
+        const uint32_t exp = (value >> 10) & 0x1f;
+        const uint32_t mant = value & 0x3ff;
+        uint32_t x;
+        if (exp == 0)
+        {
+            // zero or subnormal
+            const float f = mant/16777216.0f;
+            std::memcpy(&x, &f, sizeof(x));
+            x |= sign;
+        }
+        else if (exp == 31)
+        {
+            x = sign | 0x7f800000 | (mant << 13);
+        }
+        else
+        {
+            x = sign | ((exp - 15 + 127) << 23) | (mant << 13);
+        }
+        float f;
+        std::memcpy(&f, &x, sizeof(f));
+        return f;
+    }
+
+    inline uint16_t float_to_bf16 (
+        float value
+    )
+    {
+        uint32_t x;
+        std::memcpy(&x, &value, sizeof(x));
+        // keep nans as nans, even if all the mantissa bits would be rounded away
+        if ((x & 0x7fffffff) > 0x7f800000)
+            return static_cast<uint16_t>((x >> 16) | 0x40);
+        // round to nearest even
+        x += 0x7fff + ((x >> 16) & 1);
+        return static_cast<uint16_t>(x >> 16);
+    }
+
+    inline float bf16_to_float (
+        uint16_t value
+    )
+    {
+        const uint32_t x = static_cast<uint32_t>(value) << 16;
+        float f;
+        std::memcpy(&f, &x, sizeof(f));
+        return f;
+    }
+
+    inline uint16_t float_to_half (
+        float value,
+        half_format fmt
+    )
+    {
+        return (fmt == half_format::fp16) ? float_to_fp16(value) : float_to_bf16(value);
+    }
+
+    inline float half_to_float (
+        uint16_t value,
+        half_format fmt
+    )
+    {
+        return (fmt == half_format::fp16) ? fp16_to_float(value) : bf16_to_float(value);
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    class half_tensor
+    {
+    public:
+
+        half_tensor(
+        ) = default;
+
+        half_tensor(
+            const tensor& item,
+            half_format fmt
+        )
+        {
+            assign(item, fmt);
+        }
+
+        void assign (
+            const tensor& item,
+            half_format fmt
+        )
+        {
+            m_n = item.num_samples();
+            m_k = item.k();
+            m_nr = item.nr();
+            m_nc = item.nc();
+            format = fmt;
+            data.resize(item.size());
+            const float* src = item.host();
+            for (size_t i = 0; i < data.size(); ++i)
+                data[i] = float_to_half(src[i], format);
+        }
+
+        void copy_to (
+            tensor& dest
+        ) const
+        {
+            DLIB_CASSERT(dest.num_samples() == num_samples() &&
+                         dest.k() == k() &&
+                         dest.nr() == nr() &&
+                         dest.nc() == nc());
+            float* d = dest.host_write_only();
+            for (size_t i = 0; i < data.size(); ++i)
+                d[i] = half_to_float(data[i], format);
+        }
+
+        void copy_to (
+            resizable_tensor& dest
+        ) const
+        {
+            dest.set_size(num_samples(), k(), nr(), nc());
+            copy_to(static_cast<tensor&>(dest));
+        }
+
+        long long num_samples(
+        ) const { return m_n; }
+
+        long long k(
+        ) const { return m_k; }
+
+        long long nr(
+        ) const { return m_nr; }
+
+        long long nc(
+        ) const { return m_nc; }
+
+        size_t size(
+        ) const { return data.size(); }
+
+        half_format get_format(
+        ) const { return format; }
+
+        const uint16_t* host(
+        ) const { return data.data(); }
+
+        uint16_t* host(
+        ) { return data.data(); }
+
+        friend void serialize(const half_tensor& item, std::ostream& out)
+        {
+            serialize("half_tensor", out);
+            serialize(item.m_n, out);
+            serialize(item.m_k, out);
+            serialize(item.m_nr, out);
+            serialize(item.m_nc, out);
+            serialize(static_cast<int>(item.format), out);
+            // Write the raw 16 bit values in little endian order.  This keeps the file
+            // at 2 bytes per value, which is the whole point of this object.
+            std::vector<char> buf(item.data.size()*2);
+            for (size_t i = 0; i < item.data.size(); ++i)
+            {
+                buf[2*i]   = static_cast<char>(item.data[i] & 0xff);
+                buf[2*i+1] = static_cast<char>(item.data[i] >> 8);
+            }
+            out.write(buf.data(), buf.size());
+            if (!out)
+                throw serialization_error("Error serializing dlib::half_tensor.");
+        }
+
+        friend void deserialize(half_tensor& item, std::istream& in)
+        {
+            std::string version;
+            deserialize(version, in);
+            if (version != "half_tensor")
+                throw serialization_error("Unexpected version found while deserializing dlib::half_tensor.");
+            deserialize(item.m_n, in);
+            deserialize(item.m_k, in);
+            deserialize(item.m_nr, in);
+            deserialize(item.m_nc, in);
+            int fmt;
+            deserialize(fmt, in);
+            if (fmt != static_cast<int>(half_format::fp16) && fmt != static_cast<int>(half_format::bf16))
+                throw serialization_error("Invalid format found while deserializing dlib::half_tensor.");
+            item.format = static_cast<half_format>(fmt);
+            item.data.resize(item.m_n*item.m_k*item.m_nr*item.m_nc);
+            std::vector<char> buf(item.data.size()*2);
+            in.read(buf.data(), buf.size());
+            if (!in)
+                throw serialization_error("Error deserializing dlib::half_tensor.");
+            for (size_t i = 0; i < item.data.size(); ++i)
+            {
+                item.data[i] = static_cast<uint16_t>(static_cast<unsigned char>(buf[2*i])) |
+                               static_cast<uint16_t>(static_cast<unsigned char>(buf[2*i+1]) << 8);
+            }
+        }
+
+    private:
+
+        long long m_n = 0;
+        long long m_k = 0;
+        long long m_nr = 0;
+        long long m_nc = 0;
+        half_format format = half_format::fp16;
+        std::vector<uint16_t> data;
+    };
+
+// ----------------------------------------------------------------------------------------
+
+}
+
+#endif // DLIB_DNn_HALF_TENSOR_H_
+
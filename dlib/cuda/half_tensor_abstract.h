@@ -0,0 +1,191 @@
+// Copyright (C) 2026  Davis E. King (davis@dlib.net)
+// License: Boost Software License   See LICENSE.txt for the full license.
+#undef DLIB_DNn_HALF_TENSOR_ABSTRACT_H_
+#ifdef DLIB_DNn_HALF_TENSOR_ABSTRACT_H_
+
+#include "tensor_abstract.h"
+#include <cstdint>
+
+namespace dlib
+{
+
+// ----------------------------------------------------------------------------------------
+
+    enum class half_format
+    {
+        fp16, // IEEE 754 binary16: 1 sign bit, 5 exponent bits, 10 mantissa bits
+        bf16  // bfloat16: 1 sign bit, 8 exponent bits, 7 mantissa bits
+    };
+
+    std::ostream& operator<< (std::ostream& out, half_format fmt);
+    /*!
+        ensures
+            - prints "fp16" or "bf16" to out.
+    !*/
+
+// ----------------------------------------------------------------------------------------
+
+    uint16_t float_to_fp16 (
+        float value
+    );
+    /*!
+        ensures
+            - returns the bit pattern of the IEEE half precision number nearest to value,
+              with ties rounded to even.  Values too large for a half become +/-inf,
+              values too small become +/-0 or a subnormal half, and nans stay nans.
+    !*/
+
+    float fp16_to_float (
+        uint16_t value
+    );
+    /*!
+        ensures
+            - interprets value as the bit pattern of an IEEE half precision number and
+              returns it as a float.  This conversion is exact.
+    !*/
+
+    uint16_t float_to_bf16 (
+        float value
+    );
+    /*!
+        ensures
+            - returns the bit pattern of the bfloat16 number nearest to value, with ties
+              rounded to even.  Nans stay nans.
+    !*/
+
+    float bf16_to_float (
+        uint16_t value
+    );
+    /*!
+        ensures
+            - interprets value as the bit pattern of a bfloat16 number and returns it as
+              a float.  This conversion is exact.
+    !*/
+
+    uint16_t float_to_half (
+        float value,
+        half_format fmt
+    );
+    /*!
+        ensures
+            - returns float_to_fp16(value) if fmt == half_format::fp16 and
+              float_to_bf16(value) otherwise.
+    !*/
+
+    float half_to_float (
+        uint16_t value,
+        half_format fmt
+    );
+    /*!
+        ensures
+            - returns fp16_to_float(value) if fmt == half_format::fp16 and
+              bf16_to_float(value) otherwise.
+    !*/
+
+// ----------------------------------------------------------------------------------------
+
+    class half_tensor
+    {
+        /*!
+            WHAT THIS OBJECT REPRESENTS
+                This object holds a 4D array of 16 bit floating point values, using either
+                the fp16 or bf16 format.  It has the same shape conventions as dlib::tensor
+                but takes half the memory, which makes it useful for storing and shipping
+                network parameters.  All the arithmetic in dlib is still done in 32 bit
+                floats, so a half_tensor is converted to a regular tensor before use.
+
+                The data is stored in host memory.  The conversions are done with the CPU
+                reference functions above, so they give identical results on every
+                platform.
+        !*/
+
+    public:
+
+        half_tensor(
+        );
+        /*!
+            ensures
+                - #size() == 0
+                - #num_samples() == 0
+                - #k() == 0
+                - #nr() == 0
+                - #nc() == 0
+                - #get_format() == half_format::fp16
+        !*/
+
+        half_tensor(
+            const tensor& item,
+            half_format fmt
+        );
+        /*!
+            ensures
+                - performs assign(item, fmt)
+        !*/
+
+        void assign (
+            const tensor& item,
+            half_format fmt
+        );
+        /*!
+            ensures
+                - #get_format() == fmt
+                - #num_samples() == item.num_samples()
+                - #k() == item.k()
+                - #nr() == item.nr()
+                - #nc() == item.nc()
+                - for all valid i:
+                    - #host()[i] == float_to_half(item.host()[i], fmt)
+        !*/
+
+        void copy_to (
+            tensor& dest
+        ) const;
+        /*!
+            requires
+                - dest.num_samples() == num_samples()
+                - dest.k() == k()
+                - dest.nr() == nr()
+                - dest.nc() == nc()
+            ensures
+                - for all valid i:
+                    - dest.host()[i] == half_to_float(host()[i], get_format())
+        !*/
+
+        void copy_to (
+            resizable_tensor& dest
+        ) const;
+        /*!
+            ensures
+                - resizes dest to the shape of *this and then copies the values into it
+                  as described above.
+        !*/
+
+        long long num_samples() const;
+        long long k() const;
+        long long nr() const;
+        long long nc() const;
+        size_t size() const;
+        half_format get_format() const;
+
+        const uint16_t* host() const;
+        uint16_t* host();
+        /*!
+            ensures
+                - returns a pointer to the raw 16 bit values.  There are size() of them,
+                  laid out in the same order as the floats in a dlib::tensor.
+        !*/
+    };
+
+    void serialize(const half_tensor& item, std::ostream& out);
+    void deserialize(half_tensor& item, std::istream& in);
+    /*!
+        provides serialization support.  Each value takes exactly 2 bytes in the
+        serialized data.
+    !*/
+
+// ----------------------------------------------------------------------------------------
+
+}
+
+#endif // DLIB_DNn_HALF_TENSOR_ABSTRACT_H_
+
@@ -0,0 +1,205 @@
+// Copyright (C) 2026  Davis E. King (davis@dlib.net)
+// License: Boost Software License   See LICENSE.txt for the full license.
+#ifndef DLIB_DNn_INT8_TENSOR_H_
+#define DLIB_DNn_INT8_TENSOR_H_
+
+#include "int8_tensor_abstract.h"
+#include "tensor.h"
+#include "../serialize.h"
+#include <algorithm>
+#include <cmath>
+#include <cstdint>
+#include <vector>
+
+namespace dlib
+{
+
+// ----------------------------------------------------------------------------------------
+
+    inline int8_t float_to_int8 (
+        float value,
+        float scale
+    )
+    {
+        const float q = std::round(value/scale);
+        return static_cast<int8_t>(std::max(-127.0f, std::min(127.0f, q)));
+    }
+
+    inline float int8_scale_for_range (
+        float max_abs_value
+    )
+    {
+        // A range of 0 means everything is 0, so any scale works.  Using 1 avoids
+        // dividing by 0.
+        return (max_abs_value > 0) ? max_abs_value/127 : 1;
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    class int8_tensor
+    {
+    public:
+
+        int8_tensor(
+        ) = default;
+
+        explicit int8_tensor(
+            const tensor& item
+        )
+        {
+            assign(item);
+        }
+
+        void assign (
+            const tensor& item
+        )
+        {
+            set_shape(item);
+            const float* src = item.host();
+            const size_t sample_size = item.num_samples() == 0 ? 0 : item.size()/item.num_samples();
+            for (long long n = 0; n < m_n; ++n)
+            {
+                const float* s = src + n*sample_size;
+                float max_abs = 0;
+                for (size_t i = 0; i < sample_size; ++i)
+                    max_abs = std::max(max_abs, std::abs(s[i]));
+                scales[n] = int8_scale_for_range(max_abs);
+                for (size_t i = 0; i < sample_size; ++i)
+                    data[n*sample_size + i] = float_to_int8(s[i], scales[n]);
+            }
+        }
+
+        void assign (
+            const tensor& item,
+            float scale
+        )
+        {
+            DLIB_CASSERT(scale > 0);
+            set_shape(item);
+            std::fill(scales.begin(), scales.end(), scale);
+            const float* src = item.host();
+            for (size_t i = 0; i < data.size(); ++i)
+                data[i] = float_to_int8(src[i], scale);
+        }
+
+        void copy_to (
+            tensor& dest
+        ) const
+        {
+            DLIB_CASSERT(dest.num_samples() == num_samples() &&
+                         dest.k() == k() &&
+                         dest.nr() == nr() &&
+                         dest.nc() == nc());
+            float* d = dest.host_write_only();
+            const size_t sample_size = m_n == 0 ? 0 : data.size()/m_n;
+            for (size_t i = 0; i < data.size(); ++i)
+                d[i] = data[i]*scales[i/sample_size];
+        }
+
+        void copy_to (
+            resizable_tensor& dest
+        ) const
+        {
+            dest.set_size(num_samples(), k(), nr(), nc());
+            copy_to(static_cast<tensor&>(dest));
+        }
+
+        long long num_samples(
+        ) const { return m_n; }
+
+        long long k(
+        ) const { return m_k; }
+
+        long long nr(
+        ) const { return m_nr; }
+
+        long long nc(
+        ) const { return m_nc; }
+
+        size_t size(
+        ) const { return data.size(); }
+
+        float get_scale(
+            long long sample
+        ) const
+        {
+            DLIB_ASSERT(0 <= sample && sample < num_samples());
+            return scales[sample];
+        }
+
+        const int8_t* host(
+        ) const { return data.data(); }
+
+        int8_t* host(
+        ) { return data.data(); }
+
+        void clear(
+        )
+        {
+            m_n = m_k = m_nr = m_nc = 0;
+            data.clear();
+            scales.clear();
+        }
+
+        friend void serialize(const int8_tensor& item, std::ostream& out)
+        {
+            serialize("int8_tensor", out);
+            serialize(item.m_n, out);
+            serialize(item.m_k, out);
+            serialize(item.m_nr, out);
+            serialize(item.m_nc, out);
+            for (auto s : item.scales)
+                serialize(s, out);
+            // One byte per value, written as is.
+            out.write(reinterpret_cast<const char*>(item.data.data()), item.data.size());
+            if (!out)
+                throw serialization_error("Error serializing dlib::int8_tensor.");
+        }
+
+        friend void deserialize(int8_tensor& item, std::istream& in)
+        {
+            std::string version;
+            deserialize(version, in);
+            if (version != "int8_tensor")
+                throw serialization_error("Unexpected version found while deserializing dlib::int8_tensor.");
+            deserialize(item.m_n, in);
+            deserialize(item.m_k, in);
+            deserialize(item.m_nr, in);
+            deserialize(item.m_nc, in);
+            item.scales.resize(item.m_n);
+            for (auto& s : item.scales)
+                deserialize(s, in);
+            item.data.resize(item.m_n*item.m_k*item.m_nr*item.m_nc);
+            in.read(reinterpret_cast<char*>(item.data.data()), item.data.size());
+            if (!in)
+                throw serialization_error("Error deserializing dlib::int8_tensor.");
+        }
+
+    private:
+
+        void set_shape (
+            const tensor& item
+        )
+        {
+            m_n = item.num_samples();
+            m_k = item.k();
+            m_nr = item.nr();
+            m_nc = item.nc();
+            data.resize(item.size());
+            scales.resize(m_n);
+        }
+
+        long long m_n = 0;
+        long long m_k = 0;
+        long long m_nr = 0;
+        long long m_nc = 0;
+        std::vector<float> scales;
+        std::vector<int8_t> data;
+    };
+
+// ----------------------------------------------------------------------------------------
+
+}
+
+#endif // DLIB_DNn_INT8_TENSOR_H_
+
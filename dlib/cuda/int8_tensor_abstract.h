@@ -0,0 +1,177 @@
+// Copyright (C) 2026  Davis E. King (davis@dlib.net)
+// License: Boost Software License   See LICENSE.txt for the full license.
+#undef DLIB_DNn_INT8_TENSOR_ABSTRACT_H_
+#ifdef DLIB_DNn_INT8_TENSOR_ABSTRACT_H_
+
+#include "tensor_abstract.h"
+#include <cstdint>
+
+namespace dlib
+{
+
+// ----------------------------------------------------------------------------------------
+
+    int8_t float_to_int8 (
+        float value,
+        float scale
+    );
+    /*!
+        requires
+            - scale > 0
+        ensures
+            - returns value/scale rounded to the nearest integer and clamped to the range
+              [-127, 127].  This is symmetric quantization, so 0 is always represented
+              exactly and -128 is never used.
+    !*/
+
+    float int8_scale_for_range (
+        float max_abs_value
+    );
+    /*!
+        requires
+            - max_abs_value >= 0
+        ensures
+            - returns the scale that maps the range [-max_abs_value, max_abs_value] onto
+              [-127, 127].  That is, returns max_abs_value/127, or 1 if max_abs_value == 0.
+    !*/
+
+// ----------------------------------------------------------------------------------------
+
+    class int8_tensor
+    {
+        /*!
+            WHAT THIS OBJECT REPRESENTS
+                This object holds a 4D array of 8 bit integers that approximate a tensor
+                of floats.  Each sample (i.e. each slice along the num_samples()
+                dimension) has its own scale, and the float value of an element is its
+                integer value times the scale of its sample.  So if you store the filters
+                of a convolution in an int8_tensor each filter gets its own scale, which is
+                what people usually mean by per-channel quantization.
+
+                The data is stored in host memory and takes a quarter of the memory of
+                the equivalent tensor.
+        !*/
+
+    public:
+
+        int8_tensor(
+        );
+        /*!
+            ensures
+                - #size() == 0
+                - #num_samples() == 0
+                - #k() == 0
+                - #nr() == 0
+                - #nc() == 0
+        !*/
+
+        explicit int8_tensor(
+            const tensor& item
+        );
+        /*!
+            ensures
+                - performs assign(item)
+        !*/
+
+        void assign (
+            const tensor& item
+        );
+        /*!
+            ensures
+                - #num_samples() == item.num_samples()
+                - #k() == item.k()
+                - #nr() == item.nr()
+                - #nc() == item.nc()
+                - Quantizes each sample of item separately.  That is, for all valid n:
+                    - #get_scale(n) == int8_scale_for_range(M), where M is the largest
+                      absolute value in the n-th sample of item.
+                    - each element x of the n-th sample is stored as
+                      float_to_int8(x, #get_scale(n)).
+        !*/
+
+        void assign (
+            const tensor& item,
+            float scale
+        );
+        /*!
+            requires
+                - scale > 0
+            ensures
+                - #num_samples() == item.num_samples()
+                - #k() == item.k()
+                - #nr() == item.nr()
+                - #nc() == item.nc()
+                - for all valid n: #get_scale(n) == scale
+                - for all valid i: #host()[i] == float_to_int8(item.host()[i], scale)
+        !*/
+
+        void copy_to (
+            tensor& dest
+        ) const;
+        /*!
+            requires
+                - dest.num_samples() == num_samples()
+                - dest.k() == k()
+                - dest.nr() == nr()
+                - dest.nc() == nc()
+            ensures
+                - Converts *this back to floats and stores them in dest.  That is, each
+                  element of dest is set to its integer value in *this times the scale of
+                  the sample it's in.
+        !*/
+
+        void copy_to (
+            resizable_tensor& dest
+        ) const;
+        /*!
+            ensures
+                - resizes dest to the shape of *this and then copies the values into it
+                  as described above.
+        !*/
+
+        long long num_samples() const;
+        long long k() const;
+        long long nr() const;
+        long long nc() const;
+        size_t size() const;
+
+        float get_scale(
+            long long sample
+        ) const;
+        /*!
+            requires
+                - 0 <= sample < num_samples()
+            ensures
+                - returns the scale of the given sample.
+        !*/
+
+        const int8_t* host() const;
+        int8_t* host();
+        /*!
+            ensures
+                - returns a pointer to the raw 8 bit values.  There are size() of them,
+                  laid out in the same order as the floats in a dlib::tensor.
+        !*/
+
+        void clear(
+        );
+        /*!
+            ensures
+                - #*this has its initial value.
+        !*/
+    };
+
+    void serialize(const int8_tensor& item, std::ostream& out);
+    void deserialize(int8_tensor& item, std::istream& in);
+    /*!
+        provides serialization support.  Each value takes exactly 1 byte in the
+        serialized data.
+    !*/
+
+// ----------------------------------------------------------------------------------------
+
+}
+
+#endif // DLIB_DNn_INT8_TENSOR_ABSTRACT_H_
+
+
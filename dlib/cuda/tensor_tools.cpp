@@ -5,6 +5,7 @@
 
 #include "tensor_tools.h"
 #include "../string.h"
+#include "../threads.h"
 #include <atomic>
 
 namespace dlib
@@ -17,6 +18,14 @@ namespace dlib
             static std::atomic<bool> var(true);
             return var;
         }
+
+        std::atomic<size_t>& dnn_cpu_threads (
+        )
+        {
+            // 0 means use every thread in the default_thread_pool().
+            static std::atomic<size_t> var(0);
+            return var;
+        }
     }
 
     bool dnn_prefer_fastest_algorithms (
@@ -36,6 +45,23 @@ namespace dlib
     {
         dnn_prefer_fastest_algo() = false;
     }
+
+    size_t dnn_cpu_num_threads(
+    )
+    {
+        const size_t num_threads = dnn_cpu_threads();
+        if (num_threads != 0)
+            return num_threads;
+        return std::max<size_t>(1, default_thread_pool().num_threads_in_pool());
+    }
+
+    void set_dnn_cpu_num_threads(
+        size_t num_threads
+    )
+    {
+        DLIB_CASSERT(num_threads > 0);
+        dnn_cpu_threads() = num_threads;
+    }
 }
 
 namespace dlib { namespace tt
@@ -538,6 +564,156 @@ namespace dlib { namespace tt
 #endif
     }
 
+// ----------------------------------------------------------------------------------------
+
+    void compute_adamw_update (
+        size_t begin,
+        size_t end,
+        tensor& s,
+        tensor& m,
+        tensor& v,
+        const float t,
+        const float learning_rate,
+        const float weight_decay,
+        const float momentum1,
+        const float momentum2,
+        const tensor& params,
+        const tensor& params_grad
+    )
+    {
+#ifdef DLIB_USE_CUDA
+        cuda::compute_adamw_update(begin, end, s, m, v, t, learning_rate, weight_decay, momentum1, momentum2, params, params_grad);
+#else
+        cpu::compute_adamw_update(begin, end, s, m, v, t, learning_rate, weight_decay, momentum1, momentum2, params, params_grad);
+#endif
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    void compute_rmsprop_update (
+        size_t begin,
+        size_t end,
+        tensor& s,
+        tensor& v,
+        const float learning_rate,
+        const float weight_decay,
+        const float decay,
+        const tensor& params,
+        const tensor& params_grad
+    )
+    {
+#ifdef DLIB_USE_CUDA
+        cuda::compute_rmsprop_update(begin, end, s, v, learning_rate, weight_decay, decay, params, params_grad);
+#else
+        cpu::compute_rmsprop_update(begin, end, s, v, learning_rate, weight_decay, decay, params, params_grad);
+#endif
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    void compute_adagrad_update (
+        size_t begin,
+        size_t end,
+        tensor& s,
+        tensor& v,
+        const float learning_rate,
+        const float weight_decay,
+        const tensor& params,
+        const tensor& params_grad
+    )
+    {
+#ifdef DLIB_USE_CUDA
+        cuda::compute_adagrad_update(begin, end, s, v, learning_rate, weight_decay, params, params_grad);
+#else
+        cpu::compute_adagrad_update(begin, end, s, v, learning_rate, weight_decay, params, params_grad);
+#endif
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    void compute_lion_update (
+        size_t begin,
+        size_t end,
+        tensor& s,
+        tensor& m,
+        const float learning_rate,
+        const float weight_decay,
+        const float momentum1,
+        const float momentum2,
+        const tensor& params,
+        const tensor& params_grad
+    )
+    {
+#ifdef DLIB_USE_CUDA
+        cuda::compute_lion_update(begin, end, s, m, learning_rate, weight_decay, momentum1, momentum2, params, params_grad);
+#else
+        cpu::compute_lion_update(begin, end, s, m, learning_rate, weight_decay, momentum1, momentum2, params, params_grad);
+#endif
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    void round_to_half_precision (
+        tensor& data,
+        half_format fmt
+    )
+    {
+#ifdef DLIB_USE_CUDA
+        cuda::round_to_half_precision(data, fmt);
+#else
+        cpu::round_to_half_precision(data, fmt);
+#endif
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    void clamp (
+        tensor& data,
+        float lower,
+        float upper
+    )
+    {
+#ifdef DLIB_USE_CUDA
+        cuda::clamp(data, lower, upper);
+#else
+        cpu::clamp(data, lower, upper);
+#endif
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    void int8_fc (
+        resizable_tensor& output,
+        const tensor& input,
+        float input_scale,
+        const int8_tensor& weights,
+        const tensor& biases
+    )
+    {
+        // There are only CPU versions of the int8 kernels, even in CUDA builds.
+        cpu::int8_fc(output, input, input_scale, weights, biases);
+    }
+
+    void int8_conv (
+        resizable_tensor& output,
+        const tensor& input,
+        float input_scale,
+        const int8_tensor& filters,
+        const tensor& biases,
+        int stride_y,
+        int stride_x,
+        int padding_y,
+        int padding_x,
+        int dilation_y,
+        int dilation_x,
+        long groups,
+        bool use_relu
+    )
+    {
+        cpu::int8_conv(output, input, input_scale, filters, biases, stride_y, stride_x,
+            padding_y, padding_x, dilation_y, dilation_x, groups, use_relu);
+    }
+
 // ----------------------------------------------------------------------------------------
 
     void batch_normalize_inference (
@@ -20,6 +20,8 @@ namespace dlib
     bool dnn_prefer_fastest_algorithms();
     void set_dnn_prefer_fastest_algorithms();
     void set_dnn_prefer_smallest_algorithms();
+    size_t dnn_cpu_num_threads();
+    void set_dnn_cpu_num_threads(size_t num_threads);
 }
 
 namespace dlib { namespace tt
@@ -599,6 +601,238 @@ namespace dlib { namespace tt
               set begin to 0 and end to params.size().
     !*/
 
+// ----------------------------------------------------------------------------------------
+
+    void compute_adamw_update (
+        size_t begin,
+        size_t end,
+        tensor& s,
+        tensor& m,
+        tensor& v,
+        const float t,
+        const float learning_rate,
+        const float weight_decay,
+        const float momentum1,
+        const float momentum2,
+        const tensor& params,
+        const tensor& params_grad
+    );
+    /*!
+        requires
+            - s.size() == m.size() = v.size() == params.size() == params_grad.size()
+            - t > 0
+            - learning_rate > 0
+            - weight_decay >= 0
+            - 0 <= momentum1 < 1
+            - 0 <= momentum2 < 1
+            - begin <= end <= params.size()
+        ensures
+            - This function implements the AdamW parameter update method described in the
+              paper:
+                Loshchilov, Ilya, and Frank Hutter. "Decoupled weight decay
+                regularization." International Conference on Learning Representations. 2019.
+              That is, it is identical to compute_adam_update() except that the weight
+              decay is not folded into the gradient.  Instead, learning_rate*weight_decay*params
+              is subtracted directly from the update.
+            - #s is the update vector that should be added to the parameters.
+            - The function only operates in the half open range [begin,end) of the memory
+              blocks of each tensor.  E.g. to make this function run on the entire tensor
+              set begin to 0 and end to params.size().
+    !*/
+
+// ----------------------------------------------------------------------------------------
+
+    void compute_rmsprop_update (
+        size_t begin,
+        size_t end,
+        tensor& s,
+        tensor& v,
+        const float learning_rate,
+        const float weight_decay,
+        const float decay,
+        const tensor& params,
+        const tensor& params_grad
+    );
+    /*!
+        requires
+            - s.size() == v.size() == params.size() == params_grad.size()
+            - learning_rate > 0
+            - weight_decay >= 0
+            - 0 <= decay < 1
+            - begin <= end <= params.size()
+        ensures
+            - This function implements the RMSProp parameter update method.  Letting
+              g == weight_decay*params + params_grad, it performs:
+                - #v == decay*v + (1-decay)*squared(g)
+                - #s == -learning_rate*g/(sqrt(#v) + 1e-8)
+            - #s is the update vector that should be added to the parameters.
+            - The function only operates in the half open range [begin,end) of the memory
+              blocks of each tensor.  E.g. to make this function run on the entire tensor
+              set begin to 0 and end to params.size().
+    !*/
+
+// ----------------------------------------------------------------------------------------
+
+    void compute_adagrad_update (
+        size_t begin,
+        size_t end,
+        tensor& s,
+        tensor& v,
+        const float learning_rate,
+        const float weight_decay,
+        const tensor& params,
+        const tensor& params_grad
+    );
+    /*!
+        requires
+            - s.size() == v.size() == params.size() == params_grad.size()
+            - learning_rate > 0
+            - weight_decay >= 0
+            - begin <= end <= params.size()
+        ensures
+            - This function implements the Adagrad parameter update method.  Letting
+              g == weight_decay*params + params_grad, it performs:
+                - #v == v + squared(g)
+                - #s == -learning_rate*g/(sqrt(#v) + 1e-8)
+            - #s is the update vector that should be added to the parameters.
+            - The function only operates in the half open range [begin,end) of the memory
+              blocks of each tensor.  E.g. to make this function run on the entire tensor
+              set begin to 0 and end to params.size().
+    !*/
+
+// ----------------------------------------------------------------------------------------
+
+    void compute_lion_update (
+        size_t begin,
+        size_t end,
+        tensor& s,
+        tensor& m,
+        const float learning_rate,
+        const float weight_decay,
+        const float momentum1,
+        const float momentum2,
+        const tensor& params,
+        const tensor& params_grad
+    );
+    /*!
+        requires
+            - s.size() == m.size() == params.size() == params_grad.size()
+            - learning_rate > 0
+            - weight_decay >= 0
+            - 0 <= momentum1 < 1
+            - 0 <= momentum2 < 1
+            - begin <= end <= params.size()
+        ensures
+            - This function implements the Lion parameter update method described in the
+              paper:
+                Chen, Xiangning, et al. "Symbolic discovery of optimization algorithms."
+                Advances in Neural Information Processing Systems. 2023.
+              Specifically, it performs:
+                - #s == -learning_rate*(sign(momentum1*m + (1-momentum1)*params_grad) + weight_decay*params)
+                - #m == momentum2*m + (1-momentum2)*params_grad
+            - #s is the update vector that should be added to the parameters.
+            - The function only operates in the half open range [begin,end) of the memory
+              blocks of each tensor.  E.g. to make this function run on the entire tensor
+              set begin to 0 and end to params.size().
+    !*/
+
+// ----------------------------------------------------------------------------------------
+
+    void round_to_half_precision (
+        tensor& data,
+        half_format fmt
+    );
+    /*!
+        ensures
+            - Rounds every value in data to the nearest number representable in the given
+              16 bit format.  That is, for all valid i:
+                - #data.host()[i] == half_to_float(float_to_half(data.host()[i], fmt), fmt)
+              Values that are too large for the format become +/-inf.
+            - This lets you see what a computation would do with 16 bit storage while
+              still doing all the arithmetic in 32 bit floats.
+    !*/
+
+// ----------------------------------------------------------------------------------------
+
+    void clamp (
+        tensor& data,
+        float lower,
+        float upper
+    );
+    /*!
+        requires
+            - lower <= upper
+        ensures
+            - for all valid i:
+                - #data.host()[i] == std::min(upper, std::max(lower, data.host()[i]))
+    !*/
+
+// ----------------------------------------------------------------------------------------
+
+    void int8_fc (
+        resizable_tensor& output,
+        const tensor& input,
+        float input_scale,
+        const int8_tensor& weights,
+        const tensor& biases
+    );
+    /*!
+        requires
+            - input_scale > 0
+            - weights.size() == weights.num_samples()*input.size()/input.num_samples()
+            - biases.size() == 0 || biases.size() == weights.num_samples()
+        ensures
+            - Computes a fully connected layer with 8 bit integer arithmetic.  The rows
+              of the weight matrix are the samples of weights, so weights.num_samples() is
+              the number of outputs.  The input is quantized with
+              float_to_int8(x, input_scale) and then:
+                - #output.num_samples() == input.num_samples()
+                - #output.k() == weights.num_samples()
+                - #output.nr() == 1
+                - #output.nc() == 1
+                - #output is the matrix product of the quantized input and the transposed
+                  weights, accumulated in 32 bit integers, times input_scale and
+                  the scale of each weight row, plus biases (if biases.size() != 0).
+            - This function always runs on the CPU, even if dlib was built with CUDA.
+    !*/
+
+    void int8_conv (
+        resizable_tensor& output,
+        const tensor& input,
+        float input_scale,
+        const int8_tensor& filters,
+        const tensor& biases,
+        int stride_y,
+        int stride_x,
+        int padding_y,
+        int padding_x,
+        int dilation_y,
+        int dilation_x,
+        long groups,
+        bool use_relu
+    );
+    /*!
+        requires
+            - input_scale > 0
+            - stride_y > 0, stride_x > 0
+            - dilation_y > 0, dilation_x > 0
+            - groups > 0
+            - filters.num_samples()%groups == 0
+            - filters.k()*groups == input.k()
+            - biases.size() == 0 || biases.size() == filters.num_samples()
+            - The dilated filters fit inside the padded input.
+        ensures
+            - Computes the same convolution as tt::tensor_conv with the given stride,
+              padding, dilation and groups, but with 8 bit integer arithmetic.  The
+              input is quantized with float_to_int8(x, input_scale), each filter uses
+              its own scale from filters, and the products are accumulated in 32 bit
+              integers before being scaled back to floats.
+            - Adds biases to the output if biases.size() != 0, and then applies relu if
+              use_relu == true.
+            - #output has the same dimensions as the output of tt::tensor_conv.
+            - This function always runs on the CPU, even if dlib was built with CUDA.
+    !*/
+
 // ----------------------------------------------------------------------------------------
 
     void batch_normalize_inference (
@@ -1050,16 +1284,16 @@ namespace dlib { namespace tt
         /*!
             requires
                 - setup() has been called.  Specifically, setup() has been called like this:
-                    this->setup(data, filters, stride_y, stride_x, padding_y, padding_x);
+                    this->setup(data, filters, stride_y, stride_x, padding_y, padding_x, dilation_y, dilation_x, groups);
                 - is_same_object(output,data) == false
                 - is_same_object(output,filters) == false
-                - filters.k() == data.k()
-                - filters.nr() <= src.nr() + 2*padding_y
-                - filters.nc() <= src.nc() + 2*padding_x
+                - filters.k()*groups == data.k()
+                - dilation_y*(filters.nr()-1)+1 <= data.nr() + 2*padding_y
+                - dilation_x*(filters.nc()-1)+1 <= data.nc() + 2*padding_x
                 - #output.num_samples() == data.num_samples()
                 - #output.k() == filters.num_samples()
-                - #output.nr() == 1+(data.nr() + 2*padding_y - filters.nr())/stride_y
-                - #output.nc() == 1+(data.nc() + 2*padding_x - filters.nc())/stride_x
+                - #output.nr() == 1+(data.nr() + 2*padding_y - (dilation_y*(filters.nr()-1)+1))/stride_y
+                - #output.nc() == 1+(data.nc() + 2*padding_x - (dilation_x*(filters.nc()-1)+1))/stride_x
             ensures
                 - Convolves filters over data.  If add_to_output==true then we add the
                   results to output, otherwise we assign to output, overwriting the
@@ -1076,12 +1310,12 @@ namespace dlib { namespace tt
         /*!
             requires
                 - setup() has been called.  Specifically, setup() has been called like this:
-                    this->setup(data, filters, stride_y, stride_x, padding_y, padding_x);
+                    this->setup(data, filters, stride_y, stride_x, padding_y, padding_x, dilation_y, dilation_x, groups);
                 - is_same_object(output,data) == false
                 - is_same_object(output,filters) == false
-                - filters.k() == data.k()
-                - filters.nr() <= src.nr() + 2*padding_y
-                - filters.nc() <= src.nc() + 2*padding_x
+                - filters.k()*groups == data.k()
+                - dilation_y*(filters.nr()-1)+1 <= data.nr() + 2*padding_y
+                - dilation_x*(filters.nc()-1)+1 <= data.nc() + 2*padding_x
             ensures
                 - Convolves filters over data.  If add_to_output==true then we add the
                   results to output, otherwise we assign to output, overwriting the
@@ -1089,8 +1323,8 @@ namespace dlib { namespace tt
                 - filters contains filters.num_samples() filters. 
                 - #output.num_samples() == data.num_samples()
                 - #output.k() == filters.num_samples()
-                - #output.nr() == 1+(data.nr() + 2*padding_y - filters.nr())/stride_y
-                - #output.nc() == 1+(data.nc() + 2*padding_x - filters.nc())/stride_x
+                - #output.nr() == 1+(data.nr() + 2*padding_y - (dilation_y*(filters.nr()-1)+1))/stride_y
+                - #output.nc() == 1+(data.nc() + 2*padding_x - (dilation_x*(filters.nc()-1)+1))/stride_x
         !*/
 
         void operator() (
@@ -1104,17 +1338,17 @@ namespace dlib { namespace tt
         /*!
             requires
                 - setup() has been called.  Specifically, setup() has been called like this:
-                    this->setup(data, filters, stride_y, stride_x, padding_y, padding_x);
+                    this->setup(data, filters, stride_y, stride_x, padding_y, padding_x, dilation_y, dilation_x, groups);
                 - is_same_object(output,data) == false
                 - is_same_object(output,filters) == false
-                - filters.k() == data.k()
-                - filters.nr() <= src.nr() + 2*padding_y
-                - filters.nc() <= src.nc() + 2*padding_x
+                - filters.k()*groups == data.k()
+                - dilation_y*(filters.nr()-1)+1 <= data.nr() + 2*padding_y
+                - dilation_x*(filters.nc()-1)+1 <= data.nc() + 2*padding_x
                 - filters.num_samples() == biases.k()
                 - #output.num_samples() == data.num_samples()
                 - #output.k() == filters.num_samples()
-                - #output.nr() == 1+(data.nr() + 2*padding_y - filters.nr())/stride_y
-                - #output.nc() == 1+(data.nc() + 2*padding_x - filters.nc())/stride_x
+                - #output.nr() == 1+(data.nr() + 2*padding_y - (dilation_y*(filters.nr()-1)+1))/stride_y
+                - #output.nc() == 1+(data.nc() + 2*padding_x - (dilation_x*(filters.nc()-1)+1))/stride_x
             ensures
                 - Convolves filters over data.  If add_to_output==true then we add the
                   results to output, otherwise we assign to output, overwriting the
@@ -1136,12 +1370,12 @@ namespace dlib { namespace tt
         /*!
             requires
                 - setup() has been called.  Specifically, setup() has been called like this:
-                    this->setup(data, filters, stride_y, stride_x, padding_y, padding_x);
+                    this->setup(data, filters, stride_y, stride_x, padding_y, padding_x, dilation_y, dilation_x, groups);
                 - is_same_object(output,data) == false
                 - is_same_object(output,filters) == false
-                - filters.k() == data.k()
-                - filters.nr() <= src.nr() + 2*padding_y
-                - filters.nc() <= src.nc() + 2*padding_x
+                - filters.k()*groups == data.k()
+                - dilation_y*(filters.nr()-1)+1 <= data.nr() + 2*padding_y
+                - dilation_x*(filters.nc()-1)+1 <= data.nc() + 2*padding_x
                 - filters.num_samples() == biases.k()
             ensures
                 - Convolves filters over data.  If add_to_output==true then we add the
@@ -1151,8 +1385,8 @@ namespace dlib { namespace tt
                 - filters contains filters.num_samples() filters.
                 - #output.num_samples() == data.num_samples()
                 - #output.k() == filters.num_samples()
-                - #output.nr() == 1+(data.nr() + 2*padding_y - filters.nr())/stride_y
-                - #output.nc() == 1+(data.nc() + 2*padding_x - filters.nc())/stride_x
+                - #output.nr() == 1+(data.nr() + 2*padding_y - (dilation_y*(filters.nr()-1)+1))/stride_y
+                - #output.nc() == 1+(data.nc() + 2*padding_x - (dilation_x*(filters.nc()-1)+1))/stride_x
         !*/
 
         void get_gradient_for_data (
@@ -1168,12 +1402,12 @@ namespace dlib { namespace tt
                       last call to operator().  Also, data_gradient has the same dimensions
                       as the data object given to the last call to operator().
                     - setup() has been called.  Specifically, setup() has been called like this:
-                      this->setup(data_gradient, filters, stride_y, stride_x, padding_y, padding_x);
+                      this->setup(data_gradient, filters, stride_y, stride_x, padding_y, padding_x, dilation_y, dilation_x, groups);
                 - gradient_input has the following dimensions:
                     - gradient_input.num_samples() == data_gradient.num_samples()
                     - gradient_input.k() == filters.num_samples()
-                    - gradient_input.nr() == 1+(data_gradient.nr() + 2*padding_y - filters.nr())/stride_y
-                    - gradient_input.nc() == 1+(data_gradient.nc() + 2*padding_x - filters.nc())/stride_x
+                    - gradient_input.nr() == 1+(data_gradient.nr() + 2*padding_y - (dilation_y*(filters.nr()-1)+1))/stride_y
+                    - gradient_input.nc() == 1+(data_gradient.nc() + 2*padding_x - (dilation_x*(filters.nc()-1)+1))/stride_x
                     - NOTE, these dimensions are what you would obtain if gradient_input
                       has the same dimensions as the last output of operator().  
                 - is_same_object(data_gradient,filters) == false
@@ -1203,12 +1437,12 @@ namespace dlib { namespace tt
                       to the last call to operator().  Also, data has the same dimensions
                       as the data object given to the last call to operator().
                     - setup() has been called.  Specifically, setup() has been called like this:
-                      this->setup(data, filters_gradient, stride_y, stride_x, padding_y, padding_x);
+                      this->setup(data, filters_gradient, stride_y, stride_x, padding_y, padding_x, dilation_y, dilation_x, groups);
                 - gradient_input has the following dimensions:
                     - gradient_input.num_samples() == data.num_samples()
                     - gradient_input.k() == filters.num_samples()
-                    - gradient_input.nr() == 1+(data.nr() + 2*padding_y - filters.nr())/stride_y
-                    - gradient_input.nc() == 1+(data.nc() + 2*padding_x - filters.nc())/stride_x
+                    - gradient_input.nr() == 1+(data.nr() + 2*padding_y - (dilation_y*(filters.nr()-1)+1))/stride_y
+                    - gradient_input.nc() == 1+(data.nc() + 2*padding_x - (dilation_x*(filters.nc()-1)+1))/stride_x
                     - NOTE, these dimensions are what you would obtain if gradient_input
                       has the same dimensions as the last output of operator().  
                 - is_same_object(filters_gradient,data) == false
@@ -1232,19 +1466,35 @@ namespace dlib { namespace tt
             int stride_y,
             int stride_x,
             int padding_y,
-            int padding_x
-        ) {impl.setup(data,filters,stride_y,stride_x,padding_y,padding_x); }
+            int padding_x,
+            int dilation_y = 1,
+            int dilation_x = 1,
+            int groups = 1
+        ) {impl.setup(data,filters,stride_y,stride_x,padding_y,padding_x,dilation_y,dilation_x,groups); }
         /*!
             requires
-                - filters.k() == data.k()
+                - filters.k()*groups == data.k()
+                - filters.num_samples()%groups == 0
                 - stride_y > 0
                 - stride_x > 0
-                - 0 <= padding_y < filters.nr()
-                - 0 <= padding_x < filters.nc()
+                - dilation_y > 0
+                - dilation_x > 0
+                - groups > 0
+                - 0 <= padding_y < dilation_y*(filters.nr()-1)+1
+                - 0 <= padding_x < dilation_x*(filters.nc()-1)+1
             ensures
+                - The filters are applied with the given dilation, that is, filter element
+                  (r,c) is multiplied with the input element dilation_y*r rows and
+                  dilation_x*c columns away from the filter window's top left corner.  So
+                  the filters cover a dilation_y*(filters.nr()-1)+1 by
+                  dilation_x*(filters.nc()-1)+1 window of the input.
+                - The data channels and the filters are split into groups groups.  The i-th
+                  group of filters.num_samples()/groups filters is only applied to the i-th
+                  group of data.k()/groups channels and produces the i-th group of output
+                  channels.  When groups == data.k() this is a depthwise convolution.
                 - When operator() is called, the output tensor will have these dimensions:
-                    - output.nr() == 1+(data.nr() + 2*padding_y - filters.nr())/stride_y
-                    - output.nc() == 1+(data.nc() + 2*padding_x - filters.nc())/stride_x
+                    - output.nr() == 1+(data.nr() + 2*padding_y - (dilation_y*(filters.nr()-1)+1))/stride_y
+                    - output.nc() == 1+(data.nc() + 2*padding_x - (dilation_x*(filters.nc()-1)+1))/stride_x
                     - output.num_samples() == data.num_samples()
                     - output.k() == filters.num_samples()
                 - The point of setup() is to allow this object to gather information about
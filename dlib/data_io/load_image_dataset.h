@@ -245,6 +245,7 @@ namespace dlib
                         min_rect_size = std::min<double>(min_rect_size, rects.back().rect.area());
                     }
                     rects.back().label = data.images[i].boxes[j].label;
+                    rects.back().angle = data.images[i].boxes[j].angle;
 
                 }
             }
@@ -225,7 +225,8 @@ namespace dlib
               mmod_rects instead of rectangles.  In this case, both ignore and non-ignore
               rectangles go into object_locations since mmod_rect has an ignore boolean
               field that records the ignored/non-ignored state of each rectangle.  We also store 
-              a each box's string label into the mmod_rect::label field as well.
+              a each box's string label into the mmod_rect::label field as well.  Similarly,
+              each box's angle is stored into the mmod_rect::angle field.
     !*/
 
 // ----------------------------------------------------------------------------------------
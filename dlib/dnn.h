@@ -26,12 +26,16 @@
 #include "dnn/loss.h"
 #include "dnn/core.h"
 #include "dnn/solvers.h"
+#include "dnn/lr_schedulers.h"
 #include "dnn/trainer.h"
+#include "dnn/data_loader.h"
 #include "cuda/cpu_dlib.h"
 #include "cuda/tensor_tools.h"
 #include "dnn/utilities.h"
 #include "dnn/validation.h"
 #include "dnn/visitors.h"
+#include "dnn/dynamic_graph.h"
+#include "dnn/onnx.h"
 
 #endif // DLIB_DNn_
 
@@ -196,6 +196,32 @@ namespace dlib
             - #dnn_prefer_fastest_algorithms() == false 
     !*/
 
+    size_t dnn_cpu_num_threads(
+    );
+    /*!
+        ensures
+            - returns the number of threads the CPU versions of the tensor kernels (i.e.
+              the ones used when dlib isn't compiled with CUDA) split their work over.
+              The work is run on the default_thread_pool(), so using more threads than
+              default_thread_pool().num_threads_in_pool() doesn't make anything faster.
+            - On program startup this function returns
+              max(1, default_thread_pool().num_threads_in_pool()).  Note that you can
+              set the size of the default_thread_pool() with the DLIB_NUM_THREADS
+              environment variable.
+    !*/
+
+    void set_dnn_cpu_num_threads(
+        size_t num_threads
+    );
+    /*!
+        requires
+            - num_threads > 0
+        ensures
+            - #dnn_cpu_num_threads() == num_threads
+            - if (num_threads == 1) then
+                - the CPU tensor kernels run entirely in the thread that calls them.
+    !*/
+
 // ----------------------------------------------------------------------------------------
 
     template <
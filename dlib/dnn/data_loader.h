@@ -0,0 +1,376 @@
+// Copyright (C) 2026  Davis E. King (davis@dlib.net)
+// License: Boost Software License   See LICENSE.txt for the full license.
+#ifndef DLIB_DNn_DATA_LOADER_H_
+#define DLIB_DNn_DATA_LOADER_H_
+
+#include "data_loader_abstract.h"
+#include "../pipe.h"
+#include "../threads.h"
+#include "../rand.h"
+#include "../assert.h"
+#include "../string.h"
+#include <algorithm>
+#include <exception>
+#include <functional>
+#include <memory>
+#include <thread>
+#include <type_traits>
+#include <utility>
+#include <vector>
+
+namespace dlib
+{
+
+// ----------------------------------------------------------------------------------------
+
+    template <typename F>
+    class function_dataset
+    {
+    public:
+        typedef typename std::decay<decltype(std::declval<const F&>()(size_t()))>::type sample_type;
+
+        function_dataset(
+            size_t num_samples_,
+            F f_
+        ) : num_samples(num_samples_), f(std::move(f_)) {}
+
+        size_t size (
+        ) const { return num_samples; }
+
+        sample_type operator[] (
+            size_t i
+        ) const
+        {
+            DLIB_ASSERT(i < size());
+            return f(i);
+        }
+
+    private:
+        size_t num_samples;
+        F f;
+    };
+
+    template <typename F>
+    function_dataset<F> make_dataset (
+        size_t num_samples,
+        F f
+    )
+    {
+        return function_dataset<F>(num_samples, std::move(f));
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    template <
+        typename dataset_type
+        >
+    class data_loader
+    {
+    public:
+
+        typedef typename std::decay<decltype(std::declval<const dataset_type&>()[0])>::type sample_type;
+        typedef std::function<void(sample_type&, dlib::rand&)> transform_type;
+
+        data_loader() = delete;
+        data_loader(const data_loader&) = delete;
+        data_loader& operator=(const data_loader&) = delete;
+
+        data_loader (
+            const dataset_type& dataset_,
+            size_t batch_size_
+        ) :
+            dataset(dataset_),
+            batch_size(batch_size_),
+            num_workers(std::max<size_t>(1, std::thread::hardware_concurrency())),
+            prefetch_depth(2),
+            shuffle(true),
+            drop_last(false),
+            seed(0),
+            batch_pos(0),
+            start_pos(0)
+        {
+            DLIB_CASSERT(batch_size > 0);
+        }
+
+        ~data_loader (
+        )
+        {
+            stop_workers();
+        }
+
+        size_t get_batch_size (
+        ) const { return batch_size; }
+
+        void set_batch_size (
+            size_t size
+        )
+        {
+            DLIB_CASSERT(size > 0);
+            stop_workers();
+            batch_size = size;
+        }
+
+        size_t get_num_workers (
+        ) const { return num_workers; }
+
+        void set_num_workers (
+            size_t num
+        )
+        {
+            DLIB_CASSERT(num > 0);
+            stop_workers();
+            num_workers = num;
+        }
+
+        size_t get_prefetch_depth (
+        ) const { return prefetch_depth; }
+
+        void set_prefetch_depth (
+            size_t depth
+        )
+        {
+            DLIB_CASSERT(depth > 0);
+            stop_workers();
+            prefetch_depth = depth;
+        }
+
+        bool get_shuffle (
+        ) const { return shuffle; }
+
+        void set_shuffle (
+            bool value
+        )
+        {
+            stop_workers();
+            shuffle = value;
+        }
+
+        bool get_drop_last (
+        ) const { return drop_last; }
+
+        void set_drop_last (
+            bool value
+        )
+        {
+            stop_workers();
+            drop_last = value;
+        }
+
+        unsigned long long get_seed (
+        ) const { return seed; }
+
+        void set_seed (
+            unsigned long long value
+        )
+        {
+            stop_workers();
+            seed = value;
+        }
+
+        void add_transform (
+            transform_type f
+        )
+        {
+            stop_workers();
+            transforms.push_back(std::move(f));
+        }
+
+        void clear_transforms (
+        )
+        {
+            stop_workers();
+            transforms.clear();
+        }
+
+        size_t get_num_transforms (
+        ) const { return transforms.size(); }
+
+        size_t num_batches_per_epoch (
+        ) const
+        {
+            if (drop_last)
+                return dataset.size()/batch_size;
+            else
+                return (dataset.size()+batch_size-1)/batch_size;
+        }
+
+        unsigned long long get_batch_position (
+        ) const { return batch_pos; }
+
+        void set_batch_position (
+            unsigned long long pos
+        )
+        {
+            stop_workers();
+            batch_pos = pos;
+        }
+
+        unsigned long long get_epoch (
+        ) const
+        {
+            DLIB_CASSERT(num_batches_per_epoch() > 0);
+            return batch_pos/num_batches_per_epoch();
+        }
+
+        void next (
+            std::vector<sample_type>& batch
+        )
+        {
+            DLIB_CASSERT(num_batches_per_epoch() > 0, "The dataset doesn't have enough samples to make a batch.");
+            if (queues.size() == 0)
+                start_workers();
+
+            // Worker i makes batches start_pos+i, start_pos+i+num_workers, and so on, so
+            // reading from the workers in turn gives the batches in order.
+            batch_t temp;
+            queues[(batch_pos-start_pos)%num_workers]->dequeue(temp);
+            if (temp.error)
+            {
+                stop_workers();
+                std::rethrow_exception(temp.error);
+            }
+            ++batch_pos;
+            batch.swap(temp.samples);
+        }
+
+        template <typename T = sample_type>
+        void next (
+            std::vector<typename T::first_type>& inputs,
+            std::vector<typename T::second_type>& labels
+        )
+        {
+            next(pair_batch);
+            inputs.clear();
+            labels.clear();
+            inputs.reserve(pair_batch.size());
+            labels.reserve(pair_batch.size());
+            for (auto& s : pair_batch)
+            {
+                inputs.push_back(std::move(s.first));
+                labels.push_back(std::move(s.second));
+            }
+        }
+
+    private:
+
+        struct batch_t
+        {
+            std::vector<sample_type> samples;
+            std::exception_ptr error;
+        };
+
+        void start_workers (
+        )
+        {
+            if (!pool || pool->num_threads_in_pool() != num_workers)
+                pool.reset(new thread_pool(num_workers));
+
+            start_pos = batch_pos;
+            for (size_t i = 0; i < num_workers; ++i)
+                queues.emplace_back(new dlib::pipe<batch_t>(prefetch_depth));
+            for (size_t i = 0; i < num_workers; ++i)
+                pool->add_task_by_value([this,i]() { worker(i); });
+        }
+
+        void stop_workers (
+        )
+        {
+            // Disabling the pipes makes any worker blocked in enqueue() give up, so
+            // they all return promptly.
+            for (auto& q : queues)
+                q->disable();
+            if (pool)
+                pool->wait_for_all_tasks();
+            queues.clear();
+        }
+
+        void worker (
+            size_t id
+        )
+        {
+            auto& q = *queues[id];
+            std::vector<size_t> order;
+            unsigned long long order_epoch = 0;
+            dlib::rand rnd;
+            for (unsigned long long pos = start_pos+id; q.is_enabled(); pos += num_workers)
+            {
+                batch_t batch;
+                try
+                {
+                    make_batch(pos, order, order_epoch, rnd, batch.samples);
+                }
+                catch (...)
+                {
+                    batch.error = std::current_exception();
+                }
+
+                const bool failed = static_cast<bool>(batch.error);
+                if (!q.enqueue(batch) || failed)
+                    return;
+            }
+        }
+
+        void make_batch (
+            unsigned long long pos,
+            std::vector<size_t>& order,
+            unsigned long long& order_epoch,
+            dlib::rand& rnd,
+            std::vector<sample_type>& samples
+        ) const
+        {
+            const unsigned long long epoch = pos/num_batches_per_epoch();
+            const size_t begin = (pos%num_batches_per_epoch())*batch_size;
+            const size_t end = std::min(begin+batch_size, dataset.size());
+
+            // The order of each epoch depends only on the seed and the epoch number, so it
+            // doesn't matter which worker computes it.
+            if (shuffle && (order.size() != dataset.size() || order_epoch != epoch))
+            {
+                order.resize(dataset.size());
+                for (size_t i = 0; i < order.size(); ++i)
+                    order[i] = i;
+                dlib::rand shuffle_rnd(cast_to_string(seed) + ":" + cast_to_string(epoch));
+                for (size_t i = order.size(); i > 1; --i)
+                    std::swap(order[i-1], order[shuffle_rnd.get_integer(i)]);
+                order_epoch = epoch;
+            }
+
+            samples.clear();
+            samples.reserve(end-begin);
+            for (size_t i = begin; i < end; ++i)
+            {
+                const size_t idx = shuffle ? order[i] : i;
+                samples.push_back(dataset[idx]);
+                if (transforms.size() != 0)
+                {
+                    // Likewise, the random numbers a sample's transforms see only depend
+                    // on the seed, the epoch, and which sample it is.
+                    rnd.set_seed(cast_to_string(seed) + ":" + cast_to_string(epoch) + ":" + cast_to_string(idx));
+                    for (auto& f : transforms)
+                        f(samples.back(), rnd);
+                }
+            }
+        }
+
+        const dataset_type& dataset;
+        size_t batch_size;
+        size_t num_workers;
+        size_t prefetch_depth;
+        bool shuffle;
+        bool drop_last;
+        unsigned long long seed;
+        std::vector<transform_type> transforms;
+
+        unsigned long long batch_pos;
+        unsigned long long start_pos;
+        std::unique_ptr<thread_pool> pool;
+        std::vector<std::unique_ptr<dlib::pipe<batch_t>>> queues;
+        std::vector<sample_type> pair_batch;
+    };
+
+// ----------------------------------------------------------------------------------------
+
+}
+
+#endif // DLIB_DNn_DATA_LOADER_H_
+
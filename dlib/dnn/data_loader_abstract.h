@@ -0,0 +1,408 @@
+// Copyright (C) 2026  Davis E. King (davis@dlib.net)
+// License: Boost Software License   See LICENSE.txt for the full license.
+#undef DLIB_DNn_DATA_LOADER_ABSTRACT_H_
+#ifdef DLIB_DNn_DATA_LOADER_ABSTRACT_H_
+
+#include "../rand/rand_kernel_abstract.h"
+#include <functional>
+#include <vector>
+
+namespace dlib
+{
+
+// ----------------------------------------------------------------------------------------
+
+    class EXAMPLE_DATASET
+    {
+        /*!
+            WHAT THIS OBJECT REPRESENTS
+                A dataset is an indexable collection of training samples that a
+                data_loader reads from.  Any object with the two member functions below
+                works, so a std::vector of samples is a dataset, and so is the object
+                returned by make_dataset().
+
+                The samples can be of any copyable type.  However, if they are
+                std::pair<input_type, label_type> objects then data_loader can split its
+                batches into separate input and label vectors, which is what dnn_trainer
+                wants.
+
+                Note that there is no dlib::EXAMPLE_DATASET type.  It is shown here purely
+                to document the interface a dataset object must implement.
+
+            THREAD SAFETY
+                operator[] is called from several threads at the same time, so it must be
+                safe to do so.  Note that the data_loader holds a reference to the dataset,
+                it does not copy it.
+        !*/
+
+    public:
+
+        size_t size (
+        ) const;
+        /*!
+            ensures
+                - returns the number of samples in this dataset.
+        !*/
+
+        sample_type operator[] (
+            size_t i
+        ) const;
+        /*!
+            requires
+                - i < size()
+            ensures
+                - returns the i-th sample.  This is where you would, for example, load
+                  the i-th image from disk.
+        !*/
+    };
+
+// ----------------------------------------------------------------------------------------
+
+    template <typename F>
+    class function_dataset
+    {
+        /*!
+            REQUIREMENTS ON F
+                - F is a function object that takes a size_t and returns a sample.  It
+                  must be safe to call it from several threads at the same time.
+
+            WHAT THIS OBJECT REPRESENTS
+                This object is a dataset, as defined by EXAMPLE_DATASET above, whose
+                samples are computed by a user supplied function.
+        !*/
+
+    public:
+
+        typedef the type returned by F sample_type;
+
+        function_dataset(
+            size_t num_samples,
+            F f
+        );
+        /*!
+            ensures
+                - #size() == num_samples
+                - (*this)[i] == f(i)
+        !*/
+
+        size_t size (
+        ) const;
+
+        sample_type operator[] (
+            size_t i
+        ) const;
+    };
+
+    template <typename F>
+    function_dataset<F> make_dataset (
+        size_t num_samples,
+        F f
+    );
+    /*!
+        ensures
+            - returns function_dataset<F>(num_samples, f)
+            - For example, you could make a dataset of images and labels stored in files
+              like this:
+                auto dataset = make_dataset(files.size(), [&](size_t i)
+                {
+                    std::pair<matrix<rgb_pixel>,unsigned long> sample;
+                    load_image(sample.first, files[i]);
+                    sample.second = labels[i];
+                    return sample;
+                });
+    !*/
+
+// ----------------------------------------------------------------------------------------
+
+    template <
+        typename dataset_type
+        >
+    class data_loader
+    {
+        /*!
+            REQUIREMENTS ON dataset_type
+                - dataset_type is an implementation of the EXAMPLE_DATASET interface
+                  defined above.
+
+            WHAT THIS OBJECT REPRESENTS
+                This object makes mini-batches of training samples from a dataset, using
+                a set of background worker threads so that the samples are ready by the
+                time dnn_trainer needs them.  It's meant to be used with
+                dnn_trainer::train_one_step() like this:
+
+                    data_loader<decltype(dataset)> loader(dataset, 64);
+                    loader.add_transform([](auto& sample, dlib::rand& rnd) { ... });
+                    std::vector<matrix<rgb_pixel>> images;
+                    std::vector<unsigned long> labels;
+                    while (trainer.get_learning_rate() >= 1e-5)
+                    {
+                        loader.next(images, labels);
+                        trainer.train_one_step(images, labels);
+                    }
+
+                The loader produces an endless stream of batches.  The stream is divided
+                into epochs, each of which visits every sample in the dataset once.  If
+                shuffling is enabled the samples of each epoch are put in a random order.
+                Each sample is then passed through the transform chain, which is where
+                data augmentation, like random cropping, is done.
+
+                The stream depends only on the dataset, the seed, and the other settings
+                of the loader.  In particular, it does not depend on the number of worker
+                threads or how they are scheduled.  This is because the order of each
+                epoch is computed from just the seed and the epoch number, and the random
+                number generator given to the transforms is seeded from just the seed, the
+                epoch number, and the index of the sample.
+
+                The workers start when next() is first called.  Changing any of the
+                settings stops them, and they start again, from the current position in
+                the stream, at the next call to next().
+
+            THREAD SAFETY
+                The member functions of this object must not be called from more than one
+                thread at a time.
+        !*/
+
+    public:
+
+        typedef the type of the samples in dataset_type sample_type;
+        typedef std::function<void(sample_type&, dlib::rand&)> transform_type;
+
+        data_loader() = delete;
+        data_loader(const data_loader&) = delete;
+        data_loader& operator=(const data_loader&) = delete;
+
+        data_loader (
+            const dataset_type& dataset,
+            size_t batch_size
+        );
+        /*!
+            requires
+                - batch_size > 0
+            ensures
+                - This object will make batches from the samples in dataset.  Note that it
+                  holds a reference to dataset, it does not copy it.  Therefore, you must
+                  ensure dataset has a lifetime at least as long as the data_loader.
+                - #get_batch_size() == batch_size
+                - #get_num_workers() == the number of hardware threads on this computer
+                - #get_prefetch_depth() == 2
+                - #get_shuffle() == true
+                - #get_drop_last() == false
+                - #get_seed() == 0
+                - #get_num_transforms() == 0
+                - #get_batch_position() == 0
+        !*/
+
+        ~data_loader (
+        );
+        /*!
+            ensures
+                - Stops the worker threads and waits for them to finish.
+        !*/
+
+        size_t get_batch_size (
+        ) const;
+        /*!
+            ensures
+                - returns the number of samples in each batch.  The last batch of an epoch
+                  may be smaller, see get_drop_last().
+        !*/
+
+        void set_batch_size (
+            size_t size
+        );
+        /*!
+            requires
+                - size > 0
+            ensures
+                - #get_batch_size() == size
+        !*/
+
+        size_t get_num_workers (
+        ) const;
+        /*!
+            ensures
+                - returns the number of threads that make batches in the background.
+        !*/
+
+        void set_num_workers (
+            size_t num
+        );
+        /*!
+            requires
+                - num > 0
+            ensures
+                - #get_num_workers() == num
+        !*/
+
+        size_t get_prefetch_depth (
+        ) const;
+        /*!
+            ensures
+                - returns the number of finished batches each worker may have waiting to
+                  be read.  So at most get_num_workers()*get_prefetch_depth() batches are
+                  held in memory by this object.
+        !*/
+
+        void set_prefetch_depth (
+            size_t depth
+        );
+        /*!
+            requires
+                - depth > 0
+            ensures
+                - #get_prefetch_depth() == depth
+        !*/
+
+        bool get_shuffle (
+        ) const;
+        /*!
+            ensures
+                - returns true if each epoch visits the samples in a random order and false
+                  if it visits them in the order they appear in the dataset.
+        !*/
+
+        void set_shuffle (
+            bool value
+        );
+        /*!
+            ensures
+                - #get_shuffle() == value
+        !*/
+
+        bool get_drop_last (
+        ) const;
+        /*!
+            ensures
+                - returns true if the samples left over at the end of an epoch, when the
+                  dataset size isn't a multiple of get_batch_size(), are skipped.  If
+                  false, they are output as a smaller last batch.
+        !*/
+
+        void set_drop_last (
+            bool value
+        );
+        /*!
+            ensures
+                - #get_drop_last() == value
+        !*/
+
+        unsigned long long get_seed (
+        ) const;
+        /*!
+            ensures
+                - returns the seed for all the random choices made by this object.  Two
+                  loaders with the same settings and seed produce the same batches.
+        !*/
+
+        void set_seed (
+            unsigned long long value
+        );
+        /*!
+            ensures
+                - #get_seed() == value
+        !*/
+
+        void add_transform (
+            transform_type f
+        );
+        /*!
+            ensures
+                - Appends f to the transform chain.  Each sample is given to each of the
+                  transforms, in the order they were added, before it's put into a batch.
+                  The dlib::rand object given to them is seeded as described above, so
+                  transforms should get all their randomness from it.
+                - f is called from the worker threads, so it must be safe to call it from
+                  several threads at the same time.  If it throws, the exception is
+                  rethrown by next().
+                - #get_num_transforms() == get_num_transforms() + 1
+        !*/
+
+        void clear_transforms (
+        );
+        /*!
+            ensures
+                - #get_num_transforms() == 0
+        !*/
+
+        size_t get_num_transforms (
+        ) const;
+        /*!
+            ensures
+                - returns the number of transforms in the transform chain.
+        !*/
+
+        size_t num_batches_per_epoch (
+        ) const;
+        /*!
+            ensures
+                - returns the number of batches in each epoch.  That is,
+                  dataset.size()/get_batch_size() rounded up, or rounded down if
+                  get_drop_last() is true.
+        !*/
+
+        unsigned long long get_batch_position (
+        ) const;
+        /*!
+            ensures
+                - returns the number of batches output so far.  That is, the position in
+                  the stream of the batch the next call to next() will output.
+        !*/
+
+        void set_batch_position (
+            unsigned long long pos
+        );
+        /*!
+            ensures
+                - #get_batch_position() == pos
+                - Since the stream of batches is deterministic, this can be used to resume
+                  an interrupted training session.  E.g. by setting it to the
+                  dnn_trainer's get_num_training_steps() after loading its sync file.
+        !*/
+
+        unsigned long long get_epoch (
+        ) const;
+        /*!
+            requires
+                - num_batches_per_epoch() > 0
+            ensures
+                - returns get_batch_position()/num_batches_per_epoch().  That is, the
+                  epoch the next batch belongs to.
+        !*/
+
+        void next (
+            std::vector<sample_type>& batch
+        );
+        /*!
+            requires
+                - num_batches_per_epoch() > 0
+            ensures
+                - #batch == the batch at position get_batch_position() in the stream.
+                - #get_batch_position() == get_batch_position() + 1
+                - Blocks until the batch is ready.
+                - If the dataset or one of the transforms threw an exception while making
+                  this batch then that exception is rethrown here and
+                  #get_batch_position() == get_batch_position().
+        !*/
+
+        void next (
+            std::vector<input_type>& inputs,
+            std::vector<label_type>& labels
+        );
+        /*!
+            requires
+                - sample_type is std::pair<input_type, label_type>
+                - num_batches_per_epoch() > 0
+            ensures
+                - Gets the next batch just like next(batch) above, but puts the first
+                  member of each sample into inputs and the second member into labels.
+                  That is, for all valid i, #inputs[i] == batch[i].first and #labels[i] ==
+                  batch[i].second.
+        !*/
+    };
+
+// ----------------------------------------------------------------------------------------
+
+}
+
+#endif // DLIB_DNn_DATA_LOADER_ABSTRACT_H_
+
@@ -0,0 +1,1431 @@
+// Copyright (C) 2026  Davis E. King (davis@dlib.net)
+// License: Boost Software License   See LICENSE.txt for the full license.
+#ifndef DLIB_DNn_DYNAMIC_GRAPH_H_
+#define DLIB_DNn_DYNAMIC_GRAPH_H_
+
+#include "dynamic_graph_abstract.h"
+#include "core.h"
+#include "layers.h"
+#include "../xml_parser.h"
+#include <memory>
+#include <vector>
+#include <map>
+#include <string>
+#include <sstream>
+#include <fstream>
+
+namespace dlib
+{
+
+// ----------------------------------------------------------------------------------------
+
+    namespace impl
+    {
+        class dynamic_graph_subnet
+        {
+            /*!
+                This object lets the layer implementations run on a tensor inside a
+                dynamic_graph_ as though it were the output of a normal subnetwork.
+            !*/
+        public:
+            dynamic_graph_subnet(
+                const tensor& output_,
+                tensor* gradient_input_ = nullptr
+            ) : output(output_), gradient_input(gradient_input_) {}
+
+            const tensor& get_output() const { return output; }
+            tensor& get_gradient_input()
+            {
+                DLIB_CASSERT(gradient_input != nullptr);
+                return *gradient_input;
+            }
+
+        private:
+            const tensor& output;
+            tensor* gradient_input;
+        };
+
+    // ------------------------------------------------------------------------------------
+
+        class dynamic_graph_op
+        {
+        public:
+            virtual ~dynamic_graph_op() = default;
+
+            virtual std::unique_ptr<dynamic_graph_op> clone() const = 0;
+
+            // The name used for this op in XML and in serialized graphs.
+            virtual std::string type() const = 0;
+
+            // False for ops, like concat, that only read tagged tensors.
+            virtual bool uses_main_input() const { return true; }
+
+            virtual void setup(const std::vector<const tensor*>& inputs) = 0;
+            virtual void forward(const std::vector<const tensor*>& inputs, resizable_tensor& output) = 0;
+            virtual void backward(
+                const std::vector<const tensor*>& inputs,
+                const tensor& computed_output,
+                const tensor& gradient_input,
+                const std::vector<tensor*>& input_grads,
+                tensor& params_grad
+            ) = 0;
+
+            virtual const tensor& get_layer_params() const { return empty_params; }
+            virtual tensor& get_layer_params() { return empty_params; }
+            virtual double learning_rate_multiplier() const { return 1; }
+
+            virtual void serialize(std::ostream& out) const = 0;
+            virtual void deserialize(std::istream& in) = 0;
+            virtual void print(std::ostream& out) const = 0;
+            virtual void to_xml(std::ostream& out, const std::vector<unsigned long>& input_tags) const = 0;
+
+        private:
+            resizable_tensor empty_params;
+        };
+
+    // ------------------------------------------------------------------------------------
+
+        // The layers' serialize() and to_xml() are friend functions, so they are called
+        // from here, where they aren't hidden by the members of dynamic_graph_op.
+        template <typename LAYER>
+        void serialize_layer(const LAYER& l, std::ostream& out) { serialize(l, out); }
+        template <typename LAYER>
+        void deserialize_layer(LAYER& l, std::istream& in) { deserialize(l, in); }
+        template <typename LAYER>
+        void layer_to_xml(const LAYER& l, std::ostream& out) { to_xml(l, out); }
+
+        template <typename LAYER>
+        class dynamic_graph_layer : public dynamic_graph_op
+        {
+            /*!
+                Runs one of the normal layer implementations, e.g. fc_ or relu_, inside a
+                dynamic_graph_.
+            !*/
+        public:
+            dynamic_graph_layer(
+                const std::string& name_,
+                const LAYER& details_ = LAYER()
+            ) : name(name_), details(details_) {}
+
+            std::unique_ptr<dynamic_graph_op> clone() const override { return std::unique_ptr<dynamic_graph_op>(new dynamic_graph_layer(*this)); }
+            std::string type() const override { return name; }
+
+            void setup(const std::vector<const tensor*>& inputs) override
+            {
+                dynamic_graph_subnet sub(*inputs[0]);
+                details.setup(sub);
+            }
+
+            void forward(const std::vector<const tensor*>& inputs, resizable_tensor& output) override
+            {
+                dynamic_graph_subnet sub(*inputs[0]);
+                // In-place layers expect the output to already have the size of the input.
+                // The other layers size it themselves.
+                output.copy_size(*inputs[0]);
+                call_layer_forward(details, sub, output);
+            }
+
+            void backward(
+                const std::vector<const tensor*>& inputs,
+                const tensor& computed_output,
+                const tensor& gradient_input,
+                const std::vector<tensor*>& input_grads,
+                tensor& params_grad
+            ) override
+            {
+                dynamic_graph_subnet sub(*inputs[0], input_grads[0]);
+                call_layer_backward(details, computed_output, gradient_input, sub, params_grad);
+            }
+
+            const tensor& get_layer_params() const override { return details.get_layer_params(); }
+            tensor& get_layer_params() override { return details.get_layer_params(); }
+            double learning_rate_multiplier() const override { return get_learning_rate_multiplier(details); }
+
+            void serialize(std::ostream& out) const override { serialize_layer(details, out); }
+            void deserialize(std::istream& in) override { deserialize_layer(details, in); }
+            void print(std::ostream& out) const override { out << details; }
+            void to_xml(std::ostream& out, const std::vector<unsigned long>&) const override { layer_to_xml(details, out); }
+
+        private:
+            std::string name;
+            LAYER details;
+        };
+
+    // ------------------------------------------------------------------------------------
+
+        class dynamic_graph_con : public dynamic_graph_op
+        {
+            /*!
+                A con_ layer whose filter size, stride, padding, dilation and groups are
+                chosen at runtime.  It uses the same parameter layout as con_, so the
+                parameters of a con_ layer can be loaded into it directly.
+            !*/
+        public:
+            dynamic_graph_con() = default;
+
+            dynamic_graph_con(const dynamic_graph_con& item) :
+                num_filters(item.num_filters), nr(item.nr), nc(item.nc),
+                stride_y(item.stride_y), stride_x(item.stride_x),
+                padding_y(item.padding_y), padding_x(item.padding_x),
+                dilation_y(item.dilation_y), dilation_x(item.dilation_x), groups(item.groups),
+                learning_rate_mult(item.learning_rate_mult), weight_decay_mult(item.weight_decay_mult),
+                bias_learning_rate_mult(item.bias_learning_rate_mult), bias_weight_decay_mult(item.bias_weight_decay_mult),
+                use_bias(item.use_bias), use_relu(item.use_relu),
+                params(item.params), filters(item.filters), biases(item.biases)
+            {
+                // this->conv is non-copyable and basically stateless, so it isn't copied.
+            }
+
+            long num_filters = 1;
+            long nr = 3;
+            long nc = 3;
+            long stride_y = 1;
+            long stride_x = 1;
+            long padding_y = 1;
+            long padding_x = 1;
+            long dilation_y = 1;
+            long dilation_x = 1;
+            long groups = 1;
+            double learning_rate_mult = 1;
+            double weight_decay_mult = 1;
+            double bias_learning_rate_mult = 1;
+            double bias_weight_decay_mult = 0;
+            bool use_bias = true;
+            bool use_relu = false;
+
+            std::unique_ptr<dynamic_graph_op> clone() const override { return std::unique_ptr<dynamic_graph_op>(new dynamic_graph_con(*this)); }
+            std::string type() const override { return "con"; }
+
+            void setup(const std::vector<const tensor*>& inputs) override
+            {
+                const tensor& data = *inputs[0];
+                const long filt_nr = nr!=0 ? nr : data.nr();
+                const long filt_nc = nc!=0 ? nc : data.nc();
+                DLIB_CASSERT(data.k()%groups == 0 && num_filters%groups == 0,
+                    "The number of input channels and filters must be divisible by the number of groups.");
+
+                const long num_inputs = filt_nr*filt_nc*data.k()/groups;
+                params.set_size(num_inputs*num_filters + static_cast<int>(use_bias)*num_filters);
+                dlib::rand rnd(std::rand());
+                randomize_parameters(params, num_inputs+num_filters, rnd);
+
+                filters = alias_tensor(num_filters, data.k()/groups, filt_nr, filt_nc);
+                if (use_bias)
+                {
+                    biases = alias_tensor(1, num_filters);
+                    biases(params, filters.size()) = 0;
+                }
+            }
+
+            void forward(const std::vector<const tensor*>& inputs, resizable_tensor& output) override
+            {
+                const tensor& data = *inputs[0];
+                conv.setup(data, filters(params,0), stride_y, stride_x, padding_y, padding_x,
+                    dilation_y, dilation_x, groups);
+                if (use_bias)
+                    conv(false, output, data, filters(params,0), biases(params, filters.size()), use_relu);
+                else
+                    conv(false, output, data, filters(params,0));
+            }
+
+            void backward(
+                const std::vector<const tensor*>& inputs,
+                const tensor& /*computed_output*/,
+                const tensor& gradient_input,
+                const std::vector<tensor*>& input_grads,
+                tensor& params_grad
+            ) override
+            {
+                conv.get_gradient_for_data(true, gradient_input, filters(params,0), *input_grads[0]);
+                if (learning_rate_mult != 0)
+                {
+                    auto filt = filters(params_grad,0);
+                    conv.get_gradient_for_filters(false, gradient_input, *inputs[0], filt);
+                    if (use_bias)
+                    {
+                        auto b = biases(params_grad, filters.size());
+                        tt::assign_conv_bias_gradient(b, gradient_input);
+                    }
+                }
+            }
+
+            const tensor& get_layer_params() const override { return params; }
+            tensor& get_layer_params() override { return params; }
+            double learning_rate_multiplier() const override { return learning_rate_mult; }
+
+            void serialize(std::ostream& out) const override
+            {
+                dlib::serialize("dynamic_graph_con", out);
+                dlib::serialize(params, out);
+                dlib::serialize(num_filters, out);
+                dlib::serialize(nr, out);
+                dlib::serialize(nc, out);
+                dlib::serialize(stride_y, out);
+                dlib::serialize(stride_x, out);
+                dlib::serialize(padding_y, out);
+                dlib::serialize(padding_x, out);
+                dlib::serialize(dilation_y, out);
+                dlib::serialize(dilation_x, out);
+                dlib::serialize(groups, out);
+                dlib::serialize(learning_rate_mult, out);
+                dlib::serialize(weight_decay_mult, out);
+                dlib::serialize(bias_learning_rate_mult, out);
+                dlib::serialize(bias_weight_decay_mult, out);
+                dlib::serialize(use_bias, out);
+                dlib::serialize(use_relu, out);
+                dlib::serialize(filters, out);
+                dlib::serialize(biases, out);
+            }
+
+            void deserialize(std::istream& in) override
+            {
+                std::string version;
+                dlib::deserialize(version, in);
+                if (version != "dynamic_graph_con")
+                    throw serialization_error("Unexpected version '"+version+"' found while deserializing a con layer in a dlib::dynamic_graph_.");
+                dlib::deserialize(params, in);
+                dlib::deserialize(num_filters, in);
+                dlib::deserialize(nr, in);
+                dlib::deserialize(nc, in);
+                dlib::deserialize(stride_y, in);
+                dlib::deserialize(stride_x, in);
+                dlib::deserialize(padding_y, in);
+                dlib::deserialize(padding_x, in);
+                dlib::deserialize(dilation_y, in);
+                dlib::deserialize(dilation_x, in);
+                dlib::deserialize(groups, in);
+                dlib::deserialize(learning_rate_mult, in);
+                dlib::deserialize(weight_decay_mult, in);
+                dlib::deserialize(bias_learning_rate_mult, in);
+                dlib::deserialize(bias_weight_decay_mult, in);
+                dlib::deserialize(use_bias, in);
+                dlib::deserialize(use_relu, in);
+                dlib::deserialize(filters, in);
+                dlib::deserialize(biases, in);
+            }
+
+            void print(std::ostream& out) const override
+            {
+                out << "con\t ("
+                    << "num_filters="<<num_filters
+                    << ", nr="<<nr
+                    << ", nc="<<nc
+                    << ", stride_y="<<stride_y
+                    << ", stride_x="<<stride_x
+                    << ", padding_y="<<padding_y
+                    << ", padding_x="<<padding_x;
+                if (dilation_y != 1 || dilation_x != 1)
+                    out << ", dilation_y="<<dilation_y << ", dilation_x="<<dilation_x;
+                if (groups != 1)
+                    out << ", groups="<<groups;
+                out << ")";
+                out << " learning_rate_mult="<<learning_rate_mult;
+                out << " weight_decay_mult="<<weight_decay_mult;
+                if (!use_bias)
+                    out << " use_bias=false";
+                if (use_relu)
+                    out << " use_relu=true";
+            }
+
+            void to_xml(std::ostream& out, const std::vector<unsigned long>&) const override
+            {
+                out << "<con"
+                    << " num_filters='"<<num_filters<<"'"
+                    << " nr='"<<nr<<"'"
+                    << " nc='"<<nc<<"'"
+                    << " stride_y='"<<stride_y<<"'"
+                    << " stride_x='"<<stride_x<<"'"
+                    << " padding_y='"<<padding_y<<"'"
+                    << " padding_x='"<<padding_x<<"'"
+                    << " dilation_y='"<<dilation_y<<"'"
+                    << " dilation_x='"<<dilation_x<<"'"
+                    << " groups='"<<groups<<"'"
+                    << " learning_rate_mult='"<<learning_rate_mult<<"'"
+                    << " weight_decay_mult='"<<weight_decay_mult<<"'"
+                    << " bias_learning_rate_mult='"<<bias_learning_rate_mult<<"'"
+                    << " bias_weight_decay_mult='"<<bias_weight_decay_mult<<"'"
+                    << " use_bias='"<<(use_bias?"true":"false")<<"'"
+                    << " use_relu='"<<(use_relu?"true":"false")<<"'"
+                    << ">\n";
+                out << mat(params);
+                out << "</con>\n";
+            }
+
+        private:
+            resizable_tensor params;
+            alias_tensor filters, biases;
+            tt::tensor_conv conv;
+        };
+
+    // ------------------------------------------------------------------------------------
+
+        class dynamic_graph_pool : public dynamic_graph_op
+        {
+            /*!
+                A max_pool_ or avg_pool_ layer whose window is chosen at runtime.  A
+                window size of 0 means the window covers the whole input, just like in
+                max_pool_ and avg_pool_.
+            !*/
+        public:
+            explicit dynamic_graph_pool(
+                bool is_max_ = true
+            ) : is_max(is_max_) {}
+
+            dynamic_graph_pool(const dynamic_graph_pool& item) :
+                nr(item.nr), nc(item.nc),
+                stride_y(item.stride_y), stride_x(item.stride_x),
+                padding_y(item.padding_y), padding_x(item.padding_x),
+                is_max(item.is_max)
+            {
+                // this->mp is non-copyable so it isn't copied.
+            }
+
+            long nr = 3;
+            long nc = 3;
+            long stride_y = 1;
+            long stride_x = 1;
+            long padding_y = 1;
+            long padding_x = 1;
+
+            std::unique_ptr<dynamic_graph_op> clone() const override { return std::unique_ptr<dynamic_graph_op>(new dynamic_graph_pool(*this)); }
+            std::string type() const override { return is_max ? "max_pool" : "avg_pool"; }
+
+            void setup(const std::vector<const tensor*>& /*inputs*/) override {}
+
+            void forward(const std::vector<const tensor*>& inputs, resizable_tensor& output) override
+            {
+                setup_pooling(*inputs[0]);
+                mp(output, *inputs[0]);
+            }
+
+            void backward(
+                const std::vector<const tensor*>& inputs,
+                const tensor& computed_output,
+                const tensor& gradient_input,
+                const std::vector<tensor*>& input_grads,
+                tensor& /*params_grad*/
+            ) override
+            {
+                setup_pooling(*inputs[0]);
+                mp.get_gradient(gradient_input, computed_output, *inputs[0], *input_grads[0]);
+            }
+
+            void serialize(std::ostream& out) const override
+            {
+                dlib::serialize("dynamic_graph_pool", out);
+                dlib::serialize(is_max, out);
+                dlib::serialize(nr, out);
+                dlib::serialize(nc, out);
+                dlib::serialize(stride_y, out);
+                dlib::serialize(stride_x, out);
+                dlib::serialize(padding_y, out);
+                dlib::serialize(padding_x, out);
+            }
+
+            void deserialize(std::istream& in) override
+            {
+                std::string version;
+                dlib::deserialize(version, in);
+                if (version != "dynamic_graph_pool")
+                    throw serialization_error("Unexpected version '"+version+"' found while deserializing a pooling layer in a dlib::dynamic_graph_.");
+                dlib::deserialize(is_max, in);
+                dlib::deserialize(nr, in);
+                dlib::deserialize(nc, in);
+                dlib::deserialize(stride_y, in);
+                dlib::deserialize(stride_x, in);
+                dlib::deserialize(padding_y, in);
+                dlib::deserialize(padding_x, in);
+            }
+
+            void print(std::ostream& out) const override
+            {
+                out << type() << "\t ("
+                    << "nr="<<nr
+                    << ", nc="<<nc
+                    << ", stride_y="<<stride_y
+                    << ", stride_x="<<stride_x
+                    << ", padding_y="<<padding_y
+                    << ", padding_x="<<padding_x
+                    << ")";
+            }
+
+            void to_xml(std::ostream& out, const std::vector<unsigned long>&) const override
+            {
+                out << "<" << type()
+                    << " nr='"<<nr<<"'"
+                    << " nc='"<<nc<<"'"
+                    << " stride_y='"<<stride_y<<"'"
+                    << " stride_x='"<<stride_x<<"'"
+                    << " padding_y='"<<padding_y<<"'"
+                    << " padding_x='"<<padding_x<<"'"
+                    << "/>\n";
+            }
+
+        private:
+            void setup_pooling(const tensor& data)
+            {
+                if (is_max)
+                    mp.setup_max_pooling(nr!=0?nr:data.nr(), nc!=0?nc:data.nc(), stride_y, stride_x, padding_y, padding_x);
+                else
+                    mp.setup_avg_pooling(nr!=0?nr:data.nr(), nc!=0?nc:data.nc(), stride_y, stride_x, padding_y, padding_x);
+            }
+
+            bool is_max;
+            tt::pooling mp;
+        };
+
+    // ------------------------------------------------------------------------------------
+
+        class dynamic_graph_upsample : public dynamic_graph_op
+        {
+        public:
+            long scale_y = 2;
+            long scale_x = 2;
+
+            std::unique_ptr<dynamic_graph_op> clone() const override { return std::unique_ptr<dynamic_graph_op>(new dynamic_graph_upsample(*this)); }
+            std::string type() const override { return "upsample"; }
+
+            void setup(const std::vector<const tensor*>& /*inputs*/) override {}
+
+            void forward(const std::vector<const tensor*>& inputs, resizable_tensor& output) override
+            {
+                const tensor& data = *inputs[0];
+                output.set_size(data.num_samples(), data.k(), scale_y*data.nr(), scale_x*data.nc());
+                tt::resize_bilinear(output, data);
+            }
+
+            void backward(
+                const std::vector<const tensor*>& /*inputs*/,
+                const tensor& /*computed_output*/,
+                const tensor& gradient_input,
+                const std::vector<tensor*>& input_grads,
+                tensor& /*params_grad*/
+            ) override
+            {
+                tt::resize_bilinear_gradient(*input_grads[0], gradient_input);
+            }
+
+            void serialize(std::ostream& out) const override
+            {
+                dlib::serialize("dynamic_graph_upsample", out);
+                dlib::serialize(scale_y, out);
+                dlib::serialize(scale_x, out);
+            }
+
+            void deserialize(std::istream& in) override
+            {
+                std::string version;
+                dlib::deserialize(version, in);
+                if (version != "dynamic_graph_upsample")
+                    throw serialization_error("Unexpected version '"+version+"' found while deserializing an upsample layer in a dlib::dynamic_graph_.");
+                dlib::deserialize(scale_y, in);
+                dlib::deserialize(scale_x, in);
+            }
+
+            void print(std::ostream& out) const override
+            {
+                out << "upsample\t (scale_y="<<scale_y<<", scale_x="<<scale_x<<")";
+            }
+
+            void to_xml(std::ostream& out, const std::vector<unsigned long>&) const override
+            {
+                out << "<upsample scale_y='"<<scale_y<<"' scale_x='"<<scale_x<<"'/>\n";
+            }
+        };
+
+    // ------------------------------------------------------------------------------------
+
+        class dynamic_graph_merge : public dynamic_graph_op
+        {
+            /*!
+                Implements add_prev_ and mult_prev_, which combine the main input with a
+                tagged tensor, and concat_, which stacks the tagged tensors along k().
+            !*/
+        public:
+            enum merge_kind { add_prev, mult_prev, concat };
+
+            explicit dynamic_graph_merge(
+                merge_kind kind_ = add_prev
+            ) : kind(kind_) {}
+
+            std::unique_ptr<dynamic_graph_op> clone() const override { return std::unique_ptr<dynamic_graph_op>(new dynamic_graph_merge(*this)); }
+            std::string type() const override
+            {
+                switch (kind)
+                {
+                    case add_prev: return "add_prev";
+                    case mult_prev: return "mult_prev";
+                    default: return "concat";
+                }
+            }
+            bool uses_main_input() const override { return kind != concat; }
+
+            void setup(const std::vector<const tensor*>& /*inputs*/) override {}
+
+            void forward(const std::vector<const tensor*>& inputs, resizable_tensor& output) override
+            {
+                if (kind == concat)
+                {
+                    long k = 0;
+                    for (auto t : inputs)
+                        k += t->k();
+                    output.set_size(inputs[0]->num_samples(), k, inputs[0]->nr(), inputs[0]->nc());
+                    size_t k_offset = 0;
+                    for (auto t : inputs)
+                    {
+                        tt::copy_tensor(false, output, k_offset, *t, 0, t->k());
+                        k_offset += t->k();
+                    }
+                    return;
+                }
+
+                const tensor& t1 = *inputs[0];
+                const tensor& t2 = *inputs[1];
+                output.set_size(std::max(t1.num_samples(),t2.num_samples()),
+                                std::max(t1.k(),t2.k()),
+                                std::max(t1.nr(),t2.nr()),
+                                std::max(t1.nc(),t2.nc()));
+                if (kind == add_prev)
+                    tt::add(output, t1, t2);
+                else
+                    tt::multiply_zero_padded(false, output, t1, t2);
+            }
+
+            void backward(
+                const std::vector<const tensor*>& inputs,
+                const tensor& /*computed_output*/,
+                const tensor& gradient_input,
+                const std::vector<tensor*>& input_grads,
+                tensor& /*params_grad*/
+            ) override
+            {
+                if (kind == concat)
+                {
+                    size_t k_offset = 0;
+                    for (auto g : input_grads)
+                    {
+                        tt::copy_tensor(true, *g, 0, gradient_input, k_offset, g->k());
+                        k_offset += g->k();
+                    }
+                }
+                else if (kind == add_prev)
+                {
+                    tt::add(*input_grads[0], *input_grads[0], gradient_input);
+                    tt::add(*input_grads[1], *input_grads[1], gradient_input);
+                }
+                else
+                {
+                    tt::multiply_zero_padded(true, *input_grads[0], *inputs[1], gradient_input);
+                    tt::multiply_zero_padded(true, *input_grads[1], *inputs[0], gradient_input);
+                }
+            }
+
+            void serialize(std::ostream& out) const override
+            {
+                dlib::serialize("dynamic_graph_merge", out);
+                dlib::serialize(static_cast<int>(kind), out);
+            }
+
+            void deserialize(std::istream& in) override
+            {
+                std::string version;
+                dlib::deserialize(version, in);
+                if (version != "dynamic_graph_merge")
+                    throw serialization_error("Unexpected version '"+version+"' found while deserializing a dlib::dynamic_graph_.");
+                int temp;
+                dlib::deserialize(temp, in);
+                kind = static_cast<merge_kind>(temp);
+            }
+
+            void print(std::ostream& out) const override
+            {
+                out << type();
+            }
+
+            void to_xml(std::ostream& out, const std::vector<unsigned long>& input_tags) const override
+            {
+                if (kind == concat)
+                {
+                    out << "<concat tags='";
+                    for (size_t i = 0; i < input_tags.size(); ++i)
+                        out << (i == 0 ? "" : ",") << input_tags[i];
+                    out << "'/>\n";
+                }
+                else
+                {
+                    out << "<" << type() << " tag='" << input_tags[1] << "'/>\n";
+                }
+            }
+
+        private:
+            merge_kind kind;
+        };
+
+    // ------------------------------------------------------------------------------------
+
+        using dynamic_graph_attributes = std::map<std::string,std::string>;
+
+        template <typename T>
+        T dynamic_graph_attribute (
+            const dynamic_graph_attributes& atts,
+            const std::string& key,
+            const T& default_value
+        )
+        {
+            const auto i = atts.find(key);
+            if (i == atts.end())
+                return default_value;
+            std::istringstream sin(i->second);
+            T value;
+            sin >> std::boolalpha >> value;
+            if (!sin)
+                throw serialization_error("Invalid value '"+i->second+"' for the '"+key+"' attribute of a dynamic_graph_ layer.");
+            return value;
+        }
+
+        template <typename LAYER>
+        void set_dynamic_graph_multipliers (
+            LAYER& l,
+            const dynamic_graph_attributes& atts
+        )
+        {
+            l.set_learning_rate_multiplier(dynamic_graph_attribute(atts, "learning_rate_mult", l.get_learning_rate_multiplier()));
+            l.set_weight_decay_multiplier(dynamic_graph_attribute(atts, "weight_decay_mult", l.get_weight_decay_multiplier()));
+            l.set_bias_learning_rate_multiplier(dynamic_graph_attribute(atts, "bias_learning_rate_mult", l.get_bias_learning_rate_multiplier()));
+            l.set_bias_weight_decay_multiplier(dynamic_graph_attribute(atts, "bias_weight_decay_mult", l.get_bias_weight_decay_multiplier()));
+        }
+
+        template <typename LAYER>
+        std::unique_ptr<dynamic_graph_op> wrap_dynamic_graph_layer (
+            const std::string& name,
+            const LAYER& l
+        )
+        {
+            return std::unique_ptr<dynamic_graph_op>(new dynamic_graph_layer<LAYER>(name, l));
+        }
+
+        inline std::unique_ptr<dynamic_graph_op> make_dynamic_graph_op (
+            const std::string& name,
+            const dynamic_graph_attributes& atts
+        )
+        {
+            /*!
+                ensures
+                    - returns the op named by the XML element name, configured by its
+                      attributes.  Returns a null pointer if name isn't a supported layer.
+            !*/
+            const auto get_bool = [&](const std::string& key, bool default_value)
+            {
+                return dynamic_graph_attribute(atts, key, default_value);
+            };
+
+            if (name == "con")
+            {
+                std::unique_ptr<dynamic_graph_con> op(new dynamic_graph_con);
+                op->num_filters = dynamic_graph_attribute(atts, "num_filters", op->num_filters);
+                op->nr = dynamic_graph_attribute(atts, "nr", op->nr);
+                op->nc = dynamic_graph_attribute(atts, "nc", op->nc);
+                op->stride_y = dynamic_graph_attribute(atts, "stride_y", op->stride_y);
+                op->stride_x = dynamic_graph_attribute(atts, "stride_x", op->stride_x);
+                // Use the same default padding as con_.
+                op->padding_y = dynamic_graph_attribute(atts, "padding_y", op->stride_y!=1 ? 0 : op->nr/2);
+                op->padding_x = dynamic_graph_attribute(atts, "padding_x", op->stride_x!=1 ? 0 : op->nc/2);
+                op->dilation_y = dynamic_graph_attribute(atts, "dilation_y", op->dilation_y);
+                op->dilation_x = dynamic_graph_attribute(atts, "dilation_x", op->dilation_x);
+                op->groups = dynamic_graph_attribute(atts, "groups", op->groups);
+                op->learning_rate_mult = dynamic_graph_attribute(atts, "learning_rate_mult", op->learning_rate_mult);
+                op->weight_decay_mult = dynamic_graph_attribute(atts, "weight_decay_mult", op->weight_decay_mult);
+                op->bias_learning_rate_mult = dynamic_graph_attribute(atts, "bias_learning_rate_mult", op->bias_learning_rate_mult);
+                op->bias_weight_decay_mult = dynamic_graph_attribute(atts, "bias_weight_decay_mult", op->bias_weight_decay_mult);
+                op->use_bias = get_bool("use_bias", op->use_bias);
+                op->use_relu = get_bool("use_relu", op->use_relu);
+                if (op->num_filters <= 0 || op->nr < 0 || op->nc < 0 || op->stride_y <= 0 || op->stride_x <= 0 ||
+                    op->dilation_y <= 0 || op->dilation_x <= 0 || op->groups <= 0 || op->num_filters%op->groups != 0)
+                    throw serialization_error("Invalid con layer settings found in a dynamic_graph_ description.");
+                return std::unique_ptr<dynamic_graph_op>(std::move(op));
+            }
+            if (name == "max_pool" || name == "avg_pool")
+            {
+                std::unique_ptr<dynamic_graph_pool> op(new dynamic_graph_pool(name == "max_pool"));
+                op->nr = dynamic_graph_attribute(atts, "nr", op->nr);
+                op->nc = dynamic_graph_attribute(atts, "nc", op->nc);
+                op->stride_y = dynamic_graph_attribute(atts, "stride_y", op->stride_y);
+                op->stride_x = dynamic_graph_attribute(atts, "stride_x", op->stride_x);
+                op->padding_y = dynamic_graph_attribute(atts, "padding_y", op->stride_y!=1 ? 0 : op->nr/2);
+                op->padding_x = dynamic_graph_attribute(atts, "padding_x", op->stride_x!=1 ? 0 : op->nc/2);
+                if (op->nr < 0 || op->nc < 0 || op->stride_y <= 0 || op->stride_x <= 0)
+                    throw serialization_error("Invalid "+name+" layer settings found in a dynamic_graph_ description.");
+                return std::unique_ptr<dynamic_graph_op>(std::move(op));
+            }
+            if (name == "upsample")
+            {
+                std::unique_ptr<dynamic_graph_upsample> op(new dynamic_graph_upsample);
+                op->scale_y = dynamic_graph_attribute(atts, "scale_y", op->scale_y);
+                op->scale_x = dynamic_graph_attribute(atts, "scale_x", op->scale_x);
+                if (op->scale_y < 1 || op->scale_x < 1)
+                    throw serialization_error("Invalid upsample layer settings found in a dynamic_graph_ description.");
+                return std::unique_ptr<dynamic_graph_op>(std::move(op));
+            }
+            if (name == "add_prev")
+                return std::unique_ptr<dynamic_graph_op>(new dynamic_graph_merge(dynamic_graph_merge::add_prev));
+            if (name == "mult_prev")
+                return std::unique_ptr<dynamic_graph_op>(new dynamic_graph_merge(dynamic_graph_merge::mult_prev));
+            if (name == "concat")
+                return std::unique_ptr<dynamic_graph_op>(new dynamic_graph_merge(dynamic_graph_merge::concat));
+
+            if (name == "fc" || name == "fc_no_bias")
+            {
+                const long num_outputs = dynamic_graph_attribute(atts, "num_outputs", 1L);
+                if (num_outputs <= 0)
+                    throw serialization_error("Invalid fc layer settings found in a dynamic_graph_ description.");
+                if (name == "fc_no_bias")
+                {
+                    fc_<1,FC_NO_BIAS> l{num_fc_outputs(num_outputs)};
+                    l.set_learning_rate_multiplier(dynamic_graph_attribute(atts, "learning_rate_mult", l.get_learning_rate_multiplier()));
+                    l.set_weight_decay_multiplier(dynamic_graph_attribute(atts, "weight_decay_mult", l.get_weight_decay_multiplier()));
+                    return wrap_dynamic_graph_layer(name, l);
+                }
+                fc_<1,FC_HAS_BIAS> l{num_fc_outputs(num_outputs)};
+                set_dynamic_graph_multipliers(l, atts);
+                if (!get_bool("use_bias", true))
+                    l.disable_bias();
+                return wrap_dynamic_graph_layer(name, l);
+            }
+            if (name == "bn_con" || name == "bn_fc")
+            {
+                const unsigned long window = dynamic_graph_attribute(atts, "running_stats_window_size", 100UL);
+                const double eps = dynamic_graph_attribute(atts, "eps", DEFAULT_BATCH_NORM_EPS);
+                if (window == 0 || eps <= 0)
+                    throw serialization_error("Invalid "+name+" layer settings found in a dynamic_graph_ description.");
+                if (name == "bn_con")
+                {
+                    bn_<CONV_MODE> l(window, eps);
+                    set_dynamic_graph_multipliers(l, atts);
+                    return wrap_dynamic_graph_layer(name, l);
+                }
+                bn_<FC_MODE> l(window, eps);
+                set_dynamic_graph_multipliers(l, atts);
+                return wrap_dynamic_graph_layer(name, l);
+            }
+            if (name == "affine_con" || name == "affine_fc")
+            {
+                affine_ l(name == "affine_con" ? CONV_MODE : FC_MODE);
+                if (get_bool("disabled", false))
+                    l.disable();
+                return wrap_dynamic_graph_layer(name, l);
+            }
+            if (name == "relu")
+            {
+                relu_ l;
+                if (get_bool("disabled", false))
+                    l.disable();
+                return wrap_dynamic_graph_layer(name, l);
+            }
+            if (name == "prelu")
+                return wrap_dynamic_graph_layer(name, prelu_(dynamic_graph_attribute(atts, "initial_param_value", 0.25f)));
+            if (name == "leaky_relu")
+                return wrap_dynamic_graph_layer(name, leaky_relu_(dynamic_graph_attribute(atts, "alpha", 0.01f)));
+            if (name == "elu")
+                return wrap_dynamic_graph_layer(name, elu_(dynamic_graph_attribute(atts, "alpha", 1.0f)));
+            if (name == "clipped_relu")
+                return wrap_dynamic_graph_layer(name, clipped_relu_(dynamic_graph_attribute(atts, "ceiling", 6.0f)));
+            if (name == "smelu")
+                return wrap_dynamic_graph_layer(name, smelu_(dynamic_graph_attribute(atts, "beta", 1.0f)));
+            if (name == "dropout")
+                return wrap_dynamic_graph_layer(name, dropout_(dynamic_graph_attribute(atts, "drop_rate", 0.5f)));
+            if (name == "multiply")
+                return wrap_dynamic_graph_layer(name, multiply_(dynamic_graph_attribute(atts, "val", 0.5f)));
+            if (name == "l2normalize")
+                return wrap_dynamic_graph_layer(name, l2normalize_(dynamic_graph_attribute(atts, "eps", DEFAULT_L2_NORM_EPS)));
+            if (name == "sig")
+                return wrap_dynamic_graph_layer(name, sig_());
+            if (name == "htan")
+                return wrap_dynamic_graph_layer(name, htan_());
+            if (name == "mish")
+                return wrap_dynamic_graph_layer(name, mish_());
+            if (name == "gelu")
+                return wrap_dynamic_graph_layer(name, gelu_());
+            if (name == "silu")
+                return wrap_dynamic_graph_layer(name, silu_());
+            if (name == "softmax")
+                return wrap_dynamic_graph_layer(name, softmax_());
+            if (name == "softmax_all")
+                return wrap_dynamic_graph_layer(name, softmax_all_());
+
+            return nullptr;
+        }
+
+    // ------------------------------------------------------------------------------------
+
+        struct dynamic_graph_layer_spec
+        {
+            std::string kind;   // comp, tag, skip, input, or loss
+            std::string name;   // the element name of a comp layer, e.g. con
+            dynamic_graph_attributes attributes;
+            std::vector<float> values;
+            unsigned long line = 0;
+            bool transparent = false;
+        };
+
+        class dynamic_graph_xml_reader : public document_handler
+        {
+            /*!
+                Collects the <layer> elements of an XML file written by net_to_xml().  The
+                layers are stored in the order they appear in the file.  A <layer> that
+                holds a <dynamic_graph> element is replaced by the layers inside it.
+            !*/
+        public:
+            std::vector<dynamic_graph_layer_spec> layers;
+
+            void start_document() override {}
+            void end_document() override {}
+            void processing_instruction(const unsigned long, const std::string&, const std::string&) override {}
+
+            void start_element(
+                const unsigned long line_number,
+                const std::string& name,
+                const dlib::attribute_list& atts
+            ) override
+            {
+                if (name == "layer")
+                {
+                    stack.emplace_back();
+                    auto& spec = stack.back();
+                    spec.line = line_number;
+                    spec.kind = atts.is_in_list("type") ? atts["type"] : "comp";
+                    if (atts.is_in_list("id"))
+                        spec.attributes["id"] = atts["id"];
+                    depth.push_back(0);
+                    return;
+                }
+
+                if (stack.size() == 0)
+                    return;
+
+                auto& spec = stack.back();
+                if (depth.back()++ != 0 || spec.kind != "comp" || spec.transparent)
+                    return;
+
+                if (name == "dynamic_graph")
+                {
+                    spec.transparent = true;
+                    return;
+                }
+                spec.name = name;
+                atts.reset();
+                while (atts.move_next())
+                    spec.attributes[atts.element().key()] = atts.element().value();
+            }
+
+            void end_element(
+                const unsigned long,
+                const std::string& name
+            ) override
+            {
+                if (stack.size() == 0)
+                    return;
+
+                if (name == "layer" && depth.back() == 0)
+                {
+                    if (!stack.back().transparent)
+                        layers.push_back(stack.back());
+                    stack.pop_back();
+                    depth.pop_back();
+                    return;
+                }
+                if (depth.back() > 0)
+                    --depth.back();
+            }
+
+            void characters(
+                const std::string& data
+            ) override
+            {
+                if (stack.size() == 0 || depth.back() != 1)
+                    return;
+                auto& spec = stack.back();
+                if (spec.kind != "comp" || spec.transparent)
+                    return;
+
+                std::istringstream sin(data);
+                float value;
+                while (sin >> value)
+                    spec.values.push_back(value);
+            }
+
+        private:
+            std::vector<dynamic_graph_layer_spec> stack;
+            // How many elements are open inside each <layer> on the stack.
+            std::vector<long> depth;
+        };
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    class dynamic_graph_
+    {
+    public:
+
+        dynamic_graph_(
+        ) :
+            learning_rate_multiplier(1),
+            weight_decay_multiplier(1)
+        {}
+
+        dynamic_graph_(
+            const dynamic_graph_& item
+        ) :
+            params(item.params),
+            params_stale(item.params_stale),
+            learning_rate_multiplier(item.learning_rate_multiplier),
+            weight_decay_multiplier(item.weight_decay_multiplier)
+        {
+            nodes.reserve(item.nodes.size());
+            for (auto& n : item.nodes)
+                nodes.emplace_back(n);
+        }
+
+        dynamic_graph_& operator= (
+            const dynamic_graph_& item
+        )
+        {
+            if (this == &item)
+                return *this;
+            dynamic_graph_ temp(item);
+            swap(temp);
+            return *this;
+        }
+
+        dynamic_graph_(dynamic_graph_&&) = default;
+        dynamic_graph_& operator=(dynamic_graph_&&) = default;
+
+        void load_xml (
+            std::istream& in
+        )
+        {
+            impl::dynamic_graph_xml_reader reader;
+            parse_xml(in, reader);
+            build(reader.layers);
+        }
+
+        void load_xml (
+            const std::string& filename
+        )
+        {
+            std::ifstream fin(filename);
+            if (!fin)
+                throw serialization_error("Unable to open file '" + filename + "'.");
+            load_xml(fin);
+        }
+
+        size_t num_nodes (
+        ) const { return nodes.size(); }
+
+        std::string node_type (
+            size_t i
+        ) const
+        {
+            DLIB_CASSERT(1 <= i && i <= num_nodes());
+            return nodes[i-1].op->type();
+        }
+
+        const std::vector<size_t>& node_inputs (
+            size_t i
+        ) const
+        {
+            DLIB_CASSERT(1 <= i && i <= num_nodes());
+            return nodes[i-1].inputs;
+        }
+
+        double get_learning_rate_multiplier () const  { return learning_rate_multiplier; }
+        double get_weight_decay_multiplier () const   { return weight_decay_multiplier; }
+        void set_learning_rate_multiplier(double val) { learning_rate_multiplier = val; }
+        void set_weight_decay_multiplier(double val)  { weight_decay_multiplier  = val; }
+
+        template <typename SUBNET>
+        void setup (const SUBNET& sub)
+        {
+            // Setting up a layer requires the outputs of the layers below it, so run the
+            // graph forward as we go.
+            for (size_t i = 0; i < nodes.size(); ++i)
+            {
+                auto& n = nodes[i];
+                const auto inputs = node_input_tensors(i, sub.get_output());
+                n.op->setup(inputs);
+                tensor& p = n.op->get_layer_params();
+                if (n.initial_params.size() != 0)
+                {
+                    if (n.initial_params.size() != p.size())
+                    {
+                        std::ostringstream sout;
+                        sout << "The " << n.op->type() << " layer in the dynamic_graph_ description has " << n.initial_params.size()
+                             << " parameter values but it needs " << p.size() << " for inputs with k()==" << inputs[0]->k()
+                             << ", nr()==" << inputs[0]->nr() << ", and nc()==" << inputs[0]->nc() << ".";
+                        throw serialization_error(sout.str());
+                    }
+                    std::copy(n.initial_params.begin(), n.initial_params.end(), p.host());
+                    n.initial_params.clear();
+                }
+                n.op->forward(inputs, n.output);
+            }
+            gather_params();
+        }
+
+        template <typename SUBNET>
+        void forward(const SUBNET& sub, resizable_tensor& output)
+        {
+            scatter_params();
+            for (size_t i = 0; i < nodes.size(); ++i)
+                nodes[i].op->forward(node_input_tensors(i, sub.get_output()), nodes[i].output);
+
+            if (nodes.size() == 0)
+                output = sub.get_output();
+            else
+                output = nodes.back().output;
+        }
+
+        template <typename SUBNET>
+        void backward(const tensor& gradient_input, SUBNET& sub, tensor& params_grad)
+        {
+            if (nodes.size() == 0)
+            {
+                tt::add(sub.get_gradient_input(), sub.get_gradient_input(), gradient_input);
+                return;
+            }
+
+            for (size_t i = 0; i+1 < nodes.size(); ++i)
+            {
+                nodes[i].grad.copy_size(nodes[i].output);
+                nodes[i].grad = 0;
+            }
+            // Layers with a learning rate multiplier of 0 don't bother to output their
+            // parameter gradients.
+            if (params_grad.size() != 0)
+                params_grad = 0;
+
+            for (size_t i = nodes.size(); i-- > 0;)
+            {
+                auto& n = nodes[i];
+                std::vector<tensor*> input_grads;
+                for (auto j : n.inputs)
+                    input_grads.push_back(j == 0 ? &sub.get_gradient_input() : &nodes[j-1].grad);
+                const tensor& g = i+1 == nodes.size() ? gradient_input : n.grad;
+
+                tensor& p = n.op->get_layer_params();
+                const alias_tensor pa(p.num_samples(), p.k(), p.nr(), p.nc());
+                auto pg = pa(params_grad, p.size() != 0 ? n.params_offset : 0);
+                n.op->backward(node_input_tensors(i, sub.get_output()), n.output, g, input_grads, pg);
+
+                const double mult = n.op->learning_rate_multiplier();
+                if (p.size() != 0 && mult != 1)
+                    tt::affine_transform(pg, pg, mult);
+            }
+        }
+
+        const tensor& get_layer_params() const { return params; }
+        tensor& get_layer_params() { params_stale = true; return params; }
+
+        void clean()
+        {
+            for (auto& n : nodes)
+            {
+                n.output.clear();
+                n.grad.clear();
+            }
+        }
+
+        void swap (dynamic_graph_& item)
+        {
+            std::swap(nodes, item.nodes);
+            std::swap(params, item.params);
+            std::swap(params_stale, item.params_stale);
+            std::swap(learning_rate_multiplier, item.learning_rate_multiplier);
+            std::swap(weight_decay_multiplier, item.weight_decay_multiplier);
+        }
+
+        friend void serialize(const dynamic_graph_& item, std::ostream& out)
+        {
+            // The layers are the ones that get saved, so make sure they hold the latest
+            // parameter values.
+            item.scatter_params();
+            serialize("dynamic_graph_", out);
+            serialize(item.learning_rate_multiplier, out);
+            serialize(item.weight_decay_multiplier, out);
+            serialize(item.nodes.size(), out);
+            for (auto& n : item.nodes)
+            {
+                serialize(n.op->type(), out);
+                n.op->serialize(out);
+                serialize(n.inputs, out);
+                serialize(n.initial_params, out);
+            }
+        }
+
+        friend void deserialize(dynamic_graph_& item, std::istream& in)
+        {
+            std::string version;
+            deserialize(version, in);
+            if (version != "dynamic_graph_")
+                throw serialization_error("Unexpected version '"+version+"' found while deserializing dlib::dynamic_graph_.");
+            dynamic_graph_ temp;
+            deserialize(temp.learning_rate_multiplier, in);
+            deserialize(temp.weight_decay_multiplier, in);
+            size_t num;
+            deserialize(num, in);
+            for (size_t i = 0; i < num; ++i)
+            {
+                std::string type;
+                deserialize(type, in);
+                node n;
+                n.op = impl::make_dynamic_graph_op(type, impl::dynamic_graph_attributes());
+                if (!n.op)
+                    throw serialization_error("Unknown layer type '"+type+"' found while deserializing dlib::dynamic_graph_.");
+                n.op->deserialize(in);
+                deserialize(n.inputs, in);
+                deserialize(n.initial_params, in);
+                for (auto j : n.inputs)
+                {
+                    if (j > i)
+                        throw serialization_error("Invalid graph found while deserializing dlib::dynamic_graph_.");
+                }
+                temp.nodes.push_back(std::move(n));
+            }
+            temp.gather_params();
+            item.swap(temp);
+        }
+
+        friend std::ostream& operator<<(std::ostream& out, const dynamic_graph_& item)
+        {
+            out << "dynamic_graph\t (nodes=" << item.nodes.size() << ")";
+            out << " learning_rate_mult="<<item.learning_rate_multiplier;
+            out << " weight_decay_mult="<<item.weight_decay_multiplier;
+            return out;
+        }
+
+        friend void to_xml(const dynamic_graph_& item, std::ostream& out)
+        {
+            item.scatter_params();
+
+            // The graph is written as a chain of layers, like net_to_xml() does, so any
+            // node that is used somewhere other than by the next node gets a tag.
+            const size_t num = item.nodes.size();
+            std::vector<unsigned long> tags(num+1, 0);
+            unsigned long next_tag = 1;
+            const auto need_tag = [&](size_t j) { if (tags[j] == 0) tags[j] = next_tag++; };
+            for (size_t i = 0; i < num; ++i)
+            {
+                const auto& n = item.nodes[i];
+                for (size_t j = 0; j < n.inputs.size(); ++j)
+                {
+                    if (j != 0 || !n.op->uses_main_input() || n.inputs[j] != i)
+                        need_tag(n.inputs[j]);
+                }
+            }
+
+            // Build the chain from the input side, then write it out starting with the
+            // layer closest to the loss, which is the order net_to_xml() uses.
+            std::vector<std::string> chain;
+            const auto tag_xml = [](unsigned long id) { return "<layer idx='0' type='tag' id='" + std::to_string(id) + "'/>\n"; };
+            if (tags[0] != 0)
+                chain.push_back(tag_xml(tags[0]));
+            size_t current = 0;
+            for (size_t i = 0; i < num; ++i)
+            {
+                const auto& n = item.nodes[i];
+                if (n.op->uses_main_input() && n.inputs[0] != current)
+                    chain.push_back("<layer idx='0' type='skip' id='" + std::to_string(tags[n.inputs[0]]) + "'/>\n");
+
+                std::vector<unsigned long> input_tags;
+                for (auto j : n.inputs)
+                    input_tags.push_back(tags[j]);
+                std::ostringstream sout;
+                sout.precision(out.precision());
+                sout << "<layer idx='0' type='comp'>\n";
+                n.op->to_xml(sout, input_tags);
+                sout << "</layer>\n";
+                chain.push_back(sout.str());
+                current = i+1;
+
+                if (tags[i+1] != 0)
+                    chain.push_back(tag_xml(tags[i+1]));
+            }
+
+            out << "<dynamic_graph learning_rate_mult='"<<item.learning_rate_multiplier<<"'"
+                << " weight_decay_mult='"<<item.weight_decay_multiplier<<"'>\n";
+            for (size_t i = chain.size(); i-- > 0;)
+                out << chain[i];
+            out << "</dynamic_graph>\n";
+        }
+
+    private:
+
+        struct node
+        {
+            node() = default;
+            node(const node& item) :
+                op(item.op ? item.op->clone() : nullptr),
+                inputs(item.inputs),
+                initial_params(item.initial_params),
+                params_offset(item.params_offset)
+            {}
+            node(node&&) = default;
+            node& operator=(node&&) = default;
+
+            std::unique_ptr<impl::dynamic_graph_op> op;
+            // Index 0 is the input to the graph and index i+1 is nodes[i].
+            std::vector<size_t> inputs;
+            // Parameter values from an XML description, loaded into the layer by setup().
+            std::vector<float> initial_params;
+            size_t params_offset = 0;
+            resizable_tensor output;
+            resizable_tensor grad;
+        };
+
+        std::vector<const tensor*> node_input_tensors (
+            size_t i,
+            const tensor& graph_input
+        ) const
+        {
+            std::vector<const tensor*> inputs;
+            for (auto j : nodes[i].inputs)
+                inputs.push_back(j == 0 ? &graph_input : &nodes[j-1].output);
+            return inputs;
+        }
+
+        void gather_params (
+        )
+        {
+            size_t total = 0;
+            for (auto& n : nodes)
+            {
+                n.params_offset = total;
+                total += n.op->get_layer_params().size();
+            }
+
+            params.set_size(total);
+            for (auto& n : nodes)
+            {
+                const tensor& p = n.op->get_layer_params();
+                if (p.size() != 0)
+                    std::copy(p.host(), p.host()+p.size(), params.host()+n.params_offset);
+            }
+            params_stale = false;
+        }
+
+        void scatter_params (
+        ) const
+        {
+            // The solvers update params, but the layers run on their own copies of the
+            // parameters.  So copy any new values into the layers before using them.
+            if (!params_stale)
+                return;
+            for (auto& n : nodes)
+            {
+                tensor& p = n.op->get_layer_params();
+                if (p.size() != 0)
+                    memcpy(p, alias_tensor(p.num_samples(), p.k(), p.nr(), p.nc())(params, n.params_offset));
+            }
+            params_stale = false;
+        }
+
+        void build (
+            const std::vector<impl::dynamic_graph_layer_spec>& specs
+        )
+        {
+            std::vector<node> new_nodes;
+            std::map<unsigned long, size_t> tagged;
+            size_t current = 0;
+
+            const auto tagged_node = [&](const impl::dynamic_graph_layer_spec& spec, const std::string& id) -> size_t
+            {
+                const auto i = tagged.find(std::stoul(id));
+                if (i == tagged.end())
+                {
+                    std::ostringstream sout;
+                    sout << "The layer on line " << spec.line << " refers to tag " << id << ", which doesn't come before it in the network.";
+                    throw serialization_error(sout.str());
+                }
+                return i->second;
+            };
+
+            // net_to_xml() writes the layer closest to the loss first, so walk the list
+            // backwards.
+            for (size_t s = specs.size(); s-- > 0;)
+            {
+                const auto& spec = specs[s];
+                if (spec.kind == "input" || spec.kind == "loss")
+                    continue;
+                if (spec.kind == "tag")
+                {
+                    tagged[impl::dynamic_graph_attribute(spec.attributes, "id", 0UL)] = current;
+                    continue;
+                }
+                if (spec.kind == "skip")
+                {
+                    current = tagged_node(spec, spec.attributes.at("id"));
+                    continue;
+                }
+                if (spec.kind != "comp")
+                {
+                    std::ostringstream sout;
+                    sout << "Unknown layer type '" << spec.kind << "' found on line " << spec.line << ".";
+                    throw serialization_error(sout.str());
+                }
+
+                node n;
+                n.op = impl::make_dynamic_graph_op(spec.name, spec.attributes);
+                if (!n.op)
+                {
+                    std::ostringstream sout;
+                    sout << "The " << spec.name << " layer on line " << spec.line << " isn't supported by dynamic_graph_.";
+                    throw serialization_error(sout.str());
+                }
+
+                if (spec.name == "add_prev" || spec.name == "mult_prev")
+                {
+                    n.inputs = {current, tagged_node(spec, spec.attributes.count("tag") ? spec.attributes.at("tag") : "")};
+                }
+                else if (spec.name == "concat")
+                {
+                    std::istringstream sin(spec.attributes.count("tags") ? spec.attributes.at("tags") : "");
+                    std::string id;
+                    while (std::getline(sin, id, ','))
+                        n.inputs.push_back(tagged_node(spec, id));
+                    if (n.inputs.size() == 0)
+                        throw serialization_error("A concat layer in a dynamic_graph_ description must list the tags it concatenates.");
+                }
+                else
+                {
+                    n.inputs = {current};
+                }
+                n.initial_params = spec.values;
+                new_nodes.push_back(std::move(n));
+                current = new_nodes.size();
+            }
+
+            // The output of the graph is the last layer, so a trailing skip layer would
+            // be silently ignored.
+            if (current != new_nodes.size())
+                throw serialization_error("A dynamic_graph_ description can't end with a skip layer.");
+
+            nodes = std::move(new_nodes);
+            params.clear();
+            params_stale = false;
+        }
+
+        std::vector<node> nodes;
+        resizable_tensor params;
+        mutable bool params_stale = false;
+        double learning_rate_multiplier;
+        double weight_decay_multiplier;
+    };
+
+    template <typename SUBNET>
+    using dynamic_graph = add_layer<dynamic_graph_, SUBNET>;
+
+// ----------------------------------------------------------------------------------------
+
+}
+
+#endif // DLIB_DNn_DYNAMIC_GRAPH_H_
+
@@ -0,0 +1,222 @@
+// Copyright (C) 2026  Davis E. King (davis@dlib.net)
+// License: Boost Software License   See LICENSE.txt for the full license.
+#undef DLIB_DNn_DYNAMIC_GRAPH_ABSTRACT_H_
+#ifdef DLIB_DNn_DYNAMIC_GRAPH_ABSTRACT_H_
+
+#include "layers_abstract.h"
+#include "visitors_abstract.h"
+#include <string>
+#include <vector>
+#include <iostream>
+
+namespace dlib
+{
+
+// ----------------------------------------------------------------------------------------
+
+    class dynamic_graph_
+    {
+        /*!
+            WHAT THIS OBJECT REPRESENTS
+                This is an implementation of the EXAMPLE_COMPUTATIONAL_LAYER_ interface
+                defined in layers_abstract.h.  In particular, it runs a network whose
+                layers are chosen at runtime rather than at compile time.  That is, it
+                holds a directed acyclic graph of layers that is built from a description
+                loaded by load_xml().  The description is the XML written by net_to_xml(),
+                so you can define a network as a normal dlib network type, save it with
+                net_to_xml(), and then load its architecture and parameters into a
+                dynamic_graph_ without having that network type available.
+
+                Since dnn_trainer needs the input and loss layers to be known at compile
+                time, a dynamic_graph_ is meant to sit between them.  For example:
+                    using net_type = loss_multiclass_log<dynamic_graph<input<matrix<float>>>>;
+                    net_type net;
+                    layer<1>(net).layer_details().load_xml("net.xml");
+                    dnn_trainer<net_type> trainer(net);
+                The input and loss layers in the XML are ignored and every computational,
+                tag, and skip layer between them becomes part of the graph.
+
+                The graph supports the following layers, with the same settings and
+                parameter layouts as their compile-time counterparts:
+                    - con, fc, fc_no_bias, bn_con, bn_fc, affine_con, affine_fc
+                    - max_pool, avg_pool, upsample
+                    - add_prev, mult_prev, concat
+                    - relu, prelu, leaky_relu, sig, htan, mish, gelu, silu, elu,
+                      clipped_relu, smelu, softmax, softmax_all, dropout, multiply,
+                      l2normalize
+                Note that net_to_xml() doesn't save the running statistics of bn_ layers, so
+                a network loaded from XML should be trained before its bn_ layers are
+                converted to affine_ layers.
+
+                THREAD SAFETY
+                    Like all dlib layers, it is not safe to touch the same dynamic_graph_
+                    from multiple threads at once.
+        !*/
+
+    public:
+
+        dynamic_graph_(
+        );
+        /*!
+            ensures
+                - #num_nodes() == 0, so this object is the identity function until
+                  load_xml() is called.
+                - #get_learning_rate_multiplier() == 1
+                - #get_weight_decay_multiplier() == 1
+        !*/
+
+        void load_xml (
+            std::istream& in
+        );
+        /*!
+            ensures
+                - Reads an XML network description, as written by net_to_xml(), from in and
+                  replaces the graph in *this with the layers it describes.
+                - Any parameter values in the XML are loaded into the layers the next time
+                  setup() is called.  Layers without parameter values in the XML are
+                  randomly initialized by setup(), just like normal layers.
+                - A <layer> holding a <dynamic_graph> element, like the ones written by
+                  to_xml(), is replaced by the layers inside it.  So the XML of a network
+                  containing a dynamic_graph_ can be loaded as well.
+                - The output of the graph is the output of the layer closest to the loss
+                  layer.
+            throws
+                - xml_parse_error if in doesn't contain valid XML.
+                - serialization_error if the XML contains a layer that isn't supported or
+                  a skip or add_prev layer that refers to a tag that doesn't exist.  If
+                  this happens then *this is unchanged.
+        !*/
+
+        void load_xml (
+            const std::string& filename
+        );
+        /*!
+            ensures
+                - Performs load_xml() on the contents of the given file.
+            throws
+                - serialization_error if the file can't be opened, along with the
+                  exceptions thrown by load_xml(std::istream&).
+        !*/
+
+        size_t num_nodes (
+        ) const;
+        /*!
+            ensures
+                - returns the number of layers in the graph.  Tag and skip layers are not
+                  counted since they only determine how the layers are connected.
+        !*/
+
+        std::string node_type (
+            size_t i
+        ) const;
+        /*!
+            requires
+                - 1 <= i <= num_nodes()
+            ensures
+                - returns the XML name of the i-th layer, e.g. "con" or "relu".  The layers
+                  are numbered in the order they run, from the input towards the loss.
+        !*/
+
+        const std::vector<size_t>& node_inputs (
+            size_t i
+        ) const;
+        /*!
+            requires
+                - 1 <= i <= num_nodes()
+            ensures
+                - returns the nodes whose outputs are the inputs of the i-th layer.  Node 0
+                  is the input to the graph, that is, the output of the subnetwork.  Every
+                  returned index is < i.
+                - add_prev and mult_prev layers have two inputs, the main input and the
+                  tagged tensor.  concat layers have one input per tag.  All other layers
+                  have one input.
+        !*/
+
+        double get_learning_rate_multiplier(
+        ) const;
+        /*!
+            ensures
+                - returns a multiplier number.  The interpretation is that this object is
+                  requesting that the learning rate used to optimize its parameters be
+                  multiplied by get_learning_rate_multiplier().  The learning rate
+                  multipliers of the individual layers in the graph are also applied.
+        !*/
+
+        double get_weight_decay_multiplier(
+        ) const;
+        /*!
+            ensures
+                - returns a multiplier number.  The interpretation is that this object is
+                  requesting that the weight decay used to optimize its parameters be
+                  multiplied by get_weight_decay_multiplier().  Since the solvers see all
+                  the parameters of the graph as one tensor, this multiplier is used for
+                  every layer in the graph and the weight decay multipliers of the
+                  individual layers are ignored during training.
+        !*/
+
+        void set_learning_rate_multiplier(
+            double val
+        );
+        /*!
+            requires
+                - val >= 0
+            ensures
+                - #get_learning_rate_multiplier() == val
+        !*/
+
+        void set_weight_decay_multiplier(
+            double val
+        );
+        /*!
+            requires
+                - val >= 0
+            ensures
+                - #get_weight_decay_multiplier() == val
+        !*/
+
+        void clean(
+        );
+        /*!
+            ensures
+                - Releases the outputs and gradients cached by the layers of the graph.
+                  This reduces the memory used by a network being saved to disk.
+        !*/
+
+        template <typename SUBNET> void setup (const SUBNET& sub);
+        template <typename SUBNET> void forward(const SUBNET& sub, resizable_tensor& output);
+        template <typename SUBNET> void backward(const tensor& gradient_input, SUBNET& sub, tensor& params_grad);
+        const tensor& get_layer_params() const;
+        tensor& get_layer_params();
+        /*!
+            These functions are implemented as described in the EXAMPLE_COMPUTATIONAL_LAYER_
+            interface.  setup() throws serialization_error if the parameter values loaded
+            by load_xml() don't match the size of the layer they belong to.  The layer
+            parameters are the parameters of all the layers in the graph, concatenated in
+            the order the layers run.
+        !*/
+    };
+
+    void serialize(const dynamic_graph_& item, std::ostream& out);
+    void deserialize(dynamic_graph_& item, std::istream& in);
+    /*!
+        provides serialization support.  The whole graph is saved, so a deserialized
+        dynamic_graph_ doesn't need the XML it was loaded from.
+    !*/
+
+    void to_xml(const dynamic_graph_& item, std::ostream& out);
+    /*!
+        ensures
+            - Writes the graph to out as a <dynamic_graph> element holding the graph's
+              layers in the format written by net_to_xml().  So net_to_xml() of a network
+              containing a dynamic_graph_ can be read back by load_xml().
+    !*/
+
+    template <typename SUBNET>
+    using dynamic_graph = add_layer<dynamic_graph_, SUBNET>;
+
+// ----------------------------------------------------------------------------------------
+
+}
+
+#endif // DLIB_DNn_DYNAMIC_GRAPH_ABSTRACT_H_
+
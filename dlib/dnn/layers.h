@@ -34,7 +34,10 @@ namespace dlib
         int _stride_y,
         int _stride_x,
         int _padding_y = _stride_y!=1? 0 : _nr/2,
-        int _padding_x = _stride_x!=1? 0 : _nc/2
+        int _padding_x = _stride_x!=1? 0 : _nc/2,
+        int _dilation_y = 1,
+        int _dilation_x = 1,
+        long _groups = 1
         >
     class con_
     {
@@ -45,10 +48,16 @@ namespace dlib
         static_assert(_nc >= 0, "The number of columns in a filter must be >= 0");
         static_assert(_stride_y > 0, "The filter stride must be > 0");
         static_assert(_stride_x > 0, "The filter stride must be > 0");
-        static_assert(_nr==0 || (0 <= _padding_y && _padding_y < _nr), "The padding must be smaller than the filter size.");
-        static_assert(_nc==0 || (0 <= _padding_x && _padding_x < _nc), "The padding must be smaller than the filter size.");
+        static_assert(_dilation_y > 0, "The filter dilation must be > 0");
+        static_assert(_dilation_x > 0, "The filter dilation must be > 0");
+        static_assert(_groups > 0, "The number of groups must be > 0");
+        static_assert(_num_filters%_groups == 0, "The number of filters must be divisible by the number of groups");
+        static_assert(_nr==0 || (0 <= _padding_y && _padding_y < _dilation_y*(_nr-1)+1), "The padding must be smaller than the dilated filter size.");
+        static_assert(_nc==0 || (0 <= _padding_x && _padding_x < _dilation_x*(_nc-1)+1), "The padding must be smaller than the dilated filter size.");
         static_assert(_nr!=0 || 0 == _padding_y, "If _nr==0 then the padding must be set to 0 as well.");
         static_assert(_nc!=0 || 0 == _padding_x, "If _nr==0 then the padding must be set to 0 as well.");
+        static_assert(_nr!=0 || 1 == _dilation_y, "If _nr==0 then the dilation must be set to 1 as well.");
+        static_assert(_nc!=0 || 1 == _dilation_x, "If _nc==0 then the dilation must be set to 1 as well.");
 
         con_(
             num_con_outputs o
@@ -87,10 +96,14 @@ namespace dlib
         long stride_x() const { return _stride_x; }
         long padding_y() const { return padding_y_; }
         long padding_x() const { return padding_x_; }
+        long dilation_y() const { return _dilation_y; }
+        long dilation_x() const { return _dilation_x; }
+        long groups() const { return _groups; }
 
         void set_num_filters(long num) 
         {
             DLIB_CASSERT(num > 0);
+            DLIB_CASSERT(num%_groups == 0, "The number of filters must be divisible by the number of groups.");
             if (num != num_filters_)
             {
                 DLIB_CASSERT(get_layer_params().size() == 0, 
@@ -156,12 +169,91 @@ namespace dlib
             biases(params, filters.size()) = 0;
         }
 
+        bool is_quantized() const { return int8_filters.size() != 0; }
+
+        void quantize_to_int8 (
+            float input_range
+        )
+        {
+            DLIB_CASSERT(input_range >= 0);
+            DLIB_CASSERT(!is_quantized());
+            DLIB_CASSERT(params.size() != 0, "The con_ layer must be allocated before it can be quantized.");
+
+            int8_filters.assign(filters(params,0));
+            if (use_bias)
+            {
+                auto b = biases(params, filters.size());
+                int8_biases.copy_size(b);
+                memcpy(int8_biases, b);
+            }
+            int8_input_scale = int8_scale_for_range(input_range);
+            // The float parameters aren't needed anymore.  Getting rid of them is the
+            // whole point, since quantized networks are used where memory is tight.
+            params.clear();
+        }
+
+        float get_int8_input_scale() const { return int8_input_scale; }
+
+        void prune_filters (
+            const std::vector<long>& keep
+        )
+        {
+            DLIB_CASSERT(_groups == 1, "Only con_ layers without groups can be pruned.");
+            DLIB_CASSERT(params.size() != 0 && !is_quantized(), "The con_ layer must be allocated and not quantized to be pruned.");
+            DLIB_CASSERT(keep.size() > 0);
+
+            const long filter_size = filters.size()/num_filters_;
+            const resizable_tensor temp(params);
+            const alias_tensor new_filters(keep.size(), filters.k(), filters.nr(), filters.nc());
+            params.set_size(new_filters.size() + static_cast<int>(use_bias)*keep.size());
+            for (size_t i = 0; i < keep.size(); ++i)
+            {
+                DLIB_CASSERT(0 <= keep[i] && keep[i] < num_filters_ && (i == 0 || keep[i-1] < keep[i]),
+                    "The filters to keep must be sorted and in the range [0, num_filters()).");
+                std::copy(temp.host() + keep[i]*filter_size, temp.host() + (keep[i]+1)*filter_size,
+                    params.host() + i*filter_size);
+                if (use_bias)
+                    params.host()[new_filters.size()+i] = temp.host()[filters.size()+keep[i]];
+            }
+            filters = new_filters;
+            num_filters_ = keep.size();
+            if (use_bias)
+                biases = alias_tensor(1, num_filters_);
+        }
+
+        void prune_input_channels (
+            const std::vector<long>& keep
+        )
+        {
+            DLIB_CASSERT(_groups == 1, "Only con_ layers without groups can be pruned.");
+            DLIB_CASSERT(params.size() != 0 && !is_quantized(), "The con_ layer must be allocated and not quantized to be pruned.");
+            DLIB_CASSERT(keep.size() > 0);
+
+            const long channel_size = filters.nr()*filters.nc();
+            const resizable_tensor temp(params);
+            const alias_tensor new_filters(num_filters_, keep.size(), filters.nr(), filters.nc());
+            params.set_size(new_filters.size() + static_cast<int>(use_bias)*num_filters_);
+            for (long n = 0; n < num_filters_; ++n)
+            {
+                for (size_t i = 0; i < keep.size(); ++i)
+                {
+                    DLIB_CASSERT(0 <= keep[i] && keep[i] < filters.k() && (i == 0 || keep[i-1] < keep[i]),
+                        "The channels to keep must be sorted and in the range of the input channels.");
+                    const float* src = temp.host() + (n*filters.k() + keep[i])*channel_size;
+                    std::copy(src, src + channel_size, params.host() + (n*keep.size() + i)*channel_size);
+                }
+            }
+            if (use_bias)
+                std::copy(temp.host() + filters.size(), temp.host() + temp.size(), params.host() + new_filters.size());
+            filters = new_filters;
+        }
+
         inline dpoint map_input_to_output (
             dpoint p
         ) const
         {
-            p.x() = (p.x()+padding_x()-nc()/2)/stride_x();
-            p.y() = (p.y()+padding_y()-nr()/2)/stride_y();
+            p.x() = (p.x()+padding_x()-dilation_x()*(nc()/2))/stride_x();
+            p.y() = (p.y()+padding_y()-dilation_y()*(nr()/2))/stride_y();
             return p;
         }
 
@@ -169,8 +261,8 @@ namespace dlib
             dpoint p
         ) const
         {
-            p.x() = p.x()*stride_x() - padding_x() + nc()/2;
-            p.y() = p.y()*stride_y() - padding_y() + nr()/2;
+            p.x() = p.x()*stride_x() - padding_x() + dilation_x()*(nc()/2);
+            p.y() = p.y()*stride_y() - padding_y() + dilation_y()*(nr()/2);
             return p;
         }
 
@@ -188,7 +280,10 @@ namespace dlib
             padding_y_(item.padding_y_),
             padding_x_(item.padding_x_),
             use_bias(item.use_bias),
-            use_relu(item.use_relu)
+            use_relu(item.use_relu),
+            int8_filters(item.int8_filters),
+            int8_biases(item.int8_biases),
+            int8_input_scale(item.int8_input_scale)
         {
             // this->conv is non-copyable and basically stateless, so we have to write our
             // own copy to avoid trying to copy it and getting an error.
@@ -215,6 +310,9 @@ namespace dlib
             num_filters_ = item.num_filters_;
             use_bias = item.use_bias;
             use_relu = item.use_relu;
+            int8_filters = item.int8_filters;
+            int8_biases = item.int8_biases;
+            int8_input_scale = item.int8_input_scale;
             return *this;
         }
 
@@ -223,8 +321,13 @@ namespace dlib
         {
             const long filt_nr = _nr!=0 ? _nr : sub.get_output().nr();
             const long filt_nc = _nc!=0 ? _nc : sub.get_output().nc();
+            DLIB_CASSERT(sub.get_output().k()%_groups == 0,
+                "The number of input channels must be divisible by the number of groups.");
+            DLIB_CASSERT(num_filters_%_groups == 0,
+                "The number of filters must be divisible by the number of groups.");
 
-            long num_inputs = filt_nr*filt_nc*sub.get_output().k();
+            // Each filter only looks at the k()/_groups channels of its own group.
+            long num_inputs = filt_nr*filt_nc*sub.get_output().k()/_groups;
             long num_outputs = num_filters_;
             // allocate params for the filters and also for the filter bias values.
             params.set_size(num_inputs*num_filters_ + static_cast<int>(use_bias) * num_filters_);
@@ -232,7 +335,7 @@ namespace dlib
             dlib::rand rnd(std::rand());
             randomize_parameters(params, num_inputs+num_outputs, rnd);
 
-            filters = alias_tensor(num_filters_, sub.get_output().k(), filt_nr, filt_nc);
+            filters = alias_tensor(num_filters_, sub.get_output().k()/_groups, filt_nr, filt_nc);
             if (use_bias)
             {
                 biases = alias_tensor(1,num_filters_);
@@ -244,12 +347,22 @@ namespace dlib
         template <typename SUBNET>
         void forward(const SUBNET& sub, resizable_tensor& output)
         {
+            if (is_quantized())
+            {
+                tt::int8_conv(output, sub.get_output(), int8_input_scale, int8_filters, int8_biases,
+                    _stride_y, _stride_x, padding_y_, padding_x_, _dilation_y, _dilation_x, _groups, use_relu);
+                return;
+            }
+
             conv.setup(sub.get_output(),
                        filters(params,0),
                        _stride_y,
                        _stride_x,
                        padding_y_,
-                       padding_x_);
+                       padding_x_,
+                       _dilation_y,
+                       _dilation_x,
+                       _groups);
 
             if (use_bias)
             {
@@ -270,6 +383,7 @@ namespace dlib
         template <typename SUBNET>
         void backward(const tensor& gradient_input, SUBNET& sub, tensor& params_grad)
         {
+            DLIB_CASSERT(!is_quantized(), "A con_ layer can't be trained after it has been quantized.");
             conv.get_gradient_for_data (true, gradient_input, filters(params,0), sub.get_gradient_input());
             // no point computing the parameter gradients if they won't be used.
             if (learning_rate_multiplier != 0)
@@ -289,7 +403,7 @@ namespace dlib
 
         friend void serialize(const con_& item, std::ostream& out)
         {
-            serialize("con_6", out);
+            serialize("con_8", out);
             serialize(item.params, out);
             serialize(item.num_filters_, out);
             serialize(_nr, out);
@@ -306,6 +420,12 @@ namespace dlib
             serialize(item.bias_weight_decay_multiplier, out);
             serialize(item.use_bias, out);
             serialize(item.use_relu, out);
+            serialize(_dilation_y, out);
+            serialize(_dilation_x, out);
+            serialize(_groups, out);
+            serialize(item.int8_filters, out);
+            serialize(item.int8_biases, out);
+            serialize(item.int8_input_scale, out);
         }
 
         friend void deserialize(con_& item, std::istream& in)
@@ -316,7 +436,10 @@ namespace dlib
             long nc;
             int stride_y;
             int stride_x;
-            if (version == "con_4" || version == "con_5" || version == "con_6")
+            int dilation_y = 1;
+            int dilation_x = 1;
+            long groups = 1;
+            if (version == "con_4" || version == "con_5" || version == "con_6" || version == "con_7" || version == "con_8")
             {
                 deserialize(item.params, in);
                 deserialize(item.num_filters_, in);
@@ -338,14 +461,35 @@ namespace dlib
                 if (nc != _nc) throw serialization_error("Wrong nc found while deserializing dlib::con_");
                 if (stride_y != _stride_y) throw serialization_error("Wrong stride_y found while deserializing dlib::con_");
                 if (stride_x != _stride_x) throw serialization_error("Wrong stride_x found while deserializing dlib::con_");
-                if (version == "con_5" || version == "con_6")
+                if (version == "con_5" || version == "con_6" || version == "con_7" || version == "con_8")
                 {
                     deserialize(item.use_bias, in);
                 }
-                if (version == "con_6")
+                if (version == "con_6" || version == "con_7" || version == "con_8")
                 {
                     deserialize(item.use_relu, in);
                 }
+                if (version == "con_7" || version == "con_8")
+                {
+                    deserialize(dilation_y, in);
+                    deserialize(dilation_x, in);
+                    deserialize(groups, in);
+                }
+                if (version == "con_8")
+                {
+                    deserialize(item.int8_filters, in);
+                    deserialize(item.int8_biases, in);
+                    deserialize(item.int8_input_scale, in);
+                }
+                else
+                {
+                    item.int8_filters.clear();
+                    item.int8_biases.clear();
+                    item.int8_input_scale = 0;
+                }
+                if (dilation_y != _dilation_y) throw serialization_error("Wrong dilation_y found while deserializing dlib::con_");
+                if (dilation_x != _dilation_x) throw serialization_error("Wrong dilation_x found while deserializing dlib::con_");
+                if (groups != _groups) throw serialization_error("Wrong groups found while deserializing dlib::con_");
             }
             else
             {
@@ -363,8 +507,15 @@ namespace dlib
                 << ", stride_y="<<_stride_y
                 << ", stride_x="<<_stride_x
                 << ", padding_y="<<item.padding_y_
-                << ", padding_x="<<item.padding_x_
-                << ")";
+                << ", padding_x="<<item.padding_x_;
+            if (_dilation_y != 1 || _dilation_x != 1)
+            {
+                out << ", dilation_y="<<_dilation_y
+                    << ", dilation_x="<<_dilation_x;
+            }
+            if (_groups != 1)
+                out << ", groups="<<_groups;
+            out << ")";
             out << " learning_rate_mult="<<item.learning_rate_multiplier;
             out << " weight_decay_mult="<<item.weight_decay_multiplier;
             if (item.use_bias)
@@ -380,6 +531,10 @@ namespace dlib
             {
                 out << " use_relu="<< std::boolalpha << item.use_relu;
             }
+            if (item.is_quantized())
+            {
+                out << " int8";
+            }
             return out;
         }
 
@@ -393,6 +548,9 @@ namespace dlib
                 << " stride_x='"<<_stride_x<<"'"
                 << " padding_y='"<<item.padding_y_<<"'"
                 << " padding_x='"<<item.padding_x_<<"'"
+                << " dilation_y='"<<_dilation_y<<"'"
+                << " dilation_x='"<<_dilation_x<<"'"
+                << " groups='"<<_groups<<"'"
                 << " learning_rate_mult='"<<item.learning_rate_multiplier<<"'"
                 << " weight_decay_mult='"<<item.weight_decay_multiplier<<"'"
                 << " bias_learning_rate_mult='"<<item.bias_learning_rate_multiplier<<"'"
@@ -422,6 +580,9 @@ namespace dlib
         int padding_x_;
         bool use_bias;
         bool use_relu;
+        int8_tensor int8_filters;
+        resizable_tensor int8_biases;
+        float int8_input_scale = 0;
     };
 
     template <
@@ -434,6 +595,35 @@ namespace dlib
         >
     using con = add_layer<con_<num_filters,nr,nc,stride_y,stride_x>, SUBNET>;
 
+    template <
+        long num_filters,
+        long nr,
+        long nc,
+        int stride_y,
+        int stride_x,
+        int dilation_y,
+        int dilation_x,
+        typename SUBNET
+        >
+    using dilated_con = add_layer<con_<num_filters,nr,nc,stride_y,stride_x,
+        stride_y!=1? 0 : dilation_y*(nr/2),
+        stride_x!=1? 0 : dilation_x*(nc/2),
+        dilation_y,dilation_x>, SUBNET>;
+
+    template <
+        long num_filters,
+        long nr,
+        long nc,
+        int stride_y,
+        int stride_x,
+        long groups,
+        typename SUBNET
+        >
+    using grouped_con = add_layer<con_<num_filters,nr,nc,stride_y,stride_x,
+        stride_y!=1? 0 : nr/2,
+        stride_x!=1? 0 : nc/2,
+        1,1,groups>, SUBNET>;
+
 // ----------------------------------------------------------------------------------------
 
     template <
@@ -443,7 +633,10 @@ namespace dlib
         int _stride_y,
         int _stride_x,
         int _padding_y = _stride_y!=1? 0 : _nr/2,
-        int _padding_x = _stride_x!=1? 0 : _nc/2
+        int _padding_x = _stride_x!=1? 0 : _nc/2,
+        int _dilation_y = 1,
+        int _dilation_x = 1,
+        long _groups = 1
         >
     class cont_
     {
@@ -454,8 +647,12 @@ namespace dlib
         static_assert(_nc > 0, "The number of columns in a filter must be > 0");
         static_assert(_stride_y > 0, "The filter stride must be > 0");
         static_assert(_stride_x > 0, "The filter stride must be > 0");
-        static_assert(0 <= _padding_y && _padding_y < _nr, "The padding must be smaller than the filter size.");
-        static_assert(0 <= _padding_x && _padding_x < _nc, "The padding must be smaller than the filter size.");
+        static_assert(_dilation_y > 0, "The filter dilation must be > 0");
+        static_assert(_dilation_x > 0, "The filter dilation must be > 0");
+        static_assert(_groups > 0, "The number of groups must be > 0");
+        static_assert(_num_filters%_groups == 0, "The number of filters must be divisible by the number of groups");
+        static_assert(0 <= _padding_y && _padding_y < _dilation_y*(_nr-1)+1, "The padding must be smaller than the dilated filter size.");
+        static_assert(0 <= _padding_x && _padding_x < _dilation_x*(_nc-1)+1, "The padding must be smaller than the dilated filter size.");
 
         cont_(
             num_con_outputs o
@@ -481,10 +678,14 @@ namespace dlib
         long stride_x() const { return _stride_x; }
         long padding_y() const { return padding_y_; }
         long padding_x() const { return padding_x_; }
+        long dilation_y() const { return _dilation_y; }
+        long dilation_x() const { return _dilation_x; }
+        long groups() const { return _groups; }
 
         void set_num_filters(long num)
         {
             DLIB_CASSERT(num > 0);
+            DLIB_CASSERT(num%_groups == 0, "The number of filters must be divisible by the number of groups.");
             if (num != num_filters_)
             {
                 DLIB_CASSERT(get_layer_params().size() == 0,
@@ -509,8 +710,8 @@ namespace dlib
             dpoint p
         ) const
         {
-            p.x() = (p.x()+padding_x()-nc()/2)/stride_x();
-            p.y() = (p.y()+padding_y()-nr()/2)/stride_y();
+            p.x() = (p.x()+padding_x()-dilation_x()*(nc()/2))/stride_x();
+            p.y() = (p.y()+padding_y()-dilation_y()*(nr()/2))/stride_y();
             return p;
         }
 
@@ -518,8 +719,8 @@ namespace dlib
             dpoint p
         ) const
         {
-            p.x() = p.x()*stride_x() - padding_x() + nc()/2;
-            p.y() = p.y()*stride_y() - padding_y() + nr()/2;
+            p.x() = p.x()*stride_x() - padding_x() + dilation_x()*(nc()/2);
+            p.y() = p.y()*stride_y() - padding_y() + dilation_y()*(nr()/2);
             return p;
         }
 
@@ -568,7 +769,12 @@ namespace dlib
         template <typename SUBNET>
         void setup (const SUBNET& sub)
         {
-            long num_inputs = _nr*_nc*sub.get_output().k();
+            DLIB_CASSERT(sub.get_output().k()%_groups == 0,
+                "The number of input channels must be divisible by the number of groups.");
+            DLIB_CASSERT(num_filters_%_groups == 0,
+                "The number of filters must be divisible by the number of groups.");
+
+            long num_inputs = _nr*_nc*sub.get_output().k()/_groups;
             long num_outputs = num_filters_;
             // allocate params for the filters and also for the filter bias values.
             params.set_size(num_inputs*num_filters_ + num_filters_ * static_cast<int>(use_bias));
@@ -576,7 +782,7 @@ namespace dlib
             dlib::rand rnd(std::rand());
             randomize_parameters(params, num_inputs+num_outputs, rnd);
 
-            filters = alias_tensor(sub.get_output().k(), num_filters_, _nr, _nc);
+            filters = alias_tensor(sub.get_output().k(), num_filters_/_groups, _nr, _nc);
             if (use_bias)
             {
                 biases = alias_tensor(1,num_filters_);
@@ -589,12 +795,12 @@ namespace dlib
         void forward(const SUBNET& sub, resizable_tensor& output)
         {
             auto filt = filters(params,0);
-            unsigned int gnr = _stride_y * (sub.get_output().nr() - 1) + filt.nr() - 2 * padding_y_;
-            unsigned int gnc = _stride_x * (sub.get_output().nc() - 1) + filt.nc() - 2 * padding_x_;
+            unsigned int gnr = _stride_y * (sub.get_output().nr() - 1) + _dilation_y * (filt.nr() - 1) + 1 - 2 * padding_y_;
+            unsigned int gnc = _stride_x * (sub.get_output().nc() - 1) + _dilation_x * (filt.nc() - 1) + 1 - 2 * padding_x_;
             unsigned int gnsamps = sub.get_output().num_samples();
-            unsigned int gk = filt.k();
+            unsigned int gk = filt.k()*_groups;
             output.set_size(gnsamps,gk,gnr,gnc);
-            conv.setup(output,filt,_stride_y,_stride_x,padding_y_,padding_x_);
+            conv.setup(output,filt,_stride_y,_stride_x,padding_y_,padding_x_,_dilation_y,_dilation_x,_groups);
             conv.get_gradient_for_data(false, sub.get_output(),filt,output);            
             if (use_bias)
             {
@@ -625,7 +831,7 @@ namespace dlib
 
         friend void serialize(const cont_& item, std::ostream& out)
         {
-            serialize("cont_2", out);
+            serialize("cont_3", out);
             serialize(item.params, out);
             serialize(item.num_filters_, out);
             serialize(_nr, out);
@@ -641,6 +847,9 @@ namespace dlib
             serialize(item.bias_learning_rate_multiplier, out);
             serialize(item.bias_weight_decay_multiplier, out);
             serialize(item.use_bias, out);
+            serialize(_dilation_y, out);
+            serialize(_dilation_x, out);
+            serialize(_groups, out);
         }
 
         friend void deserialize(cont_& item, std::istream& in)
@@ -651,7 +860,10 @@ namespace dlib
             long nc;
             int stride_y;
             int stride_x;
-            if (version == "cont_1" || version == "cont_2")
+            int dilation_y = 1;
+            int dilation_x = 1;
+            long groups = 1;
+            if (version == "cont_1" || version == "cont_2" || version == "cont_3")
             {
                 deserialize(item.params, in);
                 deserialize(item.num_filters_, in);
@@ -673,10 +885,19 @@ namespace dlib
                 if (nc != _nc) throw serialization_error("Wrong nc found while deserializing dlib::con_");
                 if (stride_y != _stride_y) throw serialization_error("Wrong stride_y found while deserializing dlib::con_");
                 if (stride_x != _stride_x) throw serialization_error("Wrong stride_x found while deserializing dlib::con_");
-                if (version == "cont_2")
+                if (version == "cont_2" || version == "cont_3")
                 {
                     deserialize(item.use_bias, in);
                 }
+                if (version == "cont_3")
+                {
+                    deserialize(dilation_y, in);
+                    deserialize(dilation_x, in);
+                    deserialize(groups, in);
+                }
+                if (dilation_y != _dilation_y) throw serialization_error("Wrong dilation_y found while deserializing dlib::cont_");
+                if (dilation_x != _dilation_x) throw serialization_error("Wrong dilation_x found while deserializing dlib::cont_");
+                if (groups != _groups) throw serialization_error("Wrong groups found while deserializing dlib::cont_");
             }
             else
             {
@@ -694,8 +915,15 @@ namespace dlib
                 << ", stride_y="<<_stride_y
                 << ", stride_x="<<_stride_x
                 << ", padding_y="<<item.padding_y_
-                << ", padding_x="<<item.padding_x_
-                << ")";
+                << ", padding_x="<<item.padding_x_;
+            if (_dilation_y != 1 || _dilation_x != 1)
+            {
+                out << ", dilation_y="<<_dilation_y
+                    << ", dilation_x="<<_dilation_x;
+            }
+            if (_groups != 1)
+                out << ", groups="<<_groups;
+            out << ")";
             out << " learning_rate_mult="<<item.learning_rate_multiplier;
             out << " weight_decay_mult="<<item.weight_decay_multiplier;
             if (item.use_bias)
@@ -720,6 +948,9 @@ namespace dlib
                 << " stride_x='"<<_stride_x<<"'"
                 << " padding_y='"<<item.padding_y_<<"'"
                 << " padding_x='"<<item.padding_x_<<"'"
+                << " dilation_y='"<<_dilation_y<<"'"
+                << " dilation_x='"<<_dilation_x<<"'"
+                << " groups='"<<_groups<<"'"
                 << " learning_rate_mult='"<<item.learning_rate_multiplier<<"'"
                 << " weight_decay_mult='"<<item.weight_decay_multiplier<<"'"
                 << " bias_learning_rate_mult='"<<item.bias_learning_rate_multiplier<<"'"
@@ -1670,6 +1901,40 @@ namespace dlib
         }
         double get_eps() const { return eps; }
 
+        const tensor& get_running_means() const { return running_means; }
+        tensor& get_running_means() { return running_means; }
+        const tensor& get_running_variances() const { return running_variances; }
+        tensor& get_running_variances() { return running_variances; }
+
+        void prune_channels (
+            const std::vector<long>& keep
+        )
+        {
+            DLIB_CASSERT(mode == CONV_MODE, "Only bn_ layers in CONV_MODE can be pruned.");
+            DLIB_CASSERT(params.size() != 0, "The bn_ layer must be allocated to be pruned.");
+            DLIB_CASSERT(keep.size() > 0);
+
+            const long k = gamma.size();
+            const resizable_tensor temp(params), temp_means(running_means), temp_variances(running_variances);
+            gamma = alias_tensor(1, keep.size());
+            beta = gamma;
+            params.set_size(2*keep.size());
+            running_means.set_size(1, keep.size());
+            running_variances.set_size(1, keep.size());
+            for (size_t i = 0; i < keep.size(); ++i)
+            {
+                DLIB_CASSERT(0 <= keep[i] && keep[i] < k && (i == 0 || keep[i-1] < keep[i]),
+                    "The channels to keep must be sorted and in the range of the input channels.");
+                params.host()[i] = temp.host()[keep[i]];
+                params.host()[keep.size()+i] = temp.host()[k+keep[i]];
+                running_means.host()[i] = temp_means.host()[keep[i]];
+                running_variances.host()[i] = temp_variances.host()[keep[i]];
+            }
+            // These are recomputed by each forward pass in training mode.
+            means.clear();
+            invstds.clear();
+        }
+
         double get_learning_rate_multiplier () const  { return learning_rate_multiplier; }
         double get_weight_decay_multiplier () const   { return weight_decay_multiplier; }
         void set_learning_rate_multiplier(double val) { learning_rate_multiplier = val; }
@@ -1928,6 +2193,33 @@ namespace dlib
         fc_bias_mode get_bias_mode (
         ) const { return bias_mode; }
 
+        bool is_quantized() const { return int8_weights.size() != 0; }
+
+        void quantize_to_int8 (
+            float input_range
+        )
+        {
+            DLIB_CASSERT(input_range >= 0);
+            DLIB_CASSERT(!is_quantized());
+            DLIB_CASSERT(params.size() != 0, "The fc_ layer must be allocated before it can be quantized.");
+
+            // Each output gets its own scale, so we store the weights with one row per
+            // output.  That's the transpose of how they are laid out in params.
+            resizable_tensor w(num_outputs, num_inputs);
+            w = trans(mat(weights(params,0)));
+            int8_weights.assign(w);
+            if (bias_mode == FC_HAS_BIAS && use_bias)
+            {
+                auto b = biases(params, weights.size());
+                int8_biases.copy_size(b);
+                memcpy(int8_biases, b);
+            }
+            int8_input_scale = int8_scale_for_range(input_range);
+            params.clear();
+        }
+
+        float get_int8_input_scale() const { return int8_input_scale; }
+
         template <typename SUBNET>
         void setup (const SUBNET& sub)
         {
@@ -1955,6 +2247,12 @@ namespace dlib
         {
             DLIB_CASSERT((long)num_inputs == sub.get_output().nr()*sub.get_output().nc()*sub.get_output().k(),
                 "The size of the input tensor to this fc layer doesn't match the size the fc layer was trained with.");
+            if (is_quantized())
+            {
+                tt::int8_fc(output, sub.get_output(), int8_input_scale, int8_weights, int8_biases);
+                return;
+            }
+
             output.set_size(sub.get_output().num_samples(), num_outputs);
 
             auto w = weights(params, 0);
@@ -1969,6 +2267,7 @@ namespace dlib
         template <typename SUBNET>
         void backward(const tensor& gradient_input, SUBNET& sub, tensor& params_grad)
         {
+            DLIB_CASSERT(!is_quantized(), "A fc_ layer can't be trained after it has been quantized.");
             // no point computing the parameter gradients if they won't be used.
             if (learning_rate_multiplier != 0)
             {
@@ -2018,7 +2317,7 @@ namespace dlib
 
         friend void serialize(const fc_& item, std::ostream& out)
         {
-            serialize("fc_3", out);
+            serialize("fc_4", out);
             serialize(item.num_outputs, out);
             serialize(item.num_inputs, out);
             serialize(item.params, out);
@@ -2030,13 +2329,16 @@ namespace dlib
             serialize(item.bias_learning_rate_multiplier, out);
             serialize(item.bias_weight_decay_multiplier, out);
             serialize(item.use_bias, out);
+            serialize(item.int8_weights, out);
+            serialize(item.int8_biases, out);
+            serialize(item.int8_input_scale, out);
         }
 
         friend void deserialize(fc_& item, std::istream& in)
         {
             std::string version;
             deserialize(version, in);
-            if (version == "fc_2" || version == "fc_3")
+            if (version == "fc_2" || version == "fc_3" || version == "fc_4")
             {
                 deserialize(item.num_outputs, in);
                 deserialize(item.num_inputs, in);
@@ -2050,10 +2352,22 @@ namespace dlib
                 deserialize(item.weight_decay_multiplier, in);
                 deserialize(item.bias_learning_rate_multiplier, in);
                 deserialize(item.bias_weight_decay_multiplier, in);
-                if (version == "fc_3")
+                if (version == "fc_3" || version == "fc_4")
                 {
                     deserialize(item.use_bias, in);
                 }
+                if (version == "fc_4")
+                {
+                    deserialize(item.int8_weights, in);
+                    deserialize(item.int8_biases, in);
+                    deserialize(item.int8_input_scale, in);
+                }
+                else
+                {
+                    item.int8_weights.clear();
+                    item.int8_biases.clear();
+                    item.int8_input_scale = 0;
+                }
             }
             else
             {
@@ -2088,6 +2402,10 @@ namespace dlib
                 out << " learning_rate_mult="<<item.learning_rate_multiplier;
                 out << " weight_decay_mult="<<item.weight_decay_multiplier;
             }
+            if (item.is_quantized())
+            {
+                out << " int8";
+            }
             return out;
         }
 
@@ -2128,6 +2446,9 @@ namespace dlib
         double bias_learning_rate_multiplier;
         double bias_weight_decay_multiplier;
         bool use_bias;
+        int8_tensor int8_weights;
+        resizable_tensor int8_biases;
+        float int8_input_scale = 0;
     };
 
     template <
@@ -2423,6 +2744,28 @@ namespace dlib
 
         bool is_disabled() const { return disabled; }
 
+        void prune_channels (
+            const std::vector<long>& keep
+        )
+        {
+            DLIB_CASSERT(mode == CONV_MODE, "Only affine_ layers in CONV_MODE can be pruned.");
+            DLIB_CASSERT(!disabled && params.size() != 0, "The affine_ layer must be allocated and not disabled to be pruned.");
+            DLIB_CASSERT(keep.size() > 0);
+
+            const long k = gamma.size();
+            const resizable_tensor temp(params);
+            gamma = alias_tensor(1, keep.size());
+            beta = gamma;
+            params.set_size(2*keep.size());
+            for (size_t i = 0; i < keep.size(); ++i)
+            {
+                DLIB_CASSERT(0 <= keep[i] && keep[i] < k && (i == 0 || keep[i-1] < keep[i]),
+                    "The channels to keep must be sorted and in the range of the input channels.");
+                params.host()[i] = temp.host()[keep[i]];
+                params.host()[keep.size()+i] = temp.host()[k+keep[i]];
+            }
+        }
+
         inline dpoint map_input_to_output (const dpoint& p) const { return p; }
         inline dpoint map_output_to_input (const dpoint& p) const { return p; }
 
@@ -5084,6 +5427,1010 @@ namespace dlib
     template <long diag, long num, long den, typename SUBNET>
     using tril_diag = add_layer<tril_<diag, void, num, den>, SUBNET>;
 
+// ----------------------------------------------------------------------------------------
+
+    namespace impl
+    {
+        inline double rnn_sigmoid(double x) { return 1/(1 + std::exp(-x)); }
+
+        inline void randomize_recurrent_parameters (
+            tensor& params,
+            unsigned long num_outputs
+        )
+        {
+            // The usual initialization for recurrent layers, uniform in
+            // [-1/sqrt(num_outputs), 1/sqrt(num_outputs)].
+            dlib::rand rnd(std::rand());
+            const float scale = 1.0f/std::sqrt(static_cast<float>(num_outputs));
+            for (auto& p : params)
+                p = scale*(2*rnd.get_random_float()-1);
+        }
+
+        class recurrent_state
+        {
+            /*!
+                Holds the state a recurrent layer carries from one call to forward() to the
+                next when the layer is stateful.  The state of each sequence is made of
+                num_parts vectors of length num_outputs (e.g. the hidden and cell states of
+                an LSTM).  They are stored in a tensor with dimensions num_samples x k x
+                num_parts x num_outputs, matching the input tensor the state came from.
+            !*/
+        public:
+
+            void load (
+                const tensor& x,
+                long num_outputs,
+                std::vector<matrix<double>>& parts
+            ) const
+            {
+                // Returns the initial state to use for the sequences in x.  That's the
+                // saved state if it was computed for the same number of sequences,
+                // otherwise it's all zeros.
+                const long num_seqs = x.num_samples()*x.k();
+                const bool use_saved = state.num_samples() == x.num_samples() && state.k() == x.k() &&
+                                       state.nr() == (long)parts.size() && state.nc() == num_outputs;
+                for (size_t i = 0; i < parts.size(); ++i)
+                {
+                    if (use_saved)
+                    {
+                        parts[i].set_size(num_seqs, num_outputs);
+                        for (long s = 0; s < num_seqs; ++s)
+                            set_rowm(parts[i], s) = matrix_cast<double>(dlib::mat(state.host() + (s*parts.size()+i)*num_outputs, 1, num_outputs));
+                    }
+                    else
+                    {
+                        parts[i] = zeros_matrix<double>(num_seqs, num_outputs);
+                    }
+                }
+            }
+
+            void save (
+                const tensor& x,
+                const std::vector<matrix<double>>& parts
+            )
+            {
+                const long num_seqs = x.num_samples()*x.k();
+                const long num_outputs = parts[0].nc();
+                state.set_size(x.num_samples(), x.k(), parts.size(), num_outputs);
+                float* d = state.host();
+                for (long s = 0; s < num_seqs; ++s)
+                {
+                    for (size_t i = 0; i < parts.size(); ++i)
+                    {
+                        for (long j = 0; j < num_outputs; ++j)
+                            *d++ = parts[i](s,j);
+                    }
+                }
+            }
+
+            void clear() { state.clear(); }
+            const tensor& get() const { return state; }
+            void set(const tensor& val) { state = val; }
+
+            friend void serialize(const recurrent_state& item, std::ostream& out) { serialize(item.state, out); }
+            friend void deserialize(recurrent_state& item, std::istream& in) { deserialize(item.state, in); }
+
+        private:
+            resizable_tensor state;
+        };
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    template <
+        unsigned long num_outputs_
+        >
+    class lstm_
+    {
+        static_assert(num_outputs_ > 0, "The number of outputs from a lstm_ layer must be > 0");
+
+    public:
+        lstm_(
+            num_fc_outputs o
+        ) : 
+            num_outputs(o.num_outputs), 
+            num_inputs(0),
+            learning_rate_multiplier(1),
+            weight_decay_multiplier(1),
+            stateful(false)
+        {
+            DLIB_CASSERT(num_outputs > 0);
+        }
+
+        lstm_() : lstm_(num_fc_outputs(num_outputs_)) {}
+
+        unsigned long get_num_outputs (
+        ) const { return num_outputs; }
+
+        void set_num_outputs(long num) 
+        {
+            DLIB_CASSERT(num > 0);
+            if (num != (long)num_outputs)
+            {
+                DLIB_CASSERT(get_layer_params().size() == 0, 
+                    "You can't change the number of outputs in lstm_ if the parameter tensor has already been allocated.");
+                num_outputs = num;
+            }
+        }
+
+        double get_learning_rate_multiplier () const  { return learning_rate_multiplier; }
+        double get_weight_decay_multiplier () const   { return weight_decay_multiplier; }
+        void set_learning_rate_multiplier(double val) { learning_rate_multiplier = val; }
+        void set_weight_decay_multiplier(double val)  { weight_decay_multiplier  = val; }
+
+        bool is_stateful() const { return stateful; }
+        void set_stateful(bool val) 
+        { 
+            stateful = val; 
+            state.clear();
+        }
+        void reset_state() { state.clear(); }
+        const tensor& get_state() const { return state.get(); }
+        void set_state(const tensor& val) { state.set(val); }
+
+        template <typename SUBNET>
+        void setup (const SUBNET& sub)
+        {
+            const long H = num_outputs;
+            num_inputs = sub.get_output().nc();
+            input_weights = alias_tensor(num_inputs, 4*H);
+            recurrent_weights = alias_tensor(H, 4*H);
+            biases = alias_tensor(1, 4*H);
+            params.set_size(input_weights.size() + recurrent_weights.size() + biases.size());
+            impl::randomize_recurrent_parameters(params, num_outputs);
+
+            // Start with the forget gates open.  This is the usual way to help gradients
+            // flow through long sequences early in training.
+            float* b = params.host() + input_weights.size() + recurrent_weights.size();
+            std::fill(b, b+4*H, 0);
+            std::fill(b+H, b+2*H, 1);
+        }
+
+        template <typename SUBNET>
+        void forward(const SUBNET& sub, resizable_tensor& output)
+        {
+            const tensor& x = sub.get_output();
+            DLIB_CASSERT(x.nc() == num_inputs, "The input to lstm_ must always have the same number of columns.");
+            const long num_seqs = x.num_samples()*x.k();
+            const long T = x.nr();
+            const long H = num_outputs;
+            output.set_size(x.num_samples(), x.k(), T, H);
+
+            const matrix<double> U = matrix_cast<double>(mat(recurrent_weights(params, input_weights.size())));
+            const float* b = params.host() + input_weights.size() + recurrent_weights.size();
+
+            // Compute the input contributions to the gates for all time steps at once.
+            // Row s*T+t holds sequence s at time t, and the gates are stored in the order
+            // input, forget, cell, output.  After the loop below, gates holds the gate
+            // activations, which backward() needs.
+            gates = matrix_cast<double>(mat(x.host(), num_seqs*T, num_inputs))*matrix_cast<double>(mat(input_weights(params, 0)));
+            cells.set_size(num_seqs*T, H);
+
+            std::vector<matrix<double>> init(2);
+            if (stateful)
+                state.load(x, H, init);
+            else
+                init = {zeros_matrix<double>(num_seqs, H), zeros_matrix<double>(num_seqs, H)};
+            initial_h = init[0];
+            initial_c = init[1];
+
+            matrix<double> h = initial_h, c = initial_c, hu;
+            float* out = output.host();
+            for (long t = 0; t < T; ++t)
+            {
+                hu = h*U;
+                for (long s = 0; s < num_seqs; ++s)
+                {
+                    double* g = &gates(s*T+t, 0);
+                    for (long j = 0; j < H; ++j)
+                    {
+                        const double ig = impl::rnn_sigmoid(g[j]     + hu(s,j)     + b[j]);
+                        const double fg = impl::rnn_sigmoid(g[H+j]   + hu(s,H+j)   + b[H+j]);
+                        const double cg = std::tanh(        g[2*H+j] + hu(s,2*H+j) + b[2*H+j]);
+                        const double og = impl::rnn_sigmoid(g[3*H+j] + hu(s,3*H+j) + b[3*H+j]);
+                        g[j] = ig;
+                        g[H+j] = fg;
+                        g[2*H+j] = cg;
+                        g[3*H+j] = og;
+                        c(s,j) = fg*c(s,j) + ig*cg;
+                        h(s,j) = og*std::tanh(c(s,j));
+                        cells(s*T+t,j) = c(s,j);
+                        out[(s*T+t)*H+j] = h(s,j);
+                    }
+                }
+            }
+
+            if (stateful)
+                state.save(x, {h, c});
+        }
+
+        template <typename SUBNET>
+        void backward(
+            const tensor& computed_output,
+            const tensor& gradient_input,
+            SUBNET& sub,
+            tensor& params_grad
+        )
+        {
+            const tensor& x = sub.get_output();
+            const long num_seqs = x.num_samples()*x.k();
+            const long T = x.nr();
+            const long H = num_outputs;
+            const matrix<double> U = matrix_cast<double>(mat(recurrent_weights(params, input_weights.size())));
+            const float* gi = gradient_input.host();
+            const float* hs = computed_output.host();
+
+            // Backpropagate through time, computing the gradients of the gate
+            // pre-activations.  No gradient flows into the initial state, so when the
+            // layer is stateful this truncates backpropagation at the start of each call
+            // to forward().
+            matrix<double> dgates(num_seqs*T, 4*H), dgates_t(num_seqs, 4*H), h_prev(num_seqs, H);
+            matrix<double> dh_next = zeros_matrix<double>(num_seqs, H);
+            matrix<double> dc_next = zeros_matrix<double>(num_seqs, H);
+            matrix<double> dU = zeros_matrix<double>(H, 4*H);
+            for (long t = T-1; t >= 0; --t)
+            {
+                for (long s = 0; s < num_seqs; ++s)
+                {
+                    const double* g = &gates(s*T+t, 0);
+                    double* dg = &dgates_t(s, 0);
+                    for (long j = 0; j < H; ++j)
+                    {
+                        const double ig = g[j], fg = g[H+j], cg = g[2*H+j], og = g[3*H+j];
+                        const double c_prev = t > 0 ? cells(s*T+t-1,j) : initial_c(s,j);
+                        const double tc = std::tanh(cells(s*T+t,j));
+                        const double dh = gi[(s*T+t)*H+j] + dh_next(s,j);
+                        const double dc = dc_next(s,j) + dh*og*(1-tc*tc);
+                        dc_next(s,j) = dc*fg;
+                        dg[j]     = dc*cg*ig*(1-ig);
+                        dg[H+j]   = dc*c_prev*fg*(1-fg);
+                        dg[2*H+j] = dc*ig*(1-cg*cg);
+                        dg[3*H+j] = dh*tc*og*(1-og);
+                        h_prev(s,j) = t > 0 ? hs[(s*T+t-1)*H+j] : initial_h(s,j);
+                    }
+                    set_rowm(dgates, s*T+t) = rowm(dgates_t, s);
+                }
+                dh_next = dgates_t*trans(U);
+                if (learning_rate_multiplier != 0)
+                    dU += trans(h_prev)*dgates_t;
+            }
+
+            alias_tensor seqs(num_seqs*T, num_inputs);
+            auto dx = seqs(sub.get_gradient_input(), 0);
+            dx += matrix_cast<float>(dgates*trans(matrix_cast<double>(mat(input_weights(params, 0)))));
+
+            if (learning_rate_multiplier != 0)
+            {
+                auto dw = input_weights(params_grad, 0);
+                auto du = recurrent_weights(params_grad, input_weights.size());
+                auto db = biases(params_grad, input_weights.size()+recurrent_weights.size());
+                dw = matrix_cast<float>(trans(matrix_cast<double>(mat(x.host(), num_seqs*T, num_inputs)))*dgates);
+                du = matrix_cast<float>(dU);
+                db = matrix_cast<float>(sum_rows(dgates));
+            }
+        }
+
+        const tensor& get_layer_params() const { return params; }
+        tensor& get_layer_params() { return params; }
+
+        friend void serialize(const lstm_& item, std::ostream& out)
+        {
+            serialize("lstm_", out);
+            serialize(item.params, out);
+            serialize(item.num_outputs, out);
+            serialize(item.num_inputs, out);
+            serialize(item.input_weights, out);
+            serialize(item.recurrent_weights, out);
+            serialize(item.biases, out);
+            serialize(item.learning_rate_multiplier, out);
+            serialize(item.weight_decay_multiplier, out);
+            serialize(item.stateful, out);
+            serialize(item.state, out);
+        }
+
+        friend void deserialize(lstm_& item, std::istream& in)
+        {
+            std::string version;
+            deserialize(version, in);
+            if (version != "lstm_")
+                throw serialization_error("Unexpected version '"+version+"' found while deserializing dlib::lstm_.");
+            deserialize(item.params, in);
+            deserialize(item.num_outputs, in);
+            deserialize(item.num_inputs, in);
+            deserialize(item.input_weights, in);
+            deserialize(item.recurrent_weights, in);
+            deserialize(item.biases, in);
+            deserialize(item.learning_rate_multiplier, in);
+            deserialize(item.weight_decay_multiplier, in);
+            deserialize(item.stateful, in);
+            deserialize(item.state, in);
+        }
+
+        friend std::ostream& operator<<(std::ostream& out, const lstm_& item)
+        {
+            out << "lstm\t ("
+                << "num_outputs="<<item.num_outputs
+                << ")";
+            out << " learning_rate_mult="<<item.learning_rate_multiplier;
+            out << " weight_decay_mult="<<item.weight_decay_multiplier;
+            if (item.stateful)
+                out << " stateful=true";
+            return out;
+        }
+
+        friend void to_xml(const lstm_& item, std::ostream& out)
+        {
+            out << "<lstm"
+                << " num_outputs='"<<item.num_outputs<<"'"
+                << " learning_rate_mult='"<<item.learning_rate_multiplier<<"'"
+                << " weight_decay_mult='"<<item.weight_decay_multiplier<<"'"
+                << " stateful='"<<(item.stateful?"true":"false")<<"'"
+                << ">\n";
+            out << mat(item.params);
+            out << "</lstm>\n";
+        }
+
+    private:
+
+        resizable_tensor params;
+        alias_tensor input_weights, recurrent_weights, biases;
+        unsigned long num_outputs;
+        long num_inputs;
+        double learning_rate_multiplier;
+        double weight_decay_multiplier;
+        bool stateful;
+        impl::recurrent_state state;
+
+        // Computed by forward() and used by backward().
+        matrix<double> gates, cells, initial_h, initial_c;
+    };
+
+    template <
+        unsigned long num_outputs,
+        typename SUBNET
+        >
+    using lstm = add_layer<lstm_<num_outputs>, SUBNET>;
+
+// ----------------------------------------------------------------------------------------
+
+    template <
+        unsigned long num_outputs_
+        >
+    class gru_
+    {
+        static_assert(num_outputs_ > 0, "The number of outputs from a gru_ layer must be > 0");
+
+    public:
+        gru_(
+            num_fc_outputs o
+        ) : 
+            num_outputs(o.num_outputs), 
+            num_inputs(0),
+            learning_rate_multiplier(1),
+            weight_decay_multiplier(1),
+            stateful(false)
+        {
+            DLIB_CASSERT(num_outputs > 0);
+        }
+
+        gru_() : gru_(num_fc_outputs(num_outputs_)) {}
+
+        unsigned long get_num_outputs (
+        ) const { return num_outputs; }
+
+        void set_num_outputs(long num) 
+        {
+            DLIB_CASSERT(num > 0);
+            if (num != (long)num_outputs)
+            {
+                DLIB_CASSERT(get_layer_params().size() == 0, 
+                    "You can't change the number of outputs in gru_ if the parameter tensor has already been allocated.");
+                num_outputs = num;
+            }
+        }
+
+        double get_learning_rate_multiplier () const  { return learning_rate_multiplier; }
+        double get_weight_decay_multiplier () const   { return weight_decay_multiplier; }
+        void set_learning_rate_multiplier(double val) { learning_rate_multiplier = val; }
+        void set_weight_decay_multiplier(double val)  { weight_decay_multiplier  = val; }
+
+        bool is_stateful() const { return stateful; }
+        void set_stateful(bool val) 
+        { 
+            stateful = val; 
+            state.clear();
+        }
+        void reset_state() { state.clear(); }
+        const tensor& get_state() const { return state.get(); }
+        void set_state(const tensor& val) { state.set(val); }
+
+        template <typename SUBNET>
+        void setup (const SUBNET& sub)
+        {
+            const long H = num_outputs;
+            num_inputs = sub.get_output().nc();
+            input_weights = alias_tensor(num_inputs, 3*H);
+            recurrent_weights = alias_tensor(H, 3*H);
+            input_biases = alias_tensor(1, 3*H);
+            recurrent_biases = alias_tensor(1, 3*H);
+            params.set_size(input_weights.size() + recurrent_weights.size() + input_biases.size() + recurrent_biases.size());
+            impl::randomize_recurrent_parameters(params, num_outputs);
+        }
+
+        template <typename SUBNET>
+        void forward(const SUBNET& sub, resizable_tensor& output)
+        {
+            const tensor& x = sub.get_output();
+            DLIB_CASSERT(x.nc() == num_inputs, "The input to gru_ must always have the same number of columns.");
+            const long num_seqs = x.num_samples()*x.k();
+            const long T = x.nr();
+            const long H = num_outputs;
+            output.set_size(x.num_samples(), x.k(), T, H);
+
+            const matrix<double> U = matrix_cast<double>(mat(recurrent_weights(params, input_weights.size())));
+            const float* bi = params.host() + input_weights.size() + recurrent_weights.size();
+            const float* bh = bi + input_biases.size();
+
+            // Compute the input contributions to the gates for all time steps at once.
+            // Row s*T+t holds sequence s at time t, and the gates are stored in the order
+            // reset, update, new.  After the loop below, gates holds the gate activations
+            // and hidden_new holds the recurrent part of the new gate's pre-activation,
+            // both of which backward() needs.
+            gates = matrix_cast<double>(mat(x.host(), num_seqs*T, num_inputs))*matrix_cast<double>(mat(input_weights(params, 0)));
+            hidden_new.set_size(num_seqs*T, H);
+
+            std::vector<matrix<double>> init(1);
+            if (stateful)
+                state.load(x, H, init);
+            else
+                init[0] = zeros_matrix<double>(num_seqs, H);
+            initial_h = init[0];
+
+            matrix<double> h = initial_h, hu;
+            float* out = output.host();
+            for (long t = 0; t < T; ++t)
+            {
+                hu = h*U;
+                for (long s = 0; s < num_seqs; ++s)
+                {
+                    double* g = &gates(s*T+t, 0);
+                    for (long j = 0; j < H; ++j)
+                    {
+                        const double rg = impl::rnn_sigmoid(g[j]   + bi[j]   + hu(s,j)   + bh[j]);
+                        const double zg = impl::rnn_sigmoid(g[H+j] + bi[H+j] + hu(s,H+j) + bh[H+j]);
+                        const double hn = hu(s,2*H+j) + bh[2*H+j];
+                        const double ng = std::tanh(g[2*H+j] + bi[2*H+j] + rg*hn);
+                        g[j] = rg;
+                        g[H+j] = zg;
+                        g[2*H+j] = ng;
+                        hidden_new(s*T+t,j) = hn;
+                        h(s,j) = (1-zg)*ng + zg*h(s,j);
+                        out[(s*T+t)*H+j] = h(s,j);
+                    }
+                }
+            }
+
+            if (stateful)
+                state.save(x, {h});
+        }
+
+        template <typename SUBNET>
+        void backward(
+            const tensor& computed_output,
+            const tensor& gradient_input,
+            SUBNET& sub,
+            tensor& params_grad
+        )
+        {
+            const tensor& x = sub.get_output();
+            const long num_seqs = x.num_samples()*x.k();
+            const long T = x.nr();
+            const long H = num_outputs;
+            const matrix<double> U = matrix_cast<double>(mat(recurrent_weights(params, input_weights.size())));
+            const float* gi = gradient_input.host();
+            const float* hs = computed_output.host();
+
+            // Backpropagate through time.  dgates holds the gradients of the input parts
+            // of the gate pre-activations and dgates_t the gradients of the recurrent parts
+            // at time t.  As with lstm_, no gradient flows into the initial state.
+            matrix<double> dgates(num_seqs*T, 3*H), dgates_t(num_seqs, 3*H), h_prev(num_seqs, H);
+            matrix<double> dh_next = zeros_matrix<double>(num_seqs, H);
+            matrix<double> dU = zeros_matrix<double>(H, 3*H);
+            matrix<double> dbh = zeros_matrix<double>(1, 3*H);
+            for (long t = T-1; t >= 0; --t)
+            {
+                for (long s = 0; s < num_seqs; ++s)
+                {
+                    const double* g = &gates(s*T+t, 0);
+                    double* dgx = &dgates(s*T+t, 0);
+                    double* dgh = &dgates_t(s, 0);
+                    for (long j = 0; j < H; ++j)
+                    {
+                        const double rg = g[j], zg = g[H+j], ng = g[2*H+j];
+                        const double hp = t > 0 ? hs[(s*T+t-1)*H+j] : initial_h(s,j);
+                        const double dh = gi[(s*T+t)*H+j] + dh_next(s,j);
+                        const double dn = dh*(1-zg)*(1-ng*ng);
+                        const double dr = dn*hidden_new(s*T+t,j)*rg*(1-rg);
+                        const double dz = dh*(hp-ng)*zg*(1-zg);
+                        dgx[j] = dr;
+                        dgx[H+j] = dz;
+                        dgx[2*H+j] = dn;
+                        dgh[j] = dr;
+                        dgh[H+j] = dz;
+                        dgh[2*H+j] = dn*rg;
+                        dh_next(s,j) = dh*zg;
+                        h_prev(s,j) = hp;
+                    }
+                }
+                dh_next += dgates_t*trans(U);
+                if (learning_rate_multiplier != 0)
+                {
+                    dU += trans(h_prev)*dgates_t;
+                    dbh += sum_rows(dgates_t);
+                }
+            }
+
+            alias_tensor seqs(num_seqs*T, num_inputs);
+            auto dx = seqs(sub.get_gradient_input(), 0);
+            dx += matrix_cast<float>(dgates*trans(matrix_cast<double>(mat(input_weights(params, 0)))));
+
+            if (learning_rate_multiplier != 0)
+            {
+                auto dw = input_weights(params_grad, 0);
+                auto du = recurrent_weights(params_grad, input_weights.size());
+                auto dbi_ = input_biases(params_grad, input_weights.size()+recurrent_weights.size());
+                auto dbh_ = recurrent_biases(params_grad, input_weights.size()+recurrent_weights.size()+input_biases.size());
+                dw = matrix_cast<float>(trans(matrix_cast<double>(mat(x.host(), num_seqs*T, num_inputs)))*dgates);
+                du = matrix_cast<float>(dU);
+                dbi_ = matrix_cast<float>(sum_rows(dgates));
+                dbh_ = matrix_cast<float>(dbh);
+            }
+        }
+
+        const tensor& get_layer_params() const { return params; }
+        tensor& get_layer_params() { return params; }
+
+        friend void serialize(const gru_& item, std::ostream& out)
+        {
+            serialize("gru_", out);
+            serialize(item.params, out);
+            serialize(item.num_outputs, out);
+            serialize(item.num_inputs, out);
+            serialize(item.input_weights, out);
+            serialize(item.recurrent_weights, out);
+            serialize(item.input_biases, out);
+            serialize(item.recurrent_biases, out);
+            serialize(item.learning_rate_multiplier, out);
+            serialize(item.weight_decay_multiplier, out);
+            serialize(item.stateful, out);
+            serialize(item.state, out);
+        }
+
+        friend void deserialize(gru_& item, std::istream& in)
+        {
+            std::string version;
+            deserialize(version, in);
+            if (version != "gru_")
+                throw serialization_error("Unexpected version '"+version+"' found while deserializing dlib::gru_.");
+            deserialize(item.params, in);
+            deserialize(item.num_outputs, in);
+            deserialize(item.num_inputs, in);
+            deserialize(item.input_weights, in);
+            deserialize(item.recurrent_weights, in);
+            deserialize(item.input_biases, in);
+            deserialize(item.recurrent_biases, in);
+            deserialize(item.learning_rate_multiplier, in);
+            deserialize(item.weight_decay_multiplier, in);
+            deserialize(item.stateful, in);
+            deserialize(item.state, in);
+        }
+
+        friend std::ostream& operator<<(std::ostream& out, const gru_& item)
+        {
+            out << "gru\t ("
+                << "num_outputs="<<item.num_outputs
+                << ")";
+            out << " learning_rate_mult="<<item.learning_rate_multiplier;
+            out << " weight_decay_mult="<<item.weight_decay_multiplier;
+            if (item.stateful)
+                out << " stateful=true";
+            return out;
+        }
+
+        friend void to_xml(const gru_& item, std::ostream& out)
+        {
+            out << "<gru"
+                << " num_outputs='"<<item.num_outputs<<"'"
+                << " learning_rate_mult='"<<item.learning_rate_multiplier<<"'"
+                << " weight_decay_mult='"<<item.weight_decay_multiplier<<"'"
+                << " stateful='"<<(item.stateful?"true":"false")<<"'"
+                << ">\n";
+            out << mat(item.params);
+            out << "</gru>\n";
+        }
+
+    private:
+
+        resizable_tensor params;
+        alias_tensor input_weights, recurrent_weights, input_biases, recurrent_biases;
+        unsigned long num_outputs;
+        long num_inputs;
+        double learning_rate_multiplier;
+        double weight_decay_multiplier;
+        bool stateful;
+        impl::recurrent_state state;
+
+        // Computed by forward() and used by backward().
+        matrix<double> gates, hidden_new, initial_h;
+    };
+
+    template <
+        unsigned long num_outputs,
+        typename SUBNET
+        >
+    using gru = add_layer<gru_<num_outputs>, SUBNET>;
+
+// ----------------------------------------------------------------------------------------
+
+    template <
+        long num_heads_,
+        bool causal_mask_ = false,
+        int DROP_RATE_PERCENT = 0
+        >
+    class multihead_attention_
+    {
+        static_assert(num_heads_ > 0, "The number of heads in multihead_attention_ must be > 0");
+        static_assert(DROP_RATE_PERCENT >= 0 && DROP_RATE_PERCENT < 100,
+            "DROP_RATE_PERCENT must be in the range [0, 100).");
+
+        template <long, bool, int> friend class multihead_attention_;
+
+    public:
+
+        multihead_attention_(
+        ) :
+            num_inputs(0),
+            drop_rate(static_cast<float>(DROP_RATE_PERCENT)/100.0f),
+            learning_rate_multiplier(1),
+            weight_decay_multiplier(1),
+            rnd(std::rand()),
+            dropout_seed(0)
+        {
+        }
+
+        template <int DROP_RATE_PERCENT2>
+        multihead_attention_(
+            const multihead_attention_<num_heads_,causal_mask_,DROP_RATE_PERCENT2>& item
+        ) :
+            params(item.params),
+            in_weights(item.in_weights),
+            in_biases(item.in_biases),
+            out_weights(item.out_weights),
+            out_biases(item.out_biases),
+            num_inputs(item.num_inputs),
+            drop_rate(static_cast<float>(DROP_RATE_PERCENT)/100.0f),
+            learning_rate_multiplier(item.learning_rate_multiplier),
+            weight_decay_multiplier(item.weight_decay_multiplier),
+            rnd(std::rand()),
+            dropout_seed(0)
+        {
+            // This constructor lets you copy a network trained with attention dropout into
+            // an otherwise identical network without it (or the other way around).
+        }
+
+        long get_num_heads (
+        ) const { return num_heads_; }
+
+        bool uses_causal_mask (
+        ) const { return causal_mask_; }
+
+        float get_drop_rate (
+        ) const { return drop_rate; }
+
+        void set_drop_rate (
+            float val
+        )
+        {
+            DLIB_CASSERT(0 <= val && val < 1);
+            drop_rate = val;
+        }
+
+        double get_learning_rate_multiplier () const  { return learning_rate_multiplier; }
+        double get_weight_decay_multiplier () const   { return weight_decay_multiplier; }
+        void set_learning_rate_multiplier(double val) { learning_rate_multiplier = val; }
+        void set_weight_decay_multiplier(double val)  { weight_decay_multiplier  = val; }
+
+        template <typename SUBNET>
+        void setup (const SUBNET& sub)
+        {
+            const long d = sub.get_output().nc();
+            DLIB_CASSERT(d%num_heads_ == 0, 
+                "The number of columns in the input to multihead_attention_ must be a multiple of the number of heads."
+                << "\n\t sub.get_output().nc(): " << d 
+                << "\n\t num_heads: " << num_heads_ 
+            );
+            num_inputs = d;
+            in_weights = alias_tensor(d, 3*d);
+            in_biases = alias_tensor(1, 3*d);
+            out_weights = alias_tensor(d, d);
+            out_biases = alias_tensor(1, d);
+            params.set_size(in_weights.size() + in_biases.size() + out_weights.size() + out_biases.size());
+
+            dlib::rand prnd(std::rand());
+            auto wi = in_weights(params, 0);
+            randomize_parameters(wi, d+d, prnd);
+            auto wo = out_weights(params, in_weights.size() + in_biases.size());
+            randomize_parameters(wo, d+d, prnd);
+            in_biases(params, in_weights.size()) = 0;
+            out_biases(params, in_weights.size() + in_biases.size() + out_weights.size()) = 0;
+        }
+
+        template <typename SUBNET>
+        void forward(const SUBNET& sub, resizable_tensor& output)
+        {
+            const tensor& x = sub.get_output();
+            DLIB_CASSERT(x.nc() == num_inputs, "The input to multihead_attention_ must always have the same number of columns.");
+            const long num_seqs = x.num_samples()*x.k();
+            const long T = x.nr();
+            const long d = num_inputs;
+            const long dh = d/num_heads_;
+            output.set_size(x.num_samples(), x.k(), T, d);
+
+            // Project the inputs to the queries, keys and values of all the heads at once.
+            // Row s*T+t of qkv holds sequence s at time t, laid out as [Q K V], with head h
+            // using columns h*dh through (h+1)*dh-1 of each of Q, K and V.
+            qkv = matrix_cast<double>(mat(x.host(), num_seqs*T, d))*matrix_cast<double>(mat(in_weights(params, 0)));
+            const float* bi = params.host() + in_weights.size();
+            for (long r = 0; r < qkv.nr(); ++r)
+                for (long c = 0; c < qkv.nc(); ++c)
+                    qkv(r,c) += bi[c];
+
+            // Now compute the attention one query at a time.  This way we never hold a
+            // T x T matrix of attention weights in memory.  Instead, we save the log of the
+            // softmax normalizer for each query and recompute the weights in backward().
+            // The dropout mask is also regenerated in backward() from dropout_seed.
+            dropout_seed = rnd.get_random_32bit_number();
+            dlib::rand mask_rnd(dropout_seed);
+            context.set_size(num_seqs*T, d);
+            log_normalizers.set_size(num_seqs*T, num_heads_);
+            const double scale = 1/std::sqrt(static_cast<double>(dh));
+            std::vector<double> weights(T), acc(dh);
+            for (long s = 0; s < num_seqs; ++s)
+            {
+                for (long h = 0; h < num_heads_; ++h)
+                {
+                    for (long i = 0; i < T; ++i)
+                    {
+                        const long num_keys = causal_mask_ ? i+1 : T;
+                        const double* q = &qkv(s*T+i, h*dh);
+                        double max_score = -std::numeric_limits<double>::infinity();
+                        for (long j = 0; j < num_keys; ++j)
+                        {
+                            weights[j] = scale*dot_product(q, &qkv(s*T+j, d+h*dh), dh);
+                            max_score = std::max(max_score, weights[j]);
+                        }
+                        double sum = 0;
+                        for (long j = 0; j < num_keys; ++j)
+                        {
+                            weights[j] = std::exp(weights[j]-max_score);
+                            sum += weights[j];
+                        }
+
+                        std::fill(acc.begin(), acc.end(), 0);
+                        for (long j = 0; j < num_keys; ++j)
+                        {
+                            const double w = apply_dropout(weights[j]/sum, mask_rnd);
+                            const double* v = &qkv(s*T+j, 2*d+h*dh);
+                            for (long c = 0; c < dh; ++c)
+                                acc[c] += w*v[c];
+                        }
+                        for (long c = 0; c < dh; ++c)
+                            context(s*T+i, h*dh+c) = acc[c];
+                        log_normalizers(s*T+i, h) = max_score + std::log(sum);
+                    }
+                }
+            }
+
+            const matrix<double> out = context*matrix_cast<double>(mat(out_weights(params, in_weights.size() + in_biases.size())));
+            const float* bo = params.host() + in_weights.size() + in_biases.size() + out_weights.size();
+            float* y = output.host();
+            for (long r = 0; r < out.nr(); ++r)
+                for (long c = 0; c < out.nc(); ++c)
+                    *y++ = out(r,c) + bo[c];
+        }
+
+        template <typename SUBNET>
+        void backward(
+            const tensor& gradient_input,
+            SUBNET& sub,
+            tensor& params_grad
+        )
+        {
+            const tensor& x = sub.get_output();
+            const long num_seqs = x.num_samples()*x.k();
+            const long T = x.nr();
+            const long d = num_inputs;
+            const long dh = d/num_heads_;
+            const size_t out_weights_offset = in_weights.size() + in_biases.size();
+            const auto grad = mat(gradient_input.host(), num_seqs*T, d);
+
+            const matrix<double> dcontext = matrix_cast<double>(grad)*trans(matrix_cast<double>(mat(out_weights(params, out_weights_offset))));
+
+            // Run the attention backwards, recomputing the attention weights and dropout
+            // mask the same way forward() did.
+            matrix<double> dqkv = zeros_matrix<double>(num_seqs*T, 3*d);
+            dlib::rand mask_rnd(dropout_seed);
+            const double scale = 1/std::sqrt(static_cast<double>(dh));
+            std::vector<double> weights(T), dweights(T);
+            for (long s = 0; s < num_seqs; ++s)
+            {
+                for (long h = 0; h < num_heads_; ++h)
+                {
+                    for (long i = 0; i < T; ++i)
+                    {
+                        const long num_keys = causal_mask_ ? i+1 : T;
+                        const double* q = &qkv(s*T+i, h*dh);
+                        const double* dc = &dcontext(s*T+i, h*dh);
+                        const double lse = log_normalizers(s*T+i, h);
+                        double weighted_sum = 0;
+                        for (long j = 0; j < num_keys; ++j)
+                        {
+                            weights[j] = std::exp(scale*dot_product(q, &qkv(s*T+j, d+h*dh), dh) - lse);
+                            // The factor multiplying the attention weight after dropout.
+                            const double keep = apply_dropout(1, mask_rnd);
+                            double* dv = &dqkv(s*T+j, 2*d+h*dh);
+                            for (long c = 0; c < dh; ++c)
+                                dv[c] += keep*weights[j]*dc[c];
+                            dweights[j] = keep*dot_product(dc, &qkv(s*T+j, 2*d+h*dh), dh);
+                            weighted_sum += weights[j]*dweights[j];
+                        }
+
+                        // Backprop through the softmax and the scaled dot products.
+                        double* dq = &dqkv(s*T+i, h*dh);
+                        for (long j = 0; j < num_keys; ++j)
+                        {
+                            const double dscore = scale*weights[j]*(dweights[j] - weighted_sum);
+                            const double* k = &qkv(s*T+j, d+h*dh);
+                            double* dk = &dqkv(s*T+j, d+h*dh);
+                            for (long c = 0; c < dh; ++c)
+                            {
+                                dq[c] += dscore*k[c];
+                                dk[c] += dscore*q[c];
+                            }
+                        }
+                    }
+                }
+            }
+
+            const auto xmat = mat(x.host(), num_seqs*T, d);
+            auto dx = alias_tensor(num_seqs*T, d)(sub.get_gradient_input(), 0);
+            dx += matrix_cast<float>(dqkv*trans(matrix_cast<double>(mat(in_weights(params, 0)))));
+
+            if (learning_rate_multiplier != 0)
+            {
+                auto dwi = in_weights(params_grad, 0);
+                auto dbi = in_biases(params_grad, in_weights.size());
+                auto dwo = out_weights(params_grad, out_weights_offset);
+                auto dbo = out_biases(params_grad, out_weights_offset + out_weights.size());
+                dwi = matrix_cast<float>(trans(matrix_cast<double>(xmat))*dqkv);
+                dbi = matrix_cast<float>(sum_rows(dqkv));
+                dwo = matrix_cast<float>(trans(context)*matrix_cast<double>(grad));
+                dbo = sum_rows(grad);
+            }
+        }
+
+        const tensor& get_layer_params() const { return params; }
+        tensor& get_layer_params() { return params; }
+
+        friend void serialize(const multihead_attention_& item, std::ostream& out)
+        {
+            serialize("multihead_attention_", out);
+            serialize(item.params, out);
+            serialize(item.num_inputs, out);
+            serialize(item.in_weights, out);
+            serialize(item.in_biases, out);
+            serialize(item.out_weights, out);
+            serialize(item.out_biases, out);
+            serialize(num_heads_, out);
+            serialize(causal_mask_, out);
+            serialize(item.drop_rate, out);
+            serialize(item.learning_rate_multiplier, out);
+            serialize(item.weight_decay_multiplier, out);
+        }
+
+        friend void deserialize(multihead_attention_& item, std::istream& in)
+        {
+            std::string version;
+            deserialize(version, in);
+            if (version != "multihead_attention_")
+                throw serialization_error("Unexpected version '"+version+"' found while deserializing dlib::multihead_attention_.");
+            deserialize(item.params, in);
+            deserialize(item.num_inputs, in);
+            deserialize(item.in_weights, in);
+            deserialize(item.in_biases, in);
+            deserialize(item.out_weights, in);
+            deserialize(item.out_biases, in);
+            long num_heads;
+            bool causal_mask;
+            deserialize(num_heads, in);
+            deserialize(causal_mask, in);
+            if (num_heads != num_heads_) throw serialization_error("Wrong num_heads found while deserializing dlib::multihead_attention_");
+            if (causal_mask != causal_mask_) throw serialization_error("Wrong causal_mask found while deserializing dlib::multihead_attention_");
+            deserialize(item.drop_rate, in);
+            deserialize(item.learning_rate_multiplier, in);
+            deserialize(item.weight_decay_multiplier, in);
+        }
+
+        friend std::ostream& operator<<(std::ostream& out, const multihead_attention_& item)
+        {
+            out << "multihead_attention\t ("
+                << "num_heads="<<num_heads_
+                << ", causal_mask="<<(causal_mask_?"true":"false")
+                << ", drop_rate="<<item.drop_rate
+                << ")";
+            out << " learning_rate_mult="<<item.learning_rate_multiplier;
+            out << " weight_decay_mult="<<item.weight_decay_multiplier;
+            return out;
+        }
+
+        friend void to_xml(const multihead_attention_& item, std::ostream& out)
+        {
+            out << "<multihead_attention"
+                << " num_heads='"<<num_heads_<<"'"
+                << " causal_mask='"<<(causal_mask_?"true":"false")<<"'"
+                << " drop_rate='"<<item.drop_rate<<"'"
+                << " learning_rate_mult='"<<item.learning_rate_multiplier<<"'"
+                << " weight_decay_mult='"<<item.weight_decay_multiplier<<"'"
+                << ">\n";
+            out << mat(item.params);
+            out << "</multihead_attention>\n";
+        }
+
+    private:
+
+        static double dot_product(const double* a, const double* b, long n)
+        {
+            double sum = 0;
+            for (long i = 0; i < n; ++i)
+                sum += a[i]*b[i];
+            return sum;
+        }
+
+        double apply_dropout(double weight, dlib::rand& mask_rnd) const
+        {
+            // Inverted dropout, so no rescaling is needed when drop_rate is 0.
+            if (drop_rate == 0)
+                return weight;
+            if (mask_rnd.get_random_float() < drop_rate)
+                return 0;
+            return weight/(1-drop_rate);
+        }
+
+        resizable_tensor params;
+        alias_tensor in_weights, in_biases, out_weights, out_biases;
+        long num_inputs;
+        float drop_rate;
+        double learning_rate_multiplier;
+        double weight_decay_multiplier;
+        dlib::rand rnd;
+
+        // Computed by forward() and used by backward().
+        matrix<double> qkv, context, log_normalizers;
+        unsigned long dropout_seed;
+    };
+
+    template <
+        long num_heads,
+        typename SUBNET
+        >
+    using multihead_attention = add_layer<multihead_attention_<num_heads>, SUBNET>;
+
+    template <
+        long num_heads,
+        typename SUBNET
+        >
+    using causal_multihead_attention = add_layer<multihead_attention_<num_heads,true>, SUBNET>;
+
 // ----------------------------------------------------------------------------------------
 
 }
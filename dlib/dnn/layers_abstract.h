@@ -517,6 +517,46 @@ namespace dlib
                   is added to each of the outputs of this layer. 
         !*/
 
+        bool is_quantized(
+        ) const;
+        /*!
+            ensures
+                - returns true if quantize_to_int8() has been called on this layer.  A
+                  quantized layer runs its forward pass with the 8 bit integer kernel
+                  tt::int8_fc(), which always runs on the CPU.
+        !*/
+
+        void quantize_to_int8 (
+            float input_range
+        );
+        /*!
+            requires
+                - input_range >= 0
+                - is_quantized() == false
+                - get_layer_params().size() != 0
+                  (i.e. the layer has been allocated)
+            ensures
+                - #is_quantized() == true
+                - Converts the weights to 8 bit integers, with one scale for each output.
+                  The biases, if any, are kept as floats.
+                - input_range is the largest absolute value this layer is expected to see
+                  in its input, usually measured with calibrate_int8_activation_ranges().
+                  At run time the input is quantized to [-input_range, input_range] and
+                  anything outside that range is clamped.
+                - #get_int8_input_scale() == int8_scale_for_range(input_range)
+                - #get_layer_params().size() == 0.  The float parameters are discarded,
+                  so the layer takes about a quarter of the memory it used to.
+                - The layer can't be trained anymore.  Calling backward() is an error.
+        !*/
+
+        float get_int8_input_scale(
+        ) const;
+        /*!
+            ensures
+                - returns the scale used to quantize the input of this layer when
+                  is_quantized() == true.
+        !*/
+
         double get_learning_rate_multiplier(
         ) const;  
         /*!
@@ -703,7 +743,10 @@ namespace dlib
         int _stride_y,
         int _stride_x,
         int _padding_y = _stride_y!=1? 0 : _nr/2,
-        int _padding_x = _stride_x!=1? 0 : _nc/2
+        int _padding_x = _stride_x!=1? 0 : _nc/2,
+        int _dilation_y = 1,
+        int _dilation_x = 1,
+        long _groups = 1
         >
     class con_
     {
@@ -716,15 +759,21 @@ namespace dlib
                 - _stride_x > 0
                 - _padding_y >= 0
                 - _padding_x >= 0
+                - _dilation_y > 0
+                - _dilation_x > 0
+                - _groups > 0
+                - _num_filters % _groups == 0
                 - Also, we require that:
                     - if (_nr == 0) then
                         - _padding_y == 0
+                        - _dilation_y == 1
                     - else
-                        - _padding_y < _nr
+                        - _padding_y < _dilation_y*(_nr-1)+1
                     - if (_nc == 0) then
                         - _padding_x == 0
+                        - _dilation_x == 1
                     - else
-                        - _padding_x < _nc
+                        - _padding_x < _dilation_x*(_nc-1)+1
 
             WHAT THIS OBJECT REPRESENTS
                 This is an implementation of the EXAMPLE_COMPUTATIONAL_LAYER_ interface
@@ -732,12 +781,21 @@ namespace dlib
                 input tensor (nominally representing an image) and convolves it with a set
                 of filters and then outputs the results. 
 
+                The filters can be dilated, meaning the filter taps are spread out so that
+                they are dilation_y() rows and dilation_x() columns apart.  A filter with
+                nr() rows therefore covers dilation_y()*(nr()-1)+1 rows of the input.  The
+                layer can also perform a grouped convolution.  In that case the input
+                channels and the filters are split into groups() equally sized groups and
+                each filter only looks at the IN.k()/groups() input channels in its group.
+                Setting groups() equal to both IN.k() and num_filters() gives a depthwise
+                convolution.
+
                 The dimensions of the tensors output by this layer are as follows (letting
                 IN be the input tensor and OUT the output tensor):
                     - OUT.num_samples() == IN.num_samples()
                     - OUT.k()  == num_filters()
-                    - OUT.nr() == 1+(IN.nr() + 2*padding_y() - nr())/stride_y()
-                    - OUT.nc() == 1+(IN.nc() + 2*padding_x() - nc())/stride_x()
+                    - OUT.nr() == 1+(IN.nr() + 2*padding_y() - (dilation_y()*(nr()-1)+1))/stride_y()
+                    - OUT.nc() == 1+(IN.nc() + 2*padding_x() - (dilation_x()*(nc()-1)+1))/stride_x()
 
                 Note also that setting _nr or _nc to 0 has a special meaning of "set the
                 filter size equal to the input image size".  Specifically, it means: 
@@ -761,6 +819,9 @@ namespace dlib
                 - #stride_x() == _stride_x
                 - #padding_y() == _padding_y
                 - #padding_x() == _padding_x
+                - #dilation_y() == _dilation_y
+                - #dilation_x() == _dilation_x
+                - #groups() == _groups
                 - #get_learning_rate_multiplier()      == 1
                 - #get_weight_decay_multiplier()       == 1
                 - #get_bias_learning_rate_multiplier() == 1
@@ -779,6 +840,9 @@ namespace dlib
                 - #stride_x() == _stride_x
                 - #padding_y() == _padding_y
                 - #padding_x() == _padding_x
+                - #dilation_y() == _dilation_y
+                - #dilation_x() == _dilation_x
+                - #groups() == _groups
                 - #get_learning_rate_multiplier()      == 1
                 - #get_weight_decay_multiplier()       == 1
                 - #get_bias_learning_rate_multiplier() == 1
@@ -800,6 +864,7 @@ namespace dlib
         /*!
             requires
                 - num > 0
+                - num % groups() == 0
                 - get_layer_params().size() == 0 || num_filters() == num
                   (i.e. You can't change the number of filters in con_ if the parameter
                   tensor has already been allocated.)
@@ -863,6 +928,31 @@ namespace dlib
                   sides of the image.
         !*/
 
+        long dilation_y(
+        ) const; 
+        /*!
+            ensures
+                - returns the vertical dilation of the filters.  That is, adjacent rows of
+                  a filter are applied to image rows that are dilation_y() pixels apart.
+        !*/
+
+        long dilation_x(
+        ) const; 
+        /*!
+            ensures
+                - returns the horizontal dilation of the filters.  That is, adjacent
+                  columns of a filter are applied to image columns that are dilation_x()
+                  pixels apart.
+        !*/
+
+        long groups(
+        ) const; 
+        /*!
+            ensures
+                - returns the number of groups the channels are split into.  Each output
+                  channel is computed only from the input channels in its own group.
+        !*/
+
         double get_learning_rate_multiplier(
         ) const;  
         /*!
@@ -988,6 +1078,86 @@ namespace dlib
                   methods either.
         !*/
 
+        bool is_quantized(
+        ) const;
+        /*!
+            ensures
+                - returns true if quantize_to_int8() has been called on this layer.  A
+                  quantized layer runs its forward pass with the 8 bit integer kernel
+                  tt::int8_conv(), which always runs on the CPU.
+        !*/
+
+        void quantize_to_int8 (
+            float input_range
+        );
+        /*!
+            requires
+                - input_range >= 0
+                - is_quantized() == false
+                - get_layer_params().size() != 0
+                  (i.e. the layer has been allocated)
+            ensures
+                - #is_quantized() == true
+                - Converts the filters to 8 bit integers, with one scale for each filter.
+                  The biases, if any, are kept as floats.
+                - input_range is the largest absolute value this layer is expected to see
+                  in its input, usually measured with calibrate_int8_activation_ranges().
+                  At run time the input is quantized to [-input_range, input_range] and
+                  anything outside that range is clamped.
+                - #get_int8_input_scale() == int8_scale_for_range(input_range)
+                - #get_layer_params().size() == 0.  The float parameters are discarded,
+                  so the layer takes about a quarter of the memory it used to.
+                - The layer can't be trained anymore.  Calling backward() is an error.
+        !*/
+
+        float get_int8_input_scale(
+        ) const;
+        /*!
+            ensures
+                - returns the scale used to quantize the input of this layer when
+                  is_quantized() == true.
+        !*/
+
+        void prune_filters (
+            const std::vector<long>& keep
+        );
+        /*!
+            requires
+                - groups() == 1
+                - get_layer_params().size() != 0
+                  (i.e. the layer has been allocated)
+                - is_quantized() == false
+                - keep.size() > 0
+                - keep is sorted in increasing order, has no duplicates, and all its values
+                  are in the range [0, num_filters()).
+            ensures
+                - Removes all the filters, and their biases, except the ones listed in keep.
+                  So afterwards the i-th output channel of this layer is what the keep[i]-th
+                  output channel used to be.
+                - #num_filters() == keep.size()
+                - The layer above this one no longer gets the removed channels, so you
+                  must remove them from it as well, e.g. with prune_input_channels() or
+                  prune_channels().  prune_con_filters() does all of this for you.
+        !*/
+
+        void prune_input_channels (
+            const std::vector<long>& keep
+        );
+        /*!
+            requires
+                - groups() == 1
+                - get_layer_params().size() != 0
+                - is_quantized() == false
+                - keep.size() > 0
+                - keep is sorted in increasing order, has no duplicates, and all its values
+                  are less than the number of channels in the input to this layer.
+            ensures
+                - Removes the weights that apply to all the input channels except the ones
+                  listed in keep.  So afterwards this layer expects an input with keep.size()
+                  channels, where channel i is what channel keep[i] used to be.
+                - #num_filters() == num_filters()
+        !*/
+
         template <typename SUBNET> void setup (const SUBNET& sub);
         template <typename SUBNET> void forward(const SUBNET& sub, resizable_tensor& output);
         template <typename SUBNET> void backward(const tensor& gradient_input, SUBNET& sub, tensor& params_grad);
@@ -1011,6 +1181,43 @@ namespace dlib
         >
     using con = add_layer<con_<num_filters,nr,nc,stride_y,stride_x>, SUBNET>;
 
+    template <
+        long num_filters,
+        long nr,
+        long nc,
+        int stride_y,
+        int stride_x,
+        int dilation_y,
+        int dilation_x,
+        typename SUBNET
+        >
+    using dilated_con = add_layer<con_<num_filters,nr,nc,stride_y,stride_x,
+        stride_y!=1? 0 : dilation_y*(nr/2),
+        stride_x!=1? 0 : dilation_x*(nc/2),
+        dilation_y,dilation_x>, SUBNET>;
+    /*!
+        A dilated convolution.  When the stride is 1 the padding is chosen so the output
+        has the same size as the input, just like con does for odd sized filters.
+    !*/
+
+    template <
+        long num_filters,
+        long nr,
+        long nc,
+        int stride_y,
+        int stride_x,
+        long groups,
+        typename SUBNET
+        >
+    using grouped_con = add_layer<con_<num_filters,nr,nc,stride_y,stride_x,
+        stride_y!=1? 0 : nr/2,
+        stride_x!=1? 0 : nc/2,
+        1,1,groups>, SUBNET>;
+    /*!
+        A grouped convolution.  The input channels and the filters are split into groups
+        equally sized groups.
+    !*/
+
 // ----------------------------------------------------------------------------------------
 
     template <
@@ -1020,7 +1227,10 @@ namespace dlib
         int _stride_y,
         int _stride_x,
         int _padding_y = _stride_y!=1? 0 : _nr/2,
-        int _padding_x = _stride_x!=1? 0 : _nc/2
+        int _padding_x = _stride_x!=1? 0 : _nc/2,
+        int _dilation_y = 1,
+        int _dilation_x = 1,
+        long _groups = 1
         >
     class cont_
     {
@@ -1028,8 +1238,9 @@ namespace dlib
             REQUIREMENTS ON TEMPLATE ARGUMENTS
                 All of them must be > 0.
                 Also, we require that:
-                    - 0 <= _padding_y && _padding_y < _nr
-                    - 0 <= _padding_x && _padding_x < _nc
+                    - 0 <= _padding_y && _padding_y < _dilation_y*(_nr-1)+1
+                    - 0 <= _padding_x && _padding_x < _dilation_x*(_nc-1)+1
+                    - _num_filters % _groups == 0
 
             WHAT THIS OBJECT REPRESENTS
                 This is an implementation of the EXAMPLE_COMPUTATIONAL_LAYER_ interface
@@ -1041,13 +1252,16 @@ namespace dlib
                 Therefore, you can make output tensors that are larger than the input
                 tensors using this layer type. 
 
+                Like con_, the filters can be dilated and the channels can be split into
+                groups().  This layer computes the gradient of the corresponding dilated or
+                grouped con_ layer with respect to its input.
                 
                 The dimensions of the tensors output by this layer are as follows (letting
                 IN be the input tensor and OUT the output tensor):
                     - OUT.num_samples() == IN.num_samples()
                     - OUT.k()  == num_filters()
-                    - OUT.nr() == stride_y()*(IN.nr()-1) + nr() - 2*padding_y()
-                    - OUT.nc() == stride_x()*(IN.nc()-1) + nc() - 2*padding_x()
+                    - OUT.nr() == stride_y()*(IN.nr()-1) + dilation_y()*(nr()-1)+1 - 2*padding_y()
+                    - OUT.nc() == stride_x()*(IN.nc()-1) + dilation_x()*(nc()-1)+1 - 2*padding_x()
         !*/
 
     public:
@@ -1062,6 +1276,9 @@ namespace dlib
                 - #stride_x() == _stride_x
                 - #padding_y() == _padding_y
                 - #padding_x() == _padding_x
+                - #dilation_y() == _dilation_y
+                - #dilation_x() == _dilation_x
+                - #groups() == _groups
                 - #get_learning_rate_multiplier()      == 1
                 - #get_weight_decay_multiplier()       == 1
                 - #get_bias_learning_rate_multiplier() == 1
@@ -1080,6 +1297,9 @@ namespace dlib
                 - #stride_x() == _stride_x
                 - #padding_y() == _padding_y
                 - #padding_x() == _padding_x
+                - #dilation_y() == _dilation_y
+                - #dilation_x() == _dilation_x
+                - #groups() == _groups
                 - #get_learning_rate_multiplier()      == 1
                 - #get_weight_decay_multiplier()       == 1
                 - #get_bias_learning_rate_multiplier() == 1
@@ -1101,6 +1321,7 @@ namespace dlib
         /*!
             requires
                 - num > 0
+                - num % groups() == 0
                 - get_layer_params().size() == 0 || num_filters() == num
                   (i.e. You can't change the number of filters in cont_ if the parameter
                   tensor has already been allocated.)
@@ -1156,6 +1377,31 @@ namespace dlib
                   sides of the image.
         !*/
 
+        long dilation_y(
+        ) const; 
+        /*!
+            ensures
+                - returns the vertical dilation of the filters.  That is, adjacent rows of
+                  a filter are applied to image rows that are dilation_y() pixels apart.
+        !*/
+
+        long dilation_x(
+        ) const; 
+        /*!
+            ensures
+                - returns the horizontal dilation of the filters.  That is, adjacent
+                  columns of a filter are applied to image columns that are dilation_x()
+                  pixels apart.
+        !*/
+
+        long groups(
+        ) const; 
+        /*!
+            ensures
+                - returns the number of groups the channels are split into.  Each output
+                  channel is computed only from the input channels in its own group.
+        !*/
+
         double get_learning_rate_multiplier(
         ) const;  
         /*!
@@ -1957,6 +2203,59 @@ namespace dlib
                 - #get_running_stats_window_size() == new_window_size
         !*/
 
+        const tensor& get_running_means(
+        ) const;
+        /*!
+            ensures
+                - returns the running average of the means of the features.  These are the
+                  means used when this layer runs in "inference mode".  The tensor is empty
+                  until setup() has been called.
+        !*/
+
+        tensor& get_running_means(
+        );
+        /*!
+            ensures
+                - returns a non-const reference to the running average of the means of the
+                  features.  You can use it to set the statistics used in "inference mode",
+                  e.g. when loading a network trained with some other software.
+        !*/
+
+        const tensor& get_running_variances(
+        ) const;
+        /*!
+            ensures
+                - returns the running average of the variances of the features.  These are
+                  the variances used when this layer runs in "inference mode".  The tensor
+                  is empty until setup() has been called.
+        !*/
+
+        tensor& get_running_variances(
+        );
+        /*!
+            ensures
+                - returns a non-const reference to the running average of the variances of
+                  the features.
+        !*/
+
+        void prune_channels (
+            const std::vector<long>& keep
+        );
+        /*!
+            requires
+                - get_mode() == CONV_MODE
+                - get_layer_params().size() != 0
+                  (i.e. the layer has been allocated)
+                - keep.size() > 0
+                - keep is sorted in increasing order, has no duplicates, and all its values
+                  are less than the number of channels in the input to this layer.
+            ensures
+                - Removes gamma, beta, and the running means and variances of all the
+                  channels except the ones listed in keep.  So afterwards this layer
+                  expects an input with keep.size() channels, where channel i is what
+                  channel keep[i] used to be.
+        !*/
+
         double get_learning_rate_multiplier(
         ) const;  
         /*!
@@ -2135,6 +2434,23 @@ namespace dlib
                   Causing this layer to trivially perform the an identity transform.
         !*/
 
+        void prune_channels (
+            const std::vector<long>& keep
+        );
+        /*!
+            requires
+                - get_mode() == CONV_MODE
+                - disable() has not been called.
+                - This layer has been allocated, i.e. get_gamma().size() != 0.
+                - keep.size() > 0
+                - keep is sorted in increasing order, has no duplicates, and all its values
+                  are less than the number of channels in the input to this layer.
+            ensures
+                - Removes gamma and beta of all the channels except the ones listed in
+                  keep.  So afterwards this layer expects an input with keep.size()
+                  channels, where channel i is what channel keep[i] used to be.
+        !*/
+
         alias_tensor_instance get_gamma();
         /*!
             ensures
@@ -4087,6 +4403,512 @@ namespace dlib
     template <long diag, long num, long den, typename SUBNET>
     using tril_diag = add_layer<tril_<diag, void, num, den>, SUBNET>;
 
+// ----------------------------------------------------------------------------------------
+
+    template <
+        unsigned long num_outputs
+        >
+    class lstm_
+    {
+        /*!
+            REQUIREMENTS ON num_outputs
+                num_outputs > 0
+
+            WHAT THIS OBJECT REPRESENTS
+                This is an implementation of the EXAMPLE_COMPUTATIONAL_LAYER_ interface
+                defined above.  In particular, it defines a long short-term memory layer.
+                It treats its input tensor as a set of sequences laid out along the rows of
+                each channel.  That is, for each sample n and channel k, the matrix
+                image_plane(IN,n,k) is a sequence with one time step per row and one input
+                feature per column.  The layer runs the usual LSTM recurrence over each
+                sequence and outputs the hidden state at each time step.
+
+                The dimensions of the tensors output by this layer are as follows (letting
+                IN be the input tensor and OUT the output tensor):
+                    - OUT.num_samples() == IN.num_samples()
+                    - OUT.k()  == IN.k()
+                    - OUT.nr() == IN.nr()
+                    - OUT.nc() == get_num_outputs()
+
+                The gates are computed as follows, where x is the input row at time t, h and
+                c are the hidden and cell states from time t-1, and the gate order is
+                input, forget, cell, output:
+                    - [i f g o] = x*W + h*U + b
+                    - c = sigmoid(f)*c + sigmoid(i)*tanh(g)
+                    - h = sigmoid(o)*tanh(c)
+                W is an IN.nc() x 4*get_num_outputs() matrix, U is a get_num_outputs() x
+                4*get_num_outputs() matrix and b is a row vector.  They are stored in
+                get_layer_params() in that order.  The forget gate biases are initialized to
+                1.
+
+                By default each call to forward() starts all the sequences from a zero
+                state.  However, if is_stateful() then the final states from one call to
+                forward() are used as the initial states of the next call, so long as the
+                number of samples and channels don't change.  Gradients are not propagated
+                into those initial states.  This is how truncated backpropagation through
+                time is implemented: split a long sequence into chunks along nr, feed the
+                chunks in order, and call reset_state() at the start of each new sequence.
+                See dnn_trainer::set_truncated_bptt_chunks() for a way to have the trainer
+                do this for you.
+        !*/
+
+    public:
+
+        lstm_(
+        );
+        /*!
+            ensures
+                - #get_num_outputs() == num_outputs
+                - #get_learning_rate_multiplier() == 1
+                - #get_weight_decay_multiplier()  == 1
+                - #is_stateful() == false
+        !*/
+
+        lstm_(
+            num_fc_outputs o
+        );
+        /*!
+            ensures
+                - #get_num_outputs() == o.num_outputs
+                - #get_learning_rate_multiplier() == 1
+                - #get_weight_decay_multiplier()  == 1
+                - #is_stateful() == false
+        !*/
+
+        unsigned long get_num_outputs (
+        ) const; 
+        /*!
+            ensures
+                - returns the size of the hidden state of this layer.  This is also the
+                  number of columns in the output tensor.
+        !*/
+
+        void set_num_outputs(
+            long num
+        );
+        /*!
+            requires
+                - num > 0
+                - get_layer_params().size() == 0 || get_num_outputs() == num
+                  (i.e. You can't change the number of outputs in lstm_ if the parameter
+                  tensor has already been allocated.)
+            ensures
+                - #get_num_outputs() == num
+        !*/
+
+        double get_learning_rate_multiplier(
+        ) const;  
+        /*!
+            ensures
+                - returns a multiplier number.  The interpretation is that this object is
+                  requesting that the learning rate used to optimize its parameters be
+                  multiplied by get_learning_rate_multiplier().
+        !*/
+
+        double get_weight_decay_multiplier(
+        ) const; 
+        /*!
+            ensures
+                - returns a multiplier number.  The interpretation is that this object is
+                  requesting that the weight decay used to optimize its parameters be
+                  multiplied by get_weight_decay_multiplier().
+        !*/
+
+        void set_learning_rate_multiplier(
+            double val
+        );
+        /*!
+            requires
+                - val >= 0
+            ensures
+                - #get_learning_rate_multiplier() == val
+        !*/
+
+        void set_weight_decay_multiplier(
+            double val
+        ); 
+        /*!
+            requires
+                - val >= 0
+            ensures
+                - #get_weight_decay_multiplier() == val
+        !*/
+
+        bool is_stateful(
+        ) const;
+        /*!
+            ensures
+                - returns true if this layer carries its state from one call to forward()
+                  to the next.
+        !*/
+
+        void set_stateful(
+            bool val
+        );
+        /*!
+            ensures
+                - #is_stateful() == val
+                - #get_state().size() == 0
+        !*/
+
+        void reset_state(
+        );
+        /*!
+            ensures
+                - #get_state().size() == 0
+                  (i.e. the next call to forward() starts from a zero state)
+        !*/
+
+        const tensor& get_state(
+        ) const;
+        /*!
+            ensures
+                - returns the state saved by the last call to forward() when is_stateful().
+                  It has dimensions N x K x 2 x get_num_outputs(), where N and K are the
+                  num_samples() and k() of the last input.  Row 0 of each plane is the
+                  hidden state and row 1 the cell state of the corresponding sequence.
+        !*/
+
+        void set_state(
+            const tensor& val
+        );
+        /*!
+            ensures
+                - #get_state() == val
+        !*/
+
+        template <typename SUBNET> void setup (const SUBNET& sub);
+        template <typename SUBNET> void forward(const SUBNET& sub, resizable_tensor& output);
+        template <typename SUBNET> void backward(const tensor& computed_output, const tensor& gradient_input, SUBNET& sub, tensor& params_grad);
+        const tensor& get_layer_params() const; 
+        tensor& get_layer_params(); 
+        /*!
+            These functions are implemented as described in the EXAMPLE_COMPUTATIONAL_LAYER_ interface.
+        !*/
+    };
+
+    template <
+        unsigned long num_outputs,
+        typename SUBNET
+        >
+    using lstm = add_layer<lstm_<num_outputs>, SUBNET>;
+
+// ----------------------------------------------------------------------------------------
+
+    template <
+        unsigned long num_outputs
+        >
+    class gru_
+    {
+        /*!
+            REQUIREMENTS ON num_outputs
+                num_outputs > 0
+
+            WHAT THIS OBJECT REPRESENTS
+                This is an implementation of the EXAMPLE_COMPUTATIONAL_LAYER_ interface
+                defined above.  In particular, it defines a gated recurrent unit layer.  It
+                uses the same tensor layout as lstm_, i.e. image_plane(IN,n,k) is a sequence
+                with one time step per row, and it outputs the hidden state at each time
+                step.
+
+                The dimensions of the tensors output by this layer are as follows (letting
+                IN be the input tensor and OUT the output tensor):
+                    - OUT.num_samples() == IN.num_samples()
+                    - OUT.k()  == IN.k()
+                    - OUT.nr() == IN.nr()
+                    - OUT.nc() == get_num_outputs()
+
+                The gates are computed as follows, where x is the input row at time t, h is
+                the hidden state from time t-1, and the gate order is reset, update, new:
+                    - [ar az an] = x*W + bi
+                    - [hr hz hn] = h*U + bh
+                    - r = sigmoid(ar + hr)
+                    - z = sigmoid(az + hz)
+                    - n = tanh(an + r*hn)
+                    - h = (1-z)*n + z*h
+                W is an IN.nc() x 3*get_num_outputs() matrix, U is a get_num_outputs() x
+                3*get_num_outputs() matrix and bi and bh are row vectors.  They are stored
+                in get_layer_params() in that order.
+
+                The stateful mode works the same way as in lstm_.
+        !*/
+
+    public:
+
+        gru_(
+        );
+        /*!
+            ensures
+                - #get_num_outputs() == num_outputs
+                - #get_learning_rate_multiplier() == 1
+                - #get_weight_decay_multiplier()  == 1
+                - #is_stateful() == false
+        !*/
+
+        gru_(
+            num_fc_outputs o
+        );
+        /*!
+            ensures
+                - #get_num_outputs() == o.num_outputs
+                - #get_learning_rate_multiplier() == 1
+                - #get_weight_decay_multiplier()  == 1
+                - #is_stateful() == false
+        !*/
+
+        unsigned long get_num_outputs (
+        ) const; 
+        /*!
+            ensures
+                - returns the size of the hidden state of this layer.  This is also the
+                  number of columns in the output tensor.
+        !*/
+
+        void set_num_outputs(
+            long num
+        );
+        /*!
+            requires
+                - num > 0
+                - get_layer_params().size() == 0 || get_num_outputs() == num
+                  (i.e. You can't change the number of outputs in gru_ if the parameter
+                  tensor has already been allocated.)
+            ensures
+                - #get_num_outputs() == num
+        !*/
+
+        double get_learning_rate_multiplier(
+        ) const;  
+        double get_weight_decay_multiplier(
+        ) const; 
+        void set_learning_rate_multiplier(
+            double val
+        );
+        void set_weight_decay_multiplier(
+            double val
+        ); 
+        /*!
+            These functions behave the same as the corresponding lstm_ functions.
+        !*/
+
+        bool is_stateful(
+        ) const;
+        void set_stateful(
+            bool val
+        );
+        void reset_state(
+        );
+        void set_state(
+            const tensor& val
+        );
+        /*!
+            These functions behave the same as the corresponding lstm_ functions.
+        !*/
+
+        const tensor& get_state(
+        ) const;
+        /*!
+            ensures
+                - returns the state saved by the last call to forward() when is_stateful().
+                  It has dimensions N x K x 1 x get_num_outputs(), where N and K are the
+                  num_samples() and k() of the last input, and holds the hidden state of
+                  each sequence.
+        !*/
+
+        template <typename SUBNET> void setup (const SUBNET& sub);
+        template <typename SUBNET> void forward(const SUBNET& sub, resizable_tensor& output);
+        template <typename SUBNET> void backward(const tensor& computed_output, const tensor& gradient_input, SUBNET& sub, tensor& params_grad);
+        const tensor& get_layer_params() const; 
+        tensor& get_layer_params(); 
+        /*!
+            These functions are implemented as described in the EXAMPLE_COMPUTATIONAL_LAYER_ interface.
+        !*/
+    };
+
+    template <
+        unsigned long num_outputs,
+        typename SUBNET
+        >
+    using gru = add_layer<gru_<num_outputs>, SUBNET>;
+
+// ----------------------------------------------------------------------------------------
+
+    template <
+        long num_heads,
+        bool causal_mask = false,
+        int DROP_RATE_PERCENT = 0
+        >
+    class multihead_attention_
+    {
+        /*!
+            REQUIREMENTS ON TEMPLATE ARGUMENTS
+                - num_heads > 0
+                - 0 <= DROP_RATE_PERCENT < 100
+
+            WHAT THIS OBJECT REPRESENTS
+                This is an implementation of the EXAMPLE_COMPUTATIONAL_LAYER_ interface
+                defined above.  In particular, it defines a multi-head self-attention layer
+                as described in the paper:
+                    Vaswani, A., et al. (2017). Attention is all you need. In Advances in
+                    neural information processing systems (pp. 5998-6008).
+                It does the same job as the combination of fc_, transpose_, scale_,
+                softmax_, tril_ and mult_prev_ layers normally used to build the attention
+                block of a transformer, but in a single layer that uses much less memory.
+
+                Like lstm_, it treats each channel of each sample of the input tensor as a
+                sequence with one token per row.  So image_plane(IN,n,k) is a sequence of
+                IN.nr() tokens, each a vector of d == IN.nc() numbers.  For each sequence X,
+                the layer computes:
+                    - [Q K V] = X*W_in + b_in
+                    - For each head h, with Q_h, K_h and V_h being the columns of Q, K and V
+                      used by head h (d/num_heads columns each):
+                        - C_h = softmax(Q_h*trans(K_h)/sqrt(d/num_heads))*V_h
+                      where the softmax is applied to each row.  If causal_mask==true then
+                      token t only attends to tokens 0 through t.  That is, the elements
+                      above the diagonal are set to -infinity before the softmax.
+                    - OUT = [C_0 C_1 ...]*W_out + b_out
+                W_in is a d x 3*d matrix, b_in a row vector, W_out a d x d matrix and b_out a
+                row vector.  They are stored in get_layer_params() in the order W_in, b_in,
+                W_out, b_out.
+
+                If get_drop_rate() != 0 then dropout is applied to the attention weights
+                (i.e. to the outputs of the softmax) during forward().  This is inverted
+                dropout, so the remaining weights are scaled by 1/(1-get_drop_rate()) and
+                no rescaling is needed at inference time.  As with dropout_, the dropout is
+                applied every time forward() is called.  So to run a trained network
+                without dropout, copy it into a network using multihead_attention_ objects
+                with DROP_RATE_PERCENT == 0, or call set_drop_rate(0).
+
+                The attention weights are computed one query at a time and never stored.
+                backward() recomputes them from the queries and keys, and regenerates the
+                dropout masks from a saved random seed.  So the memory used by this layer
+                grows linearly with the sequence length rather than quadratically.
+
+                The dimensions of the tensors output by this layer are the same as the
+                input tensor.
+        !*/
+
+    public:
+
+        multihead_attention_(
+        );
+        /*!
+            ensures
+                - #get_num_heads() == num_heads
+                - #uses_causal_mask() == causal_mask
+                - #get_drop_rate() == DROP_RATE_PERCENT/100.0
+                - #get_learning_rate_multiplier() == 1
+                - #get_weight_decay_multiplier()  == 1
+        !*/
+
+        template <int DROP_RATE_PERCENT2>
+        multihead_attention_(
+            const multihead_attention_<num_heads,causal_mask,DROP_RATE_PERCENT2>& item
+        );
+        /*!
+            ensures
+                - #get_layer_params() == item.get_layer_params()
+                - #get_drop_rate() == DROP_RATE_PERCENT/100.0
+                - #get_learning_rate_multiplier() == item.get_learning_rate_multiplier()
+                - #get_weight_decay_multiplier()  == item.get_weight_decay_multiplier()
+                - This constructor lets you assign a network trained with attention dropout
+                  to an otherwise identical network that doesn't use it.
+        !*/
+
+        long get_num_heads (
+        ) const;
+        /*!
+            ensures
+                - returns the number of attention heads.  The number of columns of the
+                  input tensor must be a multiple of this number.
+        !*/
+
+        bool uses_causal_mask (
+        ) const;
+        /*!
+            ensures
+                - returns true if each token only attends to itself and the tokens before
+                  it.
+        !*/
+
+        float get_drop_rate (
+        ) const;
+        /*!
+            ensures
+                - returns the probability that an attention weight is set to zero during
+                  forward().
+        !*/
+
+        void set_drop_rate (
+            float val
+        );
+        /*!
+            requires
+                - 0 <= val < 1
+            ensures
+                - #get_drop_rate() == val
+        !*/
+
+        double get_learning_rate_multiplier(
+        ) const;  
+        /*!
+            ensures
+                - returns a multiplier number.  The interpretation is that this object is
+                  requesting that the learning rate used to optimize its parameters be
+                  multiplied by get_learning_rate_multiplier().
+        !*/
+
+        double get_weight_decay_multiplier(
+        ) const; 
+        /*!
+            ensures
+                - returns a multiplier number.  The interpretation is that this object is
+                  requesting that the weight decay used to optimize its parameters be
+                  multiplied by get_weight_decay_multiplier().
+        !*/
+
+        void set_learning_rate_multiplier(
+            double val
+        );
+        /*!
+            requires
+                - val >= 0
+            ensures
+                - #get_learning_rate_multiplier() == val
+        !*/
+
+        void set_weight_decay_multiplier(
+            double val
+        ); 
+        /*!
+            requires
+                - val >= 0
+            ensures
+                - #get_weight_decay_multiplier() == val
+        !*/
+
+        template <typename SUBNET> void setup (const SUBNET& sub);
+        template <typename SUBNET> void forward(const SUBNET& sub, resizable_tensor& output);
+        template <typename SUBNET> void backward(const tensor& gradient_input, SUBNET& sub, tensor& params_grad);
+        const tensor& get_layer_params() const; 
+        tensor& get_layer_params(); 
+        /*!
+            These functions are implemented as described in the EXAMPLE_COMPUTATIONAL_LAYER_ interface.
+        !*/
+    };
+
+    template <
+        long num_heads,
+        typename SUBNET
+        >
+    using multihead_attention = add_layer<multihead_attention_<num_heads>, SUBNET>;
+
+    template <
+        long num_heads,
+        typename SUBNET
+        >
+    using causal_multihead_attention = add_layer<multihead_attention_<num_heads,true>, SUBNET>;
+
 // ----------------------------------------------------------------------------------------
 
 }
@@ -253,6 +253,18 @@ namespace dlib
         typedef unsigned long training_label_type;
         typedef unsigned long output_label_type;
 
+        loss_multiclass_log_() = default;
+
+        explicit loss_multiclass_log_(
+            double label_smoothing_
+        ) : label_smoothing(label_smoothing_)
+        {
+            DLIB_CASSERT(0 <= label_smoothing && label_smoothing < 1);
+        }
+
+        double get_label_smoothing (
+        ) const { return label_smoothing; }
+
         template <
             typename SUB_TYPE,
             typename label_iterator
@@ -307,6 +319,10 @@ namespace dlib
 
             // The loss we output is the average loss over the mini-batch.
             const double scale = 1.0/output_tensor.num_samples();
+            // With label smoothing the target puts 1-label_smoothing on the true label and
+            // spreads label_smoothing evenly over all the labels.
+            const double off_target = label_smoothing/output_tensor.k();
+            const double on_target = 1 - label_smoothing + off_target;
             double loss = 0;
             float* g = grad.host();
             for (long i = 0; i < output_tensor.num_samples(); ++i)
@@ -320,8 +336,13 @@ namespace dlib
                     const unsigned long idx = i*output_tensor.k()+k;
                     if (k == y)
                     {
-                        loss += scale*-safe_log(g[idx]);
-                        g[idx] = scale*(g[idx]-1);
+                        loss += scale*on_target*-safe_log(g[idx]);
+                        g[idx] = scale*(g[idx]-on_target);
+                    }
+                    else if (label_smoothing != 0)
+                    {
+                        loss += scale*off_target*-safe_log(g[idx]);
+                        g[idx] = scale*(g[idx]-off_target);
                     }
                     else
                     {
@@ -332,30 +353,51 @@ namespace dlib
             return loss;
         }
 
-        friend void serialize(const loss_multiclass_log_& , std::ostream& out)
+        friend void serialize(const loss_multiclass_log_& item, std::ostream& out)
         {
-            serialize("loss_multiclass_log_", out);
+            // Without label smoothing, use the old format so the networks can still be
+            // loaded by older versions of dlib.
+            if (item.label_smoothing == 0)
+            {
+                serialize("loss_multiclass_log_", out);
+            }
+            else
+            {
+                serialize("loss_multiclass_log_2", out);
+                serialize(item.label_smoothing, out);
+            }
         }
 
-        friend void deserialize(loss_multiclass_log_& , std::istream& in)
+        friend void deserialize(loss_multiclass_log_& item, std::istream& in)
         {
             std::string version;
             deserialize(version, in);
-            if (version != "loss_multiclass_log_")
+            if (version == "loss_multiclass_log_")
+                item.label_smoothing = 0;
+            else if (version == "loss_multiclass_log_2")
+                deserialize(item.label_smoothing, in);
+            else
                 throw serialization_error("Unexpected version found while deserializing dlib::loss_multiclass_log_.");
         }
 
-        friend std::ostream& operator<<(std::ostream& out, const loss_multiclass_log_& )
+        friend std::ostream& operator<<(std::ostream& out, const loss_multiclass_log_& item)
         {
             out << "loss_multiclass_log";
+            if (item.label_smoothing != 0)
+                out << " (label_smoothing=" << item.label_smoothing << ")";
             return out;
         }
 
-        friend void to_xml(const loss_multiclass_log_& /*item*/, std::ostream& out)
+        friend void to_xml(const loss_multiclass_log_& item, std::ostream& out)
         {
-            out << "<loss_multiclass_log/>\n";
+            if (item.label_smoothing != 0)
+                out << "<loss_multiclass_log label_smoothing='" << item.label_smoothing << "'/>\n";
+            else
+                out << "<loss_multiclass_log/>\n";
         }
 
+    private:
+        double label_smoothing = 0;
     };
 
     template <typename SUBNET>
@@ -363,14 +405,25 @@ namespace dlib
 
 // ----------------------------------------------------------------------------------------
 
-    class loss_multiclass_log_weighted_
+    class loss_multiclass_focal_
     {
     public:
 
-        typedef dlib::weighted_label<unsigned long> weighted_label;
-        typedef weighted_label training_label_type;
+        typedef unsigned long training_label_type;
         typedef unsigned long output_label_type;
 
+        loss_multiclass_focal_() = default;
+
+        explicit loss_multiclass_focal_(
+            double gamma_
+        ) : gamma(gamma_)
+        {
+            DLIB_CASSERT(gamma >= 0);
+        }
+
+        double get_gamma (
+        ) const { return gamma; }
+
         template <
             typename SUB_TYPE,
             typename label_iterator
@@ -381,20 +434,7 @@ namespace dlib
             label_iterator iter
         ) const
         {
-            const tensor& output_tensor = sub.get_output();
-            DLIB_CASSERT(sub.sample_expansion_factor() == 1);
-            DLIB_CASSERT(output_tensor.nr() == 1 &&
-                         output_tensor.nc() == 1 );
-            DLIB_CASSERT(input_tensor.num_samples() == output_tensor.num_samples());
-
-
-            // Note that output_tensor.k() should match the number of labels.
-
-            for (long i = 0; i < output_tensor.num_samples(); ++i)
-            {
-                // The index of the largest output for this sample is the label.
-                *iter++ = index_of_max(rowm(mat(output_tensor),i));
-            }
+            loss_multiclass_log_().to_label(input_tensor, sub, iter);
         }
 
         template <
@@ -403,7 +443,7 @@ namespace dlib
             >
         double compute_loss_value_and_gradient (
             const tensor& input_tensor,
-            const_label_iterator truth,
+            const_label_iterator truth, 
             SUBNET& sub
         ) const
         {
@@ -415,9 +455,9 @@ namespace dlib
             DLIB_CASSERT(input_tensor.num_samples()%sub.sample_expansion_factor() == 0);
             DLIB_CASSERT(input_tensor.num_samples() == grad.num_samples());
             DLIB_CASSERT(input_tensor.num_samples() == output_tensor.num_samples());
-            DLIB_CASSERT(output_tensor.nr() == 1 &&
+            DLIB_CASSERT(output_tensor.nr() == 1 && 
                          output_tensor.nc() == 1);
-            DLIB_CASSERT(grad.nr() == 1 &&
+            DLIB_CASSERT(grad.nr() == 1 && 
                          grad.nc() == 1);
 
             tt::softmax(grad, output_tensor);
@@ -428,160 +468,93 @@ namespace dlib
             float* g = grad.host();
             for (long i = 0; i < output_tensor.num_samples(); ++i)
             {
-                const auto wl = *truth++;
-                const long y = wl.label;
-                const float weight = wl.weight;
+                const long y = (long)*truth++;
                 // The network must produce a number of outputs that is equal to the number
                 // of labels when using this type of loss.
                 DLIB_CASSERT(y < output_tensor.k(), "y: " << y << ", output_tensor.k(): " << output_tensor.k());
+                float* p = g + i*output_tensor.k();
+                const double pt = p[y];
+                // The focal loss is -(1-pt)^gamma*log(pt), where pt is the probability of
+                // the true label.  Its gradient with respect to the network outputs is the
+                // usual softmax gradient times a factor that goes to 0 as pt goes to 1.
+                const double one_minus_pt = std::max(1-pt, 1e-12);
+                const double log_pt = safe_log(pt);
+                const double modulator = std::pow(one_minus_pt, gamma);
+                const double factor = modulator - gamma*pt*std::pow(one_minus_pt, gamma-1)*log_pt;
+                loss += scale*-modulator*log_pt;
                 for (long k = 0; k < output_tensor.k(); ++k)
                 {
-                    const unsigned long idx = i*output_tensor.k()+k;
                     if (k == y)
-                    {
-                        loss += weight*scale*-safe_log(g[idx]);
-                        g[idx] =weight*scale*(g[idx]-1);
-                    }
+                        p[k] = scale*factor*(p[k]-1);
                     else
-                    {
-                        g[idx] = weight*scale*g[idx];
-                    }
+                        p[k] = scale*factor*p[k];
                 }
             }
             return loss;
         }
 
-        friend void serialize(const loss_multiclass_log_weighted_& , std::ostream& out)
+        friend void serialize(const loss_multiclass_focal_& item, std::ostream& out)
         {
-            serialize("loss_multiclass_log_weighted_", out);
+            serialize("loss_multiclass_focal_", out);
+            serialize(item.gamma, out);
         }
 
-        friend void deserialize(loss_multiclass_log_weighted_& , std::istream& in)
+        friend void deserialize(loss_multiclass_focal_& item, std::istream& in)
         {
             std::string version;
             deserialize(version, in);
-            if (version != "loss_multiclass_log_weighted_")
-                throw serialization_error("Unexpected version found while deserializing dlib::loss_multiclass_log_weighted_.");
+            if (version != "loss_multiclass_focal_")
+                throw serialization_error("Unexpected version found while deserializing dlib::loss_multiclass_focal_.");
+            deserialize(item.gamma, in);
         }
 
-        friend std::ostream& operator<<(std::ostream& out, const loss_multiclass_log_weighted_& )
+        friend std::ostream& operator<<(std::ostream& out, const loss_multiclass_focal_& item)
         {
-            out << "loss_multiclass_log_weighted";
+            out << "loss_multiclass_focal (gamma=" << item.gamma << ")";
             return out;
         }
 
-        friend void to_xml(const loss_multiclass_log_weighted_& /*item*/, std::ostream& out)
+        friend void to_xml(const loss_multiclass_focal_& item, std::ostream& out)
         {
-            out << "<loss_multiclass_log_weighted/>\n";
+            out << "<loss_multiclass_focal gamma='" << item.gamma << "'/>\n";
         }
 
+    private:
+        double gamma = 2;
     };
 
     template <typename SUBNET>
-    using loss_multiclass_log_weighted = add_loss_layer<loss_multiclass_log_weighted_, SUBNET>;
+    using loss_multiclass_focal = add_loss_layer<loss_multiclass_focal_, SUBNET>;
 
 // ----------------------------------------------------------------------------------------
 
-    class loss_multimulticlass_log_ 
+    class loss_ctc_
     {
-
     public:
 
-        loss_multimulticlass_log_ () = default;
+        typedef std::vector<unsigned long> training_label_type;
+        typedef std::vector<unsigned long> output_label_type;
 
-        loss_multimulticlass_log_ (
-            const std::map<std::string,std::vector<std::string>>& labels
-        )
-        {
-            for (auto& l : labels)
-            {
-                possible_labels[l.first] = std::make_shared<decltype(l.second)>(l.second);
-                DLIB_CASSERT(l.second.size() >= 2, "Each classifier must have at least two possible labels.");
+        loss_ctc_() = default;
 
-                for (size_t i = 0; i < l.second.size(); ++i)
-                {
-                    label_idx_lookup[l.first][l.second[i]] = i;
-                    ++total_num_labels;
-                }
-            }
-        }
+        explicit loss_ctc_(
+            unsigned long blank_label_
+        ) : blank_label(blank_label_) {}
 
-        unsigned long number_of_labels() const { return total_num_labels; }
+        unsigned long get_blank_label (
+        ) const { return blank_label; }
 
-        unsigned long number_of_classifiers() const { return possible_labels.size(); }
+        unsigned long get_beam_size (
+        ) const { return beam_size; }
 
-        std::map<std::string,std::vector<std::string>> get_labels ( 
-        ) const 
+        void set_beam_size (
+            unsigned long size
+        )
         {
-            std::map<std::string,std::vector<std::string>> info; 
-            for (auto& i : possible_labels)
-            {
-                for (auto& label : *i.second)
-                    info[i.first].emplace_back(label);
-            }
-            return info;
+            DLIB_CASSERT(size > 0);
+            beam_size = size;
         }
 
-        class classifier_output
-        {
-
-        public:
-            classifier_output() = default;
-
-            size_t num_classes() const { return class_probs.size(); }
-
-            double probability_of_class (
-                size_t i
-            ) const 
-            { 
-                DLIB_CASSERT(i < num_classes());
-                return class_probs(i); 
-            }
-
-            const std::string& label(
-                size_t i
-            ) const 
-            { 
-                DLIB_CASSERT(i < num_classes()); 
-                return (*_labels)[i]; 
-            }
-
-            operator std::string(
-            ) const
-            {
-                DLIB_CASSERT(num_classes() != 0); 
-                return (*_labels)[index_of_max(class_probs)];
-            }
-
-            friend std::ostream& operator<< (std::ostream& out, const classifier_output& item)
-            {
-                DLIB_ASSERT(item.num_classes() != 0); 
-                out << static_cast<std::string>(item);
-                return out;
-            }
-
-        private:
-
-            friend class loss_multimulticlass_log_;
-
-            template <typename EXP>
-            classifier_output(
-                const matrix_exp<EXP>& class_probs,
-                const std::shared_ptr<std::vector<std::string>>& _labels
-            ) : 
-                class_probs(class_probs), 
-                _labels(_labels)
-            {
-            }
-
-            matrix<float,1,0> class_probs;
-            std::shared_ptr<std::vector<std::string>> _labels;
-        };
-
-        typedef std::map<std::string,std::string> training_label_type;
-        typedef std::map<std::string,classifier_output> output_label_type;
-
-
         template <
             typename SUB_TYPE,
             typename label_iterator
@@ -589,45 +562,32 @@ namespace dlib
         void to_label (
             const tensor& input_tensor,
             const SUB_TYPE& sub,
-            label_iterator iter_begin
+            label_iterator iter
         ) const
         {
             const tensor& output_tensor = sub.get_output();
             DLIB_CASSERT(sub.sample_expansion_factor() == 1);
-            DLIB_CASSERT(output_tensor.nr() == 1 && 
-                         output_tensor.nc() == 1 );
             DLIB_CASSERT(input_tensor.num_samples() == output_tensor.num_samples());
+            DLIB_CASSERT(blank_label < (unsigned long)output_tensor.k());
 
-            DLIB_CASSERT(number_of_labels() != 0, "You must give the loss_multimulticlass_log_'s constructor label data before you can use it!");
-            DLIB_CASSERT(output_tensor.k() == (long)number_of_labels(), "The output tensor must have " << number_of_labels() << " channels.");
-
-
-            long k_offset = 0;
-            for (auto& l : possible_labels)
+            matrix<double> log_probs;
+            for (long i = 0; i < output_tensor.num_samples(); ++i)
             {
-                auto iter = iter_begin;
-                const std::string& classifier_name = l.first;
-                const auto& labels = (*l.second); 
-                scratch.set_size(output_tensor.num_samples(), labels.size());
-                tt::copy_tensor(false, scratch, 0, output_tensor, k_offset, labels.size());
-
-                tt::softmax(scratch, scratch);
-
-                for (long i = 0; i < scratch.num_samples(); ++i)
-                    (*iter++)[classifier_name] = classifier_output(rowm(mat(scratch),i), l.second);
-
-                k_offset += labels.size();
+                get_log_probs(output_tensor, i, log_probs);
+                if (beam_size == 1)
+                    greedy_decode(log_probs, *iter++);
+                else
+                    beam_search_decode(log_probs, *iter++);
             }
         }
 
-
         template <
             typename const_label_iterator,
             typename SUBNET
             >
         double compute_loss_value_and_gradient (
             const tensor& input_tensor,
-            const_label_iterator truth_begin, 
+            const_label_iterator truth, 
             SUBNET& sub
         ) const
         {
@@ -639,143 +599,294 @@ namespace dlib
             DLIB_CASSERT(input_tensor.num_samples()%sub.sample_expansion_factor() == 0);
             DLIB_CASSERT(input_tensor.num_samples() == grad.num_samples());
             DLIB_CASSERT(input_tensor.num_samples() == output_tensor.num_samples());
-            DLIB_CASSERT(output_tensor.nr() == 1 && 
-                         output_tensor.nc() == 1);
-            DLIB_CASSERT(grad.nr() == 1 && 
-                         grad.nc() == 1);
-            DLIB_CASSERT(number_of_labels() != 0, "You must give the loss_multimulticlass_log_'s constructor label data before you can use it!");
-            DLIB_CASSERT(output_tensor.k() == (long)number_of_labels(), "The output tensor must have " << number_of_labels() << " channels.");
+            DLIB_CASSERT(have_same_dimensions(output_tensor, grad));
+            DLIB_CASSERT(blank_label < (unsigned long)output_tensor.k());
 
+            const long num_steps = output_tensor.nr()*output_tensor.nc();
+            const long k = output_tensor.k();
             // The loss we output is the average loss over the mini-batch.
             const double scale = 1.0/output_tensor.num_samples();
             double loss = 0;
-            long k_offset = 0;
-            for (auto& l : label_idx_lookup)
+            float* g = grad.host();
+            matrix<double> log_probs, log_alpha, log_beta, log_occupancy;
+            std::vector<unsigned long> path;
+            for (long i = 0; i < output_tensor.num_samples(); ++i)
             {
-                const std::string& classifier_name = l.first;
-                const auto& int_labels = l.second; 
-                scratch.set_size(output_tensor.num_samples(), int_labels.size());
-                tt::copy_tensor(false, scratch, 0, output_tensor, k_offset, int_labels.size());
-
-                tt::softmax(scratch, scratch);
-
+                const std::vector<unsigned long>& label = *truth++;
 
-                auto truth = truth_begin;
-                float* g = scratch.host();
-                for (long i = 0; i < scratch.num_samples(); ++i)
+                // The label is aligned with the outputs through a path that puts a blank
+                // before and after each symbol.
+                path.assign(2*label.size()+1, blank_label);
+                long num_required_steps = label.size();
+                for (size_t j = 0; j < label.size(); ++j)
                 {
-                    const long y = int_labels.at(truth->at(classifier_name));
-                    ++truth;
-
-                    for (long k = 0; k < scratch.k(); ++k)
+                    DLIB_CASSERT(label[j] < (unsigned long)k && label[j] != blank_label,
+                        "The labels must be < k and not the blank label. label[j]: " << label[j] 
+                        << ", output_tensor.k(): " << k << ", blank label: " << blank_label);
+                    path[2*j+1] = label[j];
+                    if (j > 0 && label[j] == label[j-1])
+                        ++num_required_steps;
+                }
+                DLIB_CASSERT(num_required_steps <= num_steps,
+                    "The label sequence is too long to be aligned with the network output. "
+                    << "label.size(): " << label.size() << ", number of time steps: " << num_steps);
+
+                get_log_probs(output_tensor, i, log_probs);
+                const long num_states = path.size();
+                const double neg_inf = -std::numeric_limits<double>::infinity();
+
+                // Forward pass: log_alpha(t,s) is the log probability of all the alignments
+                // of the first s+1 path states with outputs 0 through t.
+                log_alpha.set_size(num_steps, num_states);
+                log_alpha = neg_inf;
+                log_alpha(0,0) = log_probs(0,path[0]);
+                if (num_states > 1)
+                    log_alpha(0,1) = log_probs(0,path[1]);
+                for (long t = 1; t < num_steps; ++t)
+                {
+                    for (long s = 0; s < num_states; ++s)
                     {
-                        const unsigned long idx = i*scratch.k()+k;
-                        if (k == y)
-                        {
-                            loss += scale*-std::log(g[idx]);
-                            g[idx] = scale*(g[idx]-1);
-                        }
-                        else
-                        {
-                            g[idx] = scale*g[idx];
-                        }
+                        double a = log_alpha(t-1,s);
+                        if (s > 0)
+                            a = log_add(a, log_alpha(t-1,s-1));
+                        if (can_skip(path, s))
+                            a = log_add(a, log_alpha(t-1,s-2));
+                        log_alpha(t,s) = a + log_probs(t,path[s]);
                     }
                 }
 
-                tt::copy_tensor(false, grad, k_offset, scratch, 0, int_labels.size());
+                // Backward pass: log_beta(t,s) is the log probability of the rest of the
+                // path given that output t is aligned with state s.
+                log_beta.set_size(num_steps, num_states);
+                log_beta = neg_inf;
+                log_beta(num_steps-1,num_states-1) = 0;
+                if (num_states > 1)
+                    log_beta(num_steps-1,num_states-2) = 0;
+                for (long t = num_steps-2; t >= 0; --t)
+                {
+                    for (long s = 0; s < num_states; ++s)
+                    {
+                        double b = log_beta(t+1,s) + log_probs(t+1,path[s]);
+                        if (s+1 < num_states)
+                            b = log_add(b, log_beta(t+1,s+1) + log_probs(t+1,path[s+1]));
+                        if (s+2 < num_states && can_skip(path, s+2))
+                            b = log_add(b, log_beta(t+1,s+2) + log_probs(t+1,path[s+2]));
+                        log_beta(t,s) = b;
+                    }
+                }
 
-                k_offset += int_labels.size();
+                double log_likelihood = log_alpha(num_steps-1,num_states-1);
+                if (num_states > 1)
+                    log_likelihood = log_add(log_likelihood, log_alpha(num_steps-1,num_states-2));
+                loss += scale*-log_likelihood;
+
+                // The gradient with respect to the network outputs is the softmax output
+                // minus the posterior probability that each output is aligned with each
+                // class.
+                log_occupancy.set_size(num_steps, k);
+                log_occupancy = neg_inf;
+                for (long t = 0; t < num_steps; ++t)
+                {
+                    for (long s = 0; s < num_states; ++s)
+                        log_occupancy(t,path[s]) = log_add(log_occupancy(t,path[s]), log_alpha(t,s) + log_beta(t,s));
+                }
+                for (long t = 0; t < num_steps; ++t)
+                {
+                    for (long c = 0; c < k; ++c)
+                    {
+                        const double d = std::exp(log_probs(t,c)) - std::exp(log_occupancy(t,c) - log_likelihood);
+                        g[(i*k + c)*num_steps + t] = scale*d;
+                    }
+                }
             }
             return loss;
         }
 
-
-        friend void serialize(const loss_multimulticlass_log_& item, std::ostream& out)
+        friend void serialize(const loss_ctc_& item, std::ostream& out)
         {
-            serialize("loss_multimulticlass_log_", out);
-            serialize(item.get_labels(), out);
+            serialize("loss_ctc_", out);
+            serialize(item.blank_label, out);
+            serialize(item.beam_size, out);
         }
 
-        friend void deserialize(loss_multimulticlass_log_& item, std::istream& in)
+        friend void deserialize(loss_ctc_& item, std::istream& in)
         {
             std::string version;
             deserialize(version, in);
-            if (version != "loss_multimulticlass_log_")
-                throw serialization_error("Unexpected version found while deserializing dlib::loss_multimulticlass_log_.");
+            if (version != "loss_ctc_")
+                throw serialization_error("Unexpected version found while deserializing dlib::loss_ctc_.");
+            deserialize(item.blank_label, in);
+            deserialize(item.beam_size, in);
+        }
 
-            std::map<std::string,std::vector<std::string>> info; 
-            deserialize(info, in);
-            item = loss_multimulticlass_log_(info);
+        friend std::ostream& operator<<(std::ostream& out, const loss_ctc_& item)
+        {
+            out << "loss_ctc (blank_label=" << item.blank_label << ", beam_size=" << item.beam_size << ")";
+            return out;
         }
 
-        friend std::ostream& operator<<(std::ostream& out, const loss_multimulticlass_log_& item)
+        friend void to_xml(const loss_ctc_& item, std::ostream& out)
         {
-            out << "loss_multimulticlass_log, labels={";
-            for (auto i = item.possible_labels.begin(); i != item.possible_labels.end(); )
-            {
-                auto& category = i->first;
-                auto& labels = *(i->second);
-                out << category << ":(";
-                for (size_t j = 0; j < labels.size(); ++j)
-                {
-                    out << labels[j];
-                    if (j+1 < labels.size())
-                        out << ",";
-                }
+            out << "<loss_ctc blank_label='" << item.blank_label << "' beam_size='" << item.beam_size << "'/>\n";
+        }
 
-                out << ")";
-                if (++i != item.possible_labels.end())
-                    out << ", ";
+    private:
+
+        static double log_add (
+            double a,
+            double b
+        )
+        {
+            if (a < b)
+                std::swap(a, b);
+            if (b == -std::numeric_limits<double>::infinity())
+                return a;
+            return a + log1pexp(b - a);
+        }
+
+        bool can_skip (
+            const std::vector<unsigned long>& path,
+            long s
+        ) const
+        {
+            // A path state can be reached directly from two states back if it's a symbol
+            // and the blank in between isn't needed to separate two equal symbols.
+            return s >= 2 && path[s] != blank_label && path[s] != path[s-2];
+        }
+
+        static void get_log_probs (
+            const tensor& output_tensor,
+            long sample,
+            matrix<double>& log_probs
+        )
+        {
+            // Each time step is one spatial location of the output tensor, in row major
+            // order, and the log softmax of the channels there gives the class log
+            // probabilities.
+            const long num_steps = output_tensor.nr()*output_tensor.nc();
+            const long k = output_tensor.k();
+            const float* out_data = output_tensor.host() + sample*k*num_steps;
+            log_probs.set_size(num_steps, k);
+            for (long t = 0; t < num_steps; ++t)
+            {
+                double max_val = -std::numeric_limits<double>::infinity();
+                for (long c = 0; c < k; ++c)
+                    max_val = std::max(max_val, (double)out_data[c*num_steps + t]);
+                double sum = 0;
+                for (long c = 0; c < k; ++c)
+                    sum += std::exp(out_data[c*num_steps + t] - max_val);
+                const double log_norm = max_val + std::log(sum);
+                for (long c = 0; c < k; ++c)
+                    log_probs(t,c) = out_data[c*num_steps + t] - log_norm;
             }
-            out << "}";
-            return out;
         }
 
-        friend void to_xml(const loss_multimulticlass_log_& item, std::ostream& out)
+        void greedy_decode (
+            const matrix<double>& log_probs,
+            std::vector<unsigned long>& label
+        ) const
         {
-            out << "<loss_multimulticlass_log>\n";
-            out << item;
-            out << "\n</loss_multimulticlass_log>\n";
+            label.clear();
+            unsigned long prev = blank_label;
+            for (long t = 0; t < log_probs.nr(); ++t)
+            {
+                const unsigned long c = index_of_max(rowm(log_probs,t));
+                if (c != blank_label && c != prev)
+                    label.push_back(c);
+                prev = c;
+            }
         }
 
-    private:
+        void beam_search_decode (
+            const matrix<double>& log_probs,
+            std::vector<unsigned long>& label
+        ) const
+        {
+            // This is the CTC prefix beam search.  Each prefix keeps the log probability
+            // of the alignments that give it and end in a blank, and of those that end in
+            // its last symbol, since they extend differently.
+            struct prefix_score
+            {
+                double blank = -std::numeric_limits<double>::infinity();
+                double symbol = -std::numeric_limits<double>::infinity();
+                double total() const { return log_add(blank, symbol); }
+            };
+            typedef std::map<std::vector<unsigned long>, prefix_score> beam_type;
 
-        std::map<std::string,std::shared_ptr<std::vector<std::string>>> possible_labels;
-        unsigned long total_num_labels = 0;
+            beam_type beam;
+            beam[std::vector<unsigned long>()].blank = 0;
+            std::vector<std::pair<double, const std::vector<unsigned long>*>> ranked;
+            for (long t = 0; t < log_probs.nr(); ++t)
+            {
+                beam_type next_beam;
+                for (const auto& item : beam)
+                {
+                    const std::vector<unsigned long>& prefix = item.first;
+                    const prefix_score& score = item.second;
 
-        // We make it true that: possible_labels[classifier][label_idx_lookup[classifier][label]] == label
-        std::map<std::string, std::map<std::string, size_t>> label_idx_lookup;
+                    prefix_score& same = next_beam[prefix];
+                    same.blank = log_add(same.blank, score.total() + log_probs(t,blank_label));
+                    if (prefix.size() != 0)
+                        same.symbol = log_add(same.symbol, score.symbol + log_probs(t,prefix.back()));
 
+                    std::vector<unsigned long> extended = prefix;
+                    extended.push_back(0);
+                    for (long c = 0; c < log_probs.nc(); ++c)
+                    {
+                        if ((unsigned long)c == blank_label)
+                            continue;
+                        extended.back() = c;
+                        prefix_score& ext = next_beam[extended];
+                        // Repeating the last symbol only starts a new symbol if there was
+                        // a blank in between.
+                        if (prefix.size() != 0 && prefix.back() == (unsigned long)c)
+                            ext.symbol = log_add(ext.symbol, score.blank + log_probs(t,c));
+                        else
+                            ext.symbol = log_add(ext.symbol, score.total() + log_probs(t,c));
+                    }
+                }
 
-        // Scratch doesn't logically contribute to the state of this object.  It's just
-        // temporary scratch space used by this class.  
-        mutable resizable_tensor scratch;
+                ranked.clear();
+                for (const auto& item : next_beam)
+                    ranked.emplace_back(item.second.total(), &item.first);
+                const size_t num_keep = std::min<size_t>(beam_size, ranked.size());
+                std::partial_sort(ranked.begin(), ranked.begin()+num_keep, ranked.end(),
+                    [](const std::pair<double, const std::vector<unsigned long>*>& a,
+                       const std::pair<double, const std::vector<unsigned long>*>& b)
+                    { return a.first > b.first; });
+                beam.clear();
+                for (size_t j = 0; j < num_keep; ++j)
+                    beam[*ranked[j].second] = next_beam[*ranked[j].second];
+            }
 
+            double best_score = -std::numeric_limits<double>::infinity();
+            label.clear();
+            for (const auto& item : beam)
+            {
+                if (item.second.total() > best_score)
+                {
+                    best_score = item.second.total();
+                    label = item.first;
+                }
+            }
+        }
 
+        unsigned long blank_label = 0;
+        unsigned long beam_size = 1;
     };
 
     template <typename SUBNET>
-    using loss_multimulticlass_log = add_loss_layer<loss_multimulticlass_log_, SUBNET>;
-
-    inline bool operator== (const std::string& lhs, const loss_multimulticlass_log_::classifier_output& rhs)
-    { return lhs == static_cast<const std::string&>(rhs); }
-    inline bool operator== (const loss_multimulticlass_log_::classifier_output& lhs, const std::string& rhs)
-    { return rhs == static_cast<const std::string&>(lhs); }
+    using loss_ctc = add_loss_layer<loss_ctc_, SUBNET>;
 
 // ----------------------------------------------------------------------------------------
 
-    class loss_multibinary_log_
+    class loss_multiclass_log_weighted_
     {
     public:
-        typedef std::vector<float> training_label_type;
-        typedef std::vector<float> output_label_type;
 
-        loss_multibinary_log_() = default;
-
-        loss_multibinary_log_(double gamma) : gamma(gamma)
-        {
-            DLIB_CASSERT(gamma >= 0);
-        }
+        typedef dlib::weighted_label<unsigned long> weighted_label;
+        typedef weighted_label training_label_type;
+        typedef unsigned long output_label_type;
 
         template <
             typename SUB_TYPE,
@@ -789,20 +900,17 @@ namespace dlib
         {
             const tensor& output_tensor = sub.get_output();
             DLIB_CASSERT(sub.sample_expansion_factor() == 1);
-            DLIB_CASSERT(output_tensor.nr() == 1 && output_tensor.nc() == 1);
+            DLIB_CASSERT(output_tensor.nr() == 1 &&
+                         output_tensor.nc() == 1 );
             DLIB_CASSERT(input_tensor.num_samples() == output_tensor.num_samples());
 
+
             // Note that output_tensor.k() should match the number of labels.
 
-            const float* out_data = output_tensor.host();
             for (long i = 0; i < output_tensor.num_samples(); ++i)
             {
-                output_label_type predictions(output_tensor.k(), 0);
-                for (long k = 0; k < output_tensor.k(); ++k)
-                {
-                    predictions[k] = out_data[i * output_tensor.k() + k];
-                }
-                *iter++ = std::move(predictions);
+                // The index of the largest output for this sample is the label.
+                *iter++ = index_of_max(rowm(mat(output_tensor),i));
             }
         }
 
@@ -821,521 +929,379 @@ namespace dlib
 
             DLIB_CASSERT(sub.sample_expansion_factor() == 1);
             DLIB_CASSERT(input_tensor.num_samples() != 0);
-            DLIB_CASSERT(input_tensor.num_samples() % sub.sample_expansion_factor() == 0);
+            DLIB_CASSERT(input_tensor.num_samples()%sub.sample_expansion_factor() == 0);
             DLIB_CASSERT(input_tensor.num_samples() == grad.num_samples());
             DLIB_CASSERT(input_tensor.num_samples() == output_tensor.num_samples());
-            DLIB_CASSERT(output_tensor.nr() == 1 && output_tensor.nc() == 1);
-            DLIB_CASSERT(grad.nr() == 1 && grad.nc() == 1);
+            DLIB_CASSERT(output_tensor.nr() == 1 &&
+                         output_tensor.nc() == 1);
+            DLIB_CASSERT(grad.nr() == 1 &&
+                         grad.nc() == 1);
 
-            tt::sigmoid(grad, output_tensor);
+            tt::softmax(grad, output_tensor);
 
             // The loss we output is the average loss over the mini-batch.
-            const double scale = 1.0 / output_tensor.num_samples();
+            const double scale = 1.0/output_tensor.num_samples();
             double loss = 0;
             float* g = grad.host();
-            const float* out_data  = output_tensor.host();
-            for (long i = 0; i < output_tensor.num_samples(); ++i, ++truth)
+            for (long i = 0; i < output_tensor.num_samples(); ++i)
             {
-                const long long num_label_categories = truth->size();
-                DLIB_CASSERT(output_tensor.k() == num_label_categories,
-                    "Number of label types should match the number of output channels. "
-                    "output_tensor.k(): " << output_tensor.k() 
-                    << ", num_label_categories: "<< num_label_categories);
+                const auto wl = *truth++;
+                const long y = wl.label;
+                const float weight = wl.weight;
+                // The network must produce a number of outputs that is equal to the number
+                // of labels when using this type of loss.
+                DLIB_CASSERT(y < output_tensor.k(), "y: " << y << ", output_tensor.k(): " << output_tensor.k());
                 for (long k = 0; k < output_tensor.k(); ++k)
                 {
-                    const float y = (*truth)[k];
-                    DLIB_CASSERT(y != 0, "y: " << y);
-                    const size_t idx = i * output_tensor.k() + k;
-                    if (y > 0)
+                    const unsigned long idx = i*output_tensor.k()+k;
+                    if (k == y)
                     {
-                        const float temp = log1pexp(-out_data[idx]);
-                        const float focus = std::pow(1 - g[idx], gamma);
-                        loss += y * scale * temp * focus;
-                        g[idx] = y * scale * focus * (g[idx] * (gamma * temp + 1) - 1);
+                        loss += weight*scale*-safe_log(g[idx]);
+                        g[idx] =weight*scale*(g[idx]-1);
                     }
                     else
                     {
-                        const float temp = -(-out_data[idx] - log1pexp(-out_data[idx]));
-                        const float focus = std::pow(g[idx], gamma);
-                        loss += -y * scale * temp * focus;
-                        g[idx] = -y * scale * focus * g[idx] * (gamma * temp + 1);
+                        g[idx] = weight*scale*g[idx];
                     }
                 }
             }
             return loss;
         }
 
-        double get_gamma () const { return gamma; }
-
-        friend void serialize(const loss_multibinary_log_& item, std::ostream& out)
+        friend void serialize(const loss_multiclass_log_weighted_& , std::ostream& out)
         {
-            serialize("loss_multibinary_log_2", out);
-            serialize(item.gamma, out);
+            serialize("loss_multiclass_log_weighted_", out);
         }
 
-        friend void deserialize(loss_multibinary_log_& item, std::istream& in)
+        friend void deserialize(loss_multiclass_log_weighted_& , std::istream& in)
         {
             std::string version;
             deserialize(version, in);
-            if (version == "loss_multibinary_log_")
-            {
-                item.gamma = 0;
-                return;
-            }
-            else if (version == "loss_multibinary_log_2")
-            {
-                deserialize(item.gamma, in);
-            }
-            else
-            {
-                throw serialization_error("Unexpected version found while deserializing dlib::loss_multibinary_log_.");
-            }
+            if (version != "loss_multiclass_log_weighted_")
+                throw serialization_error("Unexpected version found while deserializing dlib::loss_multiclass_log_weighted_.");
         }
 
-        friend std::ostream& operator<<(std::ostream& out, const loss_multibinary_log_& item)
+        friend std::ostream& operator<<(std::ostream& out, const loss_multiclass_log_weighted_& )
         {
-            out << "loss_multibinary_log (gamma=" << item.gamma << ")";
+            out << "loss_multiclass_log_weighted";
             return out;
         }
 
-        friend void to_xml(const loss_multibinary_log_& item, std::ostream& out)
+        friend void to_xml(const loss_multiclass_log_weighted_& /*item*/, std::ostream& out)
         {
-            out << "<loss_multibinary_log gamma='" << item.gamma << "'/>\n";
+            out << "<loss_multiclass_log_weighted/>\n";
         }
 
-    private:
-        double gamma = 0;
     };
 
     template <typename SUBNET>
-    using loss_multibinary_log = add_loss_layer<loss_multibinary_log_, SUBNET>;
+    using loss_multiclass_log_weighted = add_loss_layer<loss_multiclass_log_weighted_, SUBNET>;
 
-// ----------------------------------------------------------------------------------------
 // ----------------------------------------------------------------------------------------
 
-    enum class use_image_pyramid : uint8_t
+    struct distillation_label
     {
-        no,
-        yes
+        distillation_label()
+        {}
+
+        distillation_label(
+            unsigned long label,
+            const matrix<float,0,1>& teacher_logits
+        ) : label(label), teacher_logits(teacher_logits)
+        {}
+
+        unsigned long label = 0;
+        matrix<float,0,1> teacher_logits;
     };
 
-    struct mmod_options
+    inline void serialize(const distillation_label& item, std::ostream& out)
     {
-    public:
-
-        struct detector_window_details
-        {
-            detector_window_details() = default; 
-            detector_window_details(unsigned long w, unsigned long h) : width(w), height(h) {}
-            detector_window_details(unsigned long w, unsigned long h, const std::string& l) : width(w), height(h), label(l) {}
+        serialize("distillation_label", out);
+        serialize(item.label, out);
+        serialize(item.teacher_logits, out);
+    }
 
-            unsigned long width = 0;
-            unsigned long height = 0;
-            std::string label;
+    inline void deserialize(distillation_label& item, std::istream& in)
+    {
+        std::string version;
+        deserialize(version, in);
+        if (version != "distillation_label")
+            throw serialization_error("Unexpected version found while deserializing dlib::distillation_label.");
+        deserialize(item.label, in);
+        deserialize(item.teacher_logits, in);
+    }
 
-            friend inline void serialize(const detector_window_details& item, std::ostream& out)
-            {
-                int version = 2;
-                serialize(version, out);
-                serialize(item.width, out);
-                serialize(item.height, out);
-                serialize(item.label, out);
-            }
+    class loss_multiclass_distillation_
+    {
+    public:
 
-            friend inline void deserialize(detector_window_details& item, std::istream& in)
-            {
-                int version = 0;
-                deserialize(version, in);
-                if (version != 1 && version != 2)
-                    throw serialization_error("Unexpected version found while deserializing dlib::mmod_options::detector_window_details");
-                deserialize(item.width, in);
-                deserialize(item.height, in);
-                if (version == 2)
-                    deserialize(item.label, in);
-            }
+        typedef distillation_label training_label_type;
+        typedef unsigned long output_label_type;
 
-        };
+        loss_multiclass_distillation_() = default;
 
-        mmod_options() = default;
+        loss_multiclass_distillation_(
+            double temperature_,
+            double alpha_
+        ) : temperature(temperature_), alpha(alpha_)
+        {
+            DLIB_CASSERT(temperature > 0);
+            DLIB_CASSERT(0 <= alpha && alpha <= 1);
+        }
 
-        std::vector<detector_window_details> detector_windows;
-        double loss_per_false_alarm = 1;
-        double loss_per_missed_target = 1;
-        double truth_match_iou_threshold = 0.5;
-        test_box_overlap overlaps_nms = test_box_overlap(0.4);
-        test_box_overlap overlaps_ignore;
-        bool use_bounding_box_regression = false; 
-        double bbr_lambda = 100; 
-        // This field is intentionally not serialized because I want people to really think hard
-        // about ignoring the warnings that this suppresses.
-        bool be_quiet = false;
+        double get_temperature (
+        ) const { return temperature; }
 
-        use_image_pyramid assume_image_pyramid = use_image_pyramid::yes;
+        double get_alpha (
+        ) const { return alpha; }
 
-        mmod_options (
-            const std::vector<std::vector<mmod_rect>>& boxes,
-            const unsigned long target_size,       // We want the length of the longest dimension of the detector window to be this.
-            const unsigned long min_target_size,   // But we require that the smallest dimension of the detector window be at least this big.
-            const double min_detector_window_overlap_iou = 0.75
-        )
+        template <
+            typename SUB_TYPE,
+            typename label_iterator
+            >
+        void to_label (
+            const tensor& input_tensor,
+            const SUB_TYPE& sub,
+            label_iterator iter
+        ) const
         {
-            DLIB_CASSERT(0 < min_target_size && min_target_size <= target_size);
-            DLIB_CASSERT(0.5 < min_detector_window_overlap_iou && min_detector_window_overlap_iou < 1);
+            loss_multiclass_log_().to_label(input_tensor, sub, iter);
+        }
 
-            // Figure out what detector windows we will need.
-            for (auto& label : get_labels(boxes))
-            {
-                for (auto ratio : find_covering_aspect_ratios(boxes, test_box_overlap(min_detector_window_overlap_iou), label))
-                {
-                    double detector_width;
-                    double detector_height;
-                    if (ratio < 1)
-                    {
-                        detector_height = target_size;
-                        detector_width = ratio*target_size;
-                        if (detector_width < min_target_size)
-                        {
-                            detector_height = min_target_size/ratio;
-                            detector_width = min_target_size;
-                        }
-                    }
-                    else
-                    {
-                        detector_width = target_size;
-                        detector_height = target_size/ratio;
-                        if (detector_height < min_target_size)
-                        {
-                            detector_width = min_target_size*ratio;
-                            detector_height = min_target_size;
-                        }
-                    }
-
-                    detector_window_details p((unsigned long)std::round(detector_width), (unsigned long)std::round(detector_height), label);
-                    detector_windows.push_back(p);
-                }
-            }
-
-            DLIB_CASSERT(detector_windows.size() != 0, "You can't call mmod_options's constructor with a set of boxes that is empty (or only contains ignored boxes).");
-
-            set_overlap_nms(boxes);
-        }
-
-        mmod_options(
-            use_image_pyramid assume_image_pyramid,
-            const std::vector<std::vector<mmod_rect>>& boxes,
-            const double min_detector_window_overlap_iou = 0.75
-        )
-            : assume_image_pyramid(assume_image_pyramid)
+        template <
+            typename const_label_iterator,
+            typename SUBNET
+            >
+        double compute_loss_value_and_gradient (
+            const tensor& input_tensor,
+            const_label_iterator truth, 
+            SUBNET& sub
+        ) const
         {
-            DLIB_CASSERT(assume_image_pyramid == use_image_pyramid::no);
-            DLIB_CASSERT(0.5 < min_detector_window_overlap_iou && min_detector_window_overlap_iou < 1);
+            const tensor& output_tensor = sub.get_output();
+            tensor& grad = sub.get_gradient_input();
 
-            // Figure out what detector windows we will need.
-            for (auto& label : get_labels(boxes))
+            DLIB_CASSERT(sub.sample_expansion_factor() == 1);
+            DLIB_CASSERT(input_tensor.num_samples() != 0);
+            DLIB_CASSERT(input_tensor.num_samples()%sub.sample_expansion_factor() == 0);
+            DLIB_CASSERT(input_tensor.num_samples() == grad.num_samples());
+            DLIB_CASSERT(input_tensor.num_samples() == output_tensor.num_samples());
+            DLIB_CASSERT(output_tensor.nr() == 1 && 
+                         output_tensor.nc() == 1);
+            DLIB_CASSERT(grad.nr() == 1 && 
+                         grad.nc() == 1);
+
+            const long num_classes = output_tensor.k();
+            // The loss we output is the average loss over the mini-batch.
+            const double scale = 1.0/output_tensor.num_samples();
+            double loss = 0;
+            const float* out_data = output_tensor.host();
+            float* g = grad.host();
+            matrix<double,0,1> student, hard, soft_student, soft_teacher;
+            for (long i = 0; i < output_tensor.num_samples(); ++i)
             {
-                for (auto rectangle : find_covering_rectangles(boxes, test_box_overlap(min_detector_window_overlap_iou), label))
+                const distillation_label& l = *truth++;
+                DLIB_CASSERT(l.label < (unsigned long)num_classes, "label: " << l.label << ", output_tensor.k(): " << num_classes);
+                DLIB_CASSERT(l.teacher_logits.size() == num_classes,
+                    "The teacher must output one logit for each class the student outputs. "
+                    << "teacher_logits.size(): " << l.teacher_logits.size() << ", output_tensor.k(): " << num_classes);
+
+                student = matrix_cast<double>(mat(out_data + i*num_classes, num_classes, 1));
+                log_softmax(student/temperature, soft_student);
+                log_softmax(matrix_cast<double>(l.teacher_logits)/temperature, soft_teacher);
+                log_softmax(student, hard);
+
+                // The distillation term is T^2 times the KL divergence between the
+                // softened teacher and student distributions.  The T^2 keeps its gradient
+                // on the same scale as the hard label term's for any temperature.
+                const double kl = sum(pointwise_multiply(exp(soft_teacher), soft_teacher - soft_student));
+                loss += scale*(alpha*temperature*temperature*kl - (1-alpha)*hard(l.label));
+                for (long k = 0; k < num_classes; ++k)
                 {
-                    detector_windows.push_back(detector_window_details(rectangle.width(), rectangle.height(), label));
+                    const double hard_grad = std::exp(hard(k)) - (k == (long)l.label ? 1 : 0);
+                    const double soft_grad = temperature*(std::exp(soft_student(k)) - std::exp(soft_teacher(k)));
+                    g[i*num_classes + k] = scale*(alpha*soft_grad + (1-alpha)*hard_grad);
                 }
             }
-
-            DLIB_CASSERT(detector_windows.size() != 0, "You can't call mmod_options's constructor with a set of boxes that is empty (or only contains ignored boxes).");
-
-            set_overlap_nms(boxes);
+            return loss;
         }
 
-    private:
+        friend void serialize(const loss_multiclass_distillation_& item, std::ostream& out)
+        {
+            serialize("loss_multiclass_distillation_", out);
+            serialize(item.temperature, out);
+            serialize(item.alpha, out);
+        }
 
-        void set_overlap_nms(const std::vector<std::vector<mmod_rect>>& boxes)
+        friend void deserialize(loss_multiclass_distillation_& item, std::istream& in)
         {
-            // Convert from mmod_rect to rectangle so we can call
-            // find_tight_overlap_tester().
-            std::vector<std::vector<rectangle>> temp;
-            for (auto&& bi : boxes)
-            {
-                std::vector<rectangle> rtemp;
-                for (auto&& b : bi)
-                {
-                    if (b.ignore)
-                        continue;
-                    rtemp.push_back(b.rect);
-                }
-                temp.push_back(std::move(rtemp));
-            }
-            overlaps_nms = find_tight_overlap_tester(temp);
-            // Relax the non-max-suppression a little so that it doesn't accidentally make
-            // it impossible for the detector to output boxes matching the training data.
-            // This could be a problem with the tightest possible nms test since there is
-            // some small variability in how boxes get positioned between the training data
-            // and the coordinate system used by the detector when it runs.  So relaxing it
-            // here takes care of that.
-            auto iou_thresh             = advance_toward_1(overlaps_nms.get_iou_thresh());
-            auto percent_covered_thresh = advance_toward_1(overlaps_nms.get_percent_covered_thresh());
-            overlaps_nms = test_box_overlap(iou_thresh, percent_covered_thresh);
+            std::string version;
+            deserialize(version, in);
+            if (version != "loss_multiclass_distillation_")
+                throw serialization_error("Unexpected version found while deserializing dlib::loss_multiclass_distillation_.");
+            deserialize(item.temperature, in);
+            deserialize(item.alpha, in);
         }
 
-        static double advance_toward_1 (
-            double val
-        )
+        friend std::ostream& operator<<(std::ostream& out, const loss_multiclass_distillation_& item)
         {
-            if (val < 1)
-                val += (1-val)*0.1;
-            return val;
+            out << "loss_multiclass_distillation (temperature=" << item.temperature << ", alpha=" << item.alpha << ")";
+            return out;
         }
 
-        static size_t count_overlaps (
-            const std::vector<rectangle>& rects,
-            const test_box_overlap& overlaps,
-            const rectangle& ref_box
-        )
+        friend void to_xml(const loss_multiclass_distillation_& item, std::ostream& out)
         {
-            size_t cnt = 0;
-            for (auto& b : rects)
-            {
-                if (overlaps(b, ref_box))
-                    ++cnt;
-            }
-            return cnt;
+            out << "<loss_multiclass_distillation temperature='" << item.temperature << "' alpha='" << item.alpha << "'/>\n";
         }
 
-        static std::vector<rectangle> find_rectangles_overlapping_all_others (
-            std::vector<rectangle> rects,
-            const test_box_overlap& overlaps
+    private:
+
+        static void log_softmax (
+            const matrix<double,0,1>& x,
+            matrix<double,0,1>& result
         )
         {
-            std::vector<rectangle> exemplars;
-            dlib::rand rnd;
+            const double m = max(x);
+            result = x - (m + std::log(sum(exp(x - m))));
+        }
 
-            while(rects.size() > 0)
-            {
-                // Pick boxes at random and see if they overlap a lot of other boxes.  We will try
-                // 500 different boxes each iteration and select whichever hits the most others to
-                // add to our exemplar set.
-                rectangle best_ref_box;
-                size_t best_cnt = 0;
-                for (int iter = 0; iter < 500; ++iter)
-                {
-                    rectangle ref_box = rects[rnd.get_random_64bit_number()%rects.size()];
-                    size_t cnt = count_overlaps(rects, overlaps, ref_box);
-                    if (cnt >= best_cnt)
-                    {
-                        best_cnt = cnt;
-                        best_ref_box = ref_box;
-                    }
-                }
+        double temperature = 4;
+        double alpha = 0.9;
+    };
 
-                // Now mark all the boxes the new ref box hit as hit.
-                for (size_t i = 0; i < rects.size(); ++i)
-                {
-                    if (overlaps(rects[i], best_ref_box))
-                    {
-                        // remove box from rects so we don't hit it again later
-                        swap(rects[i], rects.back());
-                        rects.pop_back();
-                        --i;
-                    }
-                }
+    template <typename SUBNET>
+    using loss_multiclass_distillation = add_loss_layer<loss_multiclass_distillation_, SUBNET>;
 
-                exemplars.push_back(best_ref_box);
-            }
+    template <
+        typename teacher_net_type,
+        typename input_type
+        >
+    std::vector<distillation_label> make_distillation_labels (
+        teacher_net_type& teacher,
+        const std::vector<input_type>& inputs,
+        const std::vector<unsigned long>& labels,
+        size_t mini_batch_size = 32
+    )
+    {
+        DLIB_CASSERT(inputs.size() == labels.size());
+        DLIB_CASSERT(mini_batch_size > 0);
+        std::vector<distillation_label> result;
+        result.reserve(inputs.size());
+        for (size_t i = 0; i < inputs.size(); i += mini_batch_size)
+        {
+            const size_t end = std::min(i + mini_batch_size, inputs.size());
+            // The subnet's output is the teacher's logits, before its loss layer turns
+            // them into labels.
+            const tensor& logits = teacher.subnet()(inputs.begin()+i, inputs.begin()+end);
+            DLIB_CASSERT(logits.num_samples() == (long long)(end-i) && logits.nr() == 1 && logits.nc() == 1,
+                "The teacher must output one column of logits for each sample.");
+            for (size_t j = i; j < end; ++j)
+                result.emplace_back(labels[j], matrix<float,0,1>(trans(rowm(mat(logits), j-i))));
+        }
+        return result;
+    }
 
-            return exemplars;
-        }
+// ----------------------------------------------------------------------------------------
 
-        static std::set<std::string> get_labels (
-            const std::vector<std::vector<mmod_rect>>& rects
-        )
-        {
-            std::set<std::string> labels;
-            for (auto& rr : rects)
-            {
-                for (auto& r : rr)
-                    labels.insert(r.label);
-            }
-            return labels;
-        }
+    class loss_multimulticlass_log_ 
+    {
 
-        static std::vector<double> find_covering_aspect_ratios (
-            const std::vector<std::vector<mmod_rect>>& rects,
-            const test_box_overlap& overlaps,
-            const std::string& label
+    public:
+
+        loss_multimulticlass_log_ () = default;
+
+        loss_multimulticlass_log_ (
+            const std::map<std::string,std::vector<std::string>>& labels
         )
         {
-            std::vector<rectangle> boxes;
-            // Make sure all the boxes have the same size and position, so that the only thing our
-            // checks for overlap will care about is aspect ratio (i.e. scale and x,y position are
-            // ignored).
-            for (auto& bb : rects)
+            for (auto& l : labels)
             {
-                for (auto&& b : bb)
+                possible_labels[l.first] = std::make_shared<decltype(l.second)>(l.second);
+                DLIB_CASSERT(l.second.size() >= 2, "Each classifier must have at least two possible labels.");
+
+                for (size_t i = 0; i < l.second.size(); ++i)
                 {
-                    if (!b.ignore && b.label == label)
-                        boxes.push_back(move_rect(set_rect_area(b.rect,400*400), point(0,0)));
+                    label_idx_lookup[l.first][l.second[i]] = i;
+                    ++total_num_labels;
                 }
             }
-
-            std::vector<double> ratios;
-            for (auto r : find_rectangles_overlapping_all_others(boxes, overlaps))
-                ratios.push_back(r.width()/(double)r.height());
-            return ratios;
         }
 
-        static std::vector<dlib::rectangle> find_covering_rectangles (
-            const std::vector<std::vector<mmod_rect>>& rects,
-            const test_box_overlap& overlaps,
-            const std::string& label
-        )
+        unsigned long number_of_labels() const { return total_num_labels; }
+
+        unsigned long number_of_classifiers() const { return possible_labels.size(); }
+
+        std::map<std::string,std::vector<std::string>> get_labels ( 
+        ) const 
         {
-            std::vector<rectangle> boxes;
-            // Make sure all the boxes have the same position, so that the we only check for
-            // width and height.
-            for (auto& bb : rects)
+            std::map<std::string,std::vector<std::string>> info; 
+            for (auto& i : possible_labels)
             {
-                for (auto&& b : bb)
-                {
-                    if (!b.ignore && b.label == label)
-                        boxes.push_back(rectangle(b.rect.width(), b.rect.height()));
-                }
+                for (auto& label : *i.second)
+                    info[i.first].emplace_back(label);
             }
-
-            return find_rectangles_overlapping_all_others(boxes, overlaps);
+            return info;
         }
-    };
 
-    inline void serialize(const mmod_options& item, std::ostream& out)
-    {
-        int version = 4;
+        class classifier_output
+        {
 
-        serialize(version, out);
-        serialize(item.detector_windows, out);
-        serialize(item.loss_per_false_alarm, out);
-        serialize(item.loss_per_missed_target, out);
-        serialize(item.truth_match_iou_threshold, out);
-        serialize(item.overlaps_nms, out);
-        serialize(item.overlaps_ignore, out);
-        serialize(static_cast<uint8_t>(item.assume_image_pyramid), out);
-        serialize(item.use_bounding_box_regression, out);
-        serialize(item.bbr_lambda, out);
-    }
+        public:
+            classifier_output() = default;
 
-    inline void deserialize(mmod_options& item, std::istream& in)
-    {
-        int version = 0;
-        deserialize(version, in);
-        if (!(1 <= version && version <= 4))
-            throw serialization_error("Unexpected version found while deserializing dlib::mmod_options");
-        if (version == 1)
-        {
-            unsigned long width;
-            unsigned long height;
-            deserialize(width, in);
-            deserialize(height, in);
-            item.detector_windows = {mmod_options::detector_window_details(width, height)};
-        }
-        else
-        {
-            deserialize(item.detector_windows, in);
-        }
-        deserialize(item.loss_per_false_alarm, in);
-        deserialize(item.loss_per_missed_target, in);
-        deserialize(item.truth_match_iou_threshold, in);
-        deserialize(item.overlaps_nms, in);
-        deserialize(item.overlaps_ignore, in);
-        item.assume_image_pyramid = use_image_pyramid::yes;
-        if (version >= 3)
-        {
-            uint8_t assume_image_pyramid = 0;
-            deserialize(assume_image_pyramid, in);
-            item.assume_image_pyramid = static_cast<use_image_pyramid>(assume_image_pyramid);
-        }
-        item.use_bounding_box_regression = mmod_options().use_bounding_box_regression; // use default value since this wasn't provided
-        item.bbr_lambda = mmod_options().bbr_lambda; // use default value since this wasn't provided
-        if (version >= 4)
-        {
-            deserialize(item.use_bounding_box_regression, in);
-            deserialize(item.bbr_lambda, in);
-        }
-    }
-
-    inline std::ostream& operator<<(std::ostream& out, const std::vector<mmod_options::detector_window_details>& detector_windows)
-    {
-        // write detector windows grouped by label
-        // example output: aeroplane:74x30,131x30,70x45,54x70,198x30;bicycle:70x57,32x70,70x32,51x70,128x30,30x121;car:70x36,70x60,99x30,52x70,30x83,30x114,30x200
-
-        std::map<std::string, std::deque<mmod_options::detector_window_details>> detector_windows_by_label;
-        for (const auto& detector_window : detector_windows)
-            detector_windows_by_label[detector_window.label].push_back(detector_window);
+            size_t num_classes() const { return class_probs.size(); }
 
-        size_t label_count = 0;
-        for (const auto& i : detector_windows_by_label)
-        {
-            const auto& label = i.first;
-            const auto& detector_windows = i.second;
+            double probability_of_class (
+                size_t i
+            ) const 
+            { 
+                DLIB_CASSERT(i < num_classes());
+                return class_probs(i); 
+            }
 
-            if (label_count++ > 0)
-                out << ";";
-            out << label << ":";
+            const std::string& label(
+                size_t i
+            ) const 
+            { 
+                DLIB_CASSERT(i < num_classes()); 
+                return (*_labels)[i]; 
+            }
 
-            for (size_t j = 0; j < detector_windows.size(); ++j)
+            operator std::string(
+            ) const
             {
-                out << detector_windows[j].width << "x" << detector_windows[j].height;
-                if (j + 1 < detector_windows.size())
-                    out << ",";
+                DLIB_CASSERT(num_classes() != 0); 
+                return (*_labels)[index_of_max(class_probs)];
             }
-        }
-
-        return out;
-    }
-
-// ----------------------------------------------------------------------------------------
-
-    class loss_mmod_ 
-    {
-        struct intermediate_detection
-        {
-            intermediate_detection() = default; 
 
-            intermediate_detection(
-                rectangle rect_
-            ) : rect(rect_), rect_bbr(rect_) {}
+            friend std::ostream& operator<< (std::ostream& out, const classifier_output& item)
+            {
+                DLIB_ASSERT(item.num_classes() != 0); 
+                out << static_cast<std::string>(item);
+                return out;
+            }
 
-            intermediate_detection(
-                rectangle rect_,
-                double detection_confidence_,
-                size_t tensor_offset_,
-                long channel
-            ) : rect(rect_), detection_confidence(detection_confidence_), tensor_offset(tensor_offset_), tensor_channel(channel), rect_bbr(rect_) {}
+        private:
 
-            // rect is the rectangle you get without any bounding box regression.  So it's
-            // the basic sliding window box (aka, the "anchor box").
-            rectangle rect;
-            double detection_confidence = 0;
-            size_t tensor_offset = 0;
-            long tensor_channel = 0;
+            friend class loss_multimulticlass_log_;
 
-            // rect_bbr = rect + bounding box regression.  So more accurate.  Or if bbr is off then
-            // this is just rect.  The important thing about rect_bbr is that its the
-            // rectangle we use for doing NMS.
-            drectangle rect_bbr; 
-            size_t tensor_offset_dx = 0;
-            size_t tensor_offset_dy = 0;
-            size_t tensor_offset_dw = 0;
-            size_t tensor_offset_dh = 0;
+            template <typename EXP>
+            classifier_output(
+                const matrix_exp<EXP>& class_probs,
+                const std::shared_ptr<std::vector<std::string>>& _labels
+            ) : 
+                class_probs(class_probs), 
+                _labels(_labels)
+            {
+            }
 
-            bool operator<(const intermediate_detection& item) const { return detection_confidence < item.detection_confidence; }
+            matrix<float,1,0> class_probs;
+            std::shared_ptr<std::vector<std::string>> _labels;
         };
 
-    public:
-
-        typedef std::vector<mmod_rect> training_label_type;
-        typedef std::vector<mmod_rect> output_label_type;
-
-        loss_mmod_() {}
-
-        loss_mmod_(mmod_options options_) : options(options_) {}
+        typedef std::map<std::string,std::string> training_label_type;
+        typedef std::map<std::string,classifier_output> output_label_type;
 
-        const mmod_options& get_options (
-        ) const { return options; }
 
         template <
             typename SUB_TYPE,
@@ -1344,683 +1310,1978 @@ namespace dlib
         void to_label (
             const tensor& input_tensor,
             const SUB_TYPE& sub,
-            label_iterator iter,
-            double adjust_threshold = 0
+            label_iterator iter_begin
         ) const
         {
             const tensor& output_tensor = sub.get_output();
-            if (options.use_bounding_box_regression)
-            {
-                DLIB_CASSERT(output_tensor.k() == (long)options.detector_windows.size()*5);
-            }
-            else
-            {
-                DLIB_CASSERT(output_tensor.k() == (long)options.detector_windows.size());
-            }
+            DLIB_CASSERT(sub.sample_expansion_factor() == 1);
+            DLIB_CASSERT(output_tensor.nr() == 1 && 
+                         output_tensor.nc() == 1 );
             DLIB_CASSERT(input_tensor.num_samples() == output_tensor.num_samples());
-            DLIB_CASSERT(sub.sample_expansion_factor() == 1,  sub.sample_expansion_factor());
 
-            std::vector<intermediate_detection> dets_accum;
-            output_label_type final_dets;
-            for (long i = 0; i < output_tensor.num_samples(); ++i)
+            DLIB_CASSERT(number_of_labels() != 0, "You must give the loss_multimulticlass_log_'s constructor label data before you can use it!");
+            DLIB_CASSERT(output_tensor.k() == (long)number_of_labels(), "The output tensor must have " << number_of_labels() << " channels.");
+
+
+            long k_offset = 0;
+            for (auto& l : possible_labels)
             {
-                tensor_to_dets(input_tensor, output_tensor, i, dets_accum, adjust_threshold, sub);
+                auto iter = iter_begin;
+                const std::string& classifier_name = l.first;
+                const auto& labels = (*l.second); 
+                scratch.set_size(output_tensor.num_samples(), labels.size());
+                tt::copy_tensor(false, scratch, 0, output_tensor, k_offset, labels.size());
 
-                // Do non-max suppression
-                final_dets.clear();
-                for (unsigned long i = 0; i < dets_accum.size(); ++i)
-                {
-                    if (overlaps_any_box_nms(final_dets, dets_accum[i].rect_bbr))
-                        continue;
+                tt::softmax(scratch, scratch);
 
-                    final_dets.push_back(mmod_rect(dets_accum[i].rect_bbr,
-                                                   dets_accum[i].detection_confidence,
-                                                   options.detector_windows[dets_accum[i].tensor_channel].label));
-                }
+                for (long i = 0; i < scratch.num_samples(); ++i)
+                    (*iter++)[classifier_name] = classifier_output(rowm(mat(scratch),i), l.second);
 
-                *iter++ = std::move(final_dets);
+                k_offset += labels.size();
             }
         }
 
+
         template <
             typename const_label_iterator,
             typename SUBNET
             >
         double compute_loss_value_and_gradient (
             const tensor& input_tensor,
-            const_label_iterator truth, 
+            const_label_iterator truth_begin, 
             SUBNET& sub
         ) const
         {
             const tensor& output_tensor = sub.get_output();
             tensor& grad = sub.get_gradient_input();
 
-            DLIB_CASSERT(input_tensor.num_samples() != 0);
             DLIB_CASSERT(sub.sample_expansion_factor() == 1);
+            DLIB_CASSERT(input_tensor.num_samples() != 0);
+            DLIB_CASSERT(input_tensor.num_samples()%sub.sample_expansion_factor() == 0);
             DLIB_CASSERT(input_tensor.num_samples() == grad.num_samples());
             DLIB_CASSERT(input_tensor.num_samples() == output_tensor.num_samples());
-            if (options.use_bounding_box_regression)
-            {
-                DLIB_CASSERT(output_tensor.k() == (long)options.detector_windows.size()*5);
-            }
-            else
-            {
-                DLIB_CASSERT(output_tensor.k() == (long)options.detector_windows.size());
-            }
-
-            double det_thresh_speed_adjust = 0;
+            DLIB_CASSERT(output_tensor.nr() == 1 && 
+                         output_tensor.nc() == 1);
+            DLIB_CASSERT(grad.nr() == 1 && 
+                         grad.nc() == 1);
+            DLIB_CASSERT(number_of_labels() != 0, "You must give the loss_multimulticlass_log_'s constructor label data before you can use it!");
+            DLIB_CASSERT(output_tensor.k() == (long)number_of_labels(), "The output tensor must have " << number_of_labels() << " channels.");
 
-            // we will scale the loss so that it doesn't get really huge
-            const double scale = 1.0/(output_tensor.nr()*output_tensor.nc()*output_tensor.num_samples()*options.detector_windows.size());
+            // The loss we output is the average loss over the mini-batch.
+            const double scale = 1.0/output_tensor.num_samples();
             double loss = 0;
+            long k_offset = 0;
+            for (auto& l : label_idx_lookup)
+            {
+                const std::string& classifier_name = l.first;
+                const auto& int_labels = l.second; 
+                scratch.set_size(output_tensor.num_samples(), int_labels.size());
+                tt::copy_tensor(false, scratch, 0, output_tensor, k_offset, int_labels.size());
 
-            float* g = grad.host_write_only();
-            for (size_t i = 0; i < grad.size(); ++i)
-                g[i] = 0;
-
-            const float* out_data = output_tensor.host();
+                tt::softmax(scratch, scratch);
 
-            std::vector<intermediate_detection> dets;
-            for (long i = 0; i < output_tensor.num_samples(); ++i)
-            {
-                tensor_to_dets(input_tensor, output_tensor, i, dets, -options.loss_per_false_alarm + det_thresh_speed_adjust, sub);
 
-                const unsigned long max_num_dets = 50 + truth->size()*5;
-                // Prevent calls to tensor_to_dets() from running for a really long time
-                // due to the production of an obscene number of detections.
-                const unsigned long max_num_initial_dets = max_num_dets*100;
-                if (dets.size() > max_num_initial_dets)
+                auto truth = truth_begin;
+                float* g = scratch.host();
+                for (long i = 0; i < scratch.num_samples(); ++i)
                 {
-                    det_thresh_speed_adjust = std::max(det_thresh_speed_adjust,dets[max_num_initial_dets].detection_confidence + options.loss_per_false_alarm);
+                    const long y = int_labels.at(truth->at(classifier_name));
+                    ++truth;
+
+                    for (long k = 0; k < scratch.k(); ++k)
+                    {
+                        const unsigned long idx = i*scratch.k()+k;
+                        if (k == y)
+                        {
+                            loss += scale*-std::log(g[idx]);
+                            g[idx] = scale*(g[idx]-1);
+                        }
+                        else
+                        {
+                            g[idx] = scale*g[idx];
+                        }
+                    }
                 }
 
-                std::vector<int> truth_idxs;
-                truth_idxs.reserve(truth->size());
+                tt::copy_tensor(false, grad, k_offset, scratch, 0, int_labels.size());
 
-                std::unordered_map<size_t, rectangle> idx_to_truth_rect;
+                k_offset += int_labels.size();
+            }
+            return loss;
+        }
+
+
+        friend void serialize(const loss_multimulticlass_log_& item, std::ostream& out)
+        {
+            serialize("loss_multimulticlass_log_", out);
+            serialize(item.get_labels(), out);
+        }
+
+        friend void deserialize(loss_multimulticlass_log_& item, std::istream& in)
+        {
+            std::string version;
+            deserialize(version, in);
+            if (version != "loss_multimulticlass_log_")
+                throw serialization_error("Unexpected version found while deserializing dlib::loss_multimulticlass_log_.");
+
+            std::map<std::string,std::vector<std::string>> info; 
+            deserialize(info, in);
+            item = loss_multimulticlass_log_(info);
+        }
+
+        friend std::ostream& operator<<(std::ostream& out, const loss_multimulticlass_log_& item)
+        {
+            out << "loss_multimulticlass_log, labels={";
+            for (auto i = item.possible_labels.begin(); i != item.possible_labels.end(); )
+            {
+                auto& category = i->first;
+                auto& labels = *(i->second);
+                out << category << ":(";
+                for (size_t j = 0; j < labels.size(); ++j)
+                {
+                    out << labels[j];
+                    if (j+1 < labels.size())
+                        out << ",";
+                }
+
+                out << ")";
+                if (++i != item.possible_labels.end())
+                    out << ", ";
+            }
+            out << "}";
+            return out;
+        }
+
+        friend void to_xml(const loss_multimulticlass_log_& item, std::ostream& out)
+        {
+            out << "<loss_multimulticlass_log>\n";
+            out << item;
+            out << "\n</loss_multimulticlass_log>\n";
+        }
+
+    private:
+
+        std::map<std::string,std::shared_ptr<std::vector<std::string>>> possible_labels;
+        unsigned long total_num_labels = 0;
+
+        // We make it true that: possible_labels[classifier][label_idx_lookup[classifier][label]] == label
+        std::map<std::string, std::map<std::string, size_t>> label_idx_lookup;
+
+
+        // Scratch doesn't logically contribute to the state of this object.  It's just
+        // temporary scratch space used by this class.  
+        mutable resizable_tensor scratch;
+
+
+    };
+
+    template <typename SUBNET>
+    using loss_multimulticlass_log = add_loss_layer<loss_multimulticlass_log_, SUBNET>;
+
+    inline bool operator== (const std::string& lhs, const loss_multimulticlass_log_::classifier_output& rhs)
+    { return lhs == static_cast<const std::string&>(rhs); }
+    inline bool operator== (const loss_multimulticlass_log_::classifier_output& lhs, const std::string& rhs)
+    { return rhs == static_cast<const std::string&>(lhs); }
+
+// ----------------------------------------------------------------------------------------
+
+    class loss_multibinary_log_
+    {
+    public:
+        typedef std::vector<float> training_label_type;
+        typedef std::vector<float> output_label_type;
+
+        loss_multibinary_log_() = default;
+
+        loss_multibinary_log_(double gamma) : gamma(gamma)
+        {
+            DLIB_CASSERT(gamma >= 0);
+        }
+
+        template <
+            typename SUB_TYPE,
+            typename label_iterator
+            >
+        void to_label (
+            const tensor& input_tensor,
+            const SUB_TYPE& sub,
+            label_iterator iter
+        ) const
+        {
+            const tensor& output_tensor = sub.get_output();
+            DLIB_CASSERT(sub.sample_expansion_factor() == 1);
+            DLIB_CASSERT(output_tensor.nr() == 1 && output_tensor.nc() == 1);
+            DLIB_CASSERT(input_tensor.num_samples() == output_tensor.num_samples());
+
+            // Note that output_tensor.k() should match the number of labels.
+
+            const float* out_data = output_tensor.host();
+            for (long i = 0; i < output_tensor.num_samples(); ++i)
+            {
+                output_label_type predictions(output_tensor.k(), 0);
+                for (long k = 0; k < output_tensor.k(); ++k)
+                {
+                    predictions[k] = out_data[i * output_tensor.k() + k];
+                }
+                *iter++ = std::move(predictions);
+            }
+        }
+
+        template <
+            typename const_label_iterator,
+            typename SUBNET
+            >
+        double compute_loss_value_and_gradient (
+            const tensor& input_tensor,
+            const_label_iterator truth,
+            SUBNET& sub
+        ) const
+        {
+            const tensor& output_tensor = sub.get_output();
+            tensor& grad = sub.get_gradient_input();
+
+            DLIB_CASSERT(sub.sample_expansion_factor() == 1);
+            DLIB_CASSERT(input_tensor.num_samples() != 0);
+            DLIB_CASSERT(input_tensor.num_samples() % sub.sample_expansion_factor() == 0);
+            DLIB_CASSERT(input_tensor.num_samples() == grad.num_samples());
+            DLIB_CASSERT(input_tensor.num_samples() == output_tensor.num_samples());
+            DLIB_CASSERT(output_tensor.nr() == 1 && output_tensor.nc() == 1);
+            DLIB_CASSERT(grad.nr() == 1 && grad.nc() == 1);
+
+            tt::sigmoid(grad, output_tensor);
+
+            // The loss we output is the average loss over the mini-batch.
+            const double scale = 1.0 / output_tensor.num_samples();
+            double loss = 0;
+            float* g = grad.host();
+            const float* out_data  = output_tensor.host();
+            for (long i = 0; i < output_tensor.num_samples(); ++i, ++truth)
+            {
+                const long long num_label_categories = truth->size();
+                DLIB_CASSERT(output_tensor.k() == num_label_categories,
+                    "Number of label types should match the number of output channels. "
+                    "output_tensor.k(): " << output_tensor.k() 
+                    << ", num_label_categories: "<< num_label_categories);
+                for (long k = 0; k < output_tensor.k(); ++k)
+                {
+                    const float y = (*truth)[k];
+                    DLIB_CASSERT(y != 0, "y: " << y);
+                    const size_t idx = i * output_tensor.k() + k;
+                    if (y > 0)
+                    {
+                        const float temp = log1pexp(-out_data[idx]);
+                        const float focus = std::pow(1 - g[idx], gamma);
+                        loss += y * scale * temp * focus;
+                        g[idx] = y * scale * focus * (g[idx] * (gamma * temp + 1) - 1);
+                    }
+                    else
+                    {
+                        const float temp = -(-out_data[idx] - log1pexp(-out_data[idx]));
+                        const float focus = std::pow(g[idx], gamma);
+                        loss += -y * scale * temp * focus;
+                        g[idx] = -y * scale * focus * g[idx] * (gamma * temp + 1);
+                    }
+                }
+            }
+            return loss;
+        }
+
+        double get_gamma () const { return gamma; }
+
+        friend void serialize(const loss_multibinary_log_& item, std::ostream& out)
+        {
+            serialize("loss_multibinary_log_2", out);
+            serialize(item.gamma, out);
+        }
+
+        friend void deserialize(loss_multibinary_log_& item, std::istream& in)
+        {
+            std::string version;
+            deserialize(version, in);
+            if (version == "loss_multibinary_log_")
+            {
+                item.gamma = 0;
+                return;
+            }
+            else if (version == "loss_multibinary_log_2")
+            {
+                deserialize(item.gamma, in);
+            }
+            else
+            {
+                throw serialization_error("Unexpected version found while deserializing dlib::loss_multibinary_log_.");
+            }
+        }
+
+        friend std::ostream& operator<<(std::ostream& out, const loss_multibinary_log_& item)
+        {
+            out << "loss_multibinary_log (gamma=" << item.gamma << ")";
+            return out;
+        }
+
+        friend void to_xml(const loss_multibinary_log_& item, std::ostream& out)
+        {
+            out << "<loss_multibinary_log gamma='" << item.gamma << "'/>\n";
+        }
+
+    private:
+        double gamma = 0;
+    };
+
+    template <typename SUBNET>
+    using loss_multibinary_log = add_loss_layer<loss_multibinary_log_, SUBNET>;
+
+// ----------------------------------------------------------------------------------------
+// ----------------------------------------------------------------------------------------
+
+    enum class use_image_pyramid : uint8_t
+    {
+        no,
+        yes
+    };
+
+    struct mmod_options
+    {
+    public:
+
+        struct detector_window_details
+        {
+            detector_window_details() = default; 
+            detector_window_details(unsigned long w, unsigned long h) : width(w), height(h) {}
+            detector_window_details(unsigned long w, unsigned long h, const std::string& l) : width(w), height(h), label(l) {}
+
+            unsigned long width = 0;
+            unsigned long height = 0;
+            std::string label;
+
+            friend inline void serialize(const detector_window_details& item, std::ostream& out)
+            {
+                int version = 2;
+                serialize(version, out);
+                serialize(item.width, out);
+                serialize(item.height, out);
+                serialize(item.label, out);
+            }
+
+            friend inline void deserialize(detector_window_details& item, std::istream& in)
+            {
+                int version = 0;
+                deserialize(version, in);
+                if (version != 1 && version != 2)
+                    throw serialization_error("Unexpected version found while deserializing dlib::mmod_options::detector_window_details");
+                deserialize(item.width, in);
+                deserialize(item.height, in);
+                if (version == 2)
+                    deserialize(item.label, in);
+            }
+
+        };
+
+        mmod_options() = default;
+
+        std::vector<detector_window_details> detector_windows;
+        double loss_per_false_alarm = 1;
+        double loss_per_missed_target = 1;
+        double truth_match_iou_threshold = 0.5;
+        test_box_overlap overlaps_nms = test_box_overlap(0.4);
+        test_box_overlap overlaps_ignore;
+        bool use_bounding_box_regression = false; 
+        double bbr_lambda = 100; 
+        bool use_angle_regression = false;
+        double angle_lambda = 10;
+        // This field is intentionally not serialized because I want people to really think hard
+        // about ignoring the warnings that this suppresses.
+        bool be_quiet = false;
+
+        use_image_pyramid assume_image_pyramid = use_image_pyramid::yes;
+
+        mmod_options (
+            const std::vector<std::vector<mmod_rect>>& boxes,
+            const unsigned long target_size,       // We want the length of the longest dimension of the detector window to be this.
+            const unsigned long min_target_size,   // But we require that the smallest dimension of the detector window be at least this big.
+            const double min_detector_window_overlap_iou = 0.75
+        )
+        {
+            DLIB_CASSERT(0 < min_target_size && min_target_size <= target_size);
+            DLIB_CASSERT(0.5 < min_detector_window_overlap_iou && min_detector_window_overlap_iou < 1);
+
+            // Figure out what detector windows we will need.
+            for (auto& label : get_labels(boxes))
+            {
+                for (auto ratio : find_covering_aspect_ratios(boxes, test_box_overlap(min_detector_window_overlap_iou), label))
+                {
+                    double detector_width;
+                    double detector_height;
+                    if (ratio < 1)
+                    {
+                        detector_height = target_size;
+                        detector_width = ratio*target_size;
+                        if (detector_width < min_target_size)
+                        {
+                            detector_height = min_target_size/ratio;
+                            detector_width = min_target_size;
+                        }
+                    }
+                    else
+                    {
+                        detector_width = target_size;
+                        detector_height = target_size/ratio;
+                        if (detector_height < min_target_size)
+                        {
+                            detector_width = min_target_size*ratio;
+                            detector_height = min_target_size;
+                        }
+                    }
+
+                    detector_window_details p((unsigned long)std::round(detector_width), (unsigned long)std::round(detector_height), label);
+                    detector_windows.push_back(p);
+                }
+            }
+
+            DLIB_CASSERT(detector_windows.size() != 0, "You can't call mmod_options's constructor with a set of boxes that is empty (or only contains ignored boxes).");
+
+            set_overlap_nms(boxes);
+        }
+
+        mmod_options(
+            use_image_pyramid assume_image_pyramid,
+            const std::vector<std::vector<mmod_rect>>& boxes,
+            const double min_detector_window_overlap_iou = 0.75
+        )
+            : assume_image_pyramid(assume_image_pyramid)
+        {
+            DLIB_CASSERT(assume_image_pyramid == use_image_pyramid::no);
+            DLIB_CASSERT(0.5 < min_detector_window_overlap_iou && min_detector_window_overlap_iou < 1);
+
+            // Figure out what detector windows we will need.
+            for (auto& label : get_labels(boxes))
+            {
+                for (auto rectangle : find_covering_rectangles(boxes, test_box_overlap(min_detector_window_overlap_iou), label))
+                {
+                    detector_windows.push_back(detector_window_details(rectangle.width(), rectangle.height(), label));
+                }
+            }
+
+            DLIB_CASSERT(detector_windows.size() != 0, "You can't call mmod_options's constructor with a set of boxes that is empty (or only contains ignored boxes).");
+
+            set_overlap_nms(boxes);
+        }
+
+    private:
+
+        void set_overlap_nms(const std::vector<std::vector<mmod_rect>>& boxes)
+        {
+            // Convert from mmod_rect to rectangle so we can call
+            // find_tight_overlap_tester().
+            std::vector<std::vector<rectangle>> temp;
+            for (auto&& bi : boxes)
+            {
+                std::vector<rectangle> rtemp;
+                for (auto&& b : bi)
+                {
+                    if (b.ignore)
+                        continue;
+                    rtemp.push_back(b.rect);
+                }
+                temp.push_back(std::move(rtemp));
+            }
+            overlaps_nms = find_tight_overlap_tester(temp);
+            // Relax the non-max-suppression a little so that it doesn't accidentally make
+            // it impossible for the detector to output boxes matching the training data.
+            // This could be a problem with the tightest possible nms test since there is
+            // some small variability in how boxes get positioned between the training data
+            // and the coordinate system used by the detector when it runs.  So relaxing it
+            // here takes care of that.
+            auto iou_thresh             = advance_toward_1(overlaps_nms.get_iou_thresh());
+            auto percent_covered_thresh = advance_toward_1(overlaps_nms.get_percent_covered_thresh());
+            overlaps_nms = test_box_overlap(iou_thresh, percent_covered_thresh);
+        }
+
+        static double advance_toward_1 (
+            double val
+        )
+        {
+            if (val < 1)
+                val += (1-val)*0.1;
+            return val;
+        }
+
+        static size_t count_overlaps (
+            const std::vector<rectangle>& rects,
+            const test_box_overlap& overlaps,
+            const rectangle& ref_box
+        )
+        {
+            size_t cnt = 0;
+            for (auto& b : rects)
+            {
+                if (overlaps(b, ref_box))
+                    ++cnt;
+            }
+            return cnt;
+        }
+
+        static std::vector<rectangle> find_rectangles_overlapping_all_others (
+            std::vector<rectangle> rects,
+            const test_box_overlap& overlaps
+        )
+        {
+            std::vector<rectangle> exemplars;
+            dlib::rand rnd;
+
+            while(rects.size() > 0)
+            {
+                // Pick boxes at random and see if they overlap a lot of other boxes.  We will try
+                // 500 different boxes each iteration and select whichever hits the most others to
+                // add to our exemplar set.
+                rectangle best_ref_box;
+                size_t best_cnt = 0;
+                for (int iter = 0; iter < 500; ++iter)
+                {
+                    rectangle ref_box = rects[rnd.get_random_64bit_number()%rects.size()];
+                    size_t cnt = count_overlaps(rects, overlaps, ref_box);
+                    if (cnt >= best_cnt)
+                    {
+                        best_cnt = cnt;
+                        best_ref_box = ref_box;
+                    }
+                }
+
+                // Now mark all the boxes the new ref box hit as hit.
+                for (size_t i = 0; i < rects.size(); ++i)
+                {
+                    if (overlaps(rects[i], best_ref_box))
+                    {
+                        // remove box from rects so we don't hit it again later
+                        swap(rects[i], rects.back());
+                        rects.pop_back();
+                        --i;
+                    }
+                }
+
+                exemplars.push_back(best_ref_box);
+            }
+
+            return exemplars;
+        }
+
+        static std::set<std::string> get_labels (
+            const std::vector<std::vector<mmod_rect>>& rects
+        )
+        {
+            std::set<std::string> labels;
+            for (auto& rr : rects)
+            {
+                for (auto& r : rr)
+                    labels.insert(r.label);
+            }
+            return labels;
+        }
+
+        static std::vector<double> find_covering_aspect_ratios (
+            const std::vector<std::vector<mmod_rect>>& rects,
+            const test_box_overlap& overlaps,
+            const std::string& label
+        )
+        {
+            std::vector<rectangle> boxes;
+            // Make sure all the boxes have the same size and position, so that the only thing our
+            // checks for overlap will care about is aspect ratio (i.e. scale and x,y position are
+            // ignored).
+            for (auto& bb : rects)
+            {
+                for (auto&& b : bb)
+                {
+                    if (!b.ignore && b.label == label)
+                        boxes.push_back(move_rect(set_rect_area(b.rect,400*400), point(0,0)));
+                }
+            }
+
+            std::vector<double> ratios;
+            for (auto r : find_rectangles_overlapping_all_others(boxes, overlaps))
+                ratios.push_back(r.width()/(double)r.height());
+            return ratios;
+        }
+
+        static std::vector<dlib::rectangle> find_covering_rectangles (
+            const std::vector<std::vector<mmod_rect>>& rects,
+            const test_box_overlap& overlaps,
+            const std::string& label
+        )
+        {
+            std::vector<rectangle> boxes;
+            // Make sure all the boxes have the same position, so that the we only check for
+            // width and height.
+            for (auto& bb : rects)
+            {
+                for (auto&& b : bb)
+                {
+                    if (!b.ignore && b.label == label)
+                        boxes.push_back(rectangle(b.rect.width(), b.rect.height()));
+                }
+            }
+
+            return find_rectangles_overlapping_all_others(boxes, overlaps);
+        }
+    };
+
+    inline void serialize(const mmod_options& item, std::ostream& out)
+    {
+        int version = 5;
+
+        serialize(version, out);
+        serialize(item.detector_windows, out);
+        serialize(item.loss_per_false_alarm, out);
+        serialize(item.loss_per_missed_target, out);
+        serialize(item.truth_match_iou_threshold, out);
+        serialize(item.overlaps_nms, out);
+        serialize(item.overlaps_ignore, out);
+        serialize(static_cast<uint8_t>(item.assume_image_pyramid), out);
+        serialize(item.use_bounding_box_regression, out);
+        serialize(item.bbr_lambda, out);
+        serialize(item.use_angle_regression, out);
+        serialize(item.angle_lambda, out);
+    }
+
+    inline void deserialize(mmod_options& item, std::istream& in)
+    {
+        int version = 0;
+        deserialize(version, in);
+        if (!(1 <= version && version <= 5))
+            throw serialization_error("Unexpected version found while deserializing dlib::mmod_options");
+        if (version == 1)
+        {
+            unsigned long width;
+            unsigned long height;
+            deserialize(width, in);
+            deserialize(height, in);
+            item.detector_windows = {mmod_options::detector_window_details(width, height)};
+        }
+        else
+        {
+            deserialize(item.detector_windows, in);
+        }
+        deserialize(item.loss_per_false_alarm, in);
+        deserialize(item.loss_per_missed_target, in);
+        deserialize(item.truth_match_iou_threshold, in);
+        deserialize(item.overlaps_nms, in);
+        deserialize(item.overlaps_ignore, in);
+        item.assume_image_pyramid = use_image_pyramid::yes;
+        if (version >= 3)
+        {
+            uint8_t assume_image_pyramid = 0;
+            deserialize(assume_image_pyramid, in);
+            item.assume_image_pyramid = static_cast<use_image_pyramid>(assume_image_pyramid);
+        }
+        item.use_bounding_box_regression = mmod_options().use_bounding_box_regression; // use default value since this wasn't provided
+        item.bbr_lambda = mmod_options().bbr_lambda; // use default value since this wasn't provided
+        if (version >= 4)
+        {
+            deserialize(item.use_bounding_box_regression, in);
+            deserialize(item.bbr_lambda, in);
+        }
+        item.use_angle_regression = mmod_options().use_angle_regression; // use default value since this wasn't provided
+        item.angle_lambda = mmod_options().angle_lambda; // use default value since this wasn't provided
+        if (version >= 5)
+        {
+            deserialize(item.use_angle_regression, in);
+            deserialize(item.angle_lambda, in);
+        }
+    }
+
+    inline std::ostream& operator<<(std::ostream& out, const std::vector<mmod_options::detector_window_details>& detector_windows)
+    {
+        // write detector windows grouped by label
+        // example output: aeroplane:74x30,131x30,70x45,54x70,198x30;bicycle:70x57,32x70,70x32,51x70,128x30,30x121;car:70x36,70x60,99x30,52x70,30x83,30x114,30x200
+
+        std::map<std::string, std::deque<mmod_options::detector_window_details>> detector_windows_by_label;
+        for (const auto& detector_window : detector_windows)
+            detector_windows_by_label[detector_window.label].push_back(detector_window);
+
+        size_t label_count = 0;
+        for (const auto& i : detector_windows_by_label)
+        {
+            const auto& label = i.first;
+            const auto& detector_windows = i.second;
+
+            if (label_count++ > 0)
+                out << ";";
+            out << label << ":";
+
+            for (size_t j = 0; j < detector_windows.size(); ++j)
+            {
+                out << detector_windows[j].width << "x" << detector_windows[j].height;
+                if (j + 1 < detector_windows.size())
+                    out << ",";
+            }
+        }
+
+        return out;
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    class loss_mmod_ 
+    {
+        struct intermediate_detection
+        {
+            intermediate_detection() = default; 
+
+            intermediate_detection(
+                rectangle rect_
+            ) : rect(rect_), rect_bbr(rect_) {}
+
+            intermediate_detection(
+                rectangle rect_,
+                double detection_confidence_,
+                size_t tensor_offset_,
+                long channel
+            ) : rect(rect_), detection_confidence(detection_confidence_), tensor_offset(tensor_offset_), tensor_channel(channel), rect_bbr(rect_) {}
+
+            // rect is the rectangle you get without any bounding box regression.  So it's
+            // the basic sliding window box (aka, the "anchor box").
+            rectangle rect;
+            double detection_confidence = 0;
+            size_t tensor_offset = 0;
+            long tensor_channel = 0;
+
+            // rect_bbr = rect + bounding box regression.  So more accurate.  Or if bbr is off then
+            // this is just rect.  The important thing about rect_bbr is that its the
+            // rectangle we use for doing NMS.
+            drectangle rect_bbr; 
+            size_t tensor_offset_dx = 0;
+            size_t tensor_offset_dy = 0;
+            size_t tensor_offset_dw = 0;
+            size_t tensor_offset_dh = 0;
+
+            // The predicted angle of the detection if angle regression is on, otherwise 0.
+            double angle = 0;
+            size_t tensor_offset_cos = 0;
+            size_t tensor_offset_sin = 0;
+
+            bool operator<(const intermediate_detection& item) const { return detection_confidence < item.detection_confidence; }
+        };
+
+    public:
+
+        typedef std::vector<mmod_rect> training_label_type;
+        typedef std::vector<mmod_rect> output_label_type;
+
+        loss_mmod_() {}
+
+        loss_mmod_(mmod_options options_) : options(options_) {}
+
+        const mmod_options& get_options (
+        ) const { return options; }
+
+        template <
+            typename SUB_TYPE,
+            typename label_iterator
+            >
+        void to_label (
+            const tensor& input_tensor,
+            const SUB_TYPE& sub,
+            label_iterator iter,
+            double adjust_threshold = 0
+        ) const
+        {
+            const tensor& output_tensor = sub.get_output();
+            DLIB_CASSERT(output_tensor.k() == num_output_channels(), output_tensor.k() << " != " << num_output_channels());
+            DLIB_CASSERT(input_tensor.num_samples() == output_tensor.num_samples());
+            DLIB_CASSERT(sub.sample_expansion_factor() == 1,  sub.sample_expansion_factor());
+
+            std::vector<intermediate_detection> dets_accum;
+            output_label_type final_dets;
+            for (long i = 0; i < output_tensor.num_samples(); ++i)
+            {
+                tensor_to_dets(input_tensor, output_tensor, i, dets_accum, adjust_threshold, sub);
+
+                // Do non-max suppression
+                final_dets.clear();
+                for (unsigned long i = 0; i < dets_accum.size(); ++i)
+                {
+                    if (overlaps_any_box_nms(final_dets, dets_accum[i].rect_bbr, dets_accum[i].angle))
+                        continue;
+
+                    final_dets.push_back(mmod_rect(dets_accum[i].rect_bbr,
+                                                   dets_accum[i].detection_confidence,
+                                                   options.detector_windows[dets_accum[i].tensor_channel].label));
+                    final_dets.back().angle = dets_accum[i].angle;
+                }
+
+                *iter++ = std::move(final_dets);
+            }
+        }
+
+        template <
+            typename const_label_iterator,
+            typename SUBNET
+            >
+        double compute_loss_value_and_gradient (
+            const tensor& input_tensor,
+            const_label_iterator truth, 
+            SUBNET& sub
+        ) const
+        {
+            const tensor& output_tensor = sub.get_output();
+            tensor& grad = sub.get_gradient_input();
+
+            DLIB_CASSERT(input_tensor.num_samples() != 0);
+            DLIB_CASSERT(sub.sample_expansion_factor() == 1);
+            DLIB_CASSERT(input_tensor.num_samples() == grad.num_samples());
+            DLIB_CASSERT(input_tensor.num_samples() == output_tensor.num_samples());
+            DLIB_CASSERT(output_tensor.k() == num_output_channels(), output_tensor.k() << " != " << num_output_channels());
+
+            double det_thresh_speed_adjust = 0;
+
+            // we will scale the loss so that it doesn't get really huge
+            const double scale = 1.0/(output_tensor.nr()*output_tensor.nc()*output_tensor.num_samples()*options.detector_windows.size());
+            double loss = 0;
+
+            float* g = grad.host_write_only();
+            for (size_t i = 0; i < grad.size(); ++i)
+                g[i] = 0;
+
+            const float* out_data = output_tensor.host();
+
+            std::vector<intermediate_detection> dets;
+            for (long i = 0; i < output_tensor.num_samples(); ++i)
+            {
+                tensor_to_dets(input_tensor, output_tensor, i, dets, -options.loss_per_false_alarm + det_thresh_speed_adjust, sub);
+
+                const unsigned long max_num_dets = 50 + truth->size()*5;
+                // Prevent calls to tensor_to_dets() from running for a really long time
+                // due to the production of an obscene number of detections.
+                const unsigned long max_num_initial_dets = max_num_dets*100;
+                if (dets.size() > max_num_initial_dets)
+                {
+                    det_thresh_speed_adjust = std::max(det_thresh_speed_adjust,dets[max_num_initial_dets].detection_confidence + options.loss_per_false_alarm);
+                }
+
+                std::vector<int> truth_idxs;
+                truth_idxs.reserve(truth->size());
+
+                std::unordered_map<size_t, rectangle> idx_to_truth_rect;
+
+                // The loss will measure the number of incorrect detections.  A detection is
+                // incorrect if it doesn't hit a truth rectangle or if it is a duplicate detection
+                // on a truth rectangle.
+                loss += truth->size()*options.loss_per_missed_target;
+                for (auto&& x : *truth)
+                {
+                    if (!x.ignore)
+                    {
+                        size_t k;
+                        point p;
+                        if(image_rect_to_feat_coord(p, input_tensor, x, x.label, sub, k, options.assume_image_pyramid))
+                        {
+                            // Ignore boxes that can't be detected by the CNN.
+                            loss -= options.loss_per_missed_target;
+                            truth_idxs.push_back(-1);
+                            continue;
+                        }
+                        const size_t idx = (k*output_tensor.nr() + p.y())*output_tensor.nc() + p.x();
+                        const auto i = idx_to_truth_rect.find(idx);
+                        if (i != idx_to_truth_rect.end())
+                        {
+                            if (!options.be_quiet) 
+                            {
+                                // Ignore duplicate truth box in feature coordinates.
+                                std::cout << "Warning, ignoring object.  We encountered a truth rectangle located at " << x.rect;
+                                std::cout << ", and we are ignoring it because it maps to the exact same feature coordinates ";
+                                std::cout << "as another truth rectangle located at " << i->second << "." << std::endl;
+                            }
+
+                            loss -= options.loss_per_missed_target;
+                            truth_idxs.push_back(-1);
+                            continue;
+                        }
+                        loss -= out_data[idx];
+                        // compute gradient
+                        g[idx] = -scale;
+                        truth_idxs.push_back(idx);
+                        idx_to_truth_rect[idx] = x.rect;
+                    }
+                    else
+                    {
+                        // This box was ignored so shouldn't have been counted in the loss.
+                        loss -= options.loss_per_missed_target;
+                        truth_idxs.push_back(-1);
+                    }
+                }
+
+                // Measure the loss augmented score for the detections which hit a truth rect.
+                std::vector<double> truth_score_hits(truth->size(), 0);
+
+                // keep track of which truth boxes we have hit so far.
+                std::vector<bool> hit_truth_table(truth->size(), false);
+
+                std::vector<intermediate_detection> final_dets;
+                // The point of this loop is to fill out the truth_score_hits array. 
+                for (size_t i = 0; i < dets.size() && final_dets.size() < max_num_dets; ++i)
+                {
+                    if (overlaps_any_box_nms(final_dets, dets[i].rect_bbr, dets[i].angle))
+                        continue;
+
+                    const auto& det_label = options.detector_windows[dets[i].tensor_channel].label;
+
+                    const std::pair<double,unsigned int> hittruth = find_best_match(*truth, hit_truth_table, dets[i].rect, det_label);
+
+                    final_dets.push_back(dets[i]);
+
+                    const double truth_match = hittruth.first;
+                    // if hit truth rect
+                    if (truth_match > options.truth_match_iou_threshold)
+                    {
+                        // if this is the first time we have seen a detect which hit (*truth)[hittruth.second]
+                        const double score = dets[i].detection_confidence;
+                        if (hit_truth_table[hittruth.second] == false)
+                        {
+                            hit_truth_table[hittruth.second] = true;
+                            truth_score_hits[hittruth.second] += score;
+                        }
+                        else
+                        {
+                            truth_score_hits[hittruth.second] += score + options.loss_per_false_alarm;
+                        }
+                    }
+                }
+
+                // Check if any of the truth boxes are unobtainable because the NMS is
+                // killing them.  If so, automatically set those unobtainable boxes to
+                // ignore and print a warning message to the user.
+                for (size_t i = 0; i < hit_truth_table.size(); ++i)
+                {
+                    if (!hit_truth_table[i] && !(*truth)[i].ignore) 
+                    {
+                        // So we didn't hit this truth box.  Is that because there is
+                        // another, different truth box, that overlaps it according to NMS?
+                        const std::pair<double,unsigned int> hittruth = find_best_match(*truth, (*truth)[i], i);
+                        if (hittruth.second == i || (*truth)[hittruth.second].ignore)
+                            continue;
+                        rectangle best_matching_truth_box = (*truth)[hittruth.second];
+                        if (overlaps_nms((*truth)[hittruth.second], (*truth)[i]))
+                        {
+                            const int idx = truth_idxs[i];
+                            if (idx != -1)
+                            {
+                                // We are ignoring this box so we shouldn't have counted it in the
+                                // loss in the first place.  So we subtract out the loss values we
+                                // added for it in the code above.
+                                loss -= options.loss_per_missed_target-out_data[idx];
+                                g[idx] = 0;
+                                if (!options.be_quiet) 
+                                {
+                                    std::cout << "Warning, ignoring object.  We encountered a truth rectangle located at " << (*truth)[i].rect;
+                                    std::cout << " that is suppressed by non-max-suppression ";
+                                    std::cout << "because it is overlapped by another truth rectangle located at " << best_matching_truth_box 
+                                        << " (IoU:"<< box_intersection_over_union(best_matching_truth_box,(*truth)[i]) <<", Percent covered:" 
+                                        << box_percent_covered(best_matching_truth_box,(*truth)[i]) << ")." << std::endl;
+                                }
+                            }
+                        }
+                    }
+                }
+
+                hit_truth_table.assign(hit_truth_table.size(), false);
+                final_dets.clear();
+
+                // Now figure out which detections jointly maximize the loss and detection score sum.  We
+                // need to take into account the fact that allowing a true detection in the output, while 
+                // initially reducing the loss, may allow us to increase the loss later with many duplicate
+                // detections.
+                for (unsigned long i = 0; i < dets.size() && final_dets.size() < max_num_dets; ++i)
+                {
+                    if (overlaps_any_box_nms(final_dets, dets[i].rect_bbr, dets[i].angle))
+                        continue;
+
+                    const auto& det_label = options.detector_windows[dets[i].tensor_channel].label;
+
+                    const std::pair<double,unsigned int> hittruth = find_best_match(*truth, hit_truth_table, dets[i].rect, det_label);
+
+                    const double truth_match = hittruth.first;
+                    if (truth_match > options.truth_match_iou_threshold)
+                    {
+                        if (truth_score_hits[hittruth.second] > options.loss_per_missed_target)
+                        {
+                            if (!hit_truth_table[hittruth.second])
+                            {
+                                hit_truth_table[hittruth.second] = true;
+                                final_dets.push_back(dets[i]);
+                                loss -= options.loss_per_missed_target;
+
+                                // Now account for BBR loss and gradient if appropriate.
+                                if (options.use_bounding_box_regression)
+                                {
+                                    double dx = out_data[dets[i].tensor_offset_dx];
+                                    double dy = out_data[dets[i].tensor_offset_dy];
+                                    double dw = out_data[dets[i].tensor_offset_dw];
+                                    double dh = out_data[dets[i].tensor_offset_dh];
+
+                                    dpoint p = dcenter(dets[i].rect);
+                                    double w = dets[i].rect.width()-1;
+                                    double h = dets[i].rect.height()-1;
+                                    drectangle truth_box = (*truth)[hittruth.second].rect;
+                                    dpoint p_truth = dcenter(truth_box); 
+
+                                    DLIB_CASSERT(w > 0);
+                                    DLIB_CASSERT(h > 0);
+
+                                    double target_dx = (p_truth.x() - p.x())/w;
+                                    double target_dy = (p_truth.y() - p.y())/h;
+                                    double target_dw = std::log((truth_box.width()-1)/w);
+                                    double target_dh = std::log((truth_box.height()-1)/h);
+
+
+                                    // compute smoothed L1 loss on BBR outputs.  This loss
+                                    // is just the MSE loss when the loss is small and L1
+                                    // when large.
+                                    dx = dx-target_dx;
+                                    dy = dy-target_dy;
+                                    dw = dw-target_dw;
+                                    dh = dh-target_dh;
+
+                                    // use smoothed L1 
+                                    double ldx = std::abs(dx)<1 ? 0.5*dx*dx : std::abs(dx)-0.5;
+                                    double ldy = std::abs(dy)<1 ? 0.5*dy*dy : std::abs(dy)-0.5;
+                                    double ldw = std::abs(dw)<1 ? 0.5*dw*dw : std::abs(dw)-0.5;
+                                    double ldh = std::abs(dh)<1 ? 0.5*dh*dh : std::abs(dh)-0.5;
+
+                                    loss += options.bbr_lambda*(ldx + ldy + ldw + ldh);
+      
+                                    // now compute the derivatives of the smoothed L1 loss
+                                    ldx = put_in_range(-1,1, dx);
+                                    ldy = put_in_range(-1,1, dy);
+                                    ldw = put_in_range(-1,1, dw);
+                                    ldh = put_in_range(-1,1, dh);
+
+
+                                    // also smoothed L1 gradient goes to gradient output
+                                    g[dets[i].tensor_offset_dx] += scale*options.bbr_lambda*ldx;
+                                    g[dets[i].tensor_offset_dy] += scale*options.bbr_lambda*ldy;
+                                    g[dets[i].tensor_offset_dw] += scale*options.bbr_lambda*ldw;
+                                    g[dets[i].tensor_offset_dh] += scale*options.bbr_lambda*ldh;
+                                }
+
+                                // And the angle regression loss, which is the squared
+                                // distance between the predicted and true (cos,sin) pairs.
+                                if (options.use_angle_regression)
+                                {
+                                    const double truth_angle = (*truth)[hittruth.second].angle;
+                                    const double dcos = out_data[dets[i].tensor_offset_cos] - std::cos(truth_angle);
+                                    const double dsin = out_data[dets[i].tensor_offset_sin] - std::sin(truth_angle);
+
+                                    loss += options.angle_lambda*0.5*(dcos*dcos + dsin*dsin);
+
+                                    g[dets[i].tensor_offset_cos] += scale*options.angle_lambda*dcos;
+                                    g[dets[i].tensor_offset_sin] += scale*options.angle_lambda*dsin;
+                                }
+                            }
+                            else
+                            {
+                                final_dets.push_back(dets[i]);
+                                loss += options.loss_per_false_alarm;
+                            }
+                        }
+                    }
+                    else if (!overlaps_ignore_box(*truth, dets[i].rect))
+                    {
+                        // didn't hit anything
+                        final_dets.push_back(dets[i]);
+                        loss += options.loss_per_false_alarm;
+                    }
+                }
+
+                for (auto&& x : final_dets)
+                {
+                    loss += out_data[x.tensor_offset];
+                    g[x.tensor_offset] += scale;
+                }
+
+                ++truth;
+                g        += output_tensor.k()*output_tensor.nr()*output_tensor.nc();
+                out_data += output_tensor.k()*output_tensor.nr()*output_tensor.nc();
+            } // END for (long i = 0; i < output_tensor.num_samples(); ++i)
+
+
+            // Here we scale the loss so that it's roughly equal to the number of mistakes
+            // in an image.  Note that this scaling is different than the scaling we
+            // applied to the gradient but it doesn't matter since the loss value isn't
+            // used to update parameters.  It's used only for display and to check if we
+            // have converged.  So it doesn't matter that they are scaled differently and
+            // this way the loss that is displayed is readily interpretable to the user.
+            return loss/output_tensor.num_samples();
+        }
+
+
+        friend void serialize(const loss_mmod_& item, std::ostream& out)
+        {
+            serialize("loss_mmod_", out);
+            serialize(item.options, out);
+        }
+
+        friend void deserialize(loss_mmod_& item, std::istream& in)
+        {
+            std::string version;
+            deserialize(version, in);
+            if (version != "loss_mmod_")
+                throw serialization_error("Unexpected version found while deserializing dlib::loss_mmod_.");
+            deserialize(item.options, in);
+        }
+
+        friend std::ostream& operator<<(std::ostream& out, const loss_mmod_& item)
+        {
+            out << "loss_mmod\t (";
+
+            auto& opts = item.options;
+
+            out << "detector_windows:(" << opts.detector_windows << ")";
+
+            out << ", loss per FA:" << opts.loss_per_false_alarm;
+            out << ", loss per miss:" << opts.loss_per_missed_target;
+            out << ", truth match IOU thresh:" << opts.truth_match_iou_threshold;
+            out << ", use_bounding_box_regression:" << opts.use_bounding_box_regression;
+            if (opts.use_bounding_box_regression)
+                out << ", bbr_lambda:" << opts.bbr_lambda;
+            out << ", use_angle_regression:" << opts.use_angle_regression;
+            if (opts.use_angle_regression)
+                out << ", angle_lambda:" << opts.angle_lambda;
+            out << ", overlaps_nms:("<<opts.overlaps_nms.get_iou_thresh()<<","<<opts.overlaps_nms.get_percent_covered_thresh()<<")";
+            out << ", overlaps_ignore:("<<opts.overlaps_ignore.get_iou_thresh()<<","<<opts.overlaps_ignore.get_percent_covered_thresh()<<")";
 
-                // The loss will measure the number of incorrect detections.  A detection is
-                // incorrect if it doesn't hit a truth rectangle or if it is a duplicate detection
-                // on a truth rectangle.
-                loss += truth->size()*options.loss_per_missed_target;
-                for (auto&& x : *truth)
+            out << ")";
+            return out;
+        }
+
+        friend void to_xml(const loss_mmod_& /*item*/, std::ostream& out)
+        {
+            // TODO, add options fields
+            out << "<loss_mmod/>\n";
+        }
+
+    private:
+
+        long num_output_channels (
+        ) const
+        {
+            // One detection score per detector window, followed by 4 bounding box
+            // regression and then 2 angle regression channels per window when those are
+            // turned on.
+            long channels_per_window = 1;
+            if (options.use_bounding_box_regression)
+                channels_per_window += 4;
+            if (options.use_angle_regression)
+                channels_per_window += 2;
+            return options.detector_windows.size()*channels_per_window;
+        }
+
+        template <typename net_type>
+        void tensor_to_dets (
+            const tensor& input_tensor,
+            const tensor& output_tensor,
+            long i,
+            std::vector<intermediate_detection>& dets_accum,
+            double adjust_threshold,
+            const net_type& net 
+        ) const
+        {
+            DLIB_CASSERT(net.sample_expansion_factor() == 1,net.sample_expansion_factor());
+            DLIB_CASSERT(output_tensor.k() == num_output_channels(), output_tensor.k() << " != " << num_output_channels());
+
+            const float* out_data = output_tensor.host() + output_tensor.k()*output_tensor.nr()*output_tensor.nc()*i;
+            // scan the final layer and output the positive scoring locations
+            dets_accum.clear();
+            for (long k = 0; k < (long)options.detector_windows.size(); ++k)
+            {
+                for (long r = 0; r < output_tensor.nr(); ++r)
                 {
-                    if (!x.ignore)
+                    for (long c = 0; c < output_tensor.nc(); ++c)
                     {
-                        size_t k;
-                        point p;
-                        if(image_rect_to_feat_coord(p, input_tensor, x, x.label, sub, k, options.assume_image_pyramid))
-                        {
-                            // Ignore boxes that can't be detected by the CNN.
-                            loss -= options.loss_per_missed_target;
-                            truth_idxs.push_back(-1);
-                            continue;
-                        }
-                        const size_t idx = (k*output_tensor.nr() + p.y())*output_tensor.nc() + p.x();
-                        const auto i = idx_to_truth_rect.find(idx);
-                        if (i != idx_to_truth_rect.end())
+                        double score = out_data[(k*output_tensor.nr() + r)*output_tensor.nc() + c];
+                        if (score > adjust_threshold)
                         {
-                            if (!options.be_quiet) 
+                            dpoint p = output_tensor_to_input_tensor(net, point(c,r));
+                            drectangle rect = centered_drect(p, options.detector_windows[k].width, options.detector_windows[k].height);
+                            rect = input_layer(net).tensor_space_to_image_space(input_tensor,rect);
+
+                            dets_accum.push_back(intermediate_detection(rect, score, (k*output_tensor.nr() + r)*output_tensor.nc() + c, k));
+
+                            if (options.use_bounding_box_regression)
                             {
-                                // Ignore duplicate truth box in feature coordinates.
-                                std::cout << "Warning, ignoring object.  We encountered a truth rectangle located at " << x.rect;
-                                std::cout << ", and we are ignoring it because it maps to the exact same feature coordinates ";
-                                std::cout << "as another truth rectangle located at " << i->second << "." << std::endl;
+                                const auto offset = options.detector_windows.size() + k*4;
+                                dets_accum.back().tensor_offset_dx = ((offset+0)*output_tensor.nr() + r)*output_tensor.nc() + c;
+                                dets_accum.back().tensor_offset_dy = ((offset+1)*output_tensor.nr() + r)*output_tensor.nc() + c;
+                                dets_accum.back().tensor_offset_dw = ((offset+2)*output_tensor.nr() + r)*output_tensor.nc() + c;
+                                dets_accum.back().tensor_offset_dh = ((offset+3)*output_tensor.nr() + r)*output_tensor.nc() + c;
+
+                                // apply BBR to dets_accum.back()
+                                double dx = out_data[dets_accum.back().tensor_offset_dx];
+                                double dy = out_data[dets_accum.back().tensor_offset_dy];
+                                double dw = out_data[dets_accum.back().tensor_offset_dw];
+                                double dh = out_data[dets_accum.back().tensor_offset_dh];
+                                dw = std::exp(dw);
+                                dh = std::exp(dh);
+                                double w = rect.width()-1;
+                                double h = rect.height()-1;
+                                rect = translate_rect(rect, dpoint(dx*w,dy*h));
+                                rect = centered_drect(rect, w*dw+1, h*dh+1);
+                                dets_accum.back().rect_bbr = rect;
                             }
 
-                            loss -= options.loss_per_missed_target;
-                            truth_idxs.push_back(-1);
-                            continue;
+                            if (options.use_angle_regression)
+                            {
+                                const auto offset = options.detector_windows.size()*(options.use_bounding_box_regression ? 5 : 1) + k*2;
+                                dets_accum.back().tensor_offset_cos = ((offset+0)*output_tensor.nr() + r)*output_tensor.nc() + c;
+                                dets_accum.back().tensor_offset_sin = ((offset+1)*output_tensor.nr() + r)*output_tensor.nc() + c;
+                                dets_accum.back().angle = std::atan2(out_data[dets_accum.back().tensor_offset_sin],
+                                                                     out_data[dets_accum.back().tensor_offset_cos]);
+                            }
                         }
-                        loss -= out_data[idx];
-                        // compute gradient
-                        g[idx] = -scale;
-                        truth_idxs.push_back(idx);
-                        idx_to_truth_rect[idx] = x.rect;
                     }
-                    else
+                }
+            }
+            std::sort(dets_accum.rbegin(), dets_accum.rend());
+        }
+
+        size_t find_best_detection_window (
+            rectangle rect,
+            const std::string& label,
+            use_image_pyramid assume_image_pyramid
+        ) const
+        {
+            if (assume_image_pyramid == use_image_pyramid::yes)
+            {
+                rect = move_rect(set_rect_area(rect, 400*400), point(0,0));
+            }
+            else
+            {
+                rect = rectangle(rect.width(), rect.height());
+            }
+
+            // Figure out which detection window in options.detector_windows is most similar to rect
+            // (in terms of aspect ratio, if assume_image_pyramid == use_image_pyramid::yes).
+            size_t best_i = 0;
+            double best_ratio_diff = -std::numeric_limits<double>::infinity();
+            for (size_t i = 0; i < options.detector_windows.size(); ++i)
+            {
+                if (options.detector_windows[i].label != label)
+                    continue;
+
+                rectangle det_window;
+                
+                if (options.assume_image_pyramid == use_image_pyramid::yes)
+                {
+                    det_window = centered_rect(point(0,0), options.detector_windows[i].width, options.detector_windows[i].height);
+                    det_window = move_rect(set_rect_area(det_window, 400*400), point(0,0));
+                }
+                else
+                {
+                    det_window = rectangle(options.detector_windows[i].width, options.detector_windows[i].height);
+                }
+
+                double iou = box_intersection_over_union(rect, det_window);
+                if (iou > best_ratio_diff)
+                {
+                    best_ratio_diff = iou;
+                    best_i = i;
+                }
+            }
+            return best_i;
+        }
+
+        template <typename net_type>
+        bool image_rect_to_feat_coord (
+            point& tensor_p,
+            const tensor& input_tensor,
+            const rectangle& rect,
+            const std::string& label,
+            const net_type& net,
+            size_t& det_idx,
+            use_image_pyramid assume_image_pyramid
+        ) const 
+        {
+            if (!input_layer(net).image_contained_point(input_tensor,center(rect)))
+            {
+                std::ostringstream sout;
+                sout << "Encountered a truth rectangle located at " << rect << " that is outside the image." << std::endl;
+                sout << "The center of each truth rectangle must be within the image." << std::endl;
+                throw impossible_labeling_error(sout.str());
+            }
+
+            det_idx = find_best_detection_window(rect,label,assume_image_pyramid);
+
+            double scale = 1.0;
+            if (options.assume_image_pyramid == use_image_pyramid::yes)
+            {
+                // Compute the scale we need to be at to get from rect to our detection window.
+                // Note that we compute the scale as the max of two numbers.  It doesn't
+                // actually matter which one we pick, because if they are very different then
+                // it means the box can't be matched by the sliding window.  But picking the
+                // max causes the right error message to be selected in the logic below.
+                scale = std::max(options.detector_windows[det_idx].width/(double)rect.width(), options.detector_windows[det_idx].height/(double)rect.height());
+            }
+            else
+            {
+                // We don't want invariance to scale.
+                scale = 1.0;
+            }
+
+            const rectangle mapped_rect = input_layer(net).image_space_to_tensor_space(input_tensor, std::min(1.0,scale), rect);
+
+            // compute the detection window that we would use at this position.
+            tensor_p = center(mapped_rect);
+            rectangle det_window = centered_rect(tensor_p, options.detector_windows[det_idx].width,options.detector_windows[det_idx].height);
+            det_window = input_layer(net).tensor_space_to_image_space(input_tensor, det_window);
+
+            // make sure the rect can actually be represented by the image pyramid we are
+            // using.
+            if (box_intersection_over_union(rect, det_window) <= options.truth_match_iou_threshold)
+            {
+                std::cout << "Warning, ignoring object.  We encountered a truth rectangle with a width and height of " << rect.width() << " and " << rect.height() << ".  ";
+                std::cout << "The image pyramid and sliding windows can't output a rectangle of this shape.  ";
+                const double detector_area = options.detector_windows[det_idx].width*options.detector_windows[det_idx].height;
+                if (mapped_rect.area()/detector_area <= options.truth_match_iou_threshold)
+                {
+                    std::cout << "This is because the rectangle is smaller than the best matching detection window, which has a width ";
+                    std::cout << "and height of " << options.detector_windows[det_idx].width << " and " << options.detector_windows[det_idx].height << "." << std::endl;
+                }
+                else
+                {
+                    std::cout << "This is either because (1) the final layer's features have too large of a stride across the image, limiting the possible locations the sliding window can search ";
+                    std::cout << "or (2) because the rectangle's aspect ratio is too different from the best matching detection window, ";
+                    std::cout << "which has a width and height of " << options.detector_windows[det_idx].width << " and " << options.detector_windows[det_idx].height << "." << std::endl;
+                }
+                return true;
+            }
+
+            // now map through the CNN to the output layer.
+            tensor_p = input_tensor_to_output_tensor(net,tensor_p);
+
+            const tensor& output_tensor = net.get_output();
+            if (!get_rect(output_tensor).contains(tensor_p))
+            {
+                std::cout << "Warning, ignoring object.  We encountered a truth rectangle located at " << rect << " that is too close to the edge ";
+                std::cout << "of the image to be captured by the CNN features." << std::endl;
+                return true;
+            }
+
+            return false;
+        }
+
+
+        bool overlaps_ignore_box (
+            const std::vector<mmod_rect>& boxes,
+            const rectangle& rect
+        ) const
+        {
+            for (auto&& b : boxes)
+            {
+                if (b.ignore && options.overlaps_ignore(b, rect))
+                    return true;
+            }
+            return false;
+        }
+
+        std::pair<double,unsigned int> find_best_match(
+            const std::vector<mmod_rect>& boxes,
+            const std::vector<bool>& hit_truth_table,
+            const rectangle& rect,
+            const std::string& label
+        ) const
+        {
+            double match = 0;
+            unsigned int best_idx = 0;
+
+            for (int allow_duplicate_hit = 0; allow_duplicate_hit <= 1 && match == 0; ++allow_duplicate_hit)
+            {
+                for (unsigned long i = 0; i < boxes.size(); ++i)
+                {
+                    if (boxes[i].ignore || boxes[i].label != label)
+                        continue;
+                    if (!allow_duplicate_hit && hit_truth_table[i])
+                        continue;
+
+                    const double new_match = box_intersection_over_union(rect, boxes[i]);
+                    if (new_match > match)
                     {
-                        // This box was ignored so shouldn't have been counted in the loss.
-                        loss -= options.loss_per_missed_target;
-                        truth_idxs.push_back(-1);
+                        match = new_match;
+                        best_idx = i;
                     }
                 }
+            }
 
-                // Measure the loss augmented score for the detections which hit a truth rect.
-                std::vector<double> truth_score_hits(truth->size(), 0);
+            return std::make_pair(match,best_idx);
+        }
 
-                // keep track of which truth boxes we have hit so far.
-                std::vector<bool> hit_truth_table(truth->size(), false);
+        std::pair<double,unsigned int> find_best_match(
+            const std::vector<mmod_rect>& boxes,
+            const rectangle& rect,
+            const size_t excluded_idx
+        ) const
+        {
+            double match = 0;
+            unsigned int best_idx = 0;
+            for (unsigned long i = 0; i < boxes.size(); ++i)
+            {
+                if (boxes[i].ignore || excluded_idx == i)
+                    continue;
 
-                std::vector<intermediate_detection> final_dets;
-                // The point of this loop is to fill out the truth_score_hits array. 
-                for (size_t i = 0; i < dets.size() && final_dets.size() < max_num_dets; ++i)
+                const double new_match = box_intersection_over_union(rect, boxes[i]);
+                if (new_match > match)
                 {
-                    if (overlaps_any_box_nms(final_dets, dets[i].rect_bbr))
-                        continue;
+                    match = new_match;
+                    best_idx = i;
+                }
+            }
 
-                    const auto& det_label = options.detector_windows[dets[i].tensor_channel].label;
+            return std::make_pair(match,best_idx);
+        }
 
-                    const std::pair<double,unsigned int> hittruth = find_best_match(*truth, hit_truth_table, dets[i].rect, det_label);
+        template <typename T, typename U>
+        bool overlaps_nms (
+            const T& a,
+            const U& b
+        ) const
+        {
+            // When the detector predicts angles we do rotated NMS, otherwise the boxes are
+            // all axis aligned and any angles are ignored.
+            if (options.use_angle_regression)
+                return options.overlaps_nms(a.rect, a.angle, b.rect, b.angle);
+            return options.overlaps_nms(a.rect, b.rect);
+        }
 
-                    final_dets.push_back(dets[i].rect);
+        template <typename T>
+        inline bool overlaps_any_box_nms (
+            const std::vector<T>& rects,
+            const rectangle& rect,
+            double angle = 0
+        ) const
+        {
+            mmod_rect temp(rect);
+            temp.angle = angle;
+            for (auto&& r : rects)
+            {
+                if (overlaps_nms(r, temp))
+                    return true;
+            }
+            return false;
+        }
 
-                    const double truth_match = hittruth.first;
-                    // if hit truth rect
-                    if (truth_match > options.truth_match_iou_threshold)
-                    {
-                        // if this is the first time we have seen a detect which hit (*truth)[hittruth.second]
-                        const double score = dets[i].detection_confidence;
-                        if (hit_truth_table[hittruth.second] == false)
-                        {
-                            hit_truth_table[hittruth.second] = true;
-                            truth_score_hits[hittruth.second] += score;
-                        }
-                        else
-                        {
-                            truth_score_hits[hittruth.second] += score + options.loss_per_false_alarm;
-                        }
-                    }
-                }
 
-                // Check if any of the truth boxes are unobtainable because the NMS is
-                // killing them.  If so, automatically set those unobtainable boxes to
-                // ignore and print a warning message to the user.
-                for (size_t i = 0; i < hit_truth_table.size(); ++i)
-                {
-                    if (!hit_truth_table[i] && !(*truth)[i].ignore) 
-                    {
-                        // So we didn't hit this truth box.  Is that because there is
-                        // another, different truth box, that overlaps it according to NMS?
-                        const std::pair<double,unsigned int> hittruth = find_best_match(*truth, (*truth)[i], i);
-                        if (hittruth.second == i || (*truth)[hittruth.second].ignore)
-                            continue;
-                        rectangle best_matching_truth_box = (*truth)[hittruth.second];
-                        if (options.overlaps_nms(best_matching_truth_box, (*truth)[i]))
-                        {
-                            const int idx = truth_idxs[i];
-                            if (idx != -1)
-                            {
-                                // We are ignoring this box so we shouldn't have counted it in the
-                                // loss in the first place.  So we subtract out the loss values we
-                                // added for it in the code above.
-                                loss -= options.loss_per_missed_target-out_data[idx];
-                                g[idx] = 0;
-                                if (!options.be_quiet) 
-                                {
-                                    std::cout << "Warning, ignoring object.  We encountered a truth rectangle located at " << (*truth)[i].rect;
-                                    std::cout << " that is suppressed by non-max-suppression ";
-                                    std::cout << "because it is overlapped by another truth rectangle located at " << best_matching_truth_box 
-                                        << " (IoU:"<< box_intersection_over_union(best_matching_truth_box,(*truth)[i]) <<", Percent covered:" 
-                                        << box_percent_covered(best_matching_truth_box,(*truth)[i]) << ")." << std::endl;
-                                }
-                            }
-                        }
-                    }
-                }
+        mmod_options options;
 
-                hit_truth_table.assign(hit_truth_table.size(), false);
-                final_dets.clear();
+    };
 
-                // Now figure out which detections jointly maximize the loss and detection score sum.  We
-                // need to take into account the fact that allowing a true detection in the output, while 
-                // initially reducing the loss, may allow us to increase the loss later with many duplicate
-                // detections.
-                for (unsigned long i = 0; i < dets.size() && final_dets.size() < max_num_dets; ++i)
-                {
-                    if (overlaps_any_box_nms(final_dets, dets[i].rect_bbr))
-                        continue;
+    template <typename SUBNET>
+    using loss_mmod = add_loss_layer<loss_mmod_, SUBNET>;
 
-                    const auto& det_label = options.detector_windows[dets[i].tensor_channel].label;
+// ----------------------------------------------------------------------------------------
 
-                    const std::pair<double,unsigned int> hittruth = find_best_match(*truth, hit_truth_table, dets[i].rect, det_label);
+    class loss_metric_ 
+    {
+    public:
 
-                    const double truth_match = hittruth.first;
-                    if (truth_match > options.truth_match_iou_threshold)
-                    {
-                        if (truth_score_hits[hittruth.second] > options.loss_per_missed_target)
-                        {
-                            if (!hit_truth_table[hittruth.second])
-                            {
-                                hit_truth_table[hittruth.second] = true;
-                                final_dets.push_back(dets[i]);
-                                loss -= options.loss_per_missed_target;
+        typedef unsigned long training_label_type;
+        typedef matrix<float,0,1> output_label_type;
 
-                                // Now account for BBR loss and gradient if appropriate.
-                                if (options.use_bounding_box_regression)
-                                {
-                                    double dx = out_data[dets[i].tensor_offset_dx];
-                                    double dy = out_data[dets[i].tensor_offset_dy];
-                                    double dw = out_data[dets[i].tensor_offset_dw];
-                                    double dh = out_data[dets[i].tensor_offset_dh];
+        loss_metric_() = default;
 
-                                    dpoint p = dcenter(dets[i].rect);
-                                    double w = dets[i].rect.width()-1;
-                                    double h = dets[i].rect.height()-1;
-                                    drectangle truth_box = (*truth)[hittruth.second].rect;
-                                    dpoint p_truth = dcenter(truth_box); 
+        loss_metric_(
+            float margin_,
+            float dist_thresh_
+        ) : margin(margin_), dist_thresh(dist_thresh_) 
+        {
+            DLIB_CASSERT(margin_ > 0);
+            DLIB_CASSERT(dist_thresh_ > 0);
+        }
 
-                                    DLIB_CASSERT(w > 0);
-                                    DLIB_CASSERT(h > 0);
+        template <
+            typename SUB_TYPE,
+            typename label_iterator
+            >
+        void to_label (
+            const tensor& input_tensor,
+            const SUB_TYPE& sub,
+            label_iterator iter
+        ) const
+        {
+            const tensor& output_tensor = sub.get_output();
+            DLIB_CASSERT(sub.sample_expansion_factor() == 1);
+            DLIB_CASSERT(input_tensor.num_samples() != 0);
+            DLIB_CASSERT(input_tensor.num_samples()%sub.sample_expansion_factor() == 0);
+            DLIB_CASSERT(input_tensor.num_samples() == output_tensor.num_samples());
+            DLIB_CASSERT(output_tensor.nr() == 1 && 
+                         output_tensor.nc() == 1);
 
-                                    double target_dx = (p_truth.x() - p.x())/w;
-                                    double target_dy = (p_truth.y() - p.y())/h;
-                                    double target_dw = std::log((truth_box.width()-1)/w);
-                                    double target_dh = std::log((truth_box.height()-1)/h);
+            const float* p = output_tensor.host();
+            for (long i = 0; i < output_tensor.num_samples(); ++i)
+            {
+                *iter = mat(p,output_tensor.k(),1);
+
+                ++iter;
+                p += output_tensor.k();
+            }
+        }
+
+
+        float get_margin() const { return margin; }
+        float get_distance_threshold() const { return dist_thresh; }
+
+        template <
+            typename const_label_iterator,
+            typename SUBNET
+            >
+        double compute_loss_value_and_gradient (
+            const tensor& input_tensor,
+            const_label_iterator truth, 
+            SUBNET& sub
+        ) const
+        {
+            const tensor& output_tensor = sub.get_output();
+            tensor& grad = sub.get_gradient_input();
+
+            DLIB_CASSERT(sub.sample_expansion_factor() == 1);
+            DLIB_CASSERT(input_tensor.num_samples() != 0);
+            DLIB_CASSERT(input_tensor.num_samples()%sub.sample_expansion_factor() == 0);
+            DLIB_CASSERT(input_tensor.num_samples() == grad.num_samples());
+            DLIB_CASSERT(input_tensor.num_samples() == output_tensor.num_samples());
+            DLIB_CASSERT(output_tensor.nr() == 1 && 
+                         output_tensor.nc() == 1);
+            DLIB_CASSERT(grad.nr() == 1 && 
+                         grad.nc() == 1);
+
+
+
+            temp.set_size(output_tensor.num_samples(), output_tensor.num_samples());
+            grad_mul.copy_size(temp);
+
+            tt::gemm(0, temp, 1, output_tensor, false, output_tensor, true);
+
+
+            std::vector<double> temp_threshs;
+            const float* d = temp.host();
+            double loss = 0;
+            double num_pos_samps = 0.0001;
+            double num_neg_samps = 0.0001;
+            for (long r = 0; r < temp.num_samples(); ++r)
+            {
+                auto xx = d[r*temp.num_samples() + r];
+                const auto x_label = *(truth + r);
+                for (long c = r+1; c < temp.num_samples(); ++c)
+                {
+                    const auto y_label = *(truth + c);
+                    if (x_label == y_label)
+                    {
+                        ++num_pos_samps;
+                    }
+                    else
+                    {
+                        ++num_neg_samps;
 
+                        // Figure out what distance threshold, when applied to the negative pairs,
+                        // causes there to be an equal number of positive and negative pairs.
+                        auto yy = d[c*temp.num_samples() + c];
+                        auto xy = d[r*temp.num_samples() + c];
+                        // compute the distance between x and y samples.
+                        auto d2 = xx + yy - 2*xy;
+                        if (d2 < 0)
+                            d2 = 0;
+                        temp_threshs.push_back(d2);
+                    }
+                }
+            }
+            // The whole objective function is multiplied by this to scale the loss
+            // relative to the number of things in the mini-batch.
+            const double scale = 0.5/num_pos_samps;
+            DLIB_CASSERT(num_pos_samps>=1, "Make sure each mini-batch contains both positive pairs and negative pairs");
+            DLIB_CASSERT(num_neg_samps>=1, "Make sure each mini-batch contains both positive pairs and negative pairs");
 
-                                    // compute smoothed L1 loss on BBR outputs.  This loss
-                                    // is just the MSE loss when the loss is small and L1
-                                    // when large.
-                                    dx = dx-target_dx;
-                                    dy = dy-target_dy;
-                                    dw = dw-target_dw;
-                                    dh = dh-target_dh;
+            std::sort(temp_threshs.begin(), temp_threshs.end());
+            const float neg_thresh = std::sqrt(temp_threshs[std::min(num_pos_samps,num_neg_samps)-1]);
 
-                                    // use smoothed L1 
-                                    double ldx = std::abs(dx)<1 ? 0.5*dx*dx : std::abs(dx)-0.5;
-                                    double ldy = std::abs(dy)<1 ? 0.5*dy*dy : std::abs(dy)-0.5;
-                                    double ldw = std::abs(dw)<1 ? 0.5*dw*dw : std::abs(dw)-0.5;
-                                    double ldh = std::abs(dh)<1 ? 0.5*dh*dh : std::abs(dh)-0.5;
+            // loop over all the pairs of training samples and compute the loss and
+            // gradients.  Note that we only use the hardest negative pairs and that in
+            // particular we pick the number of negative pairs equal to the number of
+            // positive pairs so everything is balanced.
+            float* gm = grad_mul.host();
+            for (long r = 0; r < temp.num_samples(); ++r)
+            {
+                gm[r*temp.num_samples() + r] = 0;
+                const auto x_label = *(truth + r);
+                auto xx = d[r*temp.num_samples() + r];
+                for (long c = 0; c < temp.num_samples(); ++c)
+                {
+                    if (r==c)
+                        continue;
+                    const auto y_label = *(truth + c);
+                    auto yy = d[c*temp.num_samples() + c];
+                    auto xy = d[r*temp.num_samples() + c];
 
-                                    loss += options.bbr_lambda*(ldx + ldy + ldw + ldh);
-      
-                                    // now compute the derivatives of the smoothed L1 loss
-                                    ldx = put_in_range(-1,1, dx);
-                                    ldy = put_in_range(-1,1, dy);
-                                    ldw = put_in_range(-1,1, dw);
-                                    ldh = put_in_range(-1,1, dh);
+                    // compute the distance between x and y samples.
+                    auto d2 = xx + yy - 2*xy;
+                    if (d2 <= 0)
+                        d2 = 0;
+                    else 
+                        d2 = std::sqrt(d2);
 
+                    // It should be noted that the derivative of length(x-y) with respect
+                    // to the x vector is the unit vector (x-y)/length(x-y).  If you stare
+                    // at the code below long enough you will see that it's just an
+                    // application of this formula.
 
-                                    // also smoothed L1 gradient goes to gradient output
-                                    g[dets[i].tensor_offset_dx] += scale*options.bbr_lambda*ldx;
-                                    g[dets[i].tensor_offset_dy] += scale*options.bbr_lambda*ldy;
-                                    g[dets[i].tensor_offset_dw] += scale*options.bbr_lambda*ldw;
-                                    g[dets[i].tensor_offset_dh] += scale*options.bbr_lambda*ldh;
-                                }
-                            }
-                            else
-                            {
-                                final_dets.push_back(dets[i]);
-                                loss += options.loss_per_false_alarm;
-                            }
+                    if (x_label == y_label)
+                    {
+                        // Things with the same label should have distances < dist_thresh between
+                        // them.  If not then we experience non-zero loss.
+                        if (d2 < dist_thresh-margin)
+                        {
+                            gm[r*temp.num_samples() + c] = 0;
+                        }
+                        else
+                        {
+                            loss += scale*(d2 - (dist_thresh-margin));
+                            gm[r*temp.num_samples() + r] += scale/d2;
+                            gm[r*temp.num_samples() + c] = -scale/d2;
                         }
                     }
-                    else if (!overlaps_ignore_box(*truth, dets[i].rect))
+                    else
                     {
-                        // didn't hit anything
-                        final_dets.push_back(dets[i]);
-                        loss += options.loss_per_false_alarm;
+                        // Things with different labels should have distances > dist_thresh between
+                        // them.  If not then we experience non-zero loss.
+                        if (d2 > dist_thresh+margin || d2 > neg_thresh)
+                        {
+                            gm[r*temp.num_samples() + c] = 0;
+                        }
+                        else
+                        {
+                            loss += scale*((dist_thresh+margin) - d2);
+                            // don't divide by zero (or a really small number)
+                            d2 = std::max(d2, 0.001f);
+                            gm[r*temp.num_samples() + r] -= scale/d2;
+                            gm[r*temp.num_samples() + c] = scale/d2;
+                        }
                     }
                 }
+            }
 
-                for (auto&& x : final_dets)
-                {
-                    loss += out_data[x.tensor_offset];
-                    g[x.tensor_offset] += scale;
-                }
-
-                ++truth;
-                g        += output_tensor.k()*output_tensor.nr()*output_tensor.nc();
-                out_data += output_tensor.k()*output_tensor.nr()*output_tensor.nc();
-            } // END for (long i = 0; i < output_tensor.num_samples(); ++i)
 
+            tt::gemm(0, grad, 1, grad_mul, false, output_tensor, false); 
 
-            // Here we scale the loss so that it's roughly equal to the number of mistakes
-            // in an image.  Note that this scaling is different than the scaling we
-            // applied to the gradient but it doesn't matter since the loss value isn't
-            // used to update parameters.  It's used only for display and to check if we
-            // have converged.  So it doesn't matter that they are scaled differently and
-            // this way the loss that is displayed is readily interpretable to the user.
-            return loss/output_tensor.num_samples();
+            return loss;
         }
 
-
-        friend void serialize(const loss_mmod_& item, std::ostream& out)
+        friend void serialize(const loss_metric_& item, std::ostream& out)
         {
-            serialize("loss_mmod_", out);
-            serialize(item.options, out);
+            serialize("loss_metric_2", out);
+            serialize(item.margin, out);
+            serialize(item.dist_thresh, out);
         }
 
-        friend void deserialize(loss_mmod_& item, std::istream& in)
+        friend void deserialize(loss_metric_& item, std::istream& in)
         {
             std::string version;
             deserialize(version, in);
-            if (version != "loss_mmod_")
-                throw serialization_error("Unexpected version found while deserializing dlib::loss_mmod_.");
-            deserialize(item.options, in);
+            if (version == "loss_metric_")
+            {
+                // These values used to be hard coded, so for this version of the metric
+                // learning loss we just use these values.
+                item.margin = 0.1f;
+                item.dist_thresh = 0.75f;
+                return;
+            }
+            else if (version == "loss_metric_2")
+            {
+                deserialize(item.margin, in);
+                deserialize(item.dist_thresh, in);
+            }
+            else
+            {
+                throw serialization_error("Unexpected version found while deserializing dlib::loss_metric_.  Instead found " + version);
+            }
         }
 
-        friend std::ostream& operator<<(std::ostream& out, const loss_mmod_& item)
+        friend std::ostream& operator<<(std::ostream& out, const loss_metric_& item )
         {
-            out << "loss_mmod\t (";
-
-            auto& opts = item.options;
-
-            out << "detector_windows:(" << opts.detector_windows << ")";
-
-            out << ", loss per FA:" << opts.loss_per_false_alarm;
-            out << ", loss per miss:" << opts.loss_per_missed_target;
-            out << ", truth match IOU thresh:" << opts.truth_match_iou_threshold;
-            out << ", use_bounding_box_regression:" << opts.use_bounding_box_regression;
-            if (opts.use_bounding_box_regression)
-                out << ", bbr_lambda:" << opts.bbr_lambda;
-            out << ", overlaps_nms:("<<opts.overlaps_nms.get_iou_thresh()<<","<<opts.overlaps_nms.get_percent_covered_thresh()<<")";
-            out << ", overlaps_ignore:("<<opts.overlaps_ignore.get_iou_thresh()<<","<<opts.overlaps_ignore.get_percent_covered_thresh()<<")";
-
-            out << ")";
+            out << "loss_metric (margin="<<item.margin<<", distance_threshold="<<item.dist_thresh<<")";
             return out;
         }
 
-        friend void to_xml(const loss_mmod_& /*item*/, std::ostream& out)
+        friend void to_xml(const loss_metric_& item, std::ostream& out)
         {
-            // TODO, add options fields
-            out << "<loss_mmod/>\n";
+            out << "<loss_metric margin='"<<item.margin<<"' distance_threshold='"<<item.dist_thresh<<"'/>\n";
         }
 
     private:
+        float margin = 0.04f;
+        float dist_thresh = 0.6f;
 
-        template <typename net_type>
-        void tensor_to_dets (
-            const tensor& input_tensor,
-            const tensor& output_tensor,
-            long i,
-            std::vector<intermediate_detection>& dets_accum,
-            double adjust_threshold,
-            const net_type& net 
-        ) const
-        {
-            DLIB_CASSERT(net.sample_expansion_factor() == 1,net.sample_expansion_factor());
-            if (options.use_bounding_box_regression)
-            {
-                DLIB_CASSERT(output_tensor.k() == (long)options.detector_windows.size()*5);
-            }
-            else
-            {
-                DLIB_CASSERT(output_tensor.k() == (long)options.detector_windows.size());
-            }
 
-            const float* out_data = output_tensor.host() + output_tensor.k()*output_tensor.nr()*output_tensor.nc()*i;
-            // scan the final layer and output the positive scoring locations
-            dets_accum.clear();
-            for (long k = 0; k < (long)options.detector_windows.size(); ++k)
-            {
-                for (long r = 0; r < output_tensor.nr(); ++r)
-                {
-                    for (long c = 0; c < output_tensor.nc(); ++c)
-                    {
-                        double score = out_data[(k*output_tensor.nr() + r)*output_tensor.nc() + c];
-                        if (score > adjust_threshold)
-                        {
-                            dpoint p = output_tensor_to_input_tensor(net, point(c,r));
-                            drectangle rect = centered_drect(p, options.detector_windows[k].width, options.detector_windows[k].height);
-                            rect = input_layer(net).tensor_space_to_image_space(input_tensor,rect);
+        // These variables are only here to avoid being reallocated over and over in
+        // compute_loss_value_and_gradient()
+        mutable resizable_tensor temp, grad_mul;
 
-                            dets_accum.push_back(intermediate_detection(rect, score, (k*output_tensor.nr() + r)*output_tensor.nc() + c, k));
+    };
 
-                            if (options.use_bounding_box_regression)
-                            {
-                                const auto offset = options.detector_windows.size() + k*4;
-                                dets_accum.back().tensor_offset_dx = ((offset+0)*output_tensor.nr() + r)*output_tensor.nc() + c;
-                                dets_accum.back().tensor_offset_dy = ((offset+1)*output_tensor.nr() + r)*output_tensor.nc() + c;
-                                dets_accum.back().tensor_offset_dw = ((offset+2)*output_tensor.nr() + r)*output_tensor.nc() + c;
-                                dets_accum.back().tensor_offset_dh = ((offset+3)*output_tensor.nr() + r)*output_tensor.nc() + c;
+    template <typename SUBNET>
+    using loss_metric = add_loss_layer<loss_metric_, SUBNET>;
 
-                                // apply BBR to dets_accum.back()
-                                double dx = out_data[dets_accum.back().tensor_offset_dx];
-                                double dy = out_data[dets_accum.back().tensor_offset_dy];
-                                double dw = out_data[dets_accum.back().tensor_offset_dw];
-                                double dh = out_data[dets_accum.back().tensor_offset_dh];
-                                dw = std::exp(dw);
-                                dh = std::exp(dh);
-                                double w = rect.width()-1;
-                                double h = rect.height()-1;
-                                rect = translate_rect(rect, dpoint(dx*w,dy*h));
-                                rect = centered_drect(rect, w*dw+1, h*dh+1);
-                                dets_accum.back().rect_bbr = rect;
-                            }
-                        }
-                    }
-                }
-            }
-            std::sort(dets_accum.rbegin(), dets_accum.rend());
-        }
+// ----------------------------------------------------------------------------------------
+
+    class loss_ranking_
+    {
+    public:
+
+        typedef float training_label_type; // nominally +1/-1
+        typedef float output_label_type; // ranking score
 
-        size_t find_best_detection_window (
-            rectangle rect,
-            const std::string& label,
-            use_image_pyramid assume_image_pyramid
+        template <
+            typename SUB_TYPE,
+            typename label_iterator
+            >
+        void to_label (
+            const tensor& input_tensor,
+            const SUB_TYPE& sub,
+            label_iterator iter
         ) const
         {
-            if (assume_image_pyramid == use_image_pyramid::yes)
-            {
-                rect = move_rect(set_rect_area(rect, 400*400), point(0,0));
-            }
-            else
-            {
-                rect = rectangle(rect.width(), rect.height());
-            }
+            DLIB_CASSERT(sub.sample_expansion_factor() == 1);
 
-            // Figure out which detection window in options.detector_windows is most similar to rect
-            // (in terms of aspect ratio, if assume_image_pyramid == use_image_pyramid::yes).
-            size_t best_i = 0;
-            double best_ratio_diff = -std::numeric_limits<double>::infinity();
-            for (size_t i = 0; i < options.detector_windows.size(); ++i)
-            {
-                if (options.detector_windows[i].label != label)
-                    continue;
+            const tensor& output_tensor = sub.get_output();
 
-                rectangle det_window;
-                
-                if (options.assume_image_pyramid == use_image_pyramid::yes)
-                {
-                    det_window = centered_rect(point(0,0), options.detector_windows[i].width, options.detector_windows[i].height);
-                    det_window = move_rect(set_rect_area(det_window, 400*400), point(0,0));
-                }
-                else
-                {
-                    det_window = rectangle(options.detector_windows[i].width, options.detector_windows[i].height);
-                }
+            DLIB_CASSERT(output_tensor.nr() == 1 &&
+                         output_tensor.nc() == 1 &&
+                         output_tensor.k() == 1);
+            DLIB_CASSERT(input_tensor.num_samples() == output_tensor.num_samples());
 
-                double iou = box_intersection_over_union(rect, det_window);
-                if (iou > best_ratio_diff)
-                {
-                    best_ratio_diff = iou;
-                    best_i = i;
-                }
+            const float* out_data = output_tensor.host();
+            for (long i = 0; i < output_tensor.num_samples(); ++i)
+            {
+                *iter++ = out_data[i];
             }
-            return best_i;
         }
 
-        template <typename net_type>
-        bool image_rect_to_feat_coord (
-            point& tensor_p,
+
+        template <
+            typename const_label_iterator,
+            typename SUBNET
+            >
+        double compute_loss_value_and_gradient (
             const tensor& input_tensor,
-            const rectangle& rect,
-            const std::string& label,
-            const net_type& net,
-            size_t& det_idx,
-            use_image_pyramid assume_image_pyramid
-        ) const 
+            const_label_iterator truth,
+            SUBNET& sub
+        ) const
         {
-            if (!input_layer(net).image_contained_point(input_tensor,center(rect)))
-            {
-                std::ostringstream sout;
-                sout << "Encountered a truth rectangle located at " << rect << " that is outside the image." << std::endl;
-                sout << "The center of each truth rectangle must be within the image." << std::endl;
-                throw impossible_labeling_error(sout.str());
-            }
-
-            det_idx = find_best_detection_window(rect,label,assume_image_pyramid);
+            const tensor& output_tensor = sub.get_output();
+            tensor& grad = sub.get_gradient_input();
 
-            double scale = 1.0;
-            if (options.assume_image_pyramid == use_image_pyramid::yes)
-            {
-                // Compute the scale we need to be at to get from rect to our detection window.
-                // Note that we compute the scale as the max of two numbers.  It doesn't
-                // actually matter which one we pick, because if they are very different then
-                // it means the box can't be matched by the sliding window.  But picking the
-                // max causes the right error message to be selected in the logic below.
-                scale = std::max(options.detector_windows[det_idx].width/(double)rect.width(), options.detector_windows[det_idx].height/(double)rect.height());
-            }
-            else
-            {
-                // We don't want invariance to scale.
-                scale = 1.0;
-            }
+            DLIB_CASSERT(sub.sample_expansion_factor() == 1);
+            DLIB_CASSERT(input_tensor.num_samples() != 0);
+            DLIB_CASSERT(input_tensor.num_samples()%sub.sample_expansion_factor() == 0);
+            DLIB_CASSERT(input_tensor.num_samples() == grad.num_samples());
+            DLIB_CASSERT(input_tensor.num_samples() == output_tensor.num_samples());
+            DLIB_CASSERT(output_tensor.nr() == 1 &&
+                         output_tensor.nc() == 1 &&
+                         output_tensor.k() == 1);
+            DLIB_CASSERT(grad.nr() == 1 &&
+                         grad.nc() == 1 &&
+                         grad.k() == 1);
 
-            const rectangle mapped_rect = input_layer(net).image_space_to_tensor_space(input_tensor, std::min(1.0,scale), rect);
 
-            // compute the detection window that we would use at this position.
-            tensor_p = center(mapped_rect);
-            rectangle det_window = centered_rect(tensor_p, options.detector_windows[det_idx].width,options.detector_windows[det_idx].height);
-            det_window = input_layer(net).tensor_space_to_image_space(input_tensor, det_window);
+            std::vector<double> rel_scores;
+            std::vector<double> nonrel_scores;
+            std::vector<long> rel_idx, nonrel_idx;
 
-            // make sure the rect can actually be represented by the image pyramid we are
-            // using.
-            if (box_intersection_over_union(rect, det_window) <= options.truth_match_iou_threshold)
+            const float* out_data = output_tensor.host();
+            float* g = grad.host_write_only();
+            for (long i = 0; i < output_tensor.num_samples(); ++i)
             {
-                std::cout << "Warning, ignoring object.  We encountered a truth rectangle with a width and height of " << rect.width() << " and " << rect.height() << ".  ";
-                std::cout << "The image pyramid and sliding windows can't output a rectangle of this shape.  ";
-                const double detector_area = options.detector_windows[det_idx].width*options.detector_windows[det_idx].height;
-                if (mapped_rect.area()/detector_area <= options.truth_match_iou_threshold)
+                const float y = *truth++;
+                if (y > 0)
                 {
-                    std::cout << "This is because the rectangle is smaller than the best matching detection window, which has a width ";
-                    std::cout << "and height of " << options.detector_windows[det_idx].width << " and " << options.detector_windows[det_idx].height << "." << std::endl;
+                    rel_scores.push_back(out_data[i]-y);
+                    rel_idx.push_back(i);
+                }
+                else if (y < 0)
+                {
+                    nonrel_scores.push_back(out_data[i]-y);
+                    nonrel_idx.push_back(i);
                 }
                 else
                 {
-                    std::cout << "This is either because (1) the final layer's features have too large of a stride across the image, limiting the possible locations the sliding window can search ";
-                    std::cout << "or (2) because the rectangle's aspect ratio is too different from the best matching detection window, ";
-                    std::cout << "which has a width and height of " << options.detector_windows[det_idx].width << " and " << options.detector_windows[det_idx].height << "." << std::endl;
+                    g[i] = 0;
                 }
-                return true;
             }
 
-            // now map through the CNN to the output layer.
-            tensor_p = input_tensor_to_output_tensor(net,tensor_p);
 
-            const tensor& output_tensor = net.get_output();
-            if (!get_rect(output_tensor).contains(tensor_p))
+            std::vector<unsigned long> rel_counts;
+            std::vector<unsigned long> nonrel_counts;
+            count_ranking_inversions(rel_scores, nonrel_scores, rel_counts, nonrel_counts);
+            const unsigned long total_pairs = rel_scores.size()*nonrel_scores.size();
+            DLIB_CASSERT(total_pairs > 0, "You can't give a ranking mini-batch that contains only one class.  Both classes must be represented.");
+            const double scale = 1.0/total_pairs;
+
+
+            double loss = 0;
+            for (unsigned long k = 0; k < rel_counts.size(); ++k)
             {
-                std::cout << "Warning, ignoring object.  We encountered a truth rectangle located at " << rect << " that is too close to the edge ";
-                std::cout << "of the image to be captured by the CNN features." << std::endl;
-                return true;
+                loss -= rel_counts[k]*rel_scores[k];
+                g[rel_idx[k]] = -1.0*rel_counts[k]*scale;
             }
 
-            return false;
+            for (unsigned long k = 0; k < nonrel_counts.size(); ++k)
+            {
+                loss += nonrel_counts[k]*nonrel_scores[k];
+                g[nonrel_idx[k]] = nonrel_counts[k]*scale;
+            }
+
+            return loss*scale;
         }
 
+        friend void serialize(const loss_ranking_& , std::ostream& out)
+        {
+            serialize("loss_ranking_", out);
+        }
 
-        bool overlaps_ignore_box (
-            const std::vector<mmod_rect>& boxes,
-            const rectangle& rect
-        ) const
+        friend void deserialize(loss_ranking_& , std::istream& in)
         {
-            for (auto&& b : boxes)
-            {
-                if (b.ignore && options.overlaps_ignore(b, rect))
-                    return true;
-            }
-            return false;
+            std::string version;
+            deserialize(version, in);
+            if (version != "loss_ranking_")
+                throw serialization_error("Unexpected version found while deserializing dlib::loss_ranking_.");
         }
 
-        std::pair<double,unsigned int> find_best_match(
-            const std::vector<mmod_rect>& boxes,
-            const std::vector<bool>& hit_truth_table,
-            const rectangle& rect,
-            const std::string& label
+        friend std::ostream& operator<<(std::ostream& out, const loss_ranking_& )
+        {
+            out << "loss_ranking";
+            return out;
+        }
+
+        friend void to_xml(const loss_ranking_& /*item*/, std::ostream& out)
+        {
+            out << "<loss_ranking/>\n";
+        }
+
+    };
+
+    template <typename SUBNET>
+    using loss_ranking = add_loss_layer<loss_ranking_, SUBNET>;
+
+// ----------------------------------------------------------------------------------------
+
+    class loss_mean_squared_
+    {
+    public:
+
+        typedef float training_label_type;
+        typedef float output_label_type;
+
+        template <
+            typename SUB_TYPE,
+            typename label_iterator
+            >
+        void to_label (
+            const tensor& input_tensor,
+            const SUB_TYPE& sub,
+            label_iterator iter
         ) const
         {
-            double match = 0;
-            unsigned int best_idx = 0;
+            DLIB_CASSERT(sub.sample_expansion_factor() == 1);
 
-            for (int allow_duplicate_hit = 0; allow_duplicate_hit <= 1 && match == 0; ++allow_duplicate_hit)
-            {
-                for (unsigned long i = 0; i < boxes.size(); ++i)
-                {
-                    if (boxes[i].ignore || boxes[i].label != label)
-                        continue;
-                    if (!allow_duplicate_hit && hit_truth_table[i])
-                        continue;
+            const tensor& output_tensor = sub.get_output();
 
-                    const double new_match = box_intersection_over_union(rect, boxes[i]);
-                    if (new_match > match)
-                    {
-                        match = new_match;
-                        best_idx = i;
-                    }
-                }
+            DLIB_CASSERT(output_tensor.nr() == 1 &&
+                         output_tensor.nc() == 1 &&
+                         output_tensor.k() == 1);
+            DLIB_CASSERT(input_tensor.num_samples() == output_tensor.num_samples());
+
+            const float* out_data = output_tensor.host();
+            for (long i = 0; i < output_tensor.num_samples(); ++i)
+            {
+                *iter++ = out_data[i];
             }
-
-            return std::make_pair(match,best_idx);
         }
 
-        std::pair<double,unsigned int> find_best_match(
-            const std::vector<mmod_rect>& boxes,
-            const rectangle& rect,
-            const size_t excluded_idx
+
+        template <
+            typename const_label_iterator,
+            typename SUBNET
+            >
+        double compute_loss_value_and_gradient (
+            const tensor& input_tensor,
+            const_label_iterator truth,
+            SUBNET& sub
         ) const
         {
-            double match = 0;
-            unsigned int best_idx = 0;
-            for (unsigned long i = 0; i < boxes.size(); ++i)
+            const tensor& output_tensor = sub.get_output();
+            tensor& grad = sub.get_gradient_input();
+
+            DLIB_CASSERT(sub.sample_expansion_factor() == 1);
+            DLIB_CASSERT(input_tensor.num_samples() != 0);
+            DLIB_CASSERT(input_tensor.num_samples()%sub.sample_expansion_factor() == 0);
+            DLIB_CASSERT(input_tensor.num_samples() == grad.num_samples());
+            DLIB_CASSERT(input_tensor.num_samples() == output_tensor.num_samples());
+            DLIB_CASSERT(output_tensor.nr() == 1 &&
+                         output_tensor.nc() == 1 &&
+                         output_tensor.k() == 1);
+            DLIB_CASSERT(grad.nr() == 1 &&
+                         grad.nc() == 1 &&
+                         grad.k() == 1);
+
+            // The loss we output is the average loss over the mini-batch.
+            const double scale = 1.0/output_tensor.num_samples();
+            double loss = 0;
+            float* g = grad.host_write_only();
+            const float* out_data = output_tensor.host();
+            for (long i = 0; i < output_tensor.num_samples(); ++i)
             {
-                if (boxes[i].ignore || excluded_idx == i)
-                    continue;
+                const float y = *truth++;
+                const float temp1 = y - out_data[i];
+                const float temp2 = scale*temp1;
+                loss += temp2*temp1;
+                g[i] = -temp2;
 
-                const double new_match = box_intersection_over_union(rect, boxes[i]);
-                if (new_match > match)
-                {
-                    match = new_match;
-                    best_idx = i;
-                }
             }
+            return loss;
+        }
 
-            return std::make_pair(match,best_idx);
+        friend void serialize(const loss_mean_squared_& , std::ostream& out)
+        {
+            serialize("loss_mean_squared_", out);
         }
 
-        template <typename T>
-        inline bool overlaps_any_box_nms (
-            const std::vector<T>& rects,
-            const rectangle& rect
-        ) const
+        friend void deserialize(loss_mean_squared_& , std::istream& in)
         {
-            for (auto&& r : rects)
-            {
-                if (options.overlaps_nms(r.rect, rect))
-                    return true;
-            }
-            return false;
+            std::string version;
+            deserialize(version, in);
+            if (version != "loss_mean_squared_")
+                throw serialization_error("Unexpected version found while deserializing dlib::loss_mean_squared_.");
         }
 
+        friend std::ostream& operator<<(std::ostream& out, const loss_mean_squared_& )
+        {
+            out << "loss_mean_squared";
+            return out;
+        }
 
-        mmod_options options;
+        friend void to_xml(const loss_mean_squared_& /*item*/, std::ostream& out)
+        {
+            out << "<loss_mean_squared/>\n";
+        }
 
     };
 
     template <typename SUBNET>
-    using loss_mmod = add_loss_layer<loss_mmod_, SUBNET>;
+    using loss_mean_squared = add_loss_layer<loss_mean_squared_, SUBNET>;
 
 // ----------------------------------------------------------------------------------------
 
-    class loss_metric_ 
+    class loss_mean_squared_weighted_
     {
     public:
 
-        typedef unsigned long training_label_type;
-        typedef matrix<float,0,1> output_label_type;
-
-        loss_metric_() = default;
-
-        loss_metric_(
-            float margin_,
-            float dist_thresh_
-        ) : margin(margin_), dist_thresh(dist_thresh_) 
-        {
-            DLIB_CASSERT(margin_ > 0);
-            DLIB_CASSERT(dist_thresh_ > 0);
-        }
+        typedef dlib::weighted_label<float> weighted_label;
+        typedef weighted_label training_label_type;
+        typedef float output_label_type;
 
         template <
             typename SUB_TYPE,
@@ -2032,35 +3293,16 @@ namespace dlib
             label_iterator iter
         ) const
         {
-            const tensor& output_tensor = sub.get_output();
-            DLIB_CASSERT(sub.sample_expansion_factor() == 1);
-            DLIB_CASSERT(input_tensor.num_samples() != 0);
-            DLIB_CASSERT(input_tensor.num_samples()%sub.sample_expansion_factor() == 0);
-            DLIB_CASSERT(input_tensor.num_samples() == output_tensor.num_samples());
-            DLIB_CASSERT(output_tensor.nr() == 1 && 
-                         output_tensor.nc() == 1);
-
-            const float* p = output_tensor.host();
-            for (long i = 0; i < output_tensor.num_samples(); ++i)
-            {
-                *iter = mat(p,output_tensor.k(),1);
-
-                ++iter;
-                p += output_tensor.k();
-            }
+            loss_mean_squared_().to_label(input_tensor, sub, iter);
         }
 
-
-        float get_margin() const { return margin; }
-        float get_distance_threshold() const { return dist_thresh; }
-
         template <
             typename const_label_iterator,
             typename SUBNET
             >
         double compute_loss_value_and_gradient (
             const tensor& input_tensor,
-            const_label_iterator truth, 
+            const_label_iterator truth,
             SUBNET& sub
         ) const
         {
@@ -2072,194 +3314,198 @@ namespace dlib
             DLIB_CASSERT(input_tensor.num_samples()%sub.sample_expansion_factor() == 0);
             DLIB_CASSERT(input_tensor.num_samples() == grad.num_samples());
             DLIB_CASSERT(input_tensor.num_samples() == output_tensor.num_samples());
-            DLIB_CASSERT(output_tensor.nr() == 1 && 
-                         output_tensor.nc() == 1);
-            DLIB_CASSERT(grad.nr() == 1 && 
-                         grad.nc() == 1);
+            DLIB_CASSERT(output_tensor.nr() == 1 &&
+                         output_tensor.nc() == 1 &&
+                         output_tensor.k() == 1);
+            DLIB_CASSERT(grad.nr() == 1 &&
+                         grad.nc() == 1 &&
+                         grad.k() == 1);
 
+            // The loss we output is the weighted average loss over the mini-batch.
+            const double scale = 1.0/output_tensor.num_samples();
+            double loss = 0;
+            float* g = grad.host_write_only();
+            const float* out_data = output_tensor.host();
+            for (long i = 0; i < output_tensor.num_samples(); ++i)
+            {
+                const auto wl = *truth++;
+                DLIB_CASSERT(wl.weight >= 0, "weight: " << wl.weight);
+                const float temp1 = wl.label - out_data[i];
+                const float temp2 = wl.weight*scale*temp1;
+                loss += temp2*temp1;
+                g[i] = -temp2;
+            }
+            return loss;
+        }
 
+        friend void serialize(const loss_mean_squared_weighted_& , std::ostream& out)
+        {
+            serialize("loss_mean_squared_weighted_", out);
+        }
 
-            temp.set_size(output_tensor.num_samples(), output_tensor.num_samples());
-            grad_mul.copy_size(temp);
+        friend void deserialize(loss_mean_squared_weighted_& , std::istream& in)
+        {
+            std::string version;
+            deserialize(version, in);
+            if (version != "loss_mean_squared_weighted_")
+                throw serialization_error("Unexpected version found while deserializing dlib::loss_mean_squared_weighted_.");
+        }
 
-            tt::gemm(0, temp, 1, output_tensor, false, output_tensor, true);
+        friend std::ostream& operator<<(std::ostream& out, const loss_mean_squared_weighted_& )
+        {
+            out << "loss_mean_squared_weighted";
+            return out;
+        }
 
+        friend void to_xml(const loss_mean_squared_weighted_& /*item*/, std::ostream& out)
+        {
+            out << "<loss_mean_squared_weighted/>\n";
+        }
 
-            std::vector<double> temp_threshs;
-            const float* d = temp.host();
-            double loss = 0;
-            double num_pos_samps = 0.0001;
-            double num_neg_samps = 0.0001;
-            for (long r = 0; r < temp.num_samples(); ++r)
-            {
-                auto xx = d[r*temp.num_samples() + r];
-                const auto x_label = *(truth + r);
-                for (long c = r+1; c < temp.num_samples(); ++c)
-                {
-                    const auto y_label = *(truth + c);
-                    if (x_label == y_label)
-                    {
-                        ++num_pos_samps;
-                    }
-                    else
-                    {
-                        ++num_neg_samps;
+    };
 
-                        // Figure out what distance threshold, when applied to the negative pairs,
-                        // causes there to be an equal number of positive and negative pairs.
-                        auto yy = d[c*temp.num_samples() + c];
-                        auto xy = d[r*temp.num_samples() + c];
-                        // compute the distance between x and y samples.
-                        auto d2 = xx + yy - 2*xy;
-                        if (d2 < 0)
-                            d2 = 0;
-                        temp_threshs.push_back(d2);
-                    }
-                }
-            }
-            // The whole objective function is multiplied by this to scale the loss
-            // relative to the number of things in the mini-batch.
-            const double scale = 0.5/num_pos_samps;
-            DLIB_CASSERT(num_pos_samps>=1, "Make sure each mini-batch contains both positive pairs and negative pairs");
-            DLIB_CASSERT(num_neg_samps>=1, "Make sure each mini-batch contains both positive pairs and negative pairs");
+    template <typename SUBNET>
+    using loss_mean_squared_weighted = add_loss_layer<loss_mean_squared_weighted_, SUBNET>;
 
-            std::sort(temp_threshs.begin(), temp_threshs.end());
-            const float neg_thresh = std::sqrt(temp_threshs[std::min(num_pos_samps,num_neg_samps)-1]);
+// ----------------------------------------------------------------------------------------
 
-            // loop over all the pairs of training samples and compute the loss and
-            // gradients.  Note that we only use the hardest negative pairs and that in
-            // particular we pick the number of negative pairs equal to the number of
-            // positive pairs so everything is balanced.
-            float* gm = grad_mul.host();
-            for (long r = 0; r < temp.num_samples(); ++r)
-            {
-                gm[r*temp.num_samples() + r] = 0;
-                const auto x_label = *(truth + r);
-                auto xx = d[r*temp.num_samples() + r];
-                for (long c = 0; c < temp.num_samples(); ++c)
-                {
-                    if (r==c)
-                        continue;
-                    const auto y_label = *(truth + c);
-                    auto yy = d[c*temp.num_samples() + c];
-                    auto xy = d[r*temp.num_samples() + c];
+    class loss_epsilon_insensitive_
+    {
+    public:
 
-                    // compute the distance between x and y samples.
-                    auto d2 = xx + yy - 2*xy;
-                    if (d2 <= 0)
-                        d2 = 0;
-                    else 
-                        d2 = std::sqrt(d2);
+        typedef float training_label_type;
+        typedef float output_label_type;
+
+        loss_epsilon_insensitive_() = default;
+        loss_epsilon_insensitive_(double eps) : eps(eps) 
+        {
+            DLIB_CASSERT(eps >= 0, "You can't set a negative error epsilon.");
+        }
+
+        double get_epsilon () const { return eps; }
+        void set_epsilon(double e)
+        {
+            DLIB_CASSERT(e >= 0, "You can't set a negative error epsilon.");
+            eps = e;
+        }
+
+        template <
+            typename SUB_TYPE,
+            typename label_iterator
+            >
+        void to_label (
+            const tensor& input_tensor,
+            const SUB_TYPE& sub,
+            label_iterator iter
+        ) const
+        {
+            DLIB_CASSERT(sub.sample_expansion_factor() == 1);
+
+            const tensor& output_tensor = sub.get_output();
 
-                    // It should be noted that the derivative of length(x-y) with respect
-                    // to the x vector is the unit vector (x-y)/length(x-y).  If you stare
-                    // at the code below long enough you will see that it's just an
-                    // application of this formula.
+            DLIB_CASSERT(output_tensor.nr() == 1 &&
+                         output_tensor.nc() == 1 &&
+                         output_tensor.k() == 1);
+            DLIB_CASSERT(input_tensor.num_samples() == output_tensor.num_samples());
 
-                    if (x_label == y_label)
-                    {
-                        // Things with the same label should have distances < dist_thresh between
-                        // them.  If not then we experience non-zero loss.
-                        if (d2 < dist_thresh-margin)
-                        {
-                            gm[r*temp.num_samples() + c] = 0;
-                        }
-                        else
-                        {
-                            loss += scale*(d2 - (dist_thresh-margin));
-                            gm[r*temp.num_samples() + r] += scale/d2;
-                            gm[r*temp.num_samples() + c] = -scale/d2;
-                        }
-                    }
-                    else
-                    {
-                        // Things with different labels should have distances > dist_thresh between
-                        // them.  If not then we experience non-zero loss.
-                        if (d2 > dist_thresh+margin || d2 > neg_thresh)
-                        {
-                            gm[r*temp.num_samples() + c] = 0;
-                        }
-                        else
-                        {
-                            loss += scale*((dist_thresh+margin) - d2);
-                            // don't divide by zero (or a really small number)
-                            d2 = std::max(d2, 0.001f);
-                            gm[r*temp.num_samples() + r] -= scale/d2;
-                            gm[r*temp.num_samples() + c] = scale/d2;
-                        }
-                    }
-                }
+            const float* out_data = output_tensor.host();
+            for (long i = 0; i < output_tensor.num_samples(); ++i)
+            {
+                *iter++ = out_data[i];
             }
+        }
 
 
-            tt::gemm(0, grad, 1, grad_mul, false, output_tensor, false); 
+        template <
+            typename const_label_iterator,
+            typename SUBNET
+            >
+        double compute_loss_value_and_gradient (
+            const tensor& input_tensor,
+            const_label_iterator truth,
+            SUBNET& sub
+        ) const
+        {
+            const tensor& output_tensor = sub.get_output();
+            tensor& grad = sub.get_gradient_input();
+
+            DLIB_CASSERT(sub.sample_expansion_factor() == 1);
+            DLIB_CASSERT(input_tensor.num_samples() != 0);
+            DLIB_CASSERT(input_tensor.num_samples()%sub.sample_expansion_factor() == 0);
+            DLIB_CASSERT(input_tensor.num_samples() == grad.num_samples());
+            DLIB_CASSERT(input_tensor.num_samples() == output_tensor.num_samples());
+            DLIB_CASSERT(output_tensor.nr() == 1 &&
+                         output_tensor.nc() == 1 &&
+                         output_tensor.k() == 1);
+            DLIB_CASSERT(grad.nr() == 1 &&
+                         grad.nc() == 1 &&
+                         grad.k() == 1);
 
+            // The loss we output is the average loss over the mini-batch.
+            const double scale = 1.0/output_tensor.num_samples();
+            double loss = 0;
+            float* g = grad.host_write_only();
+            const float* out_data = output_tensor.host();
+            for (long i = 0; i < output_tensor.num_samples(); ++i)
+            {
+                const float y = *truth++;
+                const float err = out_data[i]-y;
+                if (err > eps)
+                {
+                    loss += scale*(err-eps);
+                    g[i] = scale;
+                }
+                else if (err < -eps)
+                {
+                    loss += scale*(eps-err);
+                    g[i] = -scale;
+                }
+            }
             return loss;
         }
 
-        friend void serialize(const loss_metric_& item, std::ostream& out)
+        friend void serialize(const loss_epsilon_insensitive_& item, std::ostream& out)
         {
-            serialize("loss_metric_2", out);
-            serialize(item.margin, out);
-            serialize(item.dist_thresh, out);
+            serialize("loss_epsilon_insensitive_", out);
+            serialize(item.eps, out);
         }
 
-        friend void deserialize(loss_metric_& item, std::istream& in)
+        friend void deserialize(loss_epsilon_insensitive_& item, std::istream& in)
         {
             std::string version;
             deserialize(version, in);
-            if (version == "loss_metric_")
-            {
-                // These values used to be hard coded, so for this version of the metric
-                // learning loss we just use these values.
-                item.margin = 0.1f;
-                item.dist_thresh = 0.75f;
-                return;
-            }
-            else if (version == "loss_metric_2")
-            {
-                deserialize(item.margin, in);
-                deserialize(item.dist_thresh, in);
-            }
-            else
-            {
-                throw serialization_error("Unexpected version found while deserializing dlib::loss_metric_.  Instead found " + version);
-            }
+            if (version != "loss_epsilon_insensitive_")
+                throw serialization_error("Unexpected version found while deserializing dlib::loss_epsilon_insensitive_.");
+            deserialize(item.eps, in);
         }
 
-        friend std::ostream& operator<<(std::ostream& out, const loss_metric_& item )
+        friend std::ostream& operator<<(std::ostream& out, const loss_epsilon_insensitive_& item)
         {
-            out << "loss_metric (margin="<<item.margin<<", distance_threshold="<<item.dist_thresh<<")";
+            out << "loss_epsilon_insensitive epsilon: " << item.eps;
             return out;
         }
 
-        friend void to_xml(const loss_metric_& item, std::ostream& out)
+        friend void to_xml(const loss_epsilon_insensitive_& item, std::ostream& out)
         {
-            out << "<loss_metric margin='"<<item.margin<<"' distance_threshold='"<<item.dist_thresh<<"'/>\n";
+            out << "<loss_epsilon_insensitive_ epsilon='" << item.eps << "'/>\n";
         }
 
     private:
-        float margin = 0.04f;
-        float dist_thresh = 0.6f;
-
-
-        // These variables are only here to avoid being reallocated over and over in
-        // compute_loss_value_and_gradient()
-        mutable resizable_tensor temp, grad_mul;
+        double eps = 1;
 
     };
 
     template <typename SUBNET>
-    using loss_metric = add_loss_layer<loss_metric_, SUBNET>;
+    using loss_epsilon_insensitive = add_loss_layer<loss_epsilon_insensitive_, SUBNET>;
 
 // ----------------------------------------------------------------------------------------
 
-    class loss_ranking_
+    class loss_mean_squared_multioutput_
     {
     public:
 
-        typedef float training_label_type; // nominally +1/-1
-        typedef float output_label_type; // ranking score
+        typedef matrix<float> training_label_type;
+        typedef matrix<float> output_label_type;
 
         template <
             typename SUB_TYPE,
@@ -2276,14 +3522,14 @@ namespace dlib
             const tensor& output_tensor = sub.get_output();
 
             DLIB_CASSERT(output_tensor.nr() == 1 &&
-                         output_tensor.nc() == 1 &&
-                         output_tensor.k() == 1);
+                         output_tensor.nc() == 1)
             DLIB_CASSERT(input_tensor.num_samples() == output_tensor.num_samples());
 
             const float* out_data = output_tensor.host();
             for (long i = 0; i < output_tensor.num_samples(); ++i)
             {
-                *iter++ = out_data[i];
+                *iter++ = mat(out_data, output_tensor.k(), 1);
+                out_data += output_tensor.k();
             }
         }
 
@@ -2307,128 +3553,216 @@ namespace dlib
             DLIB_CASSERT(input_tensor.num_samples() == grad.num_samples());
             DLIB_CASSERT(input_tensor.num_samples() == output_tensor.num_samples());
             DLIB_CASSERT(output_tensor.nr() == 1 &&
-                         output_tensor.nc() == 1 &&
-                         output_tensor.k() == 1);
+                         output_tensor.nc() == 1);
             DLIB_CASSERT(grad.nr() == 1 &&
-                         grad.nc() == 1 &&
-                         grad.k() == 1);
-
-
-            std::vector<double> rel_scores;
-            std::vector<double> nonrel_scores;
-            std::vector<long> rel_idx, nonrel_idx;
+                         grad.nc() == 1);
+            DLIB_CASSERT(grad.k() == output_tensor.k());
+            const long k = output_tensor.k();
+            for (long idx = 0; idx < output_tensor.num_samples(); ++idx)
+            {
+                const_label_iterator truth_matrix_ptr = (truth + idx);
+                DLIB_CASSERT((*truth_matrix_ptr).nr() == k &&
+                             (*truth_matrix_ptr).nc() == 1);
+            }
 
-            const float* out_data = output_tensor.host();
+            // The loss we output is the average loss over the mini-batch.
+            const double scale = 1.0/output_tensor.num_samples();
+            double loss = 0;
             float* g = grad.host_write_only();
+            const float* out_data = output_tensor.host();
+            matrix<float> ytrue;
             for (long i = 0; i < output_tensor.num_samples(); ++i)
             {
-                const float y = *truth++;
-                if (y > 0)
-                {
-                    rel_scores.push_back(out_data[i]-y);
-                    rel_idx.push_back(i);
-                }
-                else if (y < 0)
-                {
-                    nonrel_scores.push_back(out_data[i]-y);
-                    nonrel_idx.push_back(i);
-                }
-                else
+                ytrue = *truth++;
+                for (long j = 0; j < output_tensor.k(); ++j)
                 {
-                    g[i] = 0;
+                    const float y = ytrue(j, 0);
+                    const float temp1 = y - *out_data++;
+                    const float temp2 = scale*temp1;
+                    loss += temp2*temp1;
+                    *g = -temp2;
+                    ++g;
                 }
+
             }
+            return loss;
+        }
 
+        friend void serialize(const loss_mean_squared_multioutput_& , std::ostream& out)
+        {
+            serialize("loss_mean_squared_multioutput_", out);
+        }
 
-            std::vector<unsigned long> rel_counts;
-            std::vector<unsigned long> nonrel_counts;
-            count_ranking_inversions(rel_scores, nonrel_scores, rel_counts, nonrel_counts);
-            const unsigned long total_pairs = rel_scores.size()*nonrel_scores.size();
-            DLIB_CASSERT(total_pairs > 0, "You can't give a ranking mini-batch that contains only one class.  Both classes must be represented.");
-            const double scale = 1.0/total_pairs;
+        friend void deserialize(loss_mean_squared_multioutput_& , std::istream& in)
+        {
+            std::string version;
+            deserialize(version, in);
+            if (version != "loss_mean_squared_multioutput_")
+                throw serialization_error("Unexpected version found while deserializing dlib::loss_mean_squared_.");
+        }
+
+        friend std::ostream& operator<<(std::ostream& out, const loss_mean_squared_multioutput_& )
+        {
+            out << "loss_mean_squared_multioutput";
+            return out;
+        }
+
+        friend void to_xml(const loss_mean_squared_multioutput_& /*item*/, std::ostream& out)
+        {
+            out << "<loss_mean_squared_multioutput/>\n";
+        }
+
+    };
+
+    template <typename SUBNET>
+    using loss_mean_squared_multioutput = add_loss_layer<loss_mean_squared_multioutput_, SUBNET>;
+
+// ----------------------------------------------------------------------------------------
+
+    class loss_mean_squared_multioutput_weighted_
+    {
+    public:
+
+        typedef dlib::weighted_label<float> weighted_label;
+        typedef matrix<weighted_label> training_label_type;
+        typedef matrix<float> output_label_type;
 
+        template <
+            typename SUB_TYPE,
+            typename label_iterator
+            >
+        void to_label (
+            const tensor& input_tensor,
+            const SUB_TYPE& sub,
+            label_iterator iter
+        ) const
+        {
+            loss_mean_squared_multioutput_().to_label(input_tensor, sub, iter);
+        }
+
+        template <
+            typename const_label_iterator,
+            typename SUBNET
+            >
+        double compute_loss_value_and_gradient (
+            const tensor& input_tensor,
+            const_label_iterator truth,
+            SUBNET& sub
+        ) const
+        {
+            const tensor& output_tensor = sub.get_output();
+            tensor& grad = sub.get_gradient_input();
 
-            double loss = 0;
-            for (unsigned long k = 0; k < rel_counts.size(); ++k)
+            DLIB_CASSERT(sub.sample_expansion_factor() == 1);
+            DLIB_CASSERT(input_tensor.num_samples() != 0);
+            DLIB_CASSERT(input_tensor.num_samples()%sub.sample_expansion_factor() == 0);
+            DLIB_CASSERT(input_tensor.num_samples() == grad.num_samples());
+            DLIB_CASSERT(input_tensor.num_samples() == output_tensor.num_samples());
+            DLIB_CASSERT(output_tensor.nr() == 1 &&
+                         output_tensor.nc() == 1);
+            DLIB_CASSERT(grad.nr() == 1 &&
+                         grad.nc() == 1);
+            DLIB_CASSERT(grad.k() == output_tensor.k());
+            const long k = output_tensor.k();
+            for (long idx = 0; idx < output_tensor.num_samples(); ++idx)
             {
-                loss -= rel_counts[k]*rel_scores[k];
-                g[rel_idx[k]] = -1.0*rel_counts[k]*scale;
+                const_label_iterator truth_matrix_ptr = (truth + idx);
+                DLIB_CASSERT((*truth_matrix_ptr).nr() == k &&
+                             (*truth_matrix_ptr).nc() == 1);
             }
 
-            for (unsigned long k = 0; k < nonrel_counts.size(); ++k)
+            // The loss we output is the weighted average loss over the mini-batch.
+            // Outputs with a weight of 0 don't contribute to the loss or gradient, so they
+            // can be used for missing targets.
+            const double scale = 1.0/output_tensor.num_samples();
+            double loss = 0;
+            float* g = grad.host_write_only();
+            const float* out_data = output_tensor.host();
+            for (long i = 0; i < output_tensor.num_samples(); ++i, ++truth)
             {
-                loss += nonrel_counts[k]*nonrel_scores[k];
-                g[nonrel_idx[k]] = nonrel_counts[k]*scale;
+                for (long j = 0; j < k; ++j)
+                {
+                    const weighted_label& wl = (*truth)(j);
+                    DLIB_CASSERT(wl.weight >= 0, "weight: " << wl.weight);
+                    const float temp1 = wl.label - *out_data++;
+                    const float temp2 = wl.weight*scale*temp1;
+                    loss += temp2*temp1;
+                    *g++ = -temp2;
+                }
             }
-
-            return loss*scale;
+            return loss;
         }
 
-        friend void serialize(const loss_ranking_& , std::ostream& out)
+        friend void serialize(const loss_mean_squared_multioutput_weighted_& , std::ostream& out)
         {
-            serialize("loss_ranking_", out);
+            serialize("loss_mean_squared_multioutput_weighted_", out);
         }
 
-        friend void deserialize(loss_ranking_& , std::istream& in)
+        friend void deserialize(loss_mean_squared_multioutput_weighted_& , std::istream& in)
         {
             std::string version;
             deserialize(version, in);
-            if (version != "loss_ranking_")
-                throw serialization_error("Unexpected version found while deserializing dlib::loss_ranking_.");
+            if (version != "loss_mean_squared_multioutput_weighted_")
+                throw serialization_error("Unexpected version found while deserializing dlib::loss_mean_squared_multioutput_weighted_.");
         }
 
-        friend std::ostream& operator<<(std::ostream& out, const loss_ranking_& )
+        friend std::ostream& operator<<(std::ostream& out, const loss_mean_squared_multioutput_weighted_& )
         {
-            out << "loss_ranking";
+            out << "loss_mean_squared_multioutput_weighted";
             return out;
         }
 
-        friend void to_xml(const loss_ranking_& /*item*/, std::ostream& out)
+        friend void to_xml(const loss_mean_squared_multioutput_weighted_& /*item*/, std::ostream& out)
         {
-            out << "<loss_ranking/>\n";
+            out << "<loss_mean_squared_multioutput_weighted/>\n";
         }
 
     };
 
     template <typename SUBNET>
-    using loss_ranking = add_loss_layer<loss_ranking_, SUBNET>;
+    using loss_mean_squared_multioutput_weighted = add_loss_layer<loss_mean_squared_multioutput_weighted_, SUBNET>;
 
 // ----------------------------------------------------------------------------------------
 
-    class loss_mean_squared_
+    class loss_binary_log_per_pixel_
     {
     public:
 
-        typedef float training_label_type;
-        typedef float output_label_type;
+        typedef matrix<float> training_label_type;
+        typedef matrix<float> output_label_type;
 
         template <
             typename SUB_TYPE,
             typename label_iterator
             >
-        void to_label (
+        static void to_label (
             const tensor& input_tensor,
             const SUB_TYPE& sub,
             label_iterator iter
-        ) const
+        )
         {
             DLIB_CASSERT(sub.sample_expansion_factor() == 1);
 
             const tensor& output_tensor = sub.get_output();
 
-            DLIB_CASSERT(output_tensor.nr() == 1 &&
-                         output_tensor.nc() == 1 &&
-                         output_tensor.k() == 1);
+            DLIB_CASSERT(output_tensor.k() == 1);
             DLIB_CASSERT(input_tensor.num_samples() == output_tensor.num_samples());
 
-            const float* out_data = output_tensor.host();
-            for (long i = 0; i < output_tensor.num_samples(); ++i)
+            const float* const out_data = output_tensor.host();
+
+            for (long i = 0; i < output_tensor.num_samples(); ++i, ++iter) 
             {
-                *iter++ = out_data[i];
+                iter->set_size(output_tensor.nr(), output_tensor.nc());
+                for (long r = 0; r < output_tensor.nr(); ++r) 
+                {
+                    for (long c = 0; c < output_tensor.nc(); ++c) 
+                    {
+                        iter->operator()(r, c) = out_data[tensor_index(output_tensor, i, 0, r, c)];
+                    }
+                }
             }
         }
 
-
         template <
             typename const_label_iterator,
             typename SUBNET
@@ -2447,108 +3781,87 @@ namespace dlib
             DLIB_CASSERT(input_tensor.num_samples()%sub.sample_expansion_factor() == 0);
             DLIB_CASSERT(input_tensor.num_samples() == grad.num_samples());
             DLIB_CASSERT(input_tensor.num_samples() == output_tensor.num_samples());
-            DLIB_CASSERT(output_tensor.nr() == 1 &&
-                         output_tensor.nc() == 1 &&
-                         output_tensor.k() == 1);
-            DLIB_CASSERT(grad.nr() == 1 &&
-                         grad.nc() == 1 &&
-                         grad.k() == 1);
-
-            // The loss we output is the average loss over the mini-batch.
-            const double scale = 1.0/output_tensor.num_samples();
-            double loss = 0;
-            float* g = grad.host_write_only();
-            const float* out_data = output_tensor.host();
-            for (long i = 0; i < output_tensor.num_samples(); ++i)
+            DLIB_CASSERT(output_tensor.k() == 1);
+            DLIB_CASSERT(output_tensor.nr() == grad.nr() &&
+                         output_tensor.nc() == grad.nc() &&
+                         output_tensor.k() == grad.k());
+            for (long idx = 0; idx < output_tensor.num_samples(); ++idx)
             {
-                const float y = *truth++;
-                const float temp1 = y - out_data[i];
-                const float temp2 = scale*temp1;
-                loss += temp2*temp1;
-                g[i] = -temp2;
-
+                const_label_iterator truth_matrix_ptr = (truth + idx);
+                DLIB_CASSERT(truth_matrix_ptr->nr() == output_tensor.nr() &&
+                             truth_matrix_ptr->nc() == output_tensor.nc(),
+                             "truth size = " << truth_matrix_ptr->nr() << " x " << truth_matrix_ptr->nc() << ", "
+                             "output size = " << output_tensor.nr() << " x " << output_tensor.nc());
             }
+
+            double loss;
+#ifdef DLIB_USE_CUDA
+            cuda_compute(truth, output_tensor, grad, loss);
+#else
+            cpu_compute(truth, output_tensor, grad, loss);
+#endif
             return loss;
         }
 
-        friend void serialize(const loss_mean_squared_& , std::ostream& out)
+        friend void serialize(const loss_binary_log_per_pixel_& , std::ostream& out)
         {
-            serialize("loss_mean_squared_", out);
+            serialize("loss_binary_log_per_pixel_", out);
         }
 
-        friend void deserialize(loss_mean_squared_& , std::istream& in)
+        friend void deserialize(loss_binary_log_per_pixel_& , std::istream& in)
         {
             std::string version;
             deserialize(version, in);
-            if (version != "loss_mean_squared_")
-                throw serialization_error("Unexpected version found while deserializing dlib::loss_mean_squared_.");
+            if (version != "loss_binary_log_per_pixel_")
+                throw serialization_error("Unexpected version found while deserializing dlib::loss_binary_log_per_pixel_.");
         }
 
-        friend std::ostream& operator<<(std::ostream& out, const loss_mean_squared_& )
+        friend std::ostream& operator<<(std::ostream& out, const loss_binary_log_per_pixel_& )
         {
-            out << "loss_mean_squared";
+            out << "loss_binary_log_per_pixel";
             return out;
         }
 
-        friend void to_xml(const loss_mean_squared_& /*item*/, std::ostream& out)
+        friend void to_xml(const loss_binary_log_per_pixel_& /*item*/, std::ostream& out)
         {
-            out << "<loss_mean_squared/>\n";
+            out << "<loss_binary_log_per_pixel/>\n";
         }
 
+    private:
+
+#ifdef DLIB_USE_CUDA
+        cuda::compute_loss_binary_log_per_pixel cuda_compute;
+#else
+        cpu::compute_loss_binary_log_per_pixel cpu_compute;
+#endif
     };
 
     template <typename SUBNET>
-    using loss_mean_squared = add_loss_layer<loss_mean_squared_, SUBNET>;
+    using loss_binary_log_per_pixel = add_loss_layer<loss_binary_log_per_pixel_, SUBNET>;
 
 // ----------------------------------------------------------------------------------------
 
-    class loss_epsilon_insensitive_
+    class loss_binary_log_per_pixel_weighted_
     {
     public:
 
-        typedef float training_label_type;
-        typedef float output_label_type;
-
-        loss_epsilon_insensitive_() = default;
-        loss_epsilon_insensitive_(double eps) : eps(eps) 
-        {
-            DLIB_CASSERT(eps >= 0, "You can't set a negative error epsilon.");
-        }
-
-        double get_epsilon () const { return eps; }
-        void set_epsilon(double e)
-        {
-            DLIB_CASSERT(e >= 0, "You can't set a negative error epsilon.");
-            eps = e;
-        }
+        typedef dlib::weighted_label<float> weighted_label;
+        typedef matrix<weighted_label> training_label_type;
+        typedef matrix<float> output_label_type;
 
         template <
             typename SUB_TYPE,
             typename label_iterator
             >
-        void to_label (
+        static void to_label (
             const tensor& input_tensor,
             const SUB_TYPE& sub,
             label_iterator iter
-        ) const
+        )
         {
-            DLIB_CASSERT(sub.sample_expansion_factor() == 1);
-
-            const tensor& output_tensor = sub.get_output();
-
-            DLIB_CASSERT(output_tensor.nr() == 1 &&
-                         output_tensor.nc() == 1 &&
-                         output_tensor.k() == 1);
-            DLIB_CASSERT(input_tensor.num_samples() == output_tensor.num_samples());
-
-            const float* out_data = output_tensor.host();
-            for (long i = 0; i < output_tensor.num_samples(); ++i)
-            {
-                *iter++ = out_data[i];
-            }
+            loss_binary_log_per_pixel_::to_label(input_tensor, sub, iter);
         }
 
-
         template <
             typename const_label_iterator,
             typename SUBNET
@@ -2567,106 +3880,157 @@ namespace dlib
             DLIB_CASSERT(input_tensor.num_samples()%sub.sample_expansion_factor() == 0);
             DLIB_CASSERT(input_tensor.num_samples() == grad.num_samples());
             DLIB_CASSERT(input_tensor.num_samples() == output_tensor.num_samples());
-            DLIB_CASSERT(output_tensor.nr() == 1 &&
-                         output_tensor.nc() == 1 &&
-                         output_tensor.k() == 1);
-            DLIB_CASSERT(grad.nr() == 1 &&
-                         grad.nc() == 1 &&
-                         grad.k() == 1);
+            DLIB_CASSERT(output_tensor.k() == 1);
+            DLIB_CASSERT(output_tensor.nr() == grad.nr() &&
+                         output_tensor.nc() == grad.nc() &&
+                         output_tensor.k() == grad.k());
+            for (long idx = 0; idx < output_tensor.num_samples(); ++idx)
+            {
+                const_label_iterator truth_matrix_ptr = (truth + idx);
+                DLIB_CASSERT(truth_matrix_ptr->nr() == output_tensor.nr() &&
+                             truth_matrix_ptr->nc() == output_tensor.nc(),
+                             "truth size = " << truth_matrix_ptr->nr() << " x " << truth_matrix_ptr->nc() << ", "
+                             "output size = " << output_tensor.nr() << " x " << output_tensor.nc());
+            }
 
-            // The loss we output is the average loss over the mini-batch.
-            const double scale = 1.0/output_tensor.num_samples();
+            tt::sigmoid(grad, output_tensor);
+            // The loss we output is the weighted average loss over the mini-batch, and also
+            // over each element of the matrix output.
+            const double scale = 1.0/(output_tensor.num_samples()*output_tensor.nr()*output_tensor.nc());
             double loss = 0;
-            float* g = grad.host_write_only();
-            const float* out_data = output_tensor.host();
-            for (long i = 0; i < output_tensor.num_samples(); ++i)
+            float* const g = grad.host();
+            const float* const out_data = output_tensor.host();
+            for (long i = 0; i < output_tensor.num_samples(); ++i, ++truth)
             {
-                const float y = *truth++;
-                const float err = out_data[i]-y;
-                if (err > eps)
-                {
-                    loss += scale*(err-eps);
-                    g[i] = scale;
-                }
-                else if (err < -eps)
+                for (long r = 0; r < output_tensor.nr(); ++r)
                 {
-                    loss += scale*(eps-err);
-                    g[i] = -scale;
+                    for (long c = 0; c < output_tensor.nc(); ++c)
+                    {
+                        const weighted_label& wl = truth->operator()(r, c);
+                        DLIB_CASSERT(wl.weight >= 0, "weight: " << wl.weight);
+                        // Just like in loss_binary_log_per_pixel_, the magnitude of the label
+                        // also scales the loss, so this is that loss with labels y*weight.
+                        const float y = wl.label*wl.weight;
+                        const size_t idx = tensor_index(output_tensor, i, 0, r, c);
+
+                        if (y > 0.f)
+                        {
+                            const float temp = log1pexp(-out_data[idx]);
+                            loss += y*scale*temp;
+                            g[idx] = y*scale*(g[idx]-1);
+                        }
+                        else if (y < 0.f)
+                        {
+                            const float temp = -(-out_data[idx]-log1pexp(-out_data[idx]));
+                            loss += -y*scale*temp;
+                            g[idx] = -y*scale*g[idx];
+                        }
+                        else
+                        {
+                            g[idx] = 0.f;
+                        }
+                    }
                 }
             }
             return loss;
         }
 
-        friend void serialize(const loss_epsilon_insensitive_& item, std::ostream& out)
+        friend void serialize(const loss_binary_log_per_pixel_weighted_& , std::ostream& out)
         {
-            serialize("loss_epsilon_insensitive_", out);
-            serialize(item.eps, out);
+            serialize("loss_binary_log_per_pixel_weighted_", out);
         }
 
-        friend void deserialize(loss_epsilon_insensitive_& item, std::istream& in)
+        friend void deserialize(loss_binary_log_per_pixel_weighted_& , std::istream& in)
         {
             std::string version;
             deserialize(version, in);
-            if (version != "loss_epsilon_insensitive_")
-                throw serialization_error("Unexpected version found while deserializing dlib::loss_epsilon_insensitive_.");
-            deserialize(item.eps, in);
+            if (version != "loss_binary_log_per_pixel_weighted_")
+                throw serialization_error("Unexpected version found while deserializing dlib::loss_binary_log_per_pixel_weighted_.");
         }
 
-        friend std::ostream& operator<<(std::ostream& out, const loss_epsilon_insensitive_& item)
+        friend std::ostream& operator<<(std::ostream& out, const loss_binary_log_per_pixel_weighted_& )
         {
-            out << "loss_epsilon_insensitive epsilon: " << item.eps;
+            out << "loss_binary_log_per_pixel_weighted";
             return out;
         }
 
-        friend void to_xml(const loss_epsilon_insensitive_& item, std::ostream& out)
+        friend void to_xml(const loss_binary_log_per_pixel_weighted_& /*item*/, std::ostream& out)
         {
-            out << "<loss_epsilon_insensitive_ epsilon='" << item.eps << "'/>\n";
+            out << "<loss_binary_log_per_pixel_weighted/>\n";
         }
 
-    private:
-        double eps = 1;
-
     };
 
     template <typename SUBNET>
-    using loss_epsilon_insensitive = add_loss_layer<loss_epsilon_insensitive_, SUBNET>;
+    using loss_binary_log_per_pixel_weighted = add_loss_layer<loss_binary_log_per_pixel_weighted_, SUBNET>;
 
 // ----------------------------------------------------------------------------------------
 
-    class loss_mean_squared_multioutput_
+    class loss_multiclass_log_per_pixel_
     {
     public:
 
-        typedef matrix<float> training_label_type;
-        typedef matrix<float> output_label_type;
+        // In semantic segmentation, if you don't know the ground-truth of some pixel,
+        // set the label of that pixel to this value. When you do so, the pixel will be
+        // ignored when computing gradients.
+        static const uint16_t label_to_ignore = std::numeric_limits<uint16_t>::max();
+
+
+        // In semantic segmentation, 65535 classes ought to be enough for anybody.
+        typedef matrix<uint16_t> training_label_type;
+        typedef matrix<uint16_t> output_label_type;
 
         template <
             typename SUB_TYPE,
             typename label_iterator
             >
-        void to_label (
+        static void to_label (
             const tensor& input_tensor,
             const SUB_TYPE& sub,
             label_iterator iter
-        ) const
+        )
         {
             DLIB_CASSERT(sub.sample_expansion_factor() == 1);
 
             const tensor& output_tensor = sub.get_output();
 
-            DLIB_CASSERT(output_tensor.nr() == 1 &&
-                         output_tensor.nc() == 1)
+            DLIB_CASSERT(output_tensor.k() >= 1); // Note that output_tensor.k() should match the number of labels.
+            DLIB_CASSERT(output_tensor.k() < std::numeric_limits<uint16_t>::max());
             DLIB_CASSERT(input_tensor.num_samples() == output_tensor.num_samples());
 
-            const float* out_data = output_tensor.host();
-            for (long i = 0; i < output_tensor.num_samples(); ++i)
+            const float* const out_data = output_tensor.host();
+
+            // The index of the largest output for each element is the label.
+            const auto find_label = [&](long sample, long r, long c) 
             {
-                *iter++ = mat(out_data, output_tensor.k(), 1);
-                out_data += output_tensor.k();
+                uint16_t label = 0;
+                float max_value = out_data[tensor_index(output_tensor, sample, 0, r, c)];
+                for (long k = 1; k < output_tensor.k(); ++k) 
+                {
+                    const float value = out_data[tensor_index(output_tensor, sample, k, r, c)];
+                    if (value > max_value) 
+                    {
+                        label = static_cast<uint16_t>(k);
+                        max_value = value;
+                    }
+                }
+                return label;
+            };
+
+            for (long i = 0; i < output_tensor.num_samples(); ++i, ++iter) 
+            {
+                iter->set_size(output_tensor.nr(), output_tensor.nc());
+                for (long r = 0; r < output_tensor.nr(); ++r) 
+                {
+                    for (long c = 0; c < output_tensor.nc(); ++c) 
+                    {
+                        // The index of the largest output for this element is the label.
+                        iter->operator()(r, c) = find_label(i, r, c);
+                    }
+                }
             }
         }
 
-
         template <
             typename const_label_iterator,
             typename SUBNET
@@ -2685,79 +4049,75 @@ namespace dlib
             DLIB_CASSERT(input_tensor.num_samples()%sub.sample_expansion_factor() == 0);
             DLIB_CASSERT(input_tensor.num_samples() == grad.num_samples());
             DLIB_CASSERT(input_tensor.num_samples() == output_tensor.num_samples());
-            DLIB_CASSERT(output_tensor.nr() == 1 &&
-                         output_tensor.nc() == 1);
-            DLIB_CASSERT(grad.nr() == 1 &&
-                         grad.nc() == 1);
-            DLIB_CASSERT(grad.k() == output_tensor.k());
-            const long k = output_tensor.k();
+            DLIB_CASSERT(output_tensor.k() >= 1);
+            DLIB_CASSERT(output_tensor.k() < std::numeric_limits<uint16_t>::max());
+            DLIB_CASSERT(output_tensor.nr() == grad.nr() &&
+                         output_tensor.nc() == grad.nc() &&
+                         output_tensor.k() == grad.k());
             for (long idx = 0; idx < output_tensor.num_samples(); ++idx)
             {
                 const_label_iterator truth_matrix_ptr = (truth + idx);
-                DLIB_CASSERT((*truth_matrix_ptr).nr() == k &&
-                             (*truth_matrix_ptr).nc() == 1);
+                DLIB_CASSERT(truth_matrix_ptr->nr() == output_tensor.nr() &&
+                             truth_matrix_ptr->nc() == output_tensor.nc(),
+                             "truth size = " << truth_matrix_ptr->nr() << " x " << truth_matrix_ptr->nc() << ", "
+                             "output size = " << output_tensor.nr() << " x " << output_tensor.nc());
             }
 
-            // The loss we output is the average loss over the mini-batch.
-            const double scale = 1.0/output_tensor.num_samples();
-            double loss = 0;
-            float* g = grad.host_write_only();
-            const float* out_data = output_tensor.host();
-            matrix<float> ytrue;
-            for (long i = 0; i < output_tensor.num_samples(); ++i)
-            {
-                ytrue = *truth++;
-                for (long j = 0; j < output_tensor.k(); ++j)
-                {
-                    const float y = ytrue(j, 0);
-                    const float temp1 = y - *out_data++;
-                    const float temp2 = scale*temp1;
-                    loss += temp2*temp1;
-                    *g = -temp2;
-                    ++g;
-                }
 
-            }
+            double loss;
+#ifdef DLIB_USE_CUDA
+            cuda_compute(truth, output_tensor, grad, loss);
+#else
+            cpu_compute(truth, output_tensor, grad, loss);
+#endif
             return loss;
         }
 
-        friend void serialize(const loss_mean_squared_multioutput_& , std::ostream& out)
+        friend void serialize(const loss_multiclass_log_per_pixel_& , std::ostream& out)
         {
-            serialize("loss_mean_squared_multioutput_", out);
+            serialize("loss_multiclass_log_per_pixel_", out);
         }
 
-        friend void deserialize(loss_mean_squared_multioutput_& , std::istream& in)
+        friend void deserialize(loss_multiclass_log_per_pixel_& , std::istream& in)
         {
             std::string version;
             deserialize(version, in);
-            if (version != "loss_mean_squared_multioutput_")
-                throw serialization_error("Unexpected version found while deserializing dlib::loss_mean_squared_.");
+            if (version != "loss_multiclass_log_per_pixel_")
+                throw serialization_error("Unexpected version found while deserializing dlib::loss_multiclass_log_per_pixel_.");
         }
 
-        friend std::ostream& operator<<(std::ostream& out, const loss_mean_squared_multioutput_& )
+        friend std::ostream& operator<<(std::ostream& out, const loss_multiclass_log_per_pixel_& )
         {
-            out << "loss_mean_squared_multioutput";
+            out << "loss_multiclass_log_per_pixel";
             return out;
         }
 
-        friend void to_xml(const loss_mean_squared_multioutput_& /*item*/, std::ostream& out)
+        friend void to_xml(const loss_multiclass_log_per_pixel_& /*item*/, std::ostream& out)
         {
-            out << "<loss_mean_squared_multioutput/>\n";
+            out << "<loss_multiclass_log_per_pixel/>\n";
         }
 
+    private:
+
+#ifdef DLIB_USE_CUDA
+        cuda::compute_loss_multiclass_log_per_pixel cuda_compute;
+#else
+        cpu::compute_loss_multiclass_log_per_pixel cpu_compute;
+#endif
     };
 
     template <typename SUBNET>
-    using loss_mean_squared_multioutput = add_loss_layer<loss_mean_squared_multioutput_, SUBNET>;
+    using loss_multiclass_log_per_pixel = add_loss_layer<loss_multiclass_log_per_pixel_, SUBNET>;
 
 // ----------------------------------------------------------------------------------------
 
-    class loss_binary_log_per_pixel_
+    class loss_multiclass_log_per_pixel_weighted_
     {
     public:
 
-        typedef matrix<float> training_label_type;
-        typedef matrix<float> output_label_type;
+        typedef dlib::weighted_label<uint16_t> weighted_label;
+        typedef matrix<weighted_label> training_label_type;
+        typedef matrix<uint16_t> output_label_type;
 
         template <
             typename SUB_TYPE,
@@ -2769,26 +4129,7 @@ namespace dlib
             label_iterator iter
         )
         {
-            DLIB_CASSERT(sub.sample_expansion_factor() == 1);
-
-            const tensor& output_tensor = sub.get_output();
-
-            DLIB_CASSERT(output_tensor.k() == 1);
-            DLIB_CASSERT(input_tensor.num_samples() == output_tensor.num_samples());
-
-            const float* const out_data = output_tensor.host();
-
-            for (long i = 0; i < output_tensor.num_samples(); ++i, ++iter) 
-            {
-                iter->set_size(output_tensor.nr(), output_tensor.nc());
-                for (long r = 0; r < output_tensor.nr(); ++r) 
-                {
-                    for (long c = 0; c < output_tensor.nc(); ++c) 
-                    {
-                        iter->operator()(r, c) = out_data[tensor_index(output_tensor, i, 0, r, c)];
-                    }
-                }
-            }
+            loss_multiclass_log_per_pixel_::to_label(input_tensor, sub, iter);
         }
 
         template <
@@ -2809,7 +4150,8 @@ namespace dlib
             DLIB_CASSERT(input_tensor.num_samples()%sub.sample_expansion_factor() == 0);
             DLIB_CASSERT(input_tensor.num_samples() == grad.num_samples());
             DLIB_CASSERT(input_tensor.num_samples() == output_tensor.num_samples());
-            DLIB_CASSERT(output_tensor.k() == 1);
+            DLIB_CASSERT(output_tensor.k() >= 1);
+            DLIB_CASSERT(output_tensor.k() < std::numeric_limits<uint16_t>::max());
             DLIB_CASSERT(output_tensor.nr() == grad.nr() &&
                          output_tensor.nc() == grad.nc() &&
                          output_tensor.k() == grad.k());
@@ -2831,58 +4173,76 @@ namespace dlib
             return loss;
         }
 
-        friend void serialize(const loss_binary_log_per_pixel_& , std::ostream& out)
+        friend void serialize(const loss_multiclass_log_per_pixel_weighted_& , std::ostream& out)
         {
-            serialize("loss_binary_log_per_pixel_", out);
+            serialize("loss_multiclass_log_per_pixel_weighted_", out);
         }
 
-        friend void deserialize(loss_binary_log_per_pixel_& , std::istream& in)
+        friend void deserialize(loss_multiclass_log_per_pixel_weighted_& , std::istream& in)
         {
             std::string version;
             deserialize(version, in);
-            if (version != "loss_binary_log_per_pixel_")
-                throw serialization_error("Unexpected version found while deserializing dlib::loss_binary_log_per_pixel_.");
+            if (version != "loss_multiclass_log_per_pixel_weighted_")
+                throw serialization_error("Unexpected version found while deserializing dlib::loss_multiclass_log_per_pixel_weighted_.");
         }
 
-        friend std::ostream& operator<<(std::ostream& out, const loss_binary_log_per_pixel_& )
+        friend std::ostream& operator<<(std::ostream& out, const loss_multiclass_log_per_pixel_weighted_& )
         {
-            out << "loss_binary_log_per_pixel";
+            out << "loss_multiclass_log_per_pixel_weighted";
             return out;
         }
 
-        friend void to_xml(const loss_binary_log_per_pixel_& /*item*/, std::ostream& out)
+        friend void to_xml(const loss_multiclass_log_per_pixel_weighted_& /*item*/, std::ostream& out)
         {
-            out << "<loss_binary_log_per_pixel/>\n";
+            out << "<loss_multiclass_log_per_pixel_weighted/>\n";
         }
 
     private:
 
 #ifdef DLIB_USE_CUDA
-        cuda::compute_loss_binary_log_per_pixel cuda_compute;
+        cuda::compute_loss_multiclass_log_per_pixel_weighted cuda_compute;
 #else
-        cpu::compute_loss_binary_log_per_pixel cpu_compute;
+        cpu::compute_loss_multiclass_log_per_pixel_weighted cpu_compute;
 #endif
+
     };
 
     template <typename SUBNET>
-    using loss_binary_log_per_pixel = add_loss_layer<loss_binary_log_per_pixel_, SUBNET>;
+    using loss_multiclass_log_per_pixel_weighted = add_loss_layer<loss_multiclass_log_per_pixel_weighted_, SUBNET>;
 
 // ----------------------------------------------------------------------------------------
 
-    class loss_multiclass_log_per_pixel_
+    class loss_dice_per_pixel_
     {
     public:
 
-        // In semantic segmentation, if you don't know the ground-truth of some pixel,
-        // set the label of that pixel to this value. When you do so, the pixel will be
-        // ignored when computing gradients.
+        // Pixels with this label are ignored, just like with loss_multiclass_log_per_pixel_.
         static const uint16_t label_to_ignore = std::numeric_limits<uint16_t>::max();
 
-
-        // In semantic segmentation, 65535 classes ought to be enough for anybody.
         typedef matrix<uint16_t> training_label_type;
         typedef matrix<uint16_t> output_label_type;
 
+        loss_dice_per_pixel_() = default;
+
+        loss_dice_per_pixel_(
+            double alpha_,
+            double beta_,
+            double smoothing_ = 1
+        ) : alpha(alpha_), beta(beta_), smoothing(smoothing_)
+        {
+            DLIB_CASSERT(alpha >= 0 && beta >= 0);
+            DLIB_CASSERT(smoothing > 0);
+        }
+
+        double get_alpha (
+        ) const { return alpha; }
+
+        double get_beta (
+        ) const { return beta; }
+
+        double get_smoothing (
+        ) const { return smoothing; }
+
         template <
             typename SUB_TYPE,
             typename label_iterator
@@ -2896,30 +4256,16 @@ namespace dlib
             DLIB_CASSERT(sub.sample_expansion_factor() == 1);
 
             const tensor& output_tensor = sub.get_output();
+            if (output_tensor.k() > 1)
+            {
+                loss_multiclass_log_per_pixel_::to_label(input_tensor, sub, iter);
+                return;
+            }
 
-            DLIB_CASSERT(output_tensor.k() >= 1); // Note that output_tensor.k() should match the number of labels.
-            DLIB_CASSERT(output_tensor.k() < std::numeric_limits<uint16_t>::max());
+            // With a single channel the output is the log odds of the pixel being in the
+            // foreground, i.e. label 1.
             DLIB_CASSERT(input_tensor.num_samples() == output_tensor.num_samples());
-
             const float* const out_data = output_tensor.host();
-
-            // The index of the largest output for each element is the label.
-            const auto find_label = [&](long sample, long r, long c) 
-            {
-                uint16_t label = 0;
-                float max_value = out_data[tensor_index(output_tensor, sample, 0, r, c)];
-                for (long k = 1; k < output_tensor.k(); ++k) 
-                {
-                    const float value = out_data[tensor_index(output_tensor, sample, k, r, c)];
-                    if (value > max_value) 
-                    {
-                        label = static_cast<uint16_t>(k);
-                        max_value = value;
-                    }
-                }
-                return label;
-            };
-
             for (long i = 0; i < output_tensor.num_samples(); ++i, ++iter) 
             {
                 iter->set_size(output_tensor.nr(), output_tensor.nc());
@@ -2927,8 +4273,7 @@ namespace dlib
                 {
                     for (long c = 0; c < output_tensor.nc(); ++c) 
                     {
-                        // The index of the largest output for this element is the label.
-                        iter->operator()(r, c) = find_label(i, r, c);
+                        iter->operator()(r, c) = out_data[tensor_index(output_tensor, i, 0, r, c)] > 0 ? 1 : 0;
                     }
                 }
             }
@@ -2966,75 +4311,171 @@ namespace dlib
                              "output size = " << output_tensor.nr() << " x " << output_tensor.nc());
             }
 
+            // A single channel is a binary problem where only the foreground counts.
+            // Otherwise each channel is a class and the per class scores are averaged.
+            const bool binary = output_tensor.k() == 1;
+            if (binary)
+                tt::sigmoid(grad, output_tensor);
+            else
+                tt::softmax(grad, output_tensor);
 
-            double loss;
-#ifdef DLIB_USE_CUDA
-            cuda_compute(truth, output_tensor, grad, loss);
-#else
-            cpu_compute(truth, output_tensor, grad, loss);
-#endif
+            const long plane_size = output_tensor.nr()*output_tensor.nc();
+            const double scale = 1.0/output_tensor.num_samples()/output_tensor.k();
+            float* const g = grad.host();
+            dprob.set_size(output_tensor.k(), plane_size);
+            double loss = 0;
+            for (long n = 0; n < output_tensor.num_samples(); ++n, ++truth)
+            {
+                const auto& labels = *truth;
+                float* const p = g + n*output_tensor.k()*plane_size;
+                auto is_class = [&](long k, long i) 
+                {
+                    const uint16_t y = labels(i/output_tensor.nc(), i%output_tensor.nc());
+                    DLIB_CASSERT(y == label_to_ignore || (binary ? y <= 1 : y < output_tensor.k()),
+                        "y: " << y << ", output_tensor.k(): " << output_tensor.k());
+                    return binary ? y == 1 : y == k;
+                };
+
+                // The Tversky index of each class is (TP+s)/(TP + alpha*FP + beta*FN + s),
+                // where the counts are computed with the predicted probabilities.  We
+                // minimize the average of 1 minus the index.  dprob holds the derivative
+                // of the loss with respect to each probability.
+                for (long k = 0; k < output_tensor.k(); ++k)
+                {
+                    double tp = 0, fp = 0, fn = 0;
+                    for (long i = 0; i < plane_size; ++i)
+                    {
+                        if (labels(i/output_tensor.nc(), i%output_tensor.nc()) == label_to_ignore)
+                            continue;
+                        const double prob = p[k*plane_size + i];
+                        if (is_class(k, i))
+                        {
+                            tp += prob;
+                            fn += 1-prob;
+                        }
+                        else
+                        {
+                            fp += prob;
+                        }
+                    }
+                    const double num = tp + smoothing;
+                    const double den = tp + alpha*fp + beta*fn + smoothing;
+                    loss += scale*(1 - num/den);
+                    for (long i = 0; i < plane_size; ++i)
+                    {
+                        if (labels(i/output_tensor.nc(), i%output_tensor.nc()) == label_to_ignore)
+                            dprob(k,i) = 0;
+                        else if (is_class(k, i))
+                            dprob(k,i) = -scale*(den - num*(1-beta))/(den*den);
+                        else
+                            dprob(k,i) = scale*num*alpha/(den*den);
+                    }
+                }
+
+                // Now push the derivatives through the sigmoid or softmax.
+                for (long i = 0; i < plane_size; ++i)
+                {
+                    if (binary)
+                    {
+                        p[i] = dprob(0,i)*p[i]*(1-p[i]);
+                        continue;
+                    }
+                    double dot = 0;
+                    for (long k = 0; k < output_tensor.k(); ++k)
+                        dot += dprob(k,i)*p[k*plane_size + i];
+                    for (long k = 0; k < output_tensor.k(); ++k)
+                        p[k*plane_size + i] = p[k*plane_size + i]*(dprob(k,i) - dot);
+                }
+            }
             return loss;
         }
 
-        friend void serialize(const loss_multiclass_log_per_pixel_& , std::ostream& out)
+        friend void serialize(const loss_dice_per_pixel_& item, std::ostream& out)
         {
-            serialize("loss_multiclass_log_per_pixel_", out);
+            serialize("loss_dice_per_pixel_", out);
+            serialize(item.alpha, out);
+            serialize(item.beta, out);
+            serialize(item.smoothing, out);
         }
 
-        friend void deserialize(loss_multiclass_log_per_pixel_& , std::istream& in)
+        friend void deserialize(loss_dice_per_pixel_& item, std::istream& in)
         {
             std::string version;
             deserialize(version, in);
-            if (version != "loss_multiclass_log_per_pixel_")
-                throw serialization_error("Unexpected version found while deserializing dlib::loss_multiclass_log_per_pixel_.");
+            if (version != "loss_dice_per_pixel_")
+                throw serialization_error("Unexpected version found while deserializing dlib::loss_dice_per_pixel_.");
+            deserialize(item.alpha, in);
+            deserialize(item.beta, in);
+            deserialize(item.smoothing, in);
         }
 
-        friend std::ostream& operator<<(std::ostream& out, const loss_multiclass_log_per_pixel_& )
+        friend std::ostream& operator<<(std::ostream& out, const loss_dice_per_pixel_& item)
         {
-            out << "loss_multiclass_log_per_pixel";
+            out << "loss_dice_per_pixel (alpha=" << item.alpha << ", beta=" << item.beta 
+                << ", smoothing=" << item.smoothing << ")";
             return out;
         }
 
-        friend void to_xml(const loss_multiclass_log_per_pixel_& /*item*/, std::ostream& out)
+        friend void to_xml(const loss_dice_per_pixel_& item, std::ostream& out)
         {
-            out << "<loss_multiclass_log_per_pixel/>\n";
+            out << "<loss_dice_per_pixel alpha='" << item.alpha << "' beta='" << item.beta
+                << "' smoothing='" << item.smoothing << "'/>\n";
         }
 
     private:
+        double alpha = 0.5;
+        double beta = 0.5;
+        double smoothing = 1;
 
-#ifdef DLIB_USE_CUDA
-        cuda::compute_loss_multiclass_log_per_pixel cuda_compute;
-#else
-        cpu::compute_loss_multiclass_log_per_pixel cpu_compute;
-#endif
+        // This is only here to avoid being reallocated over and over in
+        // compute_loss_value_and_gradient()
+        mutable matrix<double> dprob;
     };
 
     template <typename SUBNET>
-    using loss_multiclass_log_per_pixel = add_loss_layer<loss_multiclass_log_per_pixel_, SUBNET>;
+    using loss_dice_per_pixel = add_loss_layer<loss_dice_per_pixel_, SUBNET>;
 
 // ----------------------------------------------------------------------------------------
 
-    class loss_multiclass_log_per_pixel_weighted_
+    class loss_mean_squared_per_pixel_
     {
     public:
 
-        typedef dlib::weighted_label<uint16_t> weighted_label;
-        typedef matrix<weighted_label> training_label_type;
-        typedef matrix<uint16_t> output_label_type;
+        typedef matrix<float> training_label_type;
+        typedef matrix<float> output_label_type;
 
         template <
             typename SUB_TYPE,
             typename label_iterator
             >
-        static void to_label (
+        void to_label (
             const tensor& input_tensor,
             const SUB_TYPE& sub,
             label_iterator iter
-        )
+        ) const
         {
-            loss_multiclass_log_per_pixel_::to_label(input_tensor, sub, iter);
+            DLIB_CASSERT(sub.sample_expansion_factor() == 1);
+
+            const tensor& output_tensor = sub.get_output();
+
+            DLIB_CASSERT(output_tensor.k() == 1, "output k = " << output_tensor.k());
+            DLIB_CASSERT(input_tensor.num_samples() == output_tensor.num_samples());
+
+            const float* out_data = output_tensor.host();
+            for (long i = 0; i < output_tensor.num_samples(); ++i, ++iter)
+            {
+                iter->set_size(output_tensor.nr(), output_tensor.nc());
+                for (long r = 0; r < output_tensor.nr(); ++r)
+                {
+                    for (long c = 0; c < output_tensor.nc(); ++c)
+                    {
+                        iter->operator()(r, c) = out_data[tensor_index(output_tensor, i, 0, r, c)];
+                    }
+                }
+            }
         }
 
+
         template <
             typename const_label_iterator,
             typename SUBNET
@@ -3050,76 +4491,83 @@ namespace dlib
 
             DLIB_CASSERT(sub.sample_expansion_factor() == 1);
             DLIB_CASSERT(input_tensor.num_samples() != 0);
-            DLIB_CASSERT(input_tensor.num_samples()%sub.sample_expansion_factor() == 0);
+            DLIB_CASSERT(input_tensor.num_samples() % sub.sample_expansion_factor() == 0);
             DLIB_CASSERT(input_tensor.num_samples() == grad.num_samples());
             DLIB_CASSERT(input_tensor.num_samples() == output_tensor.num_samples());
             DLIB_CASSERT(output_tensor.k() >= 1);
             DLIB_CASSERT(output_tensor.k() < std::numeric_limits<uint16_t>::max());
             DLIB_CASSERT(output_tensor.nr() == grad.nr() &&
-                         output_tensor.nc() == grad.nc() &&
-                         output_tensor.k() == grad.k());
+                output_tensor.nc() == grad.nc() &&
+                output_tensor.k() == grad.k());
             for (long idx = 0; idx < output_tensor.num_samples(); ++idx)
             {
                 const_label_iterator truth_matrix_ptr = (truth + idx);
                 DLIB_CASSERT(truth_matrix_ptr->nr() == output_tensor.nr() &&
-                             truth_matrix_ptr->nc() == output_tensor.nc(),
-                             "truth size = " << truth_matrix_ptr->nr() << " x " << truth_matrix_ptr->nc() << ", "
-                             "output size = " << output_tensor.nr() << " x " << output_tensor.nc());
+                    truth_matrix_ptr->nc() == output_tensor.nc(),
+                    "truth size = " << truth_matrix_ptr->nr() << " x " << truth_matrix_ptr->nc() << ", "
+                    "output size = " << output_tensor.nr() << " x " << output_tensor.nc());
             }
 
-            double loss;
-#ifdef DLIB_USE_CUDA
-            cuda_compute(truth, output_tensor, grad, loss);
-#else
-            cpu_compute(truth, output_tensor, grad, loss);
-#endif
+            // The loss we output is the average loss over the mini-batch, and also over each element of the matrix output.
+            const double scale = 1.0 / (output_tensor.num_samples() * output_tensor.nr() * output_tensor.nc());
+            double loss = 0;
+            float* const g = grad.host();
+            const float* out_data = output_tensor.host();
+            for (long i = 0; i < output_tensor.num_samples(); ++i, ++truth)
+            {
+                for (long r = 0; r < output_tensor.nr(); ++r)
+                {
+                    for (long c = 0; c < output_tensor.nc(); ++c)
+                    {
+                        const float y = truth->operator()(r, c);
+                        const size_t idx = tensor_index(output_tensor, i, 0, r, c);
+                        const float temp1 = y - out_data[idx];
+                        const float temp2 = scale*temp1;
+                        loss += temp2*temp1;
+                        g[idx] = -temp2;
+                    }
+                }
+            }
             return loss;
         }
 
-        friend void serialize(const loss_multiclass_log_per_pixel_weighted_& , std::ostream& out)
+        friend void serialize(const loss_mean_squared_per_pixel_& , std::ostream& out)
         {
-            serialize("loss_multiclass_log_per_pixel_weighted_", out);
+            serialize("loss_mean_squared_per_pixel_", out);
         }
 
-        friend void deserialize(loss_multiclass_log_per_pixel_weighted_& , std::istream& in)
+        friend void deserialize(loss_mean_squared_per_pixel_& , std::istream& in)
         {
             std::string version;
             deserialize(version, in);
-            if (version != "loss_multiclass_log_per_pixel_weighted_")
-                throw serialization_error("Unexpected version found while deserializing dlib::loss_multiclass_log_per_pixel_weighted_.");
+            if (version != "loss_mean_squared_per_pixel_")
+                throw serialization_error("Unexpected version found while deserializing dlib::loss_mean_squared_per_pixel_.");
         }
 
-        friend std::ostream& operator<<(std::ostream& out, const loss_multiclass_log_per_pixel_weighted_& )
+        friend std::ostream& operator<<(std::ostream& out, const loss_mean_squared_per_pixel_& )
         {
-            out << "loss_multiclass_log_per_pixel_weighted";
+            out << "loss_mean_squared_per_pixel";
             return out;
         }
 
-        friend void to_xml(const loss_multiclass_log_per_pixel_weighted_& /*item*/, std::ostream& out)
+        friend void to_xml(const loss_mean_squared_per_pixel_& /*item*/, std::ostream& out)
         {
-            out << "<loss_multiclass_log_per_pixel_weighted/>\n";
+            out << "<loss_mean_squared_per_pixel/>\n";
         }
 
-    private:
-
-#ifdef DLIB_USE_CUDA
-        cuda::compute_loss_multiclass_log_per_pixel_weighted cuda_compute;
-#else
-        cpu::compute_loss_multiclass_log_per_pixel_weighted cpu_compute;
-#endif
-
     };
 
     template <typename SUBNET>
-    using loss_multiclass_log_per_pixel_weighted = add_loss_layer<loss_multiclass_log_per_pixel_weighted_, SUBNET>;
+    using loss_mean_squared_per_pixel = add_loss_layer<loss_mean_squared_per_pixel_, SUBNET>;
 
 // ----------------------------------------------------------------------------------------
 
-    class loss_mean_squared_per_pixel_
+    class loss_mean_squared_per_pixel_weighted_
     {
     public:
 
-        typedef matrix<float> training_label_type;
+        typedef dlib::weighted_label<float> weighted_label;
+        typedef matrix<weighted_label> training_label_type;
         typedef matrix<float> output_label_type;
 
         template <
@@ -3132,28 +4580,9 @@ namespace dlib
             label_iterator iter
         ) const
         {
-            DLIB_CASSERT(sub.sample_expansion_factor() == 1);
-
-            const tensor& output_tensor = sub.get_output();
-
-            DLIB_CASSERT(output_tensor.k() == 1, "output k = " << output_tensor.k());
-            DLIB_CASSERT(input_tensor.num_samples() == output_tensor.num_samples());
-
-            const float* out_data = output_tensor.host();
-            for (long i = 0; i < output_tensor.num_samples(); ++i, ++iter)
-            {
-                iter->set_size(output_tensor.nr(), output_tensor.nc());
-                for (long r = 0; r < output_tensor.nr(); ++r)
-                {
-                    for (long c = 0; c < output_tensor.nc(); ++c)
-                    {
-                        iter->operator()(r, c) = out_data[tensor_index(output_tensor, i, 0, r, c)];
-                    }
-                }
-            }
+            loss_mean_squared_per_pixel_().to_label(input_tensor, sub, iter);
         }
 
-
         template <
             typename const_label_iterator,
             typename SUBNET
@@ -3172,8 +4601,7 @@ namespace dlib
             DLIB_CASSERT(input_tensor.num_samples() % sub.sample_expansion_factor() == 0);
             DLIB_CASSERT(input_tensor.num_samples() == grad.num_samples());
             DLIB_CASSERT(input_tensor.num_samples() == output_tensor.num_samples());
-            DLIB_CASSERT(output_tensor.k() >= 1);
-            DLIB_CASSERT(output_tensor.k() < std::numeric_limits<uint16_t>::max());
+            DLIB_CASSERT(output_tensor.k() == 1);
             DLIB_CASSERT(output_tensor.nr() == grad.nr() &&
                 output_tensor.nc() == grad.nc() &&
                 output_tensor.k() == grad.k());
@@ -3186,7 +4614,8 @@ namespace dlib
                     "output size = " << output_tensor.nr() << " x " << output_tensor.nc());
             }
 
-            // The loss we output is the average loss over the mini-batch, and also over each element of the matrix output.
+            // The loss we output is the weighted average loss over the mini-batch, and also
+            // over each element of the matrix output.
             const double scale = 1.0 / (output_tensor.num_samples() * output_tensor.nr() * output_tensor.nc());
             double loss = 0;
             float* const g = grad.host();
@@ -3197,10 +4626,11 @@ namespace dlib
                 {
                     for (long c = 0; c < output_tensor.nc(); ++c)
                     {
-                        const float y = truth->operator()(r, c);
+                        const weighted_label& wl = truth->operator()(r, c);
+                        DLIB_CASSERT(wl.weight >= 0, "weight: " << wl.weight);
                         const size_t idx = tensor_index(output_tensor, i, 0, r, c);
-                        const float temp1 = y - out_data[idx];
-                        const float temp2 = scale*temp1;
+                        const float temp1 = wl.label - out_data[idx];
+                        const float temp2 = wl.weight*scale*temp1;
                         loss += temp2*temp1;
                         g[idx] = -temp2;
                     }
@@ -3209,34 +4639,34 @@ namespace dlib
             return loss;
         }
 
-        friend void serialize(const loss_mean_squared_per_pixel_& , std::ostream& out)
+        friend void serialize(const loss_mean_squared_per_pixel_weighted_& , std::ostream& out)
         {
-            serialize("loss_mean_squared_per_pixel_", out);
+            serialize("loss_mean_squared_per_pixel_weighted_", out);
         }
 
-        friend void deserialize(loss_mean_squared_per_pixel_& , std::istream& in)
+        friend void deserialize(loss_mean_squared_per_pixel_weighted_& , std::istream& in)
         {
             std::string version;
             deserialize(version, in);
-            if (version != "loss_mean_squared_per_pixel_")
-                throw serialization_error("Unexpected version found while deserializing dlib::loss_mean_squared_per_pixel_.");
+            if (version != "loss_mean_squared_per_pixel_weighted_")
+                throw serialization_error("Unexpected version found while deserializing dlib::loss_mean_squared_per_pixel_weighted_.");
         }
 
-        friend std::ostream& operator<<(std::ostream& out, const loss_mean_squared_per_pixel_& )
+        friend std::ostream& operator<<(std::ostream& out, const loss_mean_squared_per_pixel_weighted_& )
         {
-            out << "loss_mean_squared_per_pixel";
+            out << "loss_mean_squared_per_pixel_weighted";
             return out;
         }
 
-        friend void to_xml(const loss_mean_squared_per_pixel_& /*item*/, std::ostream& out)
+        friend void to_xml(const loss_mean_squared_per_pixel_weighted_& /*item*/, std::ostream& out)
         {
-            out << "<loss_mean_squared_per_pixel/>\n";
+            out << "<loss_mean_squared_per_pixel_weighted/>\n";
         }
 
     };
 
     template <typename SUBNET>
-    using loss_mean_squared_per_pixel = add_loss_layer<loss_mean_squared_per_pixel_, SUBNET>;
+    using loss_mean_squared_per_pixel_weighted = add_loss_layer<loss_mean_squared_per_pixel_weighted_, SUBNET>;
 
 // ----------------------------------------------------------------------------------------
 
@@ -3363,6 +4793,205 @@ namespace dlib
     template <long num_channels, typename SUBNET>
     using loss_mean_squared_per_channel_and_pixel = add_loss_layer<loss_mean_squared_per_channel_and_pixel_<num_channels>, SUBNET>;
 
+// ----------------------------------------------------------------------------------------
+
+    class loss_keypoint_heatmap_
+    {
+    public:
+
+        typedef full_object_detection training_label_type;
+        typedef full_object_detection output_label_type;
+
+        loss_keypoint_heatmap_() = default;
+
+        explicit loss_keypoint_heatmap_(
+            double sigma_,
+            double presence_threshold_ = 0.25
+        ) : sigma(sigma_), presence_threshold(presence_threshold_)
+        {
+            DLIB_CASSERT(sigma > 0);
+        }
+
+        double get_sigma (
+        ) const { return sigma; }
+
+        double get_presence_threshold (
+        ) const { return presence_threshold; }
+
+        template <
+            typename SUB_TYPE,
+            typename label_iterator
+            >
+        void to_label (
+            const tensor& input_tensor,
+            const SUB_TYPE& sub,
+            label_iterator iter
+        ) const
+        {
+            const tensor& output_tensor = sub.get_output();
+            DLIB_CASSERT(sub.sample_expansion_factor() == 1);
+            DLIB_CASSERT(input_tensor.num_samples() == output_tensor.num_samples());
+
+            const long nr = output_tensor.nr();
+            const long nc = output_tensor.nc();
+            const float* out_data = output_tensor.host();
+            for (long i = 0; i < output_tensor.num_samples(); ++i, ++iter)
+            {
+                std::vector<point> parts(output_tensor.k(), OBJECT_PART_NOT_PRESENT);
+                for (long k = 0; k < output_tensor.k(); ++k, out_data += nr*nc)
+                {
+                    const long idx = std::max_element(out_data, out_data + nr*nc) - out_data;
+                    if (out_data[idx] < presence_threshold)
+                        continue;
+
+                    // Refine the location of the peak by fitting a parabola to it, and its
+                    // neighbors, along each axis.
+                    const long r = idx/nc;
+                    const long c = idx%nc;
+                    double dx = 0, dy = 0;
+                    if (0 < c && c+1 < nc)
+                        dx = peak_offset(out_data[idx-1], out_data[idx], out_data[idx+1]);
+                    if (0 < r && r+1 < nr)
+                        dy = peak_offset(out_data[idx-nc], out_data[idx], out_data[idx+nc]);
+
+                    const dpoint p = output_tensor_to_input_tensor(sub, dpoint(c+dx, r+dy));
+                    parts[k] = point(std::round(p.x()), std::round(p.y()));
+                }
+                *iter = full_object_detection(get_rect(input_tensor), parts);
+            }
+        }
+
+        template <
+            typename const_label_iterator,
+            typename SUBNET
+            >
+        double compute_loss_value_and_gradient (
+            const tensor& input_tensor,
+            const_label_iterator truth,
+            SUBNET& sub
+        ) const
+        {
+            const tensor& output_tensor = sub.get_output();
+            tensor& grad = sub.get_gradient_input();
+
+            DLIB_CASSERT(sub.sample_expansion_factor() == 1);
+            DLIB_CASSERT(input_tensor.num_samples() != 0);
+            DLIB_CASSERT(input_tensor.num_samples()%sub.sample_expansion_factor() == 0);
+            DLIB_CASSERT(input_tensor.num_samples() == grad.num_samples());
+            DLIB_CASSERT(input_tensor.num_samples() == output_tensor.num_samples());
+            DLIB_CASSERT(have_same_dimensions(output_tensor, grad));
+
+            const long nr = output_tensor.nr();
+            const long nc = output_tensor.nc();
+            // The loss we output is the squared error summed over each heatmap and
+            // averaged over the samples and parts.
+            const double scale = 1.0/(output_tensor.num_samples()*output_tensor.k());
+            double loss = 0;
+            const float* out_data = output_tensor.host();
+            float* g = grad.host();
+            for (long i = 0; i < output_tensor.num_samples(); ++i, ++truth)
+            {
+                const full_object_detection& det = *truth;
+                DLIB_CASSERT(det.num_parts() == (unsigned long)output_tensor.k(),
+                    "det.num_parts(): " << det.num_parts() << ", output_tensor.k(): " << output_tensor.k());
+                for (long k = 0; k < output_tensor.k(); ++k)
+                {
+                    // The target heatmap is a Gaussian centered on the part, or all 0s if
+                    // the part isn't present.  The Gaussian is separable, so we only need
+                    // to evaluate it along each axis.
+                    gx.set_size(nc);
+                    gy.set_size(nr);
+                    if (det.part(k) == OBJECT_PART_NOT_PRESENT)
+                    {
+                        gx = 0;
+                        gy = 0;
+                    }
+                    else
+                    {
+                        const dpoint p = input_tensor_to_output_tensor(sub, dpoint(det.part(k)));
+                        for (long c = 0; c < nc; ++c)
+                            gx(c) = std::exp(-(c-p.x())*(c-p.x())/(2*sigma*sigma));
+                        for (long r = 0; r < nr; ++r)
+                            gy(r) = std::exp(-(r-p.y())*(r-p.y())/(2*sigma*sigma));
+                    }
+
+                    for (long r = 0; r < nr; ++r)
+                    {
+                        for (long c = 0; c < nc; ++c)
+                        {
+                            const double diff = *out_data++ - gy(r)*gx(c);
+                            loss += scale*diff*diff;
+                            *g++ = 2*scale*diff;
+                        }
+                    }
+                }
+            }
+            return loss;
+        }
+
+        friend void serialize(const loss_keypoint_heatmap_& item, std::ostream& out)
+        {
+            serialize("loss_keypoint_heatmap_", out);
+            serialize(item.sigma, out);
+            serialize(item.presence_threshold, out);
+        }
+
+        friend void deserialize(loss_keypoint_heatmap_& item, std::istream& in)
+        {
+            std::string version;
+            deserialize(version, in);
+            if (version != "loss_keypoint_heatmap_")
+                throw serialization_error("Unexpected version found while deserializing dlib::loss_keypoint_heatmap_.");
+            deserialize(item.sigma, in);
+            deserialize(item.presence_threshold, in);
+        }
+
+        friend std::ostream& operator<<(std::ostream& out, const loss_keypoint_heatmap_& item)
+        {
+            out << "loss_keypoint_heatmap (sigma=" << item.sigma
+                << ", presence_threshold=" << item.presence_threshold << ")";
+            return out;
+        }
+
+        friend void to_xml(const loss_keypoint_heatmap_& item, std::ostream& out)
+        {
+            out << "<loss_keypoint_heatmap sigma='" << item.sigma
+                << "' presence_threshold='" << item.presence_threshold << "'/>\n";
+        }
+
+    private:
+
+        static double peak_offset (
+            double left,
+            double center,
+            double right
+        )
+        {
+            // The heatmaps are trained to be Gaussians, which are parabolas in log space,
+            // so we fit the parabola to the logs of the values when we can.
+            if (left > 0 && right > 0)
+            {
+                left = std::log(left);
+                center = std::log(center);
+                right = std::log(right);
+            }
+            const double den = left - 2*center + right;
+            if (den >= 0)
+                return 0;
+            return put_in_range(-0.5, 0.5, 0.5*(left - right)/den);
+        }
+
+        double sigma = 2;
+        double presence_threshold = 0.25;
+
+        // These are only here to avoid being reallocated over and over in
+        // compute_loss_value_and_gradient()
+        mutable matrix<double,0,1> gx, gy;
+    };
+
+    template <typename SUBNET>
+    using loss_keypoint_heatmap = add_loss_layer<loss_keypoint_heatmap_, SUBNET>;
+
 // ----------------------------------------------------------------------------------------
 
     class loss_dot_
@@ -3519,12 +5148,14 @@ namespace dlib
         double lambda_cls = 1.0;
         double gamma_obj = 0.0;
         double gamma_cls = 0.0;
+        bool use_angle_regression = false;
+        double lambda_angle = 1.0;
 
     };
 
     inline void serialize(const yolo_options& item, std::ostream& out)
     {
-        int version = 2;
+        int version = 3;
         serialize(version, out);
         serialize(item.anchors, out);
         serialize(item.labels, out);
@@ -3537,13 +5168,15 @@ namespace dlib
         serialize(item.lambda_cls, out);
         serialize(item.gamma_obj, out);
         serialize(item.gamma_cls, out);
+        serialize(item.use_angle_regression, out);
+        serialize(item.lambda_angle, out);
     }
 
     inline void deserialize(yolo_options& item, std::istream& in)
     {
         int version = 0;
         deserialize(version, in);
-        if (!(1 <= version && version <= 2))
+        if (!(1 <= version && version <= 3))
             throw serialization_error("Unexpected version found while deserializing dlib::yolo_options.");
         deserialize(item.anchors, in);
         deserialize(item.labels, in);
@@ -3559,6 +5192,13 @@ namespace dlib
             deserialize(item.gamma_obj, in);
             deserialize(item.gamma_cls, in);
         }
+        item.use_angle_regression = yolo_options().use_angle_regression;
+        item.lambda_angle = yolo_options().lambda_angle;
+        if (version >= 3)
+        {
+            deserialize(item.use_angle_regression, in);
+            deserialize(item.lambda_angle, in);
+        }
     }
 
     inline std::ostream& operator<<(std::ostream& out, const std::map<int, std::vector<yolo_options::anchor_box_details>>& anchors)
@@ -3584,6 +5224,15 @@ namespace dlib
 
     namespace impl
     {
+        inline long yolo_num_features (
+            const yolo_options& options
+        )
+        {
+            // The box coordinates, the objectness, the class scores and, if we predict
+            // angles, the cosine and sine of the angle.
+            return 5 + options.labels.size() + (options.use_angle_regression ? 2 : 0);
+        }
+
         template <template <typename> class TAG_TYPE, template <typename> class... TAG_TYPES>
         struct yolo_helper_impl
         {
@@ -3649,11 +5298,11 @@ namespace dlib
             {
                 const auto& anchors = options.anchors.at(tag_id<TAG_TYPE>::id);
                 const tensor& output_tensor = layer<TAG_TYPE>(sub).get_output();
-                DLIB_CASSERT(static_cast<size_t>(output_tensor.k()) == anchors.size() * (options.labels.size() + 5));
+                DLIB_CASSERT(static_cast<size_t>(output_tensor.k()) == anchors.size() * yolo_num_features(options));
                 const auto stride_x = static_cast<double>(input_tensor.nc()) / output_tensor.nc();
                 const auto stride_y = static_cast<double>(input_tensor.nr()) / output_tensor.nr();
                 const long num_feats = output_tensor.k() / anchors.size();
-                const long num_classes = num_feats - 5;
+                const long num_classes = options.labels.size();
                 const float* const out_data = output_tensor.host();
 
                 for (size_t a = 0; a < anchors.size(); ++a)
@@ -3675,6 +5324,13 @@ namespace dlib
                                 yolo_rect det(centered_drect(dpoint((x + c) * stride_x, (y + r) * stride_y),
                                                              w / (1 - w) * anchors[a].width,
                                                              h / (1 - h) * anchors[a].height));
+                                if (options.use_angle_regression)
+                                {
+                                    // The outputs are in (0, 1) so map them to (-1, 1).
+                                    const double ca = out_data[tensor_index(output_tensor, n, k + 5 + num_classes, r, c)] * 2.0 - 1;
+                                    const double sa = out_data[tensor_index(output_tensor, n, k + 6 + num_classes, r, c)] * 2.0 - 1;
+                                    det.angle = std::atan2(sa, ca);
+                                }
                                 for (long i = 0; i < num_classes; ++i)
                                 {
                                     const double conf = obj * out_data[tensor_index(output_tensor, n, k + 5 + i, r, c)];
@@ -3709,11 +5365,11 @@ namespace dlib
             {
                 const tensor& output_tensor = layer<TAG_TYPE>(sub).get_output();
                 const auto& anchors = options.anchors.at(tag_id<TAG_TYPE>::id);
-                DLIB_CASSERT(static_cast<size_t>(output_tensor.k()) == anchors.size() * (options.labels.size() + 5));
+                DLIB_CASSERT(static_cast<size_t>(output_tensor.k()) == anchors.size() * yolo_num_features(options));
                 const auto stride_x = static_cast<double>(input_tensor.nc()) / output_tensor.nc();
                 const auto stride_y = static_cast<double>(input_tensor.nr()) / output_tensor.nr();
                 const long num_feats = output_tensor.k() / anchors.size();
-                const long num_classes = num_feats - 5;
+                const long num_classes = options.labels.size();
                 const float* const out_data = output_tensor.host();
                 tensor& grad = layer<TAG_TYPE>(sub).get_gradient_input();
                 DLIB_CASSERT(input_tensor.num_samples() == grad.num_samples());
@@ -3832,6 +5488,15 @@ namespace dlib
                         g[w_idx] = scale_box * put_in_range(-1, 1, (out_data[w_idx] - tw));
                         g[h_idx] = scale_box * put_in_range(-1, 1, (out_data[h_idx] - th));
 
+                        // Regress the cosine and sine of the truth angle
+                        if (options.use_angle_regression)
+                        {
+                            const auto ca_idx = tensor_index(output_tensor, n, k + 5 + num_classes, r, c);
+                            const auto sa_idx = tensor_index(output_tensor, n, k + 6 + num_classes, r, c);
+                            g[ca_idx] = options.lambda_angle * (out_data[ca_idx] * 2.0 - 1 - std::cos(truth_box.angle));
+                            g[sa_idx] = options.lambda_angle * (out_data[sa_idx] * 2.0 - 1 - std::sin(truth_box.angle));
+                        }
+
                         // This grid cell should detect an object
                         const auto o_idx = tensor_index(output_tensor, n, k + 4, r, c);
                         {
@@ -3993,6 +5658,9 @@ namespace dlib
             out << ", lambda_cls:" << opts.lambda_cls;
             out << ", gamma_obj:" << opts.gamma_obj;
             out << ", gamma_cls:" << opts.gamma_cls;
+            out << ", use_angle_regression:" << std::boolalpha << opts.use_angle_regression;
+            if (opts.use_angle_regression)
+                out << ", lambda_angle:" << opts.lambda_angle;
             out << ", overlaps_nms:(" << opts.overlaps_nms.get_iou_thresh() << "," << opts.overlaps_nms.get_percent_covered_thresh() << ")";
             out << ", classwise_nms:" << std::boolalpha << opts.classwise_nms;
             out << ")";
@@ -4015,7 +5683,11 @@ namespace dlib
         {
             for (const auto& b : boxes)
             {
-                if (options.overlaps_nms(b.rect, box.rect))
+                // Use rotated NMS when the detector predicts angles.
+                const bool overlaps = options.use_angle_regression ?
+                    options.overlaps_nms(b.rect, b.angle, box.rect, box.angle) :
+                    options.overlaps_nms(b.rect, box.rect);
+                if (overlaps)
                 {
                     if (options.classwise_nms)
                     {
@@ -318,6 +318,12 @@ namespace dlib
                 equal to K.  Applying softmax to these K values gives the probabilities of
                 each class.  The index into that K dimensional vector with the highest
                 probability is the predicted class label.
+
+                This loss also supports label smoothing.  When it's enabled the target
+                distribution isn't all on the true label.  Instead, it puts
+                1-get_label_smoothing() on the true label and spreads get_label_smoothing()
+                evenly over all K labels.  This keeps the network from becoming overly
+                confident and often makes it generalize better.
         !*/
 
     public:
@@ -325,6 +331,31 @@ namespace dlib
         typedef unsigned long training_label_type;
         typedef unsigned long output_label_type;
 
+        loss_multiclass_log_(
+        );
+        /*!
+            ensures
+                - #get_label_smoothing() == 0
+        !*/
+
+        explicit loss_multiclass_log_(
+            double label_smoothing
+        );
+        /*!
+            requires
+                - 0 <= label_smoothing < 1
+            ensures
+                - #get_label_smoothing() == label_smoothing
+        !*/
+
+        double get_label_smoothing (
+        ) const;
+        /*!
+            ensures
+                - returns the amount of label smoothing used.  0 means the targets are the
+                  true labels, i.e. the plain multiclass logistic regression loss.
+        !*/
+
         template <
             typename SUB_TYPE,
             typename label_iterator
@@ -369,6 +400,231 @@ namespace dlib
     template <typename SUBNET>
     using loss_multiclass_log = add_loss_layer<loss_multiclass_log_, SUBNET>;
 
+// ----------------------------------------------------------------------------------------
+
+    class loss_multiclass_focal_
+    {
+        /*!
+            WHAT THIS OBJECT REPRESENTS
+                This object implements the loss layer interface defined above by
+                EXAMPLE_LOSS_LAYER_.  In particular, it implements the focal loss from the
+                paper:
+                    Focal Loss for Dense Object Detection by Tsung-Yi Lin, Priya Goyal,
+                    Ross Girshick, Kaiming He, and Piotr Dollar
+
+                It is used just like loss_multiclass_log_.  The difference is that the loss
+                of each sample is -(1-p)^get_gamma()*log(p), where p is the probability the
+                network gives to the true label.  So samples that are already classified
+                correctly with high confidence contribute very little to the loss, and
+                training concentrates on the hard ones.  This is useful when the classes
+                are very imbalanced, since the many easy examples of the common classes
+                don't swamp the gradient.  With get_gamma() == 0 this is exactly
+                loss_multiclass_log_.
+        !*/
+
+    public:
+
+        typedef unsigned long training_label_type;
+        typedef unsigned long output_label_type;
+
+        loss_multiclass_focal_(
+        );
+        /*!
+            ensures
+                - #get_gamma() == 2
+        !*/
+
+        explicit loss_multiclass_focal_(
+            double gamma
+        );
+        /*!
+            requires
+                - gamma >= 0
+            ensures
+                - #get_gamma() == gamma
+        !*/
+
+        double get_gamma (
+        ) const;
+        /*!
+            ensures
+                - returns the focusing parameter.  The bigger it is the less the well
+                  classified samples count.
+        !*/
+
+        template <
+            typename SUB_TYPE,
+            typename label_iterator
+            >
+        void to_label (
+            const tensor& input_tensor,
+            const SUB_TYPE& sub,
+            label_iterator iter
+        ) const;
+        /*!
+            This function has the same interface as EXAMPLE_LOSS_LAYER_::to_label() except
+            it has the additional calling requirements that: 
+                - sub.get_output().nr() == 1
+                - sub.get_output().nc() == 1
+                - sub.get_output().num_samples() == input_tensor.num_samples()
+                - sub.sample_expansion_factor() == 1
+            and the output label is the predicted class for each classified object.  The number
+            of possible output classes is sub.get_output().k().
+        !*/
+
+        template <
+            typename const_label_iterator,
+            typename SUBNET
+            >
+        double compute_loss_value_and_gradient (
+            const tensor& input_tensor,
+            const_label_iterator truth, 
+            SUBNET& sub
+        ) const;
+        /*!
+            This function has the same interface as EXAMPLE_LOSS_LAYER_::compute_loss_value_and_gradient() 
+            except it has the additional calling requirements that: 
+                - sub.get_output().nr() == 1
+                - sub.get_output().nc() == 1
+                - sub.get_output().num_samples() == input_tensor.num_samples()
+                - sub.sample_expansion_factor() == 1
+                - all values pointed to by truth are < sub.get_output().k()
+        !*/
+
+    };
+
+    template <typename SUBNET>
+    using loss_multiclass_focal = add_loss_layer<loss_multiclass_focal_, SUBNET>;
+
+// ----------------------------------------------------------------------------------------
+
+    class loss_ctc_
+    {
+        /*!
+            WHAT THIS OBJECT REPRESENTS
+                This object implements the loss layer interface defined above by
+                EXAMPLE_LOSS_LAYER_.  In particular, it implements the connectionist
+                temporal classification (CTC) loss from the paper:
+                    Connectionist Temporal Classification: Labelling Unsegmented Sequence
+                    Data with Recurrent Neural Networks by Alex Graves, Santiago Fernandez,
+                    Faustino Gomez, and Jurgen Schmidhuber
+
+                This loss is used to train networks that read sequences, like the
+                characters in a line of text, when you know what the sequence is but not
+                where each of its symbols is in the input.  The network outputs a score
+                for each class at each time step.  Each spatial location of the output
+                tensor is a time step, taken in row major order, so for OCR you would
+                usually make a network whose output has 1 row and whose columns run along
+                the text.  The k() channels at each time step are turned into class
+                probabilities with a softmax.
+
+                One of the classes is the blank label, which means "no symbol here".  The
+                output sequence is found by taking a class for each time step, merging
+                adjacent repeats, and removing the blanks.  E.g. with blank 0 the per time
+                step classes 3 3 0 3 5 5 0 give the sequence 3 3 5.  The loss is the
+                negative log of the total probability of all the ways the network's
+                outputs can give the true label sequence, averaged over the mini-batch.
+                Since each sample has its own label vector the samples in a mini-batch can
+                have labels of different lengths.
+        !*/
+
+    public:
+
+        typedef std::vector<unsigned long> training_label_type;
+        typedef std::vector<unsigned long> output_label_type;
+
+        loss_ctc_(
+        );
+        /*!
+            ensures
+                - #get_blank_label() == 0
+                - #get_beam_size() == 1
+        !*/
+
+        explicit loss_ctc_(
+            unsigned long blank_label
+        );
+        /*!
+            ensures
+                - #get_blank_label() == blank_label
+                - #get_beam_size() == 1
+        !*/
+
+        unsigned long get_blank_label (
+        ) const;
+        /*!
+            ensures
+                - returns the class index that means "no symbol".
+        !*/
+
+        unsigned long get_beam_size (
+        ) const;
+        /*!
+            ensures
+                - returns the number of candidate sequences kept by to_label() while it
+                  searches for the most probable output sequence.  1 means to_label() does
+                  greedy decoding, i.e. takes the most probable class at each time step.
+                  Larger values do a CTC prefix beam search, which is slower but sums the
+                  probabilities of all the alignments that give the same sequence, and so
+                  usually finds a more probable sequence.
+        !*/
+
+        void set_beam_size (
+            unsigned long size
+        );
+        /*!
+            requires
+                - size > 0
+            ensures
+                - #get_beam_size() == size
+        !*/
+
+        template <
+            typename SUB_TYPE,
+            typename label_iterator
+            >
+        void to_label (
+            const tensor& input_tensor,
+            const SUB_TYPE& sub,
+            label_iterator iter
+        ) const;
+        /*!
+            This function has the same interface as EXAMPLE_LOSS_LAYER_::to_label() except
+            it has the additional calling requirements that: 
+                - sub.get_output().num_samples() == input_tensor.num_samples()
+                - sub.sample_expansion_factor() == 1
+                - get_blank_label() < sub.get_output().k()
+            and the output label is the decoded sequence of class labels, found as
+            described by get_beam_size().  It never contains get_blank_label().
+        !*/
+
+        template <
+            typename const_label_iterator,
+            typename SUBNET
+            >
+        double compute_loss_value_and_gradient (
+            const tensor& input_tensor,
+            const_label_iterator truth, 
+            SUBNET& sub
+        ) const;
+        /*!
+            This function has the same interface as EXAMPLE_LOSS_LAYER_::compute_loss_value_and_gradient() 
+            except it has the additional calling requirements that: 
+                - sub.get_output().num_samples() == input_tensor.num_samples()
+                - sub.sample_expansion_factor() == 1
+                - get_blank_label() < sub.get_output().k()
+                - all the values in the label vectors pointed to by truth are <
+                  sub.get_output().k() and != get_blank_label().
+                - Each label vector can be aligned with the output.  That is, its size
+                  plus the number of adjacent equal pairs in it, which need a blank in
+                  between, is <= sub.get_output().nr()*sub.get_output().nc().
+        !*/
+
+    };
+
+    template <typename SUBNET>
+    using loss_ctc = add_loss_layer<loss_ctc_, SUBNET>;
+
 // ----------------------------------------------------------------------------------------
 
     template <typename label_type>
@@ -385,6 +641,9 @@ namespace dlib
                     - loss_multiclass_log_per_pixel_weighted_ with uint16_t as label_type,
                       since, in semantic segmentation, 65536 classes ought to be enough for
                       anybody.
+                    - loss_mean_squared_weighted_, loss_mean_squared_multioutput_weighted_,
+                      loss_mean_squared_per_pixel_weighted_, and
+                      loss_binary_log_per_pixel_weighted_ with float as label_type.
         !*/
         weighted_label()
         {}
@@ -470,7 +729,196 @@ namespace dlib
     };
 
     template <typename SUBNET>
-    using loss_multiclass_log_weighted = add_loss_layer<loss_multiclass_log_weighted_, SUBNET>;// ----------------------------------------------------------------------------------------
+    using loss_multiclass_log_weighted = add_loss_layer<loss_multiclass_log_weighted_, SUBNET>;
+
+// ----------------------------------------------------------------------------------------
+
+    struct distillation_label
+    {
+        /*!
+            WHAT THIS OBJECT REPRESENTS
+                This object is the training label of a single sample for
+                loss_multiclass_distillation_.  It holds the sample's true class along
+                with the logits a teacher network output for it.  You can make these with
+                make_distillation_labels().
+        !*/
+
+        distillation_label(
+        );
+
+        distillation_label(
+            unsigned long label,
+            const matrix<float,0,1>& teacher_logits
+        );
+
+        // The true class of the sample.
+        unsigned long label = 0;
+
+        // The teacher's outputs for the sample, before any softmax.  There must be one
+        // for each class.
+        matrix<float,0,1> teacher_logits;
+    };
+
+    void serialize(const distillation_label& item, std::ostream& out);
+    void deserialize(distillation_label& item, std::istream& in);
+    /*!
+        provides serialization support
+    !*/
+
+    class loss_multiclass_distillation_
+    {
+        /*!
+            WHAT THIS OBJECT REPRESENTS
+                This object implements the loss layer interface defined above by
+                EXAMPLE_LOSS_LAYER_.  In particular, it implements the knowledge
+                distillation loss from the paper:
+                    Distilling the Knowledge in a Neural Network by Geoffrey Hinton,
+                    Oriol Vinyals, and Jeff Dean
+
+                You use it to train a small student network to mimic a larger, already
+                trained, teacher network.  Like loss_multiclass_log_, the network outputs
+                one score per class and the predicted label is the class with the largest
+                score.  But each training label also has the teacher's scores for the
+                sample, and the student learns from how likely the teacher thinks each of
+                the wrong classes is as well as from the true class.
+
+                To be precise, let S and Z be the student's and the teacher's output
+                vectors for a sample with true class y, T = get_temperature(), and
+                A = get_alpha().  Then the loss for the sample is:
+                    A*T*T*KL(softmax(Z/T) || softmax(S/T)) + (1-A)*-log(softmax(S)(y))
+                where KL is the Kullback-Leibler divergence.  The second term is the loss
+                computed by loss_multiclass_log_.  Larger temperatures make the teacher's
+                probabilities softer, which shows the student more about how the teacher
+                relates the classes to each other.  The T*T keeps the size of the first
+                term's gradient about the same for all temperatures.  The loss reported
+                is the average over the mini-batch.
+
+                The teacher's scores can be computed once, before training, or for each
+                mini-batch as it's made.  The latter is needed if the training data is
+                randomly augmented.  E.g.
+                    loader.next(images, labels);
+                    trainer.train_one_step(images, make_distillation_labels(teacher, images, labels));
+        !*/
+
+    public:
+
+        typedef distillation_label training_label_type;
+        typedef unsigned long output_label_type;
+
+        loss_multiclass_distillation_(
+        );
+        /*!
+            ensures
+                - #get_temperature() == 4
+                - #get_alpha() == 0.9
+        !*/
+
+        loss_multiclass_distillation_(
+            double temperature,
+            double alpha
+        );
+        /*!
+            requires
+                - temperature > 0
+                - 0 <= alpha <= 1
+            ensures
+                - #get_temperature() == temperature
+                - #get_alpha() == alpha
+        !*/
+
+        double get_temperature (
+        ) const;
+        /*!
+            ensures
+                - returns the temperature the teacher's and student's outputs are divided
+                  by before comparing them.
+        !*/
+
+        double get_alpha (
+        ) const;
+        /*!
+            ensures
+                - returns the weight of the distillation term of the loss.  The loss on
+                  the true labels gets a weight of 1-get_alpha().  So if get_alpha() == 0
+                  this object computes the same loss as loss_multiclass_log_, and if
+                  get_alpha() == 1 the true labels are ignored.
+        !*/
+
+        template <
+            typename SUB_TYPE,
+            typename label_iterator
+            >
+        void to_label (
+            const tensor& input_tensor,
+            const SUB_TYPE& sub,
+            label_iterator iter
+        ) const;
+        /*!
+            This function has the same interface as EXAMPLE_LOSS_LAYER_::to_label() except
+            it has the additional calling requirements that: 
+                - sub.get_output().nr() == 1
+                - sub.get_output().nc() == 1
+                - sub.get_output().num_samples() == input_tensor.num_samples()
+                - sub.sample_expansion_factor() == 1
+            and the output label is the predicted class, i.e. the index of the largest
+            output.
+        !*/
+
+        template <
+            typename const_label_iterator,
+            typename SUBNET
+            >
+        double compute_loss_value_and_gradient (
+            const tensor& input_tensor,
+            const_label_iterator truth, 
+            SUBNET& sub
+        ) const;
+        /*!
+            This function has the same interface as EXAMPLE_LOSS_LAYER_::compute_loss_value_and_gradient() 
+            except it has the additional calling requirements that: 
+                - sub.get_output().nr() == 1
+                - sub.get_output().nc() == 1
+                - sub.get_output().num_samples() == input_tensor.num_samples()
+                - sub.sample_expansion_factor() == 1
+                - all the labels pointed to by truth have label < sub.get_output().k()
+                  and teacher_logits.size() == sub.get_output().k().
+        !*/
+
+    };
+
+    template <typename SUBNET>
+    using loss_multiclass_distillation = add_loss_layer<loss_multiclass_distillation_, SUBNET>;
+
+    template <
+        typename teacher_net_type,
+        typename input_type
+        >
+    std::vector<distillation_label> make_distillation_labels (
+        teacher_net_type& teacher,
+        const std::vector<input_type>& inputs,
+        const std::vector<unsigned long>& labels,
+        size_t mini_batch_size = 32
+    );
+    /*!
+        requires
+            - teacher_net_type is an add_loss_layer object, e.g. a network trained with
+              loss_multiclass_log_, whose input_type is input_type.
+            - teacher.subnet() outputs tensors with nr() == nc() == 1 and one channel per
+              class, and teacher.sample_expansion_factor() == 1.
+            - inputs.size() == labels.size()
+            - mini_batch_size > 0
+        ensures
+            - Runs the inputs through teacher.subnet(), mini_batch_size at a time, and
+              returns the labels for training a student network with
+              loss_multiclass_distillation_.  That is, returns a vector R such that:
+                - R.size() == inputs.size()
+                - R[i].label == labels[i]
+                - R[i].teacher_logits is the output of teacher.subnet() for inputs[i].
+            - Like any network being used to make predictions, the teacher should not be
+              in training mode.  So if it was trained with bn_ or dropout_ layers you
+              should replace them with affine_ and multiply_ layers.  Otherwise its
+              outputs will depend on the other samples in the mini-batch.
+    !*/
 
 // ----------------------------------------------------------------------------------------
 
@@ -892,6 +1340,25 @@ namespace dlib
         // getting the bounding box shape correct.
         double bbr_lambda = 100; 
 
+        // By default, the mmod loss only finds axis aligned boxes.  If you set
+        // use_angle_regression == true then it also learns to predict the angle of rotated
+        // boxes (see mmod_rect::angle) and the network must output 2 more channels per
+        // detector window, which come after the bounding box regression channels if there
+        // are any.  So it outputs detector_windows.size()*(1 + 2) channels, or
+        // detector_windows.size()*(1 + 4 + 2) if use_bounding_box_regression is also true.
+        // These 2 channels are trained to output the cosine and sine of the angle of each
+        // detected object.  The detector windows and the matching of detections to truth
+        // boxes use the unrotated box shapes, i.e. the mmod_rect::rect fields, so the
+        // detector windows should describe the objects in their upright pose.  Finally,
+        // non-max suppression is done on the rotated boxes, using the rotated version of
+        // test_box_overlap::operator() with overlaps_nms.
+        bool use_angle_regression = false;
+        // When using angle regression, the objective function being optimized is
+        // basic_mmod_loss + angle_lambda*angle_regression_loss, where the angle regression
+        // loss is half the squared distance between the predicted and true (cos,sin)
+        // pairs.
+        double angle_lambda = 10;
+
         // Tell the loss not to print warnings about impossible labels.  You should think very hard
         // before turning this off as it's very often telling you something is really wrong with
         // your training data.
@@ -1049,6 +1516,11 @@ namespace dlib
                   you want to output more objects (that are also of less confidence) you
                   can call to_label() with a smaller value of adjust_threshold.
                 - R.ignore == false (this value is unused by to_label()).
+                - if (get_options().use_angle_regression) then
+                    - R.angle == the predicted angle of the object.  That is, the object
+                      is rotated clockwise by R.angle radians around the center of R.rect.
+                - else
+                    - R.angle == 0
         !*/
 
         template <
@@ -1398,8 +1870,204 @@ namespace dlib
         !*/
     public:
 
-        typedef float training_label_type;
-        typedef float output_label_type;
+        typedef float training_label_type;
+        typedef float output_label_type;
+
+        template <
+            typename SUB_TYPE,
+            typename label_iterator
+            >
+        void to_label (
+            const tensor& input_tensor,
+            const SUB_TYPE& sub,
+            label_iterator iter
+        ) const;
+        /*!
+            This function has the same interface as EXAMPLE_LOSS_LAYER_::to_label() except
+            it has the additional calling requirements that:
+                - sub.get_output().nr() == 1
+                - sub.get_output().nc() == 1
+                - sub.get_output().k() == 1
+                - sub.get_output().num_samples() == input_tensor.num_samples()
+                - sub.sample_expansion_factor() == 1
+            and the output label is the predicted continuous variable.
+        !*/
+
+        template <
+            typename const_label_iterator,
+            typename SUBNET
+            >
+        double compute_loss_value_and_gradient (
+            const tensor& input_tensor,
+            const_label_iterator truth,
+            SUBNET& sub
+        ) const;
+        /*!
+            This function has the same interface as EXAMPLE_LOSS_LAYER_::compute_loss_value_and_gradient()
+            except it has the additional calling requirements that:
+                - sub.get_output().nr() == 1
+                - sub.get_output().nc() == 1
+                - sub.get_output().k() == 1
+                - sub.get_output().num_samples() == input_tensor.num_samples()
+                - sub.sample_expansion_factor() == 1
+        !*/
+
+    };
+
+    template <typename SUBNET>
+    using loss_mean_squared = add_loss_layer<loss_mean_squared_, SUBNET>;
+
+// ----------------------------------------------------------------------------------------
+
+    class loss_mean_squared_weighted_
+    {
+        /*!
+            WHAT THIS OBJECT REPRESENTS
+                This object implements the loss layer interface defined above by
+                EXAMPLE_LOSS_LAYER_.  In particular, it implements the mean squared loss,
+                which is appropriate for regression problems.  It is basically just like
+                loss_mean_squared_ except that it lets you define per-sample weights.  The
+                squared error of each sample is multiplied by its weight, so you can
+                emphasize some samples over others, or give a sample a weight of 0 to
+                ignore it entirely.
+
+                Note that if all the weights are 1 then you get loss_mean_squared_ as a
+                special case.
+        !*/
+    public:
+
+        typedef dlib::weighted_label<float> weighted_label;
+        typedef weighted_label training_label_type;
+        typedef float output_label_type;
+
+        template <
+            typename SUB_TYPE,
+            typename label_iterator
+            >
+        void to_label (
+            const tensor& input_tensor,
+            const SUB_TYPE& sub,
+            label_iterator iter
+        ) const;
+        /*!
+            This function has the same interface as EXAMPLE_LOSS_LAYER_::to_label() except
+            it has the additional calling requirements that:
+                - sub.get_output().nr() == 1
+                - sub.get_output().nc() == 1
+                - sub.get_output().k() == 1
+                - sub.get_output().num_samples() == input_tensor.num_samples()
+                - sub.sample_expansion_factor() == 1
+            and the output label is the predicted continuous variable.
+        !*/
+
+        template <
+            typename const_label_iterator,
+            typename SUBNET
+            >
+        double compute_loss_value_and_gradient (
+            const tensor& input_tensor,
+            const_label_iterator truth,
+            SUBNET& sub
+        ) const;
+        /*!
+            This function has the same interface as EXAMPLE_LOSS_LAYER_::compute_loss_value_and_gradient()
+            except it has the additional calling requirements that:
+                - sub.get_output().nr() == 1
+                - sub.get_output().nc() == 1
+                - sub.get_output().k() == 1
+                - sub.get_output().num_samples() == input_tensor.num_samples()
+                - sub.sample_expansion_factor() == 1
+                - all the weights pointed to by truth are >= 0
+        !*/
+
+    };
+
+    template <typename SUBNET>
+    using loss_mean_squared_weighted = add_loss_layer<loss_mean_squared_weighted_, SUBNET>;
+
+// ----------------------------------------------------------------------------------------
+
+    class loss_mean_squared_multioutput_
+    {
+        /*!
+            WHAT THIS OBJECT REPRESENTS
+                This object implements the loss layer interface defined above by
+                EXAMPLE_LOSS_LAYER_.  In particular, it implements the mean squared loss,
+                which is appropriate for regression problems.  It is basically just like
+                loss_mean_squared_ except that it lets you define multiple outputs instead
+                of just 1.
+        !*/
+    public:
+
+        typedef matrix<float> training_label_type;
+        typedef matrix<float> output_label_type;
+
+        template <
+            typename SUB_TYPE,
+            typename label_iterator
+            >
+        void to_label (
+            const tensor& input_tensor,
+            const SUB_TYPE& sub,
+            label_iterator iter
+        ) const;
+        /*!
+            This function has the same interface as EXAMPLE_LOSS_LAYER_::to_label() except
+            it has the additional calling requirements that:
+                - sub.get_output().nr() == 1
+                - sub.get_output().nc() == 1
+                - sub.get_output().num_samples() == input_tensor.num_samples()
+                - sub.sample_expansion_factor() == 1
+            and the output label is the predicted continuous variable.
+        !*/
+
+        template <
+            typename const_label_iterator,
+            typename SUBNET
+            >
+        double compute_loss_value_and_gradient (
+            const tensor& input_tensor,
+            const_label_iterator truth,
+            SUBNET& sub
+        ) const;
+        /*!
+            This function has the same interface as EXAMPLE_LOSS_LAYER_::compute_loss_value_and_gradient()
+            except it has the additional calling requirements that:
+                - sub.get_output().nr() == 1
+                - sub.get_output().nc() == 1
+                - sub.get_output().num_samples() == input_tensor.num_samples()
+                - sub.sample_expansion_factor() == 1
+                - (*(truth + idx)).nc() == 1 for all idx such that 0 <= idx < sub.get_output().num_samples()
+                - (*(truth + idx)).nr() == sub.get_output().k() for all idx such that 0 <= idx < sub.get_output().num_samples()
+        !*/
+
+    };
+
+    template <typename SUBNET>
+    using loss_mean_squared_multioutput = add_loss_layer<loss_mean_squared_multioutput_, SUBNET>;
+
+// ----------------------------------------------------------------------------------------
+
+    class loss_mean_squared_multioutput_weighted_
+    {
+        /*!
+            WHAT THIS OBJECT REPRESENTS
+                This object implements the loss layer interface defined above by
+                EXAMPLE_LOSS_LAYER_.  In particular, it implements the mean squared loss,
+                which is appropriate for regression problems.  It is basically just like
+                loss_mean_squared_multioutput_ except that each output of each sample has
+                its own weight.  This is useful when some of the targets of a sample are
+                missing, since giving an output a weight of 0 removes it from the loss and
+                gradient, while the other outputs of the sample are still trained.
+
+                Note that if all the weights are 1 then you get
+                loss_mean_squared_multioutput_ as a special case.
+        !*/
+    public:
+
+        typedef dlib::weighted_label<float> weighted_label;
+        typedef matrix<weighted_label> training_label_type;
+        typedef matrix<float> output_label_type;
 
         template <
             typename SUB_TYPE,
@@ -1415,7 +2083,6 @@ namespace dlib
             it has the additional calling requirements that:
                 - sub.get_output().nr() == 1
                 - sub.get_output().nc() == 1
-                - sub.get_output().k() == 1
                 - sub.get_output().num_samples() == input_tensor.num_samples()
                 - sub.sample_expansion_factor() == 1
             and the output label is the predicted continuous variable.
@@ -1435,27 +2102,31 @@ namespace dlib
             except it has the additional calling requirements that:
                 - sub.get_output().nr() == 1
                 - sub.get_output().nc() == 1
-                - sub.get_output().k() == 1
                 - sub.get_output().num_samples() == input_tensor.num_samples()
                 - sub.sample_expansion_factor() == 1
+                - (*(truth + idx)).nc() == 1 for all idx such that 0 <= idx < sub.get_output().num_samples()
+                - (*(truth + idx)).nr() == sub.get_output().k() for all idx such that 0 <= idx < sub.get_output().num_samples()
+                - all the weights pointed to by truth are >= 0
         !*/
 
     };
 
     template <typename SUBNET>
-    using loss_mean_squared = add_loss_layer<loss_mean_squared_, SUBNET>;
+    using loss_mean_squared_multioutput_weighted = add_loss_layer<loss_mean_squared_multioutput_weighted_, SUBNET>;
 
 // ----------------------------------------------------------------------------------------
 
-    class loss_mean_squared_multioutput_
+    class loss_binary_log_per_pixel_
     {
         /*!
             WHAT THIS OBJECT REPRESENTS
                 This object implements the loss layer interface defined above by
-                EXAMPLE_LOSS_LAYER_.  In particular, it implements the mean squared loss,
-                which is appropriate for regression problems.  It is basically just like
-                loss_mean_squared_ except that it lets you define multiple outputs instead
-                of just 1.
+                EXAMPLE_LOSS_LAYER_.  In particular, it implements the log loss, which is
+                appropriate for binary classification problems.  It is basically just like
+                loss_binary_log_ except that it lets you define matrix outputs instead
+                of scalar outputs.  It should be useful, for example, in segmentation
+                where we want to classify each pixel of an image, and also get at least
+                some sort of confidence estimate for each pixel.
         !*/
     public:
 
@@ -1474,11 +2145,11 @@ namespace dlib
         /*!
             This function has the same interface as EXAMPLE_LOSS_LAYER_::to_label() except
             it has the additional calling requirements that:
-                - sub.get_output().nr() == 1
-                - sub.get_output().nc() == 1
                 - sub.get_output().num_samples() == input_tensor.num_samples()
                 - sub.sample_expansion_factor() == 1
-            and the output label is the predicted continuous variable.
+            and the output label is the raw score for each classified object.  If the score
+            is > 0 then the classifier is predicting the +1 class, otherwise it is
+            predicting the -1 class.
         !*/
 
         template <
@@ -1493,36 +2164,41 @@ namespace dlib
         /*!
             This function has the same interface as EXAMPLE_LOSS_LAYER_::compute_loss_value_and_gradient()
             except it has the additional calling requirements that:
-                - sub.get_output().nr() == 1
-                - sub.get_output().nc() == 1
                 - sub.get_output().num_samples() == input_tensor.num_samples()
                 - sub.sample_expansion_factor() == 1
-                - (*(truth + idx)).nc() == 1 for all idx such that 0 <= idx < sub.get_output().num_samples()
-                - (*(truth + idx)).nr() == sub.get_output().k() for all idx such that 0 <= idx < sub.get_output().num_samples()
+                - all pixel values pointed to by truth correspond to the desired target values.
+                  Nominally they should be +1 or -1, each indicating the desired class label,
+                  or 0 to indicate that the corresponding pixel is to be ignored.
         !*/
 
     };
 
     template <typename SUBNET>
-    using loss_mean_squared_multioutput = add_loss_layer<loss_mean_squared_multioutput_, SUBNET>;
+    using loss_binary_log_per_pixel = add_loss_layer<loss_binary_log_per_pixel_, SUBNET>;
 
 // ----------------------------------------------------------------------------------------
 
-    class loss_binary_log_per_pixel_
+    class loss_binary_log_per_pixel_weighted_
     {
         /*!
             WHAT THIS OBJECT REPRESENTS
                 This object implements the loss layer interface defined above by
                 EXAMPLE_LOSS_LAYER_.  In particular, it implements the log loss, which is
                 appropriate for binary classification problems.  It is basically just like
-                loss_binary_log_ except that it lets you define matrix outputs instead
-                of scalar outputs.  It should be useful, for example, in segmentation
-                where we want to classify each pixel of an image, and also get at least
-                some sort of confidence estimate for each pixel.
+                loss_binary_log_per_pixel_ except that it lets you define per-pixel
+                weights, which may be useful e.g. if you want to emphasize a rare class or
+                ignore pixels whose ground truth is unknown.
+
+                In particular, a pixel with label y and weight w contributes to the loss
+                exactly like a pixel with label y*w does in loss_binary_log_per_pixel_.  So
+                the sign of the label gives the class, a weight of 0 means the pixel is
+                ignored, and if all the weights are 1 then you get
+                loss_binary_log_per_pixel_ as a special case.
         !*/
     public:
 
-        typedef matrix<float> training_label_type;
+        typedef dlib::weighted_label<float> weighted_label;
+        typedef matrix<weighted_label> training_label_type;
         typedef matrix<float> output_label_type;
 
         template <
@@ -1537,11 +2213,11 @@ namespace dlib
         /*!
             This function has the same interface as EXAMPLE_LOSS_LAYER_::to_label() except
             it has the additional calling requirements that:
+                - sub.get_output().k() == 1
                 - sub.get_output().num_samples() == input_tensor.num_samples()
                 - sub.sample_expansion_factor() == 1
-            and the output label is the raw score for each classified object.  If the score
-            is > 0 then the classifier is predicting the +1 class, otherwise it is
-            predicting the -1 class.
+            and the output labels are the raw scores for each classified pixel, just like
+            loss_binary_log_per_pixel_.
         !*/
 
         template <
@@ -1556,17 +2232,18 @@ namespace dlib
         /*!
             This function has the same interface as EXAMPLE_LOSS_LAYER_::compute_loss_value_and_gradient()
             except it has the additional calling requirements that:
+                - sub.get_output().k() == 1
                 - sub.get_output().num_samples() == input_tensor.num_samples()
                 - sub.sample_expansion_factor() == 1
-                - all pixel values pointed to by truth correspond to the desired target values.
-                  Nominally they should be +1 or -1, each indicating the desired class label,
-                  or 0 to indicate that the corresponding pixel is to be ignored.
+                - for all idx such that 0 <= idx < sub.get_output().num_samples():
+                    - sub.get_output().nr() == (*(truth + idx)).nr()
+                    - sub.get_output().nc() == (*(truth + idx)).nc()
+                - all the weights pointed to by truth are >= 0
         !*/
-
     };
 
     template <typename SUBNET>
-    using loss_binary_log_per_pixel = add_loss_layer<loss_binary_log_per_pixel_, SUBNET>;
+    using loss_binary_log_per_pixel_weighted = add_loss_layer<loss_binary_log_per_pixel_weighted_, SUBNET>;
 
 // ----------------------------------------------------------------------------------------
 
@@ -1702,6 +2379,146 @@ namespace dlib
     template <typename SUBNET>
     using loss_multiclass_log_per_pixel_weighted = add_loss_layer<loss_multiclass_log_per_pixel_weighted_, SUBNET>;
 
+// ----------------------------------------------------------------------------------------
+
+    class loss_dice_per_pixel_
+    {
+        /*!
+            WHAT THIS OBJECT REPRESENTS
+                This object implements the loss layer interface defined above by
+                EXAMPLE_LOSS_LAYER_.  In particular, it implements the soft Dice loss, and
+                more generally the Tversky loss, for semantic segmentation.  These losses
+                measure the overlap between the predicted and true regions of each class
+                rather than scoring each pixel on its own.  This makes them insensitive to
+                how many pixels each class has, so they work well when the objects of
+                interest cover only a small part of the image.
+
+                The network can output either 1 or K channels:
+                    - With 1 channel this is a binary segmentation loss.  The output is the
+                      log odds of each pixel being in the foreground, so the labels must be
+                      1 (foreground), 0 (background), or label_to_ignore.
+                    - With K > 1 channels this is a multiclass segmentation loss and the
+                      labels must be < K or label_to_ignore.  The outputs are turned into
+                      class probabilities with a softmax over the channels, just as in
+                      loss_multiclass_log_per_pixel_.
+
+                For each sample and class, let P be the predicted probabilities of the
+                class and G be the indicator of the pixels that truly have the class
+                (only the foreground class in the binary case).  Then, with the sums
+                taken over the pixels that aren't ignored:
+                    TP = sum(P*G)
+                    FP = sum(P*(1-G))
+                    FN = sum((1-P)*G)
+                    index = (TP + get_smoothing())/(TP + get_alpha()*FP + get_beta()*FN + get_smoothing())
+                The loss is 1 minus the average of the index over all the samples and
+                classes.  With get_alpha() == get_beta() == 0.5 the index is the Dice
+                coefficient.  Other values give the Tversky index, which lets you trade
+                off false positives against false negatives.  E.g. get_beta() > get_alpha()
+                penalizes missing parts of the objects more than false detections.
+        !*/
+    public:
+
+        // Pixels with this label are ignored when computing the loss.
+        static const uint16_t label_to_ignore = std::numeric_limits<uint16_t>::max();
+
+        typedef matrix<uint16_t> training_label_type;
+        typedef matrix<uint16_t> output_label_type;
+
+        loss_dice_per_pixel_(
+        );
+        /*!
+            ensures
+                - #get_alpha() == 0.5
+                - #get_beta() == 0.5
+                - #get_smoothing() == 1
+                  (i.e. this is the Dice loss)
+        !*/
+
+        loss_dice_per_pixel_(
+            double alpha,
+            double beta,
+            double smoothing = 1
+        );
+        /*!
+            requires
+                - alpha >= 0
+                - beta >= 0
+                - smoothing > 0
+            ensures
+                - #get_alpha() == alpha
+                - #get_beta() == beta
+                - #get_smoothing() == smoothing
+        !*/
+
+        double get_alpha (
+        ) const;
+        /*!
+            ensures
+                - returns the weight of the false positives in the Tversky index.
+        !*/
+
+        double get_beta (
+        ) const;
+        /*!
+            ensures
+                - returns the weight of the false negatives in the Tversky index.
+        !*/
+
+        double get_smoothing (
+        ) const;
+        /*!
+            ensures
+                - returns the value added to the numerator and denominator of the Tversky
+                  index.  It keeps the loss well defined for classes that don't appear in
+                  an image, and makes their index close to 1 when the network correctly
+                  predicts that they aren't there.
+        !*/
+
+        template <
+            typename SUB_TYPE,
+            typename label_iterator
+            >
+        void to_label (
+            const tensor& input_tensor,
+            const SUB_TYPE& sub,
+            label_iterator iter
+        ) const;
+        /*!
+            This function has the same interface as EXAMPLE_LOSS_LAYER_::to_label() except
+            it has the additional calling requirements that:
+                - sub.get_output().num_samples() == input_tensor.num_samples()
+                - sub.sample_expansion_factor() == 1
+            and the output label is the predicted class for each pixel.  If
+            sub.get_output().k() == 1 this is 1 for pixels with a positive output and 0
+            otherwise.  If not, it's the index of the largest channel.
+        !*/
+
+        template <
+            typename const_label_iterator,
+            typename SUBNET
+            >
+        double compute_loss_value_and_gradient (
+            const tensor& input_tensor,
+            const_label_iterator truth,
+            SUBNET& sub
+        ) const;
+        /*!
+            This function has the same interface as EXAMPLE_LOSS_LAYER_::compute_loss_value_and_gradient()
+            except it has the additional calling requirements that:
+                - sub.get_output().num_samples() == input_tensor.num_samples()
+                - sub.sample_expansion_factor() == 1
+                - if (sub.get_output().k() == 1) then
+                    - all values pointed to by truth are 0, 1, or label_to_ignore.
+                - else
+                    - all values pointed to by truth are < sub.get_output().k() or are
+                      equal to label_to_ignore.
+        !*/
+
+    };
+
+    template <typename SUBNET>
+    using loss_dice_per_pixel = add_loss_layer<loss_dice_per_pixel_, SUBNET>;
+
 // ----------------------------------------------------------------------------------------
 
     class loss_mean_squared_per_pixel_
@@ -1760,6 +2577,71 @@ namespace dlib
     template <typename SUBNET>
     using loss_mean_squared_per_pixel = add_loss_layer<loss_mean_squared_per_pixel_, SUBNET>;
 
+// ----------------------------------------------------------------------------------------
+
+    class loss_mean_squared_per_pixel_weighted_
+    {
+        /*!
+            WHAT THIS OBJECT REPRESENTS
+                This object implements the loss layer interface defined above by
+                EXAMPLE_LOSS_LAYER_.  In particular, it implements the mean squared loss,
+                which is appropriate for regression problems.  It is basically just like
+                loss_mean_squared_per_pixel_ except that it lets you define per-pixel
+                weights.  The squared error of each pixel is multiplied by its weight, so
+                pixels without a known target can be given a weight of 0 and are then
+                ignored.
+
+                Note that if all the weights are 1 then you get loss_mean_squared_per_pixel_
+                as a special case.
+        !*/
+    public:
+
+        typedef dlib::weighted_label<float> weighted_label;
+        typedef matrix<weighted_label> training_label_type;
+        typedef matrix<float> output_label_type;
+
+        template <
+            typename SUB_TYPE,
+            typename label_iterator
+            >
+        void to_label (
+            const tensor& input_tensor,
+            const SUB_TYPE& sub,
+            label_iterator iter
+        ) const;
+        /*!
+            This function has the same interface as EXAMPLE_LOSS_LAYER_::to_label() except
+            it has the additional calling requirements that:
+                - sub.get_output().num_samples() == input_tensor.num_samples()
+                - sub.sample_expansion_factor() == 1
+            and the output labels are the predicted continuous variables.
+        !*/
+
+        template <
+            typename const_label_iterator,
+            typename SUBNET
+            >
+        double compute_loss_value_and_gradient (
+            const tensor& input_tensor,
+            const_label_iterator truth,
+            SUBNET& sub
+        ) const;
+        /*!
+            This function has the same interface as EXAMPLE_LOSS_LAYER_::compute_loss_value_and_gradient()
+            except it has the additional calling requirements that:
+                - sub.get_output().k() == 1
+                - sub.get_output().num_samples() == input_tensor.num_samples()
+                - sub.sample_expansion_factor() == 1
+                - for all idx such that 0 <= idx < sub.get_output().num_samples():
+                    - sub.get_output().nr() == (*(truth + idx)).nr()
+                    - sub.get_output().nc() == (*(truth + idx)).nc()
+                - all the weights pointed to by truth are >= 0
+        !*/
+    };
+
+    template <typename SUBNET>
+    using loss_mean_squared_per_pixel_weighted = add_loss_layer<loss_mean_squared_per_pixel_weighted_, SUBNET>;
+
 // ----------------------------------------------------------------------------------------
 
     template<long _num_channels>
@@ -1821,6 +2703,131 @@ namespace dlib
     template <long num_channels, typename SUBNET>
     using loss_mean_squared_per_channel_and_pixel = add_loss_layer<loss_mean_squared_per_channel_and_pixel_<num_channels>, SUBNET>;
 
+// ----------------------------------------------------------------------------------------
+
+    class loss_keypoint_heatmap_
+    {
+        /*!
+            WHAT THIS OBJECT REPRESENTS
+                This object implements the loss layer interface defined above by
+                EXAMPLE_LOSS_LAYER_.  In particular, it implements a heatmap regression loss
+                for keypoint detection, like finding the joints of a body or the landmarks
+                of a hand or face.  The network outputs one heatmap channel per keypoint, so
+                a keypoint network usually ends with a 1x1 convolution with as many filters
+                as there are keypoints.  For example:
+                    using net_type = loss_keypoint_heatmap<con<17,1,1,1,1,
+                                     relu<bn_con<cont<64,4,4,2,2,
+                                     ... some fully convolutional backbone ...
+                                     input_rgb_image>>>>>>;
+
+                The truth label of an image is a full_object_detection whose parts are the
+                keypoint locations in the image.  The loss renders a Gaussian, with a peak
+                of 1 and a standard deviation of get_sigma() output tensor pixels, at the
+                location of each part in the corresponding output channel and trains the
+                network to output these heatmaps with the squared error.  Parts set to
+                OBJECT_PART_NOT_PRESENT get an all zero heatmap, so the network learns to
+                report them as not present.
+
+                At inference time each channel's maximum is the detected keypoint.  Its
+                location is refined to sub-pixel accuracy by fitting a parabola to the log
+                of the peak and its neighbors, then mapped back to the input image.  So the
+                output can be at a lower resolution than the input, as long as all the
+                layers of the network provide map_input_to_output() and
+                map_output_to_input(), and the input layer puts an image into the tensor
+                without scaling it.
+        !*/
+    public:
+
+        typedef full_object_detection training_label_type;
+        typedef full_object_detection output_label_type;
+
+        loss_keypoint_heatmap_(
+        );
+        /*!
+            ensures
+                - #get_sigma() == 2
+                - #get_presence_threshold() == 0.25
+        !*/
+
+        explicit loss_keypoint_heatmap_(
+            double sigma,
+            double presence_threshold = 0.25
+        );
+        /*!
+            requires
+                - sigma > 0
+            ensures
+                - #get_sigma() == sigma
+                - #get_presence_threshold() == presence_threshold
+        !*/
+
+        double get_sigma (
+        ) const;
+        /*!
+            ensures
+                - returns the standard deviation, measured in output tensor pixels, of the
+                  Gaussians in the target heatmaps.
+        !*/
+
+        double get_presence_threshold (
+        ) const;
+        /*!
+            ensures
+                - returns the value a heatmap's maximum must reach for to_label() to report
+                  its keypoint as present.  Since the target heatmaps have a peak of 1, a
+                  well trained network outputs values close to 1 at visible keypoints.
+        !*/
+
+        template <
+            typename SUB_TYPE,
+            typename label_iterator
+            >
+        void to_label (
+            const tensor& input_tensor,
+            const SUB_TYPE& sub,
+            label_iterator iter
+        ) const;
+        /*!
+            This function has the same interface as EXAMPLE_LOSS_LAYER_::to_label() except
+            it has the additional calling requirements that:
+                - sub.get_output().num_samples() == input_tensor.num_samples()
+                - sub.sample_expansion_factor() == 1
+            and the output label is a full_object_detection DET such that:
+                - DET.get_rect() == get_rect(input_tensor)
+                - DET.num_parts() == sub.get_output().k()
+                - if (the maximum of the k-th channel of the output is less than
+                  get_presence_threshold()) then
+                    - DET.part(k) == OBJECT_PART_NOT_PRESENT
+                - else
+                    - DET.part(k) is the location of the maximum, refined to sub-pixel
+                      accuracy and then mapped to the input image and rounded to the
+                      nearest pixel.
+        !*/
+
+        template <
+            typename const_label_iterator,
+            typename SUBNET
+            >
+        double compute_loss_value_and_gradient (
+            const tensor& input_tensor,
+            const_label_iterator truth,
+            SUBNET& sub
+        ) const;
+        /*!
+            This function has the same interface as EXAMPLE_LOSS_LAYER_::compute_loss_value_and_gradient()
+            except it has the additional calling requirements that:
+                - sub.get_output().num_samples() == input_tensor.num_samples()
+                - sub.sample_expansion_factor() == 1
+                - (*(truth + idx)).num_parts() == sub.get_output().k() for all idx such
+                  that 0 <= idx < sub.get_output().num_samples()
+            The loss is the squared error between the output and target heatmaps, summed
+            over each heatmap and averaged over the samples and keypoints.
+        !*/
+    };
+
+    template <typename SUBNET>
+    using loss_keypoint_heatmap = add_loss_layer<loss_keypoint_heatmap_, SUBNET>;
+
 // ----------------------------------------------------------------------------------------
 
     class loss_dot_
@@ -1957,6 +2964,14 @@ namespace dlib
         // examples, and focusing on the difficult ones.
         double gamma_obj = 0.0;
         double gamma_cls = 0.0;
+        // When set to true, the detector also predicts the angle of rotated boxes (see
+        // yolo_rect::angle).  In this case, each anchor has 2 more output channels, after
+        // its class scores, that are trained to output (cos(angle)+1)/2 and
+        // (sin(angle)+1)/2.  The anchors and the box regression use the unrotated box
+        // shapes, and non-max suppression is done on the rotated boxes.  lambda_angle
+        // controls how much we penalize mistakes in the predicted angles.
+        bool use_angle_regression = false;
+        double lambda_angle = 1.0;
 
     };
 
@@ -1985,7 +3000,9 @@ namespace dlib
                 Where num_classes is the number of categories that the detector is trained on,
                 and num_anchors is the number of priors or anchor boxes at the output pointed
                 by the tag layer. The number 5 corresponds to the objectness plus the 4 coordinates
-                for performing bounding box regression.
+                for performing bounding box regression.  If yolo_options::use_angle_regression
+                is true then num_classes + 7 is used instead of num_classes + 5, since the
+                detector also outputs 2 channels per anchor to predict the angle of the boxes.
         !*/
 
     public:
@@ -0,0 +1,531 @@
+// Copyright (C) 2026  Davis E. King (davis@dlib.net)
+// License: Boost Software License   See LICENSE.txt for the full license.
+#ifndef DLIB_DNn_LR_SCHEDULERS_H_
+#define DLIB_DNn_LR_SCHEDULERS_H_
+
+#include "lr_schedulers_abstract.h"
+#include "../serialize.h"
+#include "../assert.h"
+#include "../type_safe_union.h"
+#include <algorithm>
+#include <cmath>
+#include <iostream>
+
+namespace dlib
+{
+
+// ----------------------------------------------------------------------------------------
+
+    namespace impl
+    {
+        // Cosine interpolation from start (at p == 0) to end (at p == 1).
+        inline double cosine_anneal (
+            double start,
+            double end,
+            double p
+        )
+        {
+            const double pi = 3.1415926535897932385;
+            return end + (start-end)/2*(1 + std::cos(pi*p));
+        }
+
+        // Linear interpolation from start (at step 0) to end (at step num_steps).
+        inline double linear_ramp (
+            double start,
+            double end,
+            unsigned long long step,
+            unsigned long long num_steps
+        )
+        {
+            if (step >= num_steps)
+                return end;
+            return start + (end-start)*step/(double)num_steps;
+        }
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    class linear_warmup_scheduler
+    {
+    public:
+
+        linear_warmup_scheduler(
+        ) : linear_warmup_scheduler(1e-5, 0.01, 1000) {}
+
+        linear_warmup_scheduler(
+            double start_learning_rate_,
+            double peak_learning_rate_,
+            unsigned long long warmup_steps_
+        ) :
+            start_learning_rate(start_learning_rate_),
+            peak_learning_rate(peak_learning_rate_),
+            warmup_steps(warmup_steps_)
+        {
+            DLIB_CASSERT(start_learning_rate > 0 && peak_learning_rate > 0);
+        }
+
+        double get_start_learning_rate (
+        ) const { return start_learning_rate; }
+
+        double get_peak_learning_rate (
+        ) const { return peak_learning_rate; }
+
+        unsigned long long get_warmup_steps (
+        ) const { return warmup_steps; }
+
+        double get_min_learning_rate (
+        ) const { return std::min(start_learning_rate, peak_learning_rate); }
+
+        double operator() (
+            unsigned long long step
+        ) const
+        {
+            return impl::linear_ramp(start_learning_rate, peak_learning_rate, step, warmup_steps);
+        }
+
+        friend void serialize(const linear_warmup_scheduler& item, std::ostream& out)
+        {
+            serialize("linear_warmup_scheduler", out);
+            serialize(item.start_learning_rate, out);
+            serialize(item.peak_learning_rate, out);
+            serialize(item.warmup_steps, out);
+        }
+
+        friend void deserialize(linear_warmup_scheduler& item, std::istream& in)
+        {
+            std::string version;
+            deserialize(version, in);
+            if (version != "linear_warmup_scheduler")
+                throw serialization_error("Unexpected version found while deserializing dlib::linear_warmup_scheduler.");
+            deserialize(item.start_learning_rate, in);
+            deserialize(item.peak_learning_rate, in);
+            deserialize(item.warmup_steps, in);
+        }
+
+        friend std::ostream& operator<< (std::ostream& out, const linear_warmup_scheduler& item)
+        {
+            out << "linear_warmup_scheduler: start_learning_rate=" << item.start_learning_rate
+                << ", peak_learning_rate=" << item.peak_learning_rate
+                << ", warmup_steps=" << item.warmup_steps;
+            return out;
+        }
+
+    private:
+        double start_learning_rate;
+        double peak_learning_rate;
+        unsigned long long warmup_steps;
+    };
+
+// ----------------------------------------------------------------------------------------
+
+    class cosine_restarts_scheduler
+    {
+    public:
+
+        cosine_restarts_scheduler(
+        ) : cosine_restarts_scheduler(0.01, 1e-5, 10000) {}
+
+        cosine_restarts_scheduler(
+            double max_learning_rate_,
+            double min_learning_rate_,
+            unsigned long long period_,
+            double period_multiplier_ = 1,
+            unsigned long long warmup_steps_ = 0
+        ) :
+            max_learning_rate(max_learning_rate_),
+            min_learning_rate(min_learning_rate_),
+            period(period_),
+            period_multiplier(period_multiplier_),
+            warmup_steps(warmup_steps_)
+        {
+            DLIB_CASSERT(0 < min_learning_rate && min_learning_rate <= max_learning_rate);
+            DLIB_CASSERT(period > 0 && period_multiplier >= 1);
+        }
+
+        double get_max_learning_rate (
+        ) const { return max_learning_rate; }
+
+        double get_min_learning_rate (
+        ) const { return min_learning_rate; }
+
+        unsigned long long get_period (
+        ) const { return period; }
+
+        double get_period_multiplier (
+        ) const { return period_multiplier; }
+
+        unsigned long long get_warmup_steps (
+        ) const { return warmup_steps; }
+
+        double operator() (
+            unsigned long long step
+        ) const
+        {
+            if (step < warmup_steps)
+                return impl::linear_ramp(min_learning_rate, max_learning_rate, step, warmup_steps);
+
+            // Find where we are inside the current cycle.
+            double t = step - warmup_steps;
+            double cycle_length = period;
+            if (period_multiplier == 1)
+            {
+                t = std::fmod(t, cycle_length);
+            }
+            else
+            {
+                while (t >= cycle_length)
+                {
+                    t -= cycle_length;
+                    cycle_length *= period_multiplier;
+                }
+            }
+            return impl::cosine_anneal(max_learning_rate, min_learning_rate, t/cycle_length);
+        }
+
+        friend void serialize(const cosine_restarts_scheduler& item, std::ostream& out)
+        {
+            serialize("cosine_restarts_scheduler", out);
+            serialize(item.max_learning_rate, out);
+            serialize(item.min_learning_rate, out);
+            serialize(item.period, out);
+            serialize(item.period_multiplier, out);
+            serialize(item.warmup_steps, out);
+        }
+
+        friend void deserialize(cosine_restarts_scheduler& item, std::istream& in)
+        {
+            std::string version;
+            deserialize(version, in);
+            if (version != "cosine_restarts_scheduler")
+                throw serialization_error("Unexpected version found while deserializing dlib::cosine_restarts_scheduler.");
+            deserialize(item.max_learning_rate, in);
+            deserialize(item.min_learning_rate, in);
+            deserialize(item.period, in);
+            deserialize(item.period_multiplier, in);
+            deserialize(item.warmup_steps, in);
+        }
+
+        friend std::ostream& operator<< (std::ostream& out, const cosine_restarts_scheduler& item)
+        {
+            out << "cosine_restarts_scheduler: max_learning_rate=" << item.max_learning_rate
+                << ", min_learning_rate=" << item.min_learning_rate
+                << ", period=" << item.period
+                << ", period_multiplier=" << item.period_multiplier
+                << ", warmup_steps=" << item.warmup_steps;
+            return out;
+        }
+
+    private:
+        double max_learning_rate;
+        double min_learning_rate;
+        unsigned long long period;
+        double period_multiplier;
+        unsigned long long warmup_steps;
+    };
+
+// ----------------------------------------------------------------------------------------
+
+    class one_cycle_scheduler
+    {
+    public:
+
+        one_cycle_scheduler(
+        ) : one_cycle_scheduler(0.01, 10000) {}
+
+        one_cycle_scheduler(
+            double max_learning_rate_,
+            unsigned long long total_steps_,
+            double pct_start_ = 0.3,
+            double div_factor_ = 25,
+            double final_div_factor_ = 1e4
+        ) :
+            max_learning_rate(max_learning_rate_),
+            total_steps(total_steps_),
+            pct_start(pct_start_),
+            div_factor(div_factor_),
+            final_div_factor(final_div_factor_)
+        {
+            DLIB_CASSERT(max_learning_rate > 0 && total_steps > 0);
+            DLIB_CASSERT(0 <= pct_start && pct_start <= 1);
+            DLIB_CASSERT(div_factor >= 1 && final_div_factor >= 1);
+        }
+
+        double get_max_learning_rate (
+        ) const { return max_learning_rate; }
+
+        unsigned long long get_total_steps (
+        ) const { return total_steps; }
+
+        double get_pct_start (
+        ) const { return pct_start; }
+
+        double get_div_factor (
+        ) const { return div_factor; }
+
+        double get_final_div_factor (
+        ) const { return final_div_factor; }
+
+        double get_initial_learning_rate (
+        ) const { return max_learning_rate/div_factor; }
+
+        double get_final_learning_rate (
+        ) const { return get_initial_learning_rate()/final_div_factor; }
+
+        double get_min_learning_rate (
+        ) const { return get_final_learning_rate(); }
+
+        double operator() (
+            unsigned long long step
+        ) const
+        {
+            const double up_steps = std::round(pct_start*total_steps);
+            if (step < up_steps)
+                return impl::cosine_anneal(get_initial_learning_rate(), max_learning_rate, step/up_steps);
+
+            const double down_steps = total_steps - up_steps;
+            const double p = (down_steps > 0) ? std::min(1.0, (step-up_steps)/down_steps) : 1.0;
+            return impl::cosine_anneal(max_learning_rate, get_final_learning_rate(), p);
+        }
+
+        friend void serialize(const one_cycle_scheduler& item, std::ostream& out)
+        {
+            serialize("one_cycle_scheduler", out);
+            serialize(item.max_learning_rate, out);
+            serialize(item.total_steps, out);
+            serialize(item.pct_start, out);
+            serialize(item.div_factor, out);
+            serialize(item.final_div_factor, out);
+        }
+
+        friend void deserialize(one_cycle_scheduler& item, std::istream& in)
+        {
+            std::string version;
+            deserialize(version, in);
+            if (version != "one_cycle_scheduler")
+                throw serialization_error("Unexpected version found while deserializing dlib::one_cycle_scheduler.");
+            deserialize(item.max_learning_rate, in);
+            deserialize(item.total_steps, in);
+            deserialize(item.pct_start, in);
+            deserialize(item.div_factor, in);
+            deserialize(item.final_div_factor, in);
+        }
+
+        friend std::ostream& operator<< (std::ostream& out, const one_cycle_scheduler& item)
+        {
+            out << "one_cycle_scheduler: max_learning_rate=" << item.max_learning_rate
+                << ", total_steps=" << item.total_steps
+                << ", pct_start=" << item.pct_start
+                << ", div_factor=" << item.div_factor
+                << ", final_div_factor=" << item.final_div_factor;
+            return out;
+        }
+
+    private:
+        double max_learning_rate;
+        unsigned long long total_steps;
+        double pct_start;
+        double div_factor;
+        double final_div_factor;
+    };
+
+// ----------------------------------------------------------------------------------------
+
+    class polynomial_decay_scheduler
+    {
+    public:
+
+        polynomial_decay_scheduler(
+        ) : polynomial_decay_scheduler(0.01, 1e-5, 10000) {}
+
+        polynomial_decay_scheduler(
+            double initial_learning_rate_,
+            double final_learning_rate_,
+            unsigned long long decay_steps_,
+            double power_ = 1,
+            unsigned long long warmup_steps_ = 0
+        ) :
+            initial_learning_rate(initial_learning_rate_),
+            final_learning_rate(final_learning_rate_),
+            decay_steps(decay_steps_),
+            power(power_),
+            warmup_steps(warmup_steps_)
+        {
+            DLIB_CASSERT(0 < final_learning_rate && final_learning_rate <= initial_learning_rate);
+            DLIB_CASSERT(decay_steps > 0 && power > 0);
+        }
+
+        double get_initial_learning_rate (
+        ) const { return initial_learning_rate; }
+
+        double get_final_learning_rate (
+        ) const { return final_learning_rate; }
+
+        unsigned long long get_decay_steps (
+        ) const { return decay_steps; }
+
+        double get_power (
+        ) const { return power; }
+
+        unsigned long long get_warmup_steps (
+        ) const { return warmup_steps; }
+
+        double get_min_learning_rate (
+        ) const { return final_learning_rate; }
+
+        double operator() (
+            unsigned long long step
+        ) const
+        {
+            if (step < warmup_steps)
+                return impl::linear_ramp(final_learning_rate, initial_learning_rate, step, warmup_steps);
+
+            const double p = std::min(1.0, (step-warmup_steps)/(double)decay_steps);
+            return (initial_learning_rate-final_learning_rate)*std::pow(1-p, power) + final_learning_rate;
+        }
+
+        friend void serialize(const polynomial_decay_scheduler& item, std::ostream& out)
+        {
+            serialize("polynomial_decay_scheduler", out);
+            serialize(item.initial_learning_rate, out);
+            serialize(item.final_learning_rate, out);
+            serialize(item.decay_steps, out);
+            serialize(item.power, out);
+            serialize(item.warmup_steps, out);
+        }
+
+        friend void deserialize(polynomial_decay_scheduler& item, std::istream& in)
+        {
+            std::string version;
+            deserialize(version, in);
+            if (version != "polynomial_decay_scheduler")
+                throw serialization_error("Unexpected version found while deserializing dlib::polynomial_decay_scheduler.");
+            deserialize(item.initial_learning_rate, in);
+            deserialize(item.final_learning_rate, in);
+            deserialize(item.decay_steps, in);
+            deserialize(item.power, in);
+            deserialize(item.warmup_steps, in);
+        }
+
+        friend std::ostream& operator<< (std::ostream& out, const polynomial_decay_scheduler& item)
+        {
+            out << "polynomial_decay_scheduler: initial_learning_rate=" << item.initial_learning_rate
+                << ", final_learning_rate=" << item.final_learning_rate
+                << ", decay_steps=" << item.decay_steps
+                << ", power=" << item.power
+                << ", warmup_steps=" << item.warmup_steps;
+            return out;
+        }
+
+    private:
+        double initial_learning_rate;
+        double final_learning_rate;
+        unsigned long long decay_steps;
+        double power;
+        unsigned long long warmup_steps;
+    };
+
+// ----------------------------------------------------------------------------------------
+
+    class step_decay_scheduler
+    {
+    public:
+
+        step_decay_scheduler(
+        ) : step_decay_scheduler(0.01, 10000) {}
+
+        step_decay_scheduler(
+            double initial_learning_rate_,
+            unsigned long long step_size_,
+            double gamma_ = 0.1,
+            double min_learning_rate_ = 1e-5
+        ) :
+            initial_learning_rate(initial_learning_rate_),
+            step_size(step_size_),
+            gamma(gamma_),
+            min_learning_rate(min_learning_rate_)
+        {
+            DLIB_CASSERT(0 < min_learning_rate && min_learning_rate <= initial_learning_rate);
+            DLIB_CASSERT(step_size > 0 && 0 < gamma && gamma <= 1);
+        }
+
+        double get_initial_learning_rate (
+        ) const { return initial_learning_rate; }
+
+        unsigned long long get_step_size (
+        ) const { return step_size; }
+
+        double get_gamma (
+        ) const { return gamma; }
+
+        double get_min_learning_rate (
+        ) const { return min_learning_rate; }
+
+        double operator() (
+            unsigned long long step
+        ) const
+        {
+            return std::max(min_learning_rate, initial_learning_rate*std::pow(gamma, (double)(step/step_size)));
+        }
+
+        friend void serialize(const step_decay_scheduler& item, std::ostream& out)
+        {
+            serialize("step_decay_scheduler", out);
+            serialize(item.initial_learning_rate, out);
+            serialize(item.step_size, out);
+            serialize(item.gamma, out);
+            serialize(item.min_learning_rate, out);
+        }
+
+        friend void deserialize(step_decay_scheduler& item, std::istream& in)
+        {
+            std::string version;
+            deserialize(version, in);
+            if (version != "step_decay_scheduler")
+                throw serialization_error("Unexpected version found while deserializing dlib::step_decay_scheduler.");
+            deserialize(item.initial_learning_rate, in);
+            deserialize(item.step_size, in);
+            deserialize(item.gamma, in);
+            deserialize(item.min_learning_rate, in);
+        }
+
+        friend std::ostream& operator<< (std::ostream& out, const step_decay_scheduler& item)
+        {
+            out << "step_decay_scheduler: initial_learning_rate=" << item.initial_learning_rate
+                << ", step_size=" << item.step_size
+                << ", gamma=" << item.gamma
+                << ", min_learning_rate=" << item.min_learning_rate;
+            return out;
+        }
+
+    private:
+        double initial_learning_rate;
+        unsigned long long step_size;
+        double gamma;
+        double min_learning_rate;
+    };
+
+// ----------------------------------------------------------------------------------------
+
+    using learning_rate_scheduler = type_safe_union<
+        linear_warmup_scheduler,
+        cosine_restarts_scheduler,
+        one_cycle_scheduler,
+        polynomial_decay_scheduler,
+        step_decay_scheduler
+        >;
+
+    inline std::ostream& operator<< (std::ostream& out, const learning_rate_scheduler& item)
+    {
+        if (item.is_empty())
+            out << "none";
+        else
+            visit([&](const auto& s) { out << s; }, item);
+        return out;
+    }
+
+// ----------------------------------------------------------------------------------------
+
+}
+
+#endif // DLIB_DNn_LR_SCHEDULERS_H_
+
@@ -0,0 +1,388 @@
+// Copyright (C) 2026  Davis E. King (davis@dlib.net)
+// License: Boost Software License   See LICENSE.txt for the full license.
+#undef DLIB_DNn_LR_SCHEDULERS_ABSTRACT_H_
+#ifdef DLIB_DNn_LR_SCHEDULERS_ABSTRACT_H_
+
+#include "../type_safe_union/type_safe_union_kernel_abstract.h"
+#include <iostream>
+
+namespace dlib
+{
+
+// ----------------------------------------------------------------------------------------
+// ----------------------------------------------------------------------------------------
+// ----------------------------------------------------------------------------------------
+
+    class EXAMPLE_LR_SCHEDULER
+    {
+        /*!
+            WHAT THIS OBJECT REPRESENTS
+                A learning rate scheduler is a function that maps the number of training
+                steps taken so far to the learning rate that should be used for the next
+                mini-batch.  Schedulers are given to dnn_trainer via
+                set_learning_rate_scheduler(), and the trainer then evaluates the
+                scheduler before every training step.
+
+                Schedulers are stateless.  All the state of a training run (i.e. the step
+                counter) lives in the dnn_trainer, which saves it along with the scheduler
+                parameters in its synchronization file.  So a run that is resumed from a
+                sync file continues on the same learning rate curve.
+
+                Note that there is no dlib::EXAMPLE_LR_SCHEDULER type.  It is shown here
+                purely to document the interface a scheduler object must implement.
+        !*/
+
+    public:
+
+        EXAMPLE_LR_SCHEDULER(
+        );
+
+        double get_min_learning_rate (
+        ) const;
+        /*!
+            ensures
+                - returns the smallest value operator() will ever return.
+                - get_min_learning_rate() > 0
+        !*/
+
+        double operator() (
+            unsigned long long step
+        ) const;
+        /*!
+            ensures
+                - returns the learning rate to use after step training steps have been
+                  performed.  That is, (*this)(0) is the learning rate for the first
+                  mini-batch.
+                - returns a value >= get_min_learning_rate()
+        !*/
+    };
+
+    void serialize(const EXAMPLE_LR_SCHEDULER& item, std::ostream& out);
+    void deserialize(EXAMPLE_LR_SCHEDULER& item, std::istream& in);
+    /*!
+        provides serialization support
+    !*/
+
+    std::ostream& operator<< (std::ostream& out, const EXAMPLE_LR_SCHEDULER& item);
+    /*!
+        Prints the scheduler's name and parameters to out.
+    !*/
+
+// ----------------------------------------------------------------------------------------
+// ----------------------------------------------------------------------------------------
+// ----------------------------------------------------------------------------------------
+
+    class linear_warmup_scheduler
+    {
+        /*!
+            WHAT THIS OBJECT REPRESENTS
+                This object implements the EXAMPLE_LR_SCHEDULER interface defined above.
+                It linearly ramps the learning rate from get_start_learning_rate() to
+                get_peak_learning_rate() over get_warmup_steps() steps and then holds it
+                at get_peak_learning_rate().
+        !*/
+
+    public:
+
+        linear_warmup_scheduler(
+        );
+        /*!
+            ensures
+                - #get_start_learning_rate() == 1e-5
+                - #get_peak_learning_rate()  == 0.01
+                - #get_warmup_steps()        == 1000
+        !*/
+
+        linear_warmup_scheduler(
+            double start_learning_rate,
+            double peak_learning_rate,
+            unsigned long long warmup_steps
+        );
+        /*!
+            requires
+                - start_learning_rate > 0
+                - peak_learning_rate > 0
+            ensures
+                - #get_start_learning_rate() == start_learning_rate
+                - #get_peak_learning_rate()  == peak_learning_rate
+                - #get_warmup_steps()        == warmup_steps
+        !*/
+
+        double get_start_learning_rate () const;
+        double get_peak_learning_rate () const;
+        unsigned long long get_warmup_steps () const;
+        double get_min_learning_rate () const;
+        double operator() (unsigned long long step) const;
+    };
+
+// ----------------------------------------------------------------------------------------
+
+    class cosine_restarts_scheduler
+    {
+        /*!
+            WHAT THIS OBJECT REPRESENTS
+                This object implements the EXAMPLE_LR_SCHEDULER interface defined above.
+                It implements cosine annealing with warm restarts as described in the
+                paper:
+                    Loshchilov, Ilya, and Frank Hutter. "SGDR: Stochastic gradient descent
+                    with warm restarts." International Conference on Learning
+                    Representations. 2017.
+
+                After an optional linear warmup from get_min_learning_rate() to
+                get_max_learning_rate(), the learning rate follows a half cosine from
+                get_max_learning_rate() down to get_min_learning_rate() over
+                get_period() steps.  It then jumps back up to get_max_learning_rate() and
+                starts the next cycle, which is get_period_multiplier() times longer than
+                the previous one.
+        !*/
+
+    public:
+
+        cosine_restarts_scheduler(
+        );
+        /*!
+            ensures
+                - #get_max_learning_rate()   == 0.01
+                - #get_min_learning_rate()   == 1e-5
+                - #get_period()              == 10000
+                - #get_period_multiplier()   == 1
+                - #get_warmup_steps()        == 0
+        !*/
+
+        cosine_restarts_scheduler(
+            double max_learning_rate,
+            double min_learning_rate,
+            unsigned long long period,
+            double period_multiplier = 1,
+            unsigned long long warmup_steps = 0
+        );
+        /*!
+            requires
+                - 0 < min_learning_rate <= max_learning_rate
+                - period > 0
+                - period_multiplier >= 1
+            ensures
+                - #get_max_learning_rate()   == max_learning_rate
+                - #get_min_learning_rate()   == min_learning_rate
+                - #get_period()              == period
+                - #get_period_multiplier()   == period_multiplier
+                - #get_warmup_steps()        == warmup_steps
+        !*/
+
+        double get_max_learning_rate () const;
+        double get_min_learning_rate () const;
+        unsigned long long get_period () const;
+        double get_period_multiplier () const;
+        unsigned long long get_warmup_steps () const;
+        double operator() (unsigned long long step) const;
+    };
+
+// ----------------------------------------------------------------------------------------
+
+    class one_cycle_scheduler
+    {
+        /*!
+            WHAT THIS OBJECT REPRESENTS
+                This object implements the EXAMPLE_LR_SCHEDULER interface defined above.
+                It implements the one-cycle policy described in the paper:
+                    Smith, Leslie N., and Nicholay Topin. "Super-convergence: Very fast
+                    training of neural networks using large learning rates." 2019.
+
+                For the first round(get_pct_start()*get_total_steps()) steps the
+                learning rate rises along a half cosine from get_initial_learning_rate()
+                to get_max_learning_rate().  For the rest of the get_total_steps() steps
+                it falls along a half cosine down to get_final_learning_rate(), where it
+                stays from then on.
+        !*/
+
+    public:
+
+        one_cycle_scheduler(
+        );
+        /*!
+            ensures
+                - #get_max_learning_rate()   == 0.01
+                - #get_total_steps()         == 10000
+                - #get_pct_start()           == 0.3
+                - #get_div_factor()          == 25
+                - #get_final_div_factor()    == 1e4
+        !*/
+
+        one_cycle_scheduler(
+            double max_learning_rate,
+            unsigned long long total_steps,
+            double pct_start = 0.3,
+            double div_factor = 25,
+            double final_div_factor = 1e4
+        );
+        /*!
+            requires
+                - max_learning_rate > 0
+                - total_steps > 0
+                - 0 <= pct_start <= 1
+                - div_factor >= 1
+                - final_div_factor >= 1
+            ensures
+                - #get_max_learning_rate()   == max_learning_rate
+                - #get_total_steps()         == total_steps
+                - #get_pct_start()           == pct_start
+                - #get_div_factor()          == div_factor
+                - #get_final_div_factor()    == final_div_factor
+        !*/
+
+        double get_max_learning_rate () const;
+        unsigned long long get_total_steps () const;
+        double get_pct_start () const;
+        double get_div_factor () const;
+        double get_final_div_factor () const;
+
+        double get_initial_learning_rate (
+        ) const;
+        /*!
+            ensures
+                - returns get_max_learning_rate()/get_div_factor()
+        !*/
+
+        double get_final_learning_rate (
+        ) const;
+        /*!
+            ensures
+                - returns get_initial_learning_rate()/get_final_div_factor()
+        !*/
+
+        double get_min_learning_rate () const;
+        double operator() (unsigned long long step) const;
+    };
+
+// ----------------------------------------------------------------------------------------
+
+    class polynomial_decay_scheduler
+    {
+        /*!
+            WHAT THIS OBJECT REPRESENTS
+                This object implements the EXAMPLE_LR_SCHEDULER interface defined above.
+                After an optional linear warmup from get_final_learning_rate() to
+                get_initial_learning_rate(), the learning rate at step s (counted from
+                the end of the warmup) is:
+                    (initial - final)*pow(1 - min(1, s/get_decay_steps()), get_power()) + final
+                A power of 1 gives a linear decay.
+        !*/
+
+    public:
+
+        polynomial_decay_scheduler(
+        );
+        /*!
+            ensures
+                - #get_initial_learning_rate() == 0.01
+                - #get_final_learning_rate()   == 1e-5
+                - #get_decay_steps()           == 10000
+                - #get_power()                 == 1
+                - #get_warmup_steps()          == 0
+        !*/
+
+        polynomial_decay_scheduler(
+            double initial_learning_rate,
+            double final_learning_rate,
+            unsigned long long decay_steps,
+            double power = 1,
+            unsigned long long warmup_steps = 0
+        );
+        /*!
+            requires
+                - 0 < final_learning_rate <= initial_learning_rate
+                - decay_steps > 0
+                - power > 0
+            ensures
+                - #get_initial_learning_rate() == initial_learning_rate
+                - #get_final_learning_rate()   == final_learning_rate
+                - #get_decay_steps()           == decay_steps
+                - #get_power()                 == power
+                - #get_warmup_steps()          == warmup_steps
+        !*/
+
+        double get_initial_learning_rate () const;
+        double get_final_learning_rate () const;
+        unsigned long long get_decay_steps () const;
+        double get_power () const;
+        unsigned long long get_warmup_steps () const;
+        double get_min_learning_rate () const;
+        double operator() (unsigned long long step) const;
+    };
+
+// ----------------------------------------------------------------------------------------
+
+    class step_decay_scheduler
+    {
+        /*!
+            WHAT THIS OBJECT REPRESENTS
+                This object implements the EXAMPLE_LR_SCHEDULER interface defined above.
+                The learning rate starts at get_initial_learning_rate() and is multiplied
+                by get_gamma() every get_step_size() steps, but never goes below
+                get_min_learning_rate().
+        !*/
+
+    public:
+
+        step_decay_scheduler(
+        );
+        /*!
+            ensures
+                - #get_initial_learning_rate() == 0.01
+                - #get_step_size()             == 10000
+                - #get_gamma()                 == 0.1
+                - #get_min_learning_rate()     == 1e-5
+        !*/
+
+        step_decay_scheduler(
+            double initial_learning_rate,
+            unsigned long long step_size,
+            double gamma = 0.1,
+            double min_learning_rate = 1e-5
+        );
+        /*!
+            requires
+                - 0 < min_learning_rate <= initial_learning_rate
+                - step_size > 0
+                - 0 < gamma <= 1
+            ensures
+                - #get_initial_learning_rate() == initial_learning_rate
+                - #get_step_size()             == step_size
+                - #get_gamma()                 == gamma
+                - #get_min_learning_rate()     == min_learning_rate
+        !*/
+
+        double get_initial_learning_rate () const;
+        unsigned long long get_step_size () const;
+        double get_gamma () const;
+        double get_min_learning_rate () const;
+        double operator() (unsigned long long step) const;
+    };
+
+// ----------------------------------------------------------------------------------------
+
+    using learning_rate_scheduler = type_safe_union<
+        linear_warmup_scheduler,
+        cosine_restarts_scheduler,
+        one_cycle_scheduler,
+        polynomial_decay_scheduler,
+        step_decay_scheduler
+        >;
+    /*!
+        This is the type dnn_trainer uses to hold whichever of the above schedulers it
+        has been given.  It is empty when the trainer isn't using a scheduler.  Like
+        every type_safe_union it is serializable.
+    !*/
+
+    std::ostream& operator<< (std::ostream& out, const learning_rate_scheduler& item);
+    /*!
+        ensures
+            - prints the contained scheduler to out, or "none" if item.is_empty().
+    !*/
+
+// ----------------------------------------------------------------------------------------
+
+}
+
+#endif // DLIB_DNn_LR_SCHEDULERS_ABSTRACT_H_
+
@@ -0,0 +1,1952 @@
+// Copyright (C) 2026  Davis E. King (davis@dlib.net)
+// License: Boost Software License   See LICENSE.txt for the full license.
+#ifndef DLIB_DNn_ONNX_H_
+#define DLIB_DNn_ONNX_H_
+
+#include "onnx_abstract.h"
+#include "input.h"
+#include "layers.h"
+#include "loss.h"
+#include "visitors.h"
+#include <algorithm>
+#include <cmath>
+#include <cstdint>
+#include <cstring>
+#include <fstream>
+#include <iterator>
+#include <map>
+#include <sstream>
+#include <string>
+#include <vector>
+
+namespace dlib
+{
+
+// ----------------------------------------------------------------------------------------
+
+    class onnx_import_error : public dlib::error
+    {
+    public:
+        onnx_import_error(const std::string& message) : error(message) {}
+    };
+
+// ----------------------------------------------------------------------------------------
+
+    namespace impl
+    {
+        /*
+            The objects in this section are a minimal in-memory representation of the
+            parts of the ONNX protocol buffer schema (onnx/onnx.proto) that dlib uses,
+            along with the code to read and write them in the protocol buffer wire
+            format.  We only need a small subset of protobuf, so rather than depending on
+            the protobuf library we encode and decode the messages directly.
+        */
+
+        class onnx_proto_writer
+        {
+        public:
+
+            void write_varint (
+                uint64_t value
+            )
+            {
+                while (value >= 0x80)
+                {
+                    buf.push_back(static_cast<char>((value & 0x7F) | 0x80));
+                    value >>= 7;
+                }
+                buf.push_back(static_cast<char>(value));
+            }
+
+            void write_int (
+                uint32_t field,
+                int64_t value
+            )
+            {
+                write_key(field, 0);
+                write_varint(static_cast<uint64_t>(value));
+            }
+
+            void write_float (
+                uint32_t field,
+                float value
+            )
+            {
+                write_key(field, 5);
+                write_fixed32(value);
+            }
+
+            void write_bytes (
+                uint32_t field,
+                const std::string& value
+            )
+            {
+                write_key(field, 2);
+                write_varint(value.size());
+                buf += value;
+            }
+
+            void write_message (
+                uint32_t field,
+                const onnx_proto_writer& message
+            )
+            {
+                write_bytes(field, message.str());
+            }
+
+            void write_packed_ints (
+                uint32_t field,
+                const std::vector<int64_t>& values
+            )
+            {
+                onnx_proto_writer temp;
+                for (auto v : values)
+                    temp.write_varint(static_cast<uint64_t>(v));
+                write_bytes(field, temp.str());
+            }
+
+            void write_packed_floats (
+                uint32_t field,
+                const std::vector<float>& values
+            )
+            {
+                write_bytes(field, encode_floats(values));
+            }
+
+            static std::string encode_floats (
+                const std::vector<float>& values
+            )
+            {
+                onnx_proto_writer temp;
+                for (auto v : values)
+                    temp.write_fixed32(v);
+                return temp.str();
+            }
+
+            static std::string encode_int64s (
+                const std::vector<int64_t>& values
+            )
+            {
+                // ONNX raw_data is always little endian, regardless of the host.
+                std::string out;
+                for (auto v : values)
+                {
+                    const uint64_t u = static_cast<uint64_t>(v);
+                    for (int i = 0; i < 8; ++i)
+                        out.push_back(static_cast<char>((u >> (8*i)) & 0xFF));
+                }
+                return out;
+            }
+
+            const std::string& str() const { return buf; }
+
+        private:
+
+            void write_key (
+                uint32_t field,
+                uint32_t wire_type
+            )
+            {
+                write_varint((static_cast<uint64_t>(field) << 3) | wire_type);
+            }
+
+            void write_fixed32 (
+                float value
+            )
+            {
+                uint32_t u;
+                static_assert(sizeof(u) == sizeof(value), "dlib requires 32 bit floats");
+                std::memcpy(&u, &value, sizeof(u));
+                for (int i = 0; i < 4; ++i)
+                    buf.push_back(static_cast<char>((u >> (8*i)) & 0xFF));
+            }
+
+            std::string buf;
+        };
+
+    // ------------------------------------------------------------------------------------
+
+        class onnx_proto_reader
+        {
+        public:
+
+            onnx_proto_reader (
+                std::string data_
+            ) : data(std::move(data_)), pos(0) {}
+
+            bool next (
+                uint32_t& field,
+                uint32_t& wire_type
+            )
+            {
+                if (at_end())
+                    return false;
+                const uint64_t key = read_varint();
+                field = static_cast<uint32_t>(key >> 3);
+                wire_type = static_cast<uint32_t>(key & 7);
+                return true;
+            }
+
+            uint64_t read_varint (
+            )
+            {
+                uint64_t value = 0;
+                for (int shift = 0; shift < 64; shift += 7)
+                {
+                    if (at_end())
+                        throw serialization_error("Unexpected end of data while parsing an ONNX file.");
+                    const uint8_t byte = static_cast<uint8_t>(data[pos++]);
+                    value |= static_cast<uint64_t>(byte & 0x7F) << shift;
+                    if ((byte & 0x80) == 0)
+                        return value;
+                }
+                throw serialization_error("Invalid varint found while parsing an ONNX file.");
+            }
+
+            float read_float (
+            )
+            {
+                const std::string bytes = read_raw(4);
+                return decode_floats(bytes)[0];
+            }
+
+            std::string read_bytes (
+            )
+            {
+                return read_raw(read_varint());
+            }
+
+            std::vector<int64_t> read_ints (
+                uint32_t wire_type
+            )
+            {
+                std::vector<int64_t> values;
+                if (wire_type == 2)
+                {
+                    onnx_proto_reader packed(read_bytes());
+                    while (!packed.at_end())
+                        values.push_back(static_cast<int64_t>(packed.read_varint()));
+                }
+                else
+                {
+                    values.push_back(static_cast<int64_t>(read_varint()));
+                }
+                return values;
+            }
+
+            std::vector<float> read_floats (
+                uint32_t wire_type
+            )
+            {
+                if (wire_type == 2)
+                    return decode_floats(read_bytes());
+                return std::vector<float>(1, read_float());
+            }
+
+            void skip (
+                uint32_t wire_type
+            )
+            {
+                switch (wire_type)
+                {
+                    case 0: read_varint(); break;
+                    case 1: read_raw(8); break;
+                    case 2: read_bytes(); break;
+                    case 5: read_raw(4); break;
+                    default:
+                        throw serialization_error("Unsupported protobuf wire type found while parsing an ONNX file.");
+                }
+            }
+
+            bool at_end() const { return pos == data.size(); }
+
+            static std::vector<float> decode_floats (
+                const std::string& bytes
+            )
+            {
+                if (bytes.size()%4 != 0)
+                    throw serialization_error("Corrupt float data found while parsing an ONNX file.");
+                std::vector<float> values(bytes.size()/4);
+                for (size_t i = 0; i < values.size(); ++i)
+                {
+                    uint32_t u = 0;
+                    for (int j = 0; j < 4; ++j)
+                        u |= static_cast<uint32_t>(static_cast<uint8_t>(bytes[4*i+j])) << (8*j);
+                    std::memcpy(&values[i], &u, sizeof(u));
+                }
+                return values;
+            }
+
+            static std::vector<int64_t> decode_int64s (
+                const std::string& bytes
+            )
+            {
+                if (bytes.size()%8 != 0)
+                    throw serialization_error("Corrupt int64 data found while parsing an ONNX file.");
+                std::vector<int64_t> values(bytes.size()/8);
+                for (size_t i = 0; i < values.size(); ++i)
+                {
+                    uint64_t u = 0;
+                    for (int j = 0; j < 8; ++j)
+                        u |= static_cast<uint64_t>(static_cast<uint8_t>(bytes[8*i+j])) << (8*j);
+                    values[i] = static_cast<int64_t>(u);
+                }
+                return values;
+            }
+
+        private:
+
+            std::string read_raw (
+                uint64_t size
+            )
+            {
+                if (static_cast<uint64_t>(data.size()-pos) < size)
+                    throw serialization_error("Unexpected end of data while parsing an ONNX file.");
+                std::string temp = data.substr(pos, size);
+                pos += size;
+                return temp;
+            }
+
+            std::string data;
+            size_t pos;
+        };
+
+    // ------------------------------------------------------------------------------------
+
+        struct onnx_attribute
+        {
+            // These values come from the AttributeProto::AttributeType enum.
+            enum attribute_type
+            {
+                UNDEFINED = 0,
+                FLOAT = 1,
+                INT = 2,
+                STRING = 3,
+                FLOATS = 6,
+                INTS = 7
+            };
+
+            std::string name;
+            int type = UNDEFINED;
+            float f = 0;
+            int64_t i = 0;
+            std::string s;
+            std::vector<float> floats;
+            std::vector<int64_t> ints;
+        };
+
+        struct onnx_node
+        {
+            std::string name;
+            std::string op_type;
+            std::vector<std::string> inputs;
+            std::vector<std::string> outputs;
+            std::vector<onnx_attribute> attributes;
+
+            const onnx_attribute* find_attribute (
+                const std::string& attr_name
+            ) const
+            {
+                for (auto& a : attributes)
+                {
+                    if (a.name == attr_name)
+                        return &a;
+                }
+                return nullptr;
+            }
+
+            int64_t get_int (
+                const std::string& attr_name,
+                int64_t default_value
+            ) const
+            {
+                auto a = find_attribute(attr_name);
+                return a ? a->i : default_value;
+            }
+
+            float get_float (
+                const std::string& attr_name,
+                float default_value
+            ) const
+            {
+                auto a = find_attribute(attr_name);
+                return a ? a->f : default_value;
+            }
+
+            std::string get_string (
+                const std::string& attr_name,
+                const std::string& default_value
+            ) const
+            {
+                auto a = find_attribute(attr_name);
+                return a ? a->s : default_value;
+            }
+
+            std::vector<int64_t> get_ints (
+                const std::string& attr_name
+            ) const
+            {
+                auto a = find_attribute(attr_name);
+                return a ? a->ints : std::vector<int64_t>();
+            }
+        };
+
+        struct onnx_tensor
+        {
+            // These values come from the TensorProto::DataType enum.
+            enum data_type
+            {
+                FLOAT = 1,
+                INT64 = 7
+            };
+
+            std::string name;
+            int type = FLOAT;
+            std::vector<int64_t> dims;
+            std::vector<float> float_data;
+            std::vector<int64_t> int64_data;
+
+            size_t size() const
+            {
+                size_t num = 1;
+                for (auto d : dims)
+                    num *= d;
+                return num;
+            }
+        };
+
+        struct onnx_value_info
+        {
+            std::string name;
+            int elem_type = onnx_tensor::FLOAT;
+            // A dimension with a value of -1 is symbolic and its name is stored in
+            // dim_params.
+            std::vector<int64_t> dims;
+            std::vector<std::string> dim_params;
+            bool has_shape = false;
+        };
+
+        struct onnx_model
+        {
+            int64_t ir_version = 7;
+            int64_t opset_version = 13;
+            std::string producer_name = "dlib";
+            std::string graph_name = "dlib";
+            std::vector<onnx_node> nodes;
+            std::vector<onnx_tensor> initializers;
+            std::vector<onnx_value_info> inputs;
+            std::vector<onnx_value_info> outputs;
+
+            const onnx_tensor* find_initializer (
+                const std::string& name
+            ) const
+            {
+                for (auto& t : initializers)
+                {
+                    if (t.name == name)
+                        return &t;
+                }
+                return nullptr;
+            }
+        };
+
+    // ------------------------------------------------------------------------------------
+
+        inline onnx_proto_writer encode_onnx_attribute (
+            const onnx_attribute& a
+        )
+        {
+            onnx_proto_writer w;
+            w.write_bytes(1, a.name);
+            switch (a.type)
+            {
+                case onnx_attribute::FLOAT: w.write_float(2, a.f); break;
+                case onnx_attribute::INT: w.write_int(3, a.i); break;
+                case onnx_attribute::STRING: w.write_bytes(4, a.s); break;
+                case onnx_attribute::FLOATS: w.write_packed_floats(7, a.floats); break;
+                case onnx_attribute::INTS: w.write_packed_ints(8, a.ints); break;
+                default: DLIB_CASSERT(false, "Unsupported ONNX attribute type: " << a.type);
+            }
+            w.write_int(20, a.type);
+            return w;
+        }
+
+        inline onnx_proto_writer encode_onnx_node (
+            const onnx_node& n
+        )
+        {
+            onnx_proto_writer w;
+            for (auto& in : n.inputs)
+                w.write_bytes(1, in);
+            for (auto& out : n.outputs)
+                w.write_bytes(2, out);
+            w.write_bytes(3, n.name);
+            w.write_bytes(4, n.op_type);
+            for (auto& a : n.attributes)
+                w.write_message(5, encode_onnx_attribute(a));
+            return w;
+        }
+
+        inline onnx_proto_writer encode_onnx_tensor (
+            const onnx_tensor& t
+        )
+        {
+            onnx_proto_writer w;
+            w.write_packed_ints(1, t.dims);
+            w.write_int(2, t.type);
+            w.write_bytes(8, t.name);
+            if (t.type == onnx_tensor::FLOAT)
+                w.write_bytes(9, onnx_proto_writer::encode_floats(t.float_data));
+            else
+                w.write_bytes(9, onnx_proto_writer::encode_int64s(t.int64_data));
+            return w;
+        }
+
+        inline onnx_proto_writer encode_onnx_value_info (
+            const onnx_value_info& v
+        )
+        {
+            onnx_proto_writer tensor_type;
+            tensor_type.write_int(1, v.elem_type);
+            if (v.has_shape)
+            {
+                onnx_proto_writer shape;
+                for (size_t i = 0; i < v.dims.size(); ++i)
+                {
+                    onnx_proto_writer dim;
+                    if (v.dims[i] >= 0)
+                        dim.write_int(1, v.dims[i]);
+                    else
+                        dim.write_bytes(2, v.dim_params[i]);
+                    shape.write_message(1, dim);
+                }
+                tensor_type.write_message(2, shape);
+            }
+            onnx_proto_writer type;
+            type.write_message(1, tensor_type);
+
+            onnx_proto_writer w;
+            w.write_bytes(1, v.name);
+            w.write_message(2, type);
+            return w;
+        }
+
+        inline std::string serialize_onnx_model (
+            const onnx_model& model
+        )
+        {
+            onnx_proto_writer graph;
+            for (auto& n : model.nodes)
+                graph.write_message(1, encode_onnx_node(n));
+            graph.write_bytes(2, model.graph_name);
+            for (auto& t : model.initializers)
+                graph.write_message(5, encode_onnx_tensor(t));
+            for (auto& v : model.inputs)
+                graph.write_message(11, encode_onnx_value_info(v));
+            for (auto& v : model.outputs)
+                graph.write_message(12, encode_onnx_value_info(v));
+
+            onnx_proto_writer opset;
+            opset.write_bytes(1, "");
+            opset.write_int(2, model.opset_version);
+
+            onnx_proto_writer w;
+            w.write_int(1, model.ir_version);
+            w.write_bytes(2, model.producer_name);
+            w.write_message(7, graph);
+            w.write_message(8, opset);
+            return w.str();
+        }
+
+    // ------------------------------------------------------------------------------------
+
+        inline onnx_attribute decode_onnx_attribute (
+            const std::string& data
+        )
+        {
+            onnx_attribute a;
+            onnx_proto_reader r(data);
+            uint32_t field, wire_type;
+            while (r.next(field, wire_type))
+            {
+                switch (field)
+                {
+                    case 1: a.name = r.read_bytes(); break;
+                    case 2: a.f = r.read_float(); break;
+                    case 3: a.i = static_cast<int64_t>(r.read_varint()); break;
+                    case 4: a.s = r.read_bytes(); break;
+                    case 7:
+                        {
+                            auto temp = r.read_floats(wire_type);
+                            a.floats.insert(a.floats.end(), temp.begin(), temp.end());
+                        } break;
+                    case 8:
+                        {
+                            auto temp = r.read_ints(wire_type);
+                            a.ints.insert(a.ints.end(), temp.begin(), temp.end());
+                        } break;
+                    case 20: a.type = static_cast<int>(r.read_varint()); break;
+                    default: r.skip(wire_type);
+                }
+            }
+            return a;
+        }
+
+        inline onnx_node decode_onnx_node (
+            const std::string& data
+        )
+        {
+            onnx_node n;
+            onnx_proto_reader r(data);
+            uint32_t field, wire_type;
+            while (r.next(field, wire_type))
+            {
+                switch (field)
+                {
+                    case 1: n.inputs.push_back(r.read_bytes()); break;
+                    case 2: n.outputs.push_back(r.read_bytes()); break;
+                    case 3: n.name = r.read_bytes(); break;
+                    case 4: n.op_type = r.read_bytes(); break;
+                    case 5: n.attributes.push_back(decode_onnx_attribute(r.read_bytes())); break;
+                    default: r.skip(wire_type);
+                }
+            }
+            return n;
+        }
+
+        inline onnx_tensor decode_onnx_tensor (
+            const std::string& data
+        )
+        {
+            onnx_tensor t;
+            std::string raw_data;
+            onnx_proto_reader r(data);
+            uint32_t field, wire_type;
+            while (r.next(field, wire_type))
+            {
+                switch (field)
+                {
+                    case 1:
+                        {
+                            auto temp = r.read_ints(wire_type);
+                            t.dims.insert(t.dims.end(), temp.begin(), temp.end());
+                        } break;
+                    case 2: t.type = static_cast<int>(r.read_varint()); break;
+                    case 4:
+                        {
+                            auto temp = r.read_floats(wire_type);
+                            t.float_data.insert(t.float_data.end(), temp.begin(), temp.end());
+                        } break;
+                    case 7:
+                        {
+                            auto temp = r.read_ints(wire_type);
+                            t.int64_data.insert(t.int64_data.end(), temp.begin(), temp.end());
+                        } break;
+                    case 8: t.name = r.read_bytes(); break;
+                    case 9: raw_data = r.read_bytes(); break;
+                    default: r.skip(wire_type);
+                }
+            }
+
+            if (!raw_data.empty())
+            {
+                if (t.type == onnx_tensor::FLOAT)
+                    t.float_data = onnx_proto_reader::decode_floats(raw_data);
+                else if (t.type == onnx_tensor::INT64)
+                    t.int64_data = onnx_proto_reader::decode_int64s(raw_data);
+            }
+            return t;
+        }
+
+        inline onnx_value_info decode_onnx_value_info (
+            const std::string& data
+        )
+        {
+            onnx_value_info v;
+            onnx_proto_reader r(data);
+            uint32_t field, wire_type;
+            while (r.next(field, wire_type))
+            {
+                if (field == 1)
+                {
+                    v.name = r.read_bytes();
+                }
+                else if (field == 2)
+                {
+                    // TypeProto
+                    onnx_proto_reader type(r.read_bytes());
+                    while (type.next(field, wire_type))
+                    {
+                        if (field != 1)
+                        {
+                            type.skip(wire_type);
+                            continue;
+                        }
+                        // TypeProto::Tensor
+                        onnx_proto_reader tensor_type(type.read_bytes());
+                        while (tensor_type.next(field, wire_type))
+                        {
+                            if (field == 1)
+                            {
+                                v.elem_type = static_cast<int>(tensor_type.read_varint());
+                            }
+                            else if (field == 2)
+                            {
+                                v.has_shape = true;
+                                onnx_proto_reader shape(tensor_type.read_bytes());
+                                while (shape.next(field, wire_type))
+                                {
+                                    if (field != 1)
+                                    {
+                                        shape.skip(wire_type);
+                                        continue;
+                                    }
+                                    int64_t dim_value = -1;
+                                    std::string dim_param;
+                                    onnx_proto_reader dim(shape.read_bytes());
+                                    while (dim.next(field, wire_type))
+                                    {
+                                        if (field == 1)
+                                            dim_value = static_cast<int64_t>(dim.read_varint());
+                                        else if (field == 2)
+                                            dim_param = dim.read_bytes();
+                                        else
+                                            dim.skip(wire_type);
+                                    }
+                                    v.dims.push_back(dim_value);
+                                    v.dim_params.push_back(dim_param);
+                                }
+                            }
+                            else
+                            {
+                                tensor_type.skip(wire_type);
+                            }
+                        }
+                    }
+                }
+                else
+                {
+                    r.skip(wire_type);
+                }
+            }
+            return v;
+        }
+
+        inline onnx_model parse_onnx_model (
+            const std::string& data
+        )
+        {
+            onnx_model model;
+            model.producer_name.clear();
+            model.graph_name.clear();
+            bool found_graph = false;
+            onnx_proto_reader r(data);
+            uint32_t field, wire_type;
+            while (r.next(field, wire_type))
+            {
+                if (field == 1)
+                {
+                    model.ir_version = static_cast<int64_t>(r.read_varint());
+                }
+                else if (field == 2)
+                {
+                    model.producer_name = r.read_bytes();
+                }
+                else if (field == 7)
+                {
+                    found_graph = true;
+                    onnx_proto_reader graph(r.read_bytes());
+                    while (graph.next(field, wire_type))
+                    {
+                        switch (field)
+                        {
+                            case 1: model.nodes.push_back(decode_onnx_node(graph.read_bytes())); break;
+                            case 2: model.graph_name = graph.read_bytes(); break;
+                            case 5: model.initializers.push_back(decode_onnx_tensor(graph.read_bytes())); break;
+                            case 11: model.inputs.push_back(decode_onnx_value_info(graph.read_bytes())); break;
+                            case 12: model.outputs.push_back(decode_onnx_value_info(graph.read_bytes())); break;
+                            default: graph.skip(wire_type);
+                        }
+                    }
+                }
+                else if (field == 8)
+                {
+                    onnx_proto_reader opset(r.read_bytes());
+                    std::string domain;
+                    int64_t version = 0;
+                    while (opset.next(field, wire_type))
+                    {
+                        if (field == 1)
+                            domain = opset.read_bytes();
+                        else if (field == 2)
+                            version = static_cast<int64_t>(opset.read_varint());
+                        else
+                            opset.skip(wire_type);
+                    }
+                    if (domain.empty() || domain == "ai.onnx")
+                        model.opset_version = version;
+                }
+                else
+                {
+                    r.skip(wire_type);
+                }
+            }
+
+            if (!found_graph)
+                throw serialization_error("The data given to dlib's ONNX parser doesn't contain an ONNX graph.");
+
+            // Graph inputs are allowed to also list the initializers.  We only want the
+            // real inputs.
+            std::vector<onnx_value_info> real_inputs;
+            for (auto& v : model.inputs)
+            {
+                if (!model.find_initializer(v.name))
+                    real_inputs.push_back(v);
+            }
+            model.inputs.swap(real_inputs);
+            return model;
+        }
+
+    // ------------------------------------------------------------------------------------
+
+        template <typename T>
+        std::string onnx_layer_type_name (
+            const T& item
+        )
+        {
+            // The first word printed by a layer's operator<< is the name of its type.
+            std::ostringstream sout;
+            sout << item;
+            const std::string name = sout.str();
+            return name.substr(0, name.find_first_of(" \t("));
+        }
+
+        class onnx_graph_builder
+        {
+            /*
+                This object converts each layer of a dlib network into ONNX nodes.  It
+                walks the network from the input to the output and keeps track of the
+                name and shape (k,nr,nc) of the tensor produced by the most recent layer.
+            */
+        public:
+
+            onnx_graph_builder(
+                onnx_model& model_,
+                long k,
+                long nr,
+                long nc
+            ) : model(model_), current("input")
+            {
+                shapes[current] = {k, nr, nc};
+            }
+
+            const std::string& output_name() const { return current; }
+            const std::vector<long>& output_shape() const { return shapes.at(current); }
+
+            template <typename input_layer_type>
+            void operator()(size_t , const input_layer_type& )
+            {
+                // The input layer is represented by the graph's input tensor.
+            }
+
+            template <typename T, typename U>
+            void operator()(size_t , const add_loss_layer<T,U>& )
+            {
+                // Loss layers aren't part of the exported graph, the graph outputs what
+                // the loss layer's input would be.
+            }
+
+            template <typename T, typename U, typename E>
+            void operator()(size_t idx, const add_layer<T,U,E>& l)
+            {
+                convert(idx, l.layer_details());
+            }
+
+            template <unsigned long ID, typename U, typename E>
+            void operator()(size_t , const add_tag_layer<ID,U,E>& )
+            {
+                tags[ID] = current;
+            }
+
+            template <template<typename> class TAG_TYPE, typename U>
+            void operator()(size_t , const add_skip_layer<TAG_TYPE,U>& )
+            {
+                current = tag_output(tag_id<TAG_TYPE>::id);
+            }
+
+        private:
+
+            template <typename T>
+            void convert(size_t idx, const T& item)
+            {
+                throw error("net_to_onnx(): layer " + std::to_string(idx) + " of type '" + onnx_layer_type_name(item) +
+                    "' can't be exported to ONNX.");
+            }
+
+            template <long nf, long nr, long nc, int sy, int sx, int py, int px, int dy, int dx, long g>
+            void convert(size_t idx, const con_<nf,nr,nc,sy,sx,py,px,dy,dx,g>& item)
+            {
+                const auto& in_shape = shapes.at(current);
+                const tensor& params = item.get_layer_params();
+                const long num_biases = item.bias_is_disabled() ? 0 : item.num_filters();
+                const long filt_nr = item.nr()!=0 ? item.nr() : in_shape[1];
+                const long filt_nc = item.nc()!=0 ? item.nc() : in_shape[2];
+                const long k_in = (params.size()-num_biases)/item.num_filters()/filt_nr/filt_nc;
+                DLIB_CASSERT(k_in*item.groups() == in_shape[0], "net_to_onnx(): the con_ layer " << idx <<
+                    " doesn't match the number of channels in its input. Has the network been allocated?");
+
+                const std::string base = layer_name(idx);
+                std::vector<std::string> inputs = {current, base+"_weights"};
+                add_initializer(inputs[1], {item.num_filters(), k_in, filt_nr, filt_nc}, params.host(), 0);
+                if (num_biases != 0)
+                {
+                    inputs.push_back(base+"_biases");
+                    add_initializer(inputs[2], {item.num_filters()}, params.host(), params.size()-num_biases);
+                }
+
+                const long out_nr = 1+(in_shape[1]+2*item.padding_y()-(item.dilation_y()*(filt_nr-1)+1))/item.stride_y();
+                const long out_nc = 1+(in_shape[2]+2*item.padding_x()-(item.dilation_x()*(filt_nc-1)+1))/item.stride_x();
+                onnx_node& n = add_node("Conv", base, inputs, {item.num_filters(), out_nr, out_nc});
+                add_ints(n, "kernel_shape", {filt_nr, filt_nc});
+                add_ints(n, "strides", {item.stride_y(), item.stride_x()});
+                add_ints(n, "pads", {item.padding_y(), item.padding_x(), item.padding_y(), item.padding_x()});
+                if (item.dilation_y() != 1 || item.dilation_x() != 1)
+                    add_ints(n, "dilations", {item.dilation_y(), item.dilation_x()});
+                if (item.groups() != 1)
+                    add_int(n, "group", item.groups());
+
+                if (!item.relu_is_disabled())
+                    add_node("Relu", base+"_relu", {current}, shapes.at(current));
+            }
+
+            template <unsigned long no, fc_bias_mode bm>
+            void convert(size_t idx, const fc_<no,bm>& item)
+            {
+                const auto& in_shape = shapes.at(current);
+                const long num_inputs = in_shape[0]*in_shape[1]*in_shape[2];
+                const long num_outputs = item.get_num_outputs();
+                const tensor& params = item.get_layer_params();
+                DLIB_CASSERT(params.size() >= (size_t)num_inputs*num_outputs, "net_to_onnx(): the fc_ layer " << idx <<
+                    " doesn't match the size of its input. Has the network been allocated?");
+
+                const std::string base = layer_name(idx);
+                add_node("Flatten", base+"_flatten", {current}, {num_inputs, 1, 1});
+                add_int(model.nodes.back(), "axis", 1);
+
+                std::vector<std::string> inputs = {current, base+"_weights"};
+                add_initializer(inputs[1], {num_inputs, num_outputs}, params.host(), 0);
+                if (params.size() > (size_t)num_inputs*num_outputs)
+                {
+                    inputs.push_back(base+"_biases");
+                    add_initializer(inputs[2], {num_outputs}, params.host(), num_inputs*num_outputs);
+                }
+                add_node("Gemm", base+"_gemm", inputs, {num_outputs, 1, 1});
+
+                // dlib tensors are always 4D so we reshape the output of Gemm to match.
+                add_int64_initializer(base+"_shape", {0, num_outputs, 1, 1});
+                add_node("Reshape", base, {current, base+"_shape"}, {num_outputs, 1, 1});
+            }
+
+            template <layer_mode mode>
+            void convert(size_t idx, const bn_<mode>& item)
+            {
+                // At inference time batch normalization is just an affine transform.
+                convert(idx, affine_(item));
+            }
+
+            void convert(size_t idx, const affine_& item)
+            {
+                if (item.is_disabled())
+                    return;
+                add_scale_and_shift(idx, item.get_mode(), item.get_gamma(), item.get_beta());
+            }
+
+            void convert(size_t idx, const relu_& item)
+            {
+                if (item.is_disabled())
+                    return;
+                add_node("Relu", layer_name(idx), {current}, shapes.at(current));
+            }
+
+            void convert(size_t idx, const sig_& )
+            {
+                add_node("Sigmoid", layer_name(idx), {current}, shapes.at(current));
+            }
+
+            void convert(size_t idx, const htan_& )
+            {
+                add_node("Tanh", layer_name(idx), {current}, shapes.at(current));
+            }
+
+            void convert(size_t idx, const leaky_relu_& item)
+            {
+                add_node("LeakyRelu", layer_name(idx), {current}, shapes.at(current));
+                add_float(model.nodes.back(), "alpha", item.get_alpha());
+            }
+
+            void convert(size_t idx, const gelu_& )
+            {
+                // gelu(x) = 0.5*x*(1+erf(x/sqrt(2)))
+                const std::string base = layer_name(idx);
+                const std::string x = current;
+                const auto shape = shapes.at(x);
+                add_scalar_initializer(base+"_sqrt2", std::sqrt(2.0f));
+                add_scalar_initializer(base+"_one", 1.0f);
+                add_scalar_initializer(base+"_half", 0.5f);
+                add_node("Div", base+"_div", {x, base+"_sqrt2"}, shape);
+                add_node("Erf", base+"_erf", {current}, shape);
+                add_node("Add", base+"_add", {current, base+"_one"}, shape);
+                add_node("Mul", base+"_mul", {current, x}, shape);
+                add_node("Mul", base, {current, base+"_half"}, shape);
+            }
+
+            void convert(size_t idx, const silu_& )
+            {
+                // silu(x) = x*sigmoid(x)
+                const std::string base = layer_name(idx);
+                const std::string x = current;
+                add_node("Sigmoid", base+"_sigmoid", {x}, shapes.at(x));
+                add_node("Mul", base, {x, current}, shapes.at(x));
+            }
+
+            void convert(size_t idx, const softmax_& )
+            {
+                // dlib's softmax_ normalizes across channels at each spatial location.
+                add_node("Softmax", layer_name(idx), {current}, shapes.at(current));
+                add_int(model.nodes.back(), "axis", 1);
+            }
+
+            void convert(size_t , const dropout_& )
+            {
+                // dropout is the identity function at inference time.
+            }
+
+            template <int DROP_RATE_PERCENT>
+            void convert(size_t , const dropout_rate_<DROP_RATE_PERCENT>& )
+            {
+            }
+
+            void convert(size_t idx, const multiply_& item)
+            {
+                const std::string base = layer_name(idx);
+                add_scalar_initializer(base+"_value", item.get_multiply_value());
+                add_node("Mul", base, {current, base+"_value"}, shapes.at(current));
+            }
+
+            void convert(size_t idx, const layer_norm_& item)
+            {
+                // dlib normalizes each sample using the mean and variance of all its
+                // elements and then applies a per channel scale and shift.
+                const std::string base = layer_name(idx);
+                const std::string x = current;
+                const auto shape = shapes.at(x);
+
+                add_node("ReduceMean", base+"_mean", {x}, {1, 1, 1});
+                add_ints(model.nodes.back(), "axes", {1, 2, 3});
+                add_node("Sub", base+"_centered", {x, current}, shape);
+                const std::string centered = current;
+                add_node("Mul", base+"_squared", {centered, centered}, shape);
+                add_node("ReduceMean", base+"_var", {current}, {1, 1, 1});
+                add_ints(model.nodes.back(), "axes", {1, 2, 3});
+                add_scalar_initializer(base+"_eps", item.get_eps());
+                add_node("Add", base+"_var_eps", {current, base+"_eps"}, {1, 1, 1});
+                add_node("Sqrt", base+"_std", {current}, {1, 1, 1});
+                add_node("Div", base+"_normalized", {centered, current}, shape);
+
+                const tensor& params = item.get_layer_params();
+                const long k = params.size()/2;
+                add_initializer(base+"_gamma", {k, 1, 1}, params.host(), 0);
+                add_initializer(base+"_beta", {k, 1, 1}, params.host(), k);
+                add_node("Mul", base+"_scaled", {current, base+"_gamma"}, shape);
+                add_node("Add", base, {current, base+"_beta"}, shape);
+            }
+
+            template <long nr, long nc, int sy, int sx, int py, int px>
+            void convert(size_t idx, const max_pool_<nr,nc,sy,sx,py,px>& item)
+            {
+                add_pooling(idx, "MaxPool", nr, nc, sy, sx, item.padding_y(), item.padding_x());
+            }
+
+            template <long nr, long nc, int sy, int sx, int py, int px>
+            void convert(size_t idx, const avg_pool_<nr,nc,sy,sx,py,px>& item)
+            {
+                add_pooling(idx, "AveragePool", nr, nc, sy, sx, item.padding_y(), item.padding_x());
+            }
+
+            template <int sy, int sx>
+            void convert(size_t idx, const upsample_<sy,sx>& )
+            {
+                // dlib's bilinear resizing maps the corner pixels onto each other.
+                const std::string base = layer_name(idx);
+                const auto& in_shape = shapes.at(current);
+                add_float_initializer(base+"_scales", {4}, {1, 1, (float)sy, (float)sx});
+                add_node("Resize", base, {current, "", base+"_scales"}, {in_shape[0], in_shape[1]*sy, in_shape[2]*sx});
+                add_string(model.nodes.back(), "mode", "linear");
+                add_string(model.nodes.back(), "coordinate_transformation_mode", "align_corners");
+            }
+
+            template <template<typename> class TAG>
+            void convert(size_t idx, const add_prev_<TAG>& )
+            {
+                add_binary_op(idx, "Add", tag_output(tag_id<TAG>::id));
+            }
+
+            template <template<typename> class TAG>
+            void convert(size_t idx, const mult_prev_<TAG>& )
+            {
+                add_binary_op(idx, "Mul", tag_output(tag_id<TAG>::id));
+            }
+
+            template <template<typename> class... TAGS>
+            void convert(size_t idx, const concat_<TAGS...>& )
+            {
+                std::ostringstream sout;
+                concat_helper_impl<TAGS...>::list_tags(sout);
+                std::vector<std::string> inputs;
+                std::vector<long> shape;
+                for (const auto& tag : dlib::split(sout.str(), ","))
+                {
+                    inputs.push_back(tag_output(std::stoul(tag)));
+                    const auto& s = shapes.at(inputs.back());
+                    if (shape.empty())
+                    {
+                        shape = s;
+                    }
+                    else
+                    {
+                        DLIB_CASSERT(s[1] == shape[1] && s[2] == shape[2],
+                            "net_to_onnx(): the inputs to the concat_ layer " << idx << " have different sizes.");
+                        shape[0] += s[0];
+                    }
+                }
+                add_node("Concat", layer_name(idx), inputs, shape);
+                add_int(model.nodes.back(), "axis", 1);
+            }
+
+        // ------------------------------------------------------------------------------------
+
+            static std::string layer_name (
+                size_t idx
+            )
+            {
+                return "layer" + std::to_string(idx);
+            }
+
+            const std::string& tag_output (
+                unsigned long id
+            ) const
+            {
+                auto i = tags.find(id);
+                DLIB_CASSERT(i != tags.end(), "net_to_onnx(): tag " << id << " was used before being defined.");
+                return i->second;
+            }
+
+            onnx_node& add_node (
+                const std::string& op_type,
+                const std::string& name,
+                const std::vector<std::string>& inputs,
+                const std::vector<long>& out_shape
+            )
+            {
+                onnx_node n;
+                n.op_type = op_type;
+                n.name = name;
+                n.inputs = inputs;
+                n.outputs = {name};
+                model.nodes.push_back(n);
+                current = name;
+                shapes[current] = out_shape;
+                return model.nodes.back();
+            }
+
+            void add_binary_op (
+                size_t idx,
+                const std::string& op_type,
+                const std::string& other
+            )
+            {
+                const auto shape = shapes.at(current);
+                DLIB_CASSERT(shape == shapes.at(other),
+                    "net_to_onnx(): layer " << idx << " combines tensors of different sizes, which ONNX doesn't support.");
+                add_node(op_type, layer_name(idx), {current, other}, shape);
+            }
+
+            void add_pooling (
+                size_t idx,
+                const std::string& op_type,
+                long nr,
+                long nc,
+                long sy,
+                long sx,
+                long py,
+                long px
+            )
+            {
+                const auto& in_shape = shapes.at(current);
+                if (nr == 0 && nc == 0)
+                {
+                    add_node("Global"+op_type, layer_name(idx), {current}, {in_shape[0], 1, 1});
+                    return;
+                }
+                nr = nr!=0 ? nr : in_shape[1];
+                nc = nc!=0 ? nc : in_shape[2];
+                const long out_nr = 1+(in_shape[1]+2*py-nr)/sy;
+                const long out_nc = 1+(in_shape[2]+2*px-nc)/sx;
+                onnx_node& n = add_node(op_type, layer_name(idx), {current}, {in_shape[0], out_nr, out_nc});
+                add_ints(n, "kernel_shape", {nr, nc});
+                add_ints(n, "strides", {sy, sx});
+                add_ints(n, "pads", {py, px, py, px});
+            }
+
+            void add_scale_and_shift (
+                size_t idx,
+                layer_mode mode,
+                const tensor& gamma,
+                const tensor& beta
+            )
+            {
+                const std::string base = layer_name(idx);
+                const auto shape = shapes.at(current);
+                std::vector<long> dims;
+                if (mode == CONV_MODE)
+                    dims = {gamma.k(), 1, 1};
+                else
+                    dims = {gamma.k(), gamma.nr(), gamma.nc()};
+                add_initializer(base+"_gamma", dims, gamma.host(), 0);
+                add_initializer(base+"_beta", dims, beta.host(), 0);
+                add_node("Mul", base+"_scaled", {current, base+"_gamma"}, shape);
+                add_node("Add", base, {current, base+"_beta"}, shape);
+            }
+
+            void add_initializer (
+                const std::string& name,
+                const std::vector<long>& dims,
+                const float* data,
+                size_t offset
+            )
+            {
+                onnx_tensor t;
+                t.name = name;
+                t.type = onnx_tensor::FLOAT;
+                t.dims.assign(dims.begin(), dims.end());
+                t.float_data.assign(data+offset, data+offset+t.size());
+                model.initializers.push_back(t);
+            }
+
+            void add_float_initializer (
+                const std::string& name,
+                const std::vector<long>& dims,
+                const std::vector<float>& values
+            )
+            {
+                add_initializer(name, dims, values.data(), 0);
+            }
+
+            void add_scalar_initializer (
+                const std::string& name,
+                float value
+            )
+            {
+                add_initializer(name, {}, &value, 0);
+            }
+
+            void add_int64_initializer (
+                const std::string& name,
+                const std::vector<int64_t>& values
+            )
+            {
+                onnx_tensor t;
+                t.name = name;
+                t.type = onnx_tensor::INT64;
+                t.dims = {(int64_t)values.size()};
+                t.int64_data = values;
+                model.initializers.push_back(t);
+            }
+
+            static void add_int (
+                onnx_node& n,
+                const std::string& name,
+                int64_t value
+            )
+            {
+                onnx_attribute a;
+                a.name = name;
+                a.type = onnx_attribute::INT;
+                a.i = value;
+                n.attributes.push_back(a);
+            }
+
+            static void add_ints (
+                onnx_node& n,
+                const std::string& name,
+                const std::vector<int64_t>& values
+            )
+            {
+                onnx_attribute a;
+                a.name = name;
+                a.type = onnx_attribute::INTS;
+                a.ints = values;
+                n.attributes.push_back(a);
+            }
+
+            static void add_float (
+                onnx_node& n,
+                const std::string& name,
+                float value
+            )
+            {
+                onnx_attribute a;
+                a.name = name;
+                a.type = onnx_attribute::FLOAT;
+                a.f = value;
+                n.attributes.push_back(a);
+            }
+
+            static void add_string (
+                onnx_node& n,
+                const std::string& name,
+                const std::string& value
+            )
+            {
+                onnx_attribute a;
+                a.name = name;
+                a.type = onnx_attribute::STRING;
+                a.s = value;
+                n.attributes.push_back(a);
+            }
+
+            onnx_model& model;
+            std::string current;
+            std::map<unsigned long, std::string> tags;
+            std::map<std::string, std::vector<long>> shapes;
+        };
+
+        class visitor_net_to_onnx
+        {
+        public:
+
+            visitor_net_to_onnx(onnx_graph_builder& builder_) : builder(builder_) {}
+
+            template <typename T>
+            void operator()(size_t idx, const T& l)
+            {
+                builder(idx, l);
+            }
+
+        private:
+
+            onnx_graph_builder& builder;
+        };
+
+    // ------------------------------------------------------------------------------------
+
+        struct onnx_weighted_op
+        {
+            /*
+                A node of an ONNX graph that holds the parameters of a dlib layer.  The kind
+                is the node's op_type, except when a MatMul or Mul is followed by an Add of
+                a bias vector, in which case the two nodes are treated as one op of kind
+                "MatMul+Add" or "Mul+Add".
+            */
+            std::string kind;
+            const onnx_node* node = nullptr;
+            const onnx_tensor* weights = nullptr;
+            const onnx_tensor* biases = nullptr;
+            const onnx_tensor* means = nullptr;
+            const onnx_tensor* variances = nullptr;
+        };
+
+        inline std::string onnx_op_description (
+            const onnx_weighted_op& op
+        )
+        {
+            const std::string& name = op.node->name.empty() ? op.node->outputs.at(0) : op.node->name;
+            return "the ONNX node '" + name + "' (" + op.kind + ")";
+        }
+
+        inline std::string onnx_shape_string (
+            const std::vector<int64_t>& dims
+        )
+        {
+            std::ostringstream sout;
+            sout << "[";
+            for (size_t i = 0; i < dims.size(); ++i)
+            {
+                if (i != 0)
+                    sout << ",";
+                sout << dims[i];
+            }
+            sout << "]";
+            return sout.str();
+        }
+
+        inline std::vector<onnx_weighted_op> find_onnx_weighted_ops (
+            const onnx_model& model
+        )
+        {
+            std::map<std::string, std::vector<size_t>> consumers;
+            for (size_t i = 0; i < model.nodes.size(); ++i)
+            {
+                for (auto& in : model.nodes[i].inputs)
+                    consumers[in].push_back(i);
+            }
+
+            auto float_initializer = [&](const onnx_node& n, size_t i) -> const onnx_tensor*
+            {
+                if (i >= n.inputs.size() || n.inputs[i].empty())
+                    return nullptr;
+                const onnx_tensor* t = model.find_initializer(n.inputs[i]);
+                return (t && t->type == onnx_tensor::FLOAT) ? t : nullptr;
+            };
+
+            // Scalar initializers are constants like the 0.5 in gelu, not parameters.
+            auto parameter_input = [&](const onnx_node& n) -> const onnx_tensor*
+            {
+                for (size_t i = 0; i < n.inputs.size(); ++i)
+                {
+                    const onnx_tensor* t = float_initializer(n, i);
+                    if (t && t->dims.size() != 0)
+                        return t;
+                }
+                return nullptr;
+            };
+
+            // Returns the index of the node that adds a bias to the output of n, or -1.
+            auto find_bias_add = [&](const onnx_node& n) -> long
+            {
+                auto c = consumers.find(n.outputs.at(0));
+                if (c == consumers.end() || c->second.size() != 1)
+                    return -1;
+                const onnx_node& next = model.nodes[c->second[0]];
+                if (next.op_type != "Add" || !parameter_input(next))
+                    return -1;
+                return c->second[0];
+            };
+
+            std::vector<bool> used(model.nodes.size(), false);
+            std::vector<onnx_weighted_op> ops;
+            for (size_t i = 0; i < model.nodes.size(); ++i)
+            {
+                if (used[i])
+                    continue;
+
+                const onnx_node& n = model.nodes[i];
+                onnx_weighted_op op;
+                op.kind = n.op_type;
+                op.node = &n;
+                if (n.op_type == "Conv" || n.op_type == "Gemm" || n.op_type == "LayerNormalization" ||
+                    n.op_type == "BatchNormalization")
+                {
+                    op.weights = float_initializer(n, 1);
+                    op.biases = float_initializer(n, 2);
+                    if (n.op_type == "BatchNormalization")
+                    {
+                        op.means = float_initializer(n, 3);
+                        op.variances = float_initializer(n, 4);
+                    }
+                    if (!op.weights || (n.op_type == "BatchNormalization" && (!op.biases || !op.means || !op.variances)))
+                    {
+                        throw onnx_import_error("onnx_to_net(): the parameters of " + onnx_op_description(op) +
+                            " aren't stored in the model's initializers.  Only models with constant weights can be loaded.");
+                    }
+                }
+                else if (n.op_type == "MatMul" || n.op_type == "Mul")
+                {
+                    op.weights = n.op_type == "MatMul" ? float_initializer(n, 1) : parameter_input(n);
+                    if (!op.weights || op.weights->dims.size() == 0)
+                        continue;
+                    const long j = find_bias_add(n);
+                    if (j >= 0)
+                    {
+                        used[j] = true;
+                        op.kind += "+Add";
+                        op.biases = parameter_input(model.nodes[j]);
+                    }
+                }
+                else if (n.op_type == "Add" || n.op_type == "Sub" || n.op_type == "Div" ||
+                    n.op_type == "PRelu" || n.op_type == "ConvTranspose")
+                {
+                    // These have parameters that dlib can't load.  We still record them so
+                    // they show up in the error messages rather than being silently ignored.
+                    op.weights = parameter_input(n);
+                    if (!op.weights)
+                        continue;
+                }
+                else
+                {
+                    continue;
+                }
+                ops.push_back(op);
+            }
+            return ops;
+        }
+
+    // ------------------------------------------------------------------------------------
+
+        class onnx_weight_loader
+        {
+            /*
+                This object walks a dlib network from the input to the output and pairs
+                each layer that has parameters with the next onnx_weighted_op.  If
+                copy_weights is false it only checks that the pairs are compatible,
+                otherwise it also copies the ONNX parameters into the layers.
+            */
+        public:
+
+            onnx_weight_loader(
+                const std::vector<onnx_weighted_op>& ops_,
+                bool copy_weights_
+            ) : ops(ops_), copy_weights(copy_weights_) {}
+
+            size_t num_ops_used() const { return next; }
+
+            template <typename T>
+            void operator()(size_t , T& )
+            {
+                // Input, loss, tag, and skip layers don't have parameters.
+            }
+
+            template <typename T, typename U, typename E>
+            void operator()(size_t idx, add_layer<T,U,E>& l)
+            {
+                load(idx, l.layer_details());
+            }
+
+        private:
+
+            template <typename T>
+            void load(size_t idx, T& item)
+            {
+                if (item.get_layer_params().size() != 0)
+                {
+                    throw onnx_import_error("onnx_to_net(): layer " + std::to_string(idx) + " (" + onnx_layer_type_name(item) +
+                        ") has parameters but dlib doesn't know how to load them from ONNX.");
+                }
+            }
+
+            template <long nf, long nr, long nc, int sy, int sx, int py, int px, int dy, int dx, long g>
+            void load(size_t idx, con_<nf,nr,nc,sy,sx,py,px,dy,dx,g>& item)
+            {
+                const onnx_weighted_op& op = next_op(idx, item, {"Conv"});
+                const onnx_node& n = *op.node;
+                tensor& params = item.get_layer_params();
+                DLIB_CASSERT(params.size() != 0, "onnx_to_net(): layer " << idx << " hasn't been allocated.");
+                const long num_filters = item.num_filters();
+                const long num_biases = item.bias_is_disabled() ? 0 : num_filters;
+                const long num_weights = params.size()-num_biases;
+                const long k = num_weights/(num_filters*item.nr()*item.nc());
+                const std::vector<int64_t> shape = {num_filters, k, item.nr(), item.nc()};
+                if (op.weights->dims != shape)
+                    throw mismatch(idx, item, op, "weights with shape " + onnx_shape_string(shape),
+                        "weights with shape " + onnx_shape_string(op.weights->dims));
+
+                check_ints(idx, item, op, "strides", n.get_ints("strides"), {item.stride_y(), item.stride_x()}, 1);
+                check_ints(idx, item, op, "pads", n.get_ints("pads"),
+                    {item.padding_y(), item.padding_x(), item.padding_y(), item.padding_x()}, 0);
+                check_ints(idx, item, op, "dilations", n.get_ints("dilations"), {item.dilation_y(), item.dilation_x()}, 1);
+                check_ints(idx, item, op, "group", {n.get_int("group", 1)}, {item.groups()}, 1);
+                const std::string auto_pad = n.get_string("auto_pad", "NOTSET");
+                if (auto_pad != "NOTSET" && !(auto_pad == "VALID" && item.padding_y() == 0 && item.padding_x() == 0))
+                    throw mismatch(idx, item, op, "explicit padding", "auto_pad=" + auto_pad);
+
+                if (op.biases)
+                    check_size(idx, item, op, "biases", op.biases, num_filters);
+
+                if (!copy_weights)
+                    return;
+
+                // The ONNX model has biases, so make sure the layer uses them.  It's common
+                // for them to be disabled by disable_duplicative_biases().
+                if (op.biases && item.bias_is_disabled())
+                    item.enable_bias();
+                float* p = item.get_layer_params().host();
+                std::copy(op.weights->float_data.begin(), op.weights->float_data.end(), p);
+                if (!item.bias_is_disabled())
+                {
+                    if (op.biases)
+                        std::copy(op.biases->float_data.begin(), op.biases->float_data.end(), p+num_weights);
+                    else
+                        std::fill(p+num_weights, p+num_weights+num_filters, 0);
+                }
+            }
+
+            template <unsigned long no, fc_bias_mode bm>
+            void load(size_t idx, fc_<no,bm>& item)
+            {
+                const onnx_weighted_op& op = next_op(idx, item, {"Gemm", "MatMul", "MatMul+Add"});
+                const onnx_node& n = *op.node;
+                tensor& params = item.get_layer_params();
+                DLIB_CASSERT(params.size() != 0, "onnx_to_net(): layer " << idx << " hasn't been allocated.");
+                const long num_outputs = item.get_num_outputs();
+                const bool has_bias = item.get_bias_mode() == FC_HAS_BIAS;
+                const long num_inputs = (params.size() - (has_bias ? num_outputs : 0))/num_outputs;
+
+                bool trans_b = false;
+                float alpha = 1, beta = 1;
+                if (op.kind == "Gemm")
+                {
+                    if (n.get_int("transA", 0) != 0)
+                        throw mismatch(idx, item, op, "an untransposed input", "transA=1");
+                    trans_b = n.get_int("transB", 0) != 0;
+                    alpha = n.get_float("alpha", 1);
+                    beta = n.get_float("beta", 1);
+                }
+                std::vector<int64_t> shape = {num_inputs, num_outputs};
+                if (trans_b)
+                    std::swap(shape[0], shape[1]);
+                if (op.weights->dims != shape)
+                    throw mismatch(idx, item, op, "weights with shape " + onnx_shape_string(shape),
+                        "weights with shape " + onnx_shape_string(op.weights->dims));
+
+                if (op.biases)
+                {
+                    check_size(idx, item, op, "biases", op.biases, num_outputs);
+                    if (!has_bias)
+                    {
+                        for (auto v : op.biases->float_data)
+                        {
+                            if (beta*v != 0)
+                                throw mismatch(idx, item, op, "no biases (it's an FC_NO_BIAS layer)", "non-zero biases");
+                        }
+                    }
+                }
+
+                if (!copy_weights)
+                    return;
+
+                float* p = params.host();
+                const auto& w = op.weights->float_data;
+                for (long i = 0; i < num_inputs; ++i)
+                {
+                    for (long j = 0; j < num_outputs; ++j)
+                        p[i*num_outputs+j] = alpha*(trans_b ? w[j*num_inputs+i] : w[i*num_outputs+j]);
+                }
+                if (has_bias)
+                {
+                    p += num_inputs*num_outputs;
+                    for (long j = 0; j < num_outputs; ++j)
+                        p[j] = op.biases ? beta*op.biases->float_data[j] : 0;
+                }
+            }
+
+            template <layer_mode mode>
+            void load(size_t idx, bn_<mode>& item)
+            {
+                const onnx_weighted_op& op = next_op(idx, item, {"BatchNormalization", "Mul+Add", "Mul"});
+                tensor& params = item.get_layer_params();
+                const size_t num = params.size()/2;
+                check_scale_and_shift(idx, item, op, num);
+
+                if (!copy_weights)
+                    return;
+
+                float* gamma = params.host();
+                float* beta = gamma + num;
+                float* means = item.get_running_means().host();
+                float* variances = item.get_running_variances().host();
+                std::copy(op.weights->float_data.begin(), op.weights->float_data.end(), gamma);
+                for (size_t i = 0; i < num; ++i)
+                    beta[i] = op.biases ? op.biases->float_data[i] : 0;
+                if (op.kind == "BatchNormalization")
+                {
+                    // Fold any difference between the ONNX and dlib epsilons into the
+                    // variances so the layer computes the same thing as the ONNX node.
+                    const double eps = op.node->get_float("epsilon", 1e-5f);
+                    for (size_t i = 0; i < num; ++i)
+                    {
+                        means[i] = op.means->float_data[i];
+                        variances[i] = std::max(0.0, op.variances->float_data[i] + eps - item.get_eps());
+                    }
+                }
+                else
+                {
+                    for (size_t i = 0; i < num; ++i)
+                    {
+                        means[i] = 0;
+                        variances[i] = std::max(0.0, 1 - item.get_eps());
+                    }
+                }
+            }
+
+            void load(size_t idx, affine_& item)
+            {
+                const onnx_weighted_op& op = next_op(idx, item, {"BatchNormalization", "Mul+Add", "Mul"});
+                auto g = item.get_gamma();
+                auto b = item.get_beta();
+                const size_t num = g.size();
+                check_scale_and_shift(idx, item, op, num);
+
+                if (!copy_weights)
+                    return;
+
+                float* gamma = g.host();
+                float* beta = b.host();
+                for (size_t i = 0; i < num; ++i)
+                {
+                    gamma[i] = op.weights->float_data[i];
+                    beta[i] = op.biases ? op.biases->float_data[i] : 0;
+                }
+                if (op.kind == "BatchNormalization")
+                {
+                    const double eps = op.node->get_float("epsilon", 1e-5f);
+                    for (size_t i = 0; i < num; ++i)
+                    {
+                        gamma[i] /= std::sqrt(op.variances->float_data[i] + eps);
+                        beta[i] -= op.means->float_data[i]*gamma[i];
+                    }
+                }
+            }
+
+            void load(size_t idx, layer_norm_& item)
+            {
+                const onnx_weighted_op& op = next_op(idx, item, {"LayerNormalization", "Mul+Add"});
+                if (op.kind == "LayerNormalization")
+                {
+                    // layer_norm_ normalizes each sample over all of k, nr, and nc.
+                    const int64_t axis = op.node->get_int("axis", -1);
+                    if (axis != 1 && axis != -3)
+                        throw mismatch(idx, item, op, "normalization over axis 1", "normalization over axis " + std::to_string(axis));
+                }
+                tensor& params = item.get_layer_params();
+                const size_t num = params.size()/2;
+                check_scale_and_shift(idx, item, op, num);
+
+                if (!copy_weights)
+                    return;
+
+                float* p = params.host();
+                for (size_t i = 0; i < num; ++i)
+                {
+                    p[i] = op.weights->float_data[i];
+                    p[num+i] = op.biases ? op.biases->float_data[i] : 0;
+                }
+            }
+
+        // ------------------------------------------------------------------------------------
+
+            template <typename T>
+            const onnx_weighted_op& next_op (
+                size_t idx,
+                const T& item,
+                const std::vector<std::string>& kinds
+            )
+            {
+                if (next >= ops.size())
+                {
+                    throw onnx_import_error("onnx_to_net(): layer " + std::to_string(idx) + " (" + onnx_layer_type_name(item) +
+                        ") has parameters but the ONNX model only has " + std::to_string(ops.size()) +
+                        " nodes with parameters and they have all been used by earlier layers.");
+                }
+                const onnx_weighted_op& op = ops[next++];
+                if (std::find(kinds.begin(), kinds.end(), op.kind) == kinds.end())
+                {
+                    std::string allowed;
+                    for (size_t i = 0; i < kinds.size(); ++i)
+                    {
+                        if (i != 0)
+                            allowed += (i+1 == kinds.size()) ? " or " : ", ";
+                        allowed += kinds[i];
+                    }
+                    throw onnx_import_error("onnx_to_net(): layer " + std::to_string(idx) + " (" + onnx_layer_type_name(item) +
+                        ") was paired with " + onnx_op_description(op) + " but it can only be loaded from " + allowed +
+                        " nodes.  The layers with parameters must appear in the same order in the dlib network and the ONNX model.");
+                }
+                return op;
+            }
+
+            template <typename T>
+            onnx_import_error mismatch (
+                size_t idx,
+                const T& item,
+                const onnx_weighted_op& op,
+                const std::string& expected,
+                const std::string& found
+            ) const
+            {
+                return onnx_import_error("onnx_to_net(): layer " + std::to_string(idx) + " (" + onnx_layer_type_name(item) +
+                    ") expects " + expected + " but " + onnx_op_description(op) + " has " + found + ".");
+            }
+
+            template <typename T>
+            void check_size (
+                size_t idx,
+                const T& item,
+                const onnx_weighted_op& op,
+                const std::string& what,
+                const onnx_tensor* t,
+                size_t expected
+            ) const
+            {
+                if (t->float_data.size() != expected)
+                {
+                    throw mismatch(idx, item, op, std::to_string(expected) + " " + what,
+                        std::to_string(t->float_data.size()) + " " + what + " " + onnx_shape_string(t->dims));
+                }
+            }
+
+            template <typename T>
+            void check_ints (
+                size_t idx,
+                const T& item,
+                const onnx_weighted_op& op,
+                const std::string& name,
+                std::vector<int64_t> values,
+                const std::vector<int64_t>& expected,
+                int64_t default_value
+            ) const
+            {
+                if (values.empty())
+                    values.assign(expected.size(), default_value);
+                if (values != expected)
+                    throw mismatch(idx, item, op, name + "=" + onnx_shape_string(expected), name + "=" + onnx_shape_string(values));
+            }
+
+            template <typename T>
+            void check_scale_and_shift (
+                size_t idx,
+                const T& item,
+                const onnx_weighted_op& op,
+                size_t num
+            ) const
+            {
+                check_size(idx, item, op, "scale values", op.weights, num);
+                if (op.biases)
+                    check_size(idx, item, op, "bias values", op.biases, num);
+                if (op.means)
+                    check_size(idx, item, op, "means", op.means, num);
+                if (op.variances)
+                    check_size(idx, item, op, "variances", op.variances, num);
+            }
+
+            const std::vector<onnx_weighted_op>& ops;
+            const bool copy_weights;
+            size_t next = 0;
+        };
+
+        class visitor_onnx_to_net
+        {
+        public:
+
+            visitor_onnx_to_net(onnx_weight_loader& loader_) : loader(loader_) {}
+
+            template <typename T>
+            void operator()(size_t idx, T& l)
+            {
+                loader(idx, l);
+            }
+
+        private:
+
+            onnx_weight_loader& loader;
+        };
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    template <typename net_type>
+    void net_to_onnx (
+        const net_type& net,
+        long k,
+        long nr,
+        long nc,
+        std::ostream& out
+    )
+    {
+        DLIB_CASSERT(k > 0 && nr > 0 && nc > 0);
+        DLIB_CASSERT(count_parameters(net) > 0, "The network has to be allocated before it can be exported to ONNX.");
+
+        impl::onnx_model model;
+        impl::onnx_graph_builder builder(model, k, nr, nc);
+        visit_layers_backwards(net, impl::visitor_net_to_onnx(builder));
+
+        // Give the graph output a predictable name.
+        impl::onnx_node identity;
+        identity.op_type = "Identity";
+        identity.name = "output";
+        identity.inputs = {builder.output_name()};
+        identity.outputs = {"output"};
+        model.nodes.push_back(identity);
+
+        impl::onnx_value_info input;
+        input.name = "input";
+        input.has_shape = true;
+        input.dims = {-1, k, nr, nc};
+        input.dim_params = {"N", "", "", ""};
+        model.inputs.push_back(input);
+
+        impl::onnx_value_info output;
+        output.name = "output";
+        output.has_shape = true;
+        const auto& shape = builder.output_shape();
+        output.dims = {-1, shape[0], shape[1], shape[2]};
+        output.dim_params = {"N", "", "", ""};
+        model.outputs.push_back(output);
+
+        const std::string data = impl::serialize_onnx_model(model);
+        out.write(data.data(), data.size());
+        if (!out)
+            throw serialization_error("Error writing ONNX model to output stream.");
+    }
+
+    template <typename net_type>
+    void net_to_onnx (
+        const net_type& net,
+        long k,
+        long nr,
+        long nc,
+        const std::string& filename
+    )
+    {
+        std::ofstream fout(filename, std::ios::binary);
+        if (!fout)
+            throw serialization_error("Unable to open " + filename + " for writing.");
+        net_to_onnx(net, k, nr, nc, fout);
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    template <typename net_type>
+    void onnx_to_net (
+        net_type& net,
+        std::istream& in
+    )
+    {
+        DLIB_CASSERT(count_parameters(net) > 0, "The network has to be allocated before ONNX weights can be loaded into it.");
+
+        const std::string data((std::istreambuf_iterator<char>(in)), std::istreambuf_iterator<char>());
+        const impl::onnx_model model = impl::parse_onnx_model(data);
+        const std::vector<impl::onnx_weighted_op> ops = impl::find_onnx_weighted_ops(model);
+
+        // Check everything before touching the network so a failed import leaves it
+        // unmodified.
+        impl::onnx_weight_loader checker(ops, false);
+        visit_layers_backwards(net, impl::visitor_onnx_to_net(checker));
+        if (checker.num_ops_used() != ops.size())
+        {
+            throw onnx_import_error("onnx_to_net(): the ONNX model has " + std::to_string(ops.size()) +
+                " nodes with parameters but the network only has " + std::to_string(checker.num_ops_used()) +
+                " layers with parameters.  The first unused node is " + impl::onnx_op_description(ops[checker.num_ops_used()]) + ".");
+        }
+
+        impl::onnx_weight_loader loader(ops, true);
+        visit_layers_backwards(net, impl::visitor_onnx_to_net(loader));
+    }
+
+    template <typename net_type>
+    void onnx_to_net (
+        net_type& net,
+        const std::string& filename
+    )
+    {
+        std::ifstream fin(filename, std::ios::binary);
+        if (!fin)
+            throw serialization_error("Unable to open " + filename + " for reading.");
+        onnx_to_net(net, fin);
+    }
+
+// ----------------------------------------------------------------------------------------
+
+}
+
+#endif // DLIB_DNn_ONNX_H_
+
@@ -0,0 +1,148 @@
+// Copyright (C) 2026  Davis E. King (davis@dlib.net)
+// License: Boost Software License   See LICENSE.txt for the full license.
+#undef DLIB_DNn_ONNX_ABSTRACT_H_
+#ifdef DLIB_DNn_ONNX_ABSTRACT_H_
+
+#include "input.h"
+#include "layers.h"
+#include "loss.h"
+
+namespace dlib
+{
+
+// ----------------------------------------------------------------------------------------
+
+    class onnx_import_error : public dlib::error
+    {
+        /*!
+            WHAT THIS OBJECT REPRESENTS
+                This is the exception thrown by onnx_to_net() when the contents of an ONNX
+                model don't match the dlib network they are being loaded into.  The
+                message says which dlib layer and which ONNX node are involved and what
+                the mismatch is.
+        !*/
+    };
+
+// ----------------------------------------------------------------------------------------
+
+    template <typename net_type>
+    void net_to_onnx (
+        const net_type& net,
+        long k,
+        long nr,
+        long nc,
+        std::ostream& out
+    );
+    /*!
+        requires
+            - net_type is an object of type add_layer, add_loss_layer, add_skip_layer, or
+              add_tag_layer.
+            - net has been properly allocated, that is: count_parameters(net) > 0.
+            - k > 0, nr > 0, nc > 0
+            - (k, nr, nc) are the dimensions of a single sample of the tensors given to
+              net.forward().  That is, the network must be able to run on a tensor of
+              dimensions num_samples x k x nr x nc.
+        ensures
+            - Writes net to out as an ONNX model (opset 13).  The model has a single input
+              named "input" with shape [N,k,nr,nc] and a single output named "output".  N
+              is a symbolic batch dimension.
+            - The model's input is the tensor produced by net.to_tensor(), not the objects
+              given to the input layer.  So any preprocessing done by the input layer, such
+              as the mean subtraction performed by input_rgb_image, must be done by the
+              ONNX runtime's user before running the model.
+            - If net has a loss layer then the loss layer is not exported.  The model
+              outputs the tensor that would be given to the loss layer, e.g. the raw
+              scores of a loss_multiclass_log_ network.
+            - Layers that are only active during training are exported using their
+              inference behavior.  That is, bn_ layers are exported as the equivalent
+              affine_ layers and dropout_ layers are omitted.
+            - The following layer types are supported: con_, fc_, bn_, affine_, relu_,
+              gelu_, silu_, sig_, htan_, leaky_relu_, softmax_, max_pool_, avg_pool_,
+              add_prev_, mult_prev_, concat_, upsample_, layer_norm_, dropout_ and
+              multiply_.  Tag and skip layers are supported as well.
+        throws
+            - dlib::error if net contains a layer that can't be represented in ONNX.
+            - serialization_error if there is a problem writing to out.
+    !*/
+
+    template <typename net_type>
+    void net_to_onnx (
+        const net_type& net,
+        long k,
+        long nr,
+        long nc,
+        const std::string& filename
+    );
+    /*!
+        requires
+            - The same requirements as the above net_to_onnx() apply.
+        ensures
+            - This function is just like the above net_to_onnx(), except it writes to a
+              file rather than an ostream.
+    !*/
+
+// ----------------------------------------------------------------------------------------
+
+    template <typename net_type>
+    void onnx_to_net (
+        net_type& net,
+        std::istream& in
+    );
+    /*!
+        requires
+            - net_type is an object of type add_layer, add_loss_layer, add_skip_layer, or
+              add_tag_layer.
+            - net has been properly allocated, that is: count_parameters(net) > 0.  You
+              can do this by running a sample through the network, e.g. net(img).
+        ensures
+            - Reads an ONNX model from in and copies its weights into net.  The network
+              architecture isn't read from the model.  It is defined by net_type, so you
+              must declare a dlib network that computes the same thing as the ONNX model.
+            - The layers of net that have parameters are paired, in order from the input
+              to the output, with the nodes of the ONNX graph that have parameters, in the
+              order they appear in the graph.  The following pairings are supported:
+                - con_ with Conv.
+                - fc_ with Gemm, or MatMul optionally followed by an Add of a bias.
+                - bn_ and affine_ with BatchNormalization, or with a Mul by a scale
+                  optionally followed by an Add of a bias.  When a bn_ layer is loaded
+                  from a BatchNormalization node the node's mean and variance become the
+                  bn_ layer's running statistics.
+                - layer_norm_ with LayerNormalization (with axis=1), or with a Mul by a
+                  scale followed by an Add of a bias.
+              Layers without parameters, e.g. relu_ or max_pool_, aren't paired with
+              anything.  So it's your responsibility to make sure the parameter free parts
+              of the two networks match.
+            - Models written by net_to_onnx() can be loaded back into the same network
+              type.
+            - If the ONNX model has a bias for a con_ layer whose bias is disabled then
+              the bias is enabled.
+            - All the checks are done before any weights are copied.  So if this function
+              throws then net is unmodified.
+        throws
+            - onnx_import_error if the ONNX model doesn't match net.  This happens when the
+              numbers of layers with parameters differ, when a layer is paired with an ONNX
+              node of an incompatible type, when a layer's parameter tensors have different
+              shapes than the node's, or when a node has attributes, such as strides or
+              padding, that are different from the layer's.
+            - serialization_error if the data in in isn't a valid ONNX model.
+    !*/
+
+    template <typename net_type>
+    void onnx_to_net (
+        net_type& net,
+        const std::string& filename
+    );
+    /*!
+        requires
+            - The same requirements as the above onnx_to_net() apply.
+        ensures
+            - This function is just like the above onnx_to_net(), except it reads from a
+              file rather than an istream.
+    !*/
+
+// ----------------------------------------------------------------------------------------
+
+}
+
+#endif // DLIB_DNn_ONNX_ABSTRACT_H_
+
@@ -196,6 +196,268 @@ namespace dlib
         Prints the solver's name and parameters to out.
     !*/
 
+// ----------------------------------------------------------------------------------------
+
+    class adamw
+    {
+        /*!
+            WHAT THIS OBJECT REPRESENTS
+                This object implements the EXAMPLE_SOLVER interface defined above.  In
+                particular, it implements the AdamW parameter update method described in
+                the paper:
+                    Loshchilov, Ilya, and Frank Hutter. "Decoupled weight decay
+                    regularization." International Conference on Learning Representations. 2019.
+
+                It is the same as adam except that the weight decay is not added to the
+                gradient before the moment estimates are computed.  Instead, the
+                parameters are shrunk directly by learning_rate*weight_decay at every
+                step, so the amount of regularization doesn't depend on the gradient
+                history.
+
+                Note that the actual learning rate and weight decay used by the solver are
+                multiplied by the per layer multipliers.  That is, the solver will call
+                get_learning_rate_multiplier(l) and get_weight_decay_multiplier(l) and
+                multiply these values with the nominal learning rate and weight decay,
+                respectively, to determine the values it will use during each step.  It is
+                also overloaded to allow additional learning rate multipliers to be applied
+                to fc_ and con_ bias parameters.
+        !*/
+
+    public:
+
+        adamw(
+        ); 
+        /*!
+            ensures
+                - #get_weight_decay()  == 0.01 
+                - #get_momentum1()     == 0.9 
+                - #get_momentum2()     == 0.999 
+        !*/
+
+        adamw(
+            float weight_decay,
+            float momentum1, 
+            float momentum2 
+        ); 
+        /*!
+            requires
+                - weight_decay >= 0
+                - 0 <= momentum1 < 1
+                - 0 <= momentum2 < 1
+            ensures
+                - #get_weight_decay()  == weight_decay 
+                - #get_momentum1()     == momentum1
+                - #get_momentum2()     == momentum2
+        !*/
+
+        float get_weight_decay () const;
+        float get_momentum1 () const; 
+        float get_momentum2 () const; 
+    };
+
+    void serialize(const adamw& item, std::ostream& out);
+    void deserialize(adamw& item, std::istream& in);
+    /*!
+        provides serialization support  
+    !*/
+
+    std::ostream& operator<< (std::ostream& out, const adamw& item);
+    /*!
+        Prints the solver's name and parameters to out.
+    !*/
+
+// ----------------------------------------------------------------------------------------
+
+    class rmsprop
+    {
+        /*!
+            WHAT THIS OBJECT REPRESENTS
+                This object implements the EXAMPLE_SOLVER interface defined above.  In
+                particular, it implements the RMSProp parameter update method.  It keeps
+                an exponentially decaying average of the squared gradients, V, and
+                produces the update step:
+                    V = decay*V + (1-decay)*squared(G)
+                    step = -learning_rate*G/(sqrt(V) + 1e-8)
+                where G == params_grad + weight_decay*params.
+
+                Note that the actual learning rate and weight decay used by the solver are
+                multiplied by the per layer multipliers.  That is, the solver will call
+                get_learning_rate_multiplier(l) and get_weight_decay_multiplier(l) and
+                multiply these values with the nominal learning rate and weight decay,
+                respectively, to determine the values it will use during each step.  It is
+                also overloaded to allow additional learning rate multipliers to be applied
+                to fc_ and con_ bias parameters.
+        !*/
+
+    public:
+
+        rmsprop(
+        ); 
+        /*!
+            ensures
+                - #get_weight_decay()  == 0.0005 
+                - #get_decay()         == 0.9 
+        !*/
+
+        rmsprop(
+            float weight_decay,
+            float decay
+        ); 
+        /*!
+            requires
+                - weight_decay >= 0
+                - 0 <= decay < 1
+            ensures
+                - #get_weight_decay()  == weight_decay 
+                - #get_decay()         == decay
+        !*/
+
+        float get_weight_decay () const;
+        float get_decay () const; 
+    };
+
+    void serialize(const rmsprop& item, std::ostream& out);
+    void deserialize(rmsprop& item, std::istream& in);
+    /*!
+        provides serialization support  
+    !*/
+
+    std::ostream& operator<< (std::ostream& out, const rmsprop& item);
+    /*!
+        Prints the solver's name and parameters to out.
+    !*/
+
+// ----------------------------------------------------------------------------------------
+
+    class adagrad
+    {
+        /*!
+            WHAT THIS OBJECT REPRESENTS
+                This object implements the EXAMPLE_SOLVER interface defined above.  In
+                particular, it implements the Adagrad parameter update method described in
+                the paper:
+                    Duchi, John, Elad Hazan, and Yoram Singer. "Adaptive subgradient
+                    methods for online learning and stochastic optimization." Journal of
+                    Machine Learning Research 12. 2011.
+                It accumulates the sum of all the squared gradients seen so far, V, and
+                produces the update step:
+                    V = V + squared(G)
+                    step = -learning_rate*G/(sqrt(V) + 1e-8)
+                where G == params_grad + weight_decay*params.
+
+                Note that the actual learning rate and weight decay used by the solver are
+                multiplied by the per layer multipliers.  That is, the solver will call
+                get_learning_rate_multiplier(l) and get_weight_decay_multiplier(l) and
+                multiply these values with the nominal learning rate and weight decay,
+                respectively, to determine the values it will use during each step.  It is
+                also overloaded to allow additional learning rate multipliers to be applied
+                to fc_ and con_ bias parameters.
+        !*/
+
+    public:
+
+        adagrad(
+        ); 
+        /*!
+            ensures
+                - #get_weight_decay()  == 0.0005 
+        !*/
+
+        adagrad(
+            float weight_decay
+        ); 
+        /*!
+            requires
+                - weight_decay >= 0
+            ensures
+                - #get_weight_decay()  == weight_decay 
+        !*/
+
+        float get_weight_decay () const;
+    };
+
+    void serialize(const adagrad& item, std::ostream& out);
+    void deserialize(adagrad& item, std::istream& in);
+    /*!
+        provides serialization support  
+    !*/
+
+    std::ostream& operator<< (std::ostream& out, const adagrad& item);
+    /*!
+        Prints the solver's name and parameters to out.
+    !*/
+
+// ----------------------------------------------------------------------------------------
+
+    class lion
+    {
+        /*!
+            WHAT THIS OBJECT REPRESENTS
+                This object implements the EXAMPLE_SOLVER interface defined above.  In
+                particular, it implements the Lion parameter update method described in
+                the paper:
+                    Chen, Xiangning, et al. "Symbolic discovery of optimization
+                    algorithms." Advances in Neural Information Processing Systems. 2023.
+                Each step has the same magnitude in every coordinate since only the sign
+                of the interpolated momentum is used:
+                    step = -learning_rate*(sign(momentum1*M + (1-momentum1)*params_grad) + weight_decay*params)
+                    M = momentum2*M + (1-momentum2)*params_grad
+                Like adamw, the weight decay is decoupled from the gradient.  Because
+                of the sign operation Lion usually needs a learning rate 3-10x smaller
+                than what you would use with adam.
+
+                Note that the actual learning rate and weight decay used by the solver are
+                multiplied by the per layer multipliers.  That is, the solver will call
+                get_learning_rate_multiplier(l) and get_weight_decay_multiplier(l) and
+                multiply these values with the nominal learning rate and weight decay,
+                respectively, to determine the values it will use during each step.  It is
+                also overloaded to allow additional learning rate multipliers to be applied
+                to fc_ and con_ bias parameters.
+        !*/
+
+    public:
+
+        lion(
+        ); 
+        /*!
+            ensures
+                - #get_weight_decay()  == 0.01 
+                - #get_momentum1()     == 0.9 
+                - #get_momentum2()     == 0.99 
+        !*/
+
+        lion(
+            float weight_decay,
+            float momentum1, 
+            float momentum2 
+        ); 
+        /*!
+            requires
+                - weight_decay >= 0
+                - 0 <= momentum1 < 1
+                - 0 <= momentum2 < 1
+            ensures
+                - #get_weight_decay()  == weight_decay 
+                - #get_momentum1()     == momentum1
+                - #get_momentum2()     == momentum2
+        !*/
+
+        float get_weight_decay () const;
+        float get_momentum1 () const; 
+        float get_momentum2 () const; 
+    };
+
+    void serialize(const lion& item, std::ostream& out);
+    void deserialize(lion& item, std::istream& in);
+    /*!
+        provides serialization support  
+    !*/
+
+    std::ostream& operator<< (std::ostream& out, const lion& item);
+    /*!
+        Prints the solver's name and parameters to out.
+    !*/
+
 // ----------------------------------------------------------------------------------------
 
 }
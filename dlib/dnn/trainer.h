@@ -6,6 +6,8 @@
 #include "trainer_abstract.h"
 #include "core.h"
 #include "solvers.h"
+#include "visitors.h"
+#include "lr_schedulers.h"
 #include "../statistics.h"
 #include <chrono>
 #include <fstream>
@@ -22,6 +24,7 @@
 #include <future>
 #include <exception>
 #include <mutex>
+#include <functional>
 #include "../dir_nav.h"
 #include "../md5.h"
 
@@ -76,6 +79,15 @@ namespace dlib
         const static size_t num_computational_layers = net_type::num_computational_layers;
         const static size_t num_layers = net_type::num_layers;
         using threads = std::vector<std::shared_ptr<thread_pool>>;
+
+        struct training_progress
+        {
+            double loss;
+            double learning_rate;
+            unsigned long long step;
+            const net_type& net;
+        };
+        typedef std::function<void(const training_progress&)> callback_type;
     private:
         typedef impl::dnn_job_t<training_label_type> job_t;
     public:
@@ -303,16 +315,17 @@ namespace dlib
         {
             DLIB_CASSERT(data.size() == labels.size() && data.size() > 0);
 
+            stop_training = false;
             // The reason these two loops don't initialize their counter variables but
             // instead use class members is so we can include the state of the loops in the
             // stuff written by sync_to_disk()
             for (; 
-                epoch_iteration < max_num_epochs && learning_rate >= min_learning_rate; 
+                epoch_iteration < max_num_epochs && learning_rate >= min_learning_rate && !stop_training; 
                 ++epoch_iteration)
             {
                 last_time = std::chrono::system_clock::now();
                 clear_average_loss();
-                for (; epoch_pos < data.size() && learning_rate >= min_learning_rate; epoch_pos += mini_batch_size)
+                for (; epoch_pos < data.size() && learning_rate >= min_learning_rate && !stop_training; epoch_pos += mini_batch_size)
                 {
                     if (verbose)
                     {
@@ -333,6 +346,10 @@ namespace dlib
                               data.begin()+std::min(epoch_pos+mini_batch_size,data.size()), 
                               labels.begin()+epoch_pos);
                 }
+                // If we were asked to stop in the middle of an epoch then leave epoch_pos
+                // where it is so that training resumes from this point.
+                if (stop_training)
+                    break;
                 epoch_pos = 0;
 
                 if (verbose)
@@ -344,6 +361,13 @@ namespace dlib
                               << "average loss: " << rpad(cast_to_string(get_average_loss()),string_pad) << "  ";
                     print_progress();
                 }
+
+                if (epoch_callbacks.size() != 0)
+                {
+                    wait_for_thread_to_pause();
+                    propagate_exception();
+                    run_callbacks(epoch_callbacks, rs.mean(), learning_rate);
+                }
             }
             wait_for_thread_to_pause();
             // if we modified the network at all then be sure to sync the final result.
@@ -360,16 +384,17 @@ namespace dlib
             static_assert(has_unsupervised_loss, 
                 "You can only call this version of train() when using an unsupervised loss.");
 
+            stop_training = false;
             // The reason these two loops don't initialize their counter variables but
             // instead use class members is so we can include the state of the loops in the
             // stuff written by sync_to_disk()
             for (; 
-                epoch_iteration < max_num_epochs && learning_rate >= min_learning_rate; 
+                epoch_iteration < max_num_epochs && learning_rate >= min_learning_rate && !stop_training; 
                 ++epoch_iteration)
             {
                 last_time = std::chrono::system_clock::now();
                 clear_average_loss();
-                for (; epoch_pos < data.size() && learning_rate >= min_learning_rate; epoch_pos += mini_batch_size)
+                for (; epoch_pos < data.size() && learning_rate >= min_learning_rate && !stop_training; epoch_pos += mini_batch_size)
                 {
                     if (verbose)
                     {
@@ -389,6 +414,10 @@ namespace dlib
                     send_job(false, data.begin()+epoch_pos, 
                              data.begin()+std::min(epoch_pos+mini_batch_size,data.size()));
                 }
+                // If we were asked to stop in the middle of an epoch then leave epoch_pos
+                // where it is so that training resumes from this point.
+                if (stop_training)
+                    break;
                 epoch_pos = 0;
 
                 if (verbose)
@@ -400,6 +429,13 @@ namespace dlib
                               << "average loss: " << rpad(cast_to_string(get_average_loss()),string_pad) << "  ";
                     print_progress();
                 }
+
+                if (epoch_callbacks.size() != 0)
+                {
+                    wait_for_thread_to_pause();
+                    propagate_exception();
+                    run_callbacks(epoch_callbacks, rs.mean(), learning_rate);
+                }
             }
             wait_for_thread_to_pause();
             // if we modified the network at all then be sure to sync the final result.
@@ -464,6 +500,7 @@ namespace dlib
             }
             learning_rate = lr;
             lr_schedule.set_size(0);
+            lr_scheduler.clear();
         }
 
         double get_learning_rate(
@@ -479,6 +516,7 @@ namespace dlib
             DLIB_CASSERT(lr > 0);
             wait_for_thread_to_pause();
             lr_schedule.set_size(0);
+            lr_scheduler.clear();
             min_learning_rate = lr;
         }
 
@@ -508,12 +546,37 @@ namespace dlib
             return lr_schedule;
         }
 
+        template <typename scheduler_type>
+        void set_learning_rate_scheduler (
+            const scheduler_type& scheduler
+        )
+        {
+            set_learning_rate(scheduler(0));
+            set_min_learning_rate(scheduler.get_min_learning_rate());
+            set_learning_rate_shrink_factor(1);
+            lr_scheduler = scheduler;
+            lr_scheduler_step = 0;
+        }
+
+        const learning_rate_scheduler& get_learning_rate_scheduler (
+        ) const
+        {
+            return lr_scheduler;
+        }
+
+        unsigned long long get_learning_rate_scheduler_step (
+        ) const
+        {
+            return lr_scheduler_step;
+        }
+
         void set_iterations_without_progress_threshold (
             unsigned long thresh 
         )
         {
             wait_for_thread_to_pause();
             lr_schedule.set_size(0);
+            lr_scheduler.clear();
             iter_without_progress_thresh = thresh;
         }
 
@@ -535,6 +598,7 @@ namespace dlib
         {
             wait_for_thread_to_pause();
             lr_schedule.set_size(0);
+            lr_scheduler.clear();
             test_iter_without_progress_thresh = thresh;
         }
 
@@ -557,6 +621,7 @@ namespace dlib
             DLIB_CASSERT(0 < shrink && shrink <= 1);
             wait_for_thread_to_pause();
             lr_schedule.set_size(0);
+            lr_scheduler.clear();
             learning_rate_shrink = shrink;
             steps_without_progress = 0;
             test_steps_without_progress = 0;
@@ -568,6 +633,238 @@ namespace dlib
             return learning_rate_shrink;
         }
 
+        void set_truncated_bptt_chunks (
+            unsigned long num_chunks
+        )
+        {
+            wait_for_thread_to_pause();
+            bptt_chunks = num_chunks;
+            bptt_chunk_pos = 0;
+            for (auto&& d : devices)
+                set_recurrent_stateful(d->net, num_chunks != 0);
+        }
+
+        unsigned long get_truncated_bptt_chunks (
+        ) const
+        {
+            return bptt_chunks;
+        }
+
+        void reset_recurrent_states (
+        )
+        {
+            wait_for_thread_to_pause();
+            bptt_chunk_pos = 0;
+            for (auto&& d : devices)
+                dlib::reset_recurrent_states(d->net);
+        }
+
+        void set_mixed_precision (
+            half_format fmt
+        )
+        {
+            wait_for_thread_to_pause();
+            mixed_precision = true;
+            mixed_precision_format = fmt;
+        }
+
+        void disable_mixed_precision (
+        )
+        {
+            wait_for_thread_to_pause();
+            mixed_precision = false;
+        }
+
+        bool uses_mixed_precision (
+        ) const { return mixed_precision; }
+
+        half_format get_mixed_precision_format (
+        ) const { return mixed_precision_format; }
+
+        void set_loss_scale (
+            double scale
+        )
+        {
+            DLIB_CASSERT(scale > 0);
+            wait_for_thread_to_pause();
+            loss_scale = scale;
+            steps_since_loss_scale_change = 0;
+        }
+
+        double get_loss_scale (
+        ) const 
+        { 
+            wait_for_thread_to_pause();
+            return loss_scale; 
+        }
+
+        void set_dynamic_loss_scaling (
+            bool enabled
+        )
+        {
+            wait_for_thread_to_pause();
+            dynamic_loss_scaling = enabled;
+            steps_since_loss_scale_change = 0;
+        }
+
+        bool uses_dynamic_loss_scaling (
+        ) const { return dynamic_loss_scaling; }
+
+        unsigned long long get_num_skipped_steps (
+        ) const 
+        { 
+            wait_for_thread_to_pause();
+            return num_skipped_steps; 
+        }
+
+        void set_gradient_accumulation_steps (
+            unsigned long num_steps
+        )
+        {
+            DLIB_CASSERT(num_steps > 0);
+            wait_for_thread_to_pause();
+            gradient_accumulation_steps = num_steps;
+            gradient_accumulation_pos = 0;
+        }
+
+        unsigned long get_gradient_accumulation_steps (
+        ) const { return gradient_accumulation_steps; }
+
+        void set_gradient_clip_norm (
+            double max_norm
+        )
+        {
+            DLIB_CASSERT(max_norm >= 0);
+            wait_for_thread_to_pause();
+            gradient_clip_norm = max_norm;
+        }
+
+        double get_gradient_clip_norm (
+        ) const { return gradient_clip_norm; }
+
+        void set_gradient_clip_value (
+            double max_value
+        )
+        {
+            DLIB_CASSERT(max_value >= 0);
+            wait_for_thread_to_pause();
+            gradient_clip_value = max_value;
+        }
+
+        double get_gradient_clip_value (
+        ) const { return gradient_clip_value; }
+
+        void set_weight_ema_decay (
+            double decay
+        )
+        {
+            DLIB_CASSERT(0 <= decay && decay < 1);
+            wait_for_thread_to_pause();
+            DLIB_CASSERT(!weight_ema_swapped_in(), "Swap the weight EMA back out before changing it.");
+            weight_ema_decay = decay;
+            if (decay == 0)
+                weight_ema.clear();
+        }
+
+        double get_weight_ema_decay (
+        ) const { return weight_ema_decay; }
+
+        bool uses_weight_ema (
+        ) const { return weight_ema_decay > 0; }
+
+        void swap_weight_ema (
+        )
+        {
+            DLIB_CASSERT(uses_weight_ema());
+            wait_for_thread_to_pause();
+            propagate_exception();
+
+            std::vector<tensor*> reference_params;
+            resizable_tensor temp;
+            visit_layer_parameters(devices[0]->net, [&](size_t i, tensor& t)
+            {
+                reference_params.push_back(&t);
+                if (i < weight_ema.size() && t.size() != 0 && t.size() == weight_ema[i].size())
+                {
+                    temp.copy_size(t);
+                    memcpy(temp, t);
+                    memcpy(t, weight_ema[i]);
+                    memcpy(weight_ema[i], temp);
+                }
+            });
+            // All the devices hold the same parameters, so they just get a copy of the
+            // ones now in the first device.
+            for (size_t i = 1; i < devices.size(); ++i)
+            {
+                visit_layer_parameters(devices[i]->net, [&](size_t j, tensor& t)
+                {
+                    memcpy(t, *reference_params[j]);
+                });
+            }
+            weight_ema_swapped = !weight_ema_swapped;
+        }
+
+        bool weight_ema_swapped_in (
+        ) const { return weight_ema_swapped; }
+
+        void add_step_callback (
+            callback_type f
+        )
+        {
+            wait_for_thread_to_pause();
+            step_callbacks.push_back(std::move(f));
+        }
+
+        void add_test_step_callback (
+            callback_type f
+        )
+        {
+            wait_for_thread_to_pause();
+            test_step_callbacks.push_back(std::move(f));
+        }
+
+        void add_epoch_callback (
+            callback_type f
+        )
+        {
+            wait_for_thread_to_pause();
+            epoch_callbacks.push_back(std::move(f));
+        }
+
+        void add_learning_rate_callback (
+            callback_type f
+        )
+        {
+            wait_for_thread_to_pause();
+            learning_rate_callbacks.push_back(std::move(f));
+        }
+
+        void clear_callbacks (
+        )
+        {
+            wait_for_thread_to_pause();
+            step_callbacks.clear();
+            test_step_callbacks.clear();
+            epoch_callbacks.clear();
+            learning_rate_callbacks.clear();
+        }
+
+        void request_stop (
+        )
+        {
+            stop_training = true;
+        }
+
+        bool stop_requested (
+        ) const { return stop_training; }
+
+        unsigned long long get_num_training_steps (
+        ) const
+        {
+            wait_for_thread_to_pause();
+            return num_training_steps;
+        }
+
         unsigned long long get_train_one_step_calls (
         ) const
         {
@@ -619,8 +916,13 @@ namespace dlib
                 dlib::cuda::set_device(dev.device_id);
                 if (next_job.test_only)
                     return dev.net.compute_loss(next_job.t[device], next_job.labels[device].begin());
-                else
-                    return dev.net.compute_parameter_gradients(next_job.t[device], next_job.labels[device].begin());
+
+                if (mixed_precision)
+                    use_half_precision_parameters(dev);
+                const double loss = dev.net.compute_parameter_gradients(next_job.t[device], next_job.labels[device].begin());
+                if (mixed_precision)
+                    restore_master_parameters(dev);
+                return loss;
             }
             else
             {
@@ -637,8 +939,13 @@ namespace dlib
                 no_label_type pick_which_run_update;
                 if (next_job.test_only)
                     return dev.net.compute_loss(next_job.t[device]);
-                else
-                    return dev.net.compute_parameter_gradients(next_job.t[device]);
+
+                if (mixed_precision)
+                    use_half_precision_parameters(dev);
+                const double loss = dev.net.compute_parameter_gradients(next_job.t[device]);
+                if (mixed_precision)
+                    restore_master_parameters(dev);
+                return loss;
             }
             else
             {
@@ -646,11 +953,167 @@ namespace dlib
             }
         }
 
+        template <typename device_type>
+        void use_half_precision_parameters(device_type& dev)
+        {
+            // Keep the float parameters around as the master copy and run the forward
+            // and backward passes with the parameters rounded to 16 bits.
+            dev.master_params.resize(num_computational_layers);
+            visit_layer_parameters(dev.net, [&](size_t i, tensor& t)
+            {
+                dev.master_params[i].copy_size(t);
+                memcpy(dev.master_params[i], t);
+                tt::round_to_half_precision(t, mixed_precision_format);
+            });
+        }
+
+        template <typename device_type>
+        void restore_master_parameters(device_type& dev)
+        {
+            // Layers are only allocated during the first forward pass, so they have no
+            // saved parameters on the very first step.  Those are left as they are.
+            visit_layer_parameters(dev.net, [&](size_t i, tensor& t)
+            {
+                if (t.size() == dev.master_params[i].size())
+                    memcpy(t, dev.master_params[i]);
+            });
+
+            // The gradients are stored in 16 bits after being multiplied by the loss
+            // scale, which keeps small gradients from flushing to zero.  If the scale is
+            // too big some of them overflow instead, and we have to skip this step.
+            dev.found_overflow = false;
+            visit_layer_parameter_gradients(dev.net, [&](tensor& g)
+            {
+                if (g.size() == 0)
+                    return;
+                tt::affine_transform(g, g, loss_scale);
+                tt::round_to_half_precision(g, mixed_precision_format);
+                if (!is_finite(mat(g)))
+                    dev.found_overflow = true;
+                tt::affine_transform(g, g, 1.0/loss_scale);
+            });
+        }
+
+        bool update_loss_scale(const job_t& next_job)
+        {
+            bool found_overflow = false;
+            for (size_t i = 0; i < devices.size(); ++i)
+                found_overflow = found_overflow || (next_job.have_data[i] && devices[i]->found_overflow);
+
+            if (found_overflow)
+            {
+                ++num_skipped_steps;
+                if (dynamic_loss_scaling)
+                    loss_scale = std::max(1.0, loss_scale/2);
+                steps_since_loss_scale_change = 0;
+                return false;
+            }
+
+            if (dynamic_loss_scaling && ++steps_since_loss_scale_change >= loss_scale_growth_interval)
+            {
+                loss_scale *= 2;
+                steps_since_loss_scale_change = 0;
+            }
+            return true;
+        }
+
+        void accumulate_gradients(size_t device, bool last_step)
+        {
+            auto&& dev = *devices[device];
+            dlib::cuda::set_device(dev.device_id);
+            dev.accumulated_gradients.resize(num_computational_layers);
+            const float scale = 1.0f/gradient_accumulation_steps;
+            visit_layer_parameter_gradients(dev.net, [&](size_t i, tensor& g)
+            {
+                auto& acc = dev.accumulated_gradients[i];
+                if (g.size() == 0)
+                    return;
+                if (gradient_accumulation_pos == 0 || acc.size() != g.size())
+                {
+                    acc.copy_size(g);
+                    tt::affine_transform(acc, g, scale);
+                }
+                else if (!last_step)
+                {
+                    tt::add(1, acc, scale, g);
+                }
+                else
+                {
+                    // Leave the average of all the accumulated gradients in the network
+                    // so the solvers see it as if it came from one big mini-batch.
+                    tt::affine_transform(g, acc, g, 1, scale);
+                }
+            });
+        }
+
+        template <typename device_type>
+        void clip_gradients(device_type& dev)
+        {
+            if (gradient_clip_value > 0)
+            {
+                visit_layer_parameter_gradients(dev.net, [&](tensor& g)
+                {
+                    if (g.size() != 0)
+                        tt::clamp(g, -gradient_clip_value, gradient_clip_value);
+                });
+            }
+
+            if (gradient_clip_norm > 0)
+            {
+                // The norm is taken over all the gradients of the network together, so
+                // clipping doesn't change the direction of the update, only its length.
+                dev.gradient_norm.set_size(1);
+                dev.gradient_norm = 0;
+                visit_layer_parameter_gradients(dev.net, [&](tensor& g)
+                {
+                    if (g.size() != 0)
+                        tt::dot(g, g, dev.gradient_norm, 0);
+                });
+                const double norm = std::sqrt(dev.gradient_norm.host()[0]);
+                if (norm > gradient_clip_norm)
+                {
+                    visit_layer_parameter_gradients(dev.net, [&](tensor& g)
+                    {
+                        if (g.size() != 0)
+                            tt::affine_transform(g, g, gradient_clip_norm/norm);
+                    });
+                }
+            }
+        }
+
         void update_parameters(size_t device)
         {
             auto&& dev = *devices[device];
             dlib::cuda::set_device(dev.device_id);
+            clip_gradients(dev);
             dev.net.update_parameters(make_sstack(dev.solvers), learning_rate);
+            // Every device ends up with the same parameters, so the moving average only
+            // needs to be kept for the first one.
+            if (device == 0 && weight_ema_decay > 0)
+                update_weight_ema(dev);
+        }
+
+        template <typename device_type>
+        void update_weight_ema(device_type& dev)
+        {
+            weight_ema.resize(num_computational_layers);
+            visit_layer_parameters(dev.net, [&](size_t i, tensor& t)
+            {
+                auto& avg = weight_ema[i];
+                if (t.size() == 0)
+                    return;
+                // Layers are only allocated during the first forward pass, so the average
+                // starts from the parameters as they are after the first update.
+                if (avg.size() != t.size())
+                {
+                    avg.copy_size(t);
+                    memcpy(avg, t);
+                }
+                else
+                {
+                    tt::affine_transform(avg, avg, t, weight_ema_decay, 1-weight_ema_decay);
+                }
+            });
         }
 
         void thread() try
@@ -689,8 +1152,22 @@ namespace dlib
             main_iteration_counter = 0;
             while(job_pipe.dequeue(next_job))
             {
+                const double prev_learning_rate = learning_rate;
                 if (next_job.test_only)
                 {
+                    // Testing shouldn't disturb the state the recurrent layers are
+                    // carrying between training chunks, so run it from a zero state and
+                    // put the training state back afterwards.
+                    std::vector<std::vector<resizable_tensor>> saved_states;
+                    if (bptt_chunks != 0)
+                    {
+                        for (auto&& d : devices)
+                        {
+                            saved_states.push_back(impl::save_recurrent_states(d->net));
+                            dlib::reset_recurrent_states(d->net);
+                        }
+                    }
+
                     // compute the testing loss
                     for (size_t i = 0; i < devices.size(); ++i)
                         tp[i]->add_task_by_value([&,i](double& loss){ loss = compute_parameter_gradients(i, next_job, pick_which_run_update); }, losses[i]);
@@ -700,6 +1177,11 @@ namespace dlib
                         theloss += loss.get();
                     record_test_loss(theloss/losses.size());
 
+                    for (size_t i = 0; i < saved_states.size(); ++i)
+                        impl::restore_recurrent_states(devices[i]->net, saved_states[i]);
+
+                    run_callbacks(test_step_callbacks, theloss/losses.size(), learning_rate);
+
                     // Check if we should shrink the learning rate based on how the test
                     // error has been doing lately.
                     if (learning_rate_shrink != 1)
@@ -720,11 +1202,26 @@ namespace dlib
                             }
                         }
                     }
+                    if (learning_rate != prev_learning_rate)
+                        run_callbacks(learning_rate_callbacks, theloss/losses.size(), learning_rate);
                     continue;
                 }
 
                 updated_net_since_last_sync = true;
                 ++main_iteration_counter;
+                // When doing truncated backpropagation through time each mini-batch is
+                // the next chunk of the same sequences, so the recurrent layers keep
+                // their state between steps.  Every bptt_chunks steps a new set of
+                // sequences begins and we start over from a zero state.
+                if (bptt_chunks != 0)
+                {
+                    if (bptt_chunk_pos == 0)
+                    {
+                        for (auto&& d : devices)
+                            dlib::reset_recurrent_states(d->net);
+                    }
+                    bptt_chunk_pos = (bptt_chunk_pos+1)%bptt_chunks;
+                }
                 // Call compute_parameter_gradients() and update_parameters() but pick the
                 // right version for unsupervised or supervised training based on the type
                 // of training_label_type.
@@ -736,9 +1233,36 @@ namespace dlib
                     theloss += loss.get();
                 record_loss(theloss/losses.size());
 
+                // With mixed precision, steps whose gradients overflowed are skipped.
+                bool apply_update = !mixed_precision || update_loss_scale(next_job);
+
+                // When accumulating gradients only every gradient_accumulation_steps-th
+                // mini-batch updates the parameters.  The ones before it just add their
+                // gradients to the accumulators.
+                bool accumulating = false;
+                if (gradient_accumulation_steps > 1)
+                {
+                    if (apply_update)
+                    {
+                        const bool last_step = gradient_accumulation_pos+1 == gradient_accumulation_steps;
+                        for (size_t i = 0; i < devices.size(); ++i)
+                            tp[i]->add_task_by_value([&,i](){ accumulate_gradients(i, last_step); });
+                        for (size_t i = 0; i < devices.size(); ++i)
+                            tp[i]->wait_for_all_tasks();
+                        gradient_accumulation_pos = last_step ? 0 : gradient_accumulation_pos+1;
+                        accumulating = !last_step;
+                        apply_update = last_step;
+                    }
+                    else
+                    {
+                        // A skipped step throws away everything accumulated so far.
+                        gradient_accumulation_pos = 0;
+                    }
+                }
+
                 // Now, if there is more than one active device we need to synchronize the
                 // gradient updates between devices.  So we do that now.
-                if (devices.size() > 1)
+                if (devices.size() > 1 && apply_update)
                 {
                     // if this is the first iteration then we need to setup the averagers.
                     // We can't do this outside the loop because the tensors that get
@@ -787,7 +1311,7 @@ namespace dlib
 
                 // Now apply all the updates to each device.
                 for (size_t i = 0; i < devices.size(); ++i)
-                    tp[i]->add_task_by_value([&,i](){ if (next_job.have_data[i]) update_parameters(i); });
+                    tp[i]->add_task_by_value([&,i](){ if (next_job.have_data[i] && apply_update) update_parameters(i); });
                 // and wait for the updates to all happen.
                 for (size_t i = 0; i < devices.size(); ++i)
                     tp[i]->wait_for_all_tasks();
@@ -845,13 +1369,25 @@ namespace dlib
                         }
                     }
                 }
-                else if (lr_schedule.size() != 0) // or use the learning rate schedule if we have one.
+                // or use the learning rate schedule if we have one.  The schedules only
+                // advance on steps that actually update the parameters.
+                else if (lr_schedule.size() != 0 && !accumulating)
                 {
                     if (lr_schedule_pos < lr_schedule.size())
                         learning_rate = lr_schedule(lr_schedule_pos++);
                     else
                         learning_rate = lr_schedule(lr_schedule.size()-1)*0.99;
                 }
+                else if (!lr_scheduler.is_empty() && !accumulating) // or ask the scheduler for the next value.
+                {
+                    ++lr_scheduler_step;
+                    learning_rate = visit([&](const auto& scheduler) { return scheduler(lr_scheduler_step); }, lr_scheduler);
+                }
+
+                ++num_training_steps;
+                run_callbacks(step_callbacks, theloss/losses.size(), prev_learning_rate);
+                if (learning_rate != prev_learning_rate)
+                    run_callbacks(learning_rate_callbacks, theloss/losses.size(), learning_rate);
             }
         }
         catch(...)
@@ -862,6 +1398,19 @@ namespace dlib
             eptr = std::current_exception();
         }
 
+        void run_callbacks (
+            const std::vector<callback_type>& callbacks,
+            double loss,
+            double lr
+        ) const
+        {
+            if (callbacks.size() == 0)
+                return;
+            const training_progress progress{loss, lr, num_training_steps, devices[0]->net};
+            for (auto&& f : callbacks)
+                f(progress);
+        }
+
         void wait_for_thread_to_pause() const
         {
             job_pipe.wait_for_num_blocked_dequeues(1);
@@ -890,6 +1439,23 @@ namespace dlib
             test_one_step_calls = 0;
             gradient_check_budget = 0;
             lr_schedule_pos = 0;
+            lr_scheduler_step = 0;
+            bptt_chunks = 0;
+            mixed_precision = false;
+            mixed_precision_format = half_format::fp16;
+            loss_scale = 65536;
+            dynamic_loss_scaling = true;
+            steps_since_loss_scale_change = 0;
+            num_skipped_steps = 0;
+            bptt_chunk_pos = 0;
+            gradient_accumulation_steps = 1;
+            gradient_accumulation_pos = 0;
+            gradient_clip_norm = 0;
+            gradient_clip_value = 0;
+            weight_ema_decay = 0;
+            weight_ema_swapped = false;
+            num_training_steps = 0;
+            stop_training = false;
 
             main_iteration_counter = 0;
             main_iteration_counter_at_last_disk_sync = 0;
@@ -913,7 +1479,7 @@ namespace dlib
         friend void serialize(const dnn_trainer& item, std::ostream& out)
         {
             item.wait_for_thread_to_pause();
-            int version = 13;
+            int version = 19;
             serialize(version, out);
 
             size_t nl = dnn_trainer::num_layers;
@@ -943,13 +1509,30 @@ namespace dlib
             serialize(item.previous_loss_values_dump_amount, out);
             serialize(item.test_previous_loss_values_dump_amount, out);
             serialize(item.previous_loss_values_to_keep_until_disk_sync, out);
+            serialize(item.bptt_chunks, out);
+            serialize(item.bptt_chunk_pos, out);
+            serialize(item.lr_scheduler, out);
+            serialize(item.lr_scheduler_step, out);
+            serialize(item.mixed_precision, out);
+            serialize(static_cast<int>(item.mixed_precision_format), out);
+            serialize(item.loss_scale, out);
+            serialize(item.dynamic_loss_scaling, out);
+            serialize(item.steps_since_loss_scale_change, out);
+            serialize(item.num_skipped_steps, out);
+            serialize(item.gradient_accumulation_steps, out);
+            serialize(item.gradient_clip_norm, out);
+            serialize(item.gradient_clip_value, out);
+            serialize(item.num_training_steps, out);
+            serialize(item.weight_ema_decay, out);
+            serialize(item.weight_ema, out);
+            serialize(item.weight_ema_swapped, out);
         }
         friend void deserialize(dnn_trainer& item, std::istream& in)
         {
             item.wait_for_thread_to_pause();
             int version = 0;
             deserialize(version, in);
-            if (version != 13)
+            if (version != 19)
                 throw serialization_error("Unexpected version found while deserializing dlib::dnn_trainer.");
 
             size_t num_layers = 0;
@@ -989,6 +1572,26 @@ namespace dlib
             deserialize(item.previous_loss_values_dump_amount, in);
             deserialize(item.test_previous_loss_values_dump_amount, in);
             deserialize(item.previous_loss_values_to_keep_until_disk_sync, in);
+            deserialize(item.bptt_chunks, in);
+            deserialize(item.bptt_chunk_pos, in);
+            deserialize(item.lr_scheduler, in);
+            deserialize(item.lr_scheduler_step, in);
+            deserialize(item.mixed_precision, in);
+            int itemp;
+            deserialize(itemp, in); item.mixed_precision_format = static_cast<half_format>(itemp);
+            deserialize(item.loss_scale, in);
+            deserialize(item.dynamic_loss_scaling, in);
+            deserialize(item.steps_since_loss_scale_change, in);
+            deserialize(item.num_skipped_steps, in);
+            deserialize(item.gradient_accumulation_steps, in);
+            deserialize(item.gradient_clip_norm, in);
+            deserialize(item.gradient_clip_value, in);
+            deserialize(item.num_training_steps, in);
+            deserialize(item.weight_ema_decay, in);
+            deserialize(item.weight_ema, in);
+            deserialize(item.weight_ema_swapped, in);
+            // The accumulated gradients aren't saved, so start a fresh accumulation.
+            item.gradient_accumulation_pos = 0;
 
             if (item.devices.size() > 1)
             {
@@ -1062,12 +1665,15 @@ namespace dlib
                         if (verbose && learning_rate_shrink != 1)
                             std::cout << "(and while at it, also shrinking the learning rate)" << std::endl;
 
+                        const double prev_learning_rate = learning_rate;
                         learning_rate = learning_rate_shrink * learning_rate;
                         steps_without_progress = 0;
                         test_steps_without_progress = 0;
 
                         drop_some_previous_loss_values();
                         drop_some_test_previous_loss_values();
+                        if (learning_rate != prev_learning_rate)
+                            run_callbacks(learning_rate_callbacks, rs.mean(), learning_rate);
                     }
                 }
                 else
@@ -1171,6 +1777,10 @@ namespace dlib
             std::shared_ptr<net_type> net_copy;
             net_type& net;
             std::vector<solver_type> solvers;
+            std::vector<resizable_tensor> master_params;
+            bool found_overflow = false;
+            std::vector<resizable_tensor> accumulated_gradients;
+            resizable_tensor gradient_norm;
         };
 
         template <
@@ -1191,6 +1801,8 @@ namespace dlib
             job.labels.resize(devs);
             job.have_data.resize(devs);
             job.test_only = test_only;
+            DLIB_CASSERT(test_only || !weight_ema_swapped,
+                "The weight EMA must be swapped back out with swap_weight_ema() before training continues.");
 
             // chop the data into devs blocks, each of about block_size elements.
             const double block_size = num / static_cast<double>(devs);
@@ -1244,7 +1856,11 @@ namespace dlib
 
         void print_progress()
         {
-            if (lr_schedule.size() == 0)
+            if (!lr_scheduler.is_empty())
+            {
+                std::cout << "scheduler step: " << lr_scheduler_step;
+            }
+            else if (lr_schedule.size() == 0)
             {
                 if (test_previous_loss_values.size() == 0)
                     std::cout << "steps without apparent progress: " << steps_without_progress;
@@ -1320,8 +1936,34 @@ namespace dlib
         unsigned long long test_one_step_calls;
         matrix<double,0,1> lr_schedule;
         long lr_schedule_pos;
+        learning_rate_scheduler lr_scheduler;
+        unsigned long long lr_scheduler_step;
+        unsigned long bptt_chunks;
+        unsigned long bptt_chunk_pos;
+        bool mixed_precision;
+        half_format mixed_precision_format;
+        double loss_scale;
+        bool dynamic_loss_scaling;
+        unsigned long steps_since_loss_scale_change;
+        unsigned long long num_skipped_steps;
+        const static unsigned long loss_scale_growth_interval = 2000;
+        unsigned long gradient_accumulation_steps;
+        unsigned long gradient_accumulation_pos;
+        double gradient_clip_norm;
+        double gradient_clip_value;
+        unsigned long long num_training_steps;
+        double weight_ema_decay;
+        std::vector<resizable_tensor> weight_ema;
+        bool weight_ema_swapped;
         unsigned long gradient_check_budget;
 
+        // The callbacks and stop flag are not serialized.
+        std::vector<callback_type> step_callbacks;
+        std::vector<callback_type> test_step_callbacks;
+        std::vector<callback_type> epoch_callbacks;
+        std::vector<callback_type> learning_rate_callbacks;
+        std::atomic<bool> stop_training;
+
         std::exception_ptr eptr = nullptr;
         mutable std::mutex eptr_mutex;
         void propagate_exception() const
@@ -1382,6 +2024,12 @@ namespace dlib
         {
             out << "  using explicit user-supplied learning rate schedule" << endl;
         }
+        else if (!trainer.get_learning_rate_scheduler().is_empty())
+        {
+            out << "  learning rate scheduler:                    "<< trainer.get_learning_rate_scheduler() << endl;
+            out << "  learning rate scheduler step:               "<< trainer.get_learning_rate_scheduler_step() << endl;
+            out << "  learning rate:                              "<< trainer.get_learning_rate() << endl;
+        }
         else
         {
             out << "  learning rate:                              "<< trainer.get_learning_rate() << endl;
@@ -1390,6 +2038,24 @@ namespace dlib
             out << "  iterations without progress threshold:      "<< trainer.get_iterations_without_progress_threshold() << endl;
             out << "  test iterations without progress threshold: "<< trainer.get_test_iterations_without_progress_threshold() << endl;
         }
+        if (trainer.get_truncated_bptt_chunks() != 0)
+            out << "  truncated BPTT chunks:                      "<< trainer.get_truncated_bptt_chunks() << endl;
+        if (trainer.uses_mixed_precision())
+        {
+            out << "  mixed precision:                            "<< trainer.get_mixed_precision_format() << endl;
+            out << "  loss scale:                                 "<< trainer.get_loss_scale()
+                << (trainer.uses_dynamic_loss_scaling() ? " (dynamic)" : "") << endl;
+            out << "  skipped steps:                              "<< trainer.get_num_skipped_steps() << endl;
+        }
+        if (trainer.get_gradient_accumulation_steps() > 1)
+            out << "  gradient accumulation steps:                "<< trainer.get_gradient_accumulation_steps() << endl;
+        if (trainer.get_gradient_clip_norm() > 0)
+            out << "  gradient clip norm:                         "<< trainer.get_gradient_clip_norm() << endl;
+        if (trainer.get_gradient_clip_value() > 0)
+            out << "  gradient clip value:                        "<< trainer.get_gradient_clip_value() << endl;
+        if (trainer.uses_weight_ema())
+            out << "  weight EMA decay:                           "<< trainer.get_weight_ema_decay()
+                << (trainer.weight_ema_swapped_in() ? " (swapped in)" : "") << endl;
         return out;
     }
 
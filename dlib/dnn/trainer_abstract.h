@@ -5,8 +5,10 @@
 
 #include "core_abstract.h"
 #include "solvers_abstract.h"
+#include "../cuda/half_tensor_abstract.h"
 #include <vector>
 #include <chrono>
+#include <functional>
 
 
 namespace dlib
@@ -60,6 +62,24 @@ namespace dlib
 
         using threads = std::vector<std::shared_ptr<thread_pool>>;
 
+        struct training_progress
+        {
+            /*!
+                WHAT THIS OBJECT REPRESENTS
+                    This object is what the trainer gives to the callbacks registered with
+                    add_step_callback(), add_test_step_callback(), add_epoch_callback(),
+                    and add_learning_rate_callback().  It describes the state of training
+                    at the moment the callback is invoked.
+            !*/
+
+            double loss;              // The loss that goes with the event.  See the add_*_callback() docs.
+            double learning_rate;     // The learning rate that goes with the event.
+            unsigned long long step;  // The value of get_num_training_steps() at the time of the event.
+            const net_type& net;      // The network being trained.
+        };
+
+        typedef std::function<void(const training_progress&)> callback_type;
+
         dnn_trainer() = delete;
         dnn_trainer(const dnn_trainer&) = delete;
         dnn_trainer& operator=(const dnn_trainer&) = delete;
@@ -89,9 +109,25 @@ namespace dlib
                 - #get_test_iterations_without_progress_threshold() == 500
                 - #get_learning_rate_shrink_factor() == 0.1
                 - #get_learning_rate_schedule().size() == 0
+                - #get_learning_rate_scheduler().is_empty() == true
                 - #get_train_one_step_calls() == 0
                 - #get_test_one_step_calls() == 0
                 - #get_synchronization_file() == ""
+                - #get_truncated_bptt_chunks() == 0
+                - #uses_mixed_precision() == false
+                - #get_mixed_precision_format() == half_format::fp16
+                - #get_loss_scale() == 65536
+                - #uses_dynamic_loss_scaling() == true
+                - #get_num_skipped_steps() == 0
+                - #get_gradient_accumulation_steps() == 1
+                - #get_gradient_clip_norm() == 0
+                - #get_gradient_clip_value() == 0
+                - #get_weight_ema_decay() == 0
+                - #uses_weight_ema() == false
+                - #weight_ema_swapped_in() == false
+                - #get_num_training_steps() == 0
+                - #stop_requested() == false
+                - No callbacks are registered.
                 - if (cuda_extra_devices.size() > 0) then
                     - This object will use multiple graphics cards to run the learning
                       algorithms.  In particular, it will always use whatever device is
@@ -190,6 +226,7 @@ namespace dlib
             ensures
                 - #get_learning_rate() == lr
                 - #get_learning_rate_schedule().size() == 0
+                - #get_learning_rate_scheduler().is_empty() == true
                 - This function blocks until all threads inside the dnn_trainer have
                   stopped touching the net. 
         !*/
@@ -216,6 +253,7 @@ namespace dlib
             ensures
                 - #get_min_learning_rate() == lr
                 - #get_learning_rate_schedule().size() == 0
+                - #get_learning_rate_scheduler().is_empty() == true
                 - This function blocks until all threads inside the dnn_trainer have
                   stopped touching the net. 
         !*/
@@ -247,6 +285,7 @@ namespace dlib
                 - #get_learning_rate() == schedule(0,0)
                 - #get_min_learning_rate() == min(schedule)
                 - #set_learning_rate_shrink_factor() == 1
+                - #get_learning_rate_scheduler().is_empty() == true
         !*/
 
         const matrix<double,0,1>& get_learning_rate_schedule (
@@ -266,6 +305,57 @@ namespace dlib
                       final value.  So in our example, eventually the learning rate would
                       be fixed to 0.99*0.06.  This allows you to test if we have reached the
                       end of the schedule by checking if get_learning_rate() >= 0.06.
+                    - When accumulating gradients, the schedule moves to its next value
+                      only on the mini-batches that update the parameters.  That is, once
+                      every get_gradient_accumulation_steps() mini-batches.
+        !*/
+
+        template <typename scheduler_type>
+        void set_learning_rate_scheduler (
+            const scheduler_type& scheduler
+        );
+        /*!
+            requires
+                - scheduler_type is one of the types held by learning_rate_scheduler (see
+                  dlib/dnn/lr_schedulers_abstract.h), e.g. cosine_restarts_scheduler.
+            ensures
+                - #get_learning_rate_scheduler().contains<scheduler_type>() == true
+                - #get_learning_rate_scheduler_step() == 0
+                - #get_learning_rate() == scheduler(0)
+                - #get_min_learning_rate() == scheduler.get_min_learning_rate()
+                - #get_learning_rate_shrink_factor() == 1
+                - #get_learning_rate_schedule().size() == 0
+                - This function blocks until all threads inside the dnn_trainer have
+                  stopped touching the net. 
+        !*/
+
+        const learning_rate_scheduler& get_learning_rate_scheduler (
+        ) const;
+        /*!
+            ensures
+                - if (get_learning_rate_scheduler().is_empty() == false) then
+                    - This trainer sets the learning rate for each training mini-batch by
+                      evaluating the contained scheduler.  That is, the learning rate used
+                      for a mini-batch is S(get_learning_rate_scheduler_step()), where S is
+                      the scheduler, and the step counter is incremented after each
+                      mini-batch.  The scheduler and the step counter are saved in the
+                      synchronization file, so training resumed from it continues on the
+                      same learning rate curve.
+                    - When accumulating gradients, the step counter is only incremented
+                      on the mini-batches that update the parameters.  That is, once every
+                      get_gradient_accumulation_steps() mini-batches.
+                    - Since the scheduler never returns a value below
+                      get_min_learning_rate(), train() will run until
+                      get_max_num_epochs() is reached.
+        !*/
+
+        unsigned long long get_learning_rate_scheduler_step (
+        ) const;
+        /*!
+            ensures
+                - returns the number of parameter updates that have been made since
+                  set_learning_rate_scheduler() was called.  Unless gradients are being
+                  accumulated this is the number of training mini-batches processed.
         !*/
 
         unsigned long get_steps_without_progress (
@@ -286,6 +376,7 @@ namespace dlib
             ensures
                 - #get_iterations_without_progress_threshold() == thresh
                 - #get_learning_rate_schedule().size() == 0
+                - #get_learning_rate_scheduler().is_empty() == true
                 - This function blocks until all threads inside the dnn_trainer have
                   stopped touching the net. 
         !*/
@@ -317,6 +408,7 @@ namespace dlib
             ensures
                 - #get_learning_rate_shrink_factor() == shrink
                 - #get_learning_rate_schedule().size() == 0
+                - #get_learning_rate_scheduler().is_empty() == true
                 - This function blocks until all threads inside the dnn_trainer have
                   stopped touching the net. 
         !*/
@@ -331,6 +423,398 @@ namespace dlib
                   get_learning_rate_shrink_factor() to 1.
         !*/
 
+        void set_truncated_bptt_chunks (
+            unsigned long num_chunks
+        );
+        /*!
+            ensures
+                - #get_truncated_bptt_chunks() == num_chunks
+                - Calls set_recurrent_stateful(net, num_chunks != 0) on the network (and its
+                  copies on any other devices).
+                - This function blocks until all threads inside the dnn_trainer have
+                  stopped touching the net. 
+        !*/
+
+        unsigned long get_truncated_bptt_chunks (
+        ) const;
+        /*!
+            ensures
+                - returns the number of chunks each training sequence is split into when
+                  doing truncated backpropagation through time with lstm_ or gru_ layers.
+                  0 means truncated backpropagation through time is disabled, which is the
+                  default.
+                - When this is non-zero the trainer expects each training mini-batch to hold
+                  the next chunk (along the nr dimension) of the same sequences as the
+                  previous mini-batch, with each sequence split into
+                  get_truncated_bptt_chunks() chunks.  The recurrent layers carry their
+                  state from one mini-batch to the next, and gradients flow back only
+                  through the current chunk.  The states are reset to zero at the start of
+                  every get_truncated_bptt_chunks() training steps.  Testing steps are run
+                  from a zero state and don't disturb the state used for training.
+                - The position within the current group of chunks is saved in the
+                  synchronization file along with the rest of the trainer state.
+        !*/
+
+        void reset_recurrent_states (
+        );
+        /*!
+            ensures
+                - Resets the state of all lstm_ and gru_ layers in the network, so that the
+                  next training step is treated as the first chunk of new sequences.  Use
+                  this if your sequences aren't all split into get_truncated_bptt_chunks()
+                  chunks.
+                - This function blocks until all threads inside the dnn_trainer have
+                  stopped touching the net. 
+        !*/
+
+        void set_mixed_precision (
+            half_format fmt
+        );
+        /*!
+            ensures
+                - #uses_mixed_precision() == true
+                - #get_mixed_precision_format() == fmt
+                - This function blocks until all threads inside the dnn_trainer have
+                  stopped touching the net. 
+        !*/
+
+        void disable_mixed_precision (
+        );
+        /*!
+            ensures
+                - #uses_mixed_precision() == false
+                - This function blocks until all threads inside the dnn_trainer have
+                  stopped touching the net. 
+        !*/
+
+        bool uses_mixed_precision (
+        ) const;
+        /*!
+            ensures
+                - returns true if the trainer simulates 16 bit training.  When it does,
+                  the network parameters are kept in 32 bit floats, the master weights,
+                  but each training step does its forward and backward passes with the
+                  parameters rounded to get_mixed_precision_format().  The resulting
+                  gradients are multiplied by get_loss_scale(), rounded to the same 16 bit
+                  format, and divided by get_loss_scale() again before the solvers apply
+                  them to the master weights.  So training sees the same rounding and
+                  overflow behavior as real 16 bit training while the arithmetic is still
+                  done in floats.
+                - If any of the scaled gradients overflow the step is skipped: the
+                  parameters are not updated and get_num_skipped_steps() is incremented.
+                - Testing steps and get_net() always use the 32 bit master weights.
+        !*/
+
+        half_format get_mixed_precision_format (
+        ) const;
+        /*!
+            ensures
+                - returns the 16 bit format used when uses_mixed_precision() == true.
+        !*/
+
+        void set_loss_scale (
+            double scale
+        );
+        /*!
+            requires
+                - scale > 0
+            ensures
+                - #get_loss_scale() == scale
+        !*/
+
+        double get_loss_scale (
+        ) const;
+        /*!
+            ensures
+                - returns the number the gradients are multiplied by before being rounded
+                  to 16 bits when uses_mixed_precision() == true.  Small gradients
+                  underflow to 0 in fp16, so scaling them up first preserves them.
+        !*/
+
+        void set_dynamic_loss_scaling (
+            bool enabled
+        );
+        /*!
+            ensures
+                - #uses_dynamic_loss_scaling() == enabled
+        !*/
+
+        bool uses_dynamic_loss_scaling (
+        ) const;
+        /*!
+            ensures
+                - returns true if the trainer adjusts get_loss_scale() on its own while
+                  doing mixed precision training.  In this case, the loss scale is halved
+                  (but never made smaller than 1) every time a step is skipped because of
+                  an overflow, and doubled after 2000 steps in a row without an overflow.
+                  This keeps the loss scale as large as possible without overflowing.
+        !*/
+
+        unsigned long long get_num_skipped_steps (
+        ) const;
+        /*!
+            ensures
+                - returns the number of training steps that were skipped because the
+                  scaled gradients overflowed during mixed precision training.
+        !*/
+
+        void set_gradient_accumulation_steps (
+            unsigned long num_steps
+        );
+        /*!
+            requires
+                - num_steps > 0
+            ensures
+                - #get_gradient_accumulation_steps() == num_steps
+                - Any gradients accumulated so far are discarded.
+                - This function blocks until all threads inside the dnn_trainer have
+                  stopped touching the net. 
+        !*/
+
+        unsigned long get_gradient_accumulation_steps (
+        ) const;
+        /*!
+            ensures
+                - returns the number of training mini-batches whose gradients are combined
+                  into each parameter update.  If this is N > 1 then the trainer only
+                  updates the parameters on every N-th mini-batch, using the average of the
+                  gradients of the last N mini-batches.  So training with a mini-batch size
+                  of B and N accumulation steps gives the same updates as a mini-batch size
+                  of N*B, but only needs the memory for B samples at a time.
+                - If a mixed precision step is skipped because of an overflow then the
+                  gradients accumulated so far are discarded too and accumulation starts
+                  over with the next mini-batch.
+                - The accumulated gradients are not saved in the synchronization file.  A
+                  trainer loaded from it starts a fresh accumulation.
+        !*/
+
+        void set_gradient_clip_norm (
+            double max_norm
+        );
+        /*!
+            requires
+                - max_norm >= 0
+            ensures
+                - #get_gradient_clip_norm() == max_norm
+                - This function blocks until all threads inside the dnn_trainer have
+                  stopped touching the net. 
+        !*/
+
+        double get_gradient_clip_norm (
+        ) const;
+        /*!
+            ensures
+                - returns the largest L2 norm the gradient may have when it's given to the
+                  solvers, or 0 if the gradient norm isn't clipped.  The norm is computed
+                  over the gradients of all the network's parameters together.  If it's
+                  bigger than get_gradient_clip_norm() then all the gradients are scaled
+                  down by the same factor so that it's equal to get_gradient_clip_norm().
+                - Clipping happens right before the solvers run.  That is, after the
+                  gradients have been accumulated and averaged over all devices.
+        !*/
+
+        void set_gradient_clip_value (
+            double max_value
+        );
+        /*!
+            requires
+                - max_value >= 0
+            ensures
+                - #get_gradient_clip_value() == max_value
+                - This function blocks until all threads inside the dnn_trainer have
+                  stopped touching the net. 
+        !*/
+
+        double get_gradient_clip_value (
+        ) const;
+        /*!
+            ensures
+                - returns the largest absolute value any element of the gradient may have
+                  when it's given to the solvers, or 0 if the gradient isn't clipped by
+                  value.  Elements outside [-get_gradient_clip_value(),
+                  get_gradient_clip_value()] are clamped to that range right before the
+                  solvers run.
+                - If both kinds of clipping are enabled then the values are clamped first
+                  and then the norm is clipped.
+        !*/
+
+        void set_weight_ema_decay (
+            double decay
+        );
+        /*!
+            requires
+                - 0 <= decay < 1
+                - weight_ema_swapped_in() == false
+            ensures
+                - #get_weight_ema_decay() == decay
+                - if (decay == 0) then
+                    - The moving average of the parameters is discarded and no longer
+                      maintained.
+                - This function blocks until all threads inside the dnn_trainer have
+                  stopped touching the net. 
+        !*/
+
+        double get_weight_ema_decay (
+        ) const;
+        /*!
+            ensures
+                - returns the decay of the exponential moving average (EMA) of the network
+                  parameters kept by this object, or 0 if no average is kept.  When it's
+                  non-zero, each parameter update is followed by:
+                    ema = get_weight_ema_decay()*ema + (1-get_weight_ema_decay())*params
+                  The average starts out equal to the parameters after the first update.
+                  Typical values are 0.999 or 0.9999.  Evaluating a network with the
+                  averaged parameters often gives noticeably better results than with the
+                  final parameters, especially for detectors and GANs.
+                - The average is kept in a separate copy of the parameters, which is saved
+                  in the synchronization file.  When training on several devices it is
+                  computed once from the parameters of the first device, since all the
+                  devices have the same parameters.
+        !*/
+
+        bool uses_weight_ema (
+        ) const;
+        /*!
+            ensures
+                - returns get_weight_ema_decay() != 0
+        !*/
+
+        void swap_weight_ema (
+        );
+        /*!
+            requires
+                - uses_weight_ema() == true
+            ensures
+                - Exchanges the parameters of get_net() with their moving average.  So
+                  after calling swap_weight_ema() get_net() holds the averaged parameters
+                  and can be evaluated or saved.  Calling it again puts the training
+                  parameters back.
+                - #weight_ema_swapped_in() == !weight_ema_swapped_in()
+                - Layers for which no average exists yet (because no training step has
+                  run) are left alone.
+                - This function blocks until all threads inside the dnn_trainer have
+                  stopped touching the net. 
+        !*/
+
+        bool weight_ema_swapped_in (
+        ) const;
+        /*!
+            ensures
+                - returns true if swap_weight_ema() has put the averaged parameters into
+                  get_net().  While this is true you can call test_one_step() to measure
+                  the loss of the averaged parameters, but you must not train.  That is,
+                  calling train() or train_one_step() is an error.
+        !*/
+
+        void add_step_callback (
+            callback_type f
+        );
+        /*!
+            ensures
+                - Arranges for f to be called after each training mini-batch has been
+                  processed, including the mini-batches that only accumulate gradients.
+                  The training_progress given to f contains the loss of that mini-batch,
+                  the learning rate that was used for it, and the number of training
+                  mini-batches processed so far (this one included).
+                - f is called from the trainer's internal thread, while no other training
+                  work is going on.  Therefore, f may look at progress.net (e.g. to
+                  evaluate it on a validation set or to serialize it) but must not call
+                  any member functions of this dnn_trainer, since many of them wait for
+                  the internal thread to pause and would deadlock.  The exception is
+                  request_stop(), which may be called from anywhere.
+                - If f throws then the exception is propagated to the user the same way an
+                  exception from the network is.
+                - This function blocks until all threads inside the dnn_trainer have
+                  stopped touching the net. 
+        !*/
+
+        void add_test_step_callback (
+            callback_type f
+        );
+        /*!
+            ensures
+                - Arranges for f to be called after each call to test_one_step() has been
+                  processed.  The training_progress given to f contains the testing loss of
+                  that mini-batch and the current learning rate.
+                - f is called from the trainer's internal thread, so the same restrictions
+                  described in add_step_callback() apply to it.
+                - This function blocks until all threads inside the dnn_trainer have
+                  stopped touching the net. 
+        !*/
+
+        void add_epoch_callback (
+            callback_type f
+        );
+        /*!
+            ensures
+                - Arranges for f to be called by train() at the end of each training
+                  epoch.  The training_progress given to f contains the average loss over
+                  the epoch (i.e. get_average_loss()) and the current learning rate.
+                - f is called from the thread that called train(), after the internal
+                  thread has finished all the work for the epoch.  So unlike the step
+                  callbacks, f may call member functions of this dnn_trainer.  For
+                  instance, it may call get_average_test_loss() or request_stop().
+                - This function blocks until all threads inside the dnn_trainer have
+                  stopped touching the net. 
+        !*/
+
+        void add_learning_rate_callback (
+            callback_type f
+        );
+        /*!
+            ensures
+                - Arranges for f to be called whenever the trainer changes the learning
+                  rate on its own.  That is, when it shrinks the learning rate because
+                  training stopped making progress, or when it moves to the next value of
+                  the learning rate schedule or scheduler.  The training_progress given to
+                  f contains the new learning rate and the loss of the mini-batch that
+                  triggered the change.  Calls to set_learning_rate() don't trigger f.
+                - f may be called from the trainer's internal thread, so the same
+                  restrictions described in add_step_callback() apply to it.
+                - This function blocks until all threads inside the dnn_trainer have
+                  stopped touching the net. 
+        !*/
+
+        void clear_callbacks (
+        );
+        /*!
+            ensures
+                - Removes all the callbacks registered with this object.
+                - This function blocks until all threads inside the dnn_trainer have
+                  stopped touching the net. 
+        !*/
+
+        void request_stop (
+        );
+        /*!
+            ensures
+                - #stop_requested() == true
+                - Any train() call that is running will return as soon as it has finished
+                  with the current mini-batch, just as if it had run out of epochs.  If
+                  this happens in the middle of an epoch then the position in the epoch is
+                  remembered, so calling train() again resumes from there.
+                - This function is safe to call from any thread, including from inside a
+                  callback.  This makes it easy to implement early stopping based on any
+                  criteria you like.
+        !*/
+
+        bool stop_requested (
+        ) const;
+        /*!
+            ensures
+                - returns true if request_stop() has been called since the last call to
+                  train() started.  train() resets this to false when it starts.  
+        !*/
+
+        unsigned long long get_num_training_steps (
+        ) const;
+        /*!
+            ensures
+                - returns the number of training mini-batches this object has processed,
+                  whether they came from train() or train_one_step().  This value is saved
+                  in the synchronization file.
+        !*/
+
         unsigned long long get_train_one_step_calls (
         ) const;
         /*!
@@ -398,6 +882,7 @@ namespace dlib
         ); 
         /*!
             requires
+                - weight_ema_swapped_in() == false
                 - data.size() == labels.size()
                 - data.size() > 0
                 - net_type uses a supervised loss.  
@@ -407,7 +892,11 @@ namespace dlib
                   The goal of training is to find the network parameters that minimize
                   get_net().compute_loss(data.begin(), data.end(), labels.begin()). 
                 - The optimizer will run until get_learning_rate() < get_min_learning_rate() 
-                  or get_max_num_epochs() training epochs have been executed. 
+                  or get_max_num_epochs() training epochs have been executed or
+                  request_stop() is called. 
+                - #stop_requested() is reset to false when train() starts.
+                - The callbacks registered with add_epoch_callback() are called at the end
+                  of each epoch.
                 - Each layer in the network will be optimized by its corresponding solver
                   in get_solvers().  
                 - Each call to train DOES NOT reinitialize the state of get_net() or
@@ -427,6 +916,7 @@ namespace dlib
         );
         /*!
             requires 
+                - weight_ema_swapped_in() == false
                 - data.size() > 0
                 - net_type uses an unsupervised loss.  
                   i.e. net_type::training_label_type == no_label_type.
@@ -435,7 +925,11 @@ namespace dlib
                   The goal of training is to find the network parameters that minimize
                   get_net().compute_loss(data.begin(), data.end()). 
                 - The optimizer will run until get_learning_rate() < get_min_learning_rate() 
-                  or get_max_num_epochs() training epochs have been executed. 
+                  or get_max_num_epochs() training epochs have been executed or
+                  request_stop() is called. 
+                - #stop_requested() is reset to false when train() starts.
+                - The callbacks registered with add_epoch_callback() are called at the end
+                  of each epoch.
                 - Each layer in the network will be optimized by its corresponding solver
                   in get_solvers().  
                 - Each call to train DOES NOT reinitialize the state of get_net() or
@@ -456,6 +950,7 @@ namespace dlib
         );
         /*!
             requires
+                - weight_ema_swapped_in() == false
                 - data.size() == labels.size()
                 - data.size() > 0
                 - net_type uses a supervised loss.  
@@ -487,6 +982,7 @@ namespace dlib
         );
         /*!
             requires
+                - weight_ema_swapped_in() == false
                 - std::advance(lbegin, std::distance(dbegin, dend) - 1) is dereferencable
                 - std::distance(dbegin, dend) > 0
                 - net_type uses a supervised loss.  
@@ -512,6 +1008,7 @@ namespace dlib
         );
         /*!
             requires
+                - weight_ema_swapped_in() == false
                 - data.size() > 0
                 - net_type uses an unsupervised loss.  
                   i.e. net_type::training_label_type == no_label_type.
@@ -540,6 +1037,7 @@ namespace dlib
         );
         /*!
             requires
+                - weight_ema_swapped_in() == false
                 - std::distance(dbegin, dend) > 0
                 - net_type uses an unsupervised loss.  
                   i.e. net_type::training_label_type == no_label_type.
@@ -719,6 +1217,7 @@ namespace dlib
             ensures
                 - #get_test_iterations_without_progress_threshold() == thresh
                 - #get_learning_rate_schedule().size() == 0
+                - #get_learning_rate_scheduler().is_empty() == true
                 - This function blocks until all threads inside the dnn_trainer have
                   stopped touching the net. 
         !*/
@@ -7,6 +7,11 @@
 #include "input.h"
 #include "layers.h"
 #include "loss.h"
+#include "../cuda/half_tensor.h"
+#include "../image_transforms/assign_image.h"
+#include <functional>
+#include <iomanip>
+#include <map>
 
 namespace dlib
 {
@@ -190,6 +195,307 @@ namespace dlib
         return num_parameters;
     }
 
+// ----------------------------------------------------------------------------------------
+
+    struct layer_summary
+    {
+        size_t index = 0;
+        std::string type;
+        long num_samples = 0;
+        long k = 0;
+        long nr = 0;
+        long nc = 0;
+        size_t num_parameters = 0;
+        size_t num_multiply_adds = 0;
+        size_t activation_memory = 0;
+    };
+
+    struct net_summary_table
+    {
+        std::vector<layer_summary> layers;
+
+        size_t num_parameters (
+        ) const
+        {
+            size_t total = 0;
+            for (const auto& l : layers)
+                total += l.num_parameters;
+            return total;
+        }
+
+        size_t num_multiply_adds (
+        ) const
+        {
+            size_t total = 0;
+            for (const auto& l : layers)
+                total += l.num_multiply_adds;
+            return total;
+        }
+
+        size_t activation_memory (
+        ) const
+        {
+            size_t total = 0;
+            for (const auto& l : layers)
+                total += l.activation_memory;
+            return total;
+        }
+    };
+
+    inline std::ostream& operator<< (
+        std::ostream& out,
+        const net_summary_table& item
+    )
+    {
+        auto shape_to_str = [](const layer_summary& l) -> std::string
+        {
+            if (l.num_samples == 0)
+                return "";
+            std::ostringstream sout;
+            sout << l.num_samples << "x" << l.k << "x" << l.nr << "x" << l.nc;
+            return sout.str();
+        };
+
+        size_t type_width = 5;
+        size_t shape_width = 12;
+        for (const auto& l : item.layers)
+        {
+            type_width = std::max(type_width, l.type.size());
+            shape_width = std::max(shape_width, shape_to_str(l).size());
+        }
+
+        const auto flags = out.flags();
+        out << std::left << std::setw(8) << "layer" << std::setw(type_width+2) << "type"
+            << std::setw(shape_width+2) << "output shape" << std::right << std::setw(14) << "parameters"
+            << std::setw(16) << "multiply-adds" << std::setw(16) << "memory (bytes)" << "\n";
+        for (const auto& l : item.layers)
+        {
+            out << std::left << std::setw(8) << l.index << std::setw(type_width+2) << l.type
+                << std::setw(shape_width+2) << shape_to_str(l) << std::right << std::setw(14) << l.num_parameters
+                << std::setw(16) << l.num_multiply_adds << std::setw(16) << l.activation_memory << "\n";
+        }
+        out << std::left << std::setw(8+type_width+2+shape_width+2) << "total" << std::right
+            << std::setw(14) << item.num_parameters() << std::setw(16) << item.num_multiply_adds()
+            << std::setw(16) << item.activation_memory() << "\n";
+        out.flags(flags);
+        return out;
+    }
+
+    namespace impl
+    {
+        class visitor_net_summary
+        {
+        public:
+
+            visitor_net_summary(
+                std::vector<layer_summary>& layers_,
+                const tensor& x
+            ) : layers(layers_), input(&x) {}
+
+            // This visitor goes from the input layer to the loss layer, so input always
+            // points to the output of the last layer it saw, which is the input of the
+            // current layer.
+            template <typename input_layer_type>
+            void operator()(size_t idx, const input_layer_type& l)
+            {
+                layer_summary& s = layers[idx];
+                s.type = type_name(l);
+                set_shape(s, *input);
+                s.activation_memory = input->size()*sizeof(float);
+            }
+
+            template <typename T, typename U>
+            void operator()(size_t idx, const add_loss_layer<T,U>& l)
+            {
+                layers[idx].type = type_name(l.loss_details());
+            }
+
+            template <typename T, typename U, typename E>
+            void operator()(size_t idx, add_layer<T,U,E>& l)
+            {
+                // Layers with an in-place layer on top of them don't allow access to their
+                // outputs, but the subnet_wrapper given to the layer above does.
+                const dimpl::subnet_wrapper<add_layer<T,U,E>> wl(l);
+                const tensor& output = wl.get_output();
+
+                layer_summary& s = layers[idx];
+                s.type = type_name(l.layer_details());
+                set_shape(s, output);
+                s.num_parameters = l.layer_details().get_layer_params().size();
+                s.num_multiply_adds = multiply_adds(l.layer_details(), *input, output);
+                // In-place layers write over the output of the layer below them.
+                if (&output != input)
+                    s.activation_memory = output.size()*sizeof(float);
+                input = &output;
+            }
+
+            template <unsigned long ID, typename U, typename E>
+            void operator()(size_t idx, const add_tag_layer<ID,U,E>& )
+            {
+                layer_summary& s = layers[idx];
+                s.type = "tag" + std::to_string(ID);
+                set_shape(s, *input);
+                tagged_outputs[ID] = input;
+            }
+
+            template <template<typename> class TAG_TYPE, typename U>
+            void operator()(size_t idx, const add_skip_layer<TAG_TYPE,U>& )
+            {
+                // The skip layer outputs whatever the nearest tag layer below it with
+                // the same ID saw, which is the last one we visited.
+                input = tagged_outputs.at(tag_id<TAG_TYPE>::id);
+                layer_summary& s = layers[idx];
+                s.type = "skip" + std::to_string(tag_id<TAG_TYPE>::id);
+                set_shape(s, *input);
+            }
+
+        private:
+
+            template <typename T>
+            static std::string type_name(const T& item)
+            {
+                // The type is the first word of what the layer prints, e.g. "con" for a
+                // con_ layer.
+                std::ostringstream sout;
+                sout << item;
+                const std::string str = sout.str();
+                return str.substr(0, str.find_first_of("\t ("));
+            }
+
+            static void set_shape(layer_summary& s, const tensor& t)
+            {
+                s.num_samples = t.num_samples();
+                s.k = t.k();
+                s.nr = t.nr();
+                s.nc = t.nc();
+            }
+
+            template <typename T>
+            static size_t multiply_adds(const T&, const tensor&, const tensor&)
+            {
+                // The other layers do a small number of operations per output value.
+                return 0;
+            }
+
+            template <long nf, long nr, long nc, int sy, int sx, int py, int px, int dy, int dx, long ng>
+            static size_t multiply_adds(const con_<nf,nr,nc,sy,sx,py,px,dy,dx,ng>& l, const tensor& input, const tensor& output)
+            {
+                return output.size()*(input.k()/l.groups())*l.nr()*l.nc();
+            }
+
+            template <long nf, long nr, long nc, int sy, int sx, int py, int px, int dy, int dx, long ng>
+            static size_t multiply_adds(const cont_<nf,nr,nc,sy,sx,py,px,dy,dx,ng>& l, const tensor& input, const tensor& )
+            {
+                return input.size()*(l.num_filters()/l.groups())*l.nr()*l.nc();
+            }
+
+            template <unsigned long no, fc_bias_mode bm>
+            static size_t multiply_adds(const fc_<no,bm>& , const tensor& input, const tensor& output)
+            {
+                return output.size()*(input.size()/input.num_samples());
+            }
+
+            template <unsigned long no>
+            static size_t multiply_adds(const lstm_<no>& l, const tensor& input, const tensor& output)
+            {
+                // Each time step multiplies the input and the previous hidden state by the
+                // weights of the 4 gates.
+                const size_t H = l.get_num_outputs();
+                return output.size()/H*(input.nc() + H)*4*H;
+            }
+
+            template <unsigned long no>
+            static size_t multiply_adds(const gru_<no>& l, const tensor& input, const tensor& output)
+            {
+                const size_t H = l.get_num_outputs();
+                return output.size()/H*(input.nc() + H)*3*H;
+            }
+
+            template <long nh, bool cm, int dr>
+            static size_t multiply_adds(const multihead_attention_<nh,cm,dr>& , const tensor& input, const tensor& )
+            {
+                // The input and output projections, plus the query-key products and the
+                // weighted sums of the values.
+                const size_t d = input.nc();
+                const size_t T = input.nr();
+                return input.size()*4*d + input.num_samples()*input.k()*2*T*T*d;
+            }
+
+            std::vector<layer_summary>& layers;
+            const tensor* input;
+            std::map<unsigned long, const tensor*> tagged_outputs;
+        };
+    }
+
+    template <typename net_type>
+    net_summary_table net_summary (
+        net_type& net,
+        const typename net_type::input_type& sample
+    )
+    {
+        resizable_tensor x;
+        net.to_tensor(&sample, &sample+1, x);
+        net.forward(x);
+
+        net_summary_table summary;
+        summary.layers.resize(net_type::num_layers);
+        for (size_t i = 0; i < summary.layers.size(); ++i)
+            summary.layers[i].index = i;
+        visit_layers_backwards(net, impl::visitor_net_summary(summary.layers, x));
+        return summary;
+    }
+
+    template <typename net_type>
+    net_summary_table net_summary (
+        net_type& net,
+        long nr,
+        long nc
+    )
+    {
+        DLIB_CASSERT(nr > 0 && nc > 0);
+        typename net_type::input_type sample;
+        sample.set_size(nr, nc);
+        assign_all_pixels(sample, 0);
+        return net_summary(net, sample);
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    template <typename net_type>
+    std::vector<half_tensor> get_half_precision_parameters (
+        const net_type& net,
+        half_format fmt
+    )
+    {
+        std::vector<half_tensor> params;
+        visit_layer_parameters(net, [&](const tensor& t) { params.emplace_back(t, fmt); });
+        return params;
+    }
+
+    template <typename net_type>
+    void set_half_precision_parameters (
+        net_type& net,
+        const std::vector<half_tensor>& params
+    )
+    {
+        DLIB_CASSERT(params.size() == net_type::num_computational_layers);
+        visit_layer_parameters(net, [&](size_t i, tensor& t)
+        {
+            DLIB_CASSERT(t.size() == params[i].size(),
+                "The network's parameters must already be allocated and have the same shape as the saved ones.");
+            params[i].copy_to(t);
+        });
+    }
+
+    template <typename net_type>
+    void round_parameters_to_half_precision (
+        net_type& net,
+        half_format fmt
+    )
+    {
+        visit_layer_parameters(net, [&](tensor& t) { tt::round_to_half_precision(t, fmt); });
+    }
+
 // ----------------------------------------------------------------------------------------
 
     namespace impl
@@ -285,6 +591,113 @@ namespace dlib
         visit_layers(net, impl::visitor_bn_running_stats_window_size(new_window_size));
     }
 
+// ----------------------------------------------------------------------------------------
+
+    namespace impl
+    {
+        class visitor_recurrent_state
+        {
+        public:
+
+            enum action
+            {
+                set_stateful,
+                reset,
+                save,
+                restore
+            };
+
+            visitor_recurrent_state(
+                action what_,
+                bool stateful_,
+                std::vector<resizable_tensor>* states_ = nullptr
+            ) : what(what_), stateful(stateful_), states(states_) {}
+
+            template <typename T>
+            void update(T&) const
+            {
+                // ignore other layer detail types
+            }
+
+            template <unsigned long num_outputs>
+            void update(lstm_<num_outputs>& l) { update_recurrent(l); }
+
+            template <unsigned long num_outputs>
+            void update(gru_<num_outputs>& l) { update_recurrent(l); }
+
+            template<typename input_layer_type>
+            void operator()(size_t , input_layer_type& ) 
+            {
+                // ignore other layers
+            }
+
+            template <typename T, typename U, typename E>
+            void operator()(size_t , add_layer<T,U,E>& l)
+            {
+                update(l.layer_details());
+            }
+
+        private:
+
+            template <typename layer_type>
+            void update_recurrent(layer_type& l)
+            {
+                switch (what)
+                {
+                    case set_stateful: l.set_stateful(stateful); break;
+                    case reset: l.reset_state(); break;
+                    case save: states->push_back(l.get_state()); break;
+                    case restore: l.set_state((*states)[next++]); break;
+                }
+            }
+
+            action what;
+            bool stateful;
+            std::vector<resizable_tensor>* states;
+            size_t next = 0;
+        };
+
+        template <typename net_type>
+        std::vector<resizable_tensor> save_recurrent_states (
+            net_type& net
+        )
+        {
+            std::vector<resizable_tensor> states;
+            visitor_recurrent_state temp(visitor_recurrent_state::save, false, &states);
+            visit_layers(net, temp);
+            return states;
+        }
+
+        template <typename net_type>
+        void restore_recurrent_states (
+            net_type& net,
+            std::vector<resizable_tensor>& states
+        )
+        {
+            visitor_recurrent_state temp(visitor_recurrent_state::restore, false, &states);
+            visit_layers(net, temp);
+        }
+    }
+
+    template <typename net_type>
+    void set_recurrent_stateful (
+        net_type& net,
+        bool stateful
+    )
+    {
+        impl::visitor_recurrent_state temp(impl::visitor_recurrent_state::set_stateful, stateful);
+        visit_layers(net, temp);
+    }
+
+    template <typename net_type>
+    void reset_recurrent_states (
+        net_type& net
+    )
+    {
+        impl::visitor_recurrent_state temp(impl::visitor_recurrent_state::reset, false);
+        visit_layers(net, temp);
+    }
+
 // ----------------------------------------------------------------------------------------
 
     namespace impl
@@ -434,8 +847,8 @@ namespace dlib
             }
 
             // handle the case of convolutional layer followed by relu
-            template <long nf, long nr, long nc, int sy, int sx, int py, int px, typename U, typename R>
-            void fuse_convolution(add_layer<relu_, add_layer<con_<nf, nr, nc, sy, sx, py, px>, U>, R>& l)
+            template <long nf, long nr, long nc, int sy, int sx, int py, int px, int dy, int dx, long ng, typename U, typename R>
+            void fuse_convolution(add_layer<relu_, add_layer<con_<nf, nr, nc, sy, sx, py, px, dy, dx, ng>, U>, R>& l)
             {
                 if (l.layer_details().is_disabled())
                     return;
@@ -450,8 +863,8 @@ namespace dlib
             }
 
             // handle the case of convolutional layer followed by affine followed by relu
-            template <long nf, long nr, long nc, int sy, int sx, int py, int px, typename U, typename E, typename R>
-            void fuse_convolution(add_layer<relu_, add_layer<affine_, add_layer<con_<nf, nr, nc, sy, sx, py, px>, U>, E>, R>& l)
+            template <long nf, long nr, long nc, int sy, int sx, int py, int px, int dy, int dx, long ng, typename U, typename E, typename R>
+            void fuse_convolution(add_layer<relu_, add_layer<affine_, add_layer<con_<nf, nr, nc, sy, sx, py, px, dy, dx, ng>, U>, E>, R>& l)
             {
                 if (l.layer_details().is_disabled())
                     return;
@@ -469,8 +882,8 @@ namespace dlib
             }
 
             // handle the case of convolutional layer followed by affine
-            template <long nf, long nr, long nc, int sy, int sx, int py, int px, typename U, typename E>
-            void fuse_convolution(add_layer<affine_, add_layer<con_<nf, nr, nc, sy, sx, py, px>, U>, E>& l)
+            template <long nf, long nr, long nc, int sy, int sx, int py, int px, int dy, int dx, long ng, typename U, typename E>
+            void fuse_convolution(add_layer<affine_, add_layer<con_<nf, nr, nc, sy, sx, py, px, dy, dx, ng>, U>, E>& l)
             {
                 if (l.layer_details().is_disabled())
                     return;
@@ -532,6 +945,337 @@ namespace dlib
         visit_layers(net, impl::visitor_fuse_layers());
     }
 
+// ----------------------------------------------------------------------------------------
+
+    namespace impl
+    {
+        class visitor_int8_calibration
+        {
+        public:
+
+            visitor_int8_calibration(
+                std::vector<float>& ranges_,
+                const tensor& x
+            ) : ranges(ranges_), get_input([&x]() -> const tensor& { return x; }) {}
+
+            // This visitor goes from the input layer to the loss layer, so the output of
+            // the last layer it saw is the input of the current one.  That's true even for
+            // the layers inside a repeat layer, whose subnet() isn't a real layer.  We
+            // only ask for the output when we need it, since layers with an in-place
+            // layer on top of them don't allow access to it.
+            template <typename layer_type>
+            void operator()(size_t , layer_type& l)
+            {
+                update_input(l, 0);
+            }
+
+            template <typename T, typename U, typename E>
+            void operator()(size_t idx, add_layer<T,U,E>& l)
+            {
+                record(idx, l.layer_details());
+                get_input = [&l]() -> const tensor& { return l.get_output(); };
+            }
+
+        private:
+
+            template <typename layer_type>
+            auto update_input(layer_type& l, int) -> decltype(l.get_output(), void())
+            {
+                get_input = [&l]() -> const tensor& { return l.get_output(); };
+            }
+
+            template <typename layer_type>
+            void update_input(layer_type&, long)
+            {
+                // Input and loss layers don't have outputs.
+            }
+
+            template <typename T>
+            void record(size_t, const T&)
+            {
+                // only con_ and fc_ layers are quantized
+            }
+
+            template <long nf, long nr, long nc, int sy, int sx, int py, int px, int dy, int dx, long ng>
+            void record(size_t idx, const con_<nf,nr,nc,sy,sx,py,px,dy,dx,ng>&)
+            {
+                update(idx);
+            }
+
+            template <unsigned long no, fc_bias_mode bm>
+            void record(size_t idx, const fc_<no,bm>&)
+            {
+                update(idx);
+            }
+
+            void update(size_t idx)
+            {
+                ranges[idx] = std::max(ranges[idx], max(abs(mat(get_input()))));
+            }
+
+            std::vector<float>& ranges;
+            std::function<const tensor&()> get_input;
+        };
+
+        class visitor_int8_quantize
+        {
+        public:
+
+            visitor_int8_quantize(const std::vector<float>& ranges_) : ranges(ranges_) {}
+
+            template <typename input_layer_type>
+            void operator()(size_t , input_layer_type& ) const
+            {
+                // ignore other layers
+            }
+
+            template <typename T, typename U, typename E>
+            void operator()(size_t idx, add_layer<T,U,E>& l)
+            {
+                quantize(idx, l.layer_details());
+            }
+
+        private:
+
+            template <typename T>
+            void quantize(size_t, T&)
+            {
+            }
+
+            template <long nf, long nr, long nc, int sy, int sx, int py, int px, int dy, int dx, long ng>
+            void quantize(size_t idx, con_<nf,nr,nc,sy,sx,py,px,dy,dx,ng>& l)
+            {
+                if (!l.is_quantized())
+                    l.quantize_to_int8(ranges[idx]);
+            }
+
+            template <unsigned long no, fc_bias_mode bm>
+            void quantize(size_t idx, fc_<no,bm>& l)
+            {
+                if (!l.is_quantized())
+                    l.quantize_to_int8(ranges[idx]);
+            }
+
+            const std::vector<float>& ranges;
+        };
+    }
+
+    template <typename net_type, typename forward_iterator>
+    std::vector<float> calibrate_int8_activation_ranges (
+        net_type& net,
+        forward_iterator ibegin,
+        forward_iterator iend,
+        size_t mini_batch_size = 32
+    )
+    {
+        DLIB_CASSERT(std::distance(ibegin, iend) > 0);
+        DLIB_CASSERT(mini_batch_size > 0);
+        std::vector<float> ranges(net_type::num_layers, 0);
+        resizable_tensor x;
+        while (ibegin != iend)
+        {
+            auto end = ibegin;
+            std::advance(end, std::min<size_t>(mini_batch_size, std::distance(ibegin, iend)));
+            net.to_tensor(ibegin, end, x);
+            net.forward(x);
+            visit_layers_backwards(net, impl::visitor_int8_calibration(ranges, x));
+            ibegin = end;
+        }
+        return ranges;
+    }
+
+    template <typename net_type>
+    void quantize_to_int8 (
+        net_type& net,
+        const std::vector<float>& activation_ranges
+    )
+    {
+        DLIB_CASSERT(activation_ranges.size() == net_type::num_layers);
+        visit_layers(net, impl::visitor_int8_quantize(activation_ranges));
+    }
+
+    template <typename net_type, typename forward_iterator>
+    void quantize_to_int8 (
+        net_type& net,
+        forward_iterator ibegin,
+        forward_iterator iend,
+        size_t mini_batch_size = 32
+    )
+    {
+        quantize_to_int8(net, calibrate_int8_activation_ranges(net, ibegin, iend, mini_batch_size));
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    enum class filter_ranking
+    {
+        l1_norm,
+        bn_gamma
+    };
+
+    namespace impl
+    {
+        class visitor_prune_con_filters
+        {
+        public:
+
+            visitor_prune_con_filters(
+                double fraction_,
+                filter_ranking ranking_,
+                size_t& num_pruned_
+            ) : fraction(fraction_), ranking(ranking_), num_pruned(num_pruned_) {}
+
+            // This visitor goes from the input layer to the loss layer.  A con_ layer's
+            // filters can be pruned when its output only goes through layers that work
+            // on each channel separately before reaching another con_ layer, since then
+            // we know every place the removed channels were used.  Any other layer,
+            // including tag and skip layers, ends the chain.
+            template <typename layer_type>
+            void operator()(size_t , layer_type& )
+            {
+                end_chain();
+            }
+
+            template <typename T, typename U, typename E>
+            void operator()(size_t , add_layer<T,U,E>& l)
+            {
+                visit(l.layer_details());
+            }
+
+        private:
+
+            template <typename T>
+            void visit(T&)
+            {
+                end_chain();
+            }
+
+            // These layers apply the same function to each channel, so they don't care
+            // how many channels there are.
+            void visit(relu_&) {}
+            void visit(prelu_&) {}
+            void visit(leaky_relu_&) {}
+            void visit(sig_&) {}
+            void visit(mish_&) {}
+            void visit(htan_&) {}
+            void visit(clipped_relu_&) {}
+            void visit(elu_&) {}
+            void visit(gelu_&) {}
+            void visit(silu_&) {}
+            void visit(dropout_&) {}
+            void visit(multiply_&) {}
+
+            void visit(bn_<CONV_MODE>& l)
+            {
+                if (!active)
+                    return;
+                if (gamma_scores.size() == 0)
+                    gamma_scores = abs(rowm(mat(l.get_layer_params()), range(0, num_filters-1)));
+                pruners.push_back([&l](const std::vector<long>& keep) { l.prune_channels(keep); });
+            }
+
+            void visit(affine_& l)
+            {
+                if (!active)
+                    return;
+                if (l.get_mode() != CONV_MODE || l.is_disabled())
+                {
+                    end_chain();
+                    return;
+                }
+                if (gamma_scores.size() == 0)
+                    gamma_scores = reshape_to_column_vector(abs(mat(l.get_gamma())));
+                pruners.push_back([&l](const std::vector<long>& keep) { l.prune_channels(keep); });
+            }
+
+            template <long nf, long nr, long nc, int sy, int sx, int py, int px, int dy, int dx, long ng>
+            void visit(con_<nf,nr,nc,sy,sx,py,px,dy,dx,ng>& l)
+            {
+                const bool can_prune = l.groups() == 1 && !l.is_quantized() && l.get_layer_params().size() != 0;
+                if (active && can_prune)
+                {
+                    const std::vector<long> keep = filters_to_keep();
+                    if ((long)keep.size() < num_filters)
+                    {
+                        for (auto& prune : pruners)
+                            prune(keep);
+                        l.prune_input_channels(keep);
+                        num_pruned += num_filters - keep.size();
+                    }
+                }
+                end_chain();
+
+                if (can_prune)
+                {
+                    active = true;
+                    num_filters = l.num_filters();
+                    get_l1_scores = [&l]()
+                    {
+                        const long k = l.num_filters();
+                        const tensor& params = l.get_layer_params();
+                        const long filter_size = (params.size() - (l.bias_is_disabled() ? 0 : k))/k;
+                        matrix<float,0,1> scores(k);
+                        for (long i = 0; i < k; ++i)
+                            scores(i) = sum(abs(rowm(mat(params), range(i*filter_size, (i+1)*filter_size-1))));
+                        return scores;
+                    };
+                    pruners.push_back([&l](const std::vector<long>& keep) { l.prune_filters(keep); });
+                }
+            }
+
+            std::vector<long> filters_to_keep(
+            ) const
+            {
+                // Use the L1 norms of the filters if there isn't a bn_ or affine_ layer in
+                // the chain to take gamma from.
+                const matrix<float,0,1> scores = (ranking == filter_ranking::bn_gamma && gamma_scores.size() != 0)
+                    ? gamma_scores : get_l1_scores();
+
+                std::vector<long> idx(num_filters);
+                for (long i = 0; i < num_filters; ++i)
+                    idx[i] = i;
+                std::stable_sort(idx.begin(), idx.end(), [&](long a, long b) { return scores(a) > scores(b); });
+                const long num_keep = std::max<long>(1, num_filters - static_cast<long>(fraction*num_filters));
+                idx.resize(num_keep);
+                std::sort(idx.begin(), idx.end());
+                return idx;
+            }
+
+            void end_chain()
+            {
+                active = false;
+                pruners.clear();
+                gamma_scores.set_size(0);
+            }
+
+            double fraction;
+            filter_ranking ranking;
+            size_t& num_pruned;
+
+            bool active = false;
+            long num_filters = 0;
+            std::function<matrix<float,0,1>()> get_l1_scores;
+            matrix<float,0,1> gamma_scores;
+            std::vector<std::function<void(const std::vector<long>&)>> pruners;
+        };
+    }
+
+    template <typename net_type>
+    size_t prune_con_filters (
+        net_type& net,
+        double fraction,
+        filter_ranking ranking = filter_ranking::l1_norm
+    )
+    {
+        DLIB_CASSERT(0 <= fraction && fraction < 1);
+        DLIB_CASSERT(count_parameters(net) > 0, "The network has to be allocated before pruning it.");
+        size_t num_pruned = 0;
+        visit_layers_backwards(net, impl::visitor_prune_con_filters(fraction, ranking, num_pruned));
+        // The outputs and gradients stored in the network have the old sizes.
+        net.clean();
+        return num_pruned;
+    }
+
 // ----------------------------------------------------------------------------------------
 
     namespace impl
@@ -691,8 +1435,8 @@ namespace dlib
                 from = tag_to_layer.at(t);
             }
 
-            template <long nf, long nr, long nc, int sy, int sx, int py, int px, typename U, typename E>
-            void operator()(size_t i, const add_layer<con_<nf, nr, nc, sy, sx, py, px>, U, E>& l)
+            template <long nf, long nr, long nc, int sy, int sx, int py, int px, int dy, int dx, long ng, typename U, typename E>
+            void operator()(size_t i, const add_layer<con_<nf, nr, nc, sy, sx, py, px, dy, dx, ng>, U, E>& l)
             {
                 start_node(i, "con");
                 out << " | {filters|{" << l.layer_details().num_filters() << "}}";
@@ -701,12 +1445,16 @@ namespace dlib
                     out << " | {stride|{" << sy<< "," << sx << "}}";
                 if (py != 0 || px != 0)
                     out << " | {pad|{" << py<< "," << px << "}}";
+                if (dy != 1 || dx != 1)
+                    out << " | {dilation|{" << dy<< "," << dx << "}}";
+                if (ng != 1)
+                    out << " | {groups|{" << ng << "}}";
                 end_node();
                 update(i);
             }
 
-            template <long nf, long nr, long nc, int sy, int sx, int py, int px, typename U, typename E>
-            void operator()(size_t i, const add_layer<cont_<nf, nr, nc, sy, sx, py, px>, U, E>& l)
+            template <long nf, long nr, long nc, int sy, int sx, int py, int px, int dy, int dx, long ng, typename U, typename E>
+            void operator()(size_t i, const add_layer<cont_<nf, nr, nc, sy, sx, py, px, dy, dx, ng>, U, E>& l)
             {
                 start_node(i, "cont");
                 out << " | {filters|{" << l.layer_details().num_filters() << "}}";
@@ -715,6 +1463,10 @@ namespace dlib
                     out << " | {stride|{" << sy<< "," << sx << "}}";
                 if (py != 0 || px != 0)
                     out << " | {pad|{" << py<< "," << px << "}}";
+                if (dy != 1 || dx != 1)
+                    out << " | {dilation|{" << dy<< "," << dx << "}}";
+                if (ng != 1)
+                    out << " | {groups|{" << ng << "}}";
                 end_node();
                 update(i);
             }
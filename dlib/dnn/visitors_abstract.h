@@ -27,6 +27,36 @@ namespace dlib
               new_window_size.
     !*/
 
+// ----------------------------------------------------------------------------------------
+
+    template <typename net_type>
+    void set_recurrent_stateful (
+        net_type& net,
+        bool stateful
+    );
+    /*!
+        requires
+            - net_type is an object of type add_layer, add_loss_layer, add_skip_layer, or
+              add_tag_layer.
+        ensures
+            - Calls set_stateful(stateful) on all lstm_ and gru_ layers in net.  That is,
+              when stateful==true these layers will carry their state from one call to
+              forward() to the next, which is how you process a long sequence in chunks.
+    !*/
+
+    template <typename net_type>
+    void reset_recurrent_states (
+        net_type& net
+    );
+    /*!
+        requires
+            - net_type is an object of type add_layer, add_loss_layer, add_skip_layer, or
+              add_tag_layer.
+        ensures
+            - Calls reset_state() on all lstm_ and gru_ layers in net.  So the next call to
+              forward() will start all sequences from a zero state.
+    !*/
+
 // ----------------------------------------------------------------------------------------
 
     template <typename net_type>
@@ -159,6 +189,294 @@ namespace dlib
               been trained then, since nothing has been allocated yet, it will return 0.
     !*/
 
+// ----------------------------------------------------------------------------------------
+
+    struct layer_summary
+    {
+        /*!
+            WHAT THIS OBJECT REPRESENTS
+                This object describes one layer of a network, as reported by
+                net_summary().
+        !*/
+
+        // The index of the layer, numbered the same way as in visit_layers().  So
+        // layer<index>(net) is the layer this object describes.
+        size_t index = 0;
+
+        // The first word of what the layer prints with operator<<, e.g. "con", "relu",
+        // "tag1" or "loss_multiclass_log".
+        std::string type;
+
+        // The dimensions of the layer's output tensor.  These are all 0 for loss layers
+        // since they don't output a tensor.
+        long num_samples = 0;
+        long k = 0;
+        long nr = 0;
+        long nc = 0;
+
+        // The number of values in the layer's parameter tensor.
+        size_t num_parameters = 0;
+
+        // The number of multiply-add operations the layer's forward pass does.  This is
+        // counted for the con_, cont_, fc_, lstm_, gru_ and multihead_attention_ layers,
+        // which do almost all of the work in a typical network.  It's 0 for the other
+        // layers, since they only do a few operations per output value.
+        size_t num_multiply_adds = 0;
+
+        // The number of bytes of the layer's output tensor.  This is 0 for layers that
+        // run in-place, since they overwrite the output of the layer below them, and for
+        // tag, skip and loss layers, which don't have their own output tensors.
+        size_t activation_memory = 0;
+    };
+
+    struct net_summary_table
+    {
+        /*!
+            WHAT THIS OBJECT REPRESENTS
+                This object is the table of layer_summary objects returned by
+                net_summary().
+        !*/
+
+        // layers[i] describes layer<i>(net).
+        std::vector<layer_summary> layers;
+
+        size_t num_parameters (
+        ) const;
+        /*!
+            ensures
+                - returns the sum of num_parameters over all the layers.
+        !*/
+
+        size_t num_multiply_adds (
+        ) const;
+        /*!
+            ensures
+                - returns the sum of num_multiply_adds over all the layers.
+        !*/
+
+        size_t activation_memory (
+        ) const;
+        /*!
+            ensures
+                - returns the sum of activation_memory over all the layers.
+        !*/
+    };
+
+    std::ostream& operator<< (
+        std::ostream& out,
+        const net_summary_table& item
+    );
+    /*!
+        ensures
+            - Prints item to out as a table with one row per layer, followed by a row with
+              the totals.
+    !*/
+
+    template <typename net_type>
+    net_summary_table net_summary (
+        net_type& net,
+        const typename net_type::input_type& sample
+    );
+    /*!
+        requires
+            - net_type is an object of type add_layer or add_loss_layer.
+        ensures
+            - Runs sample through net and returns a table describing each of its layers.
+              That is, returns a net_summary_table T such that:
+                - T.layers.size() == net_type::num_layers
+                - T.layers[i] describes layer<i>(net) when net is run on sample.
+            - Unlike count_parameters(), this function allocates the network's parameters
+              if that hasn't already happened, so you can use it on a network that hasn't
+              been trained.
+    !*/
+
+    template <typename net_type>
+    net_summary_table net_summary (
+        net_type& net,
+        long nr,
+        long nc
+    );
+    /*!
+        requires
+            - net_type is an object of type add_layer or add_loss_layer.
+            - net_type::input_type is a matrix or array2d of pixels, e.g. matrix<rgb_pixel>.
+            - nr > 0
+            - nc > 0
+        ensures
+            - returns net_summary(net, IMG) where IMG is an all black image with nr rows
+              and nc columns.
+    !*/
+
+// ----------------------------------------------------------------------------------------
+
+    template <typename net_type>
+    std::vector<half_tensor> get_half_precision_parameters (
+        const net_type& net,
+        half_format fmt
+    );
+    /*!
+        requires
+            - net_type is an object of type add_layer, add_loss_layer, add_skip_layer, or
+              add_tag_layer.
+        ensures
+            - Returns a copy of the parameters of every computational layer in net,
+              converted to the given 16 bit format.  That is, returns a vector PARAMS
+              such that:
+                - PARAMS.size() == net_type::num_computational_layers
+                - PARAMS[i] == half_tensor(P, fmt), where P is the parameter tensor of
+                  the i-th computational layer (in the order visited by
+                  visit_layer_parameters()).  Layers without parameters give empty
+                  half_tensors.
+            - Serializing this vector takes about half the space of the float parameters.
+    !*/
+
+    template <typename net_type>
+    void set_half_precision_parameters (
+        net_type& net,
+        const std::vector<half_tensor>& params
+    );
+    /*!
+        requires
+            - net_type is an object of type add_layer, add_loss_layer, add_skip_layer, or
+              add_tag_layer.
+            - params.size() == net_type::num_computational_layers
+            - The parameters of net have been allocated and have the same sizes as the
+              tensors in params.  E.g. params came from get_half_precision_parameters()
+              called on a network of the same type, and net has been deserialized or run
+              on some input so its layers are set up.
+        ensures
+            - Converts each params[i] back to float and copies it into the parameters of
+              the i-th computational layer of net.
+    !*/
+
+    template <typename net_type>
+    void round_parameters_to_half_precision (
+        net_type& net,
+        half_format fmt
+    );
+    /*!
+        requires
+            - net_type is an object of type add_layer, add_loss_layer, add_skip_layer, or
+              add_tag_layer.
+        ensures
+            - Calls tt::round_to_half_precision(P, fmt) on the parameter tensor P of every
+              computational layer in net.  So afterwards net computes exactly what it would
+              compute if its parameters were loaded from 16 bit storage.  This is useful
+              for checking how much accuracy a network loses when it is deployed with
+              half precision weights.
+    !*/
+
+// ----------------------------------------------------------------------------------------
+
+    template <typename net_type, typename forward_iterator>
+    std::vector<float> calibrate_int8_activation_ranges (
+        net_type& net,
+        forward_iterator ibegin,
+        forward_iterator iend,
+        size_t mini_batch_size = 32
+    );
+    /*!
+        requires
+            - net_type is an object of type add_layer or add_loss_layer.
+            - [ibegin, iend) is an iterator range over input_type objects, i.e. the
+              samples net takes as input.
+            - std::distance(ibegin, iend) > 0
+            - mini_batch_size > 0
+        ensures
+            - Runs the samples through net, mini_batch_size at a time, and records the
+              largest absolute value that appears in the input of each con_ and fc_ layer.
+              These are the activation ranges needed to quantize the network to 8 bit
+              integers.  The samples should be a representative set of the data the
+              network will be used on.  A few hundred are usually enough.
+            - returns a vector R such that:
+                - R.size() == net_type::num_layers
+                - R[i] is the largest absolute input value seen by layer<i>(net) if it is
+                  a con_ or fc_ layer, and 0 otherwise.  That is, the layers are
+                  numbered the same way as in visit_layers().
+    !*/
+
+    template <typename net_type>
+    void quantize_to_int8 (
+        net_type& net,
+        const std::vector<float>& activation_ranges
+    );
+    /*!
+        requires
+            - net_type is an object of type add_layer, add_loss_layer, add_skip_layer, or
+              add_tag_layer.
+            - activation_ranges.size() == net_type::num_layers
+            - net has been allocated, i.e. it has been run on some input.
+        ensures
+            - Calls quantize_to_int8(activation_ranges[i]) on each con_ and fc_ layer
+              layer<i>(net) that isn't already quantized.  After this, net uses 8 bit
+              integer arithmetic for all its convolutions and fully connected layers and
+              can no longer be trained.
+            - If you want to use fuse_layers() you must call it before this function.
+    !*/
+
+    template <typename net_type, typename forward_iterator>
+    void quantize_to_int8 (
+        net_type& net,
+        forward_iterator ibegin,
+        forward_iterator iend,
+        size_t mini_batch_size = 32
+    );
+    /*!
+        requires
+            - The requirements of calibrate_int8_activation_ranges() are satisfied.
+        ensures
+            - performs: quantize_to_int8(net, calibrate_int8_activation_ranges(net, ibegin, iend, mini_batch_size))
+    !*/
+
+// ----------------------------------------------------------------------------------------
+
+    enum class filter_ranking
+    {
+        l1_norm,
+        bn_gamma
+    };
+
+    template <typename net_type>
+    size_t prune_con_filters (
+        net_type& net,
+        double fraction,
+        filter_ranking ranking = filter_ranking::l1_norm
+    );
+    /*!
+        requires
+            - net_type is an object of type add_layer, add_loss_layer, add_skip_layer, or
+              add_tag_layer.
+            - 0 <= fraction < 1
+            - net has been allocated, that is: count_parameters(net) > 0.
+        ensures
+            - Removes the least important filters from the con_ layers of net, making it
+              smaller and faster.  Since this changes what the network computes you
+              should fine-tune it afterwards by training it some more with a new
+              dnn_trainer.  Don't keep using a dnn_trainer made before the pruning, since
+              its solvers have the old parameter sizes.
+            - A con_ layer C is pruned only if all the layers between it and the next
+              con_ layer N, going toward the output, work on each channel separately,
+              i.e. they are bn_ layers in CONV_MODE, affine_ layers in CONV_MODE, or
+              activation, dropout_ or multiply_ layers.  Moreover, C and N must both have
+              groups() == 1 and not be quantized.  In that case the removed channels are
+              also removed from the bn_ and affine_ layers in between, and from the input
+              of N, so the network stays consistent.  This means that, for example, in a
+              residual block only the con_ layers whose outputs don't go into an add_prev
+              layer are pruned.
+            - For each pruned con_ layer, floor(fraction*num_filters()) filters are
+              removed, but at least one filter is always kept.  The filters are ranked
+              by:
+                - if (ranking == filter_ranking::l1_norm) then
+                    - the sum of the absolute values of their weights.
+                - if (ranking == filter_ranking::bn_gamma) then
+                    - the absolute value of the gamma of their channel in the first bn_ or
+                      affine_ layer after the con_ layer.  Layers without a bn_ or affine_
+                      layer after them are ranked by L1 norm.
+              and the lowest ranked filters are removed.
+            - Calls net.clean(), since the outputs stored in net have the old sizes.
+            - returns the total number of filters removed.
+    !*/
+
 // ----------------------------------------------------------------------------------------
 
     template<typename net_type>
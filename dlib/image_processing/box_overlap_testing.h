@@ -6,6 +6,7 @@
 #include "box_overlap_testing_abstract.h"
 #include "../geometry.h"
 #include <vector>
+#include <cmath>
 
 namespace dlib
 {
@@ -57,6 +58,112 @@ namespace dlib
         return box_percent_covered(drectangle(a), drectangle(b));
     }
 
+// ----------------------------------------------------------------------------------------
+
+    namespace impl
+    {
+        inline std::vector<dpoint> rotated_box_corners (
+            const drectangle& rect,
+            double angle
+        )
+        {
+            // The corners are placed so that the polygon has an area of rect.area(), in
+            // keeping with the inclusive width() and height() of drectangle.  They are
+            // listed clockwise in image coordinates (i.e. with y pointing down), which is
+            // preserved by the rotation.
+            const point_rotator rot(angle);
+            const dpoint c = dcenter(rect);
+            const double w = rect.width()/2;
+            const double h = rect.height()/2;
+            return {c + rot(dpoint(-w,-h)), c + rot(dpoint(w,-h)),
+                    c + rot(dpoint(w,h)),   c + rot(dpoint(-w,h))};
+        }
+
+        inline std::vector<dpoint> clip_convex_polygon (
+            std::vector<dpoint> poly,
+            const std::vector<dpoint>& clipper
+        )
+        {
+            // Sutherland-Hodgman clipping.  poly is cut by the line through each edge of
+            // clipper in turn, keeping the part on the inside of the edge.  Both polygons
+            // must list their vertices clockwise in image coordinates.
+            std::vector<dpoint> in;
+            for (size_t i = 0; i < clipper.size() && poly.size() != 0; ++i)
+            {
+                const dpoint a = clipper[i];
+                const dpoint edge = clipper[(i+1)%clipper.size()] - a;
+                const auto side = [&](const dpoint& p) { return edge.x()*(p.y()-a.y()) - edge.y()*(p.x()-a.x()); };
+
+                in.swap(poly);
+                poly.clear();
+                for (size_t j = 0; j < in.size(); ++j)
+                {
+                    const dpoint& s = in[(j+in.size()-1)%in.size()];
+                    const dpoint& e = in[j];
+                    const double ds = side(s);
+                    const double de = side(e);
+                    if ((ds < 0) != (de < 0))
+                        poly.push_back(s + (e-s)*(ds/(ds-de)));
+                    if (de >= 0)
+                        poly.push_back(e);
+                }
+            }
+            return poly;
+        }
+
+        inline double rotated_box_intersection_area (
+            const drectangle& a,
+            double angle_a,
+            const drectangle& b,
+            double angle_b
+        )
+        {
+            if (a.is_empty() || b.is_empty())
+                return 0;
+            if (angle_a == 0 && angle_b == 0)
+                return a.intersect(b).area();
+
+            // Boxes whose circumscribed circles don't touch can't overlap.
+            const double reach = (std::hypot(a.width(), a.height()) + std::hypot(b.width(), b.height()))/2;
+            if (length_squared(dcenter(a)-dcenter(b)) >= reach*reach)
+                return 0;
+
+            return polygon_area(clip_convex_polygon(rotated_box_corners(a, angle_a),
+                                                    rotated_box_corners(b, angle_b)));
+        }
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    inline double box_intersection_over_union (
+        const drectangle& a,
+        double angle_a,
+        const drectangle& b,
+        double angle_b
+    )
+    {
+        const double inner = impl::rotated_box_intersection_area(a, angle_a, b, angle_b);
+        if (inner == 0)
+            return 0;
+        const double outer = a.area() + b.area() - inner;
+        return inner / outer;
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    inline double box_percent_covered (
+        const drectangle& a,
+        double angle_a,
+        const drectangle& b,
+        double angle_b
+    )
+    {
+        const double inner = impl::rotated_box_intersection_area(a, angle_a, b, angle_b);
+        if (inner == 0)
+            return 0;
+        return std::max(inner/a.area(), inner/b.area());
+    }
+
 // ----------------------------------------------------------------------------------------
 
     class test_box_overlap
@@ -101,6 +208,26 @@ namespace dlib
                 return false;
         }
 
+        bool operator() (
+            const drectangle& a,
+            double angle_a,
+            const drectangle& b,
+            double angle_b
+        ) const
+        {
+            const double inner = impl::rotated_box_intersection_area(a, angle_a, b, angle_b);
+            if (inner == 0)
+                return false;
+
+            const double outer = a.area() + b.area() - inner;
+            if (inner/outer > iou_thresh || 
+                inner/a.area() > percent_covered_thresh || 
+                inner/b.area() > percent_covered_thresh)
+                return true;
+            else
+                return false;
+        }
+
         double get_percent_covered_thresh (
         ) const
         {
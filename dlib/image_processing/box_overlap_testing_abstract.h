@@ -60,6 +60,42 @@ namespace dlib
               they don't overlap at all it returns 0.
     !*/
 
+// ----------------------------------------------------------------------------------------
+
+    inline double box_intersection_over_union (
+        const drectangle& a,
+        double angle_a,
+        const drectangle& b,
+        double angle_b
+    );
+    /*!
+        ensures
+            - This is the rotated box version of box_intersection_over_union().  Here, a
+              and b are rotated clockwise by angle_a and angle_b radians respectively
+              around their centers, which is the convention used by mmod_rect::angle.
+            - let OVERLAP = the area of the intersection of the two rotated boxes.
+            - returns OVERLAP/(a.area() + b.area() - OVERLAP), or 0 if the boxes don't
+              overlap.
+            - if (angle_a == 0 && angle_b == 0) then
+                - returns box_intersection_over_union(a,b)
+    !*/
+
+// ----------------------------------------------------------------------------------------
+
+    inline double box_percent_covered (
+        const drectangle& a,
+        double angle_a,
+        const drectangle& b,
+        double angle_b
+    ); 
+    /*!
+        ensures
+            - This is the rotated box version of box_percent_covered().  a and b are
+              rotated as described in the rotated box_intersection_over_union() above.
+            - let OVERLAP = the area of the intersection of the two rotated boxes.
+            - returns max(OVERLAP/a.area(), OVERLAP/b.area())
+    !*/
+
 // ----------------------------------------------------------------------------------------
 
     class test_box_overlap
@@ -112,6 +148,24 @@ namespace dlib
                     - returns false
         !*/
 
+        bool operator() (
+            const drectangle& a,
+            double angle_a,
+            const drectangle& b,
+            double angle_b
+        ) const;
+        /*!
+            ensures
+                - This is the rotated box version of the above operator(), useful for doing
+                  non-max suppression on rotated boxes.  a and b are rotated clockwise by
+                  angle_a and angle_b radians around their centers.
+                - returns true if (box_intersection_over_union(a,angle_a,b,angle_b) > get_iou_thresh() ||
+                                   box_percent_covered(a,angle_a,b,angle_b) > get_percent_covered_thresh())
+                - Note that, unlike the rectangle version above, the union here is the
+                  true union of the two boxes rather than the smallest rectangle containing
+                  both of them.
+        !*/
+
         double get_iou_thresh (
         ) const;
         /*!
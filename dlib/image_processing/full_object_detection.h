@@ -140,6 +140,7 @@ namespace dlib
         double detection_confidence = 0;
         bool ignore = false;
         std::string label;
+        double angle = 0;
 
         operator rectangle() const { return rect; }
         bool operator == (const mmod_rect& rhs) const
@@ -147,7 +148,8 @@ namespace dlib
             return rect == rhs.rect 
                    && detection_confidence == rhs.detection_confidence
                    && ignore == rhs.ignore 
-                   && label == rhs.label;
+                   && label == rhs.label
+                   && angle == rhs.angle;
         }
     };
 
@@ -160,27 +162,32 @@ namespace dlib
 
     inline void serialize(const mmod_rect& item, std::ostream& out)
     {
-        int version = 2;
+        int version = 3;
         serialize(version, out);
         serialize(item.rect, out);
         serialize(item.detection_confidence, out);
         serialize(item.ignore, out);
         serialize(item.label, out);
+        serialize(item.angle, out);
     }
 
     inline void deserialize(mmod_rect& item, std::istream& in)
     {
         int version = 0;
         deserialize(version, in);
-        if (version != 1 && version != 2)
+        if (!(1 <= version && version <= 3))
             throw serialization_error("Unexpected version found while deserializing dlib::mmod_rect");
         deserialize(item.rect, in);
         deserialize(item.detection_confidence, in);
         deserialize(item.ignore, in);
-        if (version == 2)
+        if (version >= 2)
             deserialize(item.label, in);
         else
             item.label = "";
+        if (version >= 3)
+            deserialize(item.angle, in);
+        else
+            item.angle = 0;
     }
 
 // ----------------------------------------------------------------------------------------
@@ -191,13 +198,14 @@ namespace dlib
         yolo_rect(const drectangle& r) : rect(r) {}
         yolo_rect(const drectangle& r, double score) : rect(r),detection_confidence(score) {}
         yolo_rect(const drectangle& r, double score, const std::string& label) : rect(r),detection_confidence(score), label(label) {}
-        yolo_rect(const mmod_rect& r) : rect(r.rect), detection_confidence(r.detection_confidence), ignore(r.ignore), label(r.label) {}
+        yolo_rect(const mmod_rect& r) : rect(r.rect), detection_confidence(r.detection_confidence), ignore(r.ignore), label(r.label), angle(r.angle) {}
 
         drectangle rect;
         double detection_confidence = 0;
         bool ignore = false;
         std::string label;
         std::vector<std::pair<double, std::string>> labels;
+        double angle = 0;
 
         operator rectangle() const { return rect; }
         bool operator == (const yolo_rect& rhs) const
@@ -205,7 +213,8 @@ namespace dlib
             return rect == rhs.rect
                    && detection_confidence == rhs.detection_confidence
                    && ignore == rhs.ignore
-                   && label == rhs.label;
+                   && label == rhs.label
+                   && angle == rhs.angle;
         }
         bool operator<(const yolo_rect& rhs) const
         {
@@ -215,26 +224,31 @@ namespace dlib
 
     inline void serialize(const yolo_rect& item, std::ostream& out)
     {
-        int version = 1;
+        int version = 2;
         serialize(version, out);
         serialize(item.rect, out);
         serialize(item.detection_confidence, out);
         serialize(item.ignore, out);
         serialize(item.label, out);
         serialize(item.labels, out);
+        serialize(item.angle, out);
     }
 
     inline void deserialize(yolo_rect& item, std::istream& in)
     {
         int version = 0;
         deserialize(version, in);
-        if (version != 1)
+        if (version != 1 && version != 2)
             throw serialization_error("Unexpected version found while deserializing dlib::yolo_rect");
         deserialize(item.rect, in);
         deserialize(item.detection_confidence, in);
         deserialize(item.ignore, in);
         deserialize(item.label, in);
         deserialize(item.labels, in);
+        if (version == 2)
+            deserialize(item.angle, in);
+        else
+            item.angle = 0;
     }
 
 // ----------------------------------------------------------------------------------------
@@ -154,6 +154,12 @@ namespace dlib
             WHAT THIS OBJECT REPRESENTS
                 This is a simple struct that is used to give training data and receive detections
                 from the Max-Margin Object Detection loss layer loss_mmod_ object.
+
+                The angle field lets a mmod_rect describe a rotated box.  It uses the same
+                convention as image_dataset_metadata::box::angle.  That is, rect is the box
+                around the object in its upright pose and the object is rotated clockwise by
+                angle radians around the center of rect.  So an angle of 0 means rect is an
+                ordinary axis aligned box.
         !*/
 
         mmod_rect() = default; 
@@ -165,6 +171,7 @@ namespace dlib
         double detection_confidence = 0;
         bool ignore = false;
         std::string label;
+        double angle = 0;
 
         operator rectangle() const { return rect; }
 
@@ -201,14 +208,15 @@ namespace dlib
         /*!
             WHAT THIS OBJECT REPRESENTS
                 This is a simple struct that is used to give training data and receive detections
-                from the YOLO Detection loss layer loss_yolo_ object.
+                from the YOLO Detection loss layer loss_yolo_ object.  Like mmod_rect, it
+                describes a box rotated clockwise by angle radians around the center of rect.
         !*/
 
         yolo_rect() = default;
         yolo_rect(const drectangle& r) : rect(r) {}
         yolo_rect(const drectangle& r, double score) : rect(r),detection_confidence(score) {}
         yolo_rect(const drectangle& r, double score, const std::string& label) : rect(r),detection_confidence(score), label(label) {}
-        yolo_rect(const mmod_rect& r) : rect(r.rect), detection_confidence(r.detection_confidence), ignore(r.ignore), label(r.label) {}
+        yolo_rect(const mmod_rect& r) : rect(r.rect), detection_confidence(r.detection_confidence), ignore(r.ignore), label(r.label), angle(r.angle) {}
 
         drectangle rect;
         double detection_confidence = 0;
@@ -216,12 +224,14 @@ namespace dlib
         std::string label;
         // YOLO detectors are multi label detectors: this field will contain all confidences and labels for a particular detection
         std::vector<std::pair<double, std::string>> labels;
+        double angle = 0;
 
         operator rectangle() const { return rect; }
         bool operator== (const yolo_rect& rhs) const;
         /*!
             ensures
-                - returns true if and only if rect == rhs.rect && detection_confidence == rhs.detection_confidence && label == rhs.label.
+                - returns true if and only if rect == rhs.rect && detection_confidence == rhs.detection_confidence &&
+                  ignore == rhs.ignore && label == rhs.label && angle == rhs.angle.
         !*/
 
         bool operator<(const yolo_rect& rhs) const
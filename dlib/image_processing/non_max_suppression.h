@@ -0,0 +1,485 @@
+// Copyright (C) 2026  Davis E. King (davis@dlib.net)
+// License: Boost Software License   See LICENSE.txt for the full license.
+#ifndef DLIB_NON_MAX_SUPPRESSIOn_H_
+#define DLIB_NON_MAX_SUPPRESSIOn_H_
+
+#include "non_max_suppression_abstract.h"
+#include "box_overlap_testing.h"
+#include "full_object_detection.h"
+#include "../geometry.h"
+#include <vector>
+#include <map>
+#include <string>
+#include <algorithm>
+#include <numeric>
+#include <cmath>
+
+namespace dlib
+{
+
+// ----------------------------------------------------------------------------------------
+
+    enum class soft_nms_decay
+    {
+        linear,
+        gaussian
+    };
+
+    struct soft_nms_options
+    {
+        soft_nms_decay decay = soft_nms_decay::gaussian;
+        double sigma = 0.5;
+        double iou_thresh = 0.3;
+        double min_confidence = 0.001;
+    };
+
+// ----------------------------------------------------------------------------------------
+
+    namespace impl
+    {
+        inline std::vector<unsigned long> order_by_descending_score (
+            const std::vector<double>& scores
+        )
+        {
+            std::vector<unsigned long> order(scores.size());
+            std::iota(order.begin(), order.end(), 0);
+            std::stable_sort(order.begin(), order.end(),
+                [&](unsigned long a, unsigned long b) { return scores[a] > scores[b]; });
+            return order;
+        }
+
+        inline bool all_non_negative (
+            const std::vector<double>& scores
+        )
+        {
+            for (auto s : scores)
+            {
+                if (!(s >= 0))
+                    return false;
+            }
+            return true;
+        }
+
+        inline bool all_non_negative (
+            const std::vector<mmod_rect>& dets
+        )
+        {
+            for (auto& d : dets)
+            {
+                if (!(d.detection_confidence >= 0))
+                    return false;
+            }
+            return true;
+        }
+
+        inline void sort_by_descending_confidence (
+            std::vector<mmod_rect>& dets
+        )
+        {
+            std::stable_sort(dets.begin(), dets.end(),
+                [](const mmod_rect& a, const mmod_rect& b) { return a.detection_confidence > b.detection_confidence; });
+        }
+
+        template <typename T>
+        const T& setting_for_label (
+            const std::map<std::string,T>& label_settings,
+            const std::string& label,
+            const T& default_setting
+        )
+        {
+            const auto i = label_settings.find(label);
+            if (i != label_settings.end())
+                return i->second;
+            return default_setting;
+        }
+
+        template <typename F>
+        std::vector<mmod_rect> run_per_label (
+            const std::vector<mmod_rect>& dets,
+            F&& f
+        )
+        {
+            std::map<std::string, std::vector<mmod_rect>> groups;
+            for (auto& d : dets)
+                groups[d.label].push_back(d);
+
+            std::vector<mmod_rect> result;
+            for (auto& g : groups)
+            {
+                const auto temp = f(g.first, g.second);
+                result.insert(result.end(), temp.begin(), temp.end());
+            }
+            sort_by_descending_confidence(result);
+            return result;
+        }
+
+        inline std::vector<double> get_confidences (
+            const std::vector<mmod_rect>& dets
+        )
+        {
+            std::vector<double> scores;
+            for (auto& d : dets)
+                scores.push_back(d.detection_confidence);
+            return scores;
+        }
+
+        inline bool detections_overlap (
+            const test_box_overlap& overlaps,
+            const mmod_rect& a,
+            const mmod_rect& b
+        )
+        {
+            // Only use the rotated box test when we have to, so that axis aligned boxes
+            // are compared exactly as the rectangle version of non_max_suppression()
+            // would compare them.
+            if (a.angle == 0 && b.angle == 0)
+                return overlaps(a.rect, b.rect);
+            return overlaps(a.rect, a.angle, b.rect, b.angle);
+        }
+
+        template <typename F>
+        std::vector<unsigned long> soft_non_max_suppression (
+            std::vector<double>& scores,
+            const soft_nms_options& options,
+            F&& iou
+        )
+        {
+            std::vector<unsigned long> remaining(scores.size());
+            std::iota(remaining.begin(), remaining.end(), 0);
+
+            std::vector<unsigned long> kept;
+            while (remaining.size() != 0)
+            {
+                // Pick the highest scoring remaining box.  Ties go to the earlier box so the
+                // output is deterministic.
+                auto best = remaining.begin();
+                for (auto i = remaining.begin(); i != remaining.end(); ++i)
+                {
+                    if (scores[*i] > scores[*best])
+                        best = i;
+                }
+                if (scores[*best] < options.min_confidence)
+                    break;
+
+                const unsigned long m = *best;
+                remaining.erase(best);
+                kept.push_back(m);
+
+                for (auto j : remaining)
+                {
+                    const double overlap = iou(m, j);
+                    if (options.decay == soft_nms_decay::gaussian)
+                        scores[j] *= std::exp(-overlap*overlap/options.sigma);
+                    else if (overlap > options.iou_thresh)
+                        scores[j] *= 1 - overlap;
+                }
+            }
+            return kept;
+        }
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    inline std::vector<unsigned long> non_max_suppression (
+        const std::vector<rectangle>& boxes,
+        const std::vector<double>& scores,
+        const test_box_overlap& overlaps = test_box_overlap()
+    )
+    {
+        DLIB_ASSERT(boxes.size() == scores.size(),
+            "\t std::vector<unsigned long> non_max_suppression()"
+            << "\n\t Invalid inputs were given to this function "
+            << "\n\t boxes.size():  " << boxes.size()
+            << "\n\t scores.size(): " << scores.size()
+            );
+
+        std::vector<unsigned long> kept;
+        std::vector<rectangle> kept_boxes;
+        for (auto i : impl::order_by_descending_score(scores))
+        {
+            if (!overlaps_any_box(overlaps, kept_boxes, boxes[i]))
+            {
+                kept.push_back(i);
+                kept_boxes.push_back(boxes[i]);
+            }
+        }
+        return kept;
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    inline std::vector<mmod_rect> non_max_suppression (
+        const std::vector<mmod_rect>& dets,
+        const test_box_overlap& overlaps = test_box_overlap()
+    )
+    {
+        std::vector<mmod_rect> result;
+        for (auto i : impl::order_by_descending_score(impl::get_confidences(dets)))
+        {
+            const auto overlaps_det = [&](const mmod_rect& kept) { return impl::detections_overlap(overlaps, kept, dets[i]); };
+            if (std::none_of(result.begin(), result.end(), overlaps_det))
+                result.push_back(dets[i]);
+        }
+        return result;
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    inline std::vector<mmod_rect> non_max_suppression_per_label (
+        const std::vector<mmod_rect>& dets,
+        const test_box_overlap& default_overlaps = test_box_overlap(),
+        const std::map<std::string,test_box_overlap>& label_overlaps = {}
+    )
+    {
+        return impl::run_per_label(dets, [&](const std::string& label, const std::vector<mmod_rect>& group)
+        {
+            return non_max_suppression(group, impl::setting_for_label(label_overlaps, label, default_overlaps));
+        });
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    inline std::vector<unsigned long> soft_non_max_suppression (
+        const std::vector<rectangle>& boxes,
+        std::vector<double>& scores,
+        const soft_nms_options& options = soft_nms_options()
+    )
+    {
+        DLIB_ASSERT(boxes.size() == scores.size() && impl::all_non_negative(scores) &&
+                    options.sigma > 0 && 0 <= options.iou_thresh && options.iou_thresh <= 1,
+            "\t std::vector<unsigned long> soft_non_max_suppression()"
+            << "\n\t Invalid inputs were given to this function "
+            << "\n\t boxes.size():  " << boxes.size()
+            << "\n\t scores.size(): " << scores.size()
+            << "\n\t all_non_negative(scores): " << impl::all_non_negative(scores)
+            << "\n\t options.sigma:      " << options.sigma
+            << "\n\t options.iou_thresh: " << options.iou_thresh
+            );
+
+        return impl::soft_non_max_suppression(scores, options, [&](unsigned long a, unsigned long b)
+        {
+            return box_intersection_over_union(boxes[a], boxes[b]);
+        });
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    inline std::vector<mmod_rect> soft_non_max_suppression (
+        const std::vector<mmod_rect>& dets,
+        const soft_nms_options& options = soft_nms_options()
+    )
+    {
+        std::vector<double> scores = impl::get_confidences(dets);
+        DLIB_ASSERT(impl::all_non_negative(scores) &&
+                    options.sigma > 0 && 0 <= options.iou_thresh && options.iou_thresh <= 1,
+            "\t std::vector<mmod_rect> soft_non_max_suppression()"
+            << "\n\t Invalid inputs were given to this function "
+            << "\n\t all_non_negative(scores): " << impl::all_non_negative(scores)
+            << "\n\t options.sigma:      " << options.sigma
+            << "\n\t options.iou_thresh: " << options.iou_thresh
+            );
+
+        const auto iou = [&](unsigned long a, unsigned long b)
+        {
+            return box_intersection_over_union(dets[a].rect, dets[a].angle, dets[b].rect, dets[b].angle);
+        };
+
+        std::vector<mmod_rect> result;
+        for (auto i : impl::soft_non_max_suppression(scores, options, iou))
+        {
+            result.push_back(dets[i]);
+            result.back().detection_confidence = scores[i];
+        }
+        return result;
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    inline std::vector<mmod_rect> soft_non_max_suppression_per_label (
+        const std::vector<mmod_rect>& dets,
+        const soft_nms_options& default_options = soft_nms_options(),
+        const std::map<std::string,soft_nms_options>& label_options = {}
+    )
+    {
+        return impl::run_per_label(dets, [&](const std::string& label, const std::vector<mmod_rect>& group)
+        {
+            return soft_non_max_suppression(group, impl::setting_for_label(label_options, label, default_options));
+        });
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    inline std::vector<mmod_rect> weighted_box_fusion (
+        const std::vector<std::vector<mmod_rect>>& model_dets,
+        double iou_thresh = 0.55,
+        const std::vector<double>& model_weights = {}
+    )
+    {
+        DLIB_ASSERT(0 <= iou_thresh && iou_thresh <= 1 &&
+                    (model_weights.size() == 0 || model_weights.size() == model_dets.size()),
+            "\t std::vector<mmod_rect> weighted_box_fusion()"
+            << "\n\t Invalid inputs were given to this function "
+            << "\n\t iou_thresh:           " << iou_thresh
+            << "\n\t model_weights.size(): " << model_weights.size()
+            << "\n\t model_dets.size():    " << model_dets.size()
+            );
+
+        struct entry
+        {
+            const mmod_rect* det;
+            double weight;
+            double score;
+        };
+
+        double total_weight = 0;
+        std::vector<entry> entries;
+        for (size_t m = 0; m < model_dets.size(); ++m)
+        {
+            const double w = model_weights.size() == 0 ? 1 : model_weights[m];
+            DLIB_ASSERT(w > 0 && impl::all_non_negative(model_dets[m]),
+                "\t std::vector<mmod_rect> weighted_box_fusion()"
+                << "\n\t Model weights must be positive and confidences non-negative."
+                << "\n\t m: " << m
+                << "\n\t w: " << w
+                );
+            total_weight += w;
+            for (auto& d : model_dets[m])
+                entries.push_back(entry{&d, w, w*d.detection_confidence});
+        }
+        std::stable_sort(entries.begin(), entries.end(),
+            [](const entry& a, const entry& b) { return a.score > b.score; });
+
+        struct cluster
+        {
+            std::string label;
+            unsigned long count = 0;
+            double sum_weight = 0;
+            double sum_score = 0;
+            // Box coordinates weighted by score, and unweighted in case every score is 0.
+            drectangle weighted_sum = drectangle(0,0,0,0);
+            drectangle plain_sum = drectangle(0,0,0,0);
+            drectangle fused;
+            // The angles are averaged as unit vectors so that, e.g., angles just above -pi
+            // and just below pi average to pi rather than 0.
+            dpoint weighted_direction, plain_direction;
+            double fused_angle = 0;
+
+            void add (const entry& e)
+            {
+                const drectangle r = e.det->rect;
+                if (count == 0)
+                    label = e.det->label;
+                ++count;
+                sum_weight += e.weight;
+                sum_score += e.score;
+                weighted_sum = drectangle(weighted_sum.left()   + e.score*r.left(),
+                                          weighted_sum.top()    + e.score*r.top(),
+                                          weighted_sum.right()  + e.score*r.right(),
+                                          weighted_sum.bottom() + e.score*r.bottom());
+                plain_sum = drectangle(plain_sum.left()   + r.left(),
+                                       plain_sum.top()    + r.top(),
+                                       plain_sum.right()  + r.right(),
+                                       plain_sum.bottom() + r.bottom());
+                const dpoint direction(std::cos(e.det->angle), std::sin(e.det->angle));
+                weighted_direction += e.score*direction;
+                plain_direction += direction;
+                const drectangle& s = sum_score > 0 ? weighted_sum : plain_sum;
+                const double z = sum_score > 0 ? sum_score : count;
+                fused = drectangle(s.left()/z, s.top()/z, s.right()/z, s.bottom()/z);
+                const dpoint& d = sum_score > 0 ? weighted_direction : plain_direction;
+                fused_angle = std::atan2(d.y(), d.x());
+            }
+        };
+
+        std::vector<cluster> clusters;
+        for (auto& e : entries)
+        {
+            const drectangle r = e.det->rect;
+            double best_iou = iou_thresh;
+            cluster* best = nullptr;
+            for (auto& c : clusters)
+            {
+                const double iou = box_intersection_over_union(c.fused, c.fused_angle, r, e.det->angle);
+                if (iou > best_iou)
+                {
+                    best_iou = iou;
+                    best = &c;
+                }
+            }
+            if (best == nullptr)
+            {
+                clusters.emplace_back();
+                best = &clusters.back();
+            }
+            best->add(e);
+        }
+
+        std::vector<mmod_rect> result;
+        for (auto& c : clusters)
+        {
+            // The average confidence of the cluster, scaled down when only part of the
+            // ensemble voted for it.
+            const double confidence = c.sum_score/c.sum_weight * std::min(total_weight, c.sum_weight)/total_weight;
+            result.emplace_back(rectangle(c.fused), confidence, c.label);
+            result.back().angle = c.fused_angle;
+        }
+        impl::sort_by_descending_confidence(result);
+        return result;
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    inline std::vector<mmod_rect> weighted_box_fusion (
+        const std::vector<mmod_rect>& dets,
+        double iou_thresh = 0.55
+    )
+    {
+        return weighted_box_fusion(std::vector<std::vector<mmod_rect>>{dets}, iou_thresh);
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    inline std::vector<mmod_rect> weighted_box_fusion_per_label (
+        const std::vector<std::vector<mmod_rect>>& model_dets,
+        double default_iou_thresh = 0.55,
+        const std::map<std::string,double>& label_iou_thresh = {},
+        const std::vector<double>& model_weights = {}
+    )
+    {
+        std::map<std::string, std::vector<std::vector<mmod_rect>>> groups;
+        for (size_t m = 0; m < model_dets.size(); ++m)
+        {
+            for (auto& d : model_dets[m])
+            {
+                auto& g = groups[d.label];
+                g.resize(model_dets.size());
+                g[m].push_back(d);
+            }
+        }
+
+        std::vector<mmod_rect> result;
+        for (auto& g : groups)
+        {
+            const auto temp = weighted_box_fusion(g.second, impl::setting_for_label(label_iou_thresh, g.first, default_iou_thresh), model_weights);
+            result.insert(result.end(), temp.begin(), temp.end());
+        }
+        impl::sort_by_descending_confidence(result);
+        return result;
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    inline std::vector<mmod_rect> weighted_box_fusion_per_label (
+        const std::vector<mmod_rect>& dets,
+        double default_iou_thresh = 0.55,
+        const std::map<std::string,double>& label_iou_thresh = {}
+    )
+    {
+        return weighted_box_fusion_per_label(std::vector<std::vector<mmod_rect>>{dets}, default_iou_thresh, label_iou_thresh);
+    }
+
+// ----------------------------------------------------------------------------------------
+
+}
+
+#endif // DLIB_NON_MAX_SUPPRESSIOn_H_
@@ -0,0 +1,251 @@
+// Copyright (C) 2026  Davis E. King (davis@dlib.net)
+// License: Boost Software License   See LICENSE.txt for the full license.
+#undef DLIB_NON_MAX_SUPPRESSIOn_ABSTRACT_H_
+#ifdef DLIB_NON_MAX_SUPPRESSIOn_ABSTRACT_H_
+
+#include "box_overlap_testing_abstract.h"
+#include "full_object_detection_abstract.h"
+#include "../geometry.h"
+#include <vector>
+#include <map>
+#include <string>
+
+namespace dlib
+{
+
+// ----------------------------------------------------------------------------------------
+
+    std::vector<unsigned long> non_max_suppression (
+        const std::vector<rectangle>& boxes,
+        const std::vector<double>& scores,
+        const test_box_overlap& overlaps = test_box_overlap()
+    );
+    /*!
+        requires
+            - boxes.size() == scores.size()
+        ensures
+            - Performs greedy non-max suppression on the given boxes.  That is, the boxes
+              are visited in order of decreasing score and a box is kept only if
+              overlaps(kept_box, box) is false for every box kept before it.  Ties in score
+              are broken in favor of the box with the smaller index.
+            - returns the indices of the kept boxes, sorted by decreasing score.  So for
+              all valid i, boxes[returned_value[i]] is a kept box.
+    !*/
+
+    std::vector<mmod_rect> non_max_suppression (
+        const std::vector<mmod_rect>& dets,
+        const test_box_overlap& overlaps = test_box_overlap()
+    );
+    /*!
+        ensures
+            - Performs the same greedy non-max suppression as the rectangle version of
+              non_max_suppression() above, using dets[i].rect as the boxes and
+              dets[i].detection_confidence as the scores.
+            - The detections are treated as rotated boxes.  That is, two detections A and
+              B overlap if overlaps(A.rect, B.rect) is true when A.angle == B.angle == 0
+              and if overlaps(A.rect, A.angle, B.rect, B.angle) is true otherwise.  So
+              this function performs rotated NMS on rotated detections, such as those
+              output by loss_mmod_ with angle regression enabled.
+            - Labels are ignored, so a detection may suppress a detection with a
+              different label.  Use non_max_suppression_per_label() if you don't want that.
+            - returns the kept elements of dets, sorted by decreasing detection_confidence.
+    !*/
+
+    std::vector<mmod_rect> non_max_suppression_per_label (
+        const std::vector<mmod_rect>& dets,
+        const test_box_overlap& default_overlaps = test_box_overlap(),
+        const std::map<std::string,test_box_overlap>& label_overlaps = {}
+    );
+    /*!
+        ensures
+            - Performs non_max_suppression() separately on each group of detections that
+              share the same label.  Therefore, a detection can only suppress other
+              detections with the same label.
+            - The detections with label L are suppressed using label_overlaps[L] if L is a
+              key of label_overlaps, and default_overlaps otherwise.  This lets you use a
+              different overlap threshold for each object class.
+            - returns the kept elements of dets, sorted by decreasing detection_confidence.
+    !*/
+
+// ----------------------------------------------------------------------------------------
+
+    enum class soft_nms_decay
+    {
+        linear,
+        gaussian
+    };
+
+    struct soft_nms_options
+    {
+        /*!
+            WHAT THIS OBJECT REPRESENTS
+                This object holds the parameters of soft_non_max_suppression().  When a box
+                M is selected, every remaining box B has its score multiplied by:
+                    - if (decay == soft_nms_decay::gaussian)
+                        - exp(-IOU*IOU/sigma)
+                    - if (decay == soft_nms_decay::linear)
+                        - 1-IOU if IOU > iou_thresh, and 1 otherwise.
+                where IOU == box_intersection_over_union(M,B).  Boxes whose score drops
+                below min_confidence are discarded.
+        !*/
+
+        soft_nms_decay decay = soft_nms_decay::gaussian;
+        double sigma = 0.5;
+        double iou_thresh = 0.3;
+        double min_confidence = 0.001;
+    };
+
+    std::vector<unsigned long> soft_non_max_suppression (
+        const std::vector<rectangle>& boxes,
+        std::vector<double>& scores,
+        const soft_nms_options& options = soft_nms_options()
+    );
+    /*!
+        requires
+            - boxes.size() == scores.size()
+            - for all valid i: scores[i] >= 0
+            - options.sigma > 0
+            - 0 <= options.iou_thresh <= 1
+        ensures
+            - Performs Soft-NMS as described in the paper:
+                Bodla, Navaneeth, et al. "Soft-NMS -- Improving Object Detection With One
+                Line of Code." Proceedings of the IEEE International Conference on
+                Computer Vision. 2017.
+              That is, rather than discarding every box that overlaps a higher scoring
+              box, the scores of overlapping boxes are decayed as described in
+              soft_nms_options.  The highest scoring remaining box is repeatedly selected
+              until no remaining box has a score >= options.min_confidence.
+            - returns the indices of the selected boxes, in the order they were selected.
+              This is also decreasing order of their decayed scores.
+            - #scores[i] == the decayed score of boxes[i].  Boxes that were not selected
+              have a decayed score < options.min_confidence.
+    !*/
+
+    std::vector<mmod_rect> soft_non_max_suppression (
+        const std::vector<mmod_rect>& dets,
+        const soft_nms_options& options = soft_nms_options()
+    );
+    /*!
+        requires
+            - for all valid i: dets[i].detection_confidence >= 0
+            - options.sigma > 0
+            - 0 <= options.iou_thresh <= 1
+        ensures
+            - Performs the same Soft-NMS as the rectangle version above, ignoring labels.
+              The detections are treated as rotated boxes, so the IOU used to decay the
+              scores is box_intersection_over_union(M.rect, M.angle, B.rect, B.angle).
+            - returns the selected elements of dets, sorted by decreasing confidence, with
+              detection_confidence set to the decayed score.
+    !*/
+
+    std::vector<mmod_rect> soft_non_max_suppression_per_label (
+        const std::vector<mmod_rect>& dets,
+        const soft_nms_options& default_options = soft_nms_options(),
+        const std::map<std::string,soft_nms_options>& label_options = {}
+    );
+    /*!
+        requires
+            - for all valid i: dets[i].detection_confidence >= 0
+            - all options have sigma > 0 and 0 <= iou_thresh <= 1
+        ensures
+            - Performs soft_non_max_suppression() separately on each group of detections
+              that share the same label, using label_options[L] for the detections with
+              label L if L is a key of label_options, and default_options otherwise.
+            - returns the selected detections, sorted by decreasing detection_confidence.
+    !*/
+
+// ----------------------------------------------------------------------------------------
+
+    std::vector<mmod_rect> weighted_box_fusion (
+        const std::vector<std::vector<mmod_rect>>& model_dets,
+        double iou_thresh = 0.55,
+        const std::vector<double>& model_weights = {}
+    );
+    /*!
+        requires
+            - 0 <= iou_thresh <= 1
+            - model_weights.size() == 0 || model_weights.size() == model_dets.size()
+            - all the elements of model_weights are > 0
+            - all the detection confidences in model_dets are >= 0
+        ensures
+            - Performs weighted box fusion as described in the paper:
+                Solovyev, Roman, Weimin Wang, and Tatiana Gabruseva. "Weighted boxes
+                fusion: Ensembling boxes from different object detection models." Image
+                and Vision Computing 107 (2021).
+              Rather than discarding overlapping boxes, this function merges them into a
+              single box.  This is most useful for combining the outputs of an ensemble
+              of detectors, or of one detector run on several augmentations of an image.
+            - model_dets[m] is interpreted as the detections output by the m-th model.  If
+              model_weights is empty every model gets a weight of 1, otherwise the m-th
+              model has weight model_weights[m].  Let W be the sum of the model weights.
+            - Every detection D from model m is given a score of
+              model_weights[m]*D.detection_confidence.  The detections are visited in order
+              of decreasing score and each one is added to the cluster whose fused box has
+              the largest IOU with it, provided that IOU is > iou_thresh.  Otherwise, it
+              starts a new cluster.  The detections are treated as rotated boxes, so the
+              IOU is the rotated box_intersection_over_union() of the boxes and their angles.
+            - Labels are ignored when clustering.  Each fused box takes the label of the
+              highest scoring detection in its cluster.  Use
+              weighted_box_fusion_per_label() if you only want detections with the same
+              label fused together.
+            - returns one mmod_rect R for each cluster, sorted by decreasing
+              detection_confidence, such that:
+                - R.rect is the score weighted average of the boxes in the cluster, or the
+                  plain average if all their scores are 0.
+                - R.angle is the angle of the same weighted average of the unit vectors
+                  (cos(angle), sin(angle)) of the boxes in the cluster.  So if all the
+                  boxes have an angle of 0 then R.angle == 0.
+                - Let S be the sum of the scores in the cluster and C the sum of the
+                  weights of the models that contributed them.  Then
+                  R.detection_confidence == S/C * min(W,C)/W.  That is, the average
+                  confidence of the cluster, reduced if only part of the ensemble voted for
+                  it.
+                - R.ignore == false
+    !*/
+
+    std::vector<mmod_rect> weighted_box_fusion (
+        const std::vector<mmod_rect>& dets,
+        double iou_thresh = 0.55
+    );
+    /*!
+        requires
+            - 0 <= iou_thresh <= 1
+            - for all valid i: dets[i].detection_confidence >= 0
+        ensures
+            - returns weighted_box_fusion(std::vector<std::vector<mmod_rect>>{dets}, iou_thresh)
+              That is, fuses the detections of a single model.  In this case, the
+              confidence of a fused box is the average confidence of its cluster.
+    !*/
+
+    std::vector<mmod_rect> weighted_box_fusion_per_label (
+        const std::vector<std::vector<mmod_rect>>& model_dets,
+        double default_iou_thresh = 0.55,
+        const std::map<std::string,double>& label_iou_thresh = {},
+        const std::vector<double>& model_weights = {}
+    );
+    /*!
+        requires
+            - the thresholds and model_weights satisfy the requirements of weighted_box_fusion()
+        ensures
+            - Performs weighted_box_fusion() separately on each group of detections that
+              share the same label, so only boxes with the same label are fused together.
+              The detections with label L use an IOU threshold of label_iou_thresh[L] if L
+              is a key of label_iou_thresh, and default_iou_thresh otherwise.
+            - returns the fused detections, sorted by decreasing detection_confidence.
+    !*/
+
+    std::vector<mmod_rect> weighted_box_fusion_per_label (
+        const std::vector<mmod_rect>& dets,
+        double default_iou_thresh = 0.55,
+        const std::map<std::string,double>& label_iou_thresh = {}
+    );
+    /*!
+        ensures
+            - returns weighted_box_fusion_per_label(std::vector<std::vector<mmod_rect>>{dets}, default_iou_thresh, label_iou_thresh)
+    !*/
+
+// ----------------------------------------------------------------------------------------
+
+}
+
+#endif // DLIB_NON_MAX_SUPPRESSIOn_ABSTRACT_H_
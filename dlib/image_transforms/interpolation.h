@@ -1111,12 +1111,56 @@ namespace dlib
             return tran(rect);
         }
 
+        inline double tform_box_angle (
+            const point_transform_affine& tran,
+            double angle
+        )
+        {
+            const matrix<double,2,2>& m = tran.get_m();
+            // Find where the box's horizontal axis ends up.  If tran is a reflection we
+            // also mirror the box left to right in its own frame, which keeps an upright
+            // box upright.
+            dpoint dir = m*dpoint(std::cos(angle), std::sin(angle));
+            if (m(0,0)*m(1,1) - m(0,1)*m(1,0) < 0)
+                dir = -dir;
+            return std::atan2(dir.y(), dir.x());
+        }
+
+        inline drectangle tform_rotated_box (
+            const point_transform_affine& tran,
+            const drectangle& rect
+        )
+        {
+            // A rotated box keeps its shape under a similarity transform, so we only need
+            // to move its center and scale its size.
+            const matrix<double,2,2>& m = tran.get_m();
+            const double scale = std::sqrt(std::abs(m(0,0)*m(1,1) - m(0,1)*m(1,0)));
+            return centered_drect(tran(dcenter(rect)), rect.width()*scale, rect.height()*scale);
+        }
+
+        inline rectangle tform_rotated_box (
+            const point_transform_affine& tran,
+            const rectangle& rect
+        )
+        {
+            const auto temp = tform_rotated_box(tran, drectangle(rect));
+            return centered_rect(center(temp), std::round(temp.width()), std::round(temp.height()));
+        }
+
         inline mmod_rect tform_object (
             const rectangle_transform& tran,
             mmod_rect rect
         )
         {
-            rect.rect = tform_object(tran, rect.rect);
+            if (rect.angle != 0)
+            {
+                rect.rect = tform_rotated_box(tran.get_tform(), rect.rect);
+                rect.angle = tform_box_angle(tran.get_tform(), rect.angle);
+            }
+            else
+            {
+                rect.rect = tform_object(tran, rect.rect);
+            }
             return rect;
         }
 
@@ -562,6 +562,9 @@ namespace dlib
               objects.  That is, we assume objects[i] is the set of bounding boxes in
               images[i] and we flip the bounding boxes so that they still bound the same
               objects in the new flipped images.
+            - mmod_rects with a non-zero angle are treated as rotated boxes (see
+              mmod_rect).  Their rect is mirrored and their angle is negated so that they
+              still describe the same rotated box in the flipped images.
             - #images.size() == images.size()*2
             - #objects.size() == objects.size()*2
             - All the original elements of images and objects are left unmodified.  That
@@ -596,6 +599,9 @@ namespace dlib
               objects[i] is the set of bounding boxes in images[i] and we flip the bounding
               boxes so that they still bound the same objects in the new flipped images.
               We similarly flip the boxes in objects2.
+            - mmod_rects with a non-zero angle are treated as rotated boxes (see
+              mmod_rect).  Their rect is mirrored and their angle is negated so that they
+              still describe the same rotated box in the flipped images.
             - #images.size()   == images.size()*2
             - #objects.size()  == objects.size()*2
             - #objects2.size() == objects2.size()*2
@@ -638,6 +644,10 @@ namespace dlib
             - The elements of angles are interpreted as angles in radians and we will
               rotate the images around their center using the values in angles.  Moreover,
               the rotation is done counter clockwise.
+            - mmod_rects with a non-zero angle are treated as rotated boxes (see
+              mmod_rect).  Rather than being replaced by an axis aligned box that roughly
+              bounds the rotated object, they keep their size and have their angle
+              adjusted by the rotation.
             - #images.size()   == images.size()*angles.size()
             - #objects.size()  == objects.size()*angles.size()
             - #objects2.size() == objects2.size()*angles.size()
@@ -13,6 +13,49 @@
 
 namespace dlib
 {
+    namespace impl
+    {
+        // These functions map boxes that have an angle field, like mmod_rect, as rotated
+        // boxes.  Boxes without an angle field are mapped as ordinary axis aligned boxes.
+        template <typename rectangle_type>
+        auto tform_cropped_box (
+            const point_transform_affine& tform,
+            rectangle_type& rect,
+            int
+        ) -> decltype(rect.angle, void())
+        {
+            rect.rect = tform_rotated_box(tform, rect.rect);
+            rect.angle = tform_box_angle(tform, rect.angle);
+        }
+
+        template <typename rectangle_type>
+        void tform_cropped_box (
+            const point_transform_affine& tform,
+            rectangle_type& rect,
+            long
+        )
+        {
+            rect.rect = rectangle_transform(tform)(rect.rect);
+        }
+
+        template <typename rectangle_type>
+        auto flip_cropped_box_angle (
+            rectangle_type& rect,
+            int
+        ) -> decltype(rect.angle, void())
+        {
+            rect.angle = -rect.angle;
+        }
+
+        template <typename rectangle_type>
+        void flip_cropped_box_angle (
+            rectangle_type& ,
+            long
+        )
+        {
+        }
+    }
+
     class random_cropper
     {
         chip_dims dims = chip_dims(300,300);
@@ -24,6 +67,7 @@ namespace dlib
         double background_crops_fraction = 0.5;
         double translate_amount = 0.10;
         double min_object_coverage = 1.0;
+        bool rotated_boxes = false;
 
         std::mutex rnd_mutex;
         dlib::rand rnd;
@@ -115,6 +159,12 @@ namespace dlib
             min_object_coverage = value;
         }
 
+        bool get_rotated_boxes (
+        ) const { return rotated_boxes; }
+        void set_rotated_boxes (
+            bool value
+        ) { rotated_boxes = value; }
+
         template <
             typename array_type,
             typename rectangle_type
@@ -208,7 +258,7 @@ namespace dlib
             make_crop_plan(img, rects, crop_plan, should_flip_crop);
 
             extract_image_chip(img, crop_plan, crop);
-            const rectangle_transform tform = get_mapping_to_chip(crop_plan);
+            const point_transform_affine tform = get_mapping_to_chip(crop_plan);
 
             // copy rects into crop_rects and set ones that are outside the crop to ignore or
             // drop entirely as appropriate.
@@ -216,7 +266,10 @@ namespace dlib
             for (auto rect : rects)
             {
                 // map to crop
-                rect.rect = tform(rect.rect);
+                if (rotated_boxes)
+                    impl::tform_cropped_box(tform, rect, 0);
+                else
+                    rect.rect = rectangle_transform(tform)(rect.rect);
 
                 const double intersection = get_rect(crop).intersect(rect.rect).area();
 
@@ -242,7 +295,11 @@ namespace dlib
                 flip_image_left_right(crop, temp); 
                 swap(crop,temp);
                 for (auto&& rect : crop_rects)
+                {
                     rect.rect = impl::flip_rect_left_right(rect.rect, get_rect(crop));
+                    if (rotated_boxes)
+                        impl::flip_cropped_box_angle(rect, 0);
+                }
             }
         }
 
@@ -373,6 +430,7 @@ namespace dlib
         out << "  max_object_size:             " << item.get_max_object_size() << endl;
         out << "  background_crops_fraction:   " << item.get_background_crops_fraction() << endl;
         out << "  translate_amount:            " << item.get_translate_amount() << endl;
+        out << "  rotated_boxes:               " << std::boolalpha << item.get_rotated_boxes() << endl;
         return out;
     }
 
@@ -41,6 +41,7 @@ namespace dlib
                 - #get_background_crops_fraction() == 0.5
                 - #get_translate_amount() == 0.1
                 - #get_min_object_coverage == 1.0
+                - #get_rotated_boxes() == false
         !*/
 
         void set_seed (
@@ -218,6 +219,32 @@ namespace dlib
                 - #get_min_object_coverage() == value
         !*/
 
+        bool get_rotated_boxes (
+        ) const;
+        /*!
+            ensures
+                - returns true if the boxes given to this object are treated as rotated
+                  boxes, in the sense of mmod_rect::angle.  In this case, when a crop is
+                  rotated or flipped each box keeps its shape and has its angle adjusted
+                  so that it still describes the same rotated object in the crop.  This
+                  is what you want when training a detector that predicts rotated boxes,
+                  such as loss_mmod_ with angle regression enabled.
+                - returns false if the boxes are treated as axis aligned boxes.  In this
+                  case a box in a rotated crop is replaced by an axis aligned box with the
+                  same center and area that roughly bounds the rotated object, and box
+                  angles are left unchanged.
+                - Boxes whose rectangle_type doesn't have an angle field are always
+                  treated as axis aligned boxes.
+        !*/
+
+        void set_rotated_boxes (
+            bool value
+        );
+        /*!
+            ensures
+                - #get_rotated_boxes() == value
+        !*/
+
         template <
             typename array_type,
             typename rectangle_type
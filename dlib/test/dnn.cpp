@@ -1477,6 +1477,55 @@ void test_embeddings()
         DLIB_TEST_MSG(max(abs(mat(v)-mat(vv))) < 1e-6, max(abs(mat(v)-mat(vv))));
     }
 
+    void compare_solver_updates()
+    {
+        tt::tensor_rand rnd;
+        resizable_tensor s, m, v, params, params_grad;
+        s.set_size(19,20,30,7);
+        m.copy_size(s);
+        v.copy_size(s);
+        params.copy_size(s);
+        params_grad.copy_size(s);
+
+        rnd.fill_uniform(m);
+        rnd.fill_uniform(v);
+        rnd.fill_uniform(params);
+        rnd.fill_gaussian(params_grad);
+
+        resizable_tensor m1(m), v1(v), m2(m), v2(v);
+        auto check = [&]()
+        {
+            matrix<float> s1 = mat(s);
+            s = 0;
+            return s1;
+        };
+
+        cpu::compute_adamw_update(0,params.size(),s, m1, v1, 2, 0.01, 0.001, 0.9, 0.99, params, params_grad);
+        matrix<float> s1 = check();
+        cuda::compute_adamw_update(0,params.size(),s, m2, v2, 2, 0.01, 0.001, 0.9, 0.99, params, params_grad);
+        DLIB_TEST_MSG(max(abs(s1-mat(s))) < 1e-6, max(abs(s1-mat(s))));
+        DLIB_TEST(max(abs(mat(m1)-mat(m2))) < 1e-6);
+        DLIB_TEST(max(abs(mat(v1)-mat(v2))) < 1e-6);
+
+        cpu::compute_rmsprop_update(0,params.size(),s, v1, 0.01, 0.001, 0.9, params, params_grad);
+        s1 = check();
+        cuda::compute_rmsprop_update(0,params.size(),s, v2, 0.01, 0.001, 0.9, params, params_grad);
+        DLIB_TEST_MSG(max(abs(s1-mat(s))) < 1e-6, max(abs(s1-mat(s))));
+        DLIB_TEST(max(abs(mat(v1)-mat(v2))) < 1e-6);
+
+        cpu::compute_adagrad_update(0,params.size(),s, v1, 0.01, 0.001, params, params_grad);
+        s1 = check();
+        cuda::compute_adagrad_update(0,params.size(),s, v2, 0.01, 0.001, params, params_grad);
+        DLIB_TEST_MSG(max(abs(s1-mat(s))) < 1e-6, max(abs(s1-mat(s))));
+        DLIB_TEST(max(abs(mat(v1)-mat(v2))) < 1e-5);
+
+        cpu::compute_lion_update(0,params.size(),s, m1, 0.01, 0.001, 0.9, 0.99, params, params_grad);
+        s1 = check();
+        cuda::compute_lion_update(0,params.size(),s, m2, 0.01, 0.001, 0.9, 0.99, params, params_grad);
+        DLIB_TEST_MSG(max(abs(s1-mat(s))) < 1e-6, max(abs(s1-mat(s))));
+        DLIB_TEST(max(abs(mat(m1)-mat(m2))) < 1e-6);
+    }
+
     void test_multiply_zero_padded()
     {
         print_spinner();
@@ -2112,6 +2161,30 @@ void test_embeddings()
             auto res = test_layer(l);
             DLIB_TEST_MSG(res, res);
         }        
+        {
+            print_spinner();
+            lstm_<3> l;
+            auto res = test_layer(l);
+            DLIB_TEST_MSG(res, res);
+        }
+        {
+            print_spinner();
+            gru_<3> l;
+            auto res = test_layer(l);
+            DLIB_TEST_MSG(res, res);
+        }
+        {
+            print_spinner();
+            multihead_attention_<1> l;
+            auto res = test_layer(l);
+            DLIB_TEST_MSG(res, res);
+        }
+        {
+            print_spinner();
+            multihead_attention_<2,true> l;
+            auto res = test_layer(l);
+            DLIB_TEST_MSG(res, res);
+        }
         {
             print_spinner();
             extract_<0,2,2,2> l;
@@ -2304,6 +2377,24 @@ void test_embeddings()
             auto res = test_layer(l);
             DLIB_TEST_MSG(res, res);
         }
+        {
+            print_spinner();
+            con_<3,3,3,1,1,2,2,2,2> l;
+            auto res = test_layer(l);
+            DLIB_TEST_MSG(res, res);
+        }
+        {
+            print_spinner();
+            con_<3,3,2,2,1,2,0,2,1> l;
+            auto res = test_layer(l);
+            DLIB_TEST_MSG(res, res);
+        }
+        {
+            print_spinner();
+            cont_<3,3,3,2,2,2,2,2,2> l;
+            auto res = test_layer(l);
+            DLIB_TEST_MSG(res, res);
+        }
         {
             print_spinner();
             fc_<1,FC_HAS_BIAS> l;
@@ -4466,6 +4557,161 @@ void test_embeddings()
         DLIB_TEST(dets.size() < approximate_desired_det_count * 1.05);
     }
 
+// ----------------------------------------------------------------------------------------
+
+    void test_loss_mmod_angle_regression()
+    {
+        print_spinner();
+        // The network passes its input straight through, so the input channels are the
+        // detection scores followed by the (cos,sin) outputs of the single detector window.
+        using net_type = loss_mmod<tag1<input<std::array<matrix<float>,3>>>>;
+        mmod_options options;
+        options.detector_windows = {mmod_options::detector_window_details(12, 4)};
+        options.assume_image_pyramid = use_image_pyramid::no;
+        options.use_angle_regression = true;
+        options.be_quiet = true;
+        net_type net(options);
+
+        std::array<matrix<float>,3> img;
+        for (auto& channel : img)
+            channel = zeros_matrix<float>(30, 30);
+        img[0] = -1;
+        const auto add_det = [&](long r, long c, float score, double angle)
+        {
+            img[0](r, c) = score;
+            img[1](r, c) = std::cos(angle);
+            img[2](r, c) = std::sin(angle);
+        };
+        // The first two boxes cross each other in an X, so rotated NMS keeps both of them
+        // even though their axis aligned boxes almost coincide.  The third box has nearly
+        // the same position and angle as the first one and is suppressed.
+        add_det(10, 10, 2.0f, 0.5);
+        add_det(10, 11, 1.5f, -0.5);
+        add_det(11, 10, 1.0f, 0.5);
+        add_det(20, 20, 0.5f, 0);
+
+        std::vector<mmod_rect> dets = net(img);
+        DLIB_TEST(dets.size() == 3);
+        DLIB_TEST(std::abs(dets[0].angle - 0.5) < 1e-6);
+        DLIB_TEST(std::abs(dets[1].angle + 0.5) < 1e-6);
+        DLIB_TEST(dets[2].angle == 0);
+        DLIB_TEST(length(dcenter(dets[0].rect) - dpoint(10, 10)) < 1);
+        DLIB_TEST(length(dcenter(dets[2].rect) - dpoint(20, 20)) < 1);
+        DLIB_TEST(dets[0].rect.width() == 12 && dets[0].rect.height() == 4);
+
+        // The angle regression loss is only incurred by the detection that hits the
+        // truth box, and its gradient pulls the (cos,sin) outputs toward the true angle.
+        for (auto& channel : img)
+            channel = zeros_matrix<float>(30, 30);
+        img[0] = -1;
+        add_det(10, 10, 2.0f, 0.5);
+        mmod_rect truth(centered_rect(point(10, 10), 12, 4));
+        truth.angle = 0.3;
+        const std::vector<std::vector<mmod_rect>> labels = {{truth}};
+
+        resizable_tensor x;
+        net.to_tensor(&img, &img + 1, x);
+        const double loss = net.compute_loss(x, labels.begin());
+        const tensor& grad = net.subnet().get_gradient_input();
+        DLIB_TEST(grad.k() == 3);
+        const double scale = 1.0/(30*30);
+        const float* g = grad.host();
+        const double dcos = std::cos(0.5) - std::cos(0.3);
+        const double dsin = std::sin(0.5) - std::sin(0.3);
+        DLIB_TEST(std::abs(g[(1*30 + 10)*30 + 10] - scale*options.angle_lambda*dcos) < 1e-7);
+        DLIB_TEST(std::abs(g[(2*30 + 10)*30 + 10] - scale*options.angle_lambda*dsin) < 1e-7);
+
+        options.angle_lambda = 0;
+        net_type net_no_angle_loss(options);
+        net_no_angle_loss.to_tensor(&img, &img + 1, x);
+        const double loss_no_angle = net_no_angle_loss.compute_loss(x, labels.begin());
+        DLIB_TEST(std::abs(loss - loss_no_angle - 10*0.5*(dcos*dcos + dsin*dsin)) < 1e-5);
+
+        // The angle options and the angles of the boxes are serialized.
+        std::ostringstream sout;
+        serialize(net, sout);
+        serialize(dets, sout);
+        std::istringstream sin(sout.str());
+        net_type net2;
+        std::vector<mmod_rect> dets2;
+        deserialize(net2, sin);
+        deserialize(dets2, sin);
+        DLIB_TEST(net2.loss_details().get_options().use_angle_regression);
+        DLIB_TEST(net2.loss_details().get_options().angle_lambda == 10);
+        DLIB_TEST(dets2 == dets);
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    void test_loss_yolo_angle_regression()
+    {
+        print_spinner();
+        // With one anchor and one label each output location has 8 channels: x, y, w, h,
+        // the objectness, the class score, and the (cos,sin) of the angle mapped to (0,1).
+        using net_type = add_loss_layer<loss_yolo_<tag1>, tag1<input<std::array<matrix<float>,8>>>>;
+        yolo_options options;
+        options.add_anchors<tag1>({{12, 4}});
+        options.labels = {"bar"};
+        options.use_angle_regression = true;
+        net_type net(options);
+
+        std::array<matrix<float>,8> img;
+        const auto reset = [&]()
+        {
+            for (auto& channel : img)
+                channel = zeros_matrix<float>(30, 30);
+        };
+        const auto add_det = [&](long r, long c, float obj, double angle)
+        {
+            // These values put a box the size of the anchor at (c,r).
+            img[0](r, c) = 0.25f;
+            img[1](r, c) = 0.25f;
+            img[2](r, c) = 0.5f;
+            img[3](r, c) = 0.5f;
+            img[4](r, c) = obj;
+            img[5](r, c) = 1;
+            img[6](r, c) = (std::cos(angle) + 1)/2;
+            img[7](r, c) = (std::sin(angle) + 1)/2;
+        };
+        reset();
+        add_det(10, 10, 0.9f, 0.5);
+        add_det(10, 11, 0.8f, -0.5);
+        add_det(11, 10, 0.7f, 0.5);
+
+        std::vector<yolo_rect> dets = net(img);
+        DLIB_TEST(dets.size() == 2);
+        DLIB_TEST(std::abs(dets[0].angle - 0.5) < 1e-6);
+        DLIB_TEST(std::abs(dets[1].angle + 0.5) < 1e-6);
+        DLIB_TEST(length(dcenter(dets[0].rect) - dpoint(10, 10)) < 1e-6);
+        DLIB_TEST(std::abs(dets[0].rect.width() - 12) < 1e-6);
+        DLIB_TEST(dets[0].label == "bar");
+
+        // The angle gradient is only set at the output that is responsible for the truth box.
+        yolo_rect truth(centered_drect(dpoint(10.25, 10.25), 12, 4), 1, "bar");
+        truth.angle = 0.3;
+        const std::vector<std::vector<yolo_rect>> labels = {{truth}};
+
+        resizable_tensor x;
+        net.to_tensor(&img, &img + 1, x);
+        net.compute_loss(x, labels.begin());
+        const tensor& grad = net.subnet().get_gradient_input();
+        const float* g = grad.host();
+        DLIB_TEST(std::abs(g[(6*30 + 10)*30 + 10] - (std::cos(0.5) - std::cos(0.3))) < 1e-6);
+        DLIB_TEST(std::abs(g[(7*30 + 10)*30 + 10] - (std::sin(0.5) - std::sin(0.3))) < 1e-6);
+        DLIB_TEST(g[(6*30 + 10)*30 + 11] == 0);
+
+        std::ostringstream sout;
+        serialize(net, sout);
+        serialize(dets, sout);
+        std::istringstream sin(sout.str());
+        net_type net2;
+        std::vector<yolo_rect> dets2;
+        deserialize(net2, sin);
+        deserialize(dets2, sin);
+        DLIB_TEST(net2.loss_details().get_options().use_angle_regression);
+        DLIB_TEST(dets2 == dets);
+    }
+
 // ----------------------------------------------------------------------------------------
 
     void test_fuse_layers()
@@ -4589,6 +4835,3725 @@ void test_embeddings()
         DLIB_TEST(max(abs(mat(net_output) - mat(expected_output))) < 1e-5);
     }
 
+// ----------------------------------------------------------------------------------------
+
+    // A small reference interpreter for the ONNX operators produced by net_to_onnx().  It
+    // is deliberately written without using any of dlib's tensor tools so that it gives
+    // an independent check of the exported graphs.
+    struct onnx_ref_tensor
+    {
+        std::vector<long> dims;
+        std::vector<float> data;
+
+        long size() const
+        {
+            long num = 1;
+            for (auto d : dims)
+                num *= d;
+            return num;
+        }
+    };
+
+    template <typename F>
+    onnx_ref_tensor onnx_ref_broadcast(
+        const onnx_ref_tensor& a,
+        const onnx_ref_tensor& b,
+        F op
+    )
+    {
+        const size_t rank = std::max(a.dims.size(), b.dims.size());
+        std::vector<long> da(rank, 1), db(rank, 1), dout(rank);
+        std::copy(a.dims.begin(), a.dims.end(), da.begin()+rank-a.dims.size());
+        std::copy(b.dims.begin(), b.dims.end(), db.begin()+rank-b.dims.size());
+        onnx_ref_tensor out;
+        for (size_t i = 0; i < rank; ++i)
+        {
+            DLIB_TEST(da[i] == db[i] || da[i] == 1 || db[i] == 1);
+            dout[i] = std::max(da[i], db[i]);
+        }
+        out.dims = dout;
+        out.data.resize(out.size());
+        std::vector<long> idx(rank, 0);
+        for (long i = 0; i < out.size(); ++i)
+        {
+            long ia = 0, ib = 0;
+            for (size_t j = 0; j < rank; ++j)
+            {
+                ia = ia*da[j] + (da[j] == 1 ? 0 : idx[j]);
+                ib = ib*db[j] + (db[j] == 1 ? 0 : idx[j]);
+            }
+            out.data[i] = op(a.data[ia], b.data[ib]);
+            for (long j = rank-1; j >= 0; --j)
+            {
+                if (++idx[j] < dout[j])
+                    break;
+                idx[j] = 0;
+            }
+        }
+        return out;
+    }
+
+    onnx_ref_tensor onnx_ref_pool(
+        const impl::onnx_node& n,
+        const onnx_ref_tensor& x,
+        bool is_max
+    )
+    {
+        const long N = x.dims[0], C = x.dims[1], H = x.dims[2], W = x.dims[3];
+        std::vector<int64_t> kernel = {H, W}, strides = {1, 1}, pads = {0, 0, 0, 0};
+        if (n.op_type.find("Global") != 0)
+        {
+            kernel = n.get_ints("kernel_shape");
+            strides = n.get_ints("strides");
+            pads = n.get_ints("pads");
+        }
+        onnx_ref_tensor out;
+        out.dims = {N, C, 1+(H+pads[0]+pads[2]-(long)kernel[0])/(long)strides[0], 1+(W+pads[1]+pads[3]-(long)kernel[1])/(long)strides[1]};
+        out.data.resize(out.size());
+        long o = 0;
+        for (long s = 0; s < N; ++s)
+        for (long c = 0; c < C; ++c)
+        for (long r = 0; r < out.dims[2]; ++r)
+        for (long cc = 0; cc < out.dims[3]; ++cc)
+        {
+            float best = -std::numeric_limits<float>::infinity();
+            double sum = 0;
+            long cnt = 0;
+            for (long y = r*strides[0]-pads[0]; y < r*strides[0]-pads[0]+kernel[0]; ++y)
+            for (long xx = cc*strides[1]-pads[1]; xx < cc*strides[1]-pads[1]+kernel[1]; ++xx)
+            {
+                if (y < 0 || xx < 0 || y >= H || xx >= W)
+                    continue;
+                const float v = x.data[((s*C+c)*H+y)*W+xx];
+                best = std::max(best, v);
+                sum += v;
+                ++cnt;
+            }
+            out.data[o++] = is_max ? best : sum/cnt;
+        }
+        return out;
+    }
+
+    onnx_ref_tensor run_onnx_reference(
+        const impl::onnx_model& model,
+        const onnx_ref_tensor& input
+    )
+    {
+        std::map<std::string, onnx_ref_tensor> values;
+        values[model.inputs.at(0).name] = input;
+        for (auto& t : model.initializers)
+        {
+            onnx_ref_tensor v;
+            v.dims.assign(t.dims.begin(), t.dims.end());
+            if (t.type == impl::onnx_tensor::FLOAT)
+                v.data = t.float_data;
+            else
+                v.data.assign(t.int64_data.begin(), t.int64_data.end());
+            values[t.name] = v;
+        }
+
+        for (auto& n : model.nodes)
+        {
+            const onnx_ref_tensor& x = values.at(n.inputs[0]);
+            onnx_ref_tensor out;
+            auto unary = [&](float (*f)(float)) {
+                out = x;
+                for (auto& v : out.data)
+                    v = f(v);
+            };
+            if (n.op_type == "Identity") out = x;
+            else if (n.op_type == "Relu") unary([](float v) { return std::max(v, 0.0f); });
+            else if (n.op_type == "Sigmoid") unary([](float v) { return 1.0f/(1.0f+std::exp(-v)); });
+            else if (n.op_type == "Tanh") unary([](float v) { return std::tanh(v); });
+            else if (n.op_type == "Erf") unary([](float v) { return std::erf(v); });
+            else if (n.op_type == "Sqrt") unary([](float v) { return std::sqrt(v); });
+            else if (n.op_type == "Add") out = onnx_ref_broadcast(x, values.at(n.inputs[1]), [](float a, float b) { return a+b; });
+            else if (n.op_type == "Sub") out = onnx_ref_broadcast(x, values.at(n.inputs[1]), [](float a, float b) { return a-b; });
+            else if (n.op_type == "Mul") out = onnx_ref_broadcast(x, values.at(n.inputs[1]), [](float a, float b) { return a*b; });
+            else if (n.op_type == "Div") out = onnx_ref_broadcast(x, values.at(n.inputs[1]), [](float a, float b) { return a/b; });
+            else if (n.op_type == "MaxPool" || n.op_type == "GlobalMaxPool") out = onnx_ref_pool(n, x, true);
+            else if (n.op_type == "AveragePool" || n.op_type == "GlobalAveragePool") out = onnx_ref_pool(n, x, false);
+            else if (n.op_type == "Conv")
+            {
+                const onnx_ref_tensor& w = values.at(n.inputs[1]);
+                const auto strides = n.get_ints("strides");
+                const auto pads = n.get_ints("pads");
+                auto dilations = n.get_ints("dilations");
+                if (dilations.empty())
+                    dilations = {1, 1};
+                const long G = n.get_int("group", 1);
+                const long N = x.dims[0], C = x.dims[1], H = x.dims[2], W = x.dims[3];
+                const long F = w.dims[0], KH = w.dims[2], KW = w.dims[3];
+                const long CG = C/G;
+                DLIB_TEST(w.dims[1]*G == C);
+                out.dims = {N, F, 1+(H+pads[0]+pads[2]-(dilations[0]*(KH-1)+1))/(long)strides[0],
+                                  1+(W+pads[1]+pads[3]-(dilations[1]*(KW-1)+1))/(long)strides[1]};
+                out.data.assign(out.size(), 0);
+                long o = 0;
+                for (long s = 0; s < N; ++s)
+                for (long f = 0; f < F; ++f)
+                for (long r = 0; r < out.dims[2]; ++r)
+                for (long c = 0; c < out.dims[3]; ++c)
+                {
+                    double sum = n.inputs.size() > 2 ? values.at(n.inputs[2]).data[f] : 0;
+                    const long g = f/(F/G);
+                    for (long ch = 0; ch < CG; ++ch)
+                    for (long kr = 0; kr < KH; ++kr)
+                    for (long kc = 0; kc < KW; ++kc)
+                    {
+                        const long y = r*strides[0]-pads[0]+kr*dilations[0];
+                        const long xx = c*strides[1]-pads[1]+kc*dilations[1];
+                        if (y < 0 || xx < 0 || y >= H || xx >= W)
+                            continue;
+                        sum += x.data[((s*C+g*CG+ch)*H+y)*W+xx]*w.data[((f*CG+ch)*KH+kr)*KW+kc];
+                    }
+                    out.data[o++] = sum;
+                }
+            }
+            else if (n.op_type == "Flatten")
+            {
+                out = x;
+                out.dims = {x.dims[0], x.size()/x.dims[0]};
+            }
+            else if (n.op_type == "Reshape")
+            {
+                const onnx_ref_tensor& shape = values.at(n.inputs[1]);
+                out = x;
+                out.dims.clear();
+                for (size_t i = 0; i < shape.data.size(); ++i)
+                    out.dims.push_back(shape.data[i] == 0 ? x.dims[i] : (long)shape.data[i]);
+                DLIB_TEST(out.size() == x.size());
+            }
+            else if (n.op_type == "Gemm" || n.op_type == "MatMul")
+            {
+                const onnx_ref_tensor& b = values.at(n.inputs[1]);
+                const bool trans_b = n.get_int("transB", 0) != 0;
+                const double alpha = n.get_float("alpha", 1);
+                const double beta = n.get_float("beta", 1);
+                const long N = x.dims[0], K = x.dims[1], M = trans_b ? b.dims[0] : b.dims[1];
+                DLIB_TEST(b.dims[trans_b ? 1 : 0] == K);
+                out.dims = {N, M};
+                out.data.assign(N*M, 0);
+                for (long i = 0; i < N; ++i)
+                for (long j = 0; j < M; ++j)
+                {
+                    double sum = 0;
+                    for (long kk = 0; kk < K; ++kk)
+                        sum += x.data[i*K+kk]*b.data[trans_b ? j*K+kk : kk*M+j];
+                    out.data[i*M+j] = alpha*sum;
+                    if (n.op_type == "Gemm" && n.inputs.size() > 2)
+                        out.data[i*M+j] += beta*values.at(n.inputs[2]).data[j];
+                }
+            }
+            else if (n.op_type == "BatchNormalization")
+            {
+                const auto& scale = values.at(n.inputs[1]).data;
+                const auto& bias = values.at(n.inputs[2]).data;
+                const auto& mean = values.at(n.inputs[3]).data;
+                const auto& var = values.at(n.inputs[4]).data;
+                const double eps = n.get_float("epsilon", 1e-5f);
+                out = x;
+                const long N = x.dims[0], C = x.dims[1], inner = x.size()/N/C;
+                for (long s = 0; s < N; ++s)
+                for (long c = 0; c < C; ++c)
+                for (long i = 0; i < inner; ++i)
+                {
+                    float& v = out.data[(s*C+c)*inner+i];
+                    v = (v-mean[c])/std::sqrt(var[c]+eps)*scale[c] + bias[c];
+                }
+            }
+            else if (n.op_type == "Softmax")
+            {
+                DLIB_TEST(n.get_int("axis", -1) == 1);
+                out = x;
+                const long N = x.dims[0], C = x.dims[1], inner = x.size()/N/C;
+                for (long s = 0; s < N; ++s)
+                for (long i = 0; i < inner; ++i)
+                {
+                    float m = -std::numeric_limits<float>::infinity();
+                    for (long c = 0; c < C; ++c)
+                        m = std::max(m, x.data[(s*C+c)*inner+i]);
+                    double sum = 0;
+                    for (long c = 0; c < C; ++c)
+                        sum += std::exp(x.data[(s*C+c)*inner+i]-m);
+                    for (long c = 0; c < C; ++c)
+                        out.data[(s*C+c)*inner+i] = std::exp(x.data[(s*C+c)*inner+i]-m)/sum;
+                }
+            }
+            else if (n.op_type == "ReduceMean")
+            {
+                DLIB_TEST(n.get_ints("axes") == std::vector<int64_t>({1, 2, 3}));
+                const long N = x.dims[0], inner = x.size()/N;
+                out.dims = {N, 1, 1, 1};
+                out.data.assign(N, 0);
+                for (long s = 0; s < N; ++s)
+                {
+                    double sum = 0;
+                    for (long i = 0; i < inner; ++i)
+                        sum += x.data[s*inner+i];
+                    out.data[s] = sum/inner;
+                }
+            }
+            else if (n.op_type == "Concat")
+            {
+                DLIB_TEST(n.get_int("axis", -1) == 1);
+                out.dims = x.dims;
+                out.dims[1] = 0;
+                for (auto& name : n.inputs)
+                    out.dims[1] += values.at(name).dims[1];
+                out.data.resize(out.size());
+                long o = 0;
+                for (long s = 0; s < x.dims[0]; ++s)
+                {
+                    for (auto& name : n.inputs)
+                    {
+                        const onnx_ref_tensor& t = values.at(name);
+                        const long chunk = t.size()/t.dims[0];
+                        std::copy(t.data.begin()+s*chunk, t.data.begin()+(s+1)*chunk, out.data.begin()+o);
+                        o += chunk;
+                    }
+                }
+            }
+            else if (n.op_type == "Resize")
+            {
+                DLIB_TEST(n.get_string("mode", "") == "linear");
+                DLIB_TEST(n.get_string("coordinate_transformation_mode", "") == "align_corners");
+                const onnx_ref_tensor& scales = values.at(n.inputs[2]);
+                const long N = x.dims[0], C = x.dims[1], H = x.dims[2], W = x.dims[3];
+                const long OH = std::floor(H*scales.data[2]), OW = std::floor(W*scales.data[3]);
+                out.dims = {N, C, OH, OW};
+                out.data.resize(out.size());
+                long o = 0;
+                for (long s = 0; s < N*C; ++s)
+                for (long r = 0; r < OH; ++r)
+                for (long c = 0; c < OW; ++c)
+                {
+                    const double y = OH > 1 ? r*(H-1.0)/(OH-1.0) : 0;
+                    const double xx = OW > 1 ? c*(W-1.0)/(OW-1.0) : 0;
+                    const long y0 = std::floor(y), x0 = std::floor(xx);
+                    const long y1 = std::min(y0+1, H-1), x1 = std::min(x0+1, W-1);
+                    const double fy = y-y0, fx = xx-x0;
+                    const float* p = &x.data[s*H*W];
+                    out.data[o++] = (1-fy)*((1-fx)*p[y0*W+x0] + fx*p[y0*W+x1]) +
+                                    fy*((1-fx)*p[y1*W+x0] + fx*p[y1*W+x1]);
+                }
+            }
+            else
+            {
+                DLIB_TEST_MSG(false, "unexpected ONNX operator: " << n.op_type);
+            }
+            values[n.outputs[0]] = out;
+        }
+        return values.at(model.outputs.at(0).name);
+    }
+
+
+    void test_onnx_export()
+    {
+        print_spinner();
+        using net_type = loss_multiclass_log<
+                            fc<5,relu<bn_fc<fc<12,softmax<layer_norm<
+                            concat2<tag3,tag4,
+                            tag4<avg_pool<3,3,1,1,skip2<
+                            tag3<upsample<2,silu<con<4,1,1,1,1,max_pool<2,2,2,2,
+                            tag2<add_prev1<gelu<affine<con<6,3,3,1,1,
+                            tag1<relu<bn_con<con<6,3,3,1,1,
+                            input_rgb_image>>>>>>>>>>>>>>>>>>>>>>>>>;
+        net_type net;
+
+        dlib::rand prnd(0);
+        auto random_images = [&]() {
+            std::vector<matrix<rgb_pixel>> images(4, matrix<rgb_pixel>(8, 8));
+            for (auto& img : images)
+                for (auto& p : img)
+                    p = rgb_pixel(prnd.get_random_8bit_number(), prnd.get_random_8bit_number(), prnd.get_random_8bit_number());
+            return images;
+        };
+
+        // run a few mini-batches through the network so that the bn_ layers have
+        // non-trivial running statistics.
+        resizable_tensor x;
+        for (int i = 0; i < 5; ++i)
+        {
+            const auto images = random_images();
+            net.to_tensor(images.begin(), images.end(), x);
+            net.subnet().forward(x);
+        }
+
+        std::ostringstream sout;
+        net_to_onnx(net, 3, 8, 8, sout);
+        const impl::onnx_model model = impl::parse_onnx_model(sout.str());
+
+        DLIB_TEST(model.opset_version == 13);
+        DLIB_TEST(model.producer_name == "dlib");
+        DLIB_TEST(model.inputs.size() == 1);
+        DLIB_TEST(model.inputs[0].name == "input");
+        DLIB_TEST(model.inputs[0].dims == std::vector<int64_t>({-1, 3, 8, 8}));
+        DLIB_TEST(model.inputs[0].dim_params[0] == "N");
+        DLIB_TEST(model.outputs.size() == 1);
+        DLIB_TEST(model.outputs[0].name == "output");
+        DLIB_TEST(model.outputs[0].dims == std::vector<int64_t>({-1, 5, 1, 1}));
+
+        // bn_ layers only use their running statistics when given a single sample, so
+        // compare each sample separately.
+        const auto images = random_images();
+        for (const auto& img : images)
+        {
+            resizable_tensor xi;
+            net.to_tensor(&img, &img+1, xi);
+            const tensor& expected = net.subnet().forward(xi);
+
+            onnx_ref_tensor in;
+            in.dims = {1, xi.k(), xi.nr(), xi.nc()};
+            in.data.assign(xi.begin(), xi.end());
+            const onnx_ref_tensor out = run_onnx_reference(model, in);
+            DLIB_TEST(out.dims == std::vector<long>({1, 5, 1, 1}));
+            DLIB_TEST(out.data.size() == expected.size());
+            for (size_t j = 0; j < expected.size(); ++j)
+                DLIB_TEST_MSG(std::abs(out.data[j] - expected.host()[j]) < 1e-4,
+                    out.data[j] << " vs " << expected.host()[j]);
+        }
+
+        // Layers without an ONNX equivalent are reported.
+        using unsupported_type = fc<2,l2normalize<fc<3,input<matrix<float>>>>>;
+        unsupported_type unsupported;
+        std::vector<matrix<float>> samples(2, matrix<float>(3, 3));
+        for (auto& m : samples)
+            m = matrix_cast<float>(gaussian_randm(3, 3, prnd.get_random_32bit_number()));
+        resizable_tensor y;
+        unsupported.to_tensor(samples.begin(), samples.end(), y);
+        unsupported.forward(y);
+        bool threw = false;
+        try
+        {
+            std::ostringstream sout2;
+            net_to_onnx(unsupported, 1, 3, 3, sout2);
+        }
+        catch (dlib::error& e)
+        {
+            threw = true;
+            DLIB_TEST(std::string(e.what()).find("l2normalize") != std::string::npos);
+        }
+        DLIB_TEST(threw);
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    impl::onnx_tensor make_onnx_test_tensor(
+        const std::string& name,
+        const std::vector<int64_t>& dims,
+        dlib::rand& rnd,
+        float offset = 0
+    )
+    {
+        impl::onnx_tensor t;
+        t.name = name;
+        t.dims = dims;
+        t.float_data.resize(t.size());
+        for (auto& v : t.float_data)
+            v = offset + rnd.get_random_gaussian()*0.5;
+        return t;
+    }
+
+    impl::onnx_model make_pytorch_style_onnx_model(
+        dlib::rand& rnd
+    )
+    {
+        // The kind of graph torch.onnx.export() produces for
+        // Linear(Flatten(ReLU(BatchNorm2d(Conv2d(1,4,3,padding=1,bias=False)))))
+        impl::onnx_model model;
+        model.producer_name = "pytorch";
+        impl::onnx_value_info input;
+        input.name = "x";
+        input.has_shape = true;
+        input.dims = {1, 1, 6, 6};
+        input.dim_params = {"", "", "", ""};
+        model.inputs.push_back(input);
+
+        model.initializers.push_back(make_onnx_test_tensor("conv.weight", {4, 1, 3, 3}, rnd));
+        model.initializers.push_back(make_onnx_test_tensor("bn.weight", {4}, rnd, 1));
+        model.initializers.push_back(make_onnx_test_tensor("bn.bias", {4}, rnd));
+        model.initializers.push_back(make_onnx_test_tensor("bn.running_mean", {4}, rnd));
+        model.initializers.push_back(make_onnx_test_tensor("bn.running_var", {4}, rnd, 2));
+        model.initializers.push_back(make_onnx_test_tensor("fc.weight", {3, 4*6*6}, rnd));
+        model.initializers.push_back(make_onnx_test_tensor("fc.bias", {3}, rnd));
+
+        auto add_node = [&](const std::string& op_type, const std::string& name, const std::vector<std::string>& inputs) -> impl::onnx_node& {
+            impl::onnx_node n;
+            n.op_type = op_type;
+            n.name = name;
+            n.inputs = inputs;
+            n.outputs = {name};
+            model.nodes.push_back(n);
+            return model.nodes.back();
+        };
+        auto add_attribute = [](impl::onnx_node& n, const std::string& name, int type, float f, const std::vector<int64_t>& ints) {
+            impl::onnx_attribute a;
+            a.name = name;
+            a.type = type;
+            a.f = f;
+            a.i = ints.empty() ? 0 : ints[0];
+            a.ints = ints;
+            n.attributes.push_back(a);
+        };
+
+        auto& conv = add_node("Conv", "/conv/Conv", {"x", "conv.weight"});
+        add_attribute(conv, "kernel_shape", impl::onnx_attribute::INTS, 0, {3, 3});
+        add_attribute(conv, "pads", impl::onnx_attribute::INTS, 0, {1, 1, 1, 1});
+        add_attribute(conv, "strides", impl::onnx_attribute::INTS, 0, {1, 1});
+        auto& bn = add_node("BatchNormalization", "/bn/BatchNormalization",
+            {"/conv/Conv", "bn.weight", "bn.bias", "bn.running_mean", "bn.running_var"});
+        add_attribute(bn, "epsilon", impl::onnx_attribute::FLOAT, 1e-3f, {});
+        add_node("Relu", "/relu/Relu", {"/bn/BatchNormalization"});
+        auto& flatten = add_node("Flatten", "/flatten/Flatten", {"/relu/Relu"});
+        add_attribute(flatten, "axis", impl::onnx_attribute::INT, 0, {1});
+        auto& gemm = add_node("Gemm", "/fc/Gemm", {"/flatten/Flatten", "fc.weight", "fc.bias"});
+        add_attribute(gemm, "transB", impl::onnx_attribute::INT, 0, {1});
+
+        impl::onnx_value_info output;
+        output.name = "/fc/Gemm";
+        model.outputs.push_back(output);
+        return model;
+    }
+
+    template <typename net_type>
+    void check_onnx_import_matches_reference(
+        net_type& net,
+        const impl::onnx_model& model,
+        dlib::rand& rnd
+    )
+    {
+        for (int i = 0; i < 3; ++i)
+        {
+            matrix<float> img = matrix_cast<float>(gaussian_randm(6, 6, rnd.get_random_32bit_number()));
+            resizable_tensor x;
+            net.to_tensor(&img, &img+1, x);
+            const tensor& out = net.forward(x);
+
+            onnx_ref_tensor in;
+            in.dims = {1, 1, 6, 6};
+            in.data.assign(x.begin(), x.end());
+            const onnx_ref_tensor expected = run_onnx_reference(model, in);
+            DLIB_TEST(expected.size() == (long)out.size());
+            for (size_t j = 0; j < out.size(); ++j)
+                DLIB_TEST_MSG(std::abs(expected.data[j] - out.host()[j]) < 1e-4,
+                    expected.data[j] << " vs " << out.host()[j]);
+        }
+    }
+
+    template <typename net_type>
+    std::string onnx_import_error_message(
+        net_type& net,
+        const std::string& data
+    )
+    {
+        // returns the message of the onnx_import_error thrown while loading data into net
+        // and checks that net wasn't modified.
+        std::ostringstream before;
+        serialize(net, before);
+        try
+        {
+            std::istringstream sin(data);
+            onnx_to_net(net, sin);
+        }
+        catch (onnx_import_error& e)
+        {
+            std::ostringstream after;
+            serialize(net, after);
+            DLIB_TEST(before.str() == after.str());
+            return e.what();
+        }
+        DLIB_TEST_MSG(false, "onnx_to_net() should have thrown");
+        return "";
+    }
+
+    void test_onnx_import()
+    {
+        print_spinner();
+        dlib::rand rnd(1);
+
+        // Round trip a network through net_to_onnx() and onnx_to_net().
+        {
+            using net_type = loss_multiclass_log<fc<4,relu<bn_fc<fc<6,layer_norm<
+                                add_prev1<relu<affine<con<5,3,3,1,1,
+                                tag1<relu<bn_con<con<5,3,3,1,1,
+                                input_rgb_image>>>>>>>>>>>>>>;
+            std::vector<matrix<rgb_pixel>> images(4, matrix<rgb_pixel>(6, 6));
+            for (auto& img : images)
+                for (auto& p : img)
+                    p = rgb_pixel(rnd.get_random_8bit_number(), rnd.get_random_8bit_number(), rnd.get_random_8bit_number());
+
+            net_type net;
+            resizable_tensor x;
+            for (int i = 0; i < 3; ++i)
+            {
+                net.to_tensor(images.begin(), images.end(), x);
+                net.subnet().forward(x);
+            }
+            std::ostringstream sout;
+            net_to_onnx(net, 3, 6, 6, sout);
+
+            net_type net2;
+            net2(images[0]);
+            std::istringstream sin(sout.str());
+            onnx_to_net(net2, sin);
+            for (const auto& img : images)
+            {
+                net.to_tensor(&img, &img+1, x);
+                const tensor& out1 = net.subnet().forward(x);
+                const tensor& out2 = net2.subnet().forward(x);
+                DLIB_TEST(max(abs(mat(out1) - mat(out2))) < 1e-4);
+            }
+        }
+
+        // Load a model exported by another framework, into both bn_ and affine_ versions
+        // of the same network.
+        const impl::onnx_model model = make_pytorch_style_onnx_model(rnd);
+        const std::string data = impl::serialize_onnx_model(model);
+        {
+            using net_type = fc<3,relu<bn_con<con<4,3,3,1,1,input<matrix<float>>>>>>;
+            net_type net;
+            net(zeros_matrix<float>(6, 6));
+            std::istringstream sin(data);
+            onnx_to_net(net, sin);
+            check_onnx_import_matches_reference(net, model, rnd);
+        }
+        {
+            using net_type = fc<3,relu<affine<con<4,3,3,1,1,input<matrix<float>>>>>>;
+            net_type net;
+            layer<2>(net).layer_details() = affine_(CONV_MODE);
+            net(zeros_matrix<float>(6, 6));
+            std::istringstream sin(data);
+            onnx_to_net(net, sin);
+            check_onnx_import_matches_reference(net, model, rnd);
+        }
+
+        // Mismatches are reported with the offending layer and node.
+        {
+            fc<3,relu<bn_con<con<5,3,3,1,1,input<matrix<float>>>>>> net;
+            net(zeros_matrix<float>(6, 6));
+            const std::string msg = onnx_import_error_message(net, data);
+            DLIB_TEST_MSG(msg.find("layer 3 (con)") != std::string::npos, msg);
+            DLIB_TEST_MSG(msg.find("[5,1,3,3]") != std::string::npos, msg);
+            DLIB_TEST_MSG(msg.find("'/conv/Conv' (Conv) has weights with shape [4,1,3,3]") != std::string::npos, msg);
+        }
+        {
+            fc<3,relu<bn_con<con<4,3,3,2,2,input<matrix<float>>>>>> net;
+            net(zeros_matrix<float>(6, 6));
+            const std::string msg = onnx_import_error_message(net, data);
+            DLIB_TEST_MSG(msg.find("strides=[2,2]") != std::string::npos, msg);
+        }
+        {
+            fc<3,relu<fc<3,relu<bn_con<con<4,3,3,1,1,input<matrix<float>>>>>>>> net;
+            net(zeros_matrix<float>(6, 6));
+            const std::string msg = onnx_import_error_message(net, data);
+            DLIB_TEST_MSG(msg.find("layer 0 (fc)") != std::string::npos, msg);
+            DLIB_TEST_MSG(msg.find("only has 3 nodes with parameters") != std::string::npos, msg);
+        }
+        {
+            fc<3,relu<con<4,3,3,1,1,input<matrix<float>>>>> net;
+            net(zeros_matrix<float>(6, 6));
+            const std::string msg = onnx_import_error_message(net, data);
+            DLIB_TEST_MSG(msg.find("paired with the ONNX node '/bn/BatchNormalization'") != std::string::npos, msg);
+        }
+        {
+            relu<bn_con<con<4,3,3,1,1,input<matrix<float>>>>> net;
+            net(zeros_matrix<float>(6, 6));
+            const std::string msg = onnx_import_error_message(net, data);
+            DLIB_TEST_MSG(msg.find("first unused node is the ONNX node '/fc/Gemm'") != std::string::npos, msg);
+        }
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    void test_dilated_and_grouped_conv()
+    {
+        // A grouped convolution is the same as a regular convolution whose filters are
+        // zero outside the channels of their group.  Similarly, a dilated convolution is
+        // the same as a regular convolution with a larger filter that has zeros inserted
+        // between the filter taps.  So check tensor_conv against those equivalents.
+        dlib::rand prnd;
+        tt::tensor_rand rnd;
+        auto idx = [](const tensor& t, long n, long k, long r, long c) {
+            return ((n*t.k() + k)*t.nr() + r)*t.nc() + c;
+        };
+        for (int iter = 0; iter < 50; ++iter)
+        {
+            print_spinner();
+            const long groups = prnd.get_random_32bit_number()%3+1;
+            const int dilation_y = prnd.get_random_32bit_number()%3+1;
+            const int dilation_x = prnd.get_random_32bit_number()%3+1;
+            resizable_tensor data(prnd.get_random_32bit_number()%3+1,
+                groups*(prnd.get_random_32bit_number()%3+1),
+                prnd.get_random_32bit_number()%10+5,
+                prnd.get_random_32bit_number()%10+5);
+            resizable_tensor filters(groups*(prnd.get_random_32bit_number()%3+1),
+                data.k()/groups,
+                prnd.get_random_32bit_number()%3+1,
+                prnd.get_random_32bit_number()%3+1);
+            rnd.fill_uniform(data);
+            rnd.fill_uniform(filters);
+
+            const long filters_per_group = filters.num_samples()/groups;
+            resizable_tensor big_filters(filters.num_samples(), data.k(),
+                dilation_y*(filters.nr()-1)+1, dilation_x*(filters.nc()-1)+1);
+            big_filters = 0;
+            for (long n = 0; n < filters.num_samples(); ++n)
+            for (long k = 0; k < filters.k(); ++k)
+            for (long r = 0; r < filters.nr(); ++r)
+            for (long c = 0; c < filters.nc(); ++c)
+            {
+                const long g = n/filters_per_group;
+                big_filters.host()[idx(big_filters, n, g*filters.k()+k, r*dilation_y, c*dilation_x)] =
+                    filters.host()[idx(filters, n, k, r, c)];
+            }
+
+            const int stride_y = prnd.get_random_32bit_number()%2+1;
+            const int stride_x = prnd.get_random_32bit_number()%2+1;
+            int padding_y = prnd.get_random_32bit_number()%(big_filters.nr()/2+1);
+            int padding_x = prnd.get_random_32bit_number()%(big_filters.nc()/2+1);
+            if (!(big_filters.nr() <= data.nr() + 2*padding_y))
+                padding_y = (big_filters.nr()-data.nr()+1)/2;
+            if (!(big_filters.nc() <= data.nc() + 2*padding_x))
+                padding_x = (big_filters.nc()-data.nc()+1)/2;
+
+            tt::tensor_conv conv, big_conv;
+            conv.setup(data, filters, stride_y, stride_x, padding_y, padding_x, dilation_y, dilation_x, groups);
+            big_conv.setup(data, big_filters, stride_y, stride_x, padding_y, padding_x);
+
+            resizable_tensor output, big_output;
+            conv(false, output, data, filters);
+            big_conv(false, big_output, data, big_filters);
+            DLIB_TEST(have_same_dimensions(output, big_output));
+            DLIB_TEST_MSG(max(abs(mat(output)-mat(big_output))) < 1e-4, max(abs(mat(output)-mat(big_output))));
+            conv(true, output, data, filters);
+            big_conv(true, big_output, data, big_filters);
+            DLIB_TEST_MSG(max(abs(mat(output)-mat(big_output))) < 1e-4, max(abs(mat(output)-mat(big_output))));
+
+            resizable_tensor gi;
+            gi.copy_size(output);
+            rnd.fill_uniform(gi);
+
+            resizable_tensor data_gradient, big_data_gradient;
+            data_gradient.copy_size(data);
+            big_data_gradient.copy_size(data);
+            data_gradient = 1;
+            big_data_gradient = 1;
+            conv.get_gradient_for_data(true, gi, filters, data_gradient);
+            big_conv.get_gradient_for_data(true, gi, big_filters, big_data_gradient);
+            DLIB_TEST(max(abs(mat(data_gradient)-mat(big_data_gradient))) < 1e-4);
+            conv.get_gradient_for_data(false, gi, filters, data_gradient);
+            big_conv.get_gradient_for_data(false, gi, big_filters, big_data_gradient);
+            DLIB_TEST(max(abs(mat(data_gradient)-mat(big_data_gradient))) < 1e-4);
+
+            resizable_tensor filter_gradient, big_filter_gradient;
+            filter_gradient.copy_size(filters);
+            big_filter_gradient.copy_size(big_filters);
+            filter_gradient = 1;
+            big_filter_gradient = 1;
+            for (bool add_to_output : {false, true})
+            {
+                conv.get_gradient_for_filters(add_to_output, gi, data, filter_gradient);
+                big_conv.get_gradient_for_filters(add_to_output, gi, data, big_filter_gradient);
+                double err = 0;
+                for (long n = 0; n < filters.num_samples(); ++n)
+                for (long k = 0; k < filters.k(); ++k)
+                for (long r = 0; r < filters.nr(); ++r)
+                for (long c = 0; c < filters.nc(); ++c)
+                {
+                    const long g = n/filters_per_group;
+                    err = std::max<double>(err, std::abs(filter_gradient.host()[idx(filters, n, k, r, c)] -
+                        big_filter_gradient.host()[idx(big_filters, n, g*filters.k()+k, r*dilation_y, c*dilation_x)]));
+                }
+                DLIB_TEST_MSG(err < 1e-3, err);
+            }
+        }
+
+        // A grouped cont_ matches a regular cont_ with the corresponding zeroed filters.
+        {
+            print_spinner();
+            using grouped_net = add_layer<cont_<4,3,3,2,2,0,0,1,1,2>, con<2,1,1,1,1,input<matrix<float>>>>;
+            using full_net = cont<4,3,3,2,2,con<2,1,1,1,1,input<matrix<float>>>>;
+            grouped_net net1;
+            full_net net2;
+            const matrix<float> img = matrix_cast<float>(gaussian_randm(5, 6, 1));
+            net1(img);
+            net2(img);
+            layer<1>(net2).layer_details() = layer<1>(net1).layer_details();
+            const tensor& p1 = layer<0>(net1).layer_details().get_layer_params();
+            tensor& p2 = layer<0>(net2).layer_details().get_layer_params();
+            DLIB_TEST(p1.size() == 2*2*3*3+4);
+            DLIB_TEST(p2.size() == 2*4*3*3+4);
+            p2 = 0;
+            for (long i = 0; i < 2; ++i)
+            for (long j = 0; j < 2; ++j)
+            for (long r = 0; r < 9; ++r)
+                p2.host()[(i*4 + i*2+j)*9 + r] = p1.host()[(i*2+j)*9 + r];
+            for (long i = 0; i < 4; ++i)
+                p2.host()[2*4*9+i] = p1.host()[2*2*9+i];
+
+            resizable_tensor x;
+            net1.to_tensor(&img, &img+1, x);
+            const tensor& out1 = net1.forward(x);
+            const tensor& out2 = net2.forward(x);
+            DLIB_TEST(have_same_dimensions(out1, out2));
+            DLIB_TEST(max(abs(mat(out1)-mat(out2))) < 1e-5);
+        }
+
+        // Dilation and groups are exported to ONNX and loaded back in.
+        {
+            print_spinner();
+            using net_type = fc<3,grouped_con<4,3,3,1,1,2,relu<dilated_con<4,3,3,1,1,2,2,input<matrix<float>>>>>>;
+            net_type net;
+            const matrix<float> img = matrix_cast<float>(gaussian_randm(9, 9, 2));
+            net(img);
+            DLIB_TEST(layer<3>(net).layer_details().padding_y() == 2);
+            DLIB_TEST(layer<1>(net).layer_details().groups() == 2);
+
+            std::ostringstream sout;
+            net_to_onnx(net, 1, 9, 9, sout);
+            const impl::onnx_model model = impl::parse_onnx_model(sout.str());
+            resizable_tensor x;
+            net.to_tensor(&img, &img+1, x);
+            const tensor& expected = net.forward(x);
+            onnx_ref_tensor in;
+            in.dims = {1, 1, 9, 9};
+            in.data.assign(x.begin(), x.end());
+            const onnx_ref_tensor out = run_onnx_reference(model, in);
+            DLIB_TEST(out.data.size() == expected.size());
+            for (size_t j = 0; j < expected.size(); ++j)
+                DLIB_TEST_MSG(std::abs(out.data[j] - expected.host()[j]) < 1e-4, out.data[j] << " vs " << expected.host()[j]);
+
+            net_type net2;
+            net2(img);
+            std::istringstream sin(sout.str());
+            onnx_to_net(net2, sin);
+            DLIB_TEST(max(abs(mat(net2.forward(x)) - mat(expected))) < 1e-5);
+
+            fc<3,con<4,3,3,1,1,relu<dilated_con<4,3,3,1,1,2,2,input<matrix<float>>>>>> ungrouped;
+            ungrouped(img);
+            const std::string msg = onnx_import_error_message(ungrouped, sout.str());
+            DLIB_TEST_MSG(msg.find("[4,2,3,3]") != std::string::npos, msg);
+        }
+
+        // Serialization keeps the new settings and still reads the older format.
+        {
+            print_spinner();
+            con_<4,3,3,1,1,2,1,2,1,2> l;
+            std::ostringstream sout;
+            sout << l;
+            DLIB_TEST(sout.str().find("dilation_y=2, dilation_x=1, groups=2") != std::string::npos);
+            std::ostringstream sout2;
+            sout2 << con_<4,3,3,1,1>();
+            DLIB_TEST(sout2.str().find("dilation") == std::string::npos);
+            DLIB_TEST(sout2.str().find("groups") == std::string::npos);
+
+            resizable_tensor params(3*2*3*3+3);
+            rnd.fill_uniform(params);
+            std::ostringstream out;
+            serialize("con_6", out);
+            serialize(params, out);
+            serialize(3L, out);
+            serialize(3L, out);
+            serialize(3L, out);
+            serialize(1, out);
+            serialize(1, out);
+            serialize(1, out);
+            serialize(1, out);
+            serialize(alias_tensor(3,2,3,3), out);
+            serialize(alias_tensor(1,3), out);
+            serialize(1.0, out);
+            serialize(1.0, out);
+            serialize(1.0, out);
+            serialize(0.0, out);
+            serialize(true, out);
+            serialize(false, out);
+
+            con_<3,3,3,1,1> old;
+            std::istringstream sin(out.str());
+            deserialize(old, sin);
+            DLIB_TEST(old.dilation_y() == 1 && old.dilation_x() == 1 && old.groups() == 1);
+            DLIB_TEST(mat(old.get_layer_params()) == mat(params));
+
+            std::ostringstream out2;
+            serialize(old, out2);
+            con_<3,3,3,1,1> copy;
+            std::istringstream sin2(out2.str());
+            deserialize(copy, sin2);
+            DLIB_TEST(mat(copy.get_layer_params()) == mat(params));
+
+            bool threw = false;
+            try
+            {
+                con_<3,3,3,1,1,1,1,2,2> dilated;
+                std::istringstream sin3(out.str());
+                deserialize(dilated, sin3);
+            }
+            catch (serialization_error&)
+            {
+                threw = true;
+            }
+            DLIB_TEST(threw);
+        }
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    template <typename net_type>
+    void test_recurrent_chunking()
+    {
+        // Running a sequence through a stateful network in two chunks gives the same
+        // outputs as running the whole sequence at once.
+        net_type net;
+        const matrix<float> seq = matrix_cast<float>(gaussian_randm(8, 3, 0));
+        const matrix<float> chunk1 = rowm(seq, range(0,3));
+        const matrix<float> chunk2 = rowm(seq, range(4,7));
+        const std::vector<matrix<float>> whole = {seq, 2*seq};
+        const std::vector<matrix<float>> first = {chunk1, 2*chunk1};
+        const std::vector<matrix<float>> second = {chunk2, 2*chunk2};
+
+        resizable_tensor x;
+        net.to_tensor(whole.begin(), whole.end(), x);
+        const resizable_tensor expected = net.forward(x);
+        DLIB_TEST(expected.num_samples() == 2 && expected.nr() == 8 && expected.nc() == 5);
+
+        set_recurrent_stateful(net, true);
+        net.to_tensor(first.begin(), first.end(), x);
+        const resizable_tensor out1 = net.forward(x);
+        DLIB_TEST(layer<0>(net).layer_details().get_state().num_samples() == 2);
+        net.to_tensor(second.begin(), second.end(), x);
+        const resizable_tensor out2 = net.forward(x);
+
+        for (long n = 0; n < 2; ++n)
+        {
+            DLIB_TEST(max(abs(rowm(image_plane(expected,n), range(0,3)) - image_plane(out1,n))) < 1e-5);
+            DLIB_TEST(max(abs(rowm(image_plane(expected,n), range(4,7)) - image_plane(out2,n))) < 1e-5);
+        }
+
+        // The state is serialized with the network, so a copy continues where the
+        // original left off.
+        std::ostringstream sout;
+        serialize(net, sout);
+        net_type net2;
+        std::istringstream sin(sout.str());
+        deserialize(net2, sin);
+        DLIB_TEST(layer<0>(net2).layer_details().is_stateful());
+        DLIB_TEST(max(abs(mat(layer<0>(net2).layer_details().get_state()) - mat(layer<0>(net).layer_details().get_state()))) == 0);
+        net.to_tensor(first.begin(), first.end(), x);
+        DLIB_TEST(max(abs(mat(net.forward(x)) - mat(net2.forward(x)))) < 1e-6);
+
+        // After a reset we are back to processing the start of a sequence.
+        reset_recurrent_states(net);
+        DLIB_TEST(layer<0>(net).layer_details().get_state().size() == 0);
+        DLIB_TEST(max(abs(mat(net.forward(x)) - mat(out1))) < 1e-6);
+    }
+
+    void test_recurrent_layers()
+    {
+        print_spinner();
+        test_recurrent_chunking<lstm<5,input<matrix<float>>>>();
+        print_spinner();
+        test_recurrent_chunking<gru<5,input<matrix<float>>>>();
+
+        // The output of lstm_ and gru_ at each time step only depends on the inputs up to
+        // that time step.
+        {
+            print_spinner();
+            lstm<4,input<matrix<float>>> net;
+            matrix<float> seq = matrix_cast<float>(gaussian_randm(6, 2, 1));
+            resizable_tensor x;
+            net.to_tensor(&seq, &seq+1, x);
+            const matrix<float> out1 = image_plane(net.forward(x));
+            seq(5,0) += 1;
+            net.to_tensor(&seq, &seq+1, x);
+            const matrix<float> out2 = image_plane(net.forward(x));
+            DLIB_TEST(max(abs(rowm(out1,range(0,4)) - rowm(out2,range(0,4)))) == 0);
+            DLIB_TEST(max(abs(rowm(out1,5) - rowm(out2,5))) > 0);
+        }
+
+        // Train a network that has to remember the first input of each sequence for all of
+        // the later time steps.  Each sequence is split into two chunks, so the network can
+        // only get the second chunk right by carrying its state over from the first chunk.
+        {
+            print_spinner();
+            using net_type = loss_mean_squared_per_pixel<lstm<1,lstm<10,input<matrix<float>>>>>;
+            net_type net;
+            dnn_trainer<net_type,adam> trainer(net, adam(0,0.9,0.999));
+            trainer.set_learning_rate(0.01);
+            trainer.set_truncated_bptt_chunks(2);
+            DLIB_TEST(trainer.get_truncated_bptt_chunks() == 2);
+            DLIB_TEST(layer<1>(net).layer_details().is_stateful());
+
+            dlib::rand rnd;
+            const long batch_size = 16;
+            std::vector<matrix<float>> inputs(batch_size), labels(batch_size);
+            std::vector<float> firsts(batch_size);
+            for (int iter = 0; iter < 800; ++iter)
+            {
+                for (long i = 0; i < batch_size; ++i)
+                    firsts[i] = rnd.get_random_float()-0.5;
+                for (long chunk = 0; chunk < 2; ++chunk)
+                {
+                    for (long i = 0; i < batch_size; ++i)
+                    {
+                        inputs[i] = zeros_matrix<float>(4,1);
+                        if (chunk == 0)
+                            inputs[i](0) = firsts[i];
+                        labels[i] = uniform_matrix<float>(4,1,firsts[i]);
+                    }
+                    trainer.train_one_step(inputs, labels);
+                }
+            }
+            trainer.get_net();
+            DLIB_TEST(trainer.get_train_one_step_calls() == 1600);
+
+            // Now check that the second chunk is predicted using the state carried over
+            // from the first.
+            reset_recurrent_states(net);
+            double err = 0;
+            for (long i = 0; i < batch_size; ++i)
+            {
+                firsts[i] = rnd.get_random_float()-0.5;
+                inputs[i] = zeros_matrix<float>(4,1);
+                inputs[i](0) = firsts[i];
+            }
+            net(inputs);
+            for (long i = 0; i < batch_size; ++i)
+                inputs[i] = zeros_matrix<float>(4,1);
+            const std::vector<matrix<float>> preds = net(inputs);
+            for (long i = 0; i < batch_size; ++i)
+                err += mean(squared(preds[i] - firsts[i]));
+            err /= batch_size;
+            DLIB_TEST_MSG(err < 0.01, err);
+        }
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    template <typename layer_type>
+    matrix<float> reference_multihead_attention (
+        const layer_type& l,
+        const matrix<float>& x
+    )
+    {
+        // Straightforward implementation of multi-head attention for one sequence.
+        const long d = x.nc();
+        const long num_heads = l.get_num_heads();
+        const long dh = d/num_heads;
+        const tensor& p = l.get_layer_params();
+        const matrix<float> wi = mat(p.host(), d, 3*d);
+        const matrix<float> bi = mat(p.host()+d*3*d, 1, 3*d);
+        const matrix<float> wo = mat(p.host()+d*3*d+3*d, d, d);
+        const matrix<float> bo = mat(p.host()+d*3*d+3*d+d*d, 1, d);
+
+        const matrix<float> qkv = x*wi + ones_matrix<float>(x.nr(),1)*bi;
+        matrix<float> context(x.nr(), d);
+        for (long h = 0; h < num_heads; ++h)
+        {
+            const matrix<float> q = colm(qkv, range(h*dh, (h+1)*dh-1));
+            const matrix<float> k = colm(qkv, range(d+h*dh, d+(h+1)*dh-1));
+            const matrix<float> v = colm(qkv, range(2*d+h*dh, 2*d+(h+1)*dh-1));
+            matrix<float> scores = q*trans(k)/std::sqrt(static_cast<float>(dh));
+            if (l.uses_causal_mask())
+            {
+                for (long r = 0; r < scores.nr(); ++r)
+                    for (long c = r+1; c < scores.nc(); ++c)
+                        scores(r,c) = -std::numeric_limits<float>::infinity();
+            }
+            for (long r = 0; r < scores.nr(); ++r)
+            {
+                const matrix<float> e = exp(rowm(scores,r) - max(rowm(scores,r)));
+                set_rowm(scores,r) = e/sum(e);
+            }
+            set_colm(context, range(h*dh, (h+1)*dh-1)) = scores*v;
+        }
+        return context*wo + ones_matrix<float>(x.nr(),1)*bo;
+    }
+
+    template <typename net_type>
+    void test_multihead_attention_against_reference()
+    {
+        net_type net;
+        std::vector<matrix<float>> x = {
+            matrix_cast<float>(gaussian_randm(7, 8, 0)),
+            matrix_cast<float>(gaussian_randm(7, 8, 1))
+        };
+        resizable_tensor input;
+        net.to_tensor(x.begin(), x.end(), input);
+        const tensor& out = net.forward(input);
+        DLIB_TEST(have_same_dimensions(out, input));
+        for (size_t n = 0; n < x.size(); ++n)
+        {
+            const matrix<float> expected = reference_multihead_attention(layer<0>(net).layer_details(), x[n]);
+            DLIB_TEST_MSG(max(abs(image_plane(out,n) - expected)) < 1e-4, max(abs(image_plane(out,n) - expected)));
+        }
+    }
+
+    void test_multihead_attention()
+    {
+        print_spinner();
+        test_multihead_attention_against_reference<multihead_attention<1,input<matrix<float>>>>();
+        print_spinner();
+        test_multihead_attention_against_reference<multihead_attention<4,input<matrix<float>>>>();
+        print_spinner();
+        test_multihead_attention_against_reference<causal_multihead_attention<2,input<matrix<float>>>>();
+
+        // With a causal mask the output at each time step only depends on the inputs up
+        // to that time step.
+        {
+            print_spinner();
+            causal_multihead_attention<2,input<matrix<float>>> net;
+            matrix<float> x = matrix_cast<float>(gaussian_randm(6, 4, 2));
+            const matrix<float> out1 = image_plane(net(x) , 0, 0);
+            x(5,1) += 1;
+            const matrix<float> out2 = image_plane(net(x), 0, 0);
+            DLIB_TEST(max(abs(rowm(out1,range(0,4)) - rowm(out2,range(0,4)))) < 1e-6);
+            DLIB_TEST(max(abs(rowm(out1,5) - rowm(out2,5))) > 1e-3);
+        }
+
+        // Check the gradients with attention dropout turned on.  Copies of a layer draw
+        // the same dropout masks, so we can compare against central differences computed
+        // with copies of the layer.
+        {
+            print_spinner();
+            using net_type = add_layer<multihead_attention_<2,true,30>, input<matrix<float>>>;
+            net_type net;
+            const matrix<float> x = matrix_cast<float>(gaussian_randm(5, 4, 3));
+            resizable_tensor input;
+            net.to_tensor(&x, &x+1, input);
+            net.forward(input);
+            DLIB_TEST(layer<0>(net).layer_details().get_drop_rate() == 0.3f);
+
+            const net_type saved = net;
+            resizable_tensor gradient_input;
+            gradient_input.copy_size(net.get_output());
+            tt::tensor_rand rnd(0);
+            rnd.fill_gaussian(gradient_input);
+            net.forward(input);
+            net.back_propagate_error(input, gradient_input);
+            const tensor& params_grad = layer<0>(net).get_parameter_gradient();
+            const tensor& input_grad = net.get_final_data_gradient();
+
+            // The dropout actually drops something.
+            net_type no_dropout_net;
+            no_dropout_net.to_tensor(&x, &x+1, input);
+            no_dropout_net.forward(input);
+            layer<0>(no_dropout_net).layer_details() = multihead_attention_<2,true,0>(layer<0>(net).layer_details());
+            DLIB_TEST(max(abs(mat(no_dropout_net.forward(input)) - mat(saved.get_output()))) > 1e-3);
+
+            const float eps = 1e-3;
+            auto f = [&](net_type& n, const tensor& in) {
+                const tensor& out = n.forward(in);
+                return dot(out, gradient_input);
+            };
+            for (size_t i = 0; i < params_grad.size(); i += 7)
+            {
+                net_type n1 = saved, n2 = saved;
+                layer<0>(n1).layer_details().get_layer_params().host()[i] += eps;
+                layer<0>(n2).layer_details().get_layer_params().host()[i] -= eps;
+                const double reference = (f(n1,input) - f(n2,input))/(2*eps);
+                DLIB_TEST_MSG(std::abs(reference - params_grad.host()[i]) < 1e-2, reference << " vs " << params_grad.host()[i]);
+            }
+            for (size_t i = 0; i < input.size(); ++i)
+            {
+                net_type n1 = saved, n2 = saved;
+                resizable_tensor in1 = input, in2 = input;
+                in1.host()[i] += eps;
+                in2.host()[i] -= eps;
+                const double reference = (f(n1,in1) - f(n2,in2))/(2*eps);
+                DLIB_TEST_MSG(std::abs(reference - input_grad.host()[i]) < 1e-2, reference << " vs " << input_grad.host()[i]);
+            }
+        }
+
+        // Serialization, and copying a network trained with attention dropout into one
+        // without it.
+        {
+            print_spinner();
+            using train_net_type = loss_mean_squared_per_pixel<add_layer<multihead_attention_<2,false,10>, input<matrix<float>>>>;
+            train_net_type net;
+            const matrix<float> x = matrix_cast<float>(gaussian_randm(3, 4, 4));
+            net(x);
+
+            std::ostringstream sout;
+            serialize(net, sout);
+            train_net_type net2;
+            std::istringstream sin(sout.str());
+            deserialize(net2, sin);
+            DLIB_TEST(mat(layer<1>(net2).layer_details().get_layer_params()) == mat(layer<1>(net).layer_details().get_layer_params()));
+            DLIB_TEST(layer<1>(net2).layer_details().get_drop_rate() == 0.1f);
+
+            using test_net_type = loss_mean_squared_per_pixel<multihead_attention<2,input<matrix<float>>>>;
+            test_net_type tnet = net;
+            DLIB_TEST(layer<1>(tnet).layer_details().get_drop_rate() == 0);
+            const matrix<float> expected = reference_multihead_attention(layer<1>(tnet).layer_details(), x);
+            DLIB_TEST(max(abs(tnet(x) - expected)) < 1e-4);
+
+            std::ostringstream sout2;
+            sout2 << tnet;
+            DLIB_TEST(sout2.str().find("multihead_attention\t (num_heads=2, causal_mask=false, drop_rate=0)") != std::string::npos);
+
+            bool threw = false;
+            try
+            {
+                loss_mean_squared_per_pixel<add_layer<multihead_attention_<4,false,10>, input<matrix<float>>>> wrong_net;
+                sin.clear();
+                sin.str(sout.str());
+                deserialize(wrong_net, sin);
+            }
+            catch (serialization_error&)
+            {
+                threw = true;
+            }
+            DLIB_TEST(threw);
+        }
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    void test_solver_update_kernels()
+    {
+        print_spinner();
+        tt::tensor_rand rnd(0);
+        resizable_tensor s, m, v, params, params_grad;
+        s.set_size(3,4,5,6);
+        m.copy_size(s);
+        v.copy_size(s);
+        params.copy_size(s);
+        params_grad.copy_size(s);
+        rnd.fill_gaussian(m);
+        rnd.fill_uniform(v);
+        rnd.fill_gaussian(params);
+        rnd.fill_gaussian(params_grad);
+
+        const float lr = 0.01, wd = 0.1, m1 = 0.9, m2 = 0.99, t = 3, eps = 1e-8;
+        const matrix<float> P = mat(params), G = mat(params_grad), M = mat(m), V = mat(v);
+        const matrix<float> GW = G + wd*P;
+
+        {
+            resizable_tensor mm(m), vv(v);
+            tt::compute_adamw_update(0, params.size(), s, mm, vv, t, lr, wd, m1, m2, params, params_grad);
+            const float alpha = lr*std::sqrt(1-std::pow(m2,t))/(1-std::pow(m1,t));
+            const matrix<float> M2 = m1*M + (1-m1)*G;
+            const matrix<float> V2 = m2*V + (1-m2)*squared(G);
+            const matrix<float> S = -alpha*pointwise_divide(M2, sqrt(V2)+eps) - lr*wd*P;
+            DLIB_TEST_MSG(max(abs(mat(mm)-M2)) < 1e-6, max(abs(mat(mm)-M2)));
+            DLIB_TEST_MSG(max(abs(mat(vv)-V2)) < 1e-6, max(abs(mat(vv)-V2)));
+            DLIB_TEST_MSG(max(abs(mat(s)-S)) < 1e-5, max(abs(mat(s)-S)));
+        }
+        {
+            resizable_tensor vv(v);
+            tt::compute_rmsprop_update(0, params.size(), s, vv, lr, wd, m1, params, params_grad);
+            const matrix<float> V2 = m1*V + (1-m1)*squared(GW);
+            const matrix<float> S = -lr*pointwise_divide(GW, sqrt(V2)+eps);
+            DLIB_TEST_MSG(max(abs(mat(vv)-V2)) < 1e-6, max(abs(mat(vv)-V2)));
+            DLIB_TEST_MSG(max(abs(mat(s)-S)) < 1e-5, max(abs(mat(s)-S)));
+        }
+        {
+            resizable_tensor vv(v);
+            tt::compute_adagrad_update(0, params.size(), s, vv, lr, wd, params, params_grad);
+            const matrix<float> V2 = V + squared(GW);
+            const matrix<float> S = -lr*pointwise_divide(GW, sqrt(V2)+eps);
+            DLIB_TEST_MSG(max(abs(mat(vv)-V2)) < 1e-5, max(abs(mat(vv)-V2)));
+            DLIB_TEST_MSG(max(abs(mat(s)-S)) < 1e-5, max(abs(mat(s)-S)));
+        }
+        {
+            resizable_tensor mm(m);
+            tt::compute_lion_update(0, params.size(), s, mm, lr, wd, m1, m2, params, params_grad);
+            const matrix<float> C = m1*M + (1-m1)*G;
+            matrix<float> S(C.nr(), C.nc());
+            for (long i = 0; i < C.size(); ++i)
+                S(i) = -lr*(((C(i) > 0) ? 1 : ((C(i) < 0) ? -1 : 0)) + wd*P(i));
+            const matrix<float> M2 = m2*M + (1-m2)*G;
+            DLIB_TEST_MSG(max(abs(mat(mm)-M2)) < 1e-6, max(abs(mat(mm)-M2)));
+            DLIB_TEST_MSG(max(abs(mat(s)-S)) < 1e-6, max(abs(mat(s)-S)));
+        }
+
+        // Only the [begin,end) range should be touched.
+        {
+            resizable_tensor vv(v);
+            s = 123;
+            tt::compute_adagrad_update(10, 20, s, vv, lr, wd, params, params_grad);
+            for (size_t i = 0; i < vv.size(); ++i)
+            {
+                if (10 <= i && i < 20)
+                    DLIB_TEST(vv.host()[i] != v.host()[i] && s.host()[i] != 123);
+                else
+                    DLIB_TEST(vv.host()[i] == v.host()[i]);
+            }
+        }
+    }
+
+    template <typename solver_type>
+    void test_solver (
+        const solver_type& solver,
+        const double learning_rate
+    )
+    {
+        print_spinner();
+        using net_type = loss_mean_squared_multioutput<fc<2,input<matrix<float>>>>;
+
+        // Learn a noise free linear map.
+        dlib::rand rnd(1);
+        matrix<float> A(2,3);
+        A = 1, -2, 0.5,
+            3,  1, -1;
+        const matrix<float,2,1> b = {0.5, -0.25};
+        std::vector<matrix<float>> samples;
+        std::vector<matrix<float>> labels;
+        for (int i = 0; i < 64; ++i)
+        {
+            matrix<float> x = matrix_cast<float>(gaussian_randm(3,1,rnd.get_random_32bit_number()));
+            samples.push_back(x);
+            labels.push_back(A*x + b);
+        }
+
+        net_type net;
+        dnn_trainer<net_type,solver_type> trainer(net, solver);
+        trainer.set_learning_rate(learning_rate);
+        trainer.set_mini_batch_size(16);
+        for (int i = 0; i < 600; ++i)
+            trainer.train_one_step(samples, labels);
+        // Finish with a smaller step size so the solvers that take fixed size steps (like
+        // lion) can settle down.
+        trainer.set_learning_rate(learning_rate/10);
+        for (int i = 0; i < 200; ++i)
+            trainer.train_one_step(samples, labels);
+
+        const std::vector<matrix<float>> out = trainer.get_net()(samples);
+        double err = 0;
+        for (size_t i = 0; i < out.size(); ++i)
+            err += length_squared(out[i]-labels[i]);
+        err /= out.size();
+        dlog << LINFO << solver << ", average squared error: " << err;
+        DLIB_TEST_MSG(err < 0.05, solver << ", err: " << err);
+
+        // A solver saved in the middle of training has to carry on exactly where it left
+        // off once it's loaded back in.
+        {
+            const std::string sync_filename = "dnn_solver_sync_test.dat";
+            std::remove(sync_filename.c_str());
+            std::remove((sync_filename+"_").c_str());
+
+            net_type net1, net2;
+            std::vector<matrix<float>> batch(samples.begin(), samples.begin()+16);
+            std::vector<matrix<float>> batch_labels(labels.begin(), labels.begin()+16);
+            {
+                dnn_trainer<net_type,solver_type> trainer1(net1, solver);
+                trainer1.set_learning_rate(learning_rate);
+                trainer1.set_synchronization_file(sync_filename);
+                for (int i = 0; i < 5; ++i)
+                    trainer1.train_one_step(batch, batch_labels);
+                // Forces the sync file to be written.
+                trainer1.get_net();
+
+                dnn_trainer<net_type,solver_type> trainer2(net2, solver_type());
+                trainer2.set_synchronization_file(sync_filename);
+
+                std::ostringstream sout1, sout2;
+                serialize(trainer1.get_solvers(), sout1);
+                serialize(trainer2.get_solvers(), sout2);
+                DLIB_TEST(sout1.str() == sout2.str());
+
+                trainer1.train_one_step(batch, batch_labels);
+                trainer2.train_one_step(batch, batch_labels);
+                const matrix<float> p1 = mat(layer<1>(trainer1.get_net()).layer_details().get_layer_params());
+                const matrix<float> p2 = mat(layer<1>(trainer2.get_net()).layer_details().get_layer_params());
+                DLIB_TEST(p1.size() != 0);
+                DLIB_TEST_MSG(max(abs(p1-p2)) == 0, max(abs(p1-p2)));
+            }
+
+            std::remove(sync_filename.c_str());
+            std::remove((sync_filename+"_").c_str());
+        }
+
+        // A bias learning rate multiplier of 0 must leave the fc_ bias alone.
+        {
+            net_type net1;
+            layer<1>(net1).layer_details().set_bias_learning_rate_multiplier(0);
+            dnn_trainer<net_type,solver_type> trainer1(net1, solver);
+            trainer1.set_learning_rate(learning_rate);
+            trainer1.train_one_step(samples, labels);
+            const matrix<float> p0 = mat(layer<1>(trainer1.get_net()).layer_details().get_layer_params());
+            for (int i = 0; i < 5; ++i)
+                trainer1.train_one_step(samples, labels);
+            const matrix<float> p1 = mat(layer<1>(trainer1.get_net()).layer_details().get_layer_params());
+            DLIB_TEST(max(abs(rowm(p1,range(0,2)) - rowm(p0,range(0,2)))) > 0);
+            DLIB_TEST(max(abs(rowm(p1,3) - rowm(p0,3))) == 0);
+        }
+    }
+
+    void test_solvers()
+    {
+        test_solver_update_kernels();
+        test_solver(adamw(0.0001, 0.9, 0.999), 0.03);
+        test_solver(rmsprop(0, 0.9), 0.01);
+        test_solver(adagrad(0), 0.5);
+        test_solver(lion(0.0001, 0.9, 0.99), 0.02);
+
+        std::ostringstream sout;
+        sout << adamw() << "\n" << rmsprop() << "\n" << adagrad() << "\n" << lion();
+        DLIB_TEST(sout.str() ==
+            "adamw: weight_decay=0.01, momentum1=0.9, momentum2=0.999\n"
+            "rmsprop: weight_decay=0.0005, decay=0.9\n"
+            "adagrad: weight_decay=0.0005\n"
+            "lion: weight_decay=0.01, momentum1=0.9, momentum2=0.99");
+
+        // The solvers use distinct version tags, so loading one from another's data fails.
+        std::ostringstream out;
+        serialize(adamw(), out);
+        std::istringstream in(out.str());
+        adam a;
+        bool threw = false;
+        try { deserialize(a, in); } catch (serialization_error&) { threw = true; }
+        DLIB_TEST(threw);
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    template <typename scheduler_type>
+    void check_scheduler_min_and_serialization (
+        const scheduler_type& sched,
+        unsigned long long num_steps
+    )
+    {
+        for (unsigned long long i = 0; i < num_steps; ++i)
+            DLIB_TEST_MSG(sched(i) >= sched.get_min_learning_rate(), sched << ", step: " << i);
+
+        learning_rate_scheduler s1, s2;
+        s1 = sched;
+        std::ostringstream sout;
+        serialize(s1, sout);
+        std::istringstream sin(sout.str());
+        deserialize(s2, sin);
+        DLIB_TEST(s2.template contains<scheduler_type>());
+        for (unsigned long long i = 0; i < num_steps; i += 7)
+            DLIB_TEST(s2.template get<scheduler_type>()(i) == sched(i));
+        DLIB_TEST(cast_to_string(s1) == cast_to_string(sched));
+    }
+
+    void test_lr_schedulers()
+    {
+        print_spinner();
+        const double eps = 1e-12;
+
+        linear_warmup_scheduler warmup(1e-4, 0.1, 10);
+        DLIB_TEST(std::abs(warmup(0) - 1e-4) < eps);
+        DLIB_TEST(std::abs(warmup(5) - (1e-4 + (0.1-1e-4)/2)) < eps);
+        DLIB_TEST(std::abs(warmup(10) - 0.1) < eps);
+        DLIB_TEST(std::abs(warmup(1000) - 0.1) < eps);
+        check_scheduler_min_and_serialization(warmup, 50);
+
+        cosine_restarts_scheduler cosine(0.1, 0.001, 10);
+        DLIB_TEST(std::abs(cosine(0) - 0.1) < eps);
+        DLIB_TEST(std::abs(cosine(5) - 0.0505) < eps);
+        DLIB_TEST(cosine(9) < cosine(8));
+        DLIB_TEST(std::abs(cosine(10) - 0.1) < eps);
+        DLIB_TEST(std::abs(cosine(25) - 0.0505) < eps);
+        check_scheduler_min_and_serialization(cosine, 100);
+
+        // Cycles of length 10, 20, 40, ... after a 5 step warmup.
+        cosine_restarts_scheduler cosine2(0.1, 0.001, 10, 2, 5);
+        DLIB_TEST(std::abs(cosine2(0) - 0.001) < eps);
+        DLIB_TEST(std::abs(cosine2(5) - 0.1) < eps);
+        DLIB_TEST(std::abs(cosine2(15) - 0.1) < eps);
+        DLIB_TEST(std::abs(cosine2(25) - 0.0505) < eps);
+        DLIB_TEST(std::abs(cosine2(35) - 0.1) < eps);
+        DLIB_TEST(std::abs(cosine2(55) - 0.0505) < eps);
+        check_scheduler_min_and_serialization(cosine2, 200);
+
+        one_cycle_scheduler one_cycle(0.1, 100, 0.3, 25, 1e4);
+        DLIB_TEST(std::abs(one_cycle.get_initial_learning_rate() - 0.004) < eps);
+        DLIB_TEST(std::abs(one_cycle.get_final_learning_rate() - 0.004/1e4) < eps);
+        DLIB_TEST(std::abs(one_cycle(0) - 0.004) < eps);
+        DLIB_TEST(std::abs(one_cycle(15) - 0.052) < eps);
+        DLIB_TEST(std::abs(one_cycle(30) - 0.1) < eps);
+        DLIB_TEST(std::abs(one_cycle(65) - (0.1+0.004/1e4)/2) < eps);
+        DLIB_TEST(std::abs(one_cycle(100) - 0.004/1e4) < eps);
+        DLIB_TEST(std::abs(one_cycle(500) - 0.004/1e4) < eps);
+        check_scheduler_min_and_serialization(one_cycle, 150);
+
+        polynomial_decay_scheduler poly(0.1, 0.001, 100, 2, 10);
+        DLIB_TEST(std::abs(poly(0) - 0.001) < eps);
+        DLIB_TEST(std::abs(poly(10) - 0.1) < eps);
+        DLIB_TEST(std::abs(poly(60) - (0.099*0.25 + 0.001)) < eps);
+        DLIB_TEST(std::abs(poly(110) - 0.001) < eps);
+        DLIB_TEST(std::abs(poly(1000) - 0.001) < eps);
+        check_scheduler_min_and_serialization(poly, 150);
+
+        step_decay_scheduler step(0.1, 10, 0.5, 0.01);
+        DLIB_TEST(std::abs(step(9) - 0.1) < eps);
+        DLIB_TEST(std::abs(step(10) - 0.05) < eps);
+        DLIB_TEST(std::abs(step(29) - 0.025) < eps);
+        DLIB_TEST(std::abs(step(30) - 0.0125) < eps);
+        DLIB_TEST(std::abs(step(40) - 0.01) < eps);
+        DLIB_TEST(std::abs(step(1000) - 0.01) < eps);
+        check_scheduler_min_and_serialization(step, 100);
+
+        learning_rate_scheduler empty;
+        DLIB_TEST(cast_to_string(empty) == "none");
+    }
+
+    void test_trainer_with_lr_scheduler()
+    {
+        print_spinner();
+        using net_type = loss_mean_squared_multioutput<fc<2,input<matrix<float>>>>;
+        std::vector<matrix<float>> samples(8, matrix<float>(3,1));
+        std::vector<matrix<float>> labels(8, matrix<float>(2,1));
+        dlib::rand rnd;
+        for (auto& x : samples)
+            x = matrix_cast<float>(gaussian_randm(3,1,rnd.get_random_32bit_number()));
+        for (auto& y : labels)
+            y = matrix_cast<float>(gaussian_randm(2,1,rnd.get_random_32bit_number()));
+
+        const cosine_restarts_scheduler sched(0.01, 0.0001, 10, 2, 3);
+
+        {
+            net_type net;
+            dnn_trainer<net_type> trainer(net, sgd());
+            trainer.set_learning_rate_scheduler(sched);
+            DLIB_TEST(trainer.get_learning_rate_scheduler().contains<cosine_restarts_scheduler>());
+            DLIB_TEST(trainer.get_learning_rate() == sched(0));
+            DLIB_TEST(trainer.get_min_learning_rate() == sched.get_min_learning_rate());
+            DLIB_TEST(trainer.get_learning_rate_shrink_factor() == 1);
+            for (int i = 1; i <= 25; ++i)
+            {
+                trainer.train_one_step(samples, labels);
+                trainer.get_net(force_flush_to_disk::no);
+                DLIB_TEST(trainer.get_learning_rate_scheduler_step() == (unsigned long long)i);
+                DLIB_TEST(trainer.get_learning_rate() == sched(i));
+            }
+
+            // test steps don't advance the scheduler
+            trainer.test_one_step(samples, labels);
+            trainer.get_net(force_flush_to_disk::no);
+            DLIB_TEST(trainer.get_learning_rate_scheduler_step() == 25);
+
+            std::ostringstream sout;
+            sout << trainer;
+            DLIB_TEST(sout.str().find("cosine_restarts_scheduler") != std::string::npos);
+
+            // going back to a fixed learning rate turns the scheduler off
+            trainer.set_learning_rate(0.1);
+            DLIB_TEST(trainer.get_learning_rate_scheduler().is_empty());
+            trainer.set_learning_rate_scheduler(step_decay_scheduler(0.1, 2));
+            trainer.set_learning_rate_schedule(matrix_cast<double>(linspace(0.1, 0.01, 10)));
+            DLIB_TEST(trainer.get_learning_rate_scheduler().is_empty());
+        }
+
+        // A run resumed from the sync file continues on the same learning rate curve.
+        {
+            const std::string sync_filename = "dnn_lr_scheduler_sync_test.dat";
+            std::remove(sync_filename.c_str());
+            std::remove((sync_filename+"_").c_str());
+            {
+                net_type net1, net2;
+                dnn_trainer<net_type> trainer1(net1, sgd());
+                trainer1.set_learning_rate_scheduler(sched);
+                trainer1.set_synchronization_file(sync_filename);
+                for (int i = 0; i < 7; ++i)
+                    trainer1.train_one_step(samples, labels);
+                // Forces the sync file to be written.
+                trainer1.get_net();
+
+                dnn_trainer<net_type> trainer2(net2, sgd());
+                trainer2.set_synchronization_file(sync_filename);
+                DLIB_TEST(trainer2.get_learning_rate_scheduler().contains<cosine_restarts_scheduler>());
+                DLIB_TEST(trainer2.get_learning_rate_scheduler_step() == 7);
+                DLIB_TEST(trainer2.get_learning_rate() == sched(7));
+                for (int i = 0; i < 5; ++i)
+                {
+                    trainer1.train_one_step(samples, labels);
+                    trainer2.train_one_step(samples, labels);
+                }
+                trainer1.get_net(force_flush_to_disk::no);
+                trainer2.get_net(force_flush_to_disk::no);
+                DLIB_TEST(trainer2.get_learning_rate_scheduler_step() == 12);
+                DLIB_TEST(trainer1.get_learning_rate() == trainer2.get_learning_rate());
+                DLIB_TEST(trainer2.get_learning_rate() == sched(12));
+            }
+            std::remove(sync_filename.c_str());
+            std::remove((sync_filename+"_").c_str());
+        }
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    void test_half_precision_conversions()
+    {
+        print_spinner();
+        const float inf = std::numeric_limits<float>::infinity();
+        const float nan = std::numeric_limits<float>::quiet_NaN();
+
+        DLIB_TEST(float_to_fp16(0.0f) == 0x0000);
+        DLIB_TEST(float_to_fp16(-0.0f) == 0x8000);
+        DLIB_TEST(float_to_fp16(1.0f) == 0x3c00);
+        DLIB_TEST(float_to_fp16(-2.0f) == 0xc000);
+        DLIB_TEST(float_to_fp16(65504.0f) == 0x7bff);
+        DLIB_TEST(float_to_fp16(65519.0f) == 0x7bff);
+        DLIB_TEST(float_to_fp16(65520.0f) == 0x7c00);
+        DLIB_TEST(float_to_fp16(1e10f) == 0x7c00);
+        DLIB_TEST(float_to_fp16(-1e10f) == 0xfc00);
+        DLIB_TEST(float_to_fp16(inf) == 0x7c00);
+        DLIB_TEST(float_to_fp16(-inf) == 0xfc00);
+        DLIB_TEST(std::isnan(fp16_to_float(float_to_fp16(nan))));
+        // smallest normal and subnormal numbers
+        DLIB_TEST(float_to_fp16(std::ldexp(1.0f,-14)) == 0x0400);
+        DLIB_TEST(float_to_fp16(std::ldexp(1.0f,-24)) == 0x0001);
+        DLIB_TEST(float_to_fp16(std::ldexp(1.0f,-25)) == 0x0000);
+        DLIB_TEST(float_to_fp16(std::ldexp(3.0f,-25)) == 0x0002);
+        DLIB_TEST(float_to_fp16(-std::ldexp(1.0f,-30)) == 0x8000);
+        // ties go to even
+        DLIB_TEST(float_to_fp16(1 + std::ldexp(1.0f,-11)) == 0x3c00);
+        DLIB_TEST(float_to_fp16(1 + std::ldexp(3.0f,-11)) == 0x3c02);
+        DLIB_TEST(float_to_fp16(1 + std::ldexp(1.0f,-11) + std::ldexp(1.0f,-20)) == 0x3c01);
+
+        DLIB_TEST(float_to_bf16(0.0f) == 0x0000);
+        DLIB_TEST(float_to_bf16(1.0f) == 0x3f80);
+        DLIB_TEST(float_to_bf16(-2.0f) == 0xc000);
+        DLIB_TEST(float_to_bf16(1 + std::ldexp(1.0f,-8)) == 0x3f80);
+        DLIB_TEST(float_to_bf16(1 + std::ldexp(3.0f,-8)) == 0x3f82);
+        DLIB_TEST(float_to_bf16(1e10f) != 0x7f80);
+        DLIB_TEST(float_to_bf16(std::numeric_limits<float>::max()) == 0x7f80);
+        DLIB_TEST(float_to_bf16(inf) == 0x7f80);
+        DLIB_TEST(std::isnan(bf16_to_float(float_to_bf16(nan))));
+
+        // Every 16 bit number converts to a float and back without change.
+        for (uint32_t i = 0; i < 0x10000; ++i)
+        {
+            const uint16_t h = static_cast<uint16_t>(i);
+            const float f16 = fp16_to_float(h);
+            const float b16 = bf16_to_float(h);
+            if (std::isnan(f16))
+                DLIB_TEST(std::isnan(fp16_to_float(float_to_fp16(f16))));
+            else
+                DLIB_TEST_MSG(float_to_fp16(f16) == h, i);
+            if (std::isnan(b16))
+                DLIB_TEST(std::isnan(bf16_to_float(float_to_bf16(b16))));
+            else
+                DLIB_TEST_MSG(float_to_bf16(b16) == h, i);
+        }
+
+        // Rounding error is at most half a unit in the last place.
+        dlib::rand rnd;
+        for (int i = 0; i < 10000; ++i)
+        {
+            const float x = static_cast<float>(rnd.get_random_gaussian()*std::pow(10.0, rnd.get_integer_in_range(-4,4)));
+            // below 2^-14 fp16 is subnormal and has a fixed spacing of 2^-24
+            const float fp16_err = std::max(std::ldexp(std::abs(x),-11), std::ldexp(1.0f,-25));
+            if (std::abs(x) < 65520)
+                DLIB_TEST(std::abs(fp16_to_float(float_to_fp16(x)) - x) <= fp16_err);
+            else
+                DLIB_TEST(std::isinf(fp16_to_float(float_to_fp16(x))));
+            DLIB_TEST(std::abs(bf16_to_float(float_to_bf16(x)) - x) <= std::ldexp(std::abs(x),-8));
+            DLIB_TEST(float_to_half(x, half_format::fp16) == float_to_fp16(x));
+            DLIB_TEST(float_to_half(x, half_format::bf16) == float_to_bf16(x));
+        }
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    void test_half_tensor()
+    {
+        print_spinner();
+        resizable_tensor t(2,3,4,5);
+        tt::tensor_rand rnd(0);
+        rnd.fill_gaussian(t, 0, 10);
+
+        for (auto fmt : {half_format::fp16, half_format::bf16})
+        {
+            half_tensor h(t, fmt);
+            DLIB_TEST(h.get_format() == fmt);
+            DLIB_TEST(h.num_samples() == 2 && h.k() == 3 && h.nr() == 4 && h.nc() == 5);
+            DLIB_TEST(h.size() == t.size());
+
+            resizable_tensor rounded;
+            rounded = t;
+            tt::round_to_half_precision(rounded, fmt);
+            resizable_tensor back;
+            h.copy_to(back);
+            DLIB_TEST(have_same_dimensions(back, t));
+            DLIB_TEST(max(abs(mat(back) - mat(rounded))) == 0);
+            DLIB_TEST(max(abs(mat(back) - mat(t))) > 0);
+            for (size_t i = 0; i < t.size(); ++i)
+                DLIB_TEST(h.host()[i] == float_to_half(t.host()[i], fmt));
+
+            std::ostringstream sout;
+            serialize(h, sout);
+            DLIB_TEST(sout.str().size() >= 2*t.size());
+            DLIB_TEST(sout.str().size() < 2*t.size() + 64);
+            std::istringstream sin(sout.str());
+            half_tensor h2;
+            deserialize(h2, sin);
+            DLIB_TEST(h2.get_format() == fmt);
+            DLIB_TEST(h2.num_samples() == 2 && h2.k() == 3 && h2.nr() == 4 && h2.nc() == 5);
+            DLIB_TEST(std::equal(h.host(), h.host()+h.size(), h2.host()));
+        }
+
+        resizable_tensor big(1,1,1,3);
+        big.host()[0] = 1e6;
+        big.host()[1] = -1e6;
+        big.host()[2] = 1e-9;
+        tt::round_to_half_precision(big, half_format::fp16);
+        DLIB_TEST(std::isinf(big.host()[0]) && big.host()[0] > 0);
+        DLIB_TEST(std::isinf(big.host()[1]) && big.host()[1] < 0);
+        DLIB_TEST(big.host()[2] == 0);
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    void test_half_precision_parameters()
+    {
+        print_spinner();
+        using net_type = loss_multiclass_log<fc<3,relu<bn_con<con<4,3,3,1,1,input<matrix<float>>>>>>>;
+        matrix<float> x = matrix_cast<float>(gaussian_randm(8,8));
+        net_type net1, net2;
+        net1(x);
+        net2(x);
+
+        for (auto fmt : {half_format::fp16, half_format::bf16})
+        {
+            const auto params = get_half_precision_parameters(net1, fmt);
+            DLIB_TEST(params.size() == net_type::num_computational_layers);
+            size_t total = 0;
+            for (auto& p : params)
+                total += p.size();
+            DLIB_TEST(total == count_parameters(net1));
+
+            set_half_precision_parameters(net2, params);
+
+            net_type net3 = net1;
+            round_parameters_to_half_precision(net3, fmt);
+            std::vector<matrix<float>> p2, p3;
+            visit_layer_parameters(net2, [&](const tensor& t) { p2.push_back(mat(t)); });
+            visit_layer_parameters(net3, [&](const tensor& t) { p3.push_back(mat(t)); });
+            DLIB_TEST(p2.size() == p3.size());
+            for (size_t i = 0; i < p2.size(); ++i)
+                DLIB_TEST(p2[i] == p3[i]);
+        }
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    void test_trainer_mixed_precision()
+    {
+        print_spinner();
+        using net_type = loss_mean_squared_multioutput<fc<2,input<matrix<float>>>>;
+        std::vector<matrix<float>> samples(16, matrix<float>(3,1));
+        std::vector<matrix<float>> labels;
+        dlib::rand rnd;
+        matrix<float> W(2,3);
+        W = 1, -2, 0.5,
+            3, 0.25, -1;
+        for (auto& x : samples)
+        {
+            x = matrix_cast<float>(gaussian_randm(3,1,rnd.get_random_32bit_number()));
+            labels.push_back(W*x);
+        }
+
+        for (auto fmt : {half_format::fp16, half_format::bf16})
+        {
+            net_type net;
+            dnn_trainer<net_type> trainer(net, sgd(0,0.9));
+            DLIB_TEST(!trainer.uses_mixed_precision());
+            trainer.set_mixed_precision(fmt);
+            DLIB_TEST(trainer.uses_mixed_precision());
+            DLIB_TEST(trainer.get_mixed_precision_format() == fmt);
+            trainer.set_learning_rate(0.01);
+            trainer.set_learning_rate_shrink_factor(1);
+            for (int i = 0; i < 500; ++i)
+                trainer.train_one_step(samples, labels);
+            trainer.get_net(force_flush_to_disk::no);
+            const std::vector<matrix<float>> out = net(samples);
+            double err = 0;
+            for (size_t j = 0; j < out.size(); ++j)
+                err += length_squared(out[j] - labels[j]);
+            err /= out.size();
+            DLIB_TEST_MSG(err < 1e-3, fmt << ": " << err);
+            // The master weights are still floats and aren't rounded to 16 bits.
+            const matrix<float> w = mat(layer<1>(net).layer_details().get_layer_params());
+            DLIB_TEST(max(abs(trans(rowm(w,range(0,2))) - W)) < 0.05);
+            resizable_tensor rounded;
+            rounded = layer<1>(net).layer_details().get_layer_params();
+            tt::round_to_half_precision(rounded, fmt);
+            DLIB_TEST(mat(rounded) != w);
+
+            std::ostringstream sout;
+            sout << trainer;
+            DLIB_TEST(sout.str().find("mixed precision") != std::string::npos);
+        }
+
+        // A loss scale that makes every gradient overflow means no step is taken.
+        {
+            net_type net;
+            dnn_trainer<net_type> trainer(net, sgd());
+            trainer.set_mixed_precision(half_format::fp16);
+            trainer.set_dynamic_loss_scaling(false);
+            trainer.set_loss_scale(1e10);
+            trainer.train_one_step(samples, labels);
+            trainer.get_net(force_flush_to_disk::no);
+            const matrix<float> w0 = mat(layer<1>(net).layer_details().get_layer_params());
+            for (int i = 0; i < 5; ++i)
+                trainer.train_one_step(samples, labels);
+            trainer.get_net(force_flush_to_disk::no);
+            DLIB_TEST(trainer.get_num_skipped_steps() == 6);
+            DLIB_TEST(trainer.get_loss_scale() == 1e10);
+            DLIB_TEST(mat(layer<1>(net).layer_details().get_layer_params()) == w0);
+
+            // With dynamic loss scaling the scale comes down until the steps go through.
+            trainer.set_dynamic_loss_scaling(true);
+            for (int i = 0; i < 40; ++i)
+                trainer.train_one_step(samples, labels);
+            trainer.get_net(force_flush_to_disk::no);
+            DLIB_TEST(trainer.get_loss_scale() < 65504);
+            DLIB_TEST(trainer.get_num_skipped_steps() > 6);
+            DLIB_TEST(trainer.get_num_skipped_steps() < 6+40);
+            DLIB_TEST(mat(layer<1>(net).layer_details().get_layer_params()) != w0);
+
+            // and grows again after a long enough run without overflows.
+            trainer.set_loss_scale(1);
+            for (int i = 0; i < 2000; ++i)
+                trainer.train_one_step(samples, labels);
+            trainer.get_net(force_flush_to_disk::no);
+            DLIB_TEST(trainer.get_loss_scale() == 2);
+        }
+
+        // The mixed precision settings are saved in the sync file.
+        {
+            const std::string sync_filename = "dnn_mixed_precision_sync_test.dat";
+            std::remove(sync_filename.c_str());
+            std::remove((sync_filename+"_").c_str());
+            {
+                net_type net1, net2;
+                dnn_trainer<net_type> trainer1(net1, sgd());
+                trainer1.set_mixed_precision(half_format::bf16);
+                trainer1.set_dynamic_loss_scaling(false);
+                trainer1.set_loss_scale(1e10);
+                trainer1.set_synchronization_file(sync_filename);
+                for (int i = 0; i < 3; ++i)
+                    trainer1.train_one_step(samples, labels);
+                trainer1.get_net();
+
+                dnn_trainer<net_type> trainer2(net2, sgd());
+                trainer2.set_synchronization_file(sync_filename);
+                DLIB_TEST(trainer2.uses_mixed_precision());
+                DLIB_TEST(trainer2.get_mixed_precision_format() == half_format::bf16);
+                DLIB_TEST(!trainer2.uses_dynamic_loss_scaling());
+                DLIB_TEST(trainer2.get_loss_scale() == 1e10);
+                DLIB_TEST(trainer2.get_num_skipped_steps() == trainer1.get_num_skipped_steps());
+            }
+            std::remove(sync_filename.c_str());
+            std::remove((sync_filename+"_").c_str());
+        }
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    void test_trainer_gradient_accumulation()
+    {
+        print_spinner();
+        using net_type = loss_mean_squared_multioutput<fc<2,input<matrix<float>>>>;
+        std::vector<matrix<float>> samples(16, matrix<float>(3,1));
+        std::vector<matrix<float>> labels;
+        dlib::rand rnd;
+        for (auto& x : samples)
+        {
+            x = matrix_cast<float>(gaussian_randm(3,1,rnd.get_random_32bit_number()));
+            labels.push_back(matrix_cast<float>(gaussian_randm(2,1,rnd.get_random_32bit_number())));
+        }
+
+        net_type net1;
+        net1(samples);
+        net_type net2 = net1;
+        auto params = [](net_type& net) -> matrix<float> { return mat(layer<1>(net).layer_details().get_layer_params()); };
+        const matrix<float> w0 = params(net1);
+
+        // One step on all 16 samples.
+        dnn_trainer<net_type> trainer1(net1, sgd(0,0));
+        trainer1.set_learning_rate(0.1);
+        trainer1.train_one_step(samples, labels);
+        trainer1.get_net(force_flush_to_disk::no);
+
+        // 4 steps on 4 samples each, accumulated into one update, should do the same thing.
+        dnn_trainer<net_type> trainer2(net2, sgd(0,0));
+        trainer2.set_learning_rate_scheduler(step_decay_scheduler(0.1, 1000));
+        DLIB_TEST(trainer2.get_gradient_accumulation_steps() == 1);
+        trainer2.set_gradient_accumulation_steps(4);
+        DLIB_TEST(trainer2.get_gradient_accumulation_steps() == 4);
+        for (int i = 0; i < 4; ++i)
+        {
+            const std::vector<matrix<float>> s(samples.begin()+4*i, samples.begin()+4*i+4);
+            const std::vector<matrix<float>> l(labels.begin()+4*i, labels.begin()+4*i+4);
+            trainer2.train_one_step(s, l);
+            trainer2.get_net(force_flush_to_disk::no);
+            if (i < 3)
+            {
+                DLIB_TEST(params(net2) == w0);
+                DLIB_TEST(trainer2.get_learning_rate_scheduler_step() == 0);
+            }
+        }
+        DLIB_TEST(trainer2.get_learning_rate_scheduler_step() == 1);
+        DLIB_TEST(params(net2) != w0);
+        DLIB_TEST_MSG(max(abs(params(net1) - params(net2))) < 1e-6, max(abs(params(net1) - params(net2))));
+
+        std::ostringstream sout;
+        sout << trainer2;
+        DLIB_TEST(sout.str().find("gradient accumulation steps") != std::string::npos);
+
+        // The setting is saved in the sync file.
+        const std::string sync_filename = "dnn_gradient_accumulation_sync_test.dat";
+        std::remove(sync_filename.c_str());
+        std::remove((sync_filename+"_").c_str());
+        {
+            net_type net3, net4;
+            dnn_trainer<net_type> trainer3(net3, sgd());
+            trainer3.set_gradient_accumulation_steps(3);
+            trainer3.set_gradient_clip_norm(2.5);
+            trainer3.set_gradient_clip_value(0.5);
+            trainer3.set_synchronization_file(sync_filename);
+            trainer3.train_one_step(samples, labels);
+            trainer3.get_net();
+
+            dnn_trainer<net_type> trainer4(net4, sgd());
+            trainer4.set_synchronization_file(sync_filename);
+            DLIB_TEST(trainer4.get_gradient_accumulation_steps() == 3);
+            DLIB_TEST(trainer4.get_gradient_clip_norm() == 2.5);
+            DLIB_TEST(trainer4.get_gradient_clip_value() == 0.5);
+        }
+        std::remove(sync_filename.c_str());
+        std::remove((sync_filename+"_").c_str());
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    void test_trainer_gradient_clipping()
+    {
+        print_spinner();
+        resizable_tensor t(2,3);
+        t = 1;
+        t.host()[0] = -5;
+        t.host()[1] = 0.25;
+        t.host()[2] = 7;
+        tt::clamp(t, -2, 0.5);
+        DLIB_TEST(t.host()[0] == -2);
+        DLIB_TEST(t.host()[1] == 0.25);
+        DLIB_TEST(t.host()[2] == 0.5);
+        DLIB_TEST(t.host()[3] == 0.5);
+
+        using net_type = loss_mean_squared_multioutput<fc<2,input<matrix<float>>>>;
+        std::vector<matrix<float>> samples(8, matrix<float>(3,1));
+        std::vector<matrix<float>> labels;
+        dlib::rand rnd;
+        for (auto& x : samples)
+        {
+            x = matrix_cast<float>(gaussian_randm(3,1,rnd.get_random_32bit_number()));
+            labels.push_back(matrix_cast<float>(100*gaussian_randm(2,1,rnd.get_random_32bit_number())));
+        }
+        net_type net0;
+        net0(samples);
+        auto params = [](net_type& net) -> matrix<float> { return mat(layer<1>(net).layer_details().get_layer_params()); };
+        const matrix<float> w0 = params(net0);
+
+        // With plain sgd and a learning rate of 1 the parameter change of a single step is
+        // minus the gradient the solver was given.
+        auto gradient_of_one_step = [&](double clip_norm, double clip_value)
+        {
+            net_type net = net0;
+            dnn_trainer<net_type> trainer(net, sgd(0,0));
+            trainer.set_learning_rate(1);
+            trainer.set_gradient_clip_norm(clip_norm);
+            trainer.set_gradient_clip_value(clip_value);
+            trainer.train_one_step(samples, labels);
+            trainer.get_net(force_flush_to_disk::no);
+            return matrix<float>(w0 - params(net));
+        };
+
+        const matrix<float> g = gradient_of_one_step(0, 0);
+        const double norm = length(g);
+        DLIB_TEST(norm > 10);
+
+        // A norm bigger than the gradient's changes nothing.
+        DLIB_TEST(max(abs(gradient_of_one_step(2*norm, 0) - g)) < 1e-4);
+
+        matrix<float> clipped = gradient_of_one_step(2, 0);
+        DLIB_TEST_MSG(std::abs(length(clipped) - 2) < 1e-4, length(clipped));
+        DLIB_TEST(max(abs(clipped - g*(2/norm))) < 1e-4);
+
+        clipped = gradient_of_one_step(0, 0.5);
+        DLIB_TEST(max(abs(clipped - clamp(g, -0.5, 0.5))) < 1e-5);
+        DLIB_TEST(max(abs(clipped)) <= 0.5 + 1e-5);
+
+        // Both together clamp first and then rescale.
+        clipped = gradient_of_one_step(0.5, 0.5);
+        matrix<float> expected = clamp(g, -0.5, 0.5);
+        expected *= 0.5/length(expected);
+        DLIB_TEST(max(abs(clipped - expected)) < 1e-5);
+
+        net_type net;
+        dnn_trainer<net_type> trainer(net);
+        trainer.set_gradient_clip_norm(2);
+        trainer.set_gradient_clip_value(0.5);
+        std::ostringstream sout;
+        sout << trainer;
+        DLIB_TEST(sout.str().find("gradient clip norm") != std::string::npos);
+        DLIB_TEST(sout.str().find("gradient clip value") != std::string::npos);
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    void test_cpu_num_threads()
+    {
+        // The cpu kernels must give the same answer however many threads they use.
+        print_spinner();
+        const size_t default_num_threads = dnn_cpu_num_threads();
+        DLIB_TEST(default_num_threads > 0);
+
+        tt::tensor_rand rnd;
+        resizable_tensor data(4,8,64,64), filters(6,8,3,3), gamma(1,8), beta(1,8);
+        rnd.fill_gaussian(data);
+        rnd.fill_gaussian(filters);
+        rnd.fill_gaussian(gamma);
+        rnd.fill_gaussian(beta);
+
+        struct results
+        {
+            resizable_tensor conv_out, data_grad, filters_grad, pool_out, pool_grad;
+            resizable_tensor bn_out, src_grad, gamma_grad, beta_grad, act_out, sm_out, sm_grad;
+        };
+        auto run_kernels = [&](size_t num_threads)
+        {
+            set_dnn_cpu_num_threads(num_threads);
+            DLIB_TEST(dnn_cpu_num_threads() == num_threads);
+            results r;
+
+            cpu::tensor_conv conv;
+            conv.setup(data, filters, 1, 1, 1, 1);
+            conv(false, r.conv_out, data, filters);
+            r.data_grad.copy_size(data);
+            conv.get_gradient_for_data(false, r.conv_out, filters, r.data_grad);
+            r.filters_grad.copy_size(filters);
+            conv.get_gradient_for_filters(false, r.conv_out, data, r.filters_grad);
+
+            cpu::pooling pool;
+            pool.setup_max_pooling(3, 3, 2, 2, 1, 1);
+            pool(r.pool_out, data);
+            r.pool_grad.copy_size(data);
+            r.pool_grad = 0;
+            pool.get_gradient(r.pool_out, r.pool_out, data, r.pool_grad);
+
+            resizable_tensor means, invstds, running_means, running_variances;
+            cpu::batch_normalize_conv(DEFAULT_BATCH_NORM_EPS, r.bn_out, means, invstds, 1,
+                running_means, running_variances, data, gamma, beta);
+            r.src_grad.copy_size(data);
+            r.src_grad = 0;
+            r.gamma_grad.copy_size(gamma);
+            r.beta_grad.copy_size(beta);
+            cpu::batch_normalize_conv_gradient(DEFAULT_BATCH_NORM_EPS, r.bn_out, means, invstds,
+                data, gamma, r.src_grad, r.gamma_grad, r.beta_grad);
+
+            r.act_out.copy_size(data);
+            cpu::relu(r.act_out, data);
+            cpu::sigmoid(r.act_out, r.act_out);
+            r.sm_out.copy_size(data);
+            cpu::softmax(r.sm_out, data);
+            r.sm_grad.copy_size(data);
+            r.sm_grad = 0;
+            cpu::softmax_gradient(r.sm_grad, r.sm_out, data);
+            return r;
+        };
+
+        const results one = run_kernels(1);
+        const results many = run_kernels(3);
+        set_dnn_cpu_num_threads(default_num_threads);
+
+        DLIB_TEST(mat(one.conv_out) == mat(many.conv_out));
+        DLIB_TEST(mat(one.data_grad) == mat(many.data_grad));
+        // Only the filter gradient adds up partial sums from each thread, so it may
+        // round differently.
+        DLIB_TEST(max(abs(mat(one.filters_grad) - mat(many.filters_grad))) < 1e-5*max(abs(mat(one.filters_grad))));
+        DLIB_TEST(mat(one.pool_out) == mat(many.pool_out));
+        DLIB_TEST(mat(one.pool_grad) == mat(many.pool_grad));
+        DLIB_TEST(mat(one.bn_out) == mat(many.bn_out));
+        DLIB_TEST(mat(one.src_grad) == mat(many.src_grad));
+        DLIB_TEST(mat(one.gamma_grad) == mat(many.gamma_grad));
+        DLIB_TEST(mat(one.beta_grad) == mat(many.beta_grad));
+        DLIB_TEST(mat(one.act_out) == mat(many.act_out));
+        DLIB_TEST(mat(one.sm_out) == mat(many.sm_out));
+        DLIB_TEST(mat(one.sm_grad) == mat(many.sm_grad));
+
+        // The banded convolution has to agree with the plain definition of convolution.
+        resizable_tensor small_data(2,3,9,7), small_filters(4,3,3,3);
+        rnd.fill_gaussian(small_data);
+        rnd.fill_gaussian(small_filters);
+        cpu::tensor_conv conv;
+        conv.setup(small_data, small_filters, 2, 1, 1, 0);
+        resizable_tensor out;
+        conv(false, out, small_data, small_filters);
+        double err = 0;
+        for (long n = 0; n < out.num_samples(); ++n)
+        for (long f = 0; f < out.k(); ++f)
+        for (long r = 0; r < out.nr(); ++r)
+        for (long c = 0; c < out.nc(); ++c)
+        {
+            double sum = 0;
+            for (long k = 0; k < small_filters.k(); ++k)
+            for (long y = 0; y < small_filters.nr(); ++y)
+            for (long x = 0; x < small_filters.nc(); ++x)
+            {
+                const long yy = r*2 - 1 + y;
+                const long xx = c + x;
+                if (0 <= yy && yy < small_data.nr() && xx < small_data.nc())
+                    sum += image_plane(small_data,n,k)(yy,xx)*image_plane(small_filters,f,k)(y,x);
+            }
+            err = std::max(err, std::abs(sum - image_plane(out,n,f)(r,c)));
+        }
+        DLIB_TEST_MSG(err < 1e-4, err);
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    void test_trainer_callbacks()
+    {
+        print_spinner();
+        using net_type = loss_mean_squared_multioutput<fc<2,input<matrix<float>>>>;
+        using trainer_type = dnn_trainer<net_type>;
+        std::vector<matrix<float>> samples(10, matrix<float>(3,1));
+        std::vector<matrix<float>> labels;
+        dlib::rand rnd;
+        for (auto& x : samples)
+        {
+            x = matrix_cast<float>(gaussian_randm(3,1,rnd.get_random_32bit_number()));
+            labels.push_back(matrix_cast<float>(gaussian_randm(2,1,rnd.get_random_32bit_number())));
+        }
+
+        net_type net;
+        trainer_type trainer(net, sgd());
+        trainer.set_mini_batch_size(2);
+        trainer.set_max_num_epochs(3);
+        trainer.set_learning_rate_scheduler(step_decay_scheduler(0.1, 4, 0.5));
+
+        std::vector<unsigned long long> steps;
+        std::vector<double> step_rates, epoch_losses, new_rates, test_losses;
+        std::vector<unsigned long long> epoch_steps;
+        trainer.add_step_callback([&](const trainer_type::training_progress& p)
+        {
+            DLIB_TEST(&p.net == &net);
+            DLIB_TEST(std::isfinite(p.loss));
+            steps.push_back(p.step);
+            step_rates.push_back(p.learning_rate);
+        });
+        trainer.add_epoch_callback([&](const trainer_type::training_progress& p)
+        {
+            epoch_losses.push_back(p.loss);
+            epoch_steps.push_back(p.step);
+            DLIB_TEST(p.loss == trainer.get_average_loss());
+        });
+        trainer.add_learning_rate_callback([&](const trainer_type::training_progress& p)
+        {
+            new_rates.push_back(p.learning_rate);
+        });
+        trainer.add_test_step_callback([&](const trainer_type::training_progress& p)
+        {
+            test_losses.push_back(p.loss);
+        });
+
+        trainer.train(samples, labels);
+        DLIB_TEST(trainer.get_num_training_steps() == 15);
+        DLIB_TEST(steps.size() == 15);
+        for (size_t i = 0; i < steps.size(); ++i)
+            DLIB_TEST(steps[i] == i+1);
+        DLIB_TEST(step_rates[0] == 0.1);
+        DLIB_TEST(step_rates[4] == 0.05);
+        DLIB_TEST(step_rates[14] == 0.0125);
+        DLIB_TEST(epoch_steps == std::vector<unsigned long long>({5, 10, 15}));
+        DLIB_TEST(new_rates == std::vector<double>({0.05, 0.025, 0.0125}));
+        DLIB_TEST(!trainer.stop_requested());
+
+        trainer.test_one_step(samples, labels);
+        trainer.test_one_step(samples, labels);
+        trainer.get_net(force_flush_to_disk::no);
+        DLIB_TEST(test_losses.size() == 2);
+        DLIB_TEST(std::abs(test_losses[1] - net.compute_loss(samples.begin(), samples.end(), labels.begin())) < 1e-5);
+
+        // The number of steps is saved along with the rest of the trainer state.
+        std::ostringstream sout;
+        serialize(trainer, sout);
+        std::istringstream sin(sout.str());
+        net_type net2;
+        trainer_type trainer2(net2, sgd());
+        deserialize(trainer2, sin);
+        DLIB_TEST(trainer2.get_num_training_steps() == 15);
+
+        // Stopping in the middle of an epoch and then calling train() again picks up
+        // where training left off.
+        trainer.clear_callbacks();
+        trainer.set_max_num_epochs(6);
+        steps.clear();
+        trainer.add_step_callback([&](const trainer_type::training_progress& p)
+        {
+            steps.push_back(p.step);
+            if (p.step == 17)
+                trainer.request_stop();
+        });
+        trainer.train(samples, labels);
+        DLIB_TEST(trainer.stop_requested());
+        DLIB_TEST(trainer.get_num_training_steps() >= 17);
+        DLIB_TEST(trainer.get_num_training_steps() < 20);
+        DLIB_TEST(epoch_steps.size() == 3);
+        trainer.train(samples, labels);
+        DLIB_TEST(!trainer.stop_requested());
+        DLIB_TEST(trainer.get_num_training_steps() == 30);
+        DLIB_TEST(steps.size() == 15);
+        DLIB_TEST(test_losses.size() == 2);
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    void test_trainer_weight_ema()
+    {
+        print_spinner();
+        using net_type = loss_mean_squared_multioutput<fc<2,input<matrix<float>>>>;
+        std::vector<matrix<float>> samples(8, matrix<float>(3,1));
+        std::vector<matrix<float>> labels;
+        dlib::rand rnd;
+        for (auto& x : samples)
+        {
+            x = matrix_cast<float>(gaussian_randm(3,1,rnd.get_random_32bit_number()));
+            labels.push_back(matrix_cast<float>(gaussian_randm(2,1,rnd.get_random_32bit_number())));
+        }
+        auto params = [](net_type& net) -> matrix<float> { return mat(layer<1>(net).layer_details().get_layer_params()); };
+
+        net_type net;
+        dnn_trainer<net_type> trainer(net, sgd(0,0));
+        DLIB_TEST(!trainer.uses_weight_ema());
+        trainer.set_learning_rate(0.1);
+        trainer.set_weight_ema_decay(0.5);
+        DLIB_TEST(trainer.uses_weight_ema());
+        DLIB_TEST(trainer.get_weight_ema_decay() == 0.5);
+
+        std::vector<matrix<float>> steps;
+        for (int i = 0; i < 3; ++i)
+        {
+            trainer.train_one_step(samples, labels);
+            trainer.get_net(force_flush_to_disk::no);
+            steps.push_back(params(net));
+        }
+        const matrix<float> expected = 0.5*(0.5*steps[0] + 0.5*steps[1]) + 0.5*steps[2];
+        DLIB_TEST(max(abs(expected - steps[2])) > 1e-3);
+
+        DLIB_TEST(!trainer.weight_ema_swapped_in());
+        trainer.swap_weight_ema();
+        DLIB_TEST(trainer.weight_ema_swapped_in());
+        DLIB_TEST(max(abs(params(net) - expected)) < 1e-6);
+        std::ostringstream sout;
+        sout << trainer;
+        DLIB_TEST(sout.str().find("weight EMA decay") != std::string::npos);
+
+        // Testing is allowed while the average is swapped in.
+        trainer.test_one_step(samples, labels);
+        DLIB_TEST(std::abs(trainer.get_average_test_loss() - net.compute_loss(samples.begin(), samples.end(), labels.begin())) < 1e-5);
+
+        trainer.swap_weight_ema();
+        DLIB_TEST(!trainer.weight_ema_swapped_in());
+        DLIB_TEST(max(abs(params(net) - steps[2])) == 0);
+
+        // The average is saved along with the rest of the trainer state.
+        sout.str("");
+        serialize(trainer, sout);
+        std::istringstream sin(sout.str());
+        net_type net2;
+        dnn_trainer<net_type> trainer2(net2, sgd(0,0));
+        deserialize(trainer2, sin);
+        DLIB_TEST(trainer2.get_weight_ema_decay() == 0.5);
+        trainer2.swap_weight_ema();
+        DLIB_TEST(max(abs(params(net2) - expected)) < 1e-6);
+        trainer2.swap_weight_ema();
+
+        // One more step moves the average half way to the new parameters.
+        trainer2.train_one_step(samples, labels);
+        trainer2.get_net(force_flush_to_disk::no);
+        const matrix<float> expected2 = 0.5*expected + 0.5*params(net2);
+        trainer2.swap_weight_ema();
+        DLIB_TEST(max(abs(params(net2) - expected2)) < 1e-6);
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    void test_data_loader()
+    {
+        print_spinner();
+        std::vector<std::pair<int,unsigned long>> data;
+        for (int i = 0; i < 10; ++i)
+            data.emplace_back(i, 2*i);
+
+        using loader_type = data_loader<std::vector<std::pair<int,unsigned long>>>;
+        loader_type loader(data, 4);
+        loader.set_shuffle(false);
+        loader.set_num_workers(2);
+        DLIB_TEST(loader.num_batches_per_epoch() == 3);
+        std::vector<int> inputs;
+        std::vector<unsigned long> labels;
+        loader.next(inputs, labels);
+        DLIB_TEST(inputs == std::vector<int>({0,1,2,3}));
+        DLIB_TEST(labels == std::vector<unsigned long>({0,2,4,6}));
+        loader.next(inputs, labels);
+        DLIB_TEST(inputs == std::vector<int>({4,5,6,7}));
+        loader.next(inputs, labels);
+        DLIB_TEST(inputs == std::vector<int>({8,9}));
+        DLIB_TEST(loader.get_epoch() == 1);
+        loader.next(inputs, labels);
+        DLIB_TEST(inputs == std::vector<int>({0,1,2,3}));
+
+        loader.set_drop_last(true);
+        DLIB_TEST(loader.num_batches_per_epoch() == 2);
+        loader.set_batch_position(1);
+        loader.next(inputs, labels);
+        DLIB_TEST(inputs == std::vector<int>({4,5,6,7}));
+        loader.next(inputs, labels);
+        DLIB_TEST(inputs == std::vector<int>({0,1,2,3}));
+
+        // With shuffling and random transforms the batches still only depend on the seed,
+        // not on the number of workers.
+        auto get_stream = [&](unsigned long long seed, size_t num_workers)
+        {
+            auto dataset = make_dataset(data.size(), [&](size_t i) { return data[i]; });
+            data_loader<decltype(dataset)> loader(dataset, 3);
+            loader.set_seed(seed);
+            loader.set_num_workers(num_workers);
+            loader.set_prefetch_depth(1);
+            loader.add_transform([](std::pair<int,unsigned long>& s, dlib::rand&) { s.second = 10*s.first; });
+            loader.add_transform([](std::pair<int,unsigned long>& s, dlib::rand& rnd) { s.second += rnd.get_integer(10); });
+            DLIB_TEST(loader.get_num_transforms() == 2);
+            std::vector<std::pair<int,unsigned long>> stream, batch;
+            for (int i = 0; i < 8; ++i)
+            {
+                loader.next(batch);
+                DLIB_TEST(batch.size() == (i%4 == 3 ? 1 : 3));
+                stream.insert(stream.end(), batch.begin(), batch.end());
+            }
+            return stream;
+        };
+        const auto stream = get_stream(1, 1);
+        DLIB_TEST(stream == get_stream(1, 3));
+        DLIB_TEST(stream != get_stream(2, 1));
+        std::vector<int> epoch1, epoch2;
+        for (size_t i = 0; i < stream.size(); ++i)
+        {
+            DLIB_TEST(stream[i].second/10 == (unsigned long)stream[i].first);
+            (i < 10 ? epoch1 : epoch2).push_back(stream[i].first);
+        }
+        DLIB_TEST(epoch1 != epoch2);
+        std::sort(epoch1.begin(), epoch1.end());
+        std::sort(epoch2.begin(), epoch2.end());
+        DLIB_TEST(epoch1 == std::vector<int>({0,1,2,3,4,5,6,7,8,9}));
+        DLIB_TEST(epoch1 == epoch2);
+
+        // Exceptions thrown while making a batch come out of next().
+        auto bad_dataset = make_dataset(5, [](size_t i) -> int
+        {
+            if (i == 3)
+                throw std::runtime_error("bad sample");
+            return i;
+        });
+        data_loader<decltype(bad_dataset)> bad_loader(bad_dataset, 2);
+        bad_loader.set_shuffle(false);
+        std::vector<int> batch;
+        bad_loader.next(batch);
+        DLIB_TEST(batch == std::vector<int>({0,1}));
+        bool threw = false;
+        try { bad_loader.next(batch); } catch (std::runtime_error&) { threw = true; }
+        DLIB_TEST(threw);
+        DLIB_TEST(bad_loader.get_batch_position() == 1);
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    // A stand-in for a network that just holds an output tensor, so loss layers can be
+    // tested directly on outputs we choose.
+    struct loss_test_subnet
+    {
+        resizable_tensor output;
+        resizable_tensor gradient_input;
+
+        const tensor& get_output() const { return output; }
+        tensor& get_gradient_input() { return gradient_input; }
+        unsigned int sample_expansion_factor() const { return 1; }
+    };
+
+    template <typename loss_type, typename label_type>
+    double loss_gradient_error (
+        const loss_type& loss,
+        const resizable_tensor& output,
+        const std::vector<label_type>& labels
+    )
+    {
+        // Returns the largest difference between the gradient computed by the loss and a
+        // numerical estimate of it.
+        loss_test_subnet sub;
+        sub.output = output;
+        sub.gradient_input.copy_size(output);
+        loss.compute_loss_value_and_gradient(output, labels.begin(), sub);
+        const std::vector<float> analytic(sub.gradient_input.begin(), sub.gradient_input.end());
+
+        double max_error = 0;
+        const float eps = 1e-3;
+        for (size_t i = 0; i < output.size(); ++i)
+        {
+            sub.output = output;
+            sub.output.host()[i] += eps;
+            const double l1 = loss.compute_loss_value_and_gradient(output, labels.begin(), sub);
+            sub.output = output;
+            sub.output.host()[i] -= eps;
+            const double l2 = loss.compute_loss_value_and_gradient(output, labels.begin(), sub);
+            const double numeric = (l1-l2)/(2*eps);
+            max_error = std::max(max_error, std::abs(numeric-analytic[i]));
+        }
+        return max_error;
+    }
+
+    template <typename loss_type, typename label_type>
+    double compute_loss_on_output (
+        const loss_type& loss,
+        const resizable_tensor& output,
+        const std::vector<label_type>& labels
+    )
+    {
+        loss_test_subnet sub;
+        sub.output = output;
+        sub.gradient_input.copy_size(output);
+        return loss.compute_loss_value_and_gradient(output, labels.begin(), sub);
+    }
+
+    void test_loss_multiclass_focal()
+    {
+        print_spinner();
+        dlib::rand rnd;
+        tt::tensor_rand trand;
+
+        const long num_samples = 6;
+        const long num_classes = 4;
+        resizable_tensor output(num_samples, num_classes);
+        trand.fill_gaussian(output, 0, 2);
+        std::vector<unsigned long> labels(num_samples);
+        for (auto& l : labels)
+            l = rnd.get_integer(num_classes);
+
+        // With gamma == 0 the focal loss is the usual multiclass log loss.
+        loss_test_subnet sub1, sub2;
+        sub1.output = output;
+        sub1.gradient_input.copy_size(output);
+        sub2.output = output;
+        sub2.gradient_input.copy_size(output);
+        const double focal0 = loss_multiclass_focal_(0).compute_loss_value_and_gradient(output, labels.begin(), sub1);
+        const double log_loss = loss_multiclass_log_().compute_loss_value_and_gradient(output, labels.begin(), sub2);
+        DLIB_TEST(std::abs(focal0 - log_loss) < 1e-5);
+        DLIB_TEST(max(abs(mat(sub1.gradient_input) - mat(sub2.gradient_input))) < 1e-6);
+
+        // Larger gamma discounts the loss of every sample.
+        const double focal2 = compute_loss_on_output(loss_multiclass_focal_(2), output, labels);
+        DLIB_TEST(0 < focal2 && focal2 < log_loss);
+
+        for (double gamma : {0.5, 1.0, 2.0, 5.0})
+        {
+            const double error = loss_gradient_error(loss_multiclass_focal_(gamma), output, labels);
+            DLIB_TEST_MSG(error < 2e-3, "gamma: " << gamma << ", error: " << error);
+        }
+
+        // The labels are the same as for loss_multiclass_log_.
+        std::vector<unsigned long> focal_labels(num_samples), log_labels(num_samples);
+        loss_multiclass_focal_().to_label(output, sub1, focal_labels.begin());
+        loss_multiclass_log_().to_label(output, sub1, log_labels.begin());
+        DLIB_TEST(focal_labels == log_labels);
+
+        DLIB_TEST(loss_multiclass_focal_().get_gamma() == 2);
+        std::ostringstream sout;
+        serialize(loss_multiclass_focal_(3.5), sout);
+        std::istringstream sin(sout.str());
+        loss_multiclass_focal_ item;
+        deserialize(item, sin);
+        DLIB_TEST(item.get_gamma() == 3.5);
+        sout.str("");
+        sout << item;
+        DLIB_TEST(sout.str() == "loss_multiclass_focal (gamma=3.5)");
+
+        // Make sure it can be used to train a network.
+        std::vector<matrix<float,0,1>> samples;
+        std::vector<unsigned long> sample_labels;
+        for (int i = 0; i < 200; ++i)
+        {
+            matrix<float,0,1> x = matrix_cast<float>(randm(2,1, rnd)) - 0.5;
+            samples.push_back(x);
+            sample_labels.push_back(x(0) > 0 ? (x(1) > 0 ? 0 : 1) : (x(1) > 0 ? 2 : 3));
+        }
+        using net_type = loss_multiclass_focal<fc<4, relu<fc<10, input<matrix<float,0,1>>>>>>;
+        net_type net;
+        dnn_trainer<net_type> trainer(net, sgd(0, 0.9));
+        trainer.set_learning_rate(0.1);
+        trainer.set_mini_batch_size(50);
+        trainer.set_max_num_epochs(300);
+        trainer.train(samples, sample_labels);
+        const std::vector<unsigned long> predictions = net(samples);
+        long num_right = 0;
+        for (size_t i = 0; i < samples.size(); ++i)
+            num_right += predictions[i] == sample_labels[i];
+        DLIB_TEST_MSG(num_right > 180, num_right);
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    void test_loss_multiclass_log_label_smoothing()
+    {
+        print_spinner();
+        dlib::rand rnd;
+        tt::tensor_rand trand;
+
+        const long num_samples = 4;
+        const long num_classes = 3;
+        resizable_tensor output(num_samples, num_classes);
+        trand.fill_gaussian(output, 0, 2);
+        std::vector<unsigned long> labels(num_samples);
+        for (auto& l : labels)
+            l = rnd.get_integer(num_classes);
+
+        DLIB_TEST(loss_multiclass_log_().get_label_smoothing() == 0);
+        DLIB_TEST(std::abs(compute_loss_on_output(loss_multiclass_log_(0), output, labels) -
+                           compute_loss_on_output(loss_multiclass_log_(), output, labels)) < 1e-6);
+
+        for (double ls : {0.0, 0.1, 0.5})
+        {
+            const double error = loss_gradient_error(loss_multiclass_log_(ls), output, labels);
+            DLIB_TEST_MSG(error < 2e-3, "label_smoothing: " << ls << ", error: " << error);
+        }
+
+        // With smoothing, the best output isn't infinitely confident.  It's the one whose
+        // softmax is the smoothed target distribution.
+        const double ls = 0.3;
+        const float on = 1-ls+ls/num_classes;
+        const float off = ls/num_classes;
+        resizable_tensor best(num_samples, num_classes);
+        for (long i = 0; i < num_samples; ++i)
+            for (long k = 0; k < num_classes; ++k)
+                best.host()[i*num_classes+k] = std::log(k == (long)labels[i] ? on : off);
+        loss_test_subnet sub;
+        sub.output = best;
+        sub.gradient_input.copy_size(best);
+        loss_multiclass_log_(ls).compute_loss_value_and_gradient(best, labels.begin(), sub);
+        DLIB_TEST(max(abs(mat(sub.gradient_input))) < 1e-6);
+
+        // Without smoothing the old serialization format is still used, so old models can
+        // still be loaded by older versions of dlib.
+        std::ostringstream sout;
+        serialize(loss_multiclass_log_(), sout);
+        std::istringstream sin(sout.str());
+        std::string version;
+        deserialize(version, sin);
+        DLIB_TEST(version == "loss_multiclass_log_");
+
+        sout.str("");
+        serialize(loss_multiclass_log_(0.25), sout);
+        sin.str(sout.str());
+        sin.clear();
+        loss_multiclass_log_ item;
+        deserialize(item, sin);
+        DLIB_TEST(item.get_label_smoothing() == 0.25);
+        sout.str("");
+        sout << item;
+        DLIB_TEST(sout.str() == "loss_multiclass_log (label_smoothing=0.25)");
+        sout.str("");
+        sout << loss_multiclass_log_();
+        DLIB_TEST(sout.str() == "loss_multiclass_log");
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    void test_loss_dice_per_pixel()
+    {
+        print_spinner();
+        dlib::rand rnd;
+        tt::tensor_rand trand;
+
+        const long num_samples = 2;
+        const long nr = 3;
+        const long nc = 4;
+        const uint16_t ignore = loss_dice_per_pixel_::label_to_ignore;
+
+        // The binary case, where the network outputs a single channel.
+        resizable_tensor output(num_samples, 1, nr, nc);
+        trand.fill_gaussian(output, 0, 2);
+        std::vector<matrix<uint16_t>> labels(num_samples, matrix<uint16_t>(nr, nc));
+        for (auto& l : labels)
+            for (auto& v : l)
+                v = rnd.get_integer(2);
+        labels[0](1,2) = ignore;
+
+        for (auto alpha_beta : {std::make_pair(0.5, 0.5), std::make_pair(0.3, 0.7), std::make_pair(1.0, 0.0)})
+        {
+            const loss_dice_per_pixel_ loss(alpha_beta.first, alpha_beta.second);
+            const double error = loss_gradient_error(loss, output, labels);
+            DLIB_TEST_MSG(error < 2e-3, "binary, alpha: " << alpha_beta.first << ", error: " << error);
+        }
+
+        // Ignored pixels have no effect on the loss or gradient.
+        loss_test_subnet sub;
+        sub.output = output;
+        sub.gradient_input.copy_size(output);
+        const double loss1 = loss_dice_per_pixel_().compute_loss_value_and_gradient(output, labels.begin(), sub);
+        DLIB_TEST(sub.gradient_input.host()[1*nc+2] == 0);
+        resizable_tensor output2 = output;
+        output2.host()[1*nc+2] += 10;
+        DLIB_TEST(std::abs(loss1 - compute_loss_on_output(loss_dice_per_pixel_(), output2, labels)) < 1e-6);
+
+        // Confidently correct outputs give a loss near 0 and the outputs come back as the
+        // labels.
+        resizable_tensor perfect(num_samples, 1, nr, nc);
+        for (long i = 0; i < num_samples; ++i)
+            for (long r = 0; r < nr; ++r)
+                for (long c = 0; c < nc; ++c)
+                    perfect.host()[(i*nr+r)*nc+c] = labels[i](r,c) == 1 ? 20 : -20;
+        DLIB_TEST(compute_loss_on_output(loss_dice_per_pixel_(), perfect, labels) < 1e-6);
+        sub.output = perfect;
+        std::vector<matrix<uint16_t>> predicted(num_samples);
+        loss_dice_per_pixel_().to_label(perfect, sub, predicted.begin());
+        for (long i = 0; i < num_samples; ++i)
+            for (long r = 0; r < nr; ++r)
+                for (long c = 0; c < nc; ++c)
+                    if (labels[i](r,c) != ignore)
+                        DLIB_TEST(predicted[i](r,c) == labels[i](r,c));
+
+        // The multiclass case.
+        const long num_classes = 3;
+        output.set_size(num_samples, num_classes, nr, nc);
+        trand.fill_gaussian(output, 0, 2);
+        for (auto& l : labels)
+            for (auto& v : l)
+                v = rnd.get_integer(num_classes);
+        labels[1](0,0) = ignore;
+        for (auto alpha_beta : {std::make_pair(0.5, 0.5), std::make_pair(0.2, 0.8)})
+        {
+            const loss_dice_per_pixel_ loss(alpha_beta.first, alpha_beta.second, 0.5);
+            const double error = loss_gradient_error(loss, output, labels);
+            DLIB_TEST_MSG(error < 2e-3, "multiclass, alpha: " << alpha_beta.first << ", error: " << error);
+        }
+
+        sub.output = output;
+        std::vector<matrix<uint16_t>> dice_labels(num_samples), log_labels(num_samples);
+        loss_dice_per_pixel_().to_label(output, sub, dice_labels.begin());
+        loss_multiclass_log_per_pixel_().to_label(output, sub, log_labels.begin());
+        for (long i = 0; i < num_samples; ++i)
+            DLIB_TEST(dice_labels[i] == log_labels[i]);
+
+        const loss_dice_per_pixel_ defaults;
+        DLIB_TEST(defaults.get_alpha() == 0.5 && defaults.get_beta() == 0.5 && defaults.get_smoothing() == 1);
+        std::ostringstream sout;
+        serialize(loss_dice_per_pixel_(0.3, 0.7, 2), sout);
+        std::istringstream sin(sout.str());
+        loss_dice_per_pixel_ item;
+        deserialize(item, sin);
+        DLIB_TEST(item.get_alpha() == 0.3 && item.get_beta() == 0.7 && item.get_smoothing() == 2);
+        sout.str("");
+        sout << item;
+        DLIB_TEST(sout.str() == "loss_dice_per_pixel (alpha=0.3, beta=0.7, smoothing=2)");
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    void test_loss_ctc()
+    {
+        print_spinner();
+        dlib::rand rnd;
+        tt::tensor_rand trand;
+
+        // Small enough that we can check the loss by summing over every alignment.
+        const long num_samples = 4;
+        const long num_classes = 3;
+        const long num_steps = 4;
+        resizable_tensor output(num_samples, num_classes, 1, num_steps);
+        trand.fill_gaussian(output, 0, 2);
+        const std::vector<std::vector<unsigned long>> labels = {{1}, {1,1}, {2,1,2}, {}};
+
+        auto collapse = [](const std::vector<unsigned long>& alignment)
+        {
+            std::vector<unsigned long> seq;
+            for (size_t t = 0; t < alignment.size(); ++t)
+            {
+                if (alignment[t] != 0 && (t == 0 || alignment[t] != alignment[t-1]))
+                    seq.push_back(alignment[t]);
+            }
+            return seq;
+        };
+
+        // Returns the probability of every output sequence for sample i.
+        auto sequence_probs = [&](const resizable_tensor& out, long i)
+        {
+            std::map<std::vector<unsigned long>, double> probs;
+            std::vector<unsigned long> alignment(num_steps, 0);
+            for (long n = 0; n < std::pow(num_classes, num_steps); ++n)
+            {
+                long code = n;
+                double p = 1;
+                for (long t = 0; t < num_steps; ++t)
+                {
+                    alignment[t] = code%num_classes;
+                    code /= num_classes;
+                    double norm = 0;
+                    for (long c = 0; c < num_classes; ++c)
+                        norm += std::exp(out.host()[(i*num_classes + c)*num_steps + t]);
+                    p *= std::exp(out.host()[(i*num_classes + alignment[t])*num_steps + t])/norm;
+                }
+                probs[collapse(alignment)] += p;
+            }
+            return probs;
+        };
+
+        double true_loss = 0;
+        for (long i = 0; i < num_samples; ++i)
+            true_loss += -std::log(sequence_probs(output, i)[labels[i]])/num_samples;
+        const double loss = compute_loss_on_output(loss_ctc_(), output, labels);
+        DLIB_TEST_MSG(std::abs(loss - true_loss) < 1e-5, loss << " " << true_loss);
+
+        const double error = loss_gradient_error(loss_ctc_(), output, labels);
+        DLIB_TEST_MSG(error < 2e-3, "error: " << error);
+
+        // A different blank label is just a relabeling of the classes.
+        resizable_tensor swapped = output;
+        for (long i = 0; i < num_samples; ++i)
+            for (long t = 0; t < num_steps; ++t)
+                std::swap(swapped.host()[(i*num_classes + 0)*num_steps + t], swapped.host()[(i*num_classes + 2)*num_steps + t]);
+        std::vector<std::vector<unsigned long>> swapped_labels = labels;
+        for (auto& l : swapped_labels)
+            for (auto& v : l)
+                v = v == 2 ? 0 : v;
+        DLIB_TEST(std::abs(compute_loss_on_output(loss_ctc_(2), swapped, swapped_labels) - loss) < 1e-5);
+
+        // Beam search with a big enough beam finds the most probable sequence, and greedy
+        // decoding takes the best class at each time step.
+        loss_test_subnet sub;
+        sub.output = output;
+        loss_ctc_ decoder;
+        std::vector<std::vector<unsigned long>> greedy(num_samples), beam(num_samples);
+        decoder.to_label(output, sub, greedy.begin());
+        decoder.set_beam_size(100);
+        decoder.to_label(output, sub, beam.begin());
+        for (long i = 0; i < num_samples; ++i)
+        {
+            std::vector<unsigned long> best_path(num_steps);
+            for (long t = 0; t < num_steps; ++t)
+            {
+                float best = -std::numeric_limits<float>::infinity();
+                for (long c = 0; c < num_classes; ++c)
+                {
+                    if (output.host()[(i*num_classes + c)*num_steps + t] > best)
+                    {
+                        best = output.host()[(i*num_classes + c)*num_steps + t];
+                        best_path[t] = c;
+                    }
+                }
+            }
+            DLIB_TEST(greedy[i] == collapse(best_path));
+
+            const auto probs = sequence_probs(output, i);
+            double best_prob = 0;
+            for (const auto& p : probs)
+                best_prob = std::max(best_prob, p.second);
+            DLIB_TEST(std::abs(probs.at(beam[i]) - best_prob) < 1e-6);
+        }
+
+        std::ostringstream sout;
+        serialize(decoder, sout);
+        std::istringstream sin(sout.str());
+        loss_ctc_ item;
+        deserialize(item, sin);
+        DLIB_TEST(item.get_blank_label() == 0 && item.get_beam_size() == 100);
+        sout.str("");
+        sout << loss_ctc_(3);
+        DLIB_TEST(sout.str() == "loss_ctc (blank_label=3, beam_size=1)");
+
+        // Train a network to read sequences where each input column says which class is
+        // there, or 0 for the blank.
+        print_spinner();
+        const long seq_length = 8;
+        std::vector<matrix<float>> samples;
+        std::vector<std::vector<unsigned long>> sample_labels;
+        for (int i = 0; i < 100; ++i)
+        {
+            matrix<float> x(1, seq_length);
+            std::vector<unsigned long> alignment(seq_length);
+            for (long t = 0; t < seq_length; ++t)
+            {
+                alignment[t] = rnd.get_integer(num_classes);
+                x(0,t) = alignment[t];
+            }
+            samples.push_back(x);
+            sample_labels.push_back(collapse(alignment));
+        }
+        using net_type = loss_ctc<con<num_classes,1,1,1,1,relu<con<10,1,1,1,1,input<matrix<float>>>>>>;
+        net_type net;
+        dnn_trainer<net_type, adam> trainer(net, adam(0, 0.9, 0.999));
+        trainer.set_learning_rate(0.01);
+        trainer.set_mini_batch_size(20);
+        trainer.set_max_num_epochs(300);
+        trainer.train(samples, sample_labels);
+        const std::vector<std::vector<unsigned long>> predictions = net(samples);
+        long num_right = 0;
+        for (size_t i = 0; i < samples.size(); ++i)
+            num_right += predictions[i] == sample_labels[i];
+        DLIB_TEST_MSG(num_right > 90, num_right);
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    void test_loss_multiclass_distillation()
+    {
+        print_spinner();
+        dlib::rand rnd;
+        tt::tensor_rand trand;
+
+        const long num_samples = 4;
+        const long num_classes = 3;
+        resizable_tensor output(num_samples, num_classes);
+        trand.fill_gaussian(output, 0, 2);
+        std::vector<distillation_label> labels(num_samples);
+        std::vector<unsigned long> hard_labels(num_samples);
+        for (long i = 0; i < num_samples; ++i)
+        {
+            hard_labels[i] = labels[i].label = rnd.get_integer(num_classes);
+            labels[i].teacher_logits = matrix_cast<float>(2*gaussian_randm(num_classes, 1, i));
+        }
+
+        const double T = 3, alpha = 0.7;
+        double true_loss = 0;
+        for (long i = 0; i < num_samples; ++i)
+        {
+            const matrix<double,0,1> s = matrix_cast<double>(trans(rowm(mat(output), i)));
+            const matrix<double,0,1> z = matrix_cast<double>(labels[i].teacher_logits);
+            const matrix<double,0,1> p = exp(z/T)/sum(exp(z/T));
+            const matrix<double,0,1> q = exp(s/T)/sum(exp(s/T));
+            const double kl = sum(pointwise_multiply(p, log(p) - log(q)));
+            const double ce = -std::log(std::exp(s(labels[i].label))/sum(exp(s)));
+            true_loss += (alpha*T*T*kl + (1-alpha)*ce)/num_samples;
+        }
+        const double loss = compute_loss_on_output(loss_multiclass_distillation_(T, alpha), output, labels);
+        DLIB_TEST_MSG(std::abs(loss - true_loss) < 1e-5, loss << " " << true_loss);
+
+        for (double a : {0.0, 0.5, 1.0})
+        {
+            const double error = loss_gradient_error(loss_multiclass_distillation_(2, a), output, labels);
+            DLIB_TEST_MSG(error < 2e-3, "alpha: " << a << ", error: " << error);
+        }
+
+        // Without the distillation term it's just loss_multiclass_log_.
+        DLIB_TEST(std::abs(compute_loss_on_output(loss_multiclass_distillation_(T, 0), output, labels) -
+                           compute_loss_on_output(loss_multiclass_log_(), output, hard_labels)) < 1e-5);
+
+        // A student that outputs exactly what the teacher does has nothing to learn from it.
+        for (long i = 0; i < num_samples; ++i)
+            labels[i].teacher_logits = trans(rowm(mat(output), i));
+        loss_test_subnet sub;
+        sub.output = output;
+        sub.gradient_input.copy_size(output);
+        DLIB_TEST(std::abs(loss_multiclass_distillation_(T, 1).compute_loss_value_and_gradient(output, labels.begin(), sub)) < 1e-6);
+        DLIB_TEST(max(abs(mat(sub.gradient_input))) < 1e-6);
+
+        std::ostringstream sout;
+        serialize(loss_multiclass_distillation_(2, 0.25), sout);
+        serialize(labels[1], sout);
+        std::istringstream sin(sout.str());
+        loss_multiclass_distillation_ item;
+        distillation_label label;
+        deserialize(item, sin);
+        deserialize(label, sin);
+        DLIB_TEST(item.get_temperature() == 2 && item.get_alpha() == 0.25);
+        DLIB_TEST(label.label == labels[1].label && label.teacher_logits == labels[1].teacher_logits);
+        sout.str("");
+        sout << loss_multiclass_distillation_();
+        DLIB_TEST(sout.str() == "loss_multiclass_distillation (temperature=4, alpha=0.9)");
+
+        // Train a student from a teacher that's a fixed linear classifier, giving it the
+        // teacher's outputs for each mini-batch as it's made.
+        print_spinner();
+        using teacher_type = loss_multiclass_log<fc<num_classes,input<matrix<float>>>>;
+        using student_type = loss_multiclass_distillation<fc<num_classes,relu<fc<10,input<matrix<float>>>>>>;
+        auto make_sample = [&]()
+        {
+            matrix<float> x(1, 2);
+            x = rnd.get_random_gaussian(), rnd.get_random_gaussian();
+            return x;
+        };
+        teacher_type teacher;
+        teacher(make_sample());
+        const float w[] = {2, -1, -1,
+                           0, 1.7, -1.7,
+                           0, 0, 0};
+        tensor& teacher_params = layer<1>(teacher).layer_details().get_layer_params();
+        DLIB_TEST(teacher_params.size() == 9);
+        std::copy(w, w+9, teacher_params.host());
+
+        std::vector<matrix<float>> samples(100);
+        for (auto& x : samples)
+            x = make_sample();
+        const std::vector<unsigned long> teacher_labels = teacher(samples);
+        const auto distill_labels = make_distillation_labels(teacher, samples, teacher_labels, 16);
+        DLIB_TEST(distill_labels.size() == samples.size());
+        DLIB_TEST(distill_labels[20].label == teacher_labels[20]);
+        DLIB_TEST(max(abs(distill_labels[20].teacher_logits - trans(mat(teacher.subnet()(samples[20]))))) < 1e-6);
+
+        student_type student(loss_multiclass_distillation_(2, 0.5));
+        dnn_trainer<student_type, adam> trainer(student, adam(0, 0.9, 0.999));
+        trainer.set_learning_rate(0.01);
+        for (int step = 0; step < 600; ++step)
+        {
+            std::vector<matrix<float>> batch(32);
+            for (auto& x : batch)
+                x = make_sample();
+            trainer.train_one_step(batch, make_distillation_labels(teacher, batch, teacher(batch)));
+        }
+        trainer.get_net();
+        const std::vector<unsigned long> student_labels = student(samples);
+        long num_agree = 0;
+        for (size_t i = 0; i < samples.size(); ++i)
+            num_agree += student_labels[i] == teacher_labels[i];
+        DLIB_TEST_MSG(num_agree >= 95, num_agree);
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    template <typename weighted_loss_type, typename loss_type, typename weighted_label_type, typename label_type>
+    void check_weighted_loss (
+        const weighted_loss_type& weighted_loss,
+        const loss_type& loss,
+        const resizable_tensor& output,
+        const std::vector<weighted_label_type>& weighted_labels,
+        const std::vector<label_type>& labels,
+        const long ignored_idx
+    )
+    {
+        // The weighted loss matches the unweighted loss on the given unweighted labels.
+        loss_test_subnet sub1, sub2;
+        sub1.output = output;
+        sub1.gradient_input.copy_size(output);
+        sub2.output = output;
+        sub2.gradient_input.copy_size(output);
+        const double l1 = weighted_loss.compute_loss_value_and_gradient(output, weighted_labels.begin(), sub1);
+        const double l2 = loss.compute_loss_value_and_gradient(output, labels.begin(), sub2);
+        DLIB_TEST_MSG(std::abs(l1 - l2) < 1e-5, weighted_loss << ": " << l1 << " vs " << l2);
+        DLIB_TEST(max(abs(mat(sub1.gradient_input) - mat(sub2.gradient_input))) < 1e-6);
+
+        // The output with a weight of 0 doesn't affect the loss.
+        DLIB_TEST(sub1.gradient_input.host()[ignored_idx] == 0);
+        resizable_tensor output2 = output;
+        output2.host()[ignored_idx] += 10;
+        DLIB_TEST(std::abs(l1 - compute_loss_on_output(weighted_loss, output2, weighted_labels)) < 1e-5);
+
+        // The weighted loss outputs the same labels as the unweighted loss.
+        std::vector<typename loss_type::output_label_type> out1(output.num_samples()), out2(output.num_samples());
+        weighted_loss.to_label(output, sub1, out1.begin());
+        loss.to_label(output, sub1, out2.begin());
+        DLIB_TEST(out1 == out2);
+    }
+
+    void test_weighted_regression_losses()
+    {
+        print_spinner();
+        dlib::rand rnd;
+        tt::tensor_rand trand;
+
+        const long num_samples = 4;
+        const long nr = 3;
+        const long nc = 4;
+        const long k = 3;
+        using wl = weighted_label<float>;
+
+        // Each weighted loss is given weights of 1 except for one target with a weight of
+        // 0.  It's compared to its unweighted counterpart on labels where that target is
+        // set to the output itself, so it contributes nothing there either.
+        {
+            resizable_tensor output(num_samples);
+            trand.fill_gaussian(output, 0, 2);
+            std::vector<wl> weighted_labels(num_samples);
+            std::vector<float> labels(num_samples);
+            for (long i = 0; i < num_samples; ++i)
+            {
+                labels[i] = rnd.get_random_gaussian();
+                weighted_labels[i] = wl(labels[i]);
+            }
+            weighted_labels[2] = wl(1000, 0);
+            labels[2] = output.host()[2];
+            check_weighted_loss(loss_mean_squared_weighted_(), loss_mean_squared_(), output, weighted_labels, labels, 2);
+        }
+        {
+            resizable_tensor output(num_samples, k);
+            trand.fill_gaussian(output, 0, 2);
+            std::vector<matrix<wl>> weighted_labels(num_samples, matrix<wl>(k,1));
+            std::vector<matrix<float>> labels(num_samples, matrix<float>(k,1));
+            for (long i = 0; i < num_samples; ++i)
+            {
+                for (long j = 0; j < k; ++j)
+                {
+                    labels[i](j) = rnd.get_random_gaussian();
+                    weighted_labels[i](j) = wl(labels[i](j));
+                }
+            }
+            weighted_labels[1](2) = wl(1000, 0);
+            labels[1](2) = output.host()[1*k+2];
+            check_weighted_loss(loss_mean_squared_multioutput_weighted_(), loss_mean_squared_multioutput_(), output, weighted_labels, labels, 1*k+2);
+        }
+        {
+            resizable_tensor output(num_samples, 1, nr, nc);
+            trand.fill_gaussian(output, 0, 2);
+            std::vector<matrix<wl>> weighted_labels(num_samples, matrix<wl>(nr,nc));
+            std::vector<matrix<float>> labels(num_samples, matrix<float>(nr,nc));
+            for (long i = 0; i < num_samples; ++i)
+            {
+                for (long r = 0; r < nr; ++r)
+                {
+                    for (long c = 0; c < nc; ++c)
+                    {
+                        labels[i](r,c) = rnd.get_random_gaussian();
+                        weighted_labels[i](r,c) = wl(labels[i](r,c));
+                    }
+                }
+            }
+            weighted_labels[3](1,2) = wl(1000, 0);
+            labels[3](1,2) = output.host()[(3*nr+1)*nc+2];
+            check_weighted_loss(loss_mean_squared_per_pixel_weighted_(), loss_mean_squared_per_pixel_(), output, weighted_labels, labels, (3*nr+1)*nc+2);
+        }
+        {
+            // For the binary loss, a label of y with a weight of w is the same as a label
+            // of y*w in loss_binary_log_per_pixel_.
+            resizable_tensor output(num_samples, 1, nr, nc);
+            trand.fill_gaussian(output, 0, 2);
+            std::vector<matrix<wl>> weighted_labels(num_samples, matrix<wl>(nr,nc));
+            std::vector<matrix<float>> labels(num_samples, matrix<float>(nr,nc));
+            for (long i = 0; i < num_samples; ++i)
+            {
+                for (long r = 0; r < nr; ++r)
+                {
+                    for (long c = 0; c < nc; ++c)
+                    {
+                        const float y = rnd.get_random_float() < 0.5 ? -1 : 1;
+                        const float w = 0.5 + rnd.get_random_float();
+                        weighted_labels[i](r,c) = wl(y, w);
+                        labels[i](r,c) = y*w;
+                    }
+                }
+            }
+            weighted_labels[0](2,3) = wl(1, 0);
+            labels[0](2,3) = 0;
+            check_weighted_loss(loss_binary_log_per_pixel_weighted_(), loss_binary_log_per_pixel_(), output, weighted_labels, labels, 2*nc+3);
+            const double error = loss_gradient_error(loss_binary_log_per_pixel_weighted_(), output, weighted_labels);
+            DLIB_TEST_MSG(error < 2e-3, error);
+        }
+
+        // Training with masked targets ignores them.  Here every pixel's target is 2*x-1,
+        // except that a third of the pixels have a wildly wrong target with a weight of 0.
+        print_spinner();
+        std::vector<matrix<float>> samples;
+        std::vector<matrix<wl>> targets;
+        for (int i = 0; i < 100; ++i)
+        {
+            matrix<float> x = matrix_cast<float>(randm(nr, nc, rnd));
+            matrix<wl> y(nr, nc);
+            for (long r = 0; r < nr; ++r)
+            {
+                for (long c = 0; c < nc; ++c)
+                {
+                    if (rnd.get_random_float() < 0.33)
+                        y(r,c) = wl(1000, 0);
+                    else
+                        y(r,c) = wl(2*x(r,c)-1);
+                }
+            }
+            samples.push_back(x);
+            targets.push_back(y);
+        }
+        using net_type = loss_mean_squared_per_pixel_weighted<con<1,1,1,1,1,input<matrix<float>>>>;
+        net_type net;
+        dnn_trainer<net_type> trainer(net, sgd(0, 0.9));
+        trainer.set_learning_rate(0.1);
+        trainer.set_mini_batch_size(20);
+        trainer.set_max_num_epochs(100);
+        trainer.train(samples, targets);
+        const float weight = layer<1>(net).layer_details().get_layer_params().host()[0];
+        const float bias = layer<1>(net).layer_details().get_layer_params().host()[1];
+        DLIB_TEST_MSG(std::abs(weight - 2) < 0.01 && std::abs(bias + 1) < 0.01, "weight: " << weight << ", bias: " << bias);
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    void test_loss_keypoint_heatmap()
+    {
+        print_spinner();
+        dlib::rand rnd;
+
+        // Check the gradient numerically on a network whose output has a stride of 2, with
+        // one part that is present and one that isn't.
+        {
+            using net_type = loss_keypoint_heatmap<con<2,3,3,2,2,input<matrix<float>>>>;
+            net_type net(loss_keypoint_heatmap_(1.5));
+            std::vector<matrix<float>> images;
+            std::vector<full_object_detection> dets;
+            for (int i = 0; i < 2; ++i)
+            {
+                images.push_back(matrix_cast<float>(randm(15, 17, rnd)));
+                const point p(rnd.get_integer(17), rnd.get_integer(15));
+                dets.push_back(full_object_detection(get_rect(images.back()), {p, OBJECT_PART_NOT_PRESENT}));
+            }
+            resizable_tensor x;
+            net.to_tensor(images.begin(), images.end(), x);
+            net.compute_parameter_gradients(x, dets.begin());
+            const tensor& grad = layer<1>(net).get_parameter_gradient();
+            const std::vector<float> analytic(grad.begin(), grad.end());
+
+            tensor& params = layer<1>(net).layer_details().get_layer_params();
+            const float eps = 1e-2;
+            double max_error = 0;
+            for (size_t i = 0; i < params.size(); ++i)
+            {
+                const float old = params.host()[i];
+                params.host()[i] = old + eps;
+                const double l1 = net.compute_loss(x, dets.begin());
+                params.host()[i] = old - eps;
+                const double l2 = net.compute_loss(x, dets.begin());
+                params.host()[i] = old;
+                max_error = std::max(max_error, std::abs((l1-l2)/(2*eps) - analytic[i]));
+            }
+            DLIB_TEST_MSG(max_error < 1e-3, max_error);
+        }
+
+        // Decoding finds the peaks of Gaussian heatmaps to within a pixel, even though the
+        // network halves the resolution.  The last channel is empty, so that part isn't
+        // present.
+        {
+            print_spinner();
+            using image_type = std::array<matrix<float>,3>;
+            using net_type = loss_keypoint_heatmap<avg_pool<3,3,2,2,input<image_type>>>;
+            net_type net;
+            for (int iter = 0; iter < 20; ++iter)
+            {
+                const dpoint truth[2] = {
+                    dpoint(8 + 24*rnd.get_random_double(), 8 + 24*rnd.get_random_double()),
+                    dpoint(8 + 24*rnd.get_random_double(), 8 + 24*rnd.get_random_double())
+                };
+                image_type img;
+                for (long k = 0; k < 3; ++k)
+                {
+                    img[k] = zeros_matrix<float>(40, 40);
+                    if (k == 2)
+                        continue;
+                    for (long r = 0; r < 40; ++r)
+                        for (long c = 0; c < 40; ++c)
+                            img[k](r,c) = std::exp(-length_squared(dpoint(c,r) - truth[k])/(2*4*4));
+                }
+
+                const full_object_detection det = net(img);
+                DLIB_TEST(det.get_rect() == rectangle(0, 0, 39, 39));
+                DLIB_TEST(det.num_parts() == 3);
+                for (long k = 0; k < 2; ++k)
+                    DLIB_TEST_MSG(length(dpoint(det.part(k)) - truth[k]) <= 1, det.part(k) << " vs " << truth[k]);
+                DLIB_TEST(det.part(2) == OBJECT_PART_NOT_PRESENT);
+            }
+        }
+
+        // A network can be trained to find a bright dot.  The second part is never
+        // present.
+        {
+            print_spinner();
+            auto make_sample = [&](matrix<float>& img, full_object_detection& det)
+            {
+                img = zeros_matrix<float>(16, 16);
+                const point p(3 + rnd.get_integer(10), 3 + rnd.get_integer(10));
+                img(p.y(), p.x()) = 1;
+                det = full_object_detection(get_rect(img), {p, OBJECT_PART_NOT_PRESENT});
+            };
+
+            using net_type = loss_keypoint_heatmap<con<2,5,5,1,1,input<matrix<float>>>>;
+            net_type net(loss_keypoint_heatmap_(1));
+            dnn_trainer<net_type, adam> trainer(net, adam(0, 0.9, 0.999));
+            trainer.set_learning_rate(0.01);
+            std::vector<matrix<float>> images(16);
+            std::vector<full_object_detection> dets(16);
+            for (int step = 0; step < 500; ++step)
+            {
+                for (size_t i = 0; i < images.size(); ++i)
+                    make_sample(images[i], dets[i]);
+                trainer.train_one_step(images, dets);
+            }
+            trainer.get_net();
+
+            for (size_t i = 0; i < images.size(); ++i)
+                make_sample(images[i], dets[i]);
+            const std::vector<full_object_detection> predicted = net(images);
+            for (size_t i = 0; i < images.size(); ++i)
+            {
+                DLIB_TEST_MSG(predicted[i].part(0) == dets[i].part(0), predicted[i].part(0) << " vs " << dets[i].part(0));
+                DLIB_TEST(predicted[i].part(1) == OBJECT_PART_NOT_PRESENT);
+            }
+        }
+
+        std::ostringstream sout;
+        serialize(loss_keypoint_heatmap_(3, 0.5), sout);
+        std::istringstream sin(sout.str());
+        loss_keypoint_heatmap_ item;
+        DLIB_TEST(item.get_sigma() == 2 && item.get_presence_threshold() == 0.25);
+        deserialize(item, sin);
+        DLIB_TEST(item.get_sigma() == 3 && item.get_presence_threshold() == 0.5);
+        sout.str("");
+        sout << item;
+        DLIB_TEST(sout.str() == "loss_keypoint_heatmap (sigma=3, presence_threshold=0.5)");
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    void test_int8_tensor()
+    {
+        print_spinner();
+        DLIB_TEST(float_to_int8(0, 0.5f) == 0);
+        DLIB_TEST(float_to_int8(1.2f, 0.5f) == 2);
+        DLIB_TEST(float_to_int8(-1.3f, 0.5f) == -3);
+        DLIB_TEST(float_to_int8(1000, 0.5f) == 127);
+        DLIB_TEST(float_to_int8(-1000, 0.5f) == -127);
+        DLIB_TEST(int8_scale_for_range(0) == 1);
+        DLIB_TEST(int8_scale_for_range(254) == 2);
+
+        resizable_tensor t(4,3,5,2);
+        tt::tensor_rand rnd(0);
+        rnd.fill_gaussian(t);
+        // give each sample a very different magnitude
+        for (long n = 0; n < t.num_samples(); ++n)
+            for (long i = 0; i < t.k()*t.nr()*t.nc(); ++i)
+                t.host()[n*t.k()*t.nr()*t.nc() + i] *= std::pow(10.0f, n-2);
+
+        int8_tensor q(t);
+        DLIB_TEST(q.num_samples() == 4 && q.k() == 3 && q.nr() == 5 && q.nc() == 2);
+        DLIB_TEST(q.size() == t.size());
+        resizable_tensor back;
+        q.copy_to(back);
+        DLIB_TEST(have_same_dimensions(back, t));
+        const long sample_size = t.k()*t.nr()*t.nc();
+        for (long n = 0; n < t.num_samples(); ++n)
+        {
+            const matrix<float> s = rowm(mat(t), n);
+            DLIB_TEST(std::abs(q.get_scale(n) - max(abs(s))/127) < 1e-6*max(abs(s)));
+            // each sample uses the full 8 bit range
+            int8_t biggest = 0;
+            for (long i = 0; i < sample_size; ++i)
+                biggest = std::max<int8_t>(biggest, std::abs(q.host()[n*sample_size+i]));
+            DLIB_TEST(biggest == 127);
+            DLIB_TEST(max(abs(rowm(mat(back),n) - s)) <= q.get_scale(n)/2*1.0001);
+        }
+
+        q.assign(t, 0.01f);
+        for (long n = 0; n < t.num_samples(); ++n)
+            DLIB_TEST(q.get_scale(n) == 0.01f);
+        for (size_t i = 0; i < t.size(); ++i)
+            DLIB_TEST(q.host()[i] == float_to_int8(t.host()[i], 0.01f));
+
+        std::ostringstream sout;
+        serialize(q, sout);
+        DLIB_TEST(sout.str().size() < t.size() + 64);
+        std::istringstream sin(sout.str());
+        int8_tensor q2;
+        deserialize(q2, sin);
+        DLIB_TEST(q2.num_samples() == 4 && q2.k() == 3 && q2.nr() == 5 && q2.nc() == 2);
+        DLIB_TEST(std::equal(q.host(), q.host()+q.size(), q2.host()));
+        for (long n = 0; n < t.num_samples(); ++n)
+            DLIB_TEST(q2.get_scale(n) == q.get_scale(n));
+
+        q.clear();
+        DLIB_TEST(q.size() == 0 && q.num_samples() == 0);
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    void test_int8_kernels()
+    {
+        print_spinner();
+        tt::tensor_rand rnd(0);
+        const float input_scale = 3.0f/127;
+
+        // The int8 kernels should compute exactly what the float kernels compute on the
+        // dequantized inputs and weights, up to float rounding.
+        auto dequantized_input = [&](const tensor& x)
+        {
+            int8_tensor temp;
+            temp.assign(x, input_scale);
+            resizable_tensor xq;
+            temp.copy_to(xq);
+            return xq;
+        };
+
+        {
+            resizable_tensor x(5,2,3,4), w(6,24), b(1,6);
+            rnd.fill_gaussian(x);
+            rnd.fill_gaussian(w);
+            rnd.fill_gaussian(b);
+            const int8_tensor qw(w);
+            resizable_tensor wd;
+            qw.copy_to(wd);
+            const resizable_tensor xd = dequantized_input(x);
+
+            resizable_tensor out;
+            tt::int8_fc(out, x, input_scale, qw, b);
+            DLIB_TEST(out.num_samples() == 5 && out.k() == 6 && out.nr() == 1 && out.nc() == 1);
+            matrix<float> expected = mat(xd)*trans(mat(wd));
+            for (long r = 0; r < expected.nr(); ++r)
+                set_rowm(expected, r) += mat(b);
+            DLIB_TEST(max(abs(mat(out) - expected)) < 1e-4);
+
+            resizable_tensor no_bias;
+            tt::int8_fc(out, x, input_scale, qw, no_bias);
+            DLIB_TEST(max(abs(mat(out) - mat(xd)*trans(mat(wd)))) < 1e-4);
+        }
+
+        struct conv_setting { long groups; int stride, padding, dilation; bool relu; };
+        for (auto cs : {conv_setting{1,1,1,1,false}, conv_setting{1,2,0,1,true},
+                        conv_setting{2,1,2,2,false}, conv_setting{4,1,1,1,true}})
+        {
+            resizable_tensor x(2,4,9,8), f(8,4/cs.groups,3,3), b(1,8);
+            rnd.fill_gaussian(x);
+            rnd.fill_gaussian(f);
+            rnd.fill_gaussian(b);
+            const int8_tensor qf(f);
+            resizable_tensor fd;
+            qf.copy_to(fd);
+            const resizable_tensor xd = dequantized_input(x);
+
+            resizable_tensor out, expected;
+            tt::int8_conv(out, x, input_scale, qf, b, cs.stride, cs.stride, cs.padding, cs.padding,
+                cs.dilation, cs.dilation, cs.groups, cs.relu);
+
+            tt::tensor_conv conv;
+            conv.setup(xd, fd, cs.stride, cs.stride, cs.padding, cs.padding, cs.dilation, cs.dilation, cs.groups);
+            conv(false, expected, xd, fd);
+            tt::add(1, expected, 1, b);
+            if (cs.relu)
+                tt::relu(expected, expected);
+
+            DLIB_TEST(have_same_dimensions(out, expected));
+            DLIB_TEST_MSG(max(abs(mat(out) - mat(expected))) < 1e-4, max(abs(mat(out) - mat(expected))));
+        }
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    void test_int8_quantization()
+    {
+        print_spinner();
+        // A little image classification problem: each image has a horizontal, vertical, or
+        // diagonal line in it.
+        dlib::rand rnd(0);
+        auto make_sample = [&](unsigned long label)
+        {
+            matrix<float> img = matrix_cast<float>(0.3*gaussian_randm(10,10,rnd.get_random_32bit_number()));
+            const long p = rnd.get_integer_in_range(2,8);
+            for (long i = 0; i < 10; ++i)
+            {
+                if (label == 0) img(p,i) += 1;
+                else if (label == 1) img(i,p) += 1;
+                else img(i,i) += 1;
+            }
+            return img;
+        };
+        std::vector<matrix<float>> samples, test_samples;
+        std::vector<unsigned long> labels, test_labels;
+        for (int i = 0; i < 300; ++i)
+        {
+            samples.push_back(make_sample(i%3));
+            labels.push_back(i%3);
+            test_samples.push_back(make_sample(i%3));
+            test_labels.push_back(i%3);
+        }
+
+        using net_type = loss_multiclass_log<fc<3,relu<fc<16,max_pool<2,2,2,2,relu<con<8,3,3,1,1,input<matrix<float>>>>>>>>>;
+        net_type net;
+        dnn_trainer<net_type> trainer(net, sgd(0.0005, 0.9));
+        trainer.set_learning_rate(0.01);
+        trainer.set_mini_batch_size(50);
+        trainer.set_max_num_epochs(40);
+        trainer.train(samples, labels);
+        net.clean();
+
+        auto accuracy = [&](net_type& n)
+        {
+            const std::vector<unsigned long> predicted = n(test_samples);
+            double correct = 0;
+            for (size_t i = 0; i < predicted.size(); ++i)
+                correct += predicted[i] == test_labels[i];
+            return correct/predicted.size();
+        };
+
+        net_type qnet = net;
+        const auto ranges = calibrate_int8_activation_ranges(qnet, samples.begin(), samples.begin()+100);
+        DLIB_TEST(ranges.size() == net_type::num_layers);
+        DLIB_TEST(ranges[1] > 0);  // fc<3>
+        DLIB_TEST(ranges[3] > 0);  // fc<16>
+        DLIB_TEST(ranges[6] > 0);  // con<8>
+        DLIB_TEST(ranges[0] == 0 && ranges[2] == 0 && ranges[4] == 0 && ranges[5] == 0 && ranges[7] == 0);
+        // the input to the last fc layer comes after a relu
+        const tensor& relu_out = layer<2>(qnet).get_output();
+        DLIB_TEST(ranges[1] >= max(mat(relu_out)));
+
+        quantize_to_int8(qnet, ranges);
+        DLIB_TEST(layer<1>(qnet).layer_details().is_quantized());
+        DLIB_TEST(layer<3>(qnet).layer_details().is_quantized());
+        DLIB_TEST(layer<6>(qnet).layer_details().is_quantized());
+        DLIB_TEST(count_parameters(qnet) == 0);
+        DLIB_TEST(layer<6>(qnet).layer_details().get_int8_input_scale() == int8_scale_for_range(ranges[6]));
+
+        std::ostringstream sout;
+        sout << qnet;
+        DLIB_TEST(sout.str().find("int8") != std::string::npos);
+
+        const double float_acc = accuracy(net);
+        const double int8_acc = accuracy(qnet);
+        dlog << LINFO << "float accuracy: " << float_acc << ", int8 accuracy: " << int8_acc;
+        DLIB_TEST_MSG(float_acc > 0.9, float_acc);
+        DLIB_TEST_MSG(int8_acc > float_acc - 0.02, float_acc << " " << int8_acc);
+
+        // The outputs of the two networks should be close.
+        resizable_tensor x;
+        net.to_tensor(test_samples.begin(), test_samples.end(), x);
+        const matrix<float> out_float = mat(net.subnet().forward(x));
+        const matrix<float> out_int8 = mat(qnet.subnet().forward(x));
+        const double rel_err = length(out_float - out_int8)/length(out_float);
+        dlog << LINFO << "int8 relative output error: " << rel_err;
+        DLIB_TEST_MSG(rel_err < 0.05, rel_err);
+
+        // The quantized net serializes in a quarter of the space and gives the same
+        // outputs when loaded.
+        std::ostringstream float_out, int8_out;
+        net.clean();
+        qnet.clean();
+        serialize(net, float_out);
+        serialize(qnet, int8_out);
+        dlog << LINFO << "float net size: " << float_out.str().size() << ", int8 net size: " << int8_out.str().size();
+        DLIB_TEST(int8_out.str().size() < float_out.str().size()/3);
+        net_type qnet2;
+        std::istringstream sin(int8_out.str());
+        deserialize(qnet2, sin);
+        DLIB_TEST(layer<6>(qnet2).layer_details().is_quantized());
+        DLIB_TEST(mat(qnet2.subnet().forward(x)) == out_int8);
+
+        // and the float net is unchanged by all this
+        net_type net2;
+        std::istringstream sin2(float_out.str());
+        deserialize(net2, sin2);
+        DLIB_TEST(!layer<6>(net2).layer_details().is_quantized());
+        DLIB_TEST(mat(net2.subnet().forward(x)) == out_float);
+
+        // quantize_to_int8() skips layers that are already quantized
+        quantize_to_int8(qnet, samples.begin(), samples.begin()+10);
+        DLIB_TEST(mat(qnet.subnet().forward(x)) == out_int8);
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    void test_net_summary()
+    {
+        print_spinner();
+        using net_type = loss_multiclass_log<fc<10,
+                         add_prev1<relu<con<8,3,3,1,1,
+                         tag1<relu<bn_con<con<8,5,5,2,2,
+                         input<matrix<float>>>>>>>>>>>;
+        net_type net;
+        DLIB_TEST(count_parameters(net) == 0);
+        const net_summary_table summary = net_summary(net, 20, 30);
+        DLIB_TEST(summary.layers.size() == net_type::num_layers);
+        DLIB_TEST(summary.num_parameters() == count_parameters(net));
+
+        const auto has_shape = [&](size_t i, long k, long nr, long nc)
+        {
+            const layer_summary& s = summary.layers[i];
+            return s.index == i && s.num_samples == 1 && s.k == k && s.nr == nr && s.nc == nc;
+        };
+        DLIB_TEST(summary.layers[0].type == "loss_multiclass_log");
+        DLIB_TEST(summary.layers[0].num_samples == 0 && summary.layers[0].activation_memory == 0);
+        DLIB_TEST(summary.layers[1].type == "fc" && has_shape(1, 10, 1, 1));
+        DLIB_TEST(summary.layers[2].type == "add_prev1" && has_shape(2, 8, 8, 13));
+        DLIB_TEST(summary.layers[3].type == "relu" && has_shape(3, 8, 8, 13));
+        DLIB_TEST(summary.layers[4].type == "con" && has_shape(4, 8, 8, 13));
+        DLIB_TEST(summary.layers[5].type == "tag1" && has_shape(5, 8, 8, 13));
+        DLIB_TEST(summary.layers[8].type == "con" && has_shape(8, 8, 8, 13));
+        DLIB_TEST(summary.layers[9].type == "input<matrix>" && has_shape(9, 1, 20, 30));
+
+        DLIB_TEST(summary.layers[1].num_parameters == (8*8*13+1)*10);
+        DLIB_TEST(summary.layers[4].num_parameters == 8*8*3*3+8);
+        DLIB_TEST(summary.layers[1].num_multiply_adds == 10*8*8*13);
+        DLIB_TEST(summary.layers[4].num_multiply_adds == 8*8*13*8*3*3);
+        DLIB_TEST(summary.layers[8].num_multiply_adds == 8*8*13*1*5*5);
+        DLIB_TEST(summary.layers[2].num_multiply_adds == 0);
+        DLIB_TEST(summary.num_multiply_adds() == 10*8*8*13 + 8*8*13*8*3*3 + 8*8*13*1*5*5);
+
+        // The relu layers run in-place, so only the con, bn_con, add_prev, fc and input
+        // layers have their own outputs.
+        DLIB_TEST(summary.layers[3].activation_memory == 0);
+        DLIB_TEST(summary.layers[6].activation_memory == 0);
+        DLIB_TEST(summary.layers[5].activation_memory == 0);
+        DLIB_TEST(summary.layers[4].activation_memory == 8*8*13*sizeof(float));
+        DLIB_TEST(summary.activation_memory() == (4*8*8*13 + 10 + 20*30)*sizeof(float));
+
+        std::ostringstream sout;
+        sout << summary;
+        DLIB_TEST(sout.str().find("1x8x8x13") != std::string::npos);
+        DLIB_TEST(sout.str().find("total") != std::string::npos);
+
+        // Larger inputs give larger outputs but the same convolution filters.
+        net_type net2;
+        matrix<float> img(40, 30);
+        img = 1;
+        const net_summary_table big_summary = net_summary(net2, img);
+        DLIB_TEST(big_summary.layers[4].num_parameters == summary.layers[4].num_parameters);
+        DLIB_TEST(big_summary.layers[4].nr == 18 && big_summary.layers[4].nc == 13);
+
+        // Recurrent and attention layers report their multiply-adds too.
+        using seq_net_type = fc<3,multihead_attention<2,lstm<4,input<matrix<float>>>>>;
+        seq_net_type seq_net;
+        const net_summary_table seq_summary = net_summary(seq_net, 5, 6);
+        DLIB_TEST(seq_summary.layers[2].type == "lstm" && seq_summary.layers[2].nr == 5 && seq_summary.layers[2].nc == 4);
+        DLIB_TEST(seq_summary.layers[2].num_multiply_adds == 5*(6+4)*4*4);
+        DLIB_TEST(seq_summary.layers[1].num_multiply_adds == 5*4*4*4 + 2*5*5*4);
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    void test_prune_con_filters()
+    {
+        print_spinner();
+        using net_type = loss_multiclass_log<fc<3,relu<bn_con<con<8,3,3,1,1,
+                         relu<bn_con<con<8,3,3,1,1,
+                         relu<bn_con<con<8,3,3,1,1,
+                         input<matrix<float>>>>>>>>>>>>>;
+        dlib::rand rnd;
+        auto make_sample = [&]()
+        {
+            matrix<float> x(6, 6);
+            for (auto& v : x)
+                v = rnd.get_random_gaussian();
+            return x;
+        };
+
+        net_type net;
+        net(make_sample());
+        const size_t num_params = count_parameters(net);
+
+        // Turn off half the channels of the first two bn_con layers.  Their outputs are
+        // always 0 after the relu, so pruning them with the bn_gamma ranking doesn't
+        // change what the network computes.
+        for (tensor* bn_params : {&layer<9>(net).layer_details().get_layer_params(), &layer<6>(net).layer_details().get_layer_params()})
+        {
+            for (long i = 0; i < 8; i += 2)
+                bn_params->host()[i] = bn_params->host()[8+i] = 0;
+        }
+        std::vector<matrix<float>> samples;
+        std::vector<matrix<float>> outputs;
+        for (int i = 0; i < 5; ++i)
+        {
+            samples.push_back(make_sample());
+            outputs.push_back(mat(net.subnet()(samples.back())));
+        }
+
+        net_type pruned = net;
+        DLIB_TEST(prune_con_filters(pruned, 0.5, filter_ranking::bn_gamma) == 8);
+        DLIB_TEST(layer<10>(pruned).layer_details().num_filters() == 4);
+        DLIB_TEST(layer<7>(pruned).layer_details().num_filters() == 4);
+        // The last con is followed by an fc layer so it isn't pruned.
+        DLIB_TEST(layer<4>(pruned).layer_details().num_filters() == 8);
+        DLIB_TEST(layer<9>(pruned).layer_details().get_running_means().size() == 4);
+        DLIB_TEST(count_parameters(pruned) < num_params);
+        for (size_t i = 0; i < samples.size(); ++i)
+        {
+            const matrix<float> out = mat(pruned.subnet()(samples[i]));
+            DLIB_TEST_MSG(max(abs(out - outputs[i])) < 1e-5, max(abs(out - outputs[i])));
+        }
+
+        // The L1 ranking removes the filters with the smallest weights.
+        tensor& filters = layer<10>(net).layer_details().get_layer_params();
+        for (long i = 0; i < 9; ++i)
+            filters.host()[3*9+i] *= 100;
+        net_type l1_pruned = net;
+        prune_con_filters(l1_pruned, 0.9);
+        DLIB_TEST(layer<10>(l1_pruned).layer_details().num_filters() == 1);
+        const tensor& kept = layer<10>(l1_pruned).layer_details().get_layer_params();
+        DLIB_TEST(kept.size() == 10);
+        DLIB_TEST(std::equal(kept.host(), kept.host()+9, filters.host()+3*9));
+
+        // The pruned network serializes to a smaller file and can be fine-tuned.
+        std::ostringstream full_out, pruned_out;
+        net.clean();
+        pruned.clean();
+        serialize(net, full_out);
+        serialize(pruned, pruned_out);
+        DLIB_TEST(pruned_out.str().size() < full_out.str().size());
+        net_type pruned2;
+        std::istringstream sin(pruned_out.str());
+        deserialize(pruned2, sin);
+        DLIB_TEST(layer<7>(pruned2).layer_details().num_filters() == 4);
+
+        std::vector<unsigned long> labels;
+        for (const auto& x : samples)
+            labels.push_back(x(0,0) > 0 ? 1 : 0);
+        dnn_trainer<net_type> trainer(pruned2);
+        trainer.set_learning_rate(0.01);
+        trainer.set_mini_batch_size(5);
+        for (int i = 0; i < 10; ++i)
+            trainer.train_one_step(samples, labels);
+        trainer.get_net();
+        DLIB_TEST(layer<7>(pruned2).layer_details().num_filters() == 4);
+        DLIB_TEST(std::isfinite(trainer.get_average_loss()));
+
+        // In a residual block only the con whose output doesn't go to add_prev is
+        // pruned.  affine layers made from bn_con layers are pruned like them.
+        using res_net_type = fc<2,add_prev1<bn_con<con<6,3,3,1,1,relu<bn_con<con<6,3,3,1,1,
+                             tag1<relu<bn_con<con<6,3,3,1,1,input<matrix<float>>>>>>>>>>>>>;
+        using ares_net_type = fc<2,add_prev1<affine<con<6,3,3,1,1,relu<affine<con<6,3,3,1,1,
+                              tag1<relu<affine<con<6,3,3,1,1,input<matrix<float>>>>>>>>>>>>>;
+        res_net_type res_net;
+        res_net(make_sample());
+        std::ostringstream res_out;
+        serialize(res_net, res_out);
+        ares_net_type ares_net;
+        std::istringstream res_in(res_out.str());
+        deserialize(ares_net, res_in);
+        DLIB_TEST(prune_con_filters(ares_net, 0.5, filter_ranking::bn_gamma) == 3);
+        DLIB_TEST(layer<6>(ares_net).layer_details().num_filters() == 3);
+        DLIB_TEST(layer<5>(ares_net).layer_details().get_gamma().size() == 3);
+        DLIB_TEST(layer<3>(ares_net).layer_details().num_filters() == 6);
+        DLIB_TEST(layer<10>(ares_net).layer_details().num_filters() == 6);
+        DLIB_TEST(ares_net(make_sample()).size() == 2);
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    void test_dynamic_graph()
+    {
+        print_spinner();
+        using net_type = loss_multiclass_log<fc<3,relu<bn_fc<fc<8,
+                         max_pool<2,2,2,2,
+                         concat2<tag1,tag2,
+                         tag2<mult_prev3<add_prev1<con<4,3,3,1,1,relu<skip1<
+                         tag3<sig<con<4,1,1,1,1,
+                         tag1<upsample<2,prelu<con<4,3,3,1,1,
+                         input<matrix<float>>>>>>>>>>>>>>>>>>>>>>;
+        using dyn_net_type = loss_multiclass_log<dynamic_graph<input<matrix<float>>>>;
+
+        dlib::rand rnd;
+        auto make_sample = [&]()
+        {
+            matrix<float> x(6, 6);
+            for (auto& v : x)
+                v = rnd.get_random_gaussian();
+            return x;
+        };
+        std::vector<matrix<float>> samples;
+        std::vector<unsigned long> labels;
+        for (int i = 0; i < 6; ++i)
+        {
+            samples.push_back(make_sample());
+            labels.push_back(i%3);
+        }
+
+        net_type net;
+        net(samples[0]);
+        std::ostringstream xml;
+        net_to_xml(net, xml);
+
+        dyn_net_type dnet;
+        dynamic_graph_& graph = layer<1>(dnet).layer_details();
+        std::istringstream xml_in(xml.str());
+        graph.load_xml(xml_in);
+        DLIB_TEST(graph.num_nodes() == 15);
+        DLIB_TEST(graph.node_type(1) == "con");
+        DLIB_TEST(graph.node_type(9) == "mult_prev");
+        // The relu after skip1 reads the upsample output rather than the sig output.
+        DLIB_TEST(graph.node_inputs(6) == std::vector<size_t>({3}));
+        DLIB_TEST(graph.node_inputs(8) == std::vector<size_t>({7,3}));
+        DLIB_TEST(graph.node_inputs(9) == std::vector<size_t>({8,5}));
+        DLIB_TEST(graph.node_inputs(10) == std::vector<size_t>({3,9}));
+
+        // The graph computes the same thing as the network it was loaded from.
+        resizable_tensor x;
+        net.to_tensor(samples.begin(), samples.end(), x);
+        const matrix<float> expected = mat(net.subnet().forward(x));
+        dnet.to_tensor(samples.begin(), samples.end(), x);
+        const matrix<float> out = mat(dnet.subnet().forward(x));
+        DLIB_TEST(count_parameters(dnet) == count_parameters(net));
+        DLIB_TEST_MSG(max(abs(out - expected)) < 1e-5, max(abs(out - expected)));
+
+        // It also back-propagates the same gradients, both to the input and to the
+        // parameters of the first con layer.
+        const double loss = net.compute_parameter_gradients(x, labels.begin());
+        const double dloss = dnet.compute_parameter_gradients(x, labels.begin());
+        DLIB_TEST(std::abs(loss - dloss) < 1e-5);
+        const matrix<float> data_grad = mat(net.subnet().get_final_data_gradient());
+        const matrix<float> ddata_grad = mat(dnet.subnet().get_final_data_gradient());
+        DLIB_TEST_MSG(max(abs(data_grad - ddata_grad)) < 1e-4*max(abs(data_grad)), max(abs(data_grad - ddata_grad)));
+        const tensor& con_grad = layer<19>(net).get_parameter_gradient();
+        const matrix<float> dcon_grad = rowm(mat(layer<1>(dnet).get_parameter_gradient()), range(0, con_grad.size()-1));
+        DLIB_TEST(max(abs(mat(con_grad) - dcon_grad)) < 1e-4*max(abs(mat(con_grad))));
+
+        // Serialization round trips, as does the XML written for the graph itself.
+        std::ostringstream sout;
+        serialize(dnet, sout);
+        dyn_net_type dnet2;
+        std::istringstream sin(sout.str());
+        deserialize(dnet2, sin);
+        dnet2.to_tensor(samples.begin(), samples.end(), x);
+        DLIB_TEST(max(abs(mat(dnet2.subnet().forward(x)) - expected)) < 1e-5);
+
+        std::ostringstream xml2;
+        net_to_xml(dnet2, xml2);
+        dyn_net_type dnet3;
+        std::istringstream xml2_in(xml2.str());
+        layer<1>(dnet3).layer_details().load_xml(xml2_in);
+        dnet3.to_tensor(samples.begin(), samples.end(), x);
+        DLIB_TEST(layer<1>(dnet3).layer_details().num_nodes() == 15);
+        DLIB_TEST(max(abs(mat(dnet3.subnet().forward(x)) - expected)) < 1e-5);
+
+        // Check the gradients of a graph with randomly initialized parameters.  Layers
+        // without parameter values in the XML are set up like normal layers.
+        {
+            std::istringstream small_xml(
+                "<net>\n"
+                "<layer idx='0' type='comp'><add_prev tag='1'/></layer>\n"
+                "<layer idx='1' type='comp'><con num_filters='3' nr='3' nc='3'/></layer>\n"
+                "<layer idx='2' type='tag' id='1'/>\n"
+                "<layer idx='3' type='comp'><con num_filters='3' nr='3' nc='3'/></layer>\n"
+                "</net>\n");
+            dynamic_graph_ l;
+            l.load_xml(small_xml);
+            DLIB_TEST(l.num_nodes() == 3);
+            auto res = test_layer(l);
+            DLIB_TEST_MSG(res, res);
+        }
+
+        // The graph can be trained with the usual tools.
+        dnn_trainer<dyn_net_type> trainer(dnet, sgd(0.0005, 0.9));
+        trainer.set_learning_rate(0.01);
+        trainer.set_mini_batch_size(6);
+        double first_loss = 0;
+        for (int i = 0; i < 30; ++i)
+        {
+            trainer.train_one_step(samples, labels);
+            if (i == 0)
+                first_loss = trainer.get_average_loss();
+        }
+        trainer.get_net();
+        DLIB_TEST(std::isfinite(trainer.get_average_loss()));
+        DLIB_TEST_MSG(trainer.get_average_loss() < first_loss, trainer.get_average_loss() << " " << first_loss);
+        // The trained parameters are the ones that get saved.
+        DLIB_TEST(max(abs(mat(dnet.subnet().forward(x)) - expected)) > 1e-4);
+        std::ostringstream trained_out;
+        serialize(dnet, trained_out);
+        std::istringstream trained_in(trained_out.str());
+        deserialize(dnet2, trained_in);
+        DLIB_TEST(max(abs(mat(dnet2.subnet().forward(x)) - mat(dnet.subnet().forward(x)))) < 1e-6);
+
+        std::istringstream bad_xml("<net><layer idx='0' type='comp'><resize_prev_to_tagged tag='1'/></layer></net>");
+        bool threw = false;
+        try { graph.load_xml(bad_xml); } catch (serialization_error&) { threw = true; }
+        DLIB_TEST(threw);
+        DLIB_TEST(graph.num_nodes() == 15);
+    }
+
 // ----------------------------------------------------------------------------------------
 
     class dnn_tester : public tester
@@ -4622,6 +8587,7 @@ void test_embeddings()
             test_add();
             test_multiply_zero_padded();
             compare_adam();
+            compare_solver_updates();
             test_copy_tensor_gpu();
             test_copy_tensor_add_to_gpu();
             test_scale_channels();
@@ -4698,6 +8664,8 @@ void test_embeddings()
             test_loss_dot();
             test_loss_multimulticlass_log();
             test_loss_mmod();
+            test_loss_mmod_angle_regression();
+            test_loss_yolo_angle_regression();
             test_layers_scale_and_scale_prev();
             test_disable_duplicative_biases();
             test_set_learning_rate_multipliers();
@@ -4705,6 +8673,37 @@ void test_embeddings()
             test_fuse_layers();
             test_reorg();
             test_input_tensor();
+            test_onnx_export();
+            test_onnx_import();
+            test_dilated_and_grouped_conv();
+            test_recurrent_layers();
+            test_multihead_attention();
+            test_solvers();
+            test_lr_schedulers();
+            test_trainer_with_lr_scheduler();
+            test_half_precision_conversions();
+            test_half_tensor();
+            test_half_precision_parameters();
+            test_trainer_mixed_precision();
+            test_trainer_gradient_accumulation();
+            test_trainer_gradient_clipping();
+            test_cpu_num_threads();
+            test_trainer_callbacks();
+            test_trainer_weight_ema();
+            test_data_loader();
+            test_loss_multiclass_focal();
+            test_loss_multiclass_log_label_smoothing();
+            test_loss_dice_per_pixel();
+            test_loss_ctc();
+            test_loss_multiclass_distillation();
+            test_weighted_regression_losses();
+            test_loss_keypoint_heatmap();
+            test_int8_tensor();
+            test_int8_kernels();
+            test_int8_quantization();
+            test_net_summary();
+            test_prune_con_filters();
+            test_dynamic_graph();
         }
 
         void perform_test()
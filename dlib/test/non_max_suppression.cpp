@@ -0,0 +1,289 @@
+// Copyright (C) 2026  Davis E. King (davis@dlib.net)
+// License: Boost Software License   See LICENSE.txt for the full license.
+
+#include "tester.h"
+#include <dlib/image_processing.h>
+#include <vector>
+#include <map>
+#include <cmath>
+
+namespace
+{
+    using namespace test;
+    using namespace dlib;
+    using namespace std;
+    logger dlog("test.non_max_suppression");
+
+// ----------------------------------------------------------------------------------------
+
+    void test_hard_nms (
+    )
+    {
+        print_spinner();
+        // a and b have an IOU of 81/119, c doesn't touch either of them.
+        const std::vector<rectangle> boxes = {rectangle(1,1,10,10), rectangle(0,0,9,9), rectangle(50,50,60,60)};
+        const std::vector<double> scores = {0.8, 0.9, 0.7};
+
+        std::vector<unsigned long> kept = non_max_suppression(boxes, scores);
+        DLIB_TEST(kept.size() == 2);
+        DLIB_TEST(kept[0] == 1);
+        DLIB_TEST(kept[1] == 2);
+
+        kept = non_max_suppression(boxes, scores, test_box_overlap(0.7));
+        DLIB_TEST(kept.size() == 3);
+        DLIB_TEST(kept[0] == 1 && kept[1] == 0 && kept[2] == 2);
+
+        DLIB_TEST(non_max_suppression(std::vector<rectangle>(), std::vector<double>()).size() == 0);
+
+        std::vector<mmod_rect> dets = {
+            mmod_rect(boxes[0], 0.8, "person"),
+            mmod_rect(boxes[1], 0.9, "car"),
+            mmod_rect(boxes[2], 0.7, "car"),
+            mmod_rect(rectangle(51,51,61,61), 0.6, "car")
+        };
+
+        // Ignoring labels, the person box is suppressed by the car box on top of it.
+        std::vector<mmod_rect> out = non_max_suppression(dets);
+        DLIB_TEST(out.size() == 2);
+        DLIB_TEST(out[0] == dets[1]);
+        DLIB_TEST(out[1] == dets[2]);
+
+        out = non_max_suppression_per_label(dets);
+        DLIB_TEST(out.size() == 3);
+        DLIB_TEST(out[0] == dets[1]);
+        DLIB_TEST(out[1] == dets[0]);
+        DLIB_TEST(out[2] == dets[2]);
+
+        // A looser threshold for cars keeps both of the overlapping car boxes.
+        std::map<std::string,test_box_overlap> label_overlaps;
+        label_overlaps["car"] = test_box_overlap(0.9);
+        out = non_max_suppression_per_label(dets, test_box_overlap(), label_overlaps);
+        DLIB_TEST(out.size() == 4);
+        DLIB_TEST(out[3] == dets[3]);
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    void test_soft_nms (
+    )
+    {
+        print_spinner();
+        const std::vector<rectangle> boxes = {rectangle(0,0,9,9), rectangle(1,1,10,10), rectangle(50,50,60,60)};
+        const double iou = 81.0/119;
+
+        std::vector<double> scores = {0.9, 0.8, 0.7};
+        std::vector<unsigned long> kept = soft_non_max_suppression(boxes, scores);
+        DLIB_TEST(kept.size() == 3);
+        DLIB_TEST(kept[0] == 0 && kept[1] == 2 && kept[2] == 1);
+        DLIB_TEST(scores[0] == 0.9);
+        DLIB_TEST(scores[2] == 0.7);
+        DLIB_TEST(std::abs(scores[1] - 0.8*std::exp(-iou*iou/0.5)) < 1e-12);
+
+        soft_nms_options opts;
+        opts.decay = soft_nms_decay::linear;
+        opts.iou_thresh = 0.5;
+        scores = {0.9, 0.8, 0.7};
+        kept = soft_non_max_suppression(boxes, scores, opts);
+        DLIB_TEST(kept.size() == 3);
+        DLIB_TEST(std::abs(scores[1] - 0.8*(1-iou)) < 1e-12);
+
+        // If the overlap is below iou_thresh the linear decay leaves the score alone.
+        opts.iou_thresh = 0.7;
+        scores = {0.9, 0.8, 0.7};
+        kept = soft_non_max_suppression(boxes, scores, opts);
+        DLIB_TEST(kept[1] == 1);
+        DLIB_TEST(scores[1] == 0.8);
+
+        // Boxes decayed below min_confidence are dropped.
+        std::vector<mmod_rect> dets = {
+            mmod_rect(boxes[0], 0.9, "car"),
+            mmod_rect(boxes[1], 0.8, "person"),
+            mmod_rect(boxes[2], 0.7, "car")
+        };
+        opts = soft_nms_options();
+        opts.min_confidence = 0.5;
+        std::vector<mmod_rect> out = soft_non_max_suppression(dets, opts);
+        DLIB_TEST(out.size() == 2);
+        DLIB_TEST(out[0] == dets[0]);
+        DLIB_TEST(out[1] == dets[2]);
+
+        out = soft_non_max_suppression_per_label(dets, opts);
+        DLIB_TEST(out.size() == 3);
+        DLIB_TEST(out[1] == dets[1]);
+
+        std::map<std::string,soft_nms_options> label_options;
+        label_options["car"] = opts;
+        label_options["car"].min_confidence = 0.75;
+        out = soft_non_max_suppression_per_label(dets, opts, label_options);
+        DLIB_TEST(out.size() == 2);
+        DLIB_TEST(out[0] == dets[0]);
+        DLIB_TEST(out[1] == dets[1]);
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    void test_weighted_box_fusion (
+    )
+    {
+        print_spinner();
+        // These two boxes have an IOU of 8281/12121.
+        const mmod_rect a(rectangle(0,0,100,100), 0.8, "car");
+        const mmod_rect b(rectangle(10,10,110,110), 0.2, "car");
+        const mmod_rect c(rectangle(300,300,350,350), 0.6, "car");
+
+        std::vector<mmod_rect> out = weighted_box_fusion(std::vector<mmod_rect>{a, b});
+        DLIB_TEST(out.size() == 1);
+        DLIB_TEST(out[0].rect == rectangle(2,2,102,102));
+        DLIB_TEST(std::abs(out[0].detection_confidence - 0.5) < 1e-12);
+        DLIB_TEST(out[0].label == "car");
+        DLIB_TEST(out[0].ignore == false);
+
+        out = weighted_box_fusion(std::vector<mmod_rect>{a, b}, 0.7);
+        DLIB_TEST(out.size() == 2);
+        DLIB_TEST(out[0] == a);
+        DLIB_TEST(out[1] == b);
+
+        // Two models.  The lone box found only by the second model is down weighted.
+        std::vector<std::vector<mmod_rect>> model_dets = {{a}, {b, c}};
+        out = weighted_box_fusion(model_dets);
+        DLIB_TEST(out.size() == 2);
+        DLIB_TEST(out[0].rect == rectangle(2,2,102,102));
+        DLIB_TEST(std::abs(out[0].detection_confidence - 0.5) < 1e-12);
+        DLIB_TEST(out[1].rect == c.rect);
+        DLIB_TEST(std::abs(out[1].detection_confidence - 0.3) < 1e-12);
+
+        out = weighted_box_fusion(model_dets, 0.55, {3, 1});
+        DLIB_TEST(out.size() == 2);
+        DLIB_TEST(out[0].rect == rectangle(1,1,101,101));
+        DLIB_TEST(std::abs(out[0].detection_confidence - 0.65) < 1e-12);
+        DLIB_TEST(std::abs(out[1].detection_confidence - 0.15) < 1e-12);
+
+        // Boxes with different labels are only fused by the label agnostic version.
+        mmod_rect p = b;
+        p.label = "person";
+        out = weighted_box_fusion(std::vector<mmod_rect>{p, a});
+        DLIB_TEST(out.size() == 1);
+        DLIB_TEST(out[0].label == "car");
+        out = weighted_box_fusion_per_label(std::vector<mmod_rect>{p, a});
+        DLIB_TEST(out.size() == 2);
+        DLIB_TEST(out[0] == a);
+        DLIB_TEST(out[1] == p);
+
+        std::map<std::string,double> label_iou_thresh;
+        label_iou_thresh["car"] = 0.9;
+        out = weighted_box_fusion_per_label(std::vector<mmod_rect>{a, b, p}, 0.55, label_iou_thresh);
+        DLIB_TEST(out.size() == 3);
+        out = weighted_box_fusion_per_label(model_dets, 0.55, label_iou_thresh, {3, 1});
+        DLIB_TEST(out.size() == 3);
+        DLIB_TEST(std::abs(out[0].detection_confidence - 0.6) < 1e-12);
+
+        DLIB_TEST(weighted_box_fusion(std::vector<mmod_rect>()).size() == 0);
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    void test_rotated_boxes (
+    )
+    {
+        print_spinner();
+        const double eps = 1e-9;
+        const drectangle a = rectangle(0,0,9,9);
+        const drectangle b = rectangle(1,1,10,10);
+
+        // Without any rotation we get the usual axis aligned values.
+        DLIB_TEST(box_intersection_over_union(a,0,b,0) == box_intersection_over_union(a,b));
+        DLIB_TEST(box_percent_covered(a,0,b,0) == box_percent_covered(a,b));
+        DLIB_TEST(std::abs(box_intersection_over_union(a,1e-12,b,0) - box_intersection_over_union(a,b)) < eps);
+
+        // A square and the same square rotated by 45 degrees intersect in a regular
+        // octagon, which gives an IOU of 1/sqrt(2).
+        DLIB_TEST(std::abs(box_intersection_over_union(a,0,a,pi/4) - 1/std::sqrt(2.0)) < eps);
+        DLIB_TEST(std::abs(box_intersection_over_union(a,pi/3,a,pi/3+pi/4) - 1/std::sqrt(2.0)) < eps);
+        DLIB_TEST(std::abs(box_percent_covered(a,0,a,pi/4) - 2*(std::sqrt(2.0)-1)) < eps);
+
+        // A box rotated by 90 degrees is the same as a box with its width and height
+        // swapped, and rotating by 180 degrees doesn't change it at all.
+        const drectangle wide = centered_drect(dpoint(50,50), 40, 10);
+        const drectangle tall = centered_drect(dpoint(50,50), 10, 40);
+        DLIB_TEST(std::abs(box_intersection_over_union(wide,pi/2,tall,0) - 1) < eps);
+        DLIB_TEST(std::abs(box_intersection_over_union(wide,pi,wide,0) - 1) < eps);
+        DLIB_TEST(std::abs(box_intersection_over_union(wide,0,tall,0) - 100.0/700) < eps);
+        DLIB_TEST(box_intersection_over_union(wide,pi/2,translate_rect(tall,dpoint(20,0)),0) == 0);
+
+        // A small box inside a rotated big box is entirely covered by it.
+        DLIB_TEST(std::abs(box_percent_covered(centered_drect(dpoint(50,50),20,20),pi/5,
+                                               centered_drect(dpoint(50,50),4,4),0) - 1) < eps);
+
+        // Two long thin boxes crossing in an X have identical axis aligned rects, but
+        // hardly overlap once their angles are taken into account.
+        const test_box_overlap overlaps(0.5);
+        DLIB_TEST(overlaps(rectangle(wide), rectangle(wide)));
+        DLIB_TEST(!overlaps(wide, pi/4, wide, -pi/4));
+        DLIB_TEST(overlaps(wide, pi/4, wide, pi/4+0.05));
+        DLIB_TEST(test_box_overlap(0.5, 0.8)(wide, 0, centered_drect(dpoint(50,50),20,6), 0.1));
+
+        const rectangle wide_rect = centered_rect(point(50,50), 40, 10);
+        std::vector<mmod_rect> dets = {
+            mmod_rect(wide_rect, 0.9, "car"),
+            mmod_rect(wide_rect, 0.8, "car"),
+            mmod_rect(wide_rect, 0.7, "car")
+        };
+        dets[0].angle = pi/4;
+        dets[1].angle = -pi/4;
+        dets[2].angle = pi/4 + 0.05;
+        std::vector<mmod_rect> out = non_max_suppression(dets, overlaps);
+        DLIB_TEST(out.size() == 2);
+        DLIB_TEST(out[0] == dets[0]);
+        DLIB_TEST(out[1] == dets[1]);
+
+        // Axis aligned detections are suppressed exactly as they were before.
+        for (auto& d : dets)
+            d.angle = 0;
+        DLIB_TEST(non_max_suppression(dets, overlaps).size() == 1);
+
+        dets[0].angle = pi/4;
+        dets[1].angle = -pi/4;
+        const double iou = box_intersection_over_union(wide_rect, pi/4, wide_rect, -pi/4);
+        out = soft_non_max_suppression(std::vector<mmod_rect>{dets[0], dets[1]});
+        DLIB_TEST(out.size() == 2);
+        DLIB_TEST(std::abs(out[1].detection_confidence - 0.8*std::exp(-iou*iou/0.5)) < eps);
+        DLIB_TEST(out[1].angle == -pi/4);
+
+        // Fused boxes average their angles, including across the -pi/pi boundary.
+        mmod_rect p(rectangle(0,0,100,40), 0.5, "car"), q = p;
+        p.angle = 0.1;
+        q.angle = 0.3;
+        out = weighted_box_fusion(std::vector<mmod_rect>{p, q});
+        DLIB_TEST(out.size() == 1);
+        DLIB_TEST(std::abs(out[0].angle - 0.2) < eps);
+        p.angle = pi - 0.1;
+        q.angle = -pi + 0.1;
+        out = weighted_box_fusion(std::vector<mmod_rect>{p, q});
+        DLIB_TEST(out.size() == 1);
+        DLIB_TEST(std::abs(std::abs(out[0].angle) - pi) < eps);
+        q.angle = p.angle + pi/2;
+        DLIB_TEST(weighted_box_fusion(std::vector<mmod_rect>{p, q}).size() == 2);
+    }
+
+// ----------------------------------------------------------------------------------------
+
+    class non_max_suppression_tester : public tester
+    {
+    public:
+        non_max_suppression_tester (
+        ) :
+            tester ("test_non_max_suppression",
+                    "Runs tests on the non-max suppression and box fusion tools.")
+        {}
+
+        void perform_test (
+        )
+        {
+            test_hard_nms();
+            test_soft_nms();
+            test_weighted_box_fusion();
+            test_rotated_boxes();
+        }
+    } a;
+
+}
@@ -15,7 +15,6 @@
 #include <dlib/image_transforms.h>
 #include <dlib/dir_nav.h>
 #include <iterator>
-#include <thread>
 
 using namespace std;
 using namespace dlib;
@@ -242,60 +241,40 @@ int main(int argc, char** argv) try
     // stats window to something big too.
     set_all_bn_running_stats_window_sizes(net, 1000);
 
+    // Make a dataset that loads each image from disk when it's asked for it.  The
+    // data_loader then uses a bunch of threads to read the images and pull out random
+    // crops.  It's important to be sure to feed the GPU fast enough to keep it busy.
+    // Using multiple threads for this kind of data preparation helps us do that.
+    auto dataset = make_dataset(listing.size(), [&listing](size_t i)
+    {
+        std::pair<matrix<rgb_pixel>, unsigned long> sample;
+        load_image(sample.first, listing[i].filename);
+        sample.second = listing[i].numeric_label;
+        return sample;
+    });
+    // Each mini-batch will have 160 images in it.
+    data_loader<decltype(dataset)> loader(dataset, 160);
+    loader.set_num_workers(4);
+    loader.set_seed(time(0));
+    loader.add_transform([](std::pair<matrix<rgb_pixel>, unsigned long>& sample, dlib::rand& rnd)
+    {
+        matrix<rgb_pixel> crop;
+        randomly_crop_image(sample.first, crop, rnd);
+        sample.first = std::move(crop);
+    });
+
     std::vector<matrix<rgb_pixel>> samples;
     std::vector<unsigned long> labels;
 
-    // Start a bunch of threads that read images from disk and pull out random crops.  It's
-    // important to be sure to feed the GPU fast enough to keep it busy.  Using multiple
-    // thread for this kind of data preparation helps us do that.  Each thread puts the
-    // crops into the data queue.
-    dlib::pipe<std::pair<image_info,matrix<rgb_pixel>>> data(200);
-    auto f = [&data, &listing](time_t seed)
-    {
-        dlib::rand rnd(time(0)+seed);
-        matrix<rgb_pixel> img;
-        std::pair<image_info, matrix<rgb_pixel>> temp;
-        while(data.is_enabled())
-        {
-            temp.first = listing[rnd.get_random_32bit_number()%listing.size()];
-            load_image(img, temp.first.filename);
-            randomly_crop_image(img, temp.second, rnd);
-            data.enqueue(temp);
-        }
-    };
-    std::thread data_loader1([f](){ f(1); });
-    std::thread data_loader2([f](){ f(2); });
-    std::thread data_loader3([f](){ f(3); });
-    std::thread data_loader4([f](){ f(4); });
-
-    // The main training loop.  Keep making mini-batches and giving them to the trainer.
-    // We will run until the learning rate has dropped by a factor of 1e-3.
+    // The main training loop.  Keep getting mini-batches from the loader and giving them
+    // to the trainer.  We will run until the learning rate has dropped by a factor of
+    // 1e-3.
     while(trainer.get_learning_rate() >= initial_learning_rate*1e-3)
     {
-        samples.clear();
-        labels.clear();
-
-        // make a 160 image mini-batch
-        std::pair<image_info, matrix<rgb_pixel>> img;
-        while(samples.size() < 160)
-        {
-            data.dequeue(img);
-
-            samples.push_back(std::move(img.second));
-            labels.push_back(img.first.numeric_label);
-        }
-
+        loader.next(samples, labels);
         trainer.train_one_step(samples, labels);
     }
 
-    // Training done, tell threads to stop and make sure to wait for them to finish before
-    // moving on.
-    data.disable();
-    data_loader1.join();
-    data_loader2.join();
-    data_loader3.join();
-    data_loader4.join();
-
     // also wait for threaded processing to stop in the trainer.
     trainer.get_net();
 
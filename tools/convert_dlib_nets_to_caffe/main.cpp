@@ -39,6 +39,17 @@ struct layer
             throw dlib::error("Layer doesn't have the requested attribute '" + key + "'.");
     }
 
+    double attribute (const string& key, double default_value) const
+    {
+        // For attributes that were added to dlib's XML output after older networks were
+        // saved.
+        auto i = attributes.find(key);
+        if (i != attributes.end())
+            return i->second;
+        else
+            return default_value;
+    }
+
     string caffe_layer_name() const 
     { 
         if (type == "input")
@@ -296,6 +307,14 @@ void convert_dlib_xml_to_caffe_python_code(
             fout << ", stride_h=" << i->attribute("stride_y");
             fout << ", pad_w=" << i->attribute("padding_x");
             fout << ", pad_h=" << i->attribute("padding_y");
+            if (i->attribute("dilation_y", 1) != 1 || i->attribute("dilation_x", 1) != 1)
+            {
+                if (i->attribute("dilation_y", 1) != i->attribute("dilation_x", 1))
+                    throw dlib::error("Caffe only supports convolutions with the same dilation in both dimensions.");
+                fout << ", dilation=" << i->attribute("dilation_y", 1);
+            }
+            if (i->attribute("groups", 1) != 1)
+                fout << ", group=" << i->attribute("groups", 1);
             fout << ");\n";
         }
         else if (i->detail_name == "relu")
@@ -726,8 +745,10 @@ void compute_output_tensor_shapes(const matrix<long,4,1>& input_tensor_shape, st
                 long stride_y = i->attribute("stride_y");
                 long padding_x = i->attribute("padding_x");
                 long padding_y = i->attribute("padding_y");
-                long nr = 1+(input_shape(2) + 2*padding_y - filter_nr)/stride_y;
-                long nc = 1+(input_shape(3) + 2*padding_x - filter_nc)/stride_x;
+                long dilation_x = i->attribute("dilation_x", 1);
+                long dilation_y = i->attribute("dilation_y", 1);
+                long nr = 1+(input_shape(2) + 2*padding_y - (dilation_y*(filter_nr-1)+1))/stride_y;
+                long nc = 1+(input_shape(3) + 2*padding_x - (dilation_x*(filter_nc-1)+1))/stride_x;
                 i->output_tensor_shape = {input_shape(0), num_filters, nr, nc};
             }
             else if (i->detail_name == "max_pool" || i->detail_name == "avg_pool")
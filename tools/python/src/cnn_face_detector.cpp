@@ -116,6 +116,16 @@ public:
         return all_rects;
     }
 
+    std::string summary (
+        const long rows,
+        const long columns
+    )
+    {
+        std::ostringstream sout;
+        sout << net_summary(net, rows, columns);
+        return sout.str();
+    }
+
 private:
 
     template <long num_filters, typename SUBNET> using con5d = con<num_filters,5,5,2,2,SUBNET>;
@@ -149,6 +159,14 @@ void bind_cnn_face_detection(py::module& m)
             "Find faces in an image using a deep learning model.\n\
           - Upsamples the image upsample_num_times before running the face \n\
             detector."
+            )
+        .def(
+            "net_summary",
+            &cnn_face_detection_model_v1::summary,
+            py::arg("rows"), py::arg("columns"),
+            "Returns a table with the output shape, number of parameters, multiply-adds and \n\
+activation memory of each layer of the network when it runs on an image of the \n\
+given size."
             );
     }
 
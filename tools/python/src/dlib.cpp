@@ -32,6 +32,7 @@ void bind_global_optimization(py::module& m);
 void bind_numpy_returns(py::module& m);
 void bind_image_dataset_metadata(py::module& m);
 void bind_line(py::module& m);
+void bind_dnn(py::module& m);
 
 #ifndef DLIB_NO_GUI_SUPPORT
 void bind_gui(py::module& m);
@@ -121,6 +122,7 @@ PYBIND11_MODULE(_dlib_pybind11, m)
 #endif
 
     bind_image_dataset_metadata(m);
+    bind_dnn(m);
 
 
 }
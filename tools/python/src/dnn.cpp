@@ -0,0 +1,989 @@
+// Copyright (C) 2026  Davis E. King (davis@dlib.net)
+// License: Boost Software License   See LICENSE.txt for the full license.
+
+#include "opaque_types.h"
+#include <dlib/python.h>
+#include <dlib/matrix.h>
+#include <dlib/dnn.h>
+#include <dlib/image_transforms.h>
+#include <pybind11/numpy.h>
+#include <chrono>
+
+using namespace dlib;
+using namespace std;
+
+namespace py = pybind11;
+
+// ----------------------------------------------------------------------------------------
+//                                       tensors
+// ----------------------------------------------------------------------------------------
+
+resizable_tensor tensor_from_numpy (
+    py::array_t<float, py::array::c_style | py::array::forcecast> arr
+)
+{
+    if (arr.ndim() < 1 || arr.ndim() > 4)
+        throw dlib::error("A dlib.tensor can only be made from an array with between 1 and 4 dimensions.");
+
+    // Missing trailing dimensions have a size of 1.  So an array with shape (N,K) turns
+    // into a tensor with num_samples()==N and k()==K, like the output of an fc layer.
+    long dims[4] = {1,1,1,1};
+    for (py::ssize_t i = 0; i < arr.ndim(); ++i)
+        dims[i] = arr.shape(i);
+
+    resizable_tensor t(dims[0], dims[1], dims[2], dims[3]);
+    std::copy(arr.data(), arr.data()+arr.size(), t.host());
+    return t;
+}
+
+resizable_tensor tensor_from_object (
+    const py::object& obj
+)
+{
+    if (py::isinstance<resizable_tensor>(obj))
+        return obj.cast<resizable_tensor>();
+    return tensor_from_numpy(obj.cast<py::array_t<float, py::array::c_style | py::array::forcecast>>());
+}
+
+py::tuple tensor_shape (
+    const resizable_tensor& t
+)
+{
+    return py::make_tuple(t.num_samples(), t.k(), t.nr(), t.nc());
+}
+
+string print_tensor (
+    const resizable_tensor& t
+)
+{
+    std::ostringstream sout;
+    sout << "<dlib.tensor num_samples=" << t.num_samples() << ", k=" << t.k()
+         << ", nr=" << t.nr() << ", nc=" << t.nc() << ">";
+    return sout.str();
+}
+
+// ----------------------------------------------------------------------------------------
+//                                    dynamic_net
+// ----------------------------------------------------------------------------------------
+
+class tensor_subnet
+{
+    /*!
+        This object lets a dynamic_graph_ run directly on a tensor, as though the tensor
+        was the output of an input layer.
+    !*/
+public:
+    explicit tensor_subnet(const tensor& x_) : x(x_) {}
+
+    const tensor& get_output() const { return x; }
+    tensor& get_gradient_input() { grad.copy_size(x); return grad; }
+
+private:
+    const tensor& x;
+    resizable_tensor grad;
+};
+
+void graph_to_xml (
+    const dynamic_graph_& graph,
+    std::ostream& out
+)
+{
+    to_xml(graph, out);
+}
+
+class dynamic_net
+{
+public:
+
+    dynamic_net() = default;
+
+    explicit dynamic_net (
+        const std::string& xml_filename
+    )
+    {
+        graph.load_xml(xml_filename);
+    }
+
+    resizable_tensor forward (
+        const py::object& pyx
+    )
+    {
+        const resizable_tensor x = tensor_from_object(pyx);
+        tensor_subnet sub(x);
+        // setup() allocates the layers, so it happens only once.  After that the input
+        // must keep the same number of channels.
+        if (!is_setup)
+        {
+            graph.setup(sub);
+            is_setup = true;
+        }
+        resizable_tensor output;
+        graph.forward(sub, output);
+        return output;
+    }
+
+    size_t num_layers (
+    ) const { return graph.num_nodes(); }
+
+    string layer_type (
+        size_t i
+    ) const
+    {
+        if (i >= graph.num_nodes())
+            throw dlib::error("Invalid layer index.");
+        return graph.node_type(i+1);
+    }
+
+    std::vector<size_t> layer_inputs (
+        size_t i
+    ) const
+    {
+        if (i >= graph.num_nodes())
+            throw dlib::error("Invalid layer index.");
+        // The python API numbers the layers from 0, so the graph input is -1 there.  To
+        // keep the returned values unsigned we report the graph input as num_layers().
+        std::vector<size_t> inputs;
+        for (auto j : graph.node_inputs(i+1))
+            inputs.push_back(j == 0 ? graph.num_nodes() : j-1);
+        return inputs;
+    }
+
+    size_t num_parameters (
+    ) const { return graph.get_layer_params().size(); }
+
+    string to_xml (
+    ) const
+    {
+        std::ostringstream sout;
+        sout.precision(9);
+        sout << "<net>\n";
+        graph_to_xml(graph, sout);
+        sout << "</net>\n";
+        return sout.str();
+    }
+
+    void save (
+        const std::string& filename
+    ) const
+    {
+        serialize(filename) << *this;
+    }
+
+    friend void serialize(const dynamic_net& item, std::ostream& out)
+    {
+        serialize("dynamic_net", out);
+        serialize(item.graph, out);
+        serialize(item.is_setup, out);
+    }
+
+    friend void deserialize(dynamic_net& item, std::istream& in)
+    {
+        std::string version;
+        deserialize(version, in);
+        if (version != "dynamic_net")
+            throw serialization_error("Unexpected version found while deserializing dlib.dynamic_net.");
+        deserialize(item.graph, in);
+        deserialize(item.is_setup, in);
+    }
+
+private:
+    dynamic_graph_ graph;
+    bool is_setup = false;
+};
+
+// ----------------------------------------------------------------------------------------
+//                               training options and helpers
+// ----------------------------------------------------------------------------------------
+
+struct dnn_training_options
+{
+    double learning_rate = 0.1;
+    double min_learning_rate = 1e-5;
+    double learning_rate_shrink_factor = 0.1;
+    unsigned long mini_batch_size = 32;
+    unsigned long iterations_without_progress_threshold = 2000;
+    unsigned long max_num_epochs = 10000;
+    double weight_decay = 0.0005;
+    double momentum = 0.9;
+    std::string synchronization_file;
+    bool be_verbose = false;
+};
+
+void serialize(const dnn_training_options& item, std::ostream& out)
+{
+    serialize("dnn_training_options", out);
+    serialize(item.learning_rate, out);
+    serialize(item.min_learning_rate, out);
+    serialize(item.learning_rate_shrink_factor, out);
+    serialize(item.mini_batch_size, out);
+    serialize(item.iterations_without_progress_threshold, out);
+    serialize(item.max_num_epochs, out);
+    serialize(item.weight_decay, out);
+    serialize(item.momentum, out);
+    serialize(item.synchronization_file, out);
+    serialize(item.be_verbose, out);
+}
+
+void deserialize(dnn_training_options& item, std::istream& in)
+{
+    std::string version;
+    deserialize(version, in);
+    if (version != "dnn_training_options")
+        throw serialization_error("Unexpected version found while deserializing dlib.dnn_training_options.");
+    deserialize(item.learning_rate, in);
+    deserialize(item.min_learning_rate, in);
+    deserialize(item.learning_rate_shrink_factor, in);
+    deserialize(item.mini_batch_size, in);
+    deserialize(item.iterations_without_progress_threshold, in);
+    deserialize(item.max_num_epochs, in);
+    deserialize(item.weight_decay, in);
+    deserialize(item.momentum, in);
+    deserialize(item.synchronization_file, in);
+    deserialize(item.be_verbose, in);
+}
+
+string print_dnn_training_options (
+    const dnn_training_options& o
+)
+{
+    std::ostringstream sout;
+    sout << "dnn_training_options("
+         << "learning_rate=" << o.learning_rate << ", "
+         << "min_learning_rate=" << o.min_learning_rate << ", "
+         << "learning_rate_shrink_factor=" << o.learning_rate_shrink_factor << ", "
+         << "mini_batch_size=" << o.mini_batch_size << ", "
+         << "iterations_without_progress_threshold=" << o.iterations_without_progress_threshold << ", "
+         << "max_num_epochs=" << o.max_num_epochs << ", "
+         << "weight_decay=" << o.weight_decay << ", "
+         << "momentum=" << o.momentum << ", "
+         << "synchronization_file='" << o.synchronization_file << "', "
+         << "be_verbose=" << (o.be_verbose ? "True" : "False") << ")";
+    return sout.str();
+}
+
+template <typename net_type>
+void configure_dnn_trainer (
+    dnn_trainer<net_type>& trainer,
+    const dnn_training_options& options
+)
+{
+    if (!(options.learning_rate > 0 && options.min_learning_rate > 0 &&
+          0 < options.learning_rate_shrink_factor && options.learning_rate_shrink_factor <= 1 &&
+          options.mini_batch_size > 0 && options.max_num_epochs > 0))
+    {
+        throw dlib::error("Invalid dnn_training_options: " + print_dnn_training_options(options));
+    }
+
+    trainer.set_learning_rate(options.learning_rate);
+    trainer.set_min_learning_rate(options.min_learning_rate);
+    trainer.set_learning_rate_shrink_factor(options.learning_rate_shrink_factor);
+    trainer.set_mini_batch_size(options.mini_batch_size);
+    trainer.set_iterations_without_progress_threshold(options.iterations_without_progress_threshold);
+    trainer.set_max_num_epochs(options.max_num_epochs);
+    if (options.synchronization_file.size() != 0)
+        trainer.set_synchronization_file(options.synchronization_file, std::chrono::minutes(5));
+    if (options.be_verbose)
+        trainer.be_verbose();
+}
+
+matrix<rgb_pixel> dnn_rgb_image (
+    const py::array& pyimage
+)
+{
+    matrix<rgb_pixel> image;
+    if (is_image<unsigned char>(pyimage))
+        assign_image(image, numpy_image<unsigned char>(pyimage));
+    else if (is_image<rgb_pixel>(pyimage))
+        assign_image(image, numpy_image<rgb_pixel>(pyimage));
+    else
+        throw dlib::error("Unsupported image type, must be 8bit gray or RGB image.");
+    return image;
+}
+
+std::vector<matrix<rgb_pixel>> dnn_rgb_images (
+    const py::list& pyimages,
+    bool require_same_size
+)
+{
+    std::vector<matrix<rgb_pixel>> images;
+    images.reserve(len(pyimages));
+    for (auto& img : pyimages)
+        images.push_back(dnn_rgb_image(img.cast<py::array>()));
+
+    if (require_same_size)
+    {
+        for (size_t i = 1; i < images.size(); ++i)
+        {
+            if (images[i].nr() != images[0].nr() || images[i].nc() != images[0].nc())
+                throw dlib::error("Images in list must all have the same dimensions.");
+        }
+    }
+    return images;
+}
+
+// ----------------------------------------------------------------------------------------
+//                                  resnet_classifier
+// ----------------------------------------------------------------------------------------
+
+class resnet_classifier
+{
+public:
+
+    resnet_classifier (
+    ) : resnet_classifier(2) {}
+
+    explicit resnet_classifier (
+        unsigned long num_classes
+    )
+    {
+        if (num_classes < 2)
+            throw dlib::error("A resnet_classifier must have at least 2 classes.");
+        layer<1>(net).layer_details().set_num_outputs(num_classes);
+    }
+
+    unsigned long num_classes (
+    ) const { return layer<1>(net).layer_details().get_num_outputs(); }
+
+    void train (
+        const py::list& pyimages,
+        const py::list& pylabels,
+        const dnn_training_options& options
+    )
+    {
+        if (len(pyimages) != len(pylabels))
+            throw dlib::error("The length of the labels list must match the length of the images list.");
+        if (len(pyimages) == 0)
+            throw dlib::error("You must give at least one training image.");
+
+        const auto images = dnn_rgb_images(pyimages, true);
+        std::vector<unsigned long> labels;
+        for (auto& l : pylabels)
+        {
+            labels.push_back(l.cast<unsigned long>());
+            if (labels.back() >= num_classes())
+                throw dlib::error("All the labels must be less than num_classes.");
+        }
+
+        dnn_trainer<net_type> trainer(net, sgd(options.weight_decay, options.momentum));
+        configure_dnn_trainer(trainer, options);
+        trainer.train(images, labels);
+        net.clean();
+        inference_net_is_stale = true;
+    }
+
+    unsigned long predict (
+        const py::array& pyimage
+    )
+    {
+        return inference_net()(dnn_rgb_image(pyimage));
+    }
+
+    std::vector<unsigned long> predict_batch (
+        const py::list& pyimages,
+        const unsigned long batch_size
+    )
+    {
+        return inference_net()(dnn_rgb_images(pyimages, true), batch_size);
+    }
+
+    string summary (
+        const long rows,
+        const long columns
+    )
+    {
+        std::ostringstream sout;
+        sout << net_summary(inference_net(), rows, columns);
+        return sout.str();
+    }
+
+    void save (
+        const std::string& filename
+    ) const
+    {
+        serialize(filename) << *this;
+    }
+
+    friend void serialize(const resnet_classifier& item, std::ostream& out)
+    {
+        serialize("resnet_classifier", out);
+        serialize(item.net, out);
+    }
+
+    friend void deserialize(resnet_classifier& item, std::istream& in)
+    {
+        std::string version;
+        deserialize(version, in);
+        if (version != "resnet_classifier")
+            throw serialization_error("Unexpected version found while deserializing dlib.resnet_classifier.");
+        deserialize(item.net, in);
+        item.inference_net_is_stale = true;
+    }
+
+private:
+
+    template <template <int,template<typename>class,int,typename> class block, int N, template<typename>class BN, typename SUBNET>
+    using residual = add_prev1<block<N,BN,1,tag1<SUBNET>>>;
+
+    template <template <int,template<typename>class,int,typename> class block, int N, template<typename>class BN, typename SUBNET>
+    using residual_down = add_prev2<avg_pool<2,2,2,2,skip1<tag2<block<N,BN,2,tag1<SUBNET>>>>>>;
+
+    template <int N, template <typename> class BN, int stride, typename SUBNET>
+    using block  = BN<con<N,3,3,1,1,relu<BN<con<N,3,3,stride,stride,SUBNET>>>>>;
+
+    template <int N, template <typename> class BN, typename SUBNET> using res      = relu<residual<block,N,BN,SUBNET>>;
+    template <int N, template <typename> class BN, typename SUBNET> using res_down = relu<residual_down<block,N,BN,SUBNET>>;
+
+    template <template <typename> class BN>
+    using resnet = loss_multiclass_log<fc<2,avg_pool_everything<
+                            res<64,BN,res_down<64,BN,
+                            res<32,BN,res_down<32,BN,
+                            res<16,BN,res<16,BN,
+                            max_pool<3,3,2,2,relu<BN<con<16,5,5,2,2,
+                            input_rgb_image
+                            >>>>>>>>>>>>>;
+
+    // Batch normalization is used while training.  It's replaced with affine layers
+    // when the network is used to make predictions.
+    using net_type = resnet<bn_con>;
+    using anet_type = resnet<affine>;
+
+    anet_type& inference_net (
+    )
+    {
+        if (inference_net_is_stale)
+        {
+            anet = anet_type(net);
+            inference_net_is_stale = false;
+        }
+        return anet;
+    }
+
+    net_type net;
+    anet_type anet;
+    bool inference_net_is_stale = true;
+};
+
+// ----------------------------------------------------------------------------------------
+//                                    mmod_detector
+// ----------------------------------------------------------------------------------------
+
+class mmod_detector
+{
+public:
+
+    void train (
+        const py::list& pyimages,
+        const py::list& pyboxes,
+        const dnn_training_options& options,
+        const unsigned long target_size,
+        const unsigned long min_target_size,
+        const unsigned long chip_size
+    )
+    {
+        if (len(pyimages) != len(pyboxes))
+            throw dlib::error("The length of the boxes list must match the length of the images list.");
+        if (len(pyimages) == 0)
+            throw dlib::error("You must give at least one training image.");
+        if (!(0 < min_target_size && min_target_size <= target_size && target_size <= chip_size))
+            throw dlib::error("You must have 0 < min_target_size <= target_size <= chip_size.");
+
+        const auto images = dnn_rgb_images(pyimages, false);
+        std::vector<std::vector<mmod_rect>> boxes;
+        for (auto& img_boxes : pyboxes)
+        {
+            boxes.emplace_back();
+            for (auto& b : img_boxes)
+            {
+                if (py::isinstance<mmod_rect>(b))
+                    boxes.back().push_back(b.cast<mmod_rect>());
+                else
+                    boxes.back().push_back(mmod_rect(b.cast<rectangle>()));
+            }
+        }
+
+        mmod_options mmod_opts(boxes, target_size, min_target_size);
+        net = net_type(mmod_opts);
+        net.subnet().layer_details().set_num_filters(mmod_opts.detector_windows.size());
+
+        dnn_trainer<net_type> trainer(net, sgd(options.weight_decay, options.momentum));
+        configure_dnn_trainer(trainer, options);
+
+        random_cropper cropper;
+        cropper.set_chip_dims(chip_size, chip_size);
+        cropper.set_min_object_size(target_size, min_target_size);
+
+        // There is one mini-batch of random crops for each image in an epoch.
+        const unsigned long steps_per_epoch = (images.size()+options.mini_batch_size-1)/options.mini_batch_size;
+        const unsigned long max_steps = options.max_num_epochs*steps_per_epoch;
+        std::vector<matrix<rgb_pixel>> mini_batch_samples;
+        std::vector<std::vector<mmod_rect>> mini_batch_labels;
+        while (trainer.get_learning_rate() >= options.min_learning_rate &&
+               trainer.get_train_one_step_calls() < max_steps)
+        {
+            cropper(options.mini_batch_size, images, boxes, mini_batch_samples, mini_batch_labels);
+            trainer.train_one_step(mini_batch_samples, mini_batch_labels);
+        }
+        trainer.get_net();
+        net.clean();
+        inference_net_is_stale = true;
+    }
+
+    std::vector<mmod_rect> detect (
+        const py::array& pyimage,
+        const int upsample_num_times
+    )
+    {
+        pyramid_down<2> pyr;
+        matrix<rgb_pixel> image = dnn_rgb_image(pyimage);
+        for (int i = 0; i < upsample_num_times; ++i)
+            pyramid_up(image, pyr);
+
+        auto dets = inference_net()(image);
+        for (auto& d : dets)
+            d.rect = pyr.rect_down(d.rect, upsample_num_times);
+        return dets;
+    }
+
+    std::vector<std::vector<mmod_rect>> detect_batch (
+        const py::list& pyimages,
+        const int upsample_num_times,
+        const unsigned long batch_size
+    )
+    {
+        pyramid_down<2> pyr;
+        auto images = dnn_rgb_images(pyimages, true);
+        for (auto& image : images)
+        {
+            for (int i = 0; i < upsample_num_times; ++i)
+                pyramid_up(image, pyr);
+        }
+
+        auto all_dets = inference_net()(images, batch_size);
+        for (auto& dets : all_dets)
+        {
+            for (auto& d : dets)
+                d.rect = pyr.rect_down(d.rect, upsample_num_times);
+        }
+        return all_dets;
+    }
+
+    string summary (
+        const long rows,
+        const long columns
+    )
+    {
+        std::ostringstream sout;
+        sout << net_summary(inference_net(), rows, columns);
+        return sout.str();
+    }
+
+    void save (
+        const std::string& filename
+    ) const
+    {
+        serialize(filename) << *this;
+    }
+
+    friend void serialize(const mmod_detector& item, std::ostream& out)
+    {
+        serialize("mmod_detector", out);
+        serialize(item.net, out);
+    }
+
+    friend void deserialize(mmod_detector& item, std::istream& in)
+    {
+        std::string version;
+        deserialize(version, in);
+        if (version != "mmod_detector")
+            throw serialization_error("Unexpected version found while deserializing dlib.mmod_detector.");
+        deserialize(item.net, in);
+        item.inference_net_is_stale = true;
+    }
+
+private:
+
+    template <long num_filters, typename SUBNET> using con5d = con<num_filters,5,5,2,2,SUBNET>;
+    template <long num_filters, typename SUBNET> using con5  = con<num_filters,5,5,1,1,SUBNET>;
+
+    template <template <typename> class BN, typename SUBNET>
+    using downsampler = relu<BN<con5d<32, relu<BN<con5d<32, relu<BN<con5d<16,SUBNET>>>>>>>>>;
+    template <template <typename> class BN, typename SUBNET>
+    using rcon5 = relu<BN<con5<45,SUBNET>>>;
+
+    template <template <typename> class BN>
+    using mmod_net = loss_mmod<con<1,9,9,1,1,rcon5<BN,rcon5<BN,rcon5<BN,downsampler<BN,input_rgb_image_pyramid<pyramid_down<6>>>>>>>>;
+
+    using net_type = mmod_net<bn_con>;
+    using anet_type = mmod_net<affine>;
+
+    anet_type& inference_net (
+    )
+    {
+        if (inference_net_is_stale)
+        {
+            anet = anet_type(net);
+            inference_net_is_stale = false;
+        }
+        return anet;
+    }
+
+    net_type net;
+    anet_type anet;
+    bool inference_net_is_stale = true;
+};
+
+// ----------------------------------------------------------------------------------------
+//                                  semantic_segmenter
+// ----------------------------------------------------------------------------------------
+
+class semantic_segmenter
+{
+public:
+
+    semantic_segmenter (
+    ) : semantic_segmenter(2) {}
+
+    explicit semantic_segmenter (
+        unsigned long num_classes
+    )
+    {
+        if (num_classes < 2)
+            throw dlib::error("A semantic_segmenter must have at least 2 classes.");
+        net.subnet().layer_details().set_num_filters(num_classes);
+    }
+
+    unsigned long num_classes (
+    ) const { return net.subnet().layer_details().num_filters(); }
+
+    void train (
+        const py::list& pyimages,
+        const py::list& pylabels,
+        const dnn_training_options& options
+    )
+    {
+        if (len(pyimages) != len(pylabels))
+            throw dlib::error("The length of the labels list must match the length of the images list.");
+        if (len(pyimages) == 0)
+            throw dlib::error("You must give at least one training image.");
+
+        const auto images = dnn_rgb_images(pyimages, true);
+        std::vector<matrix<uint16_t>> labels;
+        for (auto& l : pylabels)
+        {
+            labels.emplace_back();
+            assign_image(labels.back(), numpy_image<uint16_t>(l.cast<py::array>()));
+            const auto& img = images[labels.size()-1];
+            if (labels.back().nr() != img.nr() || labels.back().nc() != img.nc())
+                throw dlib::error("Each label image must have the same size as its image.");
+            for (auto v : labels.back())
+            {
+                if (v >= num_classes() && v != loss_multiclass_log_per_pixel_::label_to_ignore)
+                    throw dlib::error("All the labels must be less than num_classes, or 65535 for pixels that should be ignored.");
+            }
+        }
+        check_image_size(images[0]);
+
+        dnn_trainer<net_type> trainer(net, sgd(options.weight_decay, options.momentum));
+        configure_dnn_trainer(trainer, options);
+        trainer.train(images, labels);
+        net.clean();
+        inference_net_is_stale = true;
+    }
+
+    numpy_image<uint16_t> segment (
+        const py::array& pyimage
+    )
+    {
+        const matrix<rgb_pixel> image = dnn_rgb_image(pyimage);
+        check_image_size(image);
+        numpy_image<uint16_t> result;
+        assign_image(result, inference_net()(image));
+        return result;
+    }
+
+    string summary (
+        const long rows,
+        const long columns
+    )
+    {
+        std::ostringstream sout;
+        sout << net_summary(inference_net(), rows, columns);
+        return sout.str();
+    }
+
+    void save (
+        const std::string& filename
+    ) const
+    {
+        serialize(filename) << *this;
+    }
+
+    friend void serialize(const semantic_segmenter& item, std::ostream& out)
+    {
+        serialize("semantic_segmenter", out);
+        serialize(item.net, out);
+    }
+
+    friend void deserialize(semantic_segmenter& item, std::istream& in)
+    {
+        std::string version;
+        deserialize(version, in);
+        if (version != "semantic_segmenter")
+            throw serialization_error("Unexpected version found while deserializing dlib.semantic_segmenter.");
+        deserialize(item.net, in);
+        item.inference_net_is_stale = true;
+    }
+
+private:
+
+    static void check_image_size (
+        const matrix<rgb_pixel>& image
+    )
+    {
+        // The network downsamples the image twice and then upsamples it back, so the
+        // output only lines up with the input when the sizes are multiples of 4.
+        if (image.nr()%4 != 0 || image.nc()%4 != 0)
+            throw dlib::error("The rows and columns of images given to a semantic_segmenter must be multiples of 4.");
+    }
+
+    template <int N, template <typename> class BN, typename SUBNET>
+    using rcon = relu<BN<con<N,3,3,1,1,SUBNET>>>;
+    template <int N, template <typename> class BN, typename SUBNET>
+    using rcont_up = relu<BN<cont<N,2,2,2,2,SUBNET>>>;
+
+    // A small U-Net.  The skip connections add the encoder features to the upsampled
+    // decoder features at each resolution.
+    template <template <typename> class BN>
+    using segmentation_net = loss_multiclass_log_per_pixel<con<2,1,1,1,1,
+                             rcon<16,BN,add_prev1<rcont_up<16,BN,
+                             rcon<32,BN,add_prev2<rcont_up<32,BN,
+                             rcon<64,BN,max_pool<2,2,2,2,
+                             tag2<rcon<32,BN,max_pool<2,2,2,2,
+                             tag1<rcon<16,BN,
+                             input_rgb_image
+                             >>>>>>>>>>>>>>>;
+
+    using net_type = segmentation_net<bn_con>;
+    using anet_type = segmentation_net<affine>;
+
+    anet_type& inference_net (
+    )
+    {
+        if (inference_net_is_stale)
+        {
+            anet = anet_type(net);
+            inference_net_is_stale = false;
+        }
+        return anet;
+    }
+
+    net_type net;
+    anet_type anet;
+    bool inference_net_is_stale = true;
+};
+
+// ----------------------------------------------------------------------------------------
+
+void bind_dnn(py::module& m)
+{
+    {
+    typedef resizable_tensor type;
+    py::class_<type>(m, "tensor", py::buffer_protocol(),
+"This object is a 4D array of floats with dimensions num_samples x k x nr x nc. \n\
+It's the type of object that goes in and out of dlib's deep neural networks.  It \n\
+supports the python buffer protocol, so numpy.asarray(t) returns a numpy array \n\
+with shape (num_samples, k, nr, nc) that views the tensor's memory without \n\
+copying it.")
+        .def(py::init())
+        .def(py::init([](long num_samples, long k, long nr, long nc) {
+                if (num_samples < 0 || k < 0 || nr < 0 || nc < 0)
+                    throw dlib::error("The dimensions of a tensor can't be negative.");
+                resizable_tensor t(num_samples, k, nr, nc);
+                t = 0;
+                return t;
+            }), py::arg("num_samples"), py::arg("k")=1, py::arg("nr")=1, py::arg("nc")=1,
+            "Makes a tensor with the given dimensions and all elements set to 0.")
+        .def(py::init(&tensor_from_numpy), py::arg("array"),
+"Makes a tensor holding a copy of the given array, which must have between 1 and 4 \n\
+dimensions.  The dimensions of the array become num_samples, k, nr, and nc in that \n\
+order.  Any missing trailing dimensions are set to 1.")
+        .def_buffer([](type& t) -> py::buffer_info {
+                return py::buffer_info(
+                    t.host(),
+                    sizeof(float),
+                    py::format_descriptor<float>::format(),
+                    4,
+                    std::vector<py::ssize_t>{ t.num_samples(), t.k(), t.nr(), t.nc() },
+                    std::vector<py::ssize_t>{
+                        static_cast<py::ssize_t>(sizeof(float)*t.k()*t.nr()*t.nc()),
+                        static_cast<py::ssize_t>(sizeof(float)*t.nr()*t.nc()),
+                        static_cast<py::ssize_t>(sizeof(float)*t.nc()),
+                        static_cast<py::ssize_t>(sizeof(float)) }
+                );
+            })
+        .def_property_readonly("num_samples", &type::num_samples)
+        .def_property_readonly("k", &type::k)
+        .def_property_readonly("nr", &type::nr)
+        .def_property_readonly("nc", &type::nc)
+        .def_property_readonly("shape", &tensor_shape, "The tuple (num_samples, k, nr, nc).")
+        .def("size", &type::size, "Returns num_samples*k*nr*nc.")
+        .def("__len__", &type::num_samples)
+        .def("__repr__", &print_tensor)
+        .def(py::pickle(&getstate<type>, &setstate<type>));
+    }
+    {
+    typedef dynamic_net type;
+    py::class_<type, std::shared_ptr<type>>(m, "dynamic_net",
+"This object runs a dlib deep neural network whose architecture is loaded at runtime. \n\
+Since C++ dlib networks are defined by their type, a network saved with serialize() \n\
+can only be read by a program that contains that network type.  So instead, save \n\
+the network with net_to_xml(net, \"net.xml\") in C++ and load the XML file into a \n\
+dynamic_net.  The layers between the input and loss layers are loaded, along with \n\
+their parameters, and can then be run on tensors with forward().  See \n\
+dlib/dnn/dynamic_graph_abstract.h for the list of supported layers.  Note that the \n\
+XML doesn't contain the running statistics of batch normalization layers, so \n\
+convert them to affine layers before saving the XML.")
+        .def(py::init())
+        .def(py::init<std::string>(), py::arg("xml_filename"),
+            "Loads the network described by an XML file written by dlib's net_to_xml().")
+        .def("forward", &type::forward, py::arg("x"),
+"requires \n\
+    - x is a dlib.tensor or a numpy array that can be converted into one. \n\
+ensures \n\
+    - Runs the network on x and returns the output as a dlib.tensor. \n\
+    - The first call to forward() sets up the layers for inputs with the \n\
+      dimensions of x, so later inputs must have the same number of channels.  \n\
+      The batch size and, for convolutional networks, the image size may change.")
+        .def("__call__", &type::forward, py::arg("x"), "Same as forward().")
+        .def("num_layers", &type::num_layers,
+            "Returns the number of layers in the network.  Tag and skip layers are not counted.")
+        .def("layer_type", &type::layer_type, py::arg("idx"),
+            "Returns the XML name, e.g. 'con' or 'relu', of the idx-th layer, counting from the input.")
+        .def("layer_inputs", &type::layer_inputs, py::arg("idx"),
+"Returns the indices of the layers whose outputs are the inputs of the idx-th \n\
+layer.  The input to the network is reported as num_layers().")
+        .def("num_parameters", &type::num_parameters,
+            "Returns the number of parameters in the network.  This is 0 until forward() has been called.")
+        .def("to_xml", &type::to_xml, "Returns the network in the XML format written by net_to_xml().")
+        .def("save", &type::save, py::arg("filename"), "Saves the network, including its parameters, to a file.")
+        .def_static("load", &load_object_from_file<type>, py::arg("filename"),
+            "Returns the dynamic_net saved in the given file by dynamic_net.save().")
+        .def(py::pickle(&getstate<type>, &setstate<type>));
+    }
+    {
+    typedef dnn_training_options type;
+    py::class_<type>(m, "dnn_training_options",
+        "This object is a container for the options used to train the networks in this module.")
+        .def(py::init())
+        .def_readwrite("learning_rate", &type::learning_rate,
+            "The initial learning rate of the SGD solver.")
+        .def_readwrite("min_learning_rate", &type::min_learning_rate,
+            "Training stops once the learning rate drops below this value.")
+        .def_readwrite("learning_rate_shrink_factor", &type::learning_rate_shrink_factor,
+            "The learning rate is multiplied by this value whenever the loss stops improving.  Must be in the range (0, 1].")
+        .def_readwrite("mini_batch_size", &type::mini_batch_size,
+            "The number of samples in each mini-batch.")
+        .def_readwrite("iterations_without_progress_threshold", &type::iterations_without_progress_threshold,
+            "The learning rate is shrunk if the loss doesn't improve for about this many mini-batches.")
+        .def_readwrite("max_num_epochs", &type::max_num_epochs,
+            "Training stops after this many passes over the training data.")
+        .def_readwrite("weight_decay", &type::weight_decay,
+            "The weight decay of the SGD solver.")
+        .def_readwrite("momentum", &type::momentum,
+            "The momentum of the SGD solver.")
+        .def_readwrite("synchronization_file", &type::synchronization_file,
+"If not empty, the training state is saved to this file every 5 minutes and \n\
+training resumes from it if it already exists.")
+        .def_readwrite("be_verbose", &type::be_verbose,
+            "If true, training prints its progress to stdout.")
+        .def("__str__", &print_dnn_training_options)
+        .def("__repr__", &print_dnn_training_options)
+        .def(py::pickle(&getstate<type>, &setstate<type>));
+    }
+    {
+    typedef resnet_classifier type;
+    py::class_<type, std::shared_ptr<type>>(m, "resnet_classifier",
+"This object is a small ResNet that classifies RGB images into one of num_classes \n\
+classes.  It can be trained from scratch with train().")
+        .def(py::init<unsigned long>(), py::arg("num_classes")=2)
+        .def(py::init(&load_object_from_file<type>), py::arg("filename"),
+            "Loads a resnet_classifier from a file written by resnet_classifier.save().")
+        .def("num_classes", &type::num_classes)
+        .def("train", &type::train, py::arg("images"), py::arg("labels"), py::arg("options")=dnn_training_options(),
+"requires \n\
+    - len(images) == len(labels) > 0 \n\
+    - images is a list of 8bit grayscale or RGB numpy images, all the same size. \n\
+    - labels is a list of integers, each less than num_classes(). \n\
+ensures \n\
+    - Trains the network to predict labels[i] when given images[i].  Training \n\
+      continues from the current parameters, so train() can be called again with \n\
+      more data.")
+        .def("__call__", &type::predict, py::arg("image"),
+            "Returns the predicted label of the given 8bit grayscale or RGB numpy image.")
+        .def("__call__", &type::predict_batch, py::arg("images"), py::arg("batch_size")=32,
+            "Returns the predicted labels of a list of images that are all the same size.")
+        .def("net_summary", &type::summary, py::arg("rows"), py::arg("columns"),
+            "Returns a table describing each layer of the network when it runs on an image of the given size.")
+        .def("save", &type::save, py::arg("filename"))
+        .def(py::pickle(&getstate<type>, &setstate<type>));
+    }
+    {
+    typedef mmod_detector type;
+    py::class_<type, std::shared_ptr<type>>(m, "mmod_detector",
+"This object is a CNN based object detector trained with the max-margin object \n\
+detection loss.  It uses the same network architecture as \n\
+cnn_face_detection_model_v1, but can be trained on your own objects with train().")
+        .def(py::init())
+        .def(py::init(&load_object_from_file<type>), py::arg("filename"),
+            "Loads an mmod_detector from a file written by mmod_detector.save().")
+        .def("train", &type::train, py::arg("images"), py::arg("boxes"), py::arg("options")=dnn_training_options(),
+            py::arg("target_size")=40, py::arg("min_target_size")=40, py::arg("chip_size")=200,
+"requires \n\
+    - len(images) == len(boxes) > 0 \n\
+    - images is a list of 8bit grayscale or RGB numpy images. \n\
+    - boxes[i] is a list of the dlib.rectangles or dlib.mmod_rectangles that \n\
+      locate the objects in images[i]. \n\
+    - 0 < min_target_size <= target_size <= chip_size \n\
+ensures \n\
+    - Trains a new detector from scratch.  The detector's sliding windows are \n\
+      picked so that the objects have a size of about target_size pixels and no \n\
+      dimension smaller than min_target_size.  Each mini-batch is made of \n\
+      random chip_size by chip_size crops of the images.")
+        .def("__call__", &type::detect, py::arg("image"), py::arg("upsample_num_times")=0,
+            "Returns the objects found in an 8bit grayscale or RGB numpy image.  Upsampling the image finds smaller objects.")
+        .def("__call__", &type::detect_batch, py::arg("images"), py::arg("upsample_num_times")=0, py::arg("batch_size")=32,
+            "Returns the objects found in each of a list of images that are all the same size.")
+        .def("net_summary", &type::summary, py::arg("rows"), py::arg("columns"),
+            "Returns a table describing each layer of the network when it runs on an image of the given size.")
+        .def("save", &type::save, py::arg("filename"))
+        .def(py::pickle(&getstate<type>, &setstate<type>));
+    }
+    {
+    typedef semantic_segmenter type;
+    py::class_<type, std::shared_ptr<type>>(m, "semantic_segmenter",
+"This object is a small U-Net that labels each pixel of an RGB image with one of \n\
+num_classes classes.  It can be trained from scratch with train().")
+        .def(py::init<unsigned long>(), py::arg("num_classes")=2)
+        .def(py::init(&load_object_from_file<type>), py::arg("filename"),
+            "Loads a semantic_segmenter from a file written by semantic_segmenter.save().")
+        .def("num_classes", &type::num_classes)
+        .def("train", &type::train, py::arg("images"), py::arg("labels"), py::arg("options")=dnn_training_options(),
+"requires \n\
+    - len(images) == len(labels) > 0 \n\
+    - images is a list of 8bit grayscale or RGB numpy images, all the same size, \n\
+      with rows and columns that are multiples of 4. \n\
+    - labels[i] is a uint16 numpy array with the same size as images[i].  Each \n\
+      element is the class of the pixel, which is less than num_classes(), or \n\
+      65535 if the pixel should be ignored. \n\
+ensures \n\
+    - Trains the network to output labels[i] when given images[i].  Training \n\
+      continues from the current parameters.")
+        .def("__call__", &type::segment, py::arg("image"),
+            "Returns a uint16 numpy array holding the predicted class of each pixel of the image.")
+        .def("net_summary", &type::summary, py::arg("rows"), py::arg("columns"),
+            "Returns a table describing each layer of the network when it runs on an image of the given size.")
+        .def("save", &type::save, py::arg("filename"))
+        .def(py::pickle(&getstate<type>, &setstate<type>));
+    }
+}
+
@@ -182,6 +182,14 @@ public:
         return face_descriptors;        
     }
 
+    std::string summary (
+    )
+    {
+        std::ostringstream sout;
+        sout << net_summary(net, 150, 150);
+        return sout.str();
+    }
+
 private:
 
     dlib::rand rnd;
@@ -411,6 +419,9 @@ void bind_face_recognition(py::module &m)
             "Note that the alignment should be done in the same way dlib.get_face_chip does it."
             "Every face will be converted into 128D face descriptors.  "
             "If num_jitters>1 then each face will be randomly jittered slightly num_jitters times, each run through the 128D projection, and the average used as the face descriptor. "            
+            )
+        .def("net_summary", &face_recognition_model_v1::summary,
+            "Returns a table with the output shape, number of parameters, multiply-adds and activation memory of each layer of the network when it runs on a 150x150 face chip."
             );
     }
 
@@ -0,0 +1,176 @@
+import pickle
+
+import dlib
+import pytest
+
+import utils
+
+try:
+    import numpy as np
+except ImportError:
+    pass
+
+# A network with a 2 input, 1 output fc layer followed by a relu, written the way
+# net_to_xml() writes it.  The fc parameters are the two weights followed by the bias.
+fc_relu_xml = """<net>
+<layer idx='0' type='loss'><loss_mean_squared/></layer>
+<layer idx='1' type='comp'><relu/></layer>
+<layer idx='2' type='comp'><fc num_outputs='1' learning_rate_mult='1' weight_decay_mult='1' bias_learning_rate_mult='1' bias_weight_decay_mult='0' use_bias='true'>
+1
+2
+0.5
+</fc></layer>
+<layer idx='3' type='input'><input/></layer>
+</net>
+"""
+
+
+def write_fc_relu_xml(tmpdir):
+    filename = str(tmpdir.join("net.xml"))
+    with open(filename, "w") as f:
+        f.write(fc_relu_xml)
+    return filename
+
+
+def test_dnn_training_options():
+    opts = dlib.dnn_training_options()
+    assert opts.learning_rate == 0.1
+    assert opts.mini_batch_size == 32
+    assert not opts.be_verbose
+    opts.max_num_epochs = 7
+    opts.synchronization_file = "sync.dat"
+
+    opts2 = pickle.loads(pickle.dumps(opts, 2))
+    assert opts2.max_num_epochs == 7
+    assert opts2.synchronization_file == "sync.dat"
+    assert "max_num_epochs=7" in str(opts2)
+
+
+def test_empty_tensor():
+    t = dlib.tensor(2, 3, 4, 5)
+    assert t.shape == (2, 3, 4, 5)
+    assert t.size() == 2*3*4*5
+    assert len(t) == 2
+    assert repr(t) == "<dlib.tensor num_samples=2, k=3, nr=4, nc=5>"
+
+    view = memoryview(t)
+    assert view.shape == (2, 3, 4, 5)
+    assert view.format == "f"
+    assert view[1, 2, 3, 4] == 0
+
+    t2 = pickle.loads(pickle.dumps(t, 2))
+    assert t2.shape == t.shape
+
+
+@pytest.mark.skipif(not utils.is_numpy_installed(), reason="requires numpy")
+def test_tensor_from_numpy():
+    a = np.arange(24, dtype=np.float32).reshape(2, 3, 4)
+    t = dlib.tensor(a)
+    assert t.shape == (2, 3, 4, 1)
+    assert np.array_equal(np.asarray(t).reshape(2, 3, 4), a)
+
+    # The buffer is a view of the tensor's memory.
+    np.asarray(t)[0, 0, 0, 0] = 100
+    assert np.asarray(t)[0, 0, 0, 0] == 100
+
+    with pytest.raises(Exception):
+        dlib.tensor(np.zeros((1, 1, 1, 1, 1)))
+
+
+@pytest.mark.skipif(not utils.is_numpy_installed(), reason="requires numpy")
+def test_dynamic_net(tmpdir):
+    net = dlib.dynamic_net(write_fc_relu_xml(tmpdir))
+    assert net.num_layers() == 2
+    assert net.layer_type(0) == "fc"
+    assert net.layer_type(1) == "relu"
+    assert net.layer_inputs(0) == [2]
+    assert net.layer_inputs(1) == [0]
+
+    x = np.array([[1, 1], [-3, 1]], dtype=np.float32)
+    out = np.asarray(net(x))
+    assert out.shape == (2, 1, 1, 1)
+    assert np.allclose(out.flatten(), [3.5, 0])
+    assert net.num_parameters() == 3
+
+    filename = str(tmpdir.join("net.dat"))
+    net.save(filename)
+    for net2 in [dlib.dynamic_net.load(filename), pickle.loads(pickle.dumps(net, 2))]:
+        assert np.allclose(np.asarray(net2.forward(dlib.tensor(x))), out)
+
+    # The XML of a dynamic_net can be loaded into another one.
+    with open(filename, "w") as f:
+        f.write(net.to_xml())
+    net3 = dlib.dynamic_net(filename)
+    assert np.allclose(np.asarray(net3(x)), out)
+
+
+def quick_training_options():
+    opts = dlib.dnn_training_options()
+    opts.learning_rate = 0.01
+    opts.mini_batch_size = 4
+    opts.max_num_epochs = 20
+    return opts
+
+
+@pytest.mark.skipif(not utils.is_numpy_installed(), reason="requires numpy")
+def test_resnet_classifier(tmpdir):
+    # Dark images are class 0 and bright images are class 1.
+    images = [np.full((32, 32, 3), 10*i, dtype=np.uint8) for i in range(4)]
+    images += [np.full((32, 32, 3), 255 - 10*i, dtype=np.uint8) for i in range(4)]
+    labels = [0]*4 + [1]*4
+
+    net = dlib.resnet_classifier(2)
+    assert net.num_classes() == 2
+    net.train(images, labels, quick_training_options())
+    predictions = net(images)
+    assert len(predictions) == len(images)
+    assert all(0 <= p < 2 for p in predictions)
+    assert net(images[0]) == predictions[0]
+    assert "fc" in net.net_summary(32, 32)
+
+    filename = str(tmpdir.join("resnet.dat"))
+    net.save(filename)
+    assert dlib.resnet_classifier(filename)(images) == predictions
+    assert pickle.loads(pickle.dumps(net, 2))(images) == predictions
+
+    with pytest.raises(Exception):
+        net.train(images, [2]*len(images))
+
+
+@pytest.mark.skipif(not utils.is_numpy_installed(), reason="requires numpy")
+def test_semantic_segmenter():
+    images = [np.zeros((16, 16, 3), dtype=np.uint8) for i in range(4)]
+    labels = [np.zeros((16, 16), dtype=np.uint16) for i in range(4)]
+    for img, label in zip(images, labels):
+        img[:, 8:] = 255
+        label[:, 8:] = 1
+        label[0, 0] = 65535
+
+    net = dlib.semantic_segmenter(2)
+    net.train(images, labels, quick_training_options())
+    seg = net(images[0])
+    assert seg.shape == (16, 16)
+    assert seg.dtype == np.uint16
+    assert seg.max() < 2
+
+    with pytest.raises(Exception):
+        net(np.zeros((15, 16, 3), dtype=np.uint8))
+
+
+@pytest.mark.skipif(not utils.is_numpy_installed(), reason="requires numpy")
+def test_mmod_detector():
+    images = []
+    boxes = []
+    for i in range(4):
+        img = np.zeros((100, 100, 3), dtype=np.uint8)
+        img[30:70, 20+i:60+i] = 255
+        images.append(img)
+        boxes.append([dlib.rectangle(20+i, 30, 59+i, 69)])
+
+    opts = quick_training_options()
+    opts.max_num_epochs = 2
+    det = dlib.mmod_detector()
+    det.train(images, boxes, opts, target_size=40, min_target_size=40, chip_size=80)
+    dets = det(images[0])
+    assert isinstance(dets, dlib.mmod_rectangles)
+    assert len(det(images, batch_size=2)) == len(images)